@@ -79,13 +79,11 @@ class MZMLReaderRust:
         if self._file_info is None:
             if self._use_rust:
                 rust_info = rust_impl.MZMLUtils.get_file_info(self._file_path)
-                self._file_info = {
-                    'file_path': rust_info['file_path'],
-                    'file_size': rust_info['file_size'],
-                    'modified': rust_info['modified'],
-                    'valid': rust_impl.MZMLUtils.is_valid_mzml(self._file_path),
-                    'using_rust': True
-                }
+                self._file_info = dict(
+                    rust_info,
+                    valid=rust_impl.MZMLUtils.is_valid_mzml(self._file_path),
+                    using_rust=True
+                )
             else:
                 self._file_info = {
                     'file_path': self._file_path,
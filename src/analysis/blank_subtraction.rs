@@ -0,0 +1,105 @@
+//! 空白扣除
+//!
+//! 将样品运行与空白运行比较，去除在空白中相近保留时间、相同m/z处也有足够强度的MS1峰
+//! （溶剂、色谱柱流失等污染物）。空白的MS1谱图按保留时间排序以定位时间窗口，
+//! 每张空白谱图各建一个[`BinnedSpectraIndex`]，利用bin的最大强度跳过不可能命中的区域。
+
+use crate::core::spectrum::{BinnedSpectraIndex, SharedSpectra, Spectrum};
+use crate::core::types::*;
+use std::sync::Arc;
+
+/// 从样品MS1谱图中去除空白中也存在的峰，返回每张样品谱图被去除的峰数
+///
+/// 对样品中m/z为`mz`、强度为`I`的峰，若保留时间差在`rt_tolerance`内的某张空白MS1谱图中
+/// 有m/z在`ppm`容差内、强度不低于`I * intensity_ratio`的峰，则认为该峰来自空白并去除。
+/// 非MS1谱图保持不变，计数为0。
+pub fn subtract_run(
+    sample: &mut [Spectrum],
+    blank: &[Spectrum],
+    ppm: f64,
+    rt_tolerance: f64,
+    intensity_ratio: f64,
+) -> CoreResult<Vec<usize>> {
+    let blank: SharedSpectra = Arc::new(blank.iter().filter(|spectrum| spectrum.is_ms1()).cloned().collect());
+
+    // (保留时间, 空白谱图下标)，按保留时间升序
+    let mut rt_index: Vec<(RetentionTime, usize)> =
+        blank.iter().enumerate().map(|(index, spectrum)| (spectrum.scan.retention_time, index)).collect();
+    rt_index.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let blank_indices = (0..blank.len())
+        .map(|index| BinnedSpectraIndex::from_shared(blank.clone(), vec![index], constants::DEFAULT_BIN_SIZE))
+        .collect::<CoreResult<Vec<_>>>()?;
+
+    let mut removed = Vec::with_capacity(sample.len());
+    for spectrum in sample.iter_mut() {
+        if !spectrum.is_ms1() {
+            removed.push(0);
+            continue;
+        }
+
+        let rt = spectrum.scan.retention_time;
+        let start = rt_index.partition_point(|entry| entry.0 < rt - rt_tolerance);
+        let end = rt_index.partition_point(|entry| entry.0 <= rt + rt_tolerance);
+        let window: Vec<&BinnedSpectraIndex> = rt_index[start..end.max(start)]
+            .iter()
+            .map(|&(_, index)| &blank_indices[index])
+            .collect();
+        if window.is_empty() {
+            removed.push(0);
+            continue;
+        }
+
+        let before = spectrum.peak_count();
+        spectrum.retain_peaks(|(mz, intensity)| {
+            let tolerance = mz * ppm * 1e-6;
+            !window.iter().any(|index| {
+                index
+                    .search_range_min_intensity((mz - tolerance, mz + tolerance), intensity * intensity_ratio)
+                    .is_ok_and(|peaks| !peaks.is_empty())
+            })
+        });
+        removed.push(before - spectrum.peak_count());
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms1(rt: f64, peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::ms1().unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum.set_retention_time(rt).unwrap();
+        spectrum
+    }
+
+    #[test]
+    fn test_contaminant_removed_sample_peak_kept() {
+        // 445.1200为聚硅氧烷污染物，两次运行中都存在
+        let mut sample = vec![
+            ms1(60.0, &[(445.1201, 1e5), (500.25, 2e4)]),
+            ms1(65.0, &[(445.1199, 8e4), (622.03, 1e3)]),
+            // 远离空白保留时间，污染物峰保留
+            ms1(300.0, &[(445.1200, 1e5)]),
+        ];
+        let mut ms2 = Spectrum::ms2().unwrap();
+        ms2.add_peak(445.12, 1e5).unwrap();
+        sample.push(ms2);
+
+        let blank = vec![
+            ms1(62.0, &[(445.1200, 9e4), (622.03, 10.0)]),
+            ms1(200.0, &[(500.25, 1e6)]),
+        ];
+
+        let removed = subtract_run(&mut sample, &blank, 10.0, 30.0, 0.5).unwrap();
+        assert_eq!(removed, vec![1, 1, 0, 0]);
+        assert_eq!(sample[0].peaks(), vec![(500.25, 2e4)]);
+        // 空白中622.03的强度不到样品的一半，不去除
+        assert_eq!(sample[1].peaks(), vec![(622.03, 1e3)]);
+        assert_eq!(sample[2].peak_count(), 1);
+        assert_eq!(sample[3].peak_count(), 1);
+    }
+}
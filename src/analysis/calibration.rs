@@ -0,0 +1,325 @@
+//! 质量重校准
+//!
+//! 由(观测m/z, 理论m/z)校准点（锁定质量或可信鉴定的肽段）拟合ppm偏差随m/z变化的模型，
+//! 再把模型用于谱图：
+//! - 模型为观测m/z的多项式：常数偏差、线性或二次，最小二乘拟合前把m/z线性缩放到[-1, 1]
+//! - 一次项及以上的模型只在校准点的m/z范围内求值，范围之外取端点处的偏差，避免多项式外推发散
+//! - 校正后的m/z为`观测m/z / (1 + 偏差 × 1e-6)`，谱图中的每个峰和前体离子m/z都按各自的m/z校正；
+//!   分离窗口是仪器设置，不做校正
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::utils::helpers::{polynomial_fit, polynomial_value};
+use crate::utils::mass::ppm_diff;
+
+#[cfg(feature = "python")]
+use crate::core::ms_object::MSObject;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// ppm偏差模型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrationModel {
+    /// 与m/z无关的常数偏差
+    Constant,
+    /// 随m/z线性变化
+    Linear,
+    /// m/z的二次函数
+    Quadratic,
+}
+
+impl CalibrationModel {
+    /// 按名称解析："constant"、"linear"或"quadratic"
+    pub fn from_name(name: &str) -> CoreResult<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "constant" | "offset" => Ok(Self::Constant),
+            "linear" => Ok(Self::Linear),
+            "quadratic" => Ok(Self::Quadratic),
+            _ => Err(CoreError::InvalidFormat(format!("Unknown calibration model: {}", name))),
+        }
+    }
+
+    /// 模型名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Constant => "constant",
+            Self::Linear => "linear",
+            Self::Quadratic => "quadratic",
+        }
+    }
+
+    /// 多项式次数
+    pub fn degree(&self) -> usize {
+        match self {
+            Self::Constant => 0,
+            Self::Linear => 1,
+            Self::Quadratic => 2,
+        }
+    }
+}
+
+/// 拟合得到的质量校准
+#[derive(Debug, Clone, PartialEq)]
+pub struct MassCalibration {
+    /// 偏差模型
+    pub model: CalibrationModel,
+    /// 缩放后m/z的多项式系数（常数项在前），值为ppm偏差
+    pub coefficients: Vec<f64>,
+    /// 校准点的观测m/z范围
+    pub mz_range: (f64, f64),
+    /// 参与拟合的校准点数
+    pub calibrant_count: usize,
+    /// 校正前校准点ppm偏差的均方根
+    pub rms_ppm_before: f64,
+    /// 校正后校准点ppm偏差的均方根
+    pub rms_ppm_after: f64,
+}
+
+impl MassCalibration {
+    /// 由(观测m/z, 理论m/z)校准点拟合模型
+    ///
+    /// 忽略m/z不是正有限值的校准点；不同的观测m/z少于模型参数个数时返回错误。
+    pub fn fit(pairs: &[(f64, f64)], model: CalibrationModel) -> CoreResult<Self> {
+        let pairs: Vec<(f64, f64)> = pairs
+            .iter()
+            .copied()
+            .filter(|&(observed, theoretical)| {
+                observed.is_finite() && theoretical.is_finite() && observed > 0.0 && theoretical > 0.0
+            })
+            .collect();
+        let terms = model.degree() + 1;
+        let mut distinct: Vec<f64> = pairs.iter().map(|pair| pair.0).collect();
+        distinct.sort_by(|a, b| a.total_cmp(b));
+        distinct.dedup();
+        if distinct.len() < terms {
+            return Err(CoreError::InvalidFormat(format!(
+                "{} calibration needs calibrants at {} or more distinct m/z values, got {}",
+                model.name(),
+                terms,
+                distinct.len()
+            )));
+        }
+
+        let mz_range = (distinct[0], distinct[distinct.len() - 1]);
+        let mut calibration = Self {
+            model,
+            coefficients: vec![0.0; terms],
+            mz_range,
+            calibrant_count: pairs.len(),
+            rms_ppm_before: rms(pairs.iter().map(|&(observed, theoretical)| ppm_diff(observed, theoretical))),
+            rms_ppm_after: 0.0,
+        };
+
+        let scaled: Vec<f64> = pairs.iter().map(|pair| calibration.scaled(pair.0)).collect();
+        let errors: Vec<f64> = pairs.iter().map(|&(observed, theoretical)| ppm_diff(observed, theoretical)).collect();
+        calibration.coefficients = polynomial_fit(&scaled, &errors, model.degree())
+            .ok_or_else(|| CoreError::InvalidFormat("Calibration normal equations are singular".to_string()))?;
+        calibration.rms_ppm_after = rms(
+            pairs.iter().map(|&(observed, theoretical)| ppm_diff(calibration.correct_mz(observed), theoretical)),
+        );
+        Ok(calibration)
+    }
+
+    /// 模型在观测m/z处预测的ppm偏差
+    pub fn ppm_error_at(&self, mz: f64) -> f64 {
+        polynomial_value(&self.coefficients, self.scaled(mz))
+    }
+
+    /// 校正一个观测m/z
+    pub fn correct_mz(&self, mz: f64) -> f64 {
+        mz / (1.0 + self.ppm_error_at(mz) * 1e-6)
+    }
+
+    /// 校正谱图中所有峰和前体离子的m/z
+    pub fn apply_to_spectrum(&self, spectrum: &mut Spectrum) {
+        let peaks: Vec<Peak> = spectrum.peaks_iter().map(|(mz, intensity)| (self.correct_mz(mz), intensity)).collect();
+        spectrum.set_peaks(peaks);
+        if let Some(precursor) = spectrum.precursor.as_deref_mut() {
+            precursor.mz = self.correct_mz(precursor.mz);
+        }
+    }
+
+    /// 校正一组谱图
+    pub fn apply_to_spectra(&self, spectra: &mut [Spectrum]) {
+        for spectrum in spectra {
+            self.apply_to_spectrum(spectrum);
+        }
+    }
+
+    /// `mz`截断到校准点的m/z范围后线性缩放到[-1, 1]
+    fn scaled(&self, mz: f64) -> f64 {
+        let (low, high) = self.mz_range;
+        let half_width = (high - low) / 2.0;
+        if half_width > 0.0 {
+            (mz.clamp(low, high) - (low + high) / 2.0) / half_width
+        } else {
+            0.0
+        }
+    }
+}
+
+/// 均方根，空序列为0
+fn rms(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0usize), |(sum, count), value| (sum + value * value, count + 1));
+    if count == 0 {
+        0.0
+    } else {
+        (sum / count as f64).sqrt()
+    }
+}
+
+/// Python可用的质量校准
+#[cfg(feature = "python")]
+#[pyclass(name = "MassCalibration")]
+pub struct PyMassCalibration {
+    pub calibration: MassCalibration,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyMassCalibration {
+    /// 由(观测m/z, 理论m/z)列表拟合，`model`为"constant"、"linear"或"quadratic"
+    #[staticmethod]
+    #[pyo3(signature = (pairs, model="linear"))]
+    fn fit(pairs: Vec<(f64, f64)>, model: &str) -> PyResult<Self> {
+        CalibrationModel::from_name(model)
+            .and_then(|model| MassCalibration::fit(&pairs, model))
+            .map(|calibration| Self { calibration })
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 返回峰和前体离子m/z经过校正的MSObject副本
+    fn apply(&self, ms_objects: Vec<PyRef<'_, MSObject>>) -> Vec<MSObject> {
+        ms_objects
+            .iter()
+            .map(|ms_object| {
+                let mut spectrum = ms_object.spectrum.clone();
+                self.calibration.apply_to_spectrum(&mut spectrum);
+                MSObject { spectrum }
+            })
+            .collect()
+    }
+
+    /// 校正一个观测m/z
+    fn correct_mz(&self, mz: f64) -> f64 {
+        self.calibration.correct_mz(mz)
+    }
+
+    /// 模型在观测m/z处预测的ppm偏差
+    fn ppm_error_at(&self, mz: f64) -> f64 {
+        self.calibration.ppm_error_at(mz)
+    }
+
+    /// 模型名称
+    #[getter]
+    fn model(&self) -> &'static str {
+        self.calibration.model.name()
+    }
+
+    /// 缩放后m/z的多项式系数（常数项在前）
+    #[getter]
+    fn coefficients(&self) -> Vec<f64> {
+        self.calibration.coefficients.clone()
+    }
+
+    /// 参与拟合的校准点数
+    #[getter]
+    fn calibrant_count(&self) -> usize {
+        self.calibration.calibrant_count
+    }
+
+    /// 校正前校准点ppm偏差的均方根
+    #[getter]
+    fn rms_ppm_before(&self) -> f64 {
+        self.calibration.rms_ppm_before
+    }
+
+    /// 校正后校准点ppm偏差的均方根
+    #[getter]
+    fn rms_ppm_after(&self) -> f64 {
+        self.calibration.rms_ppm_after
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+    use crate::core::test_spectrum::pseudo_random;
+
+    /// 在理论m/z上加`error(mz)` ppm的系统偏差和±0.2 ppm的随机偏差
+    fn observe(theoretical: f64, error: impl Fn(f64) -> f64, seed: &mut u64) -> f64 {
+        theoretical * (1.0 + (error(theoretical) + 0.4 * (pseudo_random(seed) - 0.5)) * 1e-6)
+    }
+
+    fn calibrants(error: impl Fn(f64) -> f64, seed: &mut u64) -> Vec<(f64, f64)> {
+        (0..40)
+            .map(|i| {
+                let theoretical = 150.0 + 35.3 * i as f64;
+                (observe(theoretical, &error, seed), theoretical)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_removes_systematic_offset() {
+        let mut seed = 7;
+        let pairs = calibrants(|_| 8.0, &mut seed);
+        let calibration = MassCalibration::fit(&pairs, CalibrationModel::Constant).unwrap();
+        assert!((calibration.rms_ppm_before - 8.0).abs() < 0.1);
+        assert!(calibration.rms_ppm_after < 0.5);
+        assert!((calibration.ppm_error_at(1000.0) - 8.0).abs() < 0.1);
+
+        // 合成谱图：峰和前体离子都有+8 ppm的偏差
+        let theoretical_peaks = [175.119, 304.162, 433.204, 762.378, 1061.52, 1388.74];
+        let mut spectra: Vec<Spectrum> = (0..5)
+            .map(|_| {
+                let mut spectrum = Spectrum::ms2().unwrap();
+                spectrum.add_peaks(theoretical_peaks.iter().map(|&mz| (observe(mz, |_| 8.0, &mut seed), 100.0))).unwrap();
+                spectrum.set_precursor(PrecursorInfo { mz: observe(722.35, |_| 8.0, &mut seed), ..Default::default() });
+                spectrum
+            })
+            .collect();
+        calibration.apply_to_spectra(&mut spectra);
+        for spectrum in &spectra {
+            for (&observed, &theoretical) in spectrum.mz_slice().iter().zip(&theoretical_peaks) {
+                assert!(ppm_diff(observed, theoretical).abs() < 0.5, "{} vs {}", observed, theoretical);
+            }
+            assert!(ppm_diff(spectrum.precursor.as_ref().unwrap().mz, 722.35).abs() < 0.5);
+            assert_eq!(spectrum.intensity_slice(), &[100.0; 6]);
+        }
+    }
+
+    #[test]
+    fn test_mz_dependent_models() {
+        let error = |mz: f64| 8.0 - 6.0 * (mz - 150.0) / 1400.0 + 4.0 * ((mz - 850.0) / 700.0).powi(2);
+        let mut seed = 13;
+        let pairs = calibrants(error, &mut seed);
+        let constant = MassCalibration::fit(&pairs, CalibrationModel::Constant).unwrap();
+        let linear = MassCalibration::fit(&pairs, CalibrationModel::Linear).unwrap();
+        let quadratic = MassCalibration::fit(&pairs, CalibrationModel::Quadratic).unwrap();
+        assert!(constant.rms_ppm_after > linear.rms_ppm_after);
+        assert!(linear.rms_ppm_after > 0.5);
+        assert!(quadratic.rms_ppm_after < 0.5, "quadratic rms {}", quadratic.rms_ppm_after);
+        for mz in [200.0, 700.0, 1400.0] {
+            assert!((quadratic.ppm_error_at(mz) - error(mz)).abs() < 0.3);
+        }
+        // 校准范围之外取端点处的偏差
+        assert_eq!(quadratic.ppm_error_at(5000.0), quadratic.ppm_error_at(quadratic.mz_range.1));
+        assert_eq!(quadratic.calibrant_count, 40);
+    }
+
+    #[test]
+    fn test_too_few_calibrants() {
+        assert!(MassCalibration::fit(&[], CalibrationModel::Constant).is_err());
+        let pairs = [(500.004, 500.0), (500.004, 500.0), (f64::NAN, 600.0)];
+        assert!(MassCalibration::fit(&pairs, CalibrationModel::Linear).is_err());
+        let constant = MassCalibration::fit(&pairs, CalibrationModel::Constant).unwrap();
+        assert!((constant.correct_mz(500.004) - 500.0).abs() < 1e-9);
+        assert_eq!(constant.calibrant_count, 2);
+
+        assert_eq!(CalibrationModel::from_name("Quadratic").unwrap(), CalibrationModel::Quadratic);
+        assert_eq!(CalibrationModel::from_name("offset").unwrap().degree(), 0);
+        assert!(CalibrationModel::from_name("cubic").is_err());
+    }
+}
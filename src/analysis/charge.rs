@@ -0,0 +1,203 @@
+//! 由同位素间距推断前体离子电荷
+//!
+//! 部分仪器（如Waters）的mzML不记录电荷，MS2的前体离子电荷为0。这里在MS1中定位前体离子峰，
+//! 对每个候选电荷z沿两侧以1/z的间距连续寻找同位素峰，组成同位素峰簇后按
+//! 「找到的同位素峰数 × 与averagine峰型的相关系数」打分，取得分最高的电荷：
+//! - 间距更小的高电荷（如2+峰簇上的4+）在中间位置找不到峰，峰簇立即中断；
+//! - 间距更大的低电荷（如2+峰簇上的1+）只能隔峰匹配，找到的峰数更少；
+//! - 强度低于前体离子峰[`MIN_PARTNER_RATIO`]倍的峰视为噪声，不计入峰簇。
+
+use crate::core::isotopes::averagine_pattern;
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::utils::mass::{default_adduct, neutral_mass, ISOTOPE_SPACING};
+
+/// 默认尝试的最大电荷
+pub const DEFAULT_MAX_CHARGE: u8 = 6;
+/// 默认的同位素峰匹配容差 (ppm)
+pub const DEFAULT_CHARGE_TOLERANCE_PPM: f64 = 10.0;
+/// 同位素峰相对前体离子峰的最小强度比
+pub const MIN_PARTNER_RATIO: f64 = 0.1;
+/// 峰簇与averagine峰型的最小相关系数，低于该值的电荷不予考虑
+pub const MIN_PATTERN_CORRELATION: f64 = 0.5;
+/// 前体离子峰两侧各自最多寻找的同位素峰数
+const MAX_ISOTOPE_WALK: usize = 6;
+
+/// 推断前体离子电荷，找不到前体离子峰或任何电荷下都没有同位素峰时返回None
+///
+/// 前体离子峰取`precursor_mz`容差窗口内强度最高的峰；返回的电荷总为正，极性由调用方处理。
+pub fn infer_charge(ms1_spectrum: &Spectrum, precursor_mz: f64, tolerance: Tolerance, max_charge: u8) -> Option<i8> {
+    let precursor = find_peak(ms1_spectrum, precursor_mz, tolerance)?;
+    (1..=max_charge.min(Charge::MAX as u8) as Charge)
+        .filter_map(|charge| score_charge(ms1_spectrum, precursor, charge, tolerance).map(|score| (charge, score)))
+        // 得分相同时取较低电荷
+        .fold(None, |best: Option<(Charge, f64)>, candidate| match best {
+            Some(best) if best.1 >= candidate.1 => Some(best),
+            _ => Some(candidate),
+        })
+        .map(|(charge, _)| charge)
+}
+
+/// 为电荷为0的MS2谱图推断前体离子电荷，返回填写了电荷的谱图数
+///
+/// 母离子谱图为列表中位于MS2之前最近的一张MS1；推断出的电荷按MS2扫描的极性加上符号。
+pub fn infer_missing_charges(spectra: &mut [Spectrum], tolerance: Tolerance, max_charge: u8) -> usize {
+    let mut parent: Option<usize> = None;
+    let mut filled = 0;
+    for index in 0..spectra.len() {
+        if spectra[index].is_ms1() {
+            parent = Some(index);
+            continue;
+        }
+        let (Some(parent), Some(precursor)) = (parent, spectra[index].precursor.as_deref()) else {
+            continue;
+        };
+        if precursor.charge != 0 {
+            continue;
+        }
+        let Some(charge) = infer_charge(&spectra[parent], precursor.mz, tolerance, max_charge) else {
+            continue;
+        };
+        let charge = spectra[index].scan.polarity.signed_charge(charge);
+        if let Some(precursor) = spectra[index].precursor.as_deref_mut() {
+            precursor.charge = charge;
+            filled += 1;
+        }
+    }
+    filled
+}
+
+/// 容差窗口内强度最高的峰
+fn find_peak(spectrum: &Spectrum, mz: f64, tolerance: Tolerance) -> Option<Peak> {
+    let (mz_values, intensities) = spectrum.peaks_in_tolerance(mz, tolerance);
+    mz_values.into_iter().zip(intensities).filter(|peak| peak.1 > 0.0).max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// 电荷`charge`下以`precursor`为起点的同位素峰簇得分，没有同位素峰或峰型相关性过低时为None
+fn score_charge(spectrum: &Spectrum, precursor: Peak, charge: Charge, tolerance: Tolerance) -> Option<f64> {
+    let step = ISOTOPE_SPACING / charge as f64;
+    let min_intensity = precursor.1 * MIN_PARTNER_RATIO;
+    let walk = |direction: f64| {
+        let mut found = Vec::new();
+        let mut current = precursor.0;
+        while found.len() < MAX_ISOTOPE_WALK {
+            match find_peak(spectrum, current + direction * step, tolerance) {
+                Some(peak) if peak.1 >= min_intensity => {
+                    current = peak.0;
+                    found.push(peak);
+                }
+                _ => break,
+            }
+        }
+        found
+    };
+
+    // 按m/z升序组成峰簇，第一个峰视为单同位素峰
+    let mut envelope = walk(-1.0);
+    envelope.reverse();
+    envelope.push(precursor);
+    envelope.extend(walk(1.0));
+    if envelope.len() < 2 {
+        return None;
+    }
+
+    let mass = neutral_mass(envelope[0].0, charge, default_adduct(charge)).ok()?;
+    let expected = averagine_pattern(mass, charge, envelope.len()).ok()?;
+    let correlation = cosine(envelope.iter().map(|peak| peak.1), expected.iter().map(|peak| peak.1));
+    (correlation >= MIN_PATTERN_CORRELATION).then(|| (envelope.len() - 1) as f64 * correlation)
+}
+
+/// 两个强度向量的余弦相似度
+fn cosine(a: impl Iterator<Item = f64>, b: impl Iterator<Item = f64>) -> f64 {
+    let (dot, norm_a, norm_b) = a.zip(b).fold((0.0, 0.0, 0.0), |(dot, norm_a, norm_b), (x, y)| {
+        (dot + x * y, norm_a + x * x, norm_b + y * y)
+    });
+    if norm_a > 0.0 && norm_b > 0.0 {
+        dot / (norm_a.sqrt() * norm_b.sqrt())
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+    use crate::core::test_spectrum::pseudo_random;
+
+    /// m/z 400-1200之间每0.02 Da约一个强度为100-500的噪声峰，叠加若干averagine峰簇
+    fn noisy_ms1(envelopes: &[(f64, Charge)], seed: &mut u64) -> Spectrum {
+        let mut peaks: Vec<Peak> = (0..40_000)
+            .map(|i| (400.0 + 0.02 * i as f64 + 0.01 * pseudo_random(seed), 100.0 + 400.0 * pseudo_random(seed)))
+            .collect();
+        for &(mono_mz, charge) in envelopes {
+            let mass = neutral_mass(mono_mz, charge, default_adduct(charge)).unwrap();
+            for (mz, relative) in averagine_pattern(mass, charge, 6).unwrap() {
+                peaks.push((mz, 2e4 * relative));
+            }
+        }
+        let mut spectrum = Spectrum::ms1().unwrap();
+        spectrum.add_peaks(peaks).unwrap();
+        spectrum.sort_peaks();
+        spectrum
+    }
+
+    #[test]
+    fn test_infer_charge_in_noisy_scan() {
+        let mut seed = 11;
+        let spectrum = noisy_ms1(&[(650.32, 2), (801.41, 3)], &mut seed);
+        let tolerance = Tolerance::PPM(DEFAULT_CHARGE_TOLERANCE_PPM);
+
+        assert_eq!(infer_charge(&spectrum, 650.32, tolerance, DEFAULT_MAX_CHARGE), Some(2));
+        // 前体离子落在+1同位素峰上
+        assert_eq!(infer_charge(&spectrum, 650.32 + ISOTOPE_SPACING / 2.0, tolerance, DEFAULT_MAX_CHARGE), Some(2));
+        assert_eq!(infer_charge(&spectrum, 801.41 + ISOTOPE_SPACING / 3.0, tolerance, DEFAULT_MAX_CHARGE), Some(3));
+        assert_eq!(infer_charge(&spectrum, 801.41, tolerance, DEFAULT_MAX_CHARGE), Some(3));
+        // 最大电荷低于真实电荷时只能匹配到间隔的同位素峰
+        assert_eq!(infer_charge(&spectrum, 801.41, tolerance, 2), Some(1));
+        // 没有前体离子峰
+        assert_eq!(infer_charge(&Spectrum::ms1().unwrap(), 650.32, tolerance, DEFAULT_MAX_CHARGE), None);
+    }
+
+    #[test]
+    fn test_isolated_peak_has_no_charge() {
+        let mut spectrum = Spectrum::ms1().unwrap();
+        spectrum.add_peaks(vec![(500.0, 1000.0), (500.5017, 50.0), (700.0, 10.0)]).unwrap();
+        let tolerance = Tolerance::PPM(10.0);
+        // 低于强度下限的峰不计为同位素峰
+        assert_eq!(infer_charge(&spectrum, 500.0, tolerance, 4), None);
+        assert_eq!(infer_charge(&spectrum, 700.0, tolerance, 4), None);
+    }
+
+    #[test]
+    fn test_infer_missing_charges() {
+        let mut seed = 7;
+        let ms2 = |mz: f64, charge: Charge| {
+            let mut spectrum = Spectrum::ms2().unwrap();
+            spectrum.set_precursor(PrecursorInfo { mz, charge, ..PrecursorInfo::default() });
+            spectrum
+        };
+        let mut negative = ms2(801.41, 0);
+        negative.scan.polarity = Polarity::Negative;
+        let mut spectra = vec![
+            // 早于所有MS1
+            ms2(650.32, 0),
+            noisy_ms1(&[(650.32, 2)], &mut seed),
+            ms2(650.32, 0),
+            // 已有电荷的不修改
+            ms2(650.32, 1),
+            noisy_ms1(&[(801.41, 3)], &mut seed),
+            negative,
+            // 母离子谱图中没有对应的峰
+            ms2(1250.0, 0),
+        ];
+
+        let tolerance = Tolerance::PPM(DEFAULT_CHARGE_TOLERANCE_PPM);
+        assert_eq!(infer_missing_charges(&mut spectra, tolerance, DEFAULT_MAX_CHARGE), 2);
+        let charges: Vec<Charge> = spectra
+            .iter()
+            .filter_map(|spectrum| spectrum.precursor.as_deref().map(|precursor| precursor.charge))
+            .collect();
+        assert_eq!(charges, vec![0, 2, 1, -3, 0]);
+    }
+}
@@ -0,0 +1,258 @@
+//! MS2谱图聚类
+//!
+//! 大型DDA运行中同一前体离子往往被重复碎裂多次。这里按前体离子m/z和保留时间
+//! 寻找候选谱图对，以余弦相似度确认后做贪心单连接聚类，并为每个簇生成共识谱图。
+//! 候选对通过[`PrecursorIndex`]的有序前体离子列表获得，不做两两比较。
+
+use crate::analysis::precursor_index::PrecursorIndex;
+use crate::core::spectrum::{PrecursorInfo, Spectrum};
+use crate::core::types::*;
+
+pub use crate::search::similarity::cosine;
+
+#[cfg(feature = "python")]
+use crate::core::ms_object::MSObject;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 共识谱图中记录簇大小的额外信息键
+pub const CLUSTER_SIZE_KEY: &str = "cluster_size";
+
+/// 聚类参数
+#[derive(Debug, Clone)]
+pub struct ClusterParams {
+    /// 前体离子m/z容差 (ppm)
+    pub precursor_ppm: f64,
+    /// 保留时间容差 (秒)
+    pub rt_tolerance: f64,
+    /// 归入同一簇所需的最小余弦相似度
+    pub min_cosine: f64,
+    /// 碎片峰匹配容差
+    pub fragment_tolerance: ToleranceModel,
+}
+
+impl Default for ClusterParams {
+    fn default() -> Self {
+        Self {
+            precursor_ppm: 10.0,
+            rt_tolerance: 30.0,
+            min_cosine: 0.7,
+            fragment_tolerance: Tolerance::Absolute(0.02).into(),
+        }
+    }
+}
+
+/// 聚类结果
+#[derive(Debug, Clone, Default)]
+pub struct Clustering {
+    /// 每张输入谱图所属簇的编号，没有前体离子信息的谱图为None
+    pub labels: Vec<Option<usize>>,
+    /// 每个簇的共识谱图，按簇编号排列
+    pub consensus: Vec<Spectrum>,
+}
+
+/// 对MS2谱图聚类
+///
+/// 按前体离子m/z升序扫描，只与m/z容差内的后续谱图比较；保留时间差不超过
+/// `rt_tolerance`且余弦相似度不低于`min_cosine`的谱图对被连接到同一簇（单连接）。
+/// 簇编号按簇中第一张谱图在输入中的位置分配。
+pub fn cluster_ms2(spectra: &[Spectrum], params: &ClusterParams) -> Clustering {
+    let index = PrecursorIndex::new(spectra);
+    let entries = index.entries();
+
+    let peak_lists: Vec<PeakList> = spectra.iter().map(Spectrum::peaks).collect();
+    let mut parents: Vec<usize> = (0..spectra.len()).collect();
+    for (position, &(mz, i)) in entries.iter().enumerate() {
+        let tolerance = mz * params.precursor_ppm * 1e-6;
+        for &(other_mz, j) in &entries[position + 1..] {
+            if other_mz - mz > tolerance {
+                break;
+            }
+            if (spectra[i].scan.retention_time - spectra[j].scan.retention_time).abs() > params.rt_tolerance {
+                continue;
+            }
+            if find(&mut parents, i) == find(&mut parents, j) {
+                continue;
+            }
+            if cosine(&peak_lists[i], &peak_lists[j], &params.fragment_tolerance) >= params.min_cosine {
+                let (root_i, root_j) = (find(&mut parents, i), find(&mut parents, j));
+                parents[root_i.max(root_j)] = root_i.min(root_j);
+            }
+        }
+    }
+
+    let mut labels = vec![None; spectra.len()];
+    let mut members: Vec<Vec<&Spectrum>> = Vec::new();
+    let mut root_labels = vec![None; spectra.len()];
+    for (i, spectrum) in spectra.iter().enumerate() {
+        if spectrum.precursor.is_none() {
+            continue;
+        }
+        let root = find(&mut parents, i);
+        let label = *root_labels[root].get_or_insert_with(|| {
+            members.push(Vec::new());
+            members.len() - 1
+        });
+        labels[i] = Some(label);
+        members[label].push(spectrum);
+    }
+
+    let consensus = members
+        .iter()
+        .map(|group| merge_spectra(group, &params.fragment_tolerance))
+        .collect();
+    Clustering { labels, consensus }
+}
+
+/// 合并多张谱图为共识谱图
+///
+/// 碎片峰按m/z排序后与当前组的第一个峰比较分组，组内m/z取强度加权平均，
+/// 强度取所有谱图的平均值（缺失视为0）。前体离子m/z取平均，其余元数据
+/// 来自总离子流最高的谱图。
+pub fn merge_spectra(spectra: &[&Spectrum], tolerance: impl MzTolerance) -> Spectrum {
+    let Some(representative) = spectra
+        .iter()
+        .max_by(|a, b| a.total_ion_current().total_cmp(&b.total_ion_current()))
+    else {
+        return Spectrum::default();
+    };
+
+    let mut peaks: Vec<Peak> = spectra.iter().flat_map(|spectrum| spectrum.peaks_iter()).collect();
+    peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let count = spectra.len() as f64;
+    let mut merged = Vec::new();
+    let mut start = 0;
+    while start < peaks.len() {
+        let anchor = peaks[start].0;
+        let end = start + peaks[start..].partition_point(|peak| tolerance.is_within_tolerance(anchor, peak.0));
+        let group = &peaks[start..end];
+        let intensity: f64 = group.iter().map(|peak| peak.1).sum();
+        let mz = if intensity > 0.0 {
+            group.iter().map(|peak| peak.0 * peak.1).sum::<f64>() / intensity
+        } else {
+            group.iter().map(|peak| peak.0).sum::<f64>() / group.len() as f64
+        };
+        merged.push((mz, intensity / count));
+        start = end;
+    }
+
+    let mut consensus = (*representative).clone();
+    consensus.set_peaks(merged);
+    let precursors: Vec<&PrecursorInfo> = spectra.iter().filter_map(|spectrum| spectrum.precursor.as_deref()).collect();
+    if let Some(precursor) = consensus.precursor.as_deref_mut() {
+        precursor.mz = precursors.iter().map(|p| p.mz).sum::<f64>() / precursors.len() as f64;
+    }
+    consensus.additional_info.retain(|kv| kv.key != CLUSTER_SIZE_KEY);
+    consensus.additional_info.push(KeyValue::new(CLUSTER_SIZE_KEY, spectra.len().to_string()));
+    consensus
+}
+
+/// 并查集查找（带路径压缩）
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Python可用的MS2谱图聚类器
+#[cfg(feature = "python")]
+#[pyclass]
+pub struct SpectraClusterer {
+    params: ClusterParams,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl SpectraClusterer {
+    /// 创建聚类器，`fragment_tolerance`为碎片峰匹配容差：数值按Da处理，也可传入ToleranceModel
+    #[new]
+    #[pyo3(signature = (precursor_ppm=10.0, rt_tol=30.0, min_cosine=0.7, fragment_tolerance=None))]
+    fn new(precursor_ppm: f64, rt_tol: f64, min_cosine: f64, fragment_tolerance: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        let fragment_tolerance = match fragment_tolerance {
+            None => ClusterParams::default().fragment_tolerance,
+            Some(value) => match value.extract::<PyToleranceModel>() {
+                Ok(model) => model.model,
+                Err(_) => Tolerance::Absolute(value.extract::<f64>()?).into(),
+            },
+        };
+        Ok(Self {
+            params: ClusterParams {
+                precursor_ppm,
+                rt_tolerance: rt_tol,
+                min_cosine,
+                fragment_tolerance,
+            },
+        })
+    }
+
+    /// 聚类MSObject列表，返回(每张谱图的簇编号, 各簇的共识MSObject)
+    ///
+    /// 没有前体离子信息的谱图簇编号为-1。
+    fn cluster(&self, ms_objects: Vec<PyRef<'_, MSObject>>) -> (Vec<i64>, Vec<MSObject>) {
+        let spectra: Vec<Spectrum> = ms_objects.iter().map(|ms_object| ms_object.spectrum.clone()).collect();
+        let clustering = cluster_ms2(&spectra, &self.params);
+        let labels = clustering.labels.iter().map(|label| label.map_or(-1, |l| l as i64)).collect();
+        let consensus = clustering.consensus.into_iter().map(|spectrum| MSObject { spectrum }).collect();
+        (labels, consensus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms2(precursor_mz: f64, rt: f64, peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum.set_retention_time(rt).unwrap();
+        spectrum.set_precursor(PrecursorInfo { mz: precursor_mz, charge: 2, ..PrecursorInfo::default() });
+        spectrum
+    }
+
+    #[test]
+    fn test_two_groups_with_consensus() {
+        let peaks_a = [(150.1, 100.0), (250.2, 50.0), (350.3, 80.0), (450.4, 20.0)];
+        let peaks_b = [(175.0, 60.0), (275.0, 90.0), (375.0, 30.0)];
+
+        let mut spectra = Vec::new();
+        for copy in 0..5 {
+            let jitter = copy as f64 * 0.001;
+            let peaks: Vec<Peak> = peaks_a.iter().map(|&(mz, i)| (mz + jitter, i)).collect();
+            spectra.push(ms2(600.3 + jitter * 0.01, 100.0 + copy as f64 * 5.0, &peaks));
+        }
+        for copy in 0..3 {
+            spectra.push(ms2(600.3, 110.0 + copy as f64, &peaks_b));
+        }
+        spectra.push(Spectrum::new(1).unwrap());
+
+        let clustering = cluster_ms2(&spectra, &ClusterParams::default());
+        assert_eq!(clustering.consensus.len(), 2);
+        assert_eq!(&clustering.labels[..5], &[Some(0); 5]);
+        assert_eq!(&clustering.labels[5..8], &[Some(1); 3]);
+        assert_eq!(clustering.labels[8], None);
+
+        assert_eq!(clustering.consensus[0].peak_count(), 4);
+        assert_eq!(clustering.consensus[1].peak_count(), 3);
+        assert_eq!(clustering.consensus[0].get_additional_info(CLUSTER_SIZE_KEY), Some("5"));
+        assert!((clustering.consensus[1].intensity_slice()[1] - 90.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rt_and_precursor_separate_clusters() {
+        let peaks = [(200.0, 10.0), (300.0, 20.0)];
+        let spectra = vec![ms2(500.0, 10.0, &peaks), ms2(500.0, 500.0, &peaks), ms2(501.0, 10.0, &peaks)];
+        let clustering = cluster_ms2(&spectra, &ClusterParams::default());
+        assert_eq!(clustering.labels, vec![Some(0), Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn test_cosine() {
+        let a = [(100.0, 3.0), (200.0, 4.0)];
+        assert!((cosine(&a, &a, Tolerance::Absolute(0.01)) - 1.0).abs() < 1e-12);
+        assert_eq!(cosine(&a, &[(150.0, 1.0)], Tolerance::Absolute(0.01)), 0.0);
+        assert_eq!(cosine(&a, &[], Tolerance::Absolute(0.01)), 0.0);
+    }
+}
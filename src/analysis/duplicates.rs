@@ -0,0 +1,70 @@
+//! 重复谱图检测
+//!
+//! 按谱图指纹分组，再以余弦相似度确认，避免哈希碰撞造成误判。
+
+use crate::analysis::clustering::cosine;
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use std::collections::HashMap;
+
+/// 查找重复谱图，返回每组重复谱图的下标（每组至少两张，按首个下标排序）
+///
+/// 指纹相同的谱图中，与组内第一张谱图的余弦相似度不低于`min_cosine`的才计为重复；
+/// 未通过确认的谱图在剩余谱图中重新分组。
+pub fn find_duplicates<'a>(
+    spectra: impl IntoIterator<Item = &'a Spectrum>,
+    mz_precision: f64,
+    top_n: usize,
+    min_cosine: f64,
+) -> CoreResult<Vec<Vec<usize>>> {
+    let spectra: Vec<&Spectrum> = spectra.into_iter().collect();
+
+    let mut by_fingerprint: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, spectrum) in spectra.iter().enumerate() {
+        by_fingerprint.entry(spectrum.fingerprint(mz_precision, top_n)?).or_default().push(index);
+    }
+
+    let tolerance = Tolerance::Absolute(mz_precision);
+    let mut groups = Vec::new();
+    for mut candidates in by_fingerprint.into_values() {
+        while candidates.len() > 1 {
+            let representative = candidates[0];
+            let (group, rest): (Vec<usize>, Vec<usize>) = candidates.iter().partition(|&&index| {
+                index == representative
+                    || cosine(&spectra[representative].peaks(), &spectra[index].peaks(), tolerance) >= min_cosine
+            });
+            if group.len() > 1 {
+                groups.push(group);
+            }
+            candidates = rest;
+        }
+    }
+
+    groups.sort();
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectrum(peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let a = [(200.0, 100.0), (300.0, 50.0)];
+        let b = [(250.0, 100.0), (350.0, 80.0), (450.0, 10.0)];
+        let a_noisy: Vec<Peak> = a.iter().map(|&(mz, i)| (mz + 0.0001, i * 1.001)).collect();
+        let spectra = vec![spectrum(&a), spectrum(&b), spectrum(&a_noisy), spectrum(&[(999.0, 1.0)]), spectrum(&a)];
+
+        let groups = find_duplicates(&spectra, 0.01, 30, 0.99).unwrap();
+        assert_eq!(groups, vec![vec![0, 2, 4]]);
+
+        // 阈值超过1时任何谱图都无法确认为重复
+        assert!(find_duplicates(&spectra, 0.01, 30, 1.1).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+//! 碎片离子与中性丢失搜索
+//!
+//! 在整个运行的MS2谱图中查找含有指定碎片离子（如诊断离子、报告离子）
+//! 或指定中性丢失的谱图。每张谱图在按m/z排序的峰列表上二分定位容差窗口，
+//! 峰未排序时先排序一份副本。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use std::borrow::Cow;
+
+/// 一次碎片命中
+#[derive(Debug, Clone, PartialEq)]
+pub struct FragmentMatch {
+    /// 谱图在输入中的下标
+    pub spectrum_index: usize,
+    /// 命中峰的m/z
+    pub mz: f64,
+    /// 命中峰的强度
+    pub intensity: f64,
+    /// 命中峰强度相对基峰的比例
+    pub relative_intensity: f64,
+}
+
+/// 查找含有指定碎片离子的MS2谱图
+///
+/// 容差窗口内取最强的峰作为命中峰，相对强度低于`min_relative_intensity`的命中被丢弃。
+pub fn find_spectra_with_fragment(
+    spectra: &[Spectrum],
+    fragment_mz: f64,
+    tolerance: impl MzTolerance,
+    min_relative_intensity: f64,
+) -> Vec<FragmentMatch> {
+    let tolerance = tolerance.tolerance_at_mz(fragment_mz);
+    spectra
+        .iter()
+        .enumerate()
+        .filter(|(_, spectrum)| spectrum.is_ms2())
+        .filter_map(|(index, spectrum)| match_peak(index, spectrum, fragment_mz, tolerance))
+        .filter(|hit| hit.relative_intensity >= min_relative_intensity)
+        .collect()
+}
+
+/// 查找在前体离子m/z减去中性丢失处有峰的MS2谱图
+///
+/// 丢失后的碎片保留前体离子的全部电荷，因此目标m/z为`precursor_mz - loss_mass / z`；
+/// 电荷未知时按单电荷计算。没有前体离子信息的谱图被跳过。
+pub fn find_spectra_with_neutral_loss(
+    spectra: &[Spectrum],
+    loss_mass: f64,
+    tolerance: impl MzTolerance,
+) -> Vec<FragmentMatch> {
+    spectra
+        .iter()
+        .enumerate()
+        .filter(|(_, spectrum)| spectrum.is_ms2())
+        .filter_map(|(index, spectrum)| {
+            let precursor = spectrum.precursor.as_ref()?;
+            let charge = if precursor.charge != 0 { precursor.charge.unsigned_abs() as f64 } else { 1.0 };
+            let target = precursor.mz - loss_mass / charge;
+            match_peak(index, spectrum, target, tolerance.tolerance_at_mz(target))
+        })
+        .collect()
+}
+
+/// 在谱图中查找`target`容差窗口内最强的峰
+fn match_peak(index: usize, spectrum: &Spectrum, target: f64, tolerance: f64) -> Option<FragmentMatch> {
+    let (mz_array, intensity_array): (Cow<[f64]>, Cow<[f64]>) = if spectrum.mz_slice().is_sorted_by(|a, b| a <= b) {
+        (Cow::Borrowed(spectrum.mz_slice()), Cow::Borrowed(spectrum.intensity_slice()))
+    } else {
+        let mut sorted = spectrum.peaks();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (mz, intensity) = sorted.into_iter().unzip();
+        (Cow::Owned(mz), Cow::Owned(intensity))
+    };
+
+    let start = mz_array.partition_point(|&mz| mz < target - tolerance);
+    let end = mz_array.partition_point(|&mz| mz <= target + tolerance);
+    let (mz, intensity) = (start..end.max(start))
+        .map(|i| (mz_array[i], intensity_array[i]))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    let base = spectrum.base_peak().map_or(0.0, |(_, intensity)| intensity);
+    Some(FragmentMatch {
+        spectrum_index: index,
+        mz,
+        intensity,
+        relative_intensity: if base > 0.0 { intensity / base } else { 0.0 },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    fn ms2(peaks: &[Peak], precursor_mz: f64, charge: Charge) -> Spectrum {
+        let mut spectrum = Spectrum::ms2().unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum.set_precursor(PrecursorInfo { mz: precursor_mz, charge, ..Default::default() });
+        spectrum
+    }
+
+    #[test]
+    fn test_find_fragment_with_relative_floor() {
+        let mut ms1 = Spectrum::ms1().unwrap();
+        ms1.add_peak(126.1277, 1e6).unwrap();
+        let spectra = vec![
+            ms1,
+            ms2(&[(126.1278, 500.0), (300.0, 1000.0)], 500.0, 2),
+            // 峰未排序
+            ms2(&[(400.0, 1000.0), (126.1270, 20.0)], 500.0, 2),
+            // 碎片强度低于相对下限
+            ms2(&[(126.1276, 10.0), (300.0, 1000.0)], 500.0, 2),
+            ms2(&[(127.0, 10.0)], 500.0, 2),
+        ];
+
+        let hits = find_spectra_with_fragment(&spectra, 126.1277, Tolerance::PPM(10.0), 0.05);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].spectrum_index, 1);
+        assert_eq!(hits[0].intensity, 500.0);
+        assert!((hits[0].relative_intensity - 0.5).abs() < 1e-12);
+
+        let hits = find_spectra_with_fragment(&spectra, 126.1277, Tolerance::Absolute(0.01), 0.01);
+        assert_eq!(hits.iter().map(|hit| hit.spectrum_index).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_find_neutral_loss_uses_precursor_charge() {
+        // 磷酸丢失 (H3PO4, 97.9769)
+        let spectra = vec![
+            ms2(&[(502.0231, 100.0), (200.0, 50.0)], 600.0, 1),
+            ms2(&[(551.0116, 100.0)], 600.0, 2),
+            ms2(&[(502.0231, 100.0)], 600.0, 2),
+            Spectrum::ms2().unwrap(),
+        ];
+
+        let hits = find_spectra_with_neutral_loss(&spectra, 97.9769, Tolerance::PPM(20.0));
+        assert_eq!(hits.iter().map(|hit| hit.spectrum_index).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(hits[0].mz, 502.0231);
+    }
+}
@@ -0,0 +1,26 @@
+//! 数据分析模块
+//!
+//! 这个模块提供了基于已解析谱图的分析算法：
+//! - precursor_correction：根据MS1同位素峰簇校正前体离子m/z
+//! - segments：按扫描窗口、极性或扫描模式的切换拆分运行
+//! - precursor_index：按前体离子m/z排序的MS2索引
+//! - clustering：MS2谱图聚类与共识谱图
+//! - duplicates：基于谱图指纹的重复谱图检测
+//! - targeted：按前体离子目标列表提取MS2谱图并导出MGF
+//! - blank_subtraction：按空白运行去除污染物峰
+//! - fragment_search：查找含指定碎片离子或中性丢失的MS2谱图
+//! - rt_align：两次运行之间的保留时间对齐
+//! - calibration：由锁定质量或已鉴定肽段重校准m/z
+//! - charge：由MS1同位素间距推断前体离子电荷
+
+pub mod precursor_correction;
+pub mod segments;
+pub mod precursor_index;
+pub mod clustering;
+pub mod duplicates;
+pub mod targeted;
+pub mod blank_subtraction;
+pub mod fragment_search;
+pub mod rt_align;
+pub mod calibration;
+pub mod charge;
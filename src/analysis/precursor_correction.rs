@@ -0,0 +1,203 @@
+//! 前体离子m/z校正
+//!
+//! 仪器记录的是隔离窗口的目标m/z，常常落在同位素峰簇中的+1、+2峰上，
+//! 而搜索引擎需要单同位素峰。这里为每个MS2找到其母离子MS1谱图，
+//! 在MS1中定位包含目标m/z的同位素峰簇，向低m/z方向寻找单同位素峰并改写
+//! `PrecursorInfo.mz`（电荷未知时尝试由同位素间距推断）。
+
+use crate::analysis::charge::{infer_charge, DEFAULT_MAX_CHARGE};
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::utils::mass::ISOTOPE_SPACING;
+
+/// 记录同位素偏移数的额外信息键
+pub const ISOTOPE_SHIFT_KEY: &str = "precursor_isotope_shift";
+
+/// 记录原始前体离子m/z的额外信息键
+pub const ORIGINAL_MZ_KEY: &str = "original_precursor_mz";
+
+/// 向低m/z方向延伸时，候选同位素峰相对目标峰的最小强度比，低于该比例视为噪声
+const MIN_ISOTOPE_RATIO: f64 = 0.05;
+
+/// 按保留时间排序、峰按m/z排序的MS1谱图
+struct SortedMs1 {
+    retention_time: RetentionTime,
+    spectrum: Spectrum,
+}
+
+impl SortedMs1 {
+    /// 在ppm容差内查找最接近`mz`的峰
+    fn find_peak(&self, mz: f64, ppm: f64) -> Option<Peak> {
+        let tolerance = mz * ppm * 1e-6;
+        let mz_values = self.spectrum.mz_slice();
+        let start = mz_values.partition_point(|&peak_mz| peak_mz < mz - tolerance);
+        (start..mz_values.len())
+            .take_while(|&i| mz_values[i] <= mz + tolerance)
+            .min_by(|&a, &b| (mz_values[a] - mz).abs().total_cmp(&(mz_values[b] - mz).abs()))
+            .and_then(|i| self.spectrum.peak(i))
+    }
+}
+
+/// 校正MS2谱图的前体离子m/z，返回被修改的谱图数
+///
+/// - 母离子谱图为保留时间不晚于MS2的最后一张MS1；
+/// - 目标m/z必须能在MS1中以`ppm`容差找到对应峰，否则不做修改；
+/// - 单同位素峰最多向下寻找`max_isotope_shift`个同位素；
+/// - 已校正过的谱图（额外信息中含有[`ISOTOPE_SHIFT_KEY`]）会被跳过。
+pub fn correct(ms1: &[Spectrum], ms2_list: &mut [Spectrum], ppm: f64, max_isotope_shift: usize) -> usize {
+    let mut parents: Vec<SortedMs1> = ms1
+        .iter()
+        .filter(|spectrum| spectrum.is_ms1())
+        .map(|spectrum| {
+            let mut sorted = spectrum.clone();
+            sorted.sort_peaks();
+            SortedMs1 { retention_time: spectrum.scan.retention_time, spectrum: sorted }
+        })
+        .collect();
+    parents.sort_by(|a, b| a.retention_time.total_cmp(&b.retention_time));
+
+    let mut corrected = 0;
+    for spectrum in ms2_list.iter_mut() {
+        if spectrum.get_additional_info(ISOTOPE_SHIFT_KEY).is_some() {
+            continue;
+        }
+        let Some(precursor) = spectrum.precursor.as_deref() else {
+            continue;
+        };
+
+        let index = parents.partition_point(|parent| parent.retention_time <= spectrum.scan.retention_time);
+        let Some(parent) = index.checked_sub(1).map(|i| &parents[i]) else {
+            continue;
+        };
+
+        let Some(result) = locate_monoisotopic(parent, precursor.mz, precursor.charge, ppm, max_isotope_shift) else {
+            continue;
+        };
+        let (mono_mz, charge, shift) = result;
+        if shift == 0 && charge == precursor.charge {
+            continue;
+        }
+
+        let original_mz = precursor.mz;
+        if let Some(precursor) = spectrum.precursor.as_deref_mut() {
+            precursor.mz = mono_mz;
+            precursor.charge = charge;
+        }
+        // 上面已确认键不存在，这里不会失败
+        let _ = spectrum.add_additional_info(ISOTOPE_SHIFT_KEY, shift.to_string());
+        let _ = spectrum.add_additional_info(ORIGINAL_MZ_KEY, original_mz.to_string());
+        corrected += 1;
+    }
+
+    corrected
+}
+
+/// 在MS1中定位单同位素峰，返回 (单同位素m/z, 电荷, 同位素偏移数)
+///
+/// 电荷为0时由[`infer_charge`]推断。
+fn locate_monoisotopic(
+    parent: &SortedMs1,
+    target_mz: f64,
+    charge: Charge,
+    ppm: f64,
+    max_isotope_shift: usize,
+) -> Option<(f64, Charge, usize)> {
+    let target = parent.find_peak(target_mz, ppm)?;
+
+    let charge = if charge != 0 {
+        charge
+    } else {
+        infer_charge(&parent.spectrum, target.0, Tolerance::PPM(ppm), DEFAULT_MAX_CHARGE)?
+    };
+    let step = ISOTOPE_SPACING / charge.unsigned_abs() as f64;
+
+    let mut mono = target;
+    let mut shift = 0;
+    while shift < max_isotope_shift {
+        match parent.find_peak(mono.0 - step, ppm) {
+            Some(lower) if lower.1 >= target.1 * MIN_ISOTOPE_RATIO => {
+                mono = lower;
+                shift += 1;
+            }
+            _ => break,
+        }
+    }
+
+    Some((mono.0, charge, shift))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    /// 电荷为`charge`、单同位素m/z为`mono_mz`的同位素峰簇
+    fn envelope(mono_mz: f64, charge: Charge, intensities: &[f64]) -> Vec<Peak> {
+        let step = ISOTOPE_SPACING / charge as f64;
+        intensities.iter().enumerate().map(|(i, &intensity)| (mono_mz + i as f64 * step, intensity)).collect()
+    }
+
+    fn ms1(rt: f64, peaks: Vec<Peak>) -> Spectrum {
+        let mut spectrum = Spectrum::new(1).unwrap();
+        spectrum.add_peaks(peaks).unwrap();
+        spectrum.set_retention_time(rt).unwrap();
+        spectrum
+    }
+
+    fn ms2(rt: f64, target_mz: f64, charge: Charge) -> Spectrum {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.set_retention_time(rt).unwrap();
+        spectrum.set_precursor(PrecursorInfo { mz: target_mz, charge, ..PrecursorInfo::default() });
+        spectrum
+    }
+
+    #[test]
+    fn test_target_on_second_isotope() {
+        let mono = 650.3;
+        let mut peaks = envelope(mono, 2, &[60.0, 100.0, 80.0, 40.0]);
+        peaks.push((400.0, 500.0));
+        let parents = vec![ms1(10.0, peaks), ms1(20.0, vec![(650.3, 1.0)])];
+
+        let target = mono + 2.0 * ISOTOPE_SPACING / 2.0;
+        let mut spectra = vec![ms2(10.5, target, 2)];
+        assert_eq!(correct(&parents, &mut spectra, 10.0, 3), 1);
+
+        let precursor = spectra[0].precursor.as_deref().unwrap();
+        assert!((precursor.mz - (target - 2.0 * 1.00335 / 2.0)).abs() < 1e-4);
+        assert_eq!(precursor.charge, 2);
+        assert_eq!(spectra[0].get_additional_info(ISOTOPE_SHIFT_KEY), Some("2"));
+
+        // 重复调用不会再次校正
+        assert_eq!(correct(&parents, &mut spectra, 10.0, 3), 0);
+    }
+
+    #[test]
+    fn test_infer_charge_and_max_shift() {
+        let mono = 801.4;
+        let parents = vec![ms1(5.0, envelope(mono, 3, &[50.0, 100.0, 90.0, 60.0, 30.0]))];
+
+        let target = mono + 3.0 * ISOTOPE_SPACING / 3.0;
+        let mut spectra = vec![ms2(6.0, target, 0), ms2(6.0, target, 3)];
+        assert_eq!(correct(&parents, &mut spectra, 10.0, 1), 2);
+
+        let inferred = spectra[0].precursor.as_deref().unwrap();
+        assert_eq!(inferred.charge, 3);
+        // 最多向下移动1个同位素
+        assert!((inferred.mz - (mono + 2.0 * ISOTOPE_SPACING / 3.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_uncorrectable_spectra_are_left_alone() {
+        let parents = vec![ms1(10.0, envelope(500.0, 1, &[100.0, 50.0]))];
+        let mut spectra = vec![
+            // 目标已是单同位素峰
+            ms2(11.0, 500.0, 1),
+            // MS1中没有对应峰
+            ms2(11.0, 700.0, 2),
+            // 早于所有MS1
+            ms2(1.0, 501.0033548378, 1),
+        ];
+        assert_eq!(correct(&parents, &mut spectra, 10.0, 3), 0);
+        assert_eq!(spectra[1].precursor.as_deref().unwrap().mz, 700.0);
+    }
+}
@@ -0,0 +1,74 @@
+//! 前体离子索引
+//!
+//! 将MS2谱图按前体离子m/z排序，以二分查找定位ppm容差内的候选谱图，
+//! 避免聚类、靶向提取等场景中的两两比较。
+
+use crate::core::spectrum::Spectrum;
+
+/// 按前体离子m/z排序的谱图下标
+#[derive(Debug, Clone, Default)]
+pub struct PrecursorIndex {
+    /// (前体离子m/z, 谱图下标)，按m/z升序
+    entries: Vec<(f64, usize)>,
+}
+
+impl PrecursorIndex {
+    /// 为带有前体离子信息的谱图建立索引，下标为其在输入中的位置
+    pub fn new<'a>(spectra: impl IntoIterator<Item = &'a Spectrum>) -> Self {
+        let mut entries: Vec<(f64, usize)> = spectra
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, spectrum)| spectrum.precursor.as_deref().map(|precursor| (precursor.mz, index)))
+            .collect();
+        entries.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        Self { entries }
+    }
+
+    /// 已索引的谱图数
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 索引是否为空
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 按前体离子m/z升序排列的(m/z, 谱图下标)
+    pub fn entries(&self) -> &[(f64, usize)] {
+        &self.entries
+    }
+
+    /// 前体离子m/z在`mz`的`ppm`容差内的条目
+    pub fn query(&self, mz: f64, ppm: f64) -> &[(f64, usize)] {
+        let tolerance = mz * ppm * 1e-6;
+        let start = self.entries.partition_point(|entry| entry.0 < mz - tolerance);
+        let end = self.entries.partition_point(|entry| entry.0 <= mz + tolerance);
+        &self.entries[start..end.max(start)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    #[test]
+    fn test_query_within_ppm() {
+        let spectra: Vec<Spectrum> = [500.0, 300.0, 500.004, 500.02]
+            .iter()
+            .map(|&mz| {
+                let mut spectrum = Spectrum::new(2).unwrap();
+                spectrum.set_precursor(PrecursorInfo { mz, ..PrecursorInfo::default() });
+                spectrum
+            })
+            .chain(std::iter::once(Spectrum::new(1).unwrap()))
+            .collect();
+
+        let index = PrecursorIndex::new(&spectra);
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.entries()[0], (300.0, 1));
+        assert_eq!(index.query(500.0, 10.0).iter().map(|e| e.1).collect::<Vec<_>>(), vec![0, 2]);
+        assert!(index.query(800.0, 10.0).is_empty());
+    }
+}
@@ -0,0 +1,564 @@
+//! 两次运行之间的保留时间对齐
+//!
+//! 由锚点对(运行A的保留时间, 运行B的保留时间)拟合从A到B的单调映射：
+//! - 稳健线性模型：Theil-Sen估计，斜率取锚点两两之间斜率的中位数，截距取残差的中位数；
+//!   锚点多于[`MAX_THEIL_SEN_ANCHORS`]时按保留时间均匀抽取后计算斜率
+//! - LOWESS式分段映射：在锚点保留时间的分位点上做三次权重的局部线性回归，经过
+//!   [`ROBUSTNESS_ITERATIONS`]轮双平方权重的稳健重加权，再用保序回归保证节点单调不减
+//!
+//! 两种模型都表示为节点之间的分段线性函数，两端之外沿端点线段外推，因此可以直接求逆。
+//! 锚点可以由[`base_peak_anchors`]匹配两次运行的MS1基峰自动得到。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::utils::helpers::median;
+
+#[cfg(feature = "python")]
+use crate::core::ms_object::MSObject;
+#[cfg(feature = "python")]
+use crate::xic::extractor::py_shared_spectra;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::PyDict;
+
+/// Theil-Sen斜率估计最多使用的锚点数
+pub const MAX_THEIL_SEN_ANCHORS: usize = 1000;
+/// LOWESS局部回归默认使用的锚点比例
+pub const DEFAULT_LOWESS_SPAN: f64 = 0.3;
+/// LOWESS映射的最多节点数
+pub const MAX_LOWESS_KNOTS: usize = 50;
+/// LOWESS稳健重加权的轮数
+pub const ROBUSTNESS_ITERATIONS: usize = 2;
+
+/// 对齐模型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlignmentMethod {
+    /// 稳健线性模型
+    Linear,
+    /// 单调的LOWESS式分段映射，`span`为每次局部回归使用的锚点比例
+    Lowess { span: f64 },
+}
+
+impl AlignmentMethod {
+    /// 按名称解析："linear"或"lowess"
+    pub fn from_name(name: &str, span: f64) -> CoreResult<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "linear" => Ok(Self::Linear),
+            "lowess" | "loess" => Ok(Self::Lowess { span }),
+            _ => Err(CoreError::InvalidFormat(format!("Unknown RT alignment method: {}", name))),
+        }
+    }
+
+    /// 模型名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Linear => "linear",
+            Self::Lowess { .. } => "lowess",
+        }
+    }
+}
+
+/// 锚点残差（运行B的保留时间减去映射值）的统计 (秒)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResidualStats {
+    /// 参与拟合的锚点数
+    pub anchor_count: usize,
+    /// 残差均值
+    pub mean: f64,
+    /// 残差均方根
+    pub rmse: f64,
+    /// 残差绝对值的中位数
+    pub median_abs: f64,
+    /// 残差绝对值的最大值
+    pub max_abs: f64,
+}
+
+/// 从运行A到运行B的保留时间映射
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtAlignment {
+    /// 拟合使用的模型
+    pub method: AlignmentMethod,
+    /// 锚点残差统计
+    pub residuals: ResidualStats,
+    /// 映射节点，两个坐标都严格递增
+    knots: Vec<(RetentionTime, RetentionTime)>,
+}
+
+impl RtAlignment {
+    /// 用指定模型拟合锚点
+    ///
+    /// 忽略不是有限值的锚点；少于两个不同的运行A保留时间，或拟合结果不是递增映射时返回错误。
+    pub fn fit(anchors: &[(RetentionTime, RetentionTime)], method: AlignmentMethod) -> CoreResult<Self> {
+        let mut anchors: Vec<(f64, f64)> =
+            anchors.iter().copied().filter(|(rt_a, rt_b)| rt_a.is_finite() && rt_b.is_finite()).collect();
+        anchors.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        if anchors.first().map(|first| first.0) == anchors.last().map(|last| last.0) {
+            return Err(CoreError::InvalidFormat(
+                "RT alignment needs anchors at two or more distinct retention times".to_string(),
+            ));
+        }
+
+        let knots = match method {
+            AlignmentMethod::Linear => linear_knots(&anchors),
+            AlignmentMethod::Lowess { span } => lowess_knots(&anchors, span),
+        };
+        let knots = strictly_increasing(knots);
+        if knots.len() < 2 {
+            return Err(CoreError::InvalidFormat("RT alignment is not an increasing mapping".to_string()));
+        }
+
+        let mut alignment = Self { method, residuals: ResidualStats::default(), knots };
+        let residuals: Vec<f64> = anchors.iter().map(|&(rt_a, rt_b)| rt_b - alignment.map(rt_a)).collect();
+        alignment.residuals = residual_stats(&residuals);
+        Ok(alignment)
+    }
+
+    /// 用稳健线性模型拟合锚点
+    pub fn fit_linear(anchors: &[(RetentionTime, RetentionTime)]) -> CoreResult<Self> {
+        Self::fit(anchors, AlignmentMethod::Linear)
+    }
+
+    /// 用LOWESS式分段映射拟合锚点
+    pub fn fit_lowess(anchors: &[(RetentionTime, RetentionTime)], span: f64) -> CoreResult<Self> {
+        Self::fit(anchors, AlignmentMethod::Lowess { span })
+    }
+
+    /// 把运行A的保留时间映射到运行B
+    pub fn map(&self, rt: RetentionTime) -> RetentionTime {
+        interpolate(self.knots.iter().copied(), self.knots.len(), rt)
+    }
+
+    /// 把运行B的保留时间映射回运行A
+    pub fn inverse(&self, rt: RetentionTime) -> RetentionTime {
+        interpolate(self.knots.iter().map(|&(rt_a, rt_b)| (rt_b, rt_a)), self.knots.len(), rt)
+    }
+
+    /// 映射节点(运行A保留时间, 运行B保留时间)；线性模型为锚点范围两端的两个节点
+    pub fn knots(&self) -> &[(RetentionTime, RetentionTime)] {
+        &self.knots
+    }
+
+    /// 复制谱图并把保留时间映射到运行B，映射结果为负时取0
+    pub fn map_spectra(&self, spectra: &[Spectrum]) -> Vec<Spectrum> {
+        spectra
+            .iter()
+            .map(|spectrum| {
+                let mut mapped = spectrum.clone();
+                mapped.scan.retention_time = self.map(spectrum.scan.retention_time).max(0.0);
+                mapped
+            })
+            .collect()
+    }
+}
+
+/// Theil-Sen直线在锚点范围两端的两个节点；`anchors`按保留时间升序
+fn linear_knots(anchors: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let step = anchors.len().div_ceil(MAX_THEIL_SEN_ANCHORS);
+    let sample: Vec<(f64, f64)> = anchors.iter().copied().step_by(step).collect();
+    let mut slopes = Vec::with_capacity(sample.len() * (sample.len() - 1) / 2);
+    for (i, &(x0, y0)) in sample.iter().enumerate() {
+        for &(x1, y1) in &sample[i + 1..] {
+            if x1 > x0 {
+                slopes.push((y1 - y0) / (x1 - x0));
+            }
+        }
+    }
+    slopes.sort_by(|a, b| a.total_cmp(b));
+    // 抽样后所有锚点的保留时间可能相同，此时退回首尾两点的斜率
+    let slope = match median(&slopes) {
+        Some(slope) => slope,
+        None => {
+            let ((x0, y0), (x1, y1)) = (anchors[0], anchors[anchors.len() - 1]);
+            (y1 - y0) / (x1 - x0)
+        }
+    };
+
+    let mut intercepts: Vec<f64> = anchors.iter().map(|&(x, y)| y - slope * x).collect();
+    intercepts.sort_by(|a, b| a.total_cmp(b));
+    let intercept = median(&intercepts).unwrap_or(0.0);
+    let (first, last) = (anchors[0].0, anchors[anchors.len() - 1].0);
+    vec![(first, slope * first + intercept), (last, slope * last + intercept)]
+}
+
+/// LOWESS节点：在不同保留时间的分位点上做稳健局部线性回归，再做保序回归；`anchors`按保留时间升序
+fn lowess_knots(anchors: &[(f64, f64)], span: f64) -> Vec<(f64, f64)> {
+    let n = anchors.len();
+    let neighbours = ((span.clamp(0.0, 1.0) * n as f64).ceil() as usize).clamp(n.min(3), n);
+
+    let mut distinct: Vec<f64> = anchors.iter().map(|anchor| anchor.0).collect();
+    distinct.dedup();
+    let positions: Vec<f64> = if distinct.len() <= MAX_LOWESS_KNOTS {
+        distinct
+    } else {
+        (0..MAX_LOWESS_KNOTS)
+            .map(|i| distinct[i * (distinct.len() - 1) / (MAX_LOWESS_KNOTS - 1)])
+            .collect()
+    };
+
+    let mut robustness = vec![1.0; n];
+    let mut knots = Vec::new();
+    for iteration in 0..=ROBUSTNESS_ITERATIONS {
+        knots = positions.iter().map(|&x| (x, local_fit(anchors, &robustness, x, neighbours))).collect();
+        if iteration == ROBUSTNESS_ITERATIONS {
+            break;
+        }
+        let residuals: Vec<f64> =
+            anchors.iter().map(|&(x, y)| y - interpolate(knots.iter().copied(), knots.len(), x)).collect();
+        let mut absolute: Vec<f64> = residuals.iter().map(|residual| residual.abs()).collect();
+        absolute.sort_by(|a, b| a.total_cmp(b));
+        let scale = 6.0 * median(&absolute).unwrap_or(0.0);
+        if scale <= 0.0 {
+            break;
+        }
+        for (weight, residual) in robustness.iter_mut().zip(&residuals) {
+            let u = residual / scale;
+            *weight = if u.abs() < 1.0 { (1.0 - u * u).powi(2) } else { 0.0 };
+        }
+    }
+
+    let fitted = isotonic(&knots.iter().map(|knot| knot.1).collect::<Vec<f64>>());
+    knots.iter().zip(fitted).map(|(&(x, _), y)| (x, y)).collect()
+}
+
+/// `x`处的局部线性回归：取保留时间最接近的`neighbours`个锚点，按距离取三次权重再乘以稳健权重
+fn local_fit(anchors: &[(f64, f64)], robustness: &[f64], x: f64, neighbours: usize) -> f64 {
+    let n = anchors.len();
+    let mut start = anchors.partition_point(|anchor| anchor.0 < x).saturating_sub(neighbours / 2).min(n - neighbours);
+    while start > 0 && anchors[start + neighbours - 1].0 - x > x - anchors[start - 1].0 {
+        start -= 1;
+    }
+    while start + neighbours < n && x - anchors[start].0 > anchors[start + neighbours].0 - x {
+        start += 1;
+    }
+    let window = &anchors[start..start + neighbours];
+    let weights = &robustness[start..start + neighbours];
+    let bandwidth = (x - window[0].0).max(window[neighbours - 1].0 - x) * 1.001;
+
+    let (mut sw, mut swx, mut swy, mut swxx, mut swxy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (&(xi, yi), &robust) in window.iter().zip(weights) {
+        let u = if bandwidth > 0.0 { (xi - x).abs() / bandwidth } else { 0.0 };
+        let w = robust * (1.0 - u.powi(3)).max(0.0).powi(3);
+        let dx = xi - x;
+        sw += w;
+        swx += w * dx;
+        swy += w * yi;
+        swxx += w * dx * dx;
+        swxy += w * dx * yi;
+    }
+    if sw <= 0.0 {
+        return window.iter().map(|anchor| anchor.1).sum::<f64>() / neighbours as f64;
+    }
+    // 以x为原点回归，截距即为x处的拟合值
+    let denominator = sw * swxx - swx * swx;
+    if denominator <= f64::EPSILON * sw * swxx {
+        swy / sw
+    } else {
+        (swxx * swy - swx * swxy) / denominator
+    }
+}
+
+/// 等权保序回归（相邻违序块合并取均值），结果单调不减
+fn isotonic(values: &[f64]) -> Vec<f64> {
+    let mut blocks: Vec<(f64, usize)> = Vec::with_capacity(values.len());
+    for &value in values {
+        blocks.push((value, 1));
+        while let [.., (previous, previous_count), (last, last_count)] = blocks[..] {
+            if previous <= last {
+                break;
+            }
+            let count = previous_count + last_count;
+            blocks.truncate(blocks.len() - 2);
+            blocks.push(((previous * previous_count as f64 + last * last_count as f64) / count as f64, count));
+        }
+    }
+    blocks.into_iter().flat_map(|(value, count)| std::iter::repeat_n(value, count)).collect()
+}
+
+/// 去掉使任一坐标不再严格递增的节点，保证映射和逆映射都可以插值
+fn strictly_increasing(knots: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    let mut kept: Vec<(f64, f64)> = Vec::with_capacity(knots.len());
+    for knot in knots {
+        match kept.last() {
+            Some(&(x, y)) if knot.0 <= x || knot.1 <= y => {}
+            _ => kept.push(knot),
+        }
+    }
+    kept
+}
+
+/// 在按第一个坐标严格递增的节点之间线性插值，两端之外沿端点线段外推；至少需要两个节点
+fn interpolate(knots: impl Iterator<Item = (f64, f64)> + Clone, len: usize, x: f64) -> f64 {
+    let segment = knots.clone().skip(1).take(len - 2).take_while(|&(knot_x, _)| knot_x < x).count();
+    let mut pair = knots.skip(segment);
+    let ((x0, y0), (x1, y1)) = (pair.next().unwrap(), pair.next().unwrap());
+    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+}
+
+/// 残差统计
+fn residual_stats(residuals: &[f64]) -> ResidualStats {
+    let count = residuals.len();
+    let mut absolute: Vec<f64> = residuals.iter().map(|residual| residual.abs()).collect();
+    absolute.sort_by(|a, b| a.total_cmp(b));
+    ResidualStats {
+        anchor_count: count,
+        mean: residuals.iter().sum::<f64>() / count as f64,
+        rmse: (residuals.iter().map(|residual| residual * residual).sum::<f64>() / count as f64).sqrt(),
+        median_abs: median(&absolute).unwrap_or(0.0),
+        max_abs: absolute.last().copied().unwrap_or(0.0),
+    }
+}
+
+/// 由两次运行的MS1基峰匹配锚点，按运行A的保留时间升序
+///
+/// 每次运行中基峰m/z相差在`ppm`内的MS1谱图归为同一个离子，取基峰强度最高的谱图的保留时间作为
+/// 该离子的顶点；运行B中恰好有一个离子在容差内的离子组成一个锚点，有多个候选的离子不用。
+/// 贯穿整次运行的背景离子也会给出锚点，由稳健拟合剔除。
+pub fn base_peak_anchors(run_a: &[Spectrum], run_b: &[Spectrum], ppm: f64) -> Vec<(RetentionTime, RetentionTime)> {
+    let tolerance = Tolerance::PPM(ppm);
+    let ions_b = base_peak_apexes(run_b, tolerance);
+    let mut anchors: Vec<(f64, f64)> = base_peak_apexes(run_a, tolerance)
+        .into_iter()
+        .filter_map(|(mz, rt_a)| {
+            let (lower, upper) = tolerance.window(mz);
+            let start = ions_b.partition_point(|ion| ion.0 < lower);
+            match ions_b[start..].iter().take_while(|ion| ion.0 <= upper).collect::<Vec<_>>()[..] {
+                [&(_, rt_b)] => Some((rt_a, rt_b)),
+                _ => None,
+            }
+        })
+        .collect();
+    anchors.sort_by(|a, b| a.0.total_cmp(&b.0));
+    anchors
+}
+
+/// 一次运行中各基峰离子的(m/z, 顶点保留时间)，按m/z升序
+fn base_peak_apexes(spectra: &[Spectrum], tolerance: Tolerance) -> Vec<(f64, RetentionTime)> {
+    let mut base_peaks: Vec<(f64, f64, RetentionTime)> = spectra
+        .iter()
+        .filter(|spectrum| spectrum.level == 1)
+        .filter_map(|spectrum| spectrum.base_peak().map(|(mz, intensity)| (mz, intensity, spectrum.scan.retention_time)))
+        .filter(|&(_, intensity, _)| intensity > 0.0)
+        .collect();
+    base_peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut apexes: Vec<(f64, f64, RetentionTime)> = Vec::new();
+    let mut group_mz = f64::NEG_INFINITY;
+    for (mz, intensity, rt) in base_peaks {
+        match apexes.last_mut() {
+            Some(apex) if tolerance.is_within_tolerance(group_mz, mz) => {
+                if intensity > apex.1 {
+                    *apex = (mz, intensity, rt);
+                }
+            }
+            _ => {
+                group_mz = mz;
+                apexes.push((mz, intensity, rt));
+            }
+        }
+    }
+    apexes.sort_by(|a, b| a.0.total_cmp(&b.0));
+    apexes.into_iter().map(|(mz, _, rt)| (mz, rt)).collect()
+}
+
+/// Python可用的保留时间对齐
+#[cfg(feature = "python")]
+#[pyclass(name = "RtAlignment")]
+pub struct PyRtAlignment {
+    pub alignment: RtAlignment,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyRtAlignment {
+    /// 由(运行A保留时间, 运行B保留时间)锚点列表拟合，`method`为"linear"或"lowess"
+    #[staticmethod]
+    #[pyo3(signature = (pairs, method="linear", span=DEFAULT_LOWESS_SPAN))]
+    fn fit(pairs: Vec<(f64, f64)>, method: &str, span: f64) -> PyResult<Self> {
+        let method = AlignmentMethod::from_name(method, span)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        RtAlignment::fit(&pairs, method)
+            .map(|alignment| Self { alignment })
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 匹配两次运行（MSObject列表或MZMLObject）的MS1基峰得到锚点并拟合
+    #[staticmethod]
+    #[pyo3(signature = (run_a, run_b, ppm=10.0, method="linear", span=DEFAULT_LOWESS_SPAN))]
+    fn from_runs(
+        py: Python,
+        run_a: &Bound<'_, PyAny>,
+        run_b: &Bound<'_, PyAny>,
+        ppm: f64,
+        method: &str,
+        span: f64,
+    ) -> PyResult<Self> {
+        let (run_a, run_b) = (py_shared_spectra(run_a)?, py_shared_spectra(run_b)?);
+        let anchors = py.allow_threads(|| base_peak_anchors(run_a.spectra(), run_b.spectra(), ppm));
+        Self::fit(anchors, method, span)
+    }
+
+    /// 匹配两次运行的MS1基峰，返回(运行A保留时间, 运行B保留时间)锚点列表
+    #[staticmethod]
+    #[pyo3(signature = (run_a, run_b, ppm=10.0))]
+    fn base_peak_anchors(py: Python, run_a: &Bound<'_, PyAny>, run_b: &Bound<'_, PyAny>, ppm: f64) -> PyResult<Vec<(f64, f64)>> {
+        let (run_a, run_b) = (py_shared_spectra(run_a)?, py_shared_spectra(run_b)?);
+        Ok(py.allow_threads(|| base_peak_anchors(run_a.spectra(), run_b.spectra(), ppm)))
+    }
+
+    /// 把运行A的保留时间映射到运行B
+    fn map(&self, rt: f64) -> f64 {
+        self.alignment.map(rt)
+    }
+
+    /// 把运行B的保留时间映射回运行A
+    fn inverse(&self, rt: f64) -> f64 {
+        self.alignment.inverse(rt)
+    }
+
+    /// 返回保留时间映射到运行B的MSObject副本
+    fn map_spectra(&self, ms_objects: Vec<PyRef<'_, MSObject>>) -> Vec<MSObject> {
+        let spectra: Vec<Spectrum> = ms_objects.iter().map(|ms_object| ms_object.spectrum.clone()).collect();
+        self.alignment.map_spectra(&spectra).into_iter().map(|spectrum| MSObject { spectrum }).collect()
+    }
+
+    /// 模型名称
+    #[getter]
+    fn method(&self) -> &'static str {
+        self.alignment.method.name()
+    }
+
+    /// 映射节点(运行A保留时间, 运行B保留时间)
+    #[getter]
+    fn knots(&self) -> Vec<(f64, f64)> {
+        self.alignment.knots().to_vec()
+    }
+
+    /// 残差统计：anchor_count、mean、rmse、median_abs、max_abs
+    #[getter]
+    fn residuals(&self, py: Python) -> PyResult<Py<PyDict>> {
+        let residuals = &self.alignment.residuals;
+        let dict = PyDict::new(py);
+        dict.set_item("anchor_count", residuals.anchor_count)?;
+        dict.set_item("mean", residuals.mean)?;
+        dict.set_item("rmse", residuals.rmse)?;
+        dict.set_item("median_abs", residuals.median_abs)?;
+        dict.set_item("max_abs", residuals.max_abs)?;
+        Ok(dict.unbind())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_spectrum::{gaussian_noise, pseudo_random};
+
+    /// 300个锚点：运行B的保留时间为`truth(rt_a)`加标准差3秒的噪声，另有10%的锚点是随机的错误匹配
+    fn noisy_anchors(truth: impl Fn(f64) -> f64, seed: &mut u64) -> Vec<(f64, f64)> {
+        (0..300)
+            .map(|_| {
+                let rt_a = 3600.0 * pseudo_random(seed);
+                if pseudo_random(seed) < 0.1 {
+                    (rt_a, 3800.0 * pseudo_random(seed))
+                } else {
+                    (rt_a, truth(rt_a) + 3.0 * gaussian_noise(seed))
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recovers_shift_and_scale() {
+        let truth = |rt: f64| 1.05 * rt + 120.0;
+        let mut seed = 11;
+        let anchors = noisy_anchors(truth, &mut seed);
+
+        for method in [AlignmentMethod::Linear, AlignmentMethod::Lowess { span: DEFAULT_LOWESS_SPAN }] {
+            let alignment = RtAlignment::fit(&anchors, method).unwrap();
+            let intercept = alignment.map(0.0);
+            let slope = (alignment.map(3600.0) - intercept) / 3600.0;
+            assert!((slope - 1.05).abs() < 0.0105, "{:?} slope {}", method, slope);
+            assert!((intercept - 120.0).abs() < 1.2, "{:?} intercept {}", method, intercept);
+            for rt in [300.0, 1200.0, 2400.0, 3500.0] {
+                assert!((alignment.map(rt) - truth(rt)).abs() < 0.01 * truth(rt));
+                assert!((alignment.inverse(alignment.map(rt)) - rt).abs() < 1e-6);
+            }
+            assert_eq!(alignment.residuals.anchor_count, 300);
+            assert!(alignment.residuals.median_abs < 3.0, "{:?} {:?}", method, alignment.residuals);
+            assert!(alignment.residuals.max_abs > 100.0);
+        }
+    }
+
+    #[test]
+    fn test_lowess_follows_nonlinear_drift() {
+        // 梯度中段的漂移比两端大，线性模型无法同时拟合
+        let truth = |rt: f64| rt + 120.0 * (std::f64::consts::PI * rt / 3600.0).sin();
+        let mut seed = 17;
+        let anchors = noisy_anchors(truth, &mut seed);
+        let lowess = RtAlignment::fit_lowess(&anchors, 0.2).unwrap();
+        let linear = RtAlignment::fit_linear(&anchors).unwrap();
+        for rt in [600.0, 1800.0, 3000.0] {
+            assert!((lowess.map(rt) - truth(rt)).abs() < 10.0, "lowess {} at {}", lowess.map(rt), rt);
+        }
+        assert!((linear.map(1800.0) - truth(1800.0)).abs() > 30.0);
+        assert!(lowess.residuals.median_abs < linear.residuals.median_abs);
+        assert!(lowess.knots().windows(2).all(|pair| pair[1].0 > pair[0].0 && pair[1].1 > pair[0].1));
+        assert_eq!(lowess.knots().len(), MAX_LOWESS_KNOTS);
+    }
+
+    #[test]
+    fn test_degenerate_anchors_and_names() {
+        assert!(RtAlignment::fit_linear(&[]).is_err());
+        assert!(RtAlignment::fit_linear(&[(10.0, 12.0), (10.0, 15.0), (f64::NAN, 1.0)]).is_err());
+        // 保留时间顺序颠倒时不是递增映射
+        assert!(RtAlignment::fit_linear(&[(10.0, 20.0), (20.0, 10.0)]).is_err());
+
+        let alignment = RtAlignment::fit_linear(&[(10.0, 5.0), (20.0, 25.0)]).unwrap();
+        assert_eq!(alignment.map(30.0), 45.0);
+        assert_eq!(alignment.inverse(-15.0), 0.0);
+        assert_eq!(alignment.residuals.rmse, 0.0);
+
+        let mut spectrum = Spectrum::ms1().unwrap();
+        spectrum.set_retention_time(1.0).unwrap();
+        let mapped = alignment.map_spectra(std::slice::from_ref(&spectrum));
+        assert_eq!(mapped[0].scan.retention_time, 0.0);
+        assert_eq!(spectrum.scan.retention_time, 1.0);
+        spectrum.set_retention_time(12.0).unwrap();
+        assert_eq!(alignment.map_spectra(&[spectrum])[0].scan.retention_time, 9.0);
+
+        assert_eq!(AlignmentMethod::from_name("LOWESS", 0.5).unwrap(), AlignmentMethod::Lowess { span: 0.5 });
+        assert_eq!(AlignmentMethod::from_name("linear", 0.5).unwrap().name(), "linear");
+        assert!(AlignmentMethod::from_name("spline", 0.5).is_err());
+        assert_eq!(isotonic(&[1.0, 3.0, 2.0, 4.0]), vec![1.0, 2.5, 2.5, 4.0]);
+    }
+
+    /// 每秒一张MS1，各化合物以高斯峰形在`elution(i)`处洗脱
+    fn lc_run(compounds: &[f64], elution: impl Fn(usize) -> f64, mz_shift_ppm: f64) -> Vec<Spectrum> {
+        (0..1300)
+            .map(|second| {
+                let rt = second as f64;
+                let mut spectrum = Spectrum::ms1().unwrap();
+                spectrum.set_retention_time(rt).unwrap();
+                for (i, &mz) in compounds.iter().enumerate() {
+                    let intensity = 1e6 * (-0.5 * ((rt - elution(i)) / 4.0).powi(2)).exp();
+                    spectrum.add_peak(mz * (1.0 + mz_shift_ppm * 1e-6), intensity.max(1.0)).unwrap();
+                }
+                spectrum
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_base_peak_anchors() {
+        let compounds: Vec<f64> = (0..20).map(|i| 400.0 + 37.1 * i as f64).collect();
+        let run_a = lc_run(&compounds, |i| 50.0 + 50.0 * i as f64, 0.0);
+        let run_b = lc_run(&compounds, |i| 1.02 * (50.0 + 50.0 * i as f64) + 15.0, 2.0);
+        let anchors = base_peak_anchors(&run_a, &run_b, 10.0);
+        assert_eq!(anchors.len(), 20);
+        assert!(anchors.iter().all(|&(rt_a, rt_b)| (rt_b - (1.02 * rt_a + 15.0)).abs() <= 1.0));
+
+        let alignment = RtAlignment::fit_linear(&anchors).unwrap();
+        assert!((alignment.map(600.0) - 627.0).abs() < 1.0);
+        assert!(base_peak_anchors(&run_a, &run_b, 1.0).is_empty());
+    }
+}
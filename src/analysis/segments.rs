@@ -0,0 +1,216 @@
+//! 按采集分段拆分运行
+//!
+//! 分段采集方法在不同时间段使用不同的扫描范围、极性或扫描模式。
+//! 这里为每张谱图计算所选属性的标签，用一个带滞后的小状态机检测属性切换点：
+//! 新标签需连续出现`hysteresis`张谱图才确认为新分段，单张异常谱图会被忽略。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// 分段依据的谱图属性
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentBy {
+    /// MS1扫描窗口（MS2谱图不参与判断）
+    ScanWindow,
+    /// 扫描极性
+    Polarity,
+    /// 每个以MS1开始的采集周期中出现的MS级别组合，如"1,2"
+    MsLevelPattern,
+}
+
+impl SegmentBy {
+    /// 按名称解析分段依据
+    pub fn from_name(name: &str) -> CoreResult<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "scan_window" => Ok(Self::ScanWindow),
+            "polarity" => Ok(Self::Polarity),
+            "ms_level_pattern" => Ok(Self::MsLevelPattern),
+            _ => Err(CoreError::InvalidFormat(format!(
+                "Unknown segment property: {} (expected scan_window, polarity or ms_level_pattern)",
+                name
+            ))),
+        }
+    }
+}
+
+/// 检测到的分段
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// 谱图下标范围
+    pub range: Range<usize>,
+    /// 分段的属性标签，无法确定时为"unknown"
+    pub label: String,
+    /// 第一张谱图的保留时间
+    pub start_rt: RetentionTime,
+}
+
+/// 待确认的属性切换
+struct Pending {
+    label: String,
+    start: usize,
+    count: usize,
+}
+
+/// 将谱图序列拆分为连续分段
+///
+/// `hysteresis`为确认切换所需的连续谱图数（至少为1）。没有标签的谱图
+/// （如按扫描窗口分段时的MS2）不影响判断，归入所在位置的分段。
+pub fn split_segments<'a>(
+    spectra: impl IntoIterator<Item = &'a Spectrum>,
+    by: SegmentBy,
+    hysteresis: usize,
+) -> Vec<Segment> {
+    let spectra: Vec<&Spectrum> = spectra.into_iter().collect();
+    if spectra.is_empty() {
+        return Vec::new();
+    }
+    let labels = segment_labels(&spectra, by);
+    let hysteresis = hysteresis.max(1);
+
+    let mut boundaries: Vec<(usize, Option<String>)> = Vec::new();
+    let mut current: Option<String> = None;
+    let mut pending: Option<Pending> = None;
+    for (index, label) in labels.into_iter().enumerate() {
+        let Some(label) = label else { continue };
+        let Some(current_label) = &current else {
+            current = Some(label);
+            continue;
+        };
+        if *current_label == label {
+            pending = None;
+            continue;
+        }
+
+        let candidate = match pending.take() {
+            Some(mut candidate) if candidate.label == label => {
+                candidate.count += 1;
+                candidate
+            }
+            _ => Pending { label, start: index, count: 1 },
+        };
+        if candidate.count >= hysteresis {
+            boundaries.push((candidate.start, current.replace(candidate.label)));
+        } else {
+            pending = Some(candidate);
+        }
+    }
+
+    let mut segments = Vec::with_capacity(boundaries.len() + 1);
+    let mut start = 0;
+    for (end, label) in boundaries {
+        segments.push(make_segment(&spectra, start..end, label));
+        start = end;
+    }
+    segments.push(make_segment(&spectra, start..spectra.len(), current));
+    segments
+}
+
+fn make_segment(spectra: &[&Spectrum], range: Range<usize>, label: Option<String>) -> Segment {
+    Segment {
+        start_rt: spectra[range.start].scan.retention_time,
+        range,
+        label: label.unwrap_or_else(|| "unknown".to_string()),
+    }
+}
+
+/// 计算每张谱图的属性标签，None表示该谱图不参与判断
+fn segment_labels(spectra: &[&Spectrum], by: SegmentBy) -> Vec<Option<String>> {
+    match by {
+        SegmentBy::ScanWindow => spectra
+            .iter()
+            .map(|spectrum| {
+                let (low, high) = spectrum.scan.scan_window;
+                (spectrum.is_ms1() && high > low).then(|| format!("{:.2}-{:.2}", low, high))
+            })
+            .collect(),
+        SegmentBy::Polarity => spectra
+            .iter()
+            .map(|spectrum| match spectrum.scan.polarity {
+                Polarity::Unknown => None,
+                polarity => Some(polarity.name().to_string()),
+            })
+            .collect(),
+        SegmentBy::MsLevelPattern => {
+            let mut labels = Vec::with_capacity(spectra.len());
+            let mut cycle_start = 0;
+            for index in 1..=spectra.len() {
+                if index == spectra.len() || spectra[index].is_ms1() {
+                    let levels: BTreeSet<MSLevel> = spectra[cycle_start..index].iter().map(|s| s.level).collect();
+                    let label = levels.iter().map(|level| level.to_string()).collect::<Vec<_>>().join(",");
+                    labels.extend(std::iter::repeat_n(Some(label), index - cycle_start));
+                    cycle_start = index;
+                }
+            }
+            labels
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(polarities: impl IntoIterator<Item = Polarity>) -> Vec<Spectrum> {
+        polarities
+            .into_iter()
+            .enumerate()
+            .map(|(index, polarity)| {
+                let mut spectrum = Spectrum::new(1).unwrap();
+                spectrum.set_retention_time(index as f64).unwrap();
+                spectrum.scan.polarity = polarity;
+                spectrum
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_alternating_polarity() {
+        let polarities = (0..500).map(|i| if (i / 100) % 2 == 0 { Polarity::Positive } else { Polarity::Negative });
+        let spectra = run(polarities);
+
+        let segments = split_segments(&spectra, SegmentBy::Polarity, 3);
+        assert_eq!(segments.len(), 5);
+        assert_eq!(segments[1].range, 100..200);
+        assert_eq!(segments[1].label, "negative");
+        assert_eq!(segments.iter().map(|s| s.start_rt).collect::<Vec<_>>(), vec![0.0, 100.0, 200.0, 300.0, 400.0]);
+    }
+
+    #[test]
+    fn test_hysteresis_suppresses_outlier() {
+        let mut spectra = run((0..200).map(|i| if i < 100 { Polarity::Positive } else { Polarity::Negative }));
+        spectra[50].scan.polarity = Polarity::Negative;
+        spectra[120].scan.polarity = Polarity::Unknown;
+
+        let segments = split_segments(&spectra, SegmentBy::Polarity, 2);
+        assert_eq!(segments.iter().map(|s| s.range.clone()).collect::<Vec<_>>(), vec![0..100, 100..200]);
+
+        // 不使用滞后时单张异常谱图也会切分
+        assert_eq!(split_segments(&spectra, SegmentBy::Polarity, 1).len(), 4);
+    }
+
+    #[test]
+    fn test_scan_window_and_level_pattern() {
+        let mut spectra = Vec::new();
+        for cycle in 0..6 {
+            let mut ms1 = Spectrum::new(1).unwrap();
+            ms1.scan.scan_window = if cycle < 3 { (100.0, 500.0) } else { (500.0, 1500.0) };
+            spectra.push(ms1);
+            if cycle >= 4 {
+                let mut ms2 = Spectrum::new(2).unwrap();
+                ms2.scan.scan_window = (50.0, 2000.0);
+                spectra.push(ms2);
+            }
+        }
+
+        let by_window = split_segments(&spectra, SegmentBy::ScanWindow, 1);
+        assert_eq!(by_window.iter().map(|s| s.label.as_str()).collect::<Vec<_>>(), vec!["100.00-500.00", "500.00-1500.00"]);
+        assert_eq!(by_window[1].range, 3..8);
+
+        let by_pattern = split_segments(&spectra, SegmentBy::MsLevelPattern, 2);
+        assert_eq!(by_pattern.iter().map(|s| s.label.as_str()).collect::<Vec<_>>(), vec!["1", "1,2"]);
+        assert_eq!(by_pattern[1].range, 4..8);
+        assert!(SegmentBy::from_name("charge").is_err());
+    }
+}
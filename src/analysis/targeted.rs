@@ -0,0 +1,301 @@
+//! 靶向MS2提取
+//!
+//! 给定(前体离子m/z, 保留时间)目标列表，提取前体离子在ppm容差内、保留时间在窗口内的
+//! 全部MS2谱图。内存中的谱图通过[`PrecursorIndex`]查找；文件输入逐张流式读取，
+//! 只保留可能命中的MS2谱图。结果可写为一个合并的MGF文件或每个目标一个MGF文件。
+
+use crate::analysis::precursor_index::PrecursorIndex;
+use crate::core::filter::SpectrumFilter;
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::parsers::common::{ParseError, ParseResult};
+use crate::parsers::mgf::MGFWriter;
+use crate::parsers::mzml::MZMLParser;
+use crate::parsers::title::{SpectrumTitleFormatter, TitleContext};
+use crate::utils::mass::within_ppm;
+use crate::utils::path::extended_length_path;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "python")]
+use crate::core::ms_object::MSObject;
+#[cfg(feature = "python")]
+use crate::parsers::mzml::reader::MZMLObject;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::{PyDict, PyList};
+
+/// 靶向提取参数
+#[derive(Debug, Clone, Copy)]
+pub struct TargetedParams {
+    /// 前体离子m/z容差 (ppm)
+    pub ppm: f64,
+    /// 保留时间窗口半宽 (秒)
+    pub rt_window: f64,
+}
+
+impl Default for TargetedParams {
+    fn default() -> Self {
+        Self { ppm: 10.0, rt_window: 30.0 }
+    }
+}
+
+/// 为每个目标查找匹配的MS2谱图，返回各目标命中谱图在输入中的下标（升序）
+pub fn match_targets(spectra: &[Spectrum], targets: &[(f64, RetentionTime)], params: &TargetedParams) -> Vec<Vec<usize>> {
+    let index = PrecursorIndex::new(spectra.iter());
+    targets
+        .iter()
+        .map(|&(mz, rt)| {
+            let mut hits: Vec<usize> = index
+                .query(mz, params.ppm)
+                .iter()
+                .map(|&(_, i)| i)
+                .filter(|&i| spectra[i].is_ms2() && (spectra[i].scan.retention_time - rt).abs() <= params.rt_window)
+                .collect();
+            hits.sort_unstable();
+            hits
+        })
+        .collect()
+}
+
+/// 从mzML文件流式提取，返回每个目标命中的谱图
+///
+/// 只有保留时间落在所有目标窗口并集内、且前体离子m/z至少接近一个目标的MS2谱图会被保留在内存中。
+pub fn extract_from_file(
+    parser: &MZMLParser,
+    filename: impl AsRef<Path>,
+    targets: &[(f64, RetentionTime)],
+    params: &TargetedParams,
+) -> ParseResult<Vec<Vec<Spectrum>>> {
+    if targets.is_empty() {
+        return Ok(Vec::new());
+    }
+    let min_rt = targets.iter().map(|t| t.1).fold(f64::INFINITY, f64::min) - params.rt_window;
+    let max_rt = targets.iter().map(|t| t.1).fold(f64::NEG_INFINITY, f64::max) + params.rt_window;
+    let filter = SpectrumFilter::new().with_ms_levels(vec![2]).with_rt_range(min_rt, max_rt);
+
+    let mut candidates = Vec::new();
+    parser.for_each_spectrum(filename, |_, spectrum| {
+        let near_target = spectrum
+            .precursor
+            .as_deref()
+            .is_some_and(|precursor| targets.iter().any(|&(mz, _)| within_ppm(precursor.mz, mz, params.ppm)));
+        if near_target && filter.matches_spectrum(&spectrum) {
+            candidates.push(spectrum);
+        }
+        Ok(())
+    })?;
+
+    let matches = match_targets(&candidates, targets, params);
+    Ok(matches
+        .iter()
+        .map(|hits| hits.iter().map(|&i| candidates[i].clone()).collect())
+        .collect())
+}
+
+/// MGF中TITLE的默认写法，以目标序号开头以便追溯到目标
+pub fn target_title(target_index: usize, target: (f64, RetentionTime), spectrum: &Spectrum) -> String {
+    format!(
+        "target_{} mz={:.4} rt={:.2} scan={}",
+        target_index, target.0, target.1, spectrum.scan.scan_number
+    )
+}
+
+/// 导出MGF时的TITLE生成方式
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TitleOptions<'a> {
+    /// 标题模板，None时使用[`target_title`]；模板中的`{target}`为目标序号
+    pub formatter: Option<&'a SpectrumTitleFormatter>,
+    /// 来源文件，用于`{file}`占位符
+    pub file: &'a str,
+}
+
+impl TitleOptions<'_> {
+    fn title(&self, target_index: usize, target: (f64, RetentionTime), spectrum: &Spectrum) -> String {
+        match self.formatter {
+            Some(formatter) => formatter.format(
+                spectrum,
+                &TitleContext { file: self.file, native_id: "", target: Some(target_index) },
+            ),
+            None => target_title(target_index, target, spectrum),
+        }
+    }
+}
+
+/// 将提取结果写为一个合并的MGF文件，返回写出的谱图数
+pub fn write_combined_mgf(
+    path: impl AsRef<Path>,
+    targets: &[(f64, RetentionTime)],
+    matches: &[Vec<Spectrum>],
+    titles: &TitleOptions,
+) -> ParseResult<usize> {
+    let mut writer = MGFWriter::create(path)?;
+    for (target_index, (&target, spectra)) in targets.iter().zip(matches).enumerate() {
+        for spectrum in spectra {
+            writer.write_spectrum(&titles.title(target_index, target, spectrum), spectrum)?;
+        }
+    }
+    let count = writer.spectrum_count();
+    writer.finish()?;
+    Ok(count)
+}
+
+/// 在目录中为每个有命中的目标写一个`target_<序号>.mgf`，返回写出的文件路径
+pub fn write_per_target_mgf(
+    dir: impl AsRef<Path>,
+    targets: &[(f64, RetentionTime)],
+    matches: &[Vec<Spectrum>],
+    titles: &TitleOptions,
+) -> ParseResult<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(extended_length_path(dir))
+        .map_err(|source| ParseError::File { path: dir.to_path_buf(), source })?;
+    let mut paths = Vec::new();
+    for (target_index, (&target, spectra)) in targets.iter().zip(matches).enumerate() {
+        if spectra.is_empty() {
+            continue;
+        }
+        let path = dir.join(format!("target_{}.mgf", target_index));
+        let mut writer = MGFWriter::create(&path)?;
+        for spectrum in spectra {
+            writer.write_spectrum(&titles.title(target_index, target, spectrum), spectrum)?;
+        }
+        writer.finish()?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Python可用的靶向MS2提取器
+#[cfg(feature = "python")]
+#[pyclass]
+pub struct TargetedExtractor;
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl TargetedExtractor {
+    /// 提取每个(mz, rt)目标的MS2谱图，返回以目标元组为键、MSObject列表为值的字典
+    ///
+    /// `source`可以是MZMLObject、MSObject列表或mzML文件路径（str或pathlib.Path，流式读取）。
+    /// 给定`mgf_path`时同时写出MGF：默认写一个合并文件，`per_target=True`时
+    /// `mgf_path`为目录，每个有命中的目标写一个文件。`title_format`为TITLE模板
+    /// （见`format_spectrum_title`，另有`{target}`表示目标序号）。
+    #[staticmethod]
+    #[pyo3(signature = (source, targets, ppm=10.0, rt_window=30.0, mgf_path=None, per_target=false, title_format=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn extract<'py>(
+        py: Python<'py>,
+        source: &Bound<'py, PyAny>,
+        targets: Vec<(f64, f64)>,
+        ppm: f64,
+        rt_window: f64,
+        mgf_path: Option<PathBuf>,
+        per_target: bool,
+        title_format: Option<&str>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let params = TargetedParams { ppm, rt_window };
+        let source_path = source.extract::<PathBuf>().ok();
+        let matches = if let Some(path) = &source_path {
+            extract_from_file(&MZMLParser::new(), path, &targets, &params)
+                .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?
+        } else {
+            let spectra: Vec<Spectrum> = if let Ok(mzml_object) = source.downcast::<MZMLObject>() {
+                mzml_object.borrow().spectra.to_vec()
+            } else {
+                source.extract::<Vec<MSObject>>()?.into_iter().map(|ms_object| ms_object.spectrum).collect()
+            };
+            match_targets(&spectra, &targets, &params)
+                .iter()
+                .map(|hits| hits.iter().map(|&i| spectra[i].clone()).collect())
+                .collect()
+        };
+
+        if let Some(path) = mgf_path {
+            let formatter = title_format
+                .map(SpectrumTitleFormatter::new)
+                .transpose()
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+            let file = source_path.as_deref().map(Path::to_string_lossy).unwrap_or_default();
+            let titles = TitleOptions { formatter: formatter.as_ref(), file: &file };
+            let written = if per_target {
+                write_per_target_mgf(&path, &targets, &matches, &titles).map(|_| ())
+            } else {
+                write_combined_mgf(&path, &targets, &matches, &titles).map(|_| ())
+            };
+            written.map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
+        }
+
+        let result = PyDict::new(py);
+        for (target, spectra) in targets.into_iter().zip(matches) {
+            let list = PyList::empty(py);
+            for spectrum in spectra {
+                list.append(Py::new(py, MSObject { spectrum })?)?;
+            }
+            result.set_item(target, list)?;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::mzml::test_data::{build_mzml, write_temp_file, TestSpectrum};
+
+    const TARGETS: [(f64, f64); 3] = [(500.25, 60.0), (650.3, 120.0), (800.4, 200.0)];
+
+    fn test_spectra() -> Vec<TestSpectrum> {
+        vec![
+            TestSpectrum::new(1, 1, 55.0, vec![(500.25, 1e5)]),
+            TestSpectrum::new(2, 2, 58.0, vec![(150.0, 10.0)]).with_precursor(500.251, 2),
+            TestSpectrum::new(3, 2, 75.0, vec![(160.0, 20.0)]).with_precursor(500.249, 2),
+            // 保留时间超出窗口
+            TestSpectrum::new(4, 2, 100.0, vec![(170.0, 30.0)]).with_precursor(500.25, 2),
+            TestSpectrum::new(5, 2, 121.0, vec![(180.0, 40.0)]).with_precursor(650.301, 3),
+            // m/z超出容差
+            TestSpectrum::new(6, 2, 200.0, vec![(190.0, 50.0)]).with_precursor(800.5, 2),
+        ]
+    }
+
+    #[test]
+    fn test_extract_from_file() {
+        let file = write_temp_file(&build_mzml(&test_spectra()));
+        let params = TargetedParams { ppm: 10.0, rt_window: 30.0 };
+        let matches = extract_from_file(&MZMLParser::new(), file.path().to_str().unwrap(), &TARGETS, &params).unwrap();
+
+        let counts: Vec<usize> = matches.iter().map(Vec::len).collect();
+        assert_eq!(counts, vec![2, 1, 0]);
+        assert_eq!(matches[0][0].peaks(), vec![(150.0, 10.0)]);
+        assert_eq!(matches[0][1].peaks(), vec![(160.0, 20.0)]);
+        assert_eq!(matches[1][0].peaks(), vec![(180.0, 40.0)]);
+
+        // 内存路径与流式路径结果一致
+        let all: Vec<Spectrum> = matches.iter().flatten().cloned().collect();
+        assert_eq!(match_targets(&all, &TARGETS, &params), vec![vec![0, 1], vec![2], vec![]]);
+    }
+
+    #[test]
+    fn test_write_mgf() {
+        let file = write_temp_file(&build_mzml(&test_spectra()));
+        let matches =
+            extract_from_file(&MZMLParser::new(), file.path().to_str().unwrap(), &TARGETS, &TargetedParams::default()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        let combined = dir.path().join("all.mgf");
+        assert_eq!(write_combined_mgf(combined.to_str().unwrap(), &TARGETS, &matches, &TitleOptions::default()).unwrap(), 3);
+        let text = std::fs::read_to_string(&combined).unwrap();
+        assert_eq!(text.matches("BEGIN IONS").count(), 3);
+        assert_eq!(text.matches("TITLE=target_0 ").count(), 2);
+        assert_eq!(text.matches("TITLE=target_1 ").count(), 1);
+
+        let per_target_dir = dir.path().join("per_target");
+        let formatter = SpectrumTitleFormatter::new("t{target}.{file}.{scan}.{charge}").unwrap();
+        let titles = TitleOptions { formatter: Some(&formatter), file: "/data/run01.mzML" };
+        let paths = write_per_target_mgf(per_target_dir.to_str().unwrap(), &TARGETS, &matches, &titles).unwrap();
+        assert_eq!(paths.len(), 2);
+        assert!(paths[1].ends_with("target_1.mgf"));
+        assert!(!per_target_dir.join("target_2.mgf").exists());
+        let text = std::fs::read_to_string(&paths[1]).unwrap();
+        assert!(text.contains("TITLE=t1.run01.5.3\n"));
+    }
+}
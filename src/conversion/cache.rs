@@ -0,0 +1,351 @@
+//! 已解析运行的二进制缓存
+//!
+//! 反复解析大型mzML很慢，这里把解析得到的谱图写入紧凑的二进制文件（`<源文件名>.omsu`）。
+//! 文件以固定的头部开始：
+//!
+//! | 字节 | 内容 |
+//! |------|------|
+//! | 0..8 | 魔数`OMSURUN1` |
+//! | 8..12 | 格式版本 (u32, 小端) |
+//! | 12..20 | 谱图数量n (u64, 小端) |
+//! | 20..28 | 源文件大小 (u64, 小端)，未关联源文件时为0 |
+//! | 28..36 | 源文件修改时间 (自UNIX纪元的纳秒数, u64, 小端) |
+//! | 36..40 | 解析选项标志 (u32, 小端) |
+//! | 40..40+8n | 各谱图记录的起始偏移 (u64, 小端) |
+//!
+//! 每条谱图记录依次为：元数据长度 (u32) 与bincode编码的元数据（MS级别、扫描信息、前体离子、
+//! 额外信息）、峰数 (u64)，以及m/z和强度数组各自的字节数 (u64) 与数据。峰数组经[`Encoder`]
+//! 以64位小端浮点加zlib压缩，读入后与写出前逐位相同。
+//!
+//! 源文件的大小、修改时间或解析选项与头部记录不一致时缓存视为过期。
+
+use crate::conversion::encoding::{Decoder, Encoder};
+use crate::core::spectrum::{PrecursorInfo, ScanInfo, Spectrum};
+use crate::core::types::*;
+use crate::parsers::common::{BinaryDataArray, BinaryDataEncoding, CompressionType};
+use crate::utils::path::extended_length_path;
+use log::warn;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// 缓存文件的魔数
+pub const CACHE_MAGIC: [u8; 8] = *b"OMSURUN1";
+/// 当前的缓存文件格式版本
+pub const CACHE_FORMAT_VERSION: u32 = 1;
+/// 缓存文件的扩展名，追加在源文件名之后
+pub const CACHE_EXTENSION: &str = "omsu";
+/// 头部中偏移表之前的长度（字节）
+const HEADER_LEN: usize = 40;
+
+/// 缓存对应的源文件状态和解析选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStamp {
+    /// 源文件大小（字节）
+    pub source_size: u64,
+    /// 源文件修改时间（自UNIX纪元的纳秒数）
+    pub source_modified_ns: u64,
+    /// 影响解析结果的选项标志，由调用方定义
+    pub options: u32,
+}
+
+impl CacheStamp {
+    /// 读取源文件当前的大小和修改时间
+    pub fn of(source: impl AsRef<Path>, options: u32) -> CoreResult<Self> {
+        let source = source.as_ref();
+        let io_error = |source_error| CoreError::Io { path: source.to_path_buf(), source: source_error };
+        let metadata = std::fs::metadata(extended_length_path(source)).map_err(io_error)?;
+        let modified = metadata.modified().map_err(io_error)?;
+        let source_modified_ns = modified.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Ok(Self { source_size: metadata.len(), source_modified_ns, options })
+    }
+}
+
+/// 谱图的元数据，写出时借用、读入时持有
+#[derive(Serialize, Deserialize)]
+struct SpectrumMetadata<'a> {
+    level: MSLevel,
+    scan: Cow<'a, ScanInfo>,
+    precursor: Cow<'a, Option<Box<PrecursorInfo>>>,
+    additional_info: Cow<'a, SmallKeyValueList>,
+    allow_negative_intensities: bool,
+}
+
+/// 源文件对应的缓存文件路径：在完整文件名后追加`.omsu`
+pub fn cache_path(source: impl AsRef<Path>) -> PathBuf {
+    let mut path = source.as_ref().as_os_str().to_owned();
+    path.push(".");
+    path.push(CACHE_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// 写入不关联源文件的缓存，已存在的文件被覆盖
+pub fn save_run(spectra: &[Spectrum], path: impl AsRef<Path>) -> CoreResult<()> {
+    save_run_with_stamp(spectra, path, CacheStamp::default())
+}
+
+/// 写入缓存并在头部记录源文件状态
+pub fn save_run_with_stamp(spectra: &[Spectrum], path: impl AsRef<Path>, stamp: CacheStamp) -> CoreResult<()> {
+    let path = path.as_ref();
+    let records = spectra.par_iter().map(encode_record).collect::<CoreResult<Vec<_>>>()?;
+
+    let table_end = HEADER_LEN + 8 * records.len();
+    let mut data = Vec::with_capacity(table_end + records.iter().map(Vec::len).sum::<usize>());
+    data.extend_from_slice(&CACHE_MAGIC);
+    data.extend_from_slice(&CACHE_FORMAT_VERSION.to_le_bytes());
+    data.extend_from_slice(&(records.len() as u64).to_le_bytes());
+    data.extend_from_slice(&stamp.source_size.to_le_bytes());
+    data.extend_from_slice(&stamp.source_modified_ns.to_le_bytes());
+    data.extend_from_slice(&stamp.options.to_le_bytes());
+    let mut offset = table_end as u64;
+    for record in &records {
+        data.extend_from_slice(&offset.to_le_bytes());
+        offset += record.len() as u64;
+    }
+    for record in records {
+        data.extend_from_slice(&record);
+    }
+
+    std::fs::write(extended_length_path(path), data).map_err(|source| CoreError::Io { path: path.to_path_buf(), source })
+}
+
+/// 读入[`save_run`]写出的缓存
+///
+/// 魔数不符、版本不受支持、文件截断或记录损坏时返回[`CoreError::InvalidFormat`]。
+pub fn load_run(path: impl AsRef<Path>) -> CoreResult<Vec<Spectrum>> {
+    load_run_with_stamp(path).map(|(spectra, _)| spectra)
+}
+
+/// 读入缓存及头部记录的源文件状态
+pub fn load_run_with_stamp(path: impl AsRef<Path>) -> CoreResult<(Vec<Spectrum>, CacheStamp)> {
+    let path = path.as_ref();
+    let data = std::fs::read(extended_length_path(path)).map_err(|source| CoreError::Io { path: path.to_path_buf(), source })?;
+    let (stamp, offsets) = read_header(&data).map_err(|message| invalid(path, message))?;
+
+    let spectra = offsets
+        .par_iter()
+        .enumerate()
+        .map(|(index, &offset)| {
+            decode_record(&data, offset).map_err(|message| invalid(path, format!("spectrum {}: {}", index, message)))
+        })
+        .collect::<CoreResult<Vec<_>>>()?;
+    Ok((spectra, stamp))
+}
+
+/// 读入与源文件当前状态一致的缓存；缓存不存在、已过期或无法读取时返回None
+pub fn load_fresh_run(path: impl AsRef<Path>, stamp: CacheStamp) -> Option<Vec<Spectrum>> {
+    let path = path.as_ref();
+    if !extended_length_path(path).is_file() {
+        return None;
+    }
+    match load_run_with_stamp(path) {
+        Ok((spectra, cached)) if cached == stamp => Some(spectra),
+        Ok(_) => None,
+        Err(e) => {
+            warn!("Ignoring unreadable cache {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn invalid(path: &Path, message: String) -> CoreError {
+    CoreError::InvalidFormat(format!("{:?}: {}", path, message))
+}
+
+/// 把一张谱图编码为一条记录
+fn encode_record(spectrum: &Spectrum) -> CoreResult<Vec<u8>> {
+    let metadata = bincode::serialize(&SpectrumMetadata {
+        level: spectrum.level,
+        scan: Cow::Borrowed(&spectrum.scan),
+        precursor: Cow::Borrowed(&spectrum.precursor),
+        additional_info: Cow::Borrowed(&spectrum.additional_info),
+        allow_negative_intensities: spectrum.allow_negative_intensities,
+    })
+    .map_err(|e| CoreError::InvalidFormat(format!("Failed to serialize spectrum metadata: {}", e)))?;
+    let encoder = Encoder::new().with_encoding(BinaryDataEncoding::Float64Little).with_compression(Some(CompressionType::Zlib));
+    let mz = encoder.encode_mz_array(spectrum.mz_slice())?.data;
+    let intensity = encoder.encode_intensity_array(spectrum.intensity_slice())?.data;
+
+    let mut record = Vec::with_capacity(4 + metadata.len() + 24 + mz.len() + intensity.len());
+    record.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    record.extend_from_slice(&metadata);
+    record.extend_from_slice(&(spectrum.peak_count() as u64).to_le_bytes());
+    for array in [&mz, &intensity] {
+        record.extend_from_slice(&(array.len() as u64).to_le_bytes());
+        record.extend_from_slice(array);
+    }
+    Ok(record)
+}
+
+/// 按顺序读取字节的游标，越界时返回错误信息
+struct Cursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.position.checked_add(len).filter(|&end| end <= self.data.len());
+        let Some(end) = end else {
+            return Err(format!("truncated at byte {} (needed {} more bytes)", self.position, len));
+        };
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        usize::try_from(self.u64()?).map_err(|_| "length does not fit in memory".to_string())
+    }
+}
+
+/// 校验头部，返回源文件状态和各记录的偏移
+fn read_header(data: &[u8]) -> Result<(CacheStamp, Vec<usize>), String> {
+    let mut cursor = Cursor { data, position: 0 };
+    if cursor.take(8).map_err(|_| "file is too short to contain a cache header".to_string())? != CACHE_MAGIC {
+        return Err("not a run cache file (bad magic bytes)".to_string());
+    }
+    let version = cursor.u32()?;
+    if version != CACHE_FORMAT_VERSION {
+        return Err(format!("unsupported cache format version {} (expected {})", version, CACHE_FORMAT_VERSION));
+    }
+    let count = cursor.len()?;
+    let stamp = CacheStamp { source_size: cursor.u64()?, source_modified_ns: cursor.u64()?, options: cursor.u32()? };
+    if count > (data.len() - HEADER_LEN) / 8 {
+        return Err(format!("header records {} spectra but the offset table is truncated", count));
+    }
+    let offsets = (0..count).map(|_| cursor.len()).collect::<Result<Vec<_>, _>>()?;
+    Ok((stamp, offsets))
+}
+
+/// 解码从`offset`开始的一条记录
+fn decode_record(data: &[u8], offset: usize) -> Result<Spectrum, String> {
+    let mut cursor = Cursor { data, position: offset };
+    let metadata_len = cursor.u32()? as usize;
+    let metadata: SpectrumMetadata =
+        bincode::deserialize(cursor.take(metadata_len)?).map_err(|e| format!("corrupted metadata: {}", e))?;
+    let peak_count = cursor.len()?;
+    let mut arrays = Vec::with_capacity(2);
+    for _ in 0..2 {
+        let len = cursor.len()?;
+        let array = BinaryDataArray::new(BinaryDataEncoding::Float64Little, cursor.take(len)?.to_vec())
+            .with_compression(CompressionType::Zlib)
+            .with_expected_length(peak_count)
+            .with_strict(true);
+        arrays.push(Decoder::new().decode_mz_array(&array).map_err(|e| e.to_string())?);
+    }
+    let intensity = arrays.pop().unwrap_or_default();
+    let mz = arrays.pop().unwrap_or_default();
+
+    let mut spectrum = Spectrum::new(metadata.level).map_err(|e| e.to_string())?;
+    spectrum.scan = metadata.scan.into_owned();
+    spectrum.precursor = metadata.precursor.into_owned();
+    spectrum.additional_info = metadata.additional_info.into_owned();
+    spectrum.allow_negative_intensities = metadata.allow_negative_intensities;
+    spectrum.set_peak_arrays(mz, intensity).map_err(|e| e.to_string())?;
+    Ok(spectrum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::mzml::test_data::{build_mzml, write_temp_file, TestSpectrum};
+    use crate::parsers::mzml::MZMLParser;
+
+    fn run() -> Vec<Spectrum> {
+        let mzml = write_temp_file(&build_mzml(&[
+            TestSpectrum::new(1, 1, 10.0, vec![(400.123456789, 100.5), (500.0, 1e-300)]),
+            TestSpectrum::new(2, 2, 12.5, vec![(150.0, 50.0), (0.1 + 0.2, 7.0)]).with_precursor(500.0, 2),
+            TestSpectrum::new(3, 1, 14.0, vec![]),
+        ]));
+        let mut spectra = MZMLParser::new().parse(mzml.path()).unwrap();
+        spectra[1].add_additional_info("note", "cached").unwrap();
+        spectra[2].allow_negative_intensities = true;
+        spectra[2].add_peak(321.0, -4.25).unwrap();
+        spectra
+    }
+
+    #[test]
+    fn test_round_trip_is_bit_identical() {
+        let spectra = run();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.omsu");
+        save_run(&spectra, &path).unwrap();
+        let loaded = load_run(&path).unwrap();
+
+        assert_eq!(loaded.len(), spectra.len());
+        for (loaded, original) in loaded.iter().zip(&spectra) {
+            // bincode编码覆盖所有元数据，f64按位比较
+            assert_eq!(bincode::serialize(loaded).unwrap(), bincode::serialize(original).unwrap());
+            let bits = |values: &[f64]| values.iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(loaded.mz_slice()), bits(original.mz_slice()));
+            assert_eq!(bits(loaded.intensity_slice()), bits(original.intensity_slice()));
+        }
+
+        save_run(&[], &path).unwrap();
+        assert!(load_run(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stale_cache_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("run.mzML");
+        std::fs::write(&source, "original").unwrap();
+        let cache = cache_path(&source);
+        assert_eq!(cache, dir.path().join("run.mzML.omsu"));
+        assert!(load_fresh_run(&cache, CacheStamp::of(&source, 0).unwrap()).is_none());
+
+        let spectra = run();
+        save_run_with_stamp(&spectra, &cache, CacheStamp::of(&source, 0).unwrap()).unwrap();
+        assert_eq!(load_fresh_run(&cache, CacheStamp::of(&source, 0).unwrap()).unwrap().len(), spectra.len());
+        // 解析选项不同
+        assert!(load_fresh_run(&cache, CacheStamp::of(&source, 1).unwrap()).is_none());
+
+        // 大小不变、只有修改时间变化的源文件也视为过期
+        let file = std::fs::File::options().write(true).open(&source).unwrap();
+        file.set_modified(UNIX_EPOCH + std::time::Duration::from_secs(1_000_000)).unwrap();
+        drop(file);
+        assert!(load_fresh_run(&cache, CacheStamp::of(&source, 0).unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_load_rejects_bad_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.omsu");
+        save_run(&run(), &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+
+        let load = |data: &[u8]| {
+            std::fs::write(&path, data).unwrap();
+            match load_run(&path) {
+                Err(CoreError::InvalidFormat(message)) => message,
+                other => panic!("expected InvalidFormat, got {:?}", other.map(|spectra| spectra.len())),
+            }
+        };
+        assert!(load(&bytes[..bytes.len() - 3]).contains("spectrum 2: truncated"));
+        assert!(load(&bytes[..6]).contains("too short"));
+        assert!(load(&bytes[..HEADER_LEN + 4]).contains("offset table is truncated"));
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(load(&wrong_magic).contains("magic"));
+        let mut future = bytes.clone();
+        future[8..12].copy_from_slice(&(CACHE_FORMAT_VERSION + 1).to_le_bytes());
+        assert!(load(&future).contains(&format!("version {}", CACHE_FORMAT_VERSION + 1)));
+
+        // 损坏的缓存不会被当作有效缓存
+        std::fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        assert!(load_fresh_run(&path, CacheStamp::default()).is_none());
+        assert!(matches!(load_run(dir.path().join("missing.omsu")), Err(CoreError::Io { .. })));
+    }
+}
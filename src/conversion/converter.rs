@@ -1,16 +1,91 @@
 //! 主转换器
 //!
 //! 提供不同质谱数据格式之间的高性能转换功能
+//!
+//! 转换为"dict"时的格式：
+//! - `ms_level`、`peaks`：MS级别和(mz, intensity)峰列表
+//! - `retention_time`、`drift_time`、`scan_number`：常用扫描字段的快捷键，转换回来时覆盖`scan`中的同名值
+//! - `precursor`：前体离子dict（键同`Precursor`的属性），无前体离子时为None
+//! - `precursor_mz`、`precursor_charge`：前体离子m/z和电荷的快捷键，无前体离子时为None；
+//!   转换回来时覆盖`precursor`中的同名值，只给出`precursor_mz`也会创建前体离子
+//! - `scan`：扫描dict（键同`Scan`的属性，含`polarity`和`additional_info`）
+//! - `additional_info`：谱图级额外信息，str到str的dict
+//! - `total_ion_current`、`base_peak_mz`、`base_peak_intensity`：计算属性，转换回来时忽略
+//!
+//! 转换为"numpy"时为`mz_array`、`intensity_array`两个numpy数组加上`ms_level`和`retention_time`；
+//! 转换为"records"时为每个峰一行的dict列表（键见[`RECORD_COLUMNS`]），可直接用于`pandas.DataFrame`。
+//! 整个运行用[`PeakTable`]展开为同样列的长表。
+//!
+//! 前体离子和扫描dict的读写与`MSObject`构造函数共用同一套字段定义，
+//! 保证dict往返转换不丢失信息。读取时缺少的键取默认值，值为None的快捷键视为缺少，
+//! 类型不符的键抛出带有字段名的TypeError。
 
-use crate::core::{Spectrum};
-use crate::core::types::*;
+use crate::core::spectrum::Spectrum;
+use crate::core::types::{MSLevel, RetentionTime, ScanNumber};
+
+#[cfg(feature = "python")]
+use crate::analysis::{blank_subtraction, duplicates};
+#[cfg(feature = "python")]
+use crate::core::types::{Charge, CoreError};
 
 #[cfg(feature = "python")]
-use crate::core::MSObject;
+use crate::core::ms_object::{
+    extract_field, extract_peak, field, info_to_dict, parse_info_from_python, parse_precursor_from_python,
+    parse_scan_from_python, precursor_to_dict, scan_to_dict, MSObject,
+};
+#[cfg(feature = "python")]
+use crate::core::validation::{validate_run, ValidationOptions};
+#[cfg(feature = "python")]
+use crate::xic::extractor::with_py_spectra;
+#[cfg(feature = "python")]
+use crate::core::vectorize::py_binning;
+#[cfg(feature = "python")]
+use numpy::ndarray::Array2;
+#[cfg(feature = "python")]
+use numpy::{IntoPyArray, PyArray2};
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
 #[cfg(feature = "python")]
-use pyo3::types::{PyAny, PyDict, PyList, PyTuple};
+use pyo3::types::{PyDict, PyList};
+
+/// 长表的列名：扫描编号、保留时间、m/z、强度、MS级别
+pub const RECORD_COLUMNS: [&str; 5] = ["scan_number", "rt", "mz", "intensity", "level"];
+
+/// 每个峰一行的长表，各列等长，列名见[`RECORD_COLUMNS`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeakTable {
+    pub scan_number: Vec<ScanNumber>,
+    pub retention_time: Vec<RetentionTime>,
+    pub mz: Vec<f64>,
+    pub intensity: Vec<f64>,
+    pub level: Vec<MSLevel>,
+}
+
+impl PeakTable {
+    /// 按谱图顺序展开所有峰
+    pub fn from_spectra<'a>(spectra: impl IntoIterator<Item = &'a Spectrum>) -> Self {
+        let mut table = Self::default();
+        for spectrum in spectra {
+            let peaks = spectrum.peak_count();
+            table.scan_number.extend(std::iter::repeat_n(spectrum.scan.scan_number, peaks));
+            table.retention_time.extend(std::iter::repeat_n(spectrum.scan.retention_time, peaks));
+            table.level.extend(std::iter::repeat_n(spectrum.level, peaks));
+            table.mz.extend_from_slice(spectrum.mz_slice());
+            table.intensity.extend_from_slice(spectrum.intensity_slice());
+        }
+        table
+    }
+
+    /// 行数
+    pub fn len(&self) -> usize {
+        self.mz.len()
+    }
+
+    /// 是否没有行
+    pub fn is_empty(&self) -> bool {
+        self.mz.is_empty()
+    }
+}
 
 /// Python兼容的谱图转换器
 #[cfg(feature = "python")]
@@ -22,23 +97,23 @@ pub struct SpectraConverter;
 impl SpectraConverter {
     /// 将任意谱图格式转换为MSObject
     #[staticmethod]
-    fn to_msobject(py: Python, spectrum: &PyAny) -> PyResult<Py<PyAny>> {
+    fn to_msobject(py: Python, spectrum: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
         // 尝试检测输入类型并转换
         if let Ok(ms_object) = spectrum.extract::<MSObject>() {
             // 已经是MSObject，直接返回
-            return Ok(ms_object.into_py(py));
+            return Ok(Py::new(py, ms_object)?.into_any());
         }
 
         // 尝试从字典转换
         if let Ok(dict) = spectrum.downcast::<PyDict>() {
             let ms_object = Self::dict_to_msobject(dict)?;
-            return Ok(ms_object.into_py(py));
+            return Ok(Py::new(py, ms_object)?.into_any());
         }
 
         // 尝试从元组列表转换
         if let Ok(list) = spectrum.downcast::<PyList>() {
             let ms_object = Self::list_to_msobject(list)?;
-            return Ok(ms_object.into_py(py));
+            return Ok(Py::new(py, ms_object)?.into_any());
         }
 
         Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
@@ -48,7 +123,7 @@ impl SpectraConverter {
 
     /// 将MSObject转换为指定类型的谱图
     #[staticmethod]
-    fn to_spectra(py: Python, ms_object: &PyAny, spectra_type: &str) -> PyResult<Py<PyAny>> {
+    fn to_spectra(py: Python, ms_object: &Bound<'_, PyAny>, spectra_type: &str) -> PyResult<Py<PyAny>> {
         let ms_obj = ms_object.extract::<MSObject>()?;
 
         match spectra_type.to_lowercase().as_str() {
@@ -61,37 +136,148 @@ impl SpectraConverter {
                 Ok(list.into())
             }
             "numpy" => {
-                // 转换为numpy数组格式
                 let dict = Self::msobject_to_numpy_dict(&ms_obj, py)?;
                 Ok(dict.into())
             }
+            "records" => {
+                let records = Self::msobject_to_records(&ms_obj, py)?;
+                Ok(records.into())
+            }
             _ => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
                 format!("Unsupported spectra type: {}", spectra_type)
             ))
         }
     }
 
+    /// 把整个运行展开为每个峰一行的长表
+    ///
+    /// `ms_objects`为MSObject列表或MZMLObject。返回列名到numpy数组的dict（列见`to_spectra`的"records"），
+    /// 可直接用于`pandas.DataFrame(table)`；scan_number为uint32，level为uint8，其余为float64。
+    #[staticmethod]
+    fn batch_to_records<'py>(py: Python<'py>, ms_objects: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyDict>> {
+        let table = with_py_spectra(ms_objects, |spectra| PeakTable::from_spectra(spectra))?;
+        let [scan_number, rt, mz, intensity, level] = RECORD_COLUMNS;
+        let dict = PyDict::new(py);
+        dict.set_item(scan_number, table.scan_number.into_pyarray(py))?;
+        dict.set_item(rt, table.retention_time.into_pyarray(py))?;
+        dict.set_item(mz, table.mz.into_pyarray(py))?;
+        dict.set_item(intensity, table.intensity.into_pyarray(py))?;
+        dict.set_item(level, table.level.into_pyarray(py))?;
+        Ok(dict)
+    }
+
+    /// 把整个运行（MSObject列表或MZMLObject）分箱为(谱图数, 箱数)的numpy矩阵，每张谱图一行
+    ///
+    /// 参数与`MSObject.to_vector`相同；各行并行计算，计算期间释放GIL。
+    #[staticmethod]
+    #[pyo3(signature = (ms_objects, min_mz=100.0, max_mz=1700.0, bin=1.0, transform=Some("sqrt"), aggregation="sum", normalize=true))]
+    #[allow(clippy::too_many_arguments)]
+    fn to_matrix<'py>(
+        py: Python<'py>,
+        ms_objects: &Bound<'py, PyAny>,
+        min_mz: f64,
+        max_mz: f64,
+        bin: f64,
+        transform: Option<&str>,
+        aggregation: &str,
+        normalize: bool,
+    ) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let binning = py_binning(min_mz, max_mz, bin, transform, aggregation, normalize)?;
+        let (rows, matrix) = with_py_spectra(ms_objects, |spectra| {
+            let spectra: Vec<&Spectrum> = spectra.collect();
+            (spectra.len(), py.allow_threads(|| binning.matrix(&spectra)))
+        })?;
+        Array2::from_shape_vec((rows, binning.bin_count()), matrix)
+            .map(|matrix| matrix.into_pyarray(py))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
     /// 批量转换多个谱图
     #[staticmethod]
-    fn batch_convert(py: Python, spectra: Vec<&PyAny>, target_format: &str) -> PyResult<Py<PyList>> {
+    fn batch_convert(py: Python, spectra: Vec<Bound<'_, PyAny>>, target_format: &str) -> PyResult<Py<PyList>> {
         let results = PyList::empty(py);
 
         for spectrum in spectra {
             let converted = if target_format == "msobject" {
-                Self::to_msobject(py, spectrum)?
+                Self::to_msobject(py, &spectrum)?
             } else {
-                let ms_object = Self::to_msobject(py, spectrum)?;
-                Self::to_spectra(py, ms_object.into_ref(py), target_format)?
+                let ms_object = Self::to_msobject(py, &spectrum)?;
+                Self::to_spectra(py, ms_object.bind(py), target_format)?
             };
             results.append(converted)?;
         }
 
-        Ok(results.into())
+        Ok(results.unbind())
+    }
+
+    /// 查找重复谱图，返回每组重复谱图在输入中的下标
+    ///
+    /// 先按指纹分组，再用余弦相似度确认，避免哈希碰撞造成误判。
+    #[staticmethod]
+    #[pyo3(signature = (ms_objects, mz_precision_da=0.01, top_n=30, min_cosine=0.99))]
+    fn find_duplicates(
+        ms_objects: Vec<PyRef<'_, MSObject>>,
+        mz_precision_da: f64,
+        top_n: usize,
+        min_cosine: f64,
+    ) -> PyResult<Vec<Vec<usize>>> {
+        duplicates::find_duplicates(ms_objects.iter().map(|ms_object| &ms_object.spectrum), mz_precision_da, top_n, min_cosine)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 空白扣除：去除样品MS1谱图中在空白运行里也存在的峰
+    ///
+    /// 返回(扣除后的样品MSObject列表, 每张谱图去除的峰数)，输入对象不被修改。
+    #[staticmethod]
+    #[pyo3(signature = (sample_msobjects, blank_msobjects, ppm=10.0, rt_tol=30.0, ratio=0.5))]
+    fn blank_subtract(
+        sample_msobjects: Vec<MSObject>,
+        blank_msobjects: Vec<PyRef<'_, MSObject>>,
+        ppm: f64,
+        rt_tol: f64,
+        ratio: f64,
+    ) -> PyResult<(Vec<MSObject>, Vec<usize>)> {
+        let mut sample: Vec<Spectrum> = sample_msobjects.into_iter().map(|ms_object| ms_object.spectrum).collect();
+        let blank: Vec<Spectrum> = blank_msobjects.iter().map(|ms_object| ms_object.spectrum.clone()).collect();
+        let removed = blank_subtraction::subtract_run(&mut sample, &blank, ppm, rt_tol, ratio)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok((sample.into_iter().map(|spectrum| MSObject { spectrum }).collect(), removed))
+    }
+
+    /// 按离子注入时间归一化整个运行的强度
+    ///
+    /// 返回(归一化后的MSObject列表, 因缺少注入时间而跳过的谱图数)；跳过的谱图原样返回。
+    /// 输入中已归一化过的谱图会引发ValueError。
+    #[staticmethod]
+    #[pyo3(signature = (ms_objects, reference_ms=100.0))]
+    fn normalize_injection_time(ms_objects: Vec<MSObject>, reference_ms: f64) -> PyResult<(Vec<MSObject>, usize)> {
+        let mut skipped = 0;
+        let mut normalized = Vec::with_capacity(ms_objects.len());
+        for mut ms_object in ms_objects {
+            match ms_object.spectrum.normalize_by_injection_time(reference_ms) {
+                Ok(_) => {}
+                Err(CoreError::KeyNotFound { .. }) => skipped += 1,
+                Err(e) => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())),
+            }
+            normalized.push(ms_object);
+        }
+        Ok((normalized, skipped))
     }
 
     /// 验证谱图数据完整性
+    ///
+    /// `valid`为按选项验证的结果，`issues`为发现的问题说明列表；默认选项与`MSObject.validate`一致。
     #[staticmethod]
-    fn validate_spectrum(py: Python, spectrum: &PyAny) -> PyResult<Py<PyDict>> {
+    #[pyo3(signature = (spectrum, allow_empty_peaks=false, require_sorted=false, max_mz=None, require_precursor_for_ms2=false))]
+    fn validate_spectrum(
+        py: Python,
+        spectrum: &Bound<'_, PyAny>,
+        allow_empty_peaks: bool,
+        require_sorted: bool,
+        max_mz: Option<f64>,
+        require_precursor_for_ms2: bool,
+    ) -> PyResult<Py<PyDict>> {
+        let options = ValidationOptions { allow_empty_peaks, require_sorted, max_mz, require_precursor_for_ms2, check_monotonic_rt: false };
         let result = PyDict::new(py);
 
         // 尝试转换为MSObject进行验证
@@ -100,11 +286,15 @@ impl SpectraConverter {
                 let spectrum = &ms_object.spectrum;
 
                 // 基本验证
-                result.set_item("valid", true)?;
-                result.set_item("peak_count", spectrum.peaks.len())?;
+                let issues = spectrum.validation_issues(&options);
+                result.set_item("valid", issues.is_empty())?;
+                result.set_item("issues", issues.iter().map(|issue| issue.to_string()).collect::<Vec<_>>())?;
+                result.set_item("peak_count", spectrum.peak_count())?;
                 result.set_item("ms_level", spectrum.level)?;
                 result.set_item("has_retention_time", spectrum.scan.retention_time > 0.0)?;
                 result.set_item("has_drift_time", spectrum.scan.drift_time > 0.0)?;
+                result.set_item("has_precursor", spectrum.has_precursor())?;
+                result.set_item("precursor_mz", spectrum.precursor.as_ref().map(|precursor| precursor.mz))?;
 
                 // 数据质量检查
                 let total_intensity = spectrum.total_ion_current();
@@ -114,7 +304,7 @@ impl SpectraConverter {
                 result.set_item("base_peak_intensity", base_peak.map_or(0.0, |(_, intensity)| intensity))?;
 
                 // 检查m/z和强度的合理性
-                let (valid_mz_count, valid_intensity_count) = Self::validate_peak_data(&spectrum.peaks);
+                let (valid_mz_count, valid_intensity_count) = Self::validate_peak_data(spectrum.mz_slice(), spectrum.intensity_slice());
                 result.set_item("valid_mz_count", valid_mz_count)?;
                 result.set_item("valid_intensity_count", valid_intensity_count)?;
             }
@@ -124,77 +314,118 @@ impl SpectraConverter {
             }
         }
 
-        Ok(result.into())
+        Ok(result.unbind())
+    }
+
+    /// 验证整个运行（MSObject列表或MZMLObject），统计所有问题而不是在第一个问题处失败
+    ///
+    /// 返回`valid`、`spectrum_count`、`invalid_spectrum_count`、按类别统计的`issue_counts`，
+    /// 以及`issues`：(谱图下标, 类别, 说明)列表。
+    #[staticmethod]
+    #[pyo3(signature = (
+        ms_objects,
+        allow_empty_peaks=false,
+        require_sorted=false,
+        max_mz=None,
+        require_precursor_for_ms2=false,
+        check_monotonic_rt=false
+    ))]
+    fn validate_run(
+        py: Python,
+        ms_objects: &Bound<'_, PyAny>,
+        allow_empty_peaks: bool,
+        require_sorted: bool,
+        max_mz: Option<f64>,
+        require_precursor_for_ms2: bool,
+        check_monotonic_rt: bool,
+    ) -> PyResult<Py<PyDict>> {
+        let options = ValidationOptions { allow_empty_peaks, require_sorted, max_mz, require_precursor_for_ms2, check_monotonic_rt };
+        let report = with_py_spectra(ms_objects, |spectra| validate_run(spectra, &options))?;
+
+        let result = PyDict::new(py);
+        result.set_item("valid", report.is_valid())?;
+        result.set_item("spectrum_count", report.spectrum_count)?;
+        result.set_item("invalid_spectrum_count", report.invalid_spectrum_count)?;
+        result.set_item("issue_counts", report.counts())?;
+        let issues: Vec<(usize, &str, String)> =
+            report.issues.iter().map(|(index, issue)| (*index, issue.kind(), issue.to_string())).collect();
+        result.set_item("issues", issues)?;
+        Ok(result.unbind())
     }
 }
 
 #[cfg(feature = "python")]
 impl SpectraConverter {
-    /// 从字典创建MSObject
-    fn dict_to_msobject(dict: &PyDict) -> PyResult<MSObject> {
-        let mut spectrum = Spectrum::ms1().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
-        })?;
+    /// 从字典创建MSObject，字段见模块文档
+    fn dict_to_msobject(dict: &Bound<'_, PyDict>) -> PyResult<MSObject> {
+        let value_error = |e: CoreError| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string());
+        let dict = dict.as_any();
 
-        // 设置基本属性
-        if let Ok(ms_level) = dict.get_item("ms_level") {
-            if let Ok(level) = ms_level.extract::<u8>() {
-                spectrum.level = level;
-            }
+        let level = extract_field(dict, "spectrum", "ms_level")?.unwrap_or(1);
+        let mut spectrum = Spectrum::new(level).map_err(value_error)?;
+
+        if let Some(scan) = field(dict, "scan")?.filter(|scan| !scan.is_none()) {
+            spectrum.scan = parse_scan_from_python(&scan)?;
+        }
+        if let Some(precursor) = field(dict, "precursor")?.filter(|precursor| !precursor.is_none()) {
+            spectrum.set_precursor(parse_precursor_from_python(&precursor)?);
+        }
+        if let Some(additional_info) = field(dict, "additional_info")? {
+            spectrum.additional_info = parse_info_from_python(&additional_info)?;
         }
 
-        if let Ok(rt) = dict.get_item("retention_time") {
-            if let Ok(retention_time) = rt.extract::<f64>() {
-                spectrum.set_retention_time(retention_time).map_err(|e| {
-                    PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
-                })?;
-            }
+        if let Some(scan_number) = extract_field(dict, "spectrum", "scan_number")? {
+            spectrum.set_scan_number(scan_number);
+        }
+        if let Some(retention_time) = extract_field(dict, "spectrum", "retention_time")? {
+            spectrum.set_retention_time(retention_time).map_err(value_error)?;
+        }
+        if let Some(drift_time) = extract_field(dict, "spectrum", "drift_time")? {
+            spectrum.set_drift_time(drift_time).map_err(value_error)?;
         }
 
-        if let Ok(dt) = dict.get_item("drift_time") {
-            if let Ok(drift_time) = dt.extract::<f64>() {
-                spectrum.set_drift_time(drift_time).map_err(|e| {
-                    PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
-                })?;
+        let precursor_mz = extract_field::<Option<f64>>(dict, "spectrum", "precursor_mz")?.flatten();
+        let precursor_charge = extract_field::<Option<Charge>>(dict, "spectrum", "precursor_charge")?.flatten();
+        if precursor_mz.is_some() || precursor_charge.is_some() {
+            if spectrum.precursor.is_none() && precursor_mz.is_none() {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                    "precursor_charge requires precursor_mz or precursor",
+                ));
+            }
+            let mut precursor = spectrum.precursor.as_deref().cloned().unwrap_or_default();
+            if let Some(mz) = precursor_mz {
+                precursor.mz = mz;
             }
+            if let Some(charge) = precursor_charge {
+                precursor.charge = charge;
+            }
+            spectrum.set_precursor(precursor);
         }
 
-        // 添加峰数据
-        if let Ok(peaks) = dict.get_item("peaks") {
-            if let Ok(peak_list) = peaks.downcast::<PyList>() {
-                for peak in peak_list.iter() {
-                    if let Ok(peak_tuple) = peak.downcast::<PyTuple>() {
-                        if peak_tuple.len() >= 2 {
-                            let mz = peak_tuple.get_item(0)?.extract::<f64>()?;
-                            let intensity = peak_tuple.get_item(1)?.extract::<f64>()?;
-                            spectrum.add_peak(mz, intensity).map_err(|e| {
-                                PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
-                            })?;
-                        }
-                    }
-                }
+        if let Some(peaks) = field(dict, "peaks")? {
+            let items = peaks.try_iter().map_err(|_| {
+                PyErr::new::<pyo3::exceptions::PyTypeError, _>("spectrum.peaks must be a list of (mz, intensity) tuples")
+            })?;
+            for item in items {
+                let (mz, intensity) = extract_peak(&item?)?;
+                spectrum.add_peak(mz, intensity).map_err(value_error)?;
             }
         }
 
         Ok(MSObject { spectrum })
     }
 
-    /// 从列表创建MSObject
-    fn list_to_msobject(list: &PyList) -> PyResult<MSObject> {
+    /// 从(mz, intensity)列表创建MS1的MSObject
+    fn list_to_msobject(list: &Bound<'_, PyList>) -> PyResult<MSObject> {
         let mut spectrum = Spectrum::ms1().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
         })?;
 
         for item in list.iter() {
-            if let Ok(peak_tuple) = item.downcast::<PyTuple>() {
-                if peak_tuple.len() >= 2 {
-                    let mz = peak_tuple.get_item(0)?.extract::<f64>()?;
-                    let intensity = peak_tuple.get_item(1)?.extract::<f64>()?;
-                    spectrum.add_peak(mz, intensity).map_err(|e| {
-                        PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
-                    })?;
-                }
-            }
+            let (mz, intensity) = extract_peak(&item)?;
+            spectrum.add_peak(mz, intensity).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+            })?;
         }
 
         Ok(MSObject { spectrum })
@@ -209,10 +440,19 @@ impl SpectraConverter {
         dict.set_item("retention_time", spectrum.scan.retention_time)?;
         dict.set_item("drift_time", spectrum.scan.drift_time)?;
         dict.set_item("scan_number", spectrum.scan.scan_number)?;
+        let precursor = spectrum.precursor.as_deref();
+        match precursor {
+            Some(precursor) => dict.set_item("precursor", precursor_to_dict(py, precursor)?)?,
+            None => dict.set_item("precursor", py.None())?,
+        }
+        dict.set_item("precursor_mz", precursor.map(|precursor| precursor.mz))?;
+        dict.set_item("precursor_charge", precursor.map(|precursor| precursor.charge))?;
+        dict.set_item("scan", scan_to_dict(py, &spectrum.scan)?)?;
+        dict.set_item("additional_info", info_to_dict(py, &spectrum.additional_info)?)?;
 
         // 转换峰数据
         let peaks_list = PyList::empty(py);
-        for &(mz, intensity) in &spectrum.peaks {
+        for (mz, intensity) in spectrum.peaks_iter() {
             peaks_list.append((mz, intensity))?;
         }
         dict.set_item("peaks", peaks_list)?;
@@ -224,56 +464,54 @@ impl SpectraConverter {
             dict.set_item("base_peak_intensity", base_intensity)?;
         }
 
-        Ok(dict)
+        Ok(dict.unbind())
     }
 
     /// 将MSObject转换为列表
     fn msobject_to_list(ms_object: &MSObject, py: Python) -> PyResult<Py<PyList>> {
         let list = PyList::empty(py);
-        for &(mz, intensity) in &ms_object.spectrum.peaks {
+        for (mz, intensity) in ms_object.spectrum.peaks_iter() {
             list.append((mz, intensity))?;
         }
-        Ok(list)
+        Ok(list.unbind())
     }
 
-    /// 将MSObject转换为numpy兼容格式
+    /// 将MSObject转换为numpy数组格式
     fn msobject_to_numpy_dict(ms_object: &MSObject, py: Python) -> PyResult<Py<PyDict>> {
         let dict = PyDict::new(py);
         let spectrum = &ms_object.spectrum;
 
-        // 创建m/z和强度数组
-        let mz_array = PyList::empty(py);
-        let intensity_array = PyList::empty(py);
-
-        for &(mz, intensity) in &spectrum.peaks {
-            mz_array.append(mz)?;
-            intensity_array.append(intensity)?;
-        }
-
-        dict.set_item("mz_array", mz_array)?;
-        dict.set_item("intensity_array", intensity_array)?;
+        dict.set_item("mz_array", spectrum.mz_slice().to_vec().into_pyarray(py))?;
+        dict.set_item("intensity_array", spectrum.intensity_slice().to_vec().into_pyarray(py))?;
         dict.set_item("ms_level", spectrum.level)?;
         dict.set_item("retention_time", spectrum.scan.retention_time)?;
 
-        Ok(dict)
+        Ok(dict.unbind())
     }
 
-    /// 验证峰数据质量
-    fn validate_peak_data(peaks: &[(f64, f64)]) -> (usize, usize) {
-        let mut valid_mz = 0;
-        let mut valid_intensity = 0;
-
-        for &(mz, intensity) in peaks {
-            // 检查m/z是否合理
-            if mz > 0.0 && mz.is_finite() && !mz.is_nan() {
-                valid_mz += 1;
-            }
-
-            // 检查强度是否合理
-            if intensity >= 0.0 && intensity.is_finite() && !intensity.is_nan() {
-                valid_intensity += 1;
-            }
+    /// 将MSObject转换为每个峰一行的dict列表
+    fn msobject_to_records(ms_object: &MSObject, py: Python) -> PyResult<Py<PyList>> {
+        let spectrum = &ms_object.spectrum;
+        let [scan_number, rt, mz, intensity, level] = RECORD_COLUMNS;
+        let records = PyList::empty(py);
+        for (peak_mz, peak_intensity) in spectrum.peaks_iter() {
+            let record = PyDict::new(py);
+            record.set_item(scan_number, spectrum.scan.scan_number)?;
+            record.set_item(rt, spectrum.scan.retention_time)?;
+            record.set_item(mz, peak_mz)?;
+            record.set_item(intensity, peak_intensity)?;
+            record.set_item(level, spectrum.level)?;
+            records.append(record)?;
         }
+        Ok(records.unbind())
+    }
+
+    /// 验证峰数据质量
+    fn validate_peak_data(mz: &[f64], intensity: &[f64]) -> (usize, usize) {
+        // 检查m/z是否合理
+        let valid_mz = mz.iter().filter(|mz| **mz > 0.0 && mz.is_finite()).count();
+        // 检查强度是否合理
+        let valid_intensity = intensity.iter().filter(|intensity| **intensity >= 0.0 && intensity.is_finite()).count();
 
         (valid_mz, valid_intensity)
     }
@@ -283,7 +521,8 @@ impl SpectraConverter {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::spectrum::Spectrum;
+    use crate::core::spectrum::{PrecursorInfo, Spectrum};
+    use crate::core::types::{KeyValue, Polarity};
 
     #[test]
     fn test_basic_conversion() {
@@ -294,24 +533,123 @@ mod tests {
         let ms_object = MSObject { spectrum };
 
         // 测试转换（这里需要Python环境，所以只能测试基础逻辑）
-        assert_eq!(ms_object.spectrum.peaks.len(), 2);
+        assert_eq!(ms_object.spectrum.peak_count(), 2);
+    }
+
+    #[test]
+    fn test_dict_round_trip_is_lossless() {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.add_peaks([(150.5, 10.0), (320.25, 42.5)]).unwrap();
+        spectrum.set_scan_number(1234);
+        spectrum.set_retention_time(615.5).unwrap();
+        spectrum.set_drift_time(12.75).unwrap();
+        spectrum.scan.scan_window = (100.0, 1500.0);
+        spectrum.scan.injection_time = Some(35.0);
+        spectrum.scan.polarity = Polarity::Negative;
+        spectrum.scan.additional_info.push(KeyValue::new("filter string", "FTMS - p NSI d Full ms2"));
+        spectrum.set_precursor(PrecursorInfo {
+            ref_scan_number: 1230,
+            mz: 652.8312,
+            intensity: 1.5e6,
+            charge: -2,
+            activation_method: "HCD".to_string(),
+            activation_energy: 28.0,
+            isolation_window: (651.8312, 653.8312),
+            isolation_target_mz: Some(652.8312),
+            collision_energy_ev: None,
+            normalized_collision_energy: Some(28.0),
+        });
+        for (key, value) in [("source", "run_a.mzML"), ("spectrum_id", "scan=1234"), ("note", "edited")] {
+            spectrum.add_additional_info(key, value).unwrap();
+        }
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let ms_object = MSObject { spectrum: spectrum.clone() };
+            let dict = SpectraConverter::msobject_to_dict(&ms_object, py).unwrap();
+            let restored = SpectraConverter::dict_to_msobject(dict.bind(py)).unwrap().spectrum;
+
+            assert_eq!(restored.level, spectrum.level);
+            assert_eq!(restored.peaks(), spectrum.peaks());
+            assert_eq!(restored.scan, spectrum.scan);
+            assert_eq!(restored.precursor, spectrum.precursor);
+            assert_eq!(restored.additional_info, spectrum.additional_info);
+            assert_eq!(dict.bind(py).get_item("precursor_mz").unwrap().unwrap().extract::<f64>().unwrap(), 652.8312);
+
+            // 没有前体离子时快捷键为None，转换回来仍没有前体离子
+            let ms1 = MSObject { spectrum: Spectrum::ms1().unwrap() };
+            let dict = SpectraConverter::msobject_to_dict(&ms1, py).unwrap();
+            assert!(dict.bind(py).get_item("precursor_charge").unwrap().unwrap().is_none());
+            assert!(SpectraConverter::dict_to_msobject(dict.bind(py)).unwrap().spectrum.precursor.is_none());
+        });
     }
 
     #[test]
-    fn test_advanced_converter() {
-        let converter = AdvancedConverter::new();
-        assert!(converter.simd_processor.supports_avx2() || converter.simd_processor.supports_sse41());
+    fn test_peak_table_flattens_run() {
+        let spectra: Vec<Spectrum> = [(1, 10.0, 1u8, 2usize), (2, 10.5, 2, 3), (3, 11.0, 1, 0)]
+            .into_iter()
+            .map(|(scan, rt, level, peaks)| {
+                let mut spectrum = Spectrum::new(level).unwrap();
+                spectrum.set_scan_number(scan);
+                spectrum.set_retention_time(rt).unwrap();
+                spectrum.add_peaks((0..peaks).map(|i| (100.0 + i as f64, 10.0 * i as f64))).unwrap();
+                spectrum
+            })
+            .collect();
+
+        let table = PeakTable::from_spectra(&spectra);
+        assert_eq!(table.len(), 5);
+        assert_eq!(table.scan_number, vec![1, 1, 2, 2, 2]);
+        assert_eq!(table.retention_time, vec![10.0, 10.0, 10.5, 10.5, 10.5]);
+        assert_eq!(table.level, vec![1, 1, 2, 2, 2]);
+        assert_eq!(table.mz, vec![100.0, 101.0, 100.0, 101.0, 102.0]);
+        assert_eq!(table.intensity[4], 20.0);
+        assert!(PeakTable::from_spectra(&[]).is_empty());
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let ms_object = MSObject { spectrum: spectra[1].clone() };
+            let records = SpectraConverter::msobject_to_records(&ms_object, py).unwrap();
+            let records: Vec<std::collections::HashMap<String, f64>> = records.bind(py).extract().unwrap();
+            assert_eq!(records.len(), 3);
+            assert_eq!(records[2]["mz"], 102.0);
+            assert_eq!((records[2]["scan_number"], records[2]["rt"], records[2]["level"]), (2.0, 10.5, 2.0));
+        });
     }
 
     #[test]
-    fn test_conversion_report() {
-        let converter = AdvancedConverter::new();
-        let spectrum1 = Spectrum::ms1().unwrap();
-        let spectrum2 = Spectrum::ms1().unwrap();
-
-        let report = converter.generate_conversion_report(&[spectrum1, spectrum2], &[]);
-        assert_eq!(report.original_count, 2);
-        assert_eq!(report.converted_count, 0);
-        assert_eq!(report.conversion_rate, 0.0);
+    fn test_dict_fields_are_checked() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let parse = |literal: &str| {
+                let dict = py.eval(&std::ffi::CString::new(literal).unwrap(), None, None).unwrap();
+                SpectraConverter::dict_to_msobject(dict.downcast::<PyDict>().unwrap()).map(|ms_object| ms_object.spectrum)
+            };
+            let error = |literal: &str| parse(literal).unwrap_err().to_string();
+
+            // 缺少的键取默认值
+            let empty = parse("{}").unwrap();
+            assert_eq!((empty.level, empty.peak_count()), (1, 0));
+
+            // 类型不符时报出字段名，而不是静默跳过
+            assert!(error("{'retention_time': '10.5'}").starts_with("TypeError: spectrum.retention_time"));
+            assert!(error("{'ms_level': 'MS2'}").contains("spectrum.ms_level"));
+            assert!(error("{'peaks': 5}").contains("spectrum.peaks"));
+            assert!(error("{'peaks': [100.0]}").contains("(mz, intensity)"));
+            assert!(error("{'scan': {'drift_time': 'slow'}}").contains("scan.drift_time"));
+            assert!(error("{'precursor': {'charge': 2.5}}").contains("precursor.charge"));
+
+            // 快捷键创建或覆盖前体离子
+            let spectrum = parse(
+                "{'ms_level': 2, 'scan_number': 42, 'precursor_mz': 500.25, 'precursor_charge': 2, 'peaks': [(100.0, 1.0)]}",
+            )
+            .unwrap();
+            let precursor = spectrum.precursor.as_deref().unwrap();
+            assert_eq!((spectrum.scan.scan_number, precursor.mz, precursor.charge), (42, 500.25, 2));
+            assert_eq!(spectrum.peaks(), vec![(100.0, 1.0)]);
+            let overridden = parse("{'ms_level': 2, 'precursor': {'mz': 400.0, 'charge': 3}, 'precursor_charge': 2}").unwrap();
+            assert_eq!(overridden.precursor.as_deref().map(|p| (p.mz, p.charge)), Some((400.0, 2)));
+            assert!(error("{'precursor_charge': 2}").contains("precursor_mz"));
+        });
     }
 }
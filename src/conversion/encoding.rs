@@ -2,7 +2,8 @@
 //!
 //! 提供高效的质谱数据编码和解码功能，支持base64、zlib等格式
 
-use crate::core::{Spectrum, CoreResult};
+use crate::core::spectrum::Spectrum;
+use crate::core::CoreResult;
 use crate::core::types::*;
 use crate::parsers::common::{BinaryDataArray, BinaryDataEncoding, CompressionType};
 use base64::{Engine as _, engine::general_purpose};
@@ -48,7 +49,13 @@ impl Encoder {
     }
 
     /// 编码浮点数组
+    ///
+    /// 默认压缩为MS-Numpress时按Numpress编码，声明类型仍为`encoding`。
     pub fn encode_float_array(&self, values: &[f64], encoding: BinaryDataEncoding) -> CoreResult<BinaryDataArray> {
+        if let Some(compression) = self.default_compression.filter(|compression| compression.numpress().is_some()) {
+            return self.encode_numpress_array(values, encoding, compression);
+        }
+
         let mut data = Vec::with_capacity(values.len() * encoding.size());
 
         for &value in values {
@@ -82,12 +89,33 @@ impl Encoder {
             data = self.compress_data(&data, compression)?;
         }
 
-        let mut array = BinaryDataArray::new(values.len(), encoding, data);
+        let mut array = BinaryDataArray::new(encoding, data).with_expected_length(values.len());
         array.compression = self.default_compression;
 
         Ok(array)
     }
 
+    /// 用MS-Numpress编码浮点数组，`compression`为NumpressZlib时再做zlib压缩
+    pub fn encode_numpress_array(
+        &self,
+        values: &[f64],
+        encoding: BinaryDataEncoding,
+        compression: CompressionType,
+    ) -> CoreResult<BinaryDataArray> {
+        let Some(numpress) = compression.numpress() else {
+            return Err(CoreError::InvalidFormat(format!("{:?} is not an MS-Numpress compression", compression)));
+        };
+        let mut data = numpress.encode(values)
+            .map_err(|e| CoreError::InvalidFormat(e.to_string()))?;
+        if let CompressionType::NumpressZlib(_) = compression {
+            data = self.compress_data(&data, CompressionType::Zlib)?;
+        }
+
+        Ok(BinaryDataArray::new(encoding, data)
+            .with_expected_length(values.len())
+            .with_compression(compression))
+    }
+
     /// 编码整数数组
     pub fn encode_int_array(&self, values: &[i64], encoding: BinaryDataEncoding) -> CoreResult<BinaryDataArray> {
         let mut data = Vec::with_capacity(values.len() * encoding.size());
@@ -123,7 +151,7 @@ impl Encoder {
             data = self.compress_data(&data, compression)?;
         }
 
-        let mut array = BinaryDataArray::new(values.len(), encoding, data);
+        let mut array = BinaryDataArray::new(encoding, data).with_expected_length(values.len());
         array.compression = self.default_compression;
 
         Ok(array)
@@ -147,6 +175,9 @@ impl Encoder {
                 encoder.finish()
                     .map_err(|e| CoreError::InvalidFormat(format!("Compression finish error: {}", e)))
             }
+            CompressionType::Numpress(_) | CompressionType::NumpressZlib(_) => Err(CoreError::InvalidFormat(
+                "MS-Numpress compression only applies to float arrays".to_string()
+            )),
         }
     }
 
@@ -157,8 +188,8 @@ impl Encoder {
 
     /// 编码谱图为二进制格式
     pub fn encode_spectrum(&self, spectrum: &Spectrum) -> CoreResult<EncodedSpectrum> {
-        let mz_array = self.encode_mz_array(&spectrum.peaks.iter().map(|(mz, _)| *mz).collect::<Vec<_>>())?;
-        let intensity_array = self.encode_intensity_array(&spectrum.peaks.iter().map(|(_, intensity)| *intensity).collect::<Vec<_>>())?;
+        let mz_array = self.encode_mz_array(spectrum.mz_slice())?;
+        let intensity_array = self.encode_intensity_array(spectrum.intensity_slice())?;
 
         Ok(EncodedSpectrum {
             level: spectrum.level,
@@ -334,9 +365,9 @@ mod tests {
         let decoded = decoder.decode_spectrum(&encoded).unwrap();
 
         assert_eq!(decoded.level, spectrum.level);
-        assert_eq!(decoded.peaks().len(), spectrum.peaks().len());
-        assert_eq!(decoded.peaks()[0].0, 100.0);
-        assert_eq!(decoded.peaks()[0].1, 1000.0);
+        assert_eq!(decoded.peak_count(), spectrum.peak_count());
+        assert_eq!(decoded.mz_slice()[0], 100.0);
+        assert_eq!(decoded.intensity_slice()[0], 1000.0);
     }
 
     #[test]
@@ -356,4 +387,23 @@ mod tests {
         let decoded = decoder.batch_decode_spectra(&[encoded1, encoded2]).unwrap();
         assert_eq!(decoded.len(), 2);
     }
+
+    #[test]
+    fn test_numpress_encoding_round_trip() {
+        use crate::parsers::numpress::Numpress;
+
+        let mz = [100.0123, 250.5, 250.51, 999.9876];
+        for compression in [CompressionType::Numpress(Numpress::Linear), CompressionType::NumpressZlib(Numpress::Linear)] {
+            let encoder = Encoder::new().with_compression(Some(compression));
+            let array = encoder.encode_mz_array(&mz).unwrap();
+            assert_eq!(array.compression, Some(compression));
+            let decoded = Decoder::new().decode_mz_array(&array).unwrap();
+            assert!(decoded.iter().zip(&mz).all(|(a, b)| (a - b).abs() < 1e-6));
+        }
+
+        let pic = Encoder::new().with_compression(Some(CompressionType::Numpress(Numpress::Pic)));
+        assert!(pic.encode_int_array(&[1, 2], BinaryDataEncoding::Int32Little).is_err());
+        let array = pic.encode_intensity_array(&[10.4, 20.6]).unwrap();
+        assert_eq!(array.decode_f64().unwrap(), vec![10.0, 21.0]);
+    }
 }
@@ -3,10 +3,15 @@
 //! 这个模块提供了不同质谱数据格式之间的转换功能，包括：
 //! - 主转换器
 //! - 编码/解码工具
+//! - mzML输出
+//! - 已解析运行的二进制缓存
 
+pub mod cache;
 pub mod converter;
 pub mod encoding;
+pub mod mzml_writer;
 
 // 重新导出主要类型
 pub use converter::*;
 pub use encoding::*;
+pub use mzml_writer::{MZMLMetadata, MZMLWriter};
@@ -0,0 +1,329 @@
+//! mzML输出
+//!
+//! 把谱图序列化为mzML 1.1文档：cvList、fileDescription、softwareList、run和spectrumList。
+//! 峰数组通过[`Encoder`]编码，可以选择32/64位浮点数以及是否zlib压缩；默认外包indexedmzML，
+//! 追加谱图偏移索引和SHA-1文件校验和。写出的文件可以用`MZMLParser`读回。
+
+use crate::conversion::Encoder;
+use crate::core::spectrum::Spectrum;
+use crate::parsers::common::{BinaryDataEncoding, CompressionType, ParseError, ParseResult};
+use crate::parsers::mzml::writer::MZMLWriter as MZMLEventWriter;
+use quick_xml::escape::escape;
+use std::borrow::Borrow;
+use std::io::Write;
+use std::path::Path;
+
+/// 文件级元数据
+#[derive(Debug, Clone, PartialEq)]
+pub struct MZMLMetadata {
+    /// `<run>`的id
+    pub run_id: String,
+    /// 采集开始时间（xs:dateTime，如"2024-01-01T08:00:00Z"）
+    pub start_time_stamp: Option<String>,
+    /// 原始数据文件路径，写入sourceFileList
+    pub source_file: Option<String>,
+}
+
+impl Default for MZMLMetadata {
+    fn default() -> Self {
+        Self {
+            run_id: "run".to_string(),
+            start_time_stamp: None,
+            source_file: None,
+        }
+    }
+}
+
+/// mzML文档写入器
+#[derive(Debug, Clone)]
+pub struct MZMLWriter {
+    /// 峰数组的编码
+    encoding: BinaryDataEncoding,
+    /// 峰数组的压缩方式
+    compression: Option<CompressionType>,
+    /// 是否写出indexedmzML
+    indexed: bool,
+    /// 文件级元数据
+    metadata: MZMLMetadata,
+}
+
+impl MZMLWriter {
+    /// 创建新的写入器：64位浮点、zlib压缩、写出索引
+    pub fn new() -> Self {
+        Self {
+            encoding: BinaryDataEncoding::Float64Little,
+            compression: Some(CompressionType::Zlib),
+            indexed: true,
+            metadata: MZMLMetadata::default(),
+        }
+    }
+
+    /// 设置峰数组的编码，mzML只支持小端序
+    pub fn with_encoding(mut self, encoding: BinaryDataEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// 设置峰数组的压缩方式，None表示不压缩
+    pub fn with_compression(mut self, compression: Option<CompressionType>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// 设置是否写出indexedmzML（索引和校验和）
+    pub fn with_index(mut self, indexed: bool) -> Self {
+        self.indexed = indexed;
+        self
+    }
+
+    /// 设置文件级元数据
+    pub fn with_metadata(mut self, metadata: MZMLMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// 把谱图写入文件，返回写出的谱图数
+    pub fn write<I>(&self, filename: impl AsRef<Path>, spectra: I) -> ParseResult<usize>
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+        I::Item: Borrow<Spectrum>,
+    {
+        // 先检查选项，避免留下只有头部的文件
+        self.check_options()?;
+        let mut writer = MZMLEventWriter::create(filename)?;
+        let count = self.write_document(&mut writer, spectra)?;
+        writer.finish()?;
+        Ok(count)
+    }
+
+    /// 把谱图写到任意输出，返回底层输出
+    pub fn write_to<W: Write, I>(&self, inner: W, spectra: I) -> ParseResult<W>
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+        I::Item: Borrow<Spectrum>,
+    {
+        self.check_options()?;
+        let mut writer = MZMLEventWriter::new(inner);
+        self.write_document(&mut writer, spectra)?;
+        writer.finish()
+    }
+
+    /// 检查编码和压缩方式能否写入mzML
+    fn check_options(&self) -> ParseResult<()> {
+        if !self.encoding.is_float() || self.encoding.cv_term().is_none() {
+            return Err(ParseError::InvalidBinaryEncoding(format!(
+                "{:?} cannot be used for mzML peak arrays", self.encoding
+            )));
+        }
+        if let Some(compression) = self.compression.filter(|compression| compression.cv_term().is_none()) {
+            return Err(ParseError::InvalidFormat(format!(
+                "{:?} compression cannot be written to mzML", compression
+            )));
+        }
+        Ok(())
+    }
+
+    fn write_document<W: Write, I>(&self, writer: &mut MZMLEventWriter<W>, spectra: I) -> ParseResult<usize>
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+        I::Item: Borrow<Spectrum>,
+    {
+        let encoder = Encoder::new()
+            .with_encoding(self.encoding)
+            .with_compression(self.compression);
+        let spectra = spectra.into_iter();
+
+        writer.write_declaration()?;
+        if self.indexed {
+            writer.start_indexed()?;
+        }
+        writer.write_raw(&self.format_header())?;
+        writer.write_raw(&format!("    <spectrumList count=\"{}\" defaultDataProcessingRef=\"dp\">\n", spectra.len()))?;
+        for (index, spectrum) in spectra.enumerate() {
+            writer.write_encoded_spectrum(index, spectrum.borrow(), &encoder)?;
+        }
+        writer.write_raw("    </spectrumList>\n  </run>\n</mzML>")?;
+        if !self.indexed {
+            writer.write_raw("\n")?;
+        }
+        Ok(writer.spectrum_count())
+    }
+
+    /// 格式化`<mzML>`起始标签到`<run>`起始标签为止的内容
+    fn format_header(&self) -> String {
+        let mut xml = String::from(r#"<mzML xmlns="http://psi.hupo.org/ms/mzml" version="1.1.0">
+  <cvList count="2">
+    <cv id="MS" fullName="Proteomics Standards Initiative Mass Spectrometry Ontology" URI="https://raw.githubusercontent.com/HUPO-PSI/psi-ms-CV/master/psi-ms.obo"/>
+    <cv id="UO" fullName="Unit Ontology" URI="http://ontologies.berkeleybop.org/uo.obo"/>
+  </cvList>
+  <fileDescription>
+    <fileContent/>
+"#);
+        if let Some(source_file) = &self.metadata.source_file {
+            let path = Path::new(source_file);
+            let name = path.file_name().map_or(source_file.clone(), |name| name.to_string_lossy().into_owned());
+            let directory = path.parent().map(|parent| parent.to_string_lossy().replace('\\', "/")).unwrap_or_default();
+            xml.push_str("    <sourceFileList count=\"1\">\n");
+            xml.push_str(&format!(
+                "      <sourceFile id=\"SF1\" name=\"{}\" location=\"file:///{}\"/>\n",
+                escape(name.as_str()),
+                escape(directory.trim_start_matches('/'))
+            ));
+            xml.push_str("    </sourceFileList>\n");
+        }
+        xml.push_str(&format!(r#"  </fileDescription>
+  <softwareList count="1">
+    <software id="openms_utils" version="{}"/>
+  </softwareList>
+  <instrumentConfigurationList count="1">
+    <instrumentConfiguration id="IC1"/>
+  </instrumentConfigurationList>
+  <dataProcessingList count="1">
+    <dataProcessing id="dp">
+      <processingMethod order="0" softwareRef="openms_utils"/>
+    </dataProcessing>
+  </dataProcessingList>
+"#, env!("CARGO_PKG_VERSION")));
+
+        xml.push_str(&format!("  <run id=\"{}\" defaultInstrumentConfigurationRef=\"IC1\"", escape(self.metadata.run_id.as_str())));
+        if let Some(time_stamp) = &self.metadata.start_time_stamp {
+            xml.push_str(&format!(" startTimeStamp=\"{}\"", escape(time_stamp.as_str())));
+        }
+        if self.metadata.source_file.is_some() {
+            xml.push_str(" defaultSourceFileRef=\"SF1\"");
+        }
+        xml.push_str(">\n");
+        xml
+    }
+}
+
+impl Default for MZMLWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+    use crate::core::types::Polarity;
+    use crate::parsers::mzml::{MZMLIndex, MZMLParser};
+
+    fn sample_spectra() -> Vec<Spectrum> {
+        let mut ms1 = Spectrum::new(1).unwrap();
+        ms1.add_peaks([(400.123456789, 1.5e6), (401.5, 2.25e5), (402.75, 0.0)]).unwrap();
+        ms1.set_scan_number(1);
+        ms1.set_retention_time(60.25).unwrap();
+        ms1.scan.polarity = Polarity::Positive;
+        ms1.scan.injection_time = Some(12.5);
+        ms1.scan.scan_window = (350.0, 1500.0);
+
+        let mut ms2 = Spectrum::new(2).unwrap();
+        ms2.add_peaks([(150.0625, 10.0), (300.125, 42.5)]).unwrap();
+        ms2.set_scan_number(2);
+        ms2.set_retention_time(61.0).unwrap();
+        ms2.scan.polarity = Polarity::Negative;
+        ms2.scan.native_id = "controllerType=0 controllerNumber=1 scan=2".to_string();
+        ms2.set_precursor(PrecursorInfo {
+            mz: 400.123456789,
+            intensity: 1.5e6,
+            charge: -2,
+            activation_method: "HCD".to_string(),
+            activation_energy: 27.0,
+            isolation_window: (399.5, 401.0),
+            isolation_target_mz: Some(400.25),
+            normalized_collision_energy: Some(27.0),
+            ..PrecursorInfo::default()
+        });
+
+        let mut empty = Spectrum::new(2).unwrap();
+        empty.set_scan_number(3);
+        empty.set_precursor(PrecursorInfo { mz: 500.0, collision_energy_ev: Some(35.0), activation_energy: 35.0, ..PrecursorInfo::default() });
+        vec![ms1, ms2, empty]
+    }
+
+    /// 比较写出前后的谱图元数据；扫描编号只记录在native id中，`peaks`单独比较以便32位时放宽精度
+    fn assert_metadata_eq(written: &Spectrum, read: &Spectrum) {
+        assert_eq!(read.level, written.level);
+        assert_eq!(read.scan.retention_time, written.scan.retention_time);
+        assert_eq!(read.scan.polarity, written.scan.polarity);
+        assert_eq!(read.scan.injection_time, written.scan.injection_time);
+        assert_eq!(read.scan.scan_window, written.scan.scan_window);
+        assert_eq!(format!("{:?}", read.precursor), format!("{:?}", written.precursor));
+    }
+
+    #[test]
+    fn test_indexed_round_trip() {
+        let spectra = sample_spectra();
+        let file = tempfile::Builder::new().suffix(".mzML").tempfile().unwrap();
+        let metadata = MZMLMetadata {
+            run_id: "sample & blank".to_string(),
+            start_time_stamp: Some("2024-01-01T08:00:00Z".to_string()),
+            source_file: Some("/data/raw/sample.raw".to_string()),
+        };
+        let count = MZMLWriter::new().with_metadata(metadata).write(file.path(), &spectra).unwrap();
+        assert_eq!(count, 3);
+
+        let parser = MZMLParser::new();
+        let read = parser.parse_sequential(file.path()).unwrap();
+        assert_eq!(read.len(), spectra.len());
+        for (written, read) in spectra.iter().zip(&read) {
+            assert_eq!(read.peaks(), written.peaks());
+            assert_metadata_eq(written, read);
+        }
+        assert_eq!(read[0].scan.native_id, "scan=1");
+        assert_eq!(read[1].scan.native_id, spectra[1].scan.native_id);
+
+        let index = MZMLIndex::read(file.path()).unwrap().unwrap();
+        assert_eq!(index.len(), 3);
+        let by_id = parser.read_spectrum_by_id(file.path(), "scan=3").unwrap().unwrap();
+        assert_metadata_eq(&spectra[2], &by_id);
+
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        assert!(content.contains("<run id=\"sample &amp; blank\" defaultInstrumentConfigurationRef=\"IC1\" startTimeStamp=\"2024-01-01T08:00:00Z\" defaultSourceFileRef=\"SF1\">"));
+        assert!(content.contains("<sourceFile id=\"SF1\" name=\"sample.raw\" location=\"file:///data/raw\"/>"));
+        assert!(content.contains("accession=\"MS:1000574\" name=\"zlib compression\""));
+        assert!(content.contains("<spectrum index=\"2\" id=\"scan=3\" defaultArrayLength=\"0\">"));
+    }
+
+    #[test]
+    fn test_plain_32bit_round_trip() {
+        let spectra = sample_spectra();
+        let writer = MZMLWriter::new()
+            .with_encoding(BinaryDataEncoding::Float32Little)
+            .with_compression(None)
+            .with_index(false);
+        let output = String::from_utf8(writer.write_to(Vec::new(), spectra.iter()).unwrap()).unwrap();
+        assert!(output.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<mzML "));
+        assert!(!output.contains("indexedmzML"));
+        assert!(output.contains("accession=\"MS:1000521\" name=\"32-bit float\""));
+
+        let file = tempfile::Builder::new().suffix(".mzML").tempfile().unwrap();
+        std::fs::write(file.path(), &output).unwrap();
+        assert_eq!(MZMLIndex::read(file.path()).unwrap(), None);
+        let read = MZMLParser::new().parse_sequential(file.path()).unwrap();
+        for (written, read) in spectra.iter().zip(&read) {
+            let expected: Vec<(f64, f64)> = written
+                .peaks_iter()
+                .map(|(mz, intensity)| (mz as f32 as f64, intensity as f32 as f64))
+                .collect();
+            assert_eq!(read.peaks(), expected);
+            assert_metadata_eq(written, read);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_options() {
+        let spectra = sample_spectra();
+        let big_endian = MZMLWriter::new().with_encoding(BinaryDataEncoding::Float64Big);
+        assert!(big_endian.write_to(Vec::new(), &spectra).is_err());
+        let integer = MZMLWriter::new().with_encoding(BinaryDataEncoding::Int32Little);
+        assert!(integer.write_to(Vec::new(), &spectra).is_err());
+        let gzip = MZMLWriter::new().with_compression(Some(CompressionType::Gzip));
+        assert!(gzip.write_to(Vec::new(), &spectra).is_err());
+    }
+}
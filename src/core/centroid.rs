@@ -0,0 +1,145 @@
+//! 轮廓谱图质心化
+//!
+//! 轮廓（profile）模式的谱图每个扫描有数万个数据点，而XIC提取、谱图合并和搜索等算法
+//! 都假定输入为质心（centroid）数据。质心化在轮廓中寻找局部极大值，由峰顶及两侧相邻点
+//! 强度对数的抛物线顶点得到质心m/z（对高斯峰精确），强度取峰顶两侧若干点的和。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::{KeyValue, Peak};
+
+/// 记录谱图类型的额外信息键
+pub const SPECTRUM_TYPE_KEY: &str = "spectrum_type";
+/// 质心谱图的类型名（MS:1000127）
+pub const CENTROID_SPECTRUM: &str = "centroid spectrum";
+/// 轮廓谱图的类型名（MS:1000128）
+pub const PROFILE_SPECTRUM: &str = "profile spectrum";
+/// 默认的质心窗口：峰顶每侧最多使用的数据点数
+pub const DEFAULT_CENTROID_WINDOW: usize = 3;
+
+impl Spectrum {
+    /// 是否为轮廓谱图，依据解析时记录的谱图类型；未记录时为false
+    pub fn is_profile(&self) -> bool {
+        self.get_additional_info(SPECTRUM_TYPE_KEY) == Some(PROFILE_SPECTRUM)
+    }
+
+    /// 质心化，参数见[`centroid_spectrum`]
+    pub fn centroid(&self, min_intensity: f64, window: usize) -> Spectrum {
+        centroid_spectrum(self, min_intensity, window)
+    }
+}
+
+/// 将轮廓谱图转换为质心谱图，扫描信息、前体离子和额外信息保持不变，谱图类型记为质心
+///
+/// - 峰顶强度低于`min_intensity`的局部极大值被忽略；
+/// - 相同强度的连续点视为一个平顶峰顶；
+/// - 峰顶每侧最多取`window`个点，且只在强度单调下降的范围内取，不会越过两峰之间的谷底；
+/// - 质心m/z为峰顶及两侧相邻点强度对数的抛物线顶点，平顶峰或无法拟合时取所取各点的强度加权平均；
+/// - 强度为所取各点之和。
+pub fn centroid_spectrum(spectrum: &Spectrum, min_intensity: f64, window: usize) -> Spectrum {
+    let mut profile = spectrum.peaks();
+    profile.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut centroided = spectrum.with_peaks(centroid_peaks(&profile, min_intensity, window));
+    centroided.additional_info.retain(|kv| kv.key != SPECTRUM_TYPE_KEY);
+    centroided.additional_info.push(KeyValue::new(SPECTRUM_TYPE_KEY, CENTROID_SPECTRUM));
+    centroided
+}
+
+/// 在按m/z排序的轮廓数据上寻找质心
+fn centroid_peaks(profile: &[Peak], min_intensity: f64, window: usize) -> Vec<Peak> {
+    let mut peaks = Vec::new();
+    let mut start = 0;
+    while start < profile.len() {
+        let height = profile[start].1;
+        let mut end = start;
+        while end + 1 < profile.len() && profile[end + 1].1 == height {
+            end += 1;
+        }
+
+        let rises = start == 0 || profile[start - 1].1 < height;
+        let falls = end + 1 == profile.len() || profile[end + 1].1 < height;
+        if rises && falls && height > 0.0 && height >= min_intensity {
+            let mut lo = start;
+            while lo > 0 && start - lo < window && profile[lo - 1].1 <= profile[lo].1 {
+                lo -= 1;
+            }
+            let mut hi = end;
+            while hi + 1 < profile.len() && hi - end < window && profile[hi + 1].1 <= profile[hi].1 {
+                hi += 1;
+            }
+
+            let points = &profile[lo..=hi];
+            let intensity: f64 = points.iter().map(|&(_, intensity)| intensity).sum();
+            let mz = (start == end && start > 0 && end + 1 < profile.len())
+                .then(|| log_parabola_vertex(profile[start - 1], profile[start], profile[start + 1]))
+                .flatten()
+                .unwrap_or_else(|| points.iter().map(|&(mz, intensity)| mz * intensity).sum::<f64>() / intensity);
+            peaks.push((mz, intensity));
+        }
+
+        start = end + 1;
+    }
+    peaks
+}
+
+/// 过三点(m/z, ln强度)的抛物线顶点，不开口向下或有非正强度时返回None
+fn log_parabola_vertex(left: Peak, apex: Peak, right: Peak) -> Option<f64> {
+    if left.1 <= 0.0 || right.1 <= 0.0 {
+        return None;
+    }
+    // 以峰顶为原点，避免大m/z平方带来的舍入误差
+    let (u0, u2) = (left.0 - apex.0, right.0 - apex.0);
+    let (y0, y1, y2) = (left.1.ln(), apex.1.ln(), right.1.ln());
+    let d0 = (y0 - y1) / u0;
+    let d2 = (y2 - y1) / u2;
+    let curvature = (d2 - d0) / (u2 - u0);
+    if curvature >= 0.0 {
+        return None;
+    }
+    let slope = d0 - curvature * u0;
+    let offset = -slope / (2.0 * curvature);
+    (offset >= u0 && offset <= u2).then_some(apex.0 + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_spectrum::gaussian_profile;
+
+    #[test]
+    fn test_gaussian_centroid_mz() {
+        // 峰中心不落在采样点上
+        let center = 500.001_37;
+        let mut spectrum = gaussian_profile(&[(center, 1e6)], 0.004, (499.95, 500.05), 0.001);
+        spectrum.set_retention_time(12.5).unwrap();
+        assert!(spectrum.is_profile());
+
+        let centroided = centroid_spectrum(&spectrum, 100.0, DEFAULT_CENTROID_WINDOW);
+        assert_eq!(centroided.peak_count(), 1);
+        let (mz, intensity) = centroided.peak(0).unwrap();
+        assert!((mz - center).abs() < 1e-4, "{} vs {}", mz, center);
+        let expected: f64 = spectrum.peaks_iter().filter(|peak| (peak.0 - mz).abs() < 0.0035).map(|peak| peak.1).sum();
+        assert!((intensity - expected).abs() / expected < 1e-9);
+
+        assert!(!centroided.is_profile());
+        assert_eq!(centroided.get_additional_info(SPECTRUM_TYPE_KEY), Some(CENTROID_SPECTRUM));
+        assert_eq!(centroided.scan.retention_time, 12.5);
+    }
+
+    #[test]
+    fn test_neighbouring_peaks_and_threshold() {
+        let spectrum = gaussian_profile(&[(500.0, 1e6), (500.02, 5e5), (500.06, 50.0)], 0.003, (499.95, 500.1), 0.001);
+        let centroided = spectrum.centroid(100.0, 5);
+        assert_eq!(centroided.peak_count(), 2);
+        assert!((centroided.mz_slice()[0] - 500.0).abs() < 1e-4);
+        assert!((centroided.mz_slice()[1] - 500.02).abs() < 1e-4);
+
+        // 平顶峰以平台中点为质心
+        let mut flat = Spectrum::new(1).unwrap();
+        flat.add_peaks([(100.0, 1.0), (100.1, 5.0), (100.2, 5.0), (100.3, 1.0)]).unwrap();
+        let centroided = flat.centroid(0.0, 1);
+        assert_eq!(centroided.peak_count(), 1);
+        assert!((centroided.mz_slice()[0] - 100.15).abs() < 1e-9);
+        assert_eq!(centroided.intensity_slice()[0], 12.0);
+    }
+}
@@ -0,0 +1,185 @@
+//! 谱图比较
+//!
+//! 逐项比较两张谱图的元数据和峰列表，用于验证写入器/转换器的输出
+//! （例如与ProteoWizard的转换结果对比）。
+
+use crate::core::spectrum::Spectrum;
+use crate::utils::mass;
+
+/// 保留时间等浮点元数据的绝对容差
+const METADATA_ABS_TOLERANCE: f64 = 1e-6;
+
+/// 两张谱图的比较结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpectrumComparison {
+    /// 元数据差异描述，如"ms level: 1 != 2"
+    pub metadata_differences: Vec<String>,
+    /// 两张谱图的峰数
+    pub peak_counts: (usize, usize),
+    /// 在另一张谱图中找不到匹配峰的峰数（两侧合计）
+    pub unmatched_peaks: usize,
+    /// m/z匹配但强度超出容差的峰数
+    pub intensity_mismatches: usize,
+    /// 匹配峰的最大m/z偏差 (ppm)
+    pub max_mz_deviation_ppm: f64,
+}
+
+impl SpectrumComparison {
+    /// 元数据和峰列表均一致
+    pub fn is_identical(&self) -> bool {
+        !self.has_metadata_differences() && !self.has_peak_differences()
+    }
+
+    /// 存在元数据差异
+    pub fn has_metadata_differences(&self) -> bool {
+        !self.metadata_differences.is_empty()
+    }
+
+    /// 存在峰差异
+    pub fn has_peak_differences(&self) -> bool {
+        self.unmatched_peaks > 0 || self.intensity_mismatches > 0
+    }
+}
+
+impl Spectrum {
+    /// 与另一张谱图比较
+    ///
+    /// 峰按m/z排序后逐一配对，m/z偏差在`mz_tolerance_ppm`内视为匹配；
+    /// 匹配峰的强度相对偏差超过`intensity_rel_tol`时计为强度不一致。
+    pub fn compare(&self, other: &Spectrum, mz_tolerance_ppm: f64, intensity_rel_tol: f64) -> SpectrumComparison {
+        let mut comparison = SpectrumComparison {
+            metadata_differences: self.metadata_differences(other, mz_tolerance_ppm),
+            peak_counts: (self.peak_count(), other.peak_count()),
+            ..SpectrumComparison::default()
+        };
+
+        let mut peaks_a = self.peaks();
+        let mut peaks_b = other.peaks();
+        peaks_a.sort_by(|a, b| a.0.total_cmp(&b.0));
+        peaks_b.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let (mut i, mut j) = (0, 0);
+        while i < peaks_a.len() && j < peaks_b.len() {
+            let (mz_a, intensity_a) = peaks_a[i];
+            let (mz_b, intensity_b) = peaks_b[j];
+            let deviation = mass::ppm_diff(mz_b, mz_a);
+
+            if deviation.abs() <= mz_tolerance_ppm {
+                comparison.max_mz_deviation_ppm = comparison.max_mz_deviation_ppm.max(deviation.abs());
+                let scale = intensity_a.abs().max(intensity_b.abs());
+                if scale > 0.0 && (intensity_a - intensity_b).abs() / scale > intensity_rel_tol {
+                    comparison.intensity_mismatches += 1;
+                }
+                i += 1;
+                j += 1;
+            } else if mz_a < mz_b {
+                comparison.unmatched_peaks += 1;
+                i += 1;
+            } else {
+                comparison.unmatched_peaks += 1;
+                j += 1;
+            }
+        }
+        comparison.unmatched_peaks += (peaks_a.len() - i) + (peaks_b.len() - j);
+
+        comparison
+    }
+
+    /// 比较MS级别、扫描信息和前体离子信息
+    fn metadata_differences(&self, other: &Spectrum, mz_tolerance_ppm: f64) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        if self.level != other.level {
+            differences.push(format!("ms level: {} != {}", self.level, other.level));
+        }
+        if self.scan.scan_number != other.scan.scan_number {
+            differences.push(format!("scan number: {} != {}", self.scan.scan_number, other.scan.scan_number));
+        }
+        if (self.scan.retention_time - other.scan.retention_time).abs() > METADATA_ABS_TOLERANCE {
+            differences.push(format!(
+                "retention time: {} != {}", self.scan.retention_time, other.scan.retention_time
+            ));
+        }
+
+        match (&self.precursor, &other.precursor) {
+            (Some(a), Some(b)) => {
+                if !mass::within_ppm(b.mz, a.mz, mz_tolerance_ppm) {
+                    differences.push(format!("precursor m/z: {} != {}", a.mz, b.mz));
+                }
+                if a.charge != b.charge {
+                    differences.push(format!("precursor charge: {} != {}", a.charge, b.charge));
+                }
+                if a.activation_method != b.activation_method {
+                    differences.push(format!(
+                        "activation method: {} != {}", a.activation_method, b.activation_method
+                    ));
+                }
+                if (a.activation_energy - b.activation_energy).abs() > METADATA_ABS_TOLERANCE {
+                    differences.push(format!(
+                        "activation energy: {} != {}", a.activation_energy, b.activation_energy
+                    ));
+                }
+                for (name, x, y) in [
+                    ("collision energy (eV)", a.collision_energy_ev, b.collision_energy_ev),
+                    ("normalized collision energy", a.normalized_collision_energy, b.normalized_collision_energy),
+                ] {
+                    let same = match (x, y) {
+                        (Some(x), Some(y)) => (x - y).abs() <= METADATA_ABS_TOLERANCE,
+                        (x, y) => x.is_none() && y.is_none(),
+                    };
+                    if !same {
+                        differences.push(format!("{}: {:?} != {:?}", name, x, y));
+                    }
+                }
+            }
+            (Some(_), None) => differences.push("precursor: present != absent".to_string()),
+            (None, Some(_)) => differences.push("precursor: absent != present".to_string()),
+            (None, None) => {}
+        }
+
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    fn spectrum(peaks: &[(f64, f64)]) -> Spectrum {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum.set_precursor(PrecursorInfo { mz: 500.25, charge: 2, ..PrecursorInfo::default() });
+        spectrum
+    }
+
+    #[test]
+    fn test_identical_within_tolerance() {
+        let a = spectrum(&[(100.0, 10.0), (200.0, 20.0)]);
+        let b = spectrum(&[(200.0002, 20.0), (100.0001, 10.0)]);
+        let comparison = a.compare(&b, 5.0, 0.01);
+        assert!(comparison.is_identical());
+        assert!((comparison.max_mz_deviation_ppm - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_peak_differences() {
+        let a = spectrum(&[(100.0, 10.0), (200.0, 20.0), (300.0, 30.0)]);
+        let b = spectrum(&[(100.0, 10.0), (200.0, 25.0), (301.0, 30.0)]);
+        let comparison = a.compare(&b, 5.0, 0.01);
+        assert!(!comparison.has_metadata_differences());
+        assert_eq!(comparison.intensity_mismatches, 1);
+        assert_eq!(comparison.unmatched_peaks, 2);
+    }
+
+    #[test]
+    fn test_metadata_differences() {
+        let a = spectrum(&[(100.0, 10.0)]);
+        let mut b = spectrum(&[(100.0, 10.0)]);
+        b.scan.retention_time = 12.5;
+        b.clear_precursor();
+        let comparison = a.compare(&b, 5.0, 0.01);
+        assert!(!comparison.has_peak_differences());
+        assert_eq!(comparison.metadata_differences.len(), 2);
+    }
+}
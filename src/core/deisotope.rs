@@ -0,0 +1,224 @@
+//! 质心谱图去同位素与电荷识别
+//!
+//! 按强度从高到低遍历峰，对每个电荷在±1.00335/z处寻找同位素伙伴峰，
+//! 用averagine模型估计的相邻同位素峰强度比筛选候选簇，最后把每个同位素簇
+//! 合并为其单同位素峰（强度为簇内各峰之和），并可记录识别出的电荷。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::{KeyValue, Peak, PeakList, Tolerance};
+use crate::utils::mass::{ISOTOPE_SPACING, PROTON_MASS};
+
+/// 记录每个峰电荷的额外信息键，值为逗号分隔的电荷列表（与峰一一对应，0表示未识别）
+pub const PEAK_CHARGES_KEY: &str = "peak_charges";
+/// 默认的最大电荷
+pub const DEFAULT_MAX_CHARGE: u8 = 4;
+
+/// averagine模型下同位素分布的泊松参数λ与中性质量之比 (1/Da)
+///
+/// 由averagine元素组成C4.9384 H7.7583 N1.3577 O1.4773 S0.0417（111.1254 Da）
+/// 及各元素重同位素丰度得到，1000 Da的肽段λ约为0.54。
+const AVERAGINE_LAMBDA_PER_DA: f64 = 5.357e-4;
+/// 相邻同位素峰实测强度比允许偏离模型预测值的倍数
+const ISOTOPE_RATIO_FACTOR: f64 = 3.0;
+
+impl Spectrum {
+    /// 去同位素，参数见[`deisotope`]
+    pub fn deisotope(&self, tolerance: Tolerance, max_charge: u8, keep_unassigned: bool) -> Spectrum {
+        deisotope(self, tolerance, max_charge, keep_unassigned)
+    }
+}
+
+/// 去同位素：每个同位素簇只保留单同位素峰，强度为簇内各峰之和，峰按m/z排序
+///
+/// - 峰按强度从高到低作为种子，对电荷1..=`max_charge`分别向两侧寻找间距为1.00335/z、
+///   在`tolerance`内且尚未归属其他簇的峰；
+/// - 以簇内最低m/z的峰为单同位素峰，按averagine泊松模型检查相邻峰强度比，
+///   首个比值明显偏高时视为簇前混入了杂峰并将其去掉，其余不符处截断簇；
+/// - 至少两个峰才构成同位素簇，多个电荷都成立时取峰数最多者，峰数相同时取较高电荷；
+/// - 未归入任何簇的峰在`keep_unassigned`为true时原样保留。
+///
+/// 需要识别出的电荷时使用[`deisotope_with_charges`]。
+pub fn deisotope(spectrum: &Spectrum, tolerance: Tolerance, max_charge: u8, keep_unassigned: bool) -> Spectrum {
+    deisotope_with_charges(spectrum, tolerance, max_charge, keep_unassigned).0
+}
+
+/// 去同位素并返回每个输出峰的电荷（0表示未识别），规则见[`deisotope`]
+pub fn deisotope_with_charges(
+    spectrum: &Spectrum,
+    tolerance: Tolerance,
+    max_charge: u8,
+    keep_unassigned: bool,
+) -> (Spectrum, Vec<u8>) {
+    let mut peaks = spectrum.peaks();
+    peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut order: Vec<usize> = (0..peaks.len()).collect();
+    order.sort_by(|&a, &b| peaks[b].1.total_cmp(&peaks[a].1).then(a.cmp(&b)));
+
+    let mut used = vec![false; peaks.len()];
+    let mut assigned: Vec<(Peak, u8)> = Vec::new();
+    for seed in order {
+        if used[seed] {
+            continue;
+        }
+
+        let mut best: Option<(Vec<usize>, u8)> = None;
+        for charge in 1..=max_charge {
+            let cluster = isotope_cluster(&peaks, &used, seed, charge, tolerance);
+            if cluster.len() >= 2 && best.as_ref().is_none_or(|(current, _)| cluster.len() >= current.len()) {
+                best = Some((cluster, charge));
+            }
+        }
+
+        match best {
+            Some((cluster, charge)) => {
+                let intensity: f64 = cluster.iter().map(|&i| peaks[i].1).sum();
+                assigned.push(((peaks[cluster[0]].0, intensity), charge));
+                for i in cluster {
+                    used[i] = true;
+                }
+            }
+            None => {
+                used[seed] = true;
+                if keep_unassigned {
+                    assigned.push((peaks[seed], 0));
+                }
+            }
+        }
+    }
+    assigned.sort_by(|a, b| a.0.0.total_cmp(&b.0.0));
+
+    let (peaks, charges): (PeakList, Vec<u8>) = assigned.into_iter().unzip();
+    (spectrum.with_peaks(peaks), charges)
+}
+
+/// 把电荷列表写入谱图额外信息[`PEAK_CHARGES_KEY`]，替换已有记录
+pub fn annotate_charges(spectrum: &mut Spectrum, charges: &[u8]) {
+    let value = charges.iter().map(|charge| charge.to_string()).collect::<Vec<_>>().join(",");
+    spectrum.additional_info.retain(|kv| kv.key != PEAK_CHARGES_KEY);
+    spectrum.additional_info.push(KeyValue::new(PEAK_CHARGES_KEY, value));
+}
+
+/// 以`seed`为种子、按给定电荷收集同位素簇并用averagine比值筛选，返回按m/z升序的峰下标；
+/// 筛选后不含种子时只返回种子本身
+fn isotope_cluster(peaks: &[Peak], used: &[bool], seed: usize, charge: u8, tolerance: Tolerance) -> Vec<usize> {
+    let step = ISOTOPE_SPACING / charge as f64;
+    let mut cluster = vec![seed];
+    while let Some(lower) = find_partner(peaks, used, peaks[cluster[0]].0 - step, tolerance) {
+        cluster.insert(0, lower);
+    }
+    while let Some(upper) = find_partner(peaks, used, peaks[cluster[cluster.len() - 1]].0 + step, tolerance) {
+        cluster.push(upper);
+    }
+
+    let mut start = 0;
+    let mut len = 1;
+    while start + 1 < cluster.len() {
+        let mass = ((peaks[cluster[start]].0 - PROTON_MASS) * charge as f64).max(0.0);
+        let lambda = mass * AVERAGINE_LAMBDA_PER_DA;
+        let ratios_fit = (start..cluster.len() - 1)
+            .map(|k| {
+                let ratio = peaks[cluster[k + 1]].1 / peaks[cluster[k]].1;
+                let expected = lambda / (k - start + 1) as f64;
+                (ratio, expected)
+            })
+            .collect::<Vec<_>>();
+
+        let (first_ratio, first_expected) = ratios_fit[0];
+        if first_ratio > first_expected * ISOTOPE_RATIO_FACTOR {
+            // 单同位素峰候选相对下一个峰过弱，视为混入的杂峰
+            start += 1;
+            continue;
+        }
+        len = 1 + ratios_fit
+            .iter()
+            .take_while(|&&(ratio, expected)| {
+                ratio >= expected / ISOTOPE_RATIO_FACTOR && ratio <= expected * ISOTOPE_RATIO_FACTOR
+            })
+            .count();
+        break;
+    }
+
+    let kept = &cluster[start..start + len];
+    if kept.contains(&seed) { kept.to_vec() } else { vec![seed] }
+}
+
+/// 在目标m/z的容差内寻找最近的未归属峰
+fn find_partner(peaks: &[Peak], used: &[bool], target: f64, tolerance: Tolerance) -> Option<usize> {
+    let tol = tolerance.tolerance_at_mz(target);
+    let first = peaks.partition_point(|peak| peak.0 < target - tol);
+    (first..peaks.len())
+        .take_while(|&i| peaks[i].0 <= target + tol)
+        .filter(|&i| !used[i])
+        .min_by(|&a, &b| (peaks[a].0 - target).abs().total_cmp(&(peaks[b].0 - target).abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::centroid::SPECTRUM_TYPE_KEY;
+
+    /// 中性质量为`mass`、电荷为`charge`的泊松同位素分布，单同位素峰强度为`height`
+    fn envelope(mass: f64, charge: u8, height: f64, count: usize) -> Vec<Peak> {
+        let lambda = mass * AVERAGINE_LAMBDA_PER_DA;
+        let mono_mz = mass / charge as f64 + PROTON_MASS;
+        let mut relative = 1.0;
+        (0..count)
+            .map(|k| {
+                if k > 0 {
+                    relative *= lambda / k as f64;
+                }
+                (mono_mz + k as f64 * ISOTOPE_SPACING / charge as f64, height * relative)
+            })
+            .collect()
+    }
+
+    fn total(peaks: &[Peak]) -> f64 {
+        peaks.iter().map(|peak| peak.1).sum()
+    }
+
+    #[test]
+    fn test_peptide_envelopes_collapse_to_monoisotopic() {
+        let doubly = envelope(1500.0, 2, 1e6, 5);
+        // 2400 Da时+1同位素峰最强，种子不是单同位素峰
+        let triply = envelope(2400.0, 3, 4e5, 5);
+        assert!(triply[1].1 > triply[0].1);
+
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.add_peaks(doubly.iter().chain(&triply).copied()).unwrap();
+        spectrum.add_peak(900.5, 50.0).unwrap();
+        spectrum.add_additional_info(SPECTRUM_TYPE_KEY, "centroid spectrum").unwrap();
+
+        let (deisotoped, charges) = deisotope_with_charges(&spectrum, Tolerance::PPM(10.0), DEFAULT_MAX_CHARGE, true);
+        assert_eq!(deisotoped.peak_count(), 3, "{:?}", deisotoped.peaks());
+        assert_eq!(charges, vec![2, 3, 0]);
+        assert!((deisotoped.mz_slice()[0] - doubly[0].0).abs() < 1e-9);
+        assert!((deisotoped.intensity_slice()[0] - total(&doubly)).abs() < 1e-6);
+        assert!((deisotoped.mz_slice()[1] - triply[0].0).abs() < 1e-9);
+        assert!((deisotoped.intensity_slice()[1] - total(&triply)).abs() < 1e-6);
+        assert_eq!(deisotoped.peak(2).unwrap(), (900.5, 50.0));
+        assert_eq!(deisotoped.level, 2);
+        assert_eq!(deisotoped.get_additional_info(SPECTRUM_TYPE_KEY), Some("centroid spectrum"));
+
+        let dropped = spectrum.deisotope(Tolerance::PPM(10.0), DEFAULT_MAX_CHARGE, false);
+        assert_eq!(dropped.peak_count(), 2);
+
+        let mut annotated = deisotoped.clone();
+        annotate_charges(&mut annotated, &charges);
+        assert_eq!(annotated.get_additional_info(PEAK_CHARGES_KEY), Some("2,3,0"));
+    }
+
+    #[test]
+    fn test_ratio_check_rejects_leading_noise_peak() {
+        let doubly = envelope(1500.0, 2, 1e6, 4);
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.add_peaks(doubly.iter().copied()).unwrap();
+        // 单同位素峰前方恰好一个同位素间距处的弱杂峰
+        spectrum.add_peak(doubly[0].0 - ISOTOPE_SPACING / 2.0, 1e3).unwrap();
+
+        let (deisotoped, charges) = deisotope_with_charges(&spectrum, Tolerance::PPM(10.0), 3, true);
+        assert_eq!(charges, vec![0, 2]);
+        assert!((deisotoped.mz_slice()[1] - doubly[0].0).abs() < 1e-9);
+        assert!((deisotoped.intensity_slice()[1] - total(&doubly)).abs() < 1e-6);
+    }
+}
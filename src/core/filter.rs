@@ -0,0 +1,124 @@
+//! 谱图过滤条件
+//!
+//! 这个模块定义了按保留时间、扫描编号和MS级别筛选谱图的条件，
+//! 供解析、导出和子集提取等功能共用
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use serde::{Deserialize, Serialize};
+
+/// 谱图过滤条件
+///
+/// 所有范围均为闭区间，未设置的条件不参与过滤。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpectrumFilter {
+    /// 保留时间范围
+    pub rt_range: Option<(RetentionTime, RetentionTime)>,
+    /// 扫描编号范围
+    pub scan_range: Option<(ScanNumber, ScanNumber)>,
+    /// 允许的MS级别
+    pub ms_levels: Option<Vec<MSLevel>>,
+}
+
+impl SpectrumFilter {
+    /// 创建不做任何过滤的条件
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置保留时间范围
+    pub fn with_rt_range(mut self, min_rt: RetentionTime, max_rt: RetentionTime) -> Self {
+        self.rt_range = Some((min_rt, max_rt));
+        self
+    }
+
+    /// 设置扫描编号范围
+    pub fn with_scan_range(mut self, min_scan: ScanNumber, max_scan: ScanNumber) -> Self {
+        self.scan_range = Some((min_scan, max_scan));
+        self
+    }
+
+    /// 设置允许的MS级别
+    pub fn with_ms_levels(mut self, ms_levels: impl Into<Vec<MSLevel>>) -> Self {
+        self.ms_levels = Some(ms_levels.into());
+        self
+    }
+
+    /// 是否没有设置任何条件
+    pub fn is_empty(&self) -> bool {
+        self.rt_range.is_none() && self.scan_range.is_none() && self.ms_levels.is_none()
+    }
+
+    /// 检查谱图头信息是否满足条件
+    pub fn matches(&self, level: MSLevel, scan_number: ScanNumber, retention_time: RetentionTime) -> bool {
+        if let Some((min_rt, max_rt)) = self.rt_range {
+            if retention_time < min_rt || retention_time > max_rt {
+                return false;
+            }
+        }
+        if let Some((min_scan, max_scan)) = self.scan_range {
+            if scan_number < min_scan || scan_number > max_scan {
+                return false;
+            }
+        }
+        if let Some(ref levels) = self.ms_levels {
+            if !levels.contains(&level) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 检查谱图是否满足条件
+    pub fn matches_spectrum(&self, spectrum: &Spectrum) -> bool {
+        self.matches(spectrum.level, spectrum.scan.scan_number, spectrum.scan.retention_time)
+    }
+
+    /// 筛选满足条件的谱图
+    pub fn apply<'a>(&self, spectra: &'a [Spectrum]) -> Vec<&'a Spectrum> {
+        spectra.iter().filter(|s| self.matches_spectrum(s)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        let filter = SpectrumFilter::new();
+        assert!(filter.is_empty());
+        assert!(filter.matches(1, 1, 0.0));
+        assert!(filter.matches(3, 99999, 1e6));
+    }
+
+    #[test]
+    fn test_ranges_are_inclusive() {
+        let filter = SpectrumFilter::new()
+            .with_rt_range(10.0, 20.0)
+            .with_scan_range(5, 8)
+            .with_ms_levels(vec![2]);
+
+        assert!(filter.matches(2, 5, 10.0));
+        assert!(filter.matches(2, 8, 20.0));
+        assert!(!filter.matches(1, 6, 15.0));
+        assert!(!filter.matches(2, 9, 15.0));
+        assert!(!filter.matches(2, 6, 20.5));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut spectra = Vec::new();
+        for i in 0..5u32 {
+            let mut spectrum = Spectrum::ms1().unwrap();
+            spectrum.set_scan_number(i + 1);
+            spectrum.set_retention_time(i as f64 * 10.0).unwrap();
+            spectra.push(spectrum);
+        }
+
+        let filter = SpectrumFilter::new().with_rt_range(10.0, 30.0);
+        let selected = filter.apply(&spectra);
+        let scans: Vec<u32> = selected.iter().map(|s| s.scan.scan_number).collect();
+        assert_eq!(scans, vec![2, 3, 4]);
+    }
+}
@@ -0,0 +1,83 @@
+//! 谱图指纹
+//!
+//! 对谱图内容做容忍浮点噪声的哈希，用于跨文件查找重复谱图：
+//! 取最强的`top_n`个峰，m/z按给定精度量化，强度归一化为8位等级，
+//! 对得到的字节串计算SHA-1并返回十六进制字符串。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use sha1::{Digest, Sha1};
+
+/// 计算峰列表的指纹
+///
+/// `mz_precision`必须为正数。峰强度全为0或峰列表为空时仍返回确定的指纹。
+pub fn fingerprint(peaks: &[Peak], mz_precision: f64, top_n: usize) -> CoreResult<String> {
+    if mz_precision <= 0.0 || !mz_precision.is_finite() {
+        return Err(CoreError::InvalidFormat(format!(
+            "m/z precision must be positive, got {}",
+            mz_precision
+        )));
+    }
+
+    let mut top: Vec<Peak> = peaks.to_vec();
+    top.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.total_cmp(&b.0)));
+    top.truncate(top_n);
+
+    let max_intensity = top.first().map_or(0.0, |peak| peak.1);
+    let mut quantized: Vec<(i64, u8)> = top
+        .iter()
+        .map(|&(mz, intensity)| {
+            let level = if max_intensity > 0.0 { (intensity / max_intensity * 255.0).round() as u8 } else { 0 };
+            ((mz / mz_precision).round() as i64, level)
+        })
+        .collect();
+    quantized.sort_unstable();
+
+    let mut hasher = Sha1::new();
+    for (mz_bin, level) in quantized {
+        hasher.update(mz_bin.to_le_bytes());
+        hasher.update([level]);
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+impl Spectrum {
+    /// 谱图指纹，见[`fingerprint`]
+    pub fn fingerprint(&self, mz_precision: f64, top_n: usize) -> CoreResult<String> {
+        fingerprint(&self.peaks(), mz_precision, top_n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectrum(peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum
+    }
+
+    #[test]
+    fn test_float32_round_trip_keeps_fingerprint() {
+        let peaks = [(147.11280, 1200.5), (262.13975, 830.25), (375.22381, 4410.0), (504.26640, 97.125)];
+        let original = spectrum(&peaks);
+        let round_tripped = spectrum(
+            &peaks.iter().map(|&(mz, i)| (mz as f32 as f64, i as f32 as f64)).collect::<Vec<_>>(),
+        );
+        let expected = original.fingerprint(0.01, 30).unwrap();
+        assert_eq!(expected.len(), 40);
+        assert_eq!(round_tripped.fingerprint(0.01, 30).unwrap(), expected);
+
+        // 多出一个主要峰时指纹不同
+        let mut extra = peaks.to_vec();
+        extra.push((600.3, 3000.0));
+        assert_ne!(spectrum(&extra).fingerprint(0.01, 30).unwrap(), expected);
+
+        // 被top_n截掉的弱峰不影响指纹
+        let mut weak = peaks.to_vec();
+        weak.push((700.0, 1.0));
+        assert_eq!(spectrum(&weak).fingerprint(0.01, 4).unwrap(), original.fingerprint(0.01, 4).unwrap());
+        assert!(original.fingerprint(0.0, 30).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! 二进制谱图索引的持久化
+//!
+//! 为整个运行重新建立索引很费时，这里把[`BinnedSpectraIndex`]（含共享的谱图数据）
+//! 以bincode写入磁盘。文件以固定的头部开始：
+//!
+//! | 字节 | 内容 |
+//! |------|------|
+//! | 0..8 | 魔数`OMSUBIDX` |
+//! | 8..12 | 格式版本 (u32, 小端) |
+//! | 12..20 | bin数量 (u64, 小端) |
+//! | 20..28 | 总峰数量 (u64, 小端) |
+//!
+//! 其后为bincode编码的索引。读入时校验魔数、版本以及头部记录的数量，
+//! 以发现截断、损坏或由不兼容版本写出的文件。
+//!
+//! 版本2起谱图的峰以平行的m/z和强度数组编码；版本1的文件需要重新建立索引。
+
+use crate::core::spectrum::BinnedSpectraIndex;
+use crate::core::types::{CoreError, CoreResult};
+use crate::utils::path::extended_length_path;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// 索引文件的魔数
+pub const INDEX_MAGIC: [u8; 8] = *b"OMSUBIDX";
+/// 当前的索引文件格式版本（2：峰以平行数组存放）
+pub const INDEX_FORMAT_VERSION: u32 = 2;
+/// 头部长度（字节）
+const HEADER_LEN: usize = 28;
+
+impl BinnedSpectraIndex {
+    /// 保存到文件，已存在的文件被覆盖
+    pub fn save(&self, path: impl AsRef<Path>) -> CoreResult<()> {
+        let path = path.as_ref();
+        let io_error = |source| CoreError::Io { path: path.to_path_buf(), source };
+
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&INDEX_MAGIC);
+        header.extend_from_slice(&INDEX_FORMAT_VERSION.to_le_bytes());
+        header.extend_from_slice(&(self.bin_count() as u64).to_le_bytes());
+        header.extend_from_slice(&(self.total_peak_count() as u64).to_le_bytes());
+
+        let mut writer = BufWriter::new(File::create(extended_length_path(path)).map_err(io_error)?);
+        writer.write_all(&header).map_err(io_error)?;
+        bincode::serialize_into(&mut writer, self)
+            .map_err(|e| CoreError::InvalidFormat(format!("Failed to serialize index to {:?}: {}", path, e)))?;
+        writer.flush().map_err(io_error)
+    }
+
+    /// 从[`save`](Self::save)写出的文件读入索引
+    ///
+    /// 魔数不符、版本不受支持、文件截断或内容与头部不一致时返回[`CoreError::InvalidFormat`]。
+    pub fn load(path: impl AsRef<Path>) -> CoreResult<Self> {
+        let path = path.as_ref();
+        let invalid = |message: String| CoreError::InvalidFormat(format!("{:?}: {}", path, message));
+
+        let file = File::open(extended_length_path(path)).map_err(|source| CoreError::Io { path: path.to_path_buf(), source })?;
+        let mut reader = BufReader::new(file);
+        let mut header = [0u8; HEADER_LEN];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| invalid("file is too short to contain an index header".to_string()))?;
+
+        if header[0..8] != INDEX_MAGIC {
+            return Err(invalid("not a binned spectra index file (bad magic bytes)".to_string()));
+        }
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != INDEX_FORMAT_VERSION {
+            return Err(invalid(format!(
+                "unsupported index format version {} (expected {})",
+                version, INDEX_FORMAT_VERSION
+            )));
+        }
+        let bin_count = u64::from_le_bytes(header[12..20].try_into().unwrap());
+        let total_peak_count = u64::from_le_bytes(header[20..28].try_into().unwrap());
+
+        let index: BinnedSpectraIndex = bincode::deserialize_from(&mut reader)
+            .map_err(|e| invalid(format!("index data is truncated or corrupted: {}", e)))?;
+        if index.bin_count() as u64 != bin_count {
+            return Err(invalid(format!("header records {} bins but data has {}", bin_count, index.bin_count())));
+        }
+        if index.total_peak_count() as u64 != total_peak_count {
+            return Err(invalid(format!(
+                "header records {} peaks but data has {}",
+                total_peak_count,
+                index.total_peak_count()
+            )));
+        }
+        index.check_consistency().map_err(invalid)?;
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::Spectrum;
+    use crate::parsers::mzml::test_data::{build_mzml, write_temp_file, TestSpectrum};
+    use crate::parsers::mzml::MZMLParser;
+
+    fn assert_invalid(result: CoreResult<BinnedSpectraIndex>, expected: &str) {
+        match result {
+            Err(CoreError::InvalidFormat(message)) => assert!(message.contains(expected), "{}", message),
+            other => panic!("expected InvalidFormat containing {:?}, got {:?}", expected, other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_preserves_search_results() {
+        let spectra: Vec<TestSpectrum> = (0..6)
+            .map(|i| {
+                let peaks = (0..40).map(|p| (150.0 + p as f64 * 23.7 + i as f64 * 0.31, 100.0 + (p * i) as f64)).collect();
+                TestSpectrum::new(i + 1, 1, i as f64 * 0.5, peaks)
+            })
+            .collect();
+        let mzml = write_temp_file(&build_mzml(&spectra));
+        let parsed = MZMLParser::new().parse(mzml.path()).unwrap();
+        let mut index = BinnedSpectraIndex::new(parsed, 5.0).unwrap();
+        // 增量追加的谱图也要保留
+        index.add_spectra(vec![small_spectrum()]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.bidx");
+        index.save(&path).unwrap();
+        let loaded = BinnedSpectraIndex::load(&path).unwrap();
+
+        assert_eq!(loaded.bin_count(), index.bin_count());
+        assert_eq!(loaded.spectrum_count(), index.spectrum_count());
+        assert_eq!(loaded.total_peak_count(), index.total_peak_count());
+        for range in [(150.0, 160.0), (300.0, 420.5), (0.0, 2000.0)] {
+            assert_eq!(loaded.search_range(range).unwrap(), index.search_range(range).unwrap());
+            assert_eq!(loaded.search_range_locations(range), index.search_range_locations(range));
+        }
+    }
+
+    fn small_spectrum() -> Spectrum {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.add_peaks([(99.5, 10.0), (1500.25, 20.0)]).unwrap();
+        spectrum
+    }
+
+    #[test]
+    fn test_load_rejects_bad_files() {
+        let index = BinnedSpectraIndex::new(vec![small_spectrum()], 1.0).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.bidx");
+        index.save(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+
+        let write = |data: &[u8]| {
+            std::fs::write(&path, data).unwrap();
+            BinnedSpectraIndex::load(&path)
+        };
+        assert_invalid(write(&bytes[..bytes.len() - 5]), "truncated");
+        assert_invalid(write(&bytes[..10]), "header");
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert_invalid(write(&wrong_magic), "magic");
+
+        let mut future = bytes.clone();
+        future[8..12].copy_from_slice(&(INDEX_FORMAT_VERSION + 1).to_le_bytes());
+        assert_invalid(write(&future), &format!("version {}", INDEX_FORMAT_VERSION + 1));
+
+        // 版本1的文件以(m/z, 强度)元组存放峰，不能按当前格式读入
+        let mut legacy = bytes.clone();
+        legacy[8..12].copy_from_slice(&1u32.to_le_bytes());
+        assert_invalid(write(&legacy), "version 1");
+
+        let mut wrong_count = bytes.clone();
+        wrong_count[20..28].copy_from_slice(&3u64.to_le_bytes());
+        assert_invalid(write(&wrong_count), "3 peaks");
+
+        assert!(matches!(
+            BinnedSpectraIndex::load(dir.path().join("missing.bidx")),
+            Err(CoreError::Io { .. })
+        ));
+    }
+}
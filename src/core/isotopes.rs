@@ -0,0 +1,289 @@
+//! 同位素峰型预测
+//!
+//! 由分子式或averagine模型预测同位素峰簇的m/z和相对强度：
+//! - 元素的同位素质量和天然丰度取自IUPAC表（[`ELEMENTS`]）
+//! - 各元素的同位素分布按名义质量偏移（相对单同位素的整数质量差）卷积，每次卷积后只保留前
+//!   `n_isotopes`个偏移，高偏移不会影响低偏移，因此截断不损失精度；同一偏移内的精细结构合并为
+//!   丰度加权的平均质量
+//! - averagine模型按Senko等人的平均氨基酸组成缩放到给定单同位素质量，取整后用氢原子数补足质量差，
+//!   结果整体平移使单同位素峰与给定质量一致
+//!
+//! 相对强度以最高峰为1。
+
+use crate::core::types::*;
+use crate::utils::mass::{default_adduct, mz_from_neutral};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 默认预测的同位素峰数
+pub const DEFAULT_ISOTOPE_COUNT: usize = 5;
+
+/// averagine模型中一个平均氨基酸残基的元素组成 (Senko et al., 1995)
+pub const AVERAGINE: &[(&str, f64)] = &[("C", 4.9384), ("H", 7.7583), ("N", 1.3577), ("O", 1.4773), ("S", 0.0417)];
+
+/// 元素及其稳定同位素
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Element {
+    /// 元素符号
+    pub symbol: &'static str,
+    /// (同位素质量, 天然丰度)，按质量升序，第一个为单同位素
+    pub isotopes: &'static [(f64, f64)],
+}
+
+/// 元素表
+pub const ELEMENTS: &[Element] = &[
+    Element { symbol: "H", isotopes: &[(1.007_825_032_23, 0.999_885), (2.014_101_778_12, 0.000_115)] },
+    Element { symbol: "C", isotopes: &[(12.0, 0.9893), (13.003_354_835_07, 0.0107)] },
+    Element { symbol: "N", isotopes: &[(14.003_074_004_43, 0.996_36), (15.000_108_898_88, 0.003_64)] },
+    Element {
+        symbol: "O",
+        isotopes: &[(15.994_914_619_57, 0.997_57), (16.999_131_756_50, 0.000_38), (17.999_159_612_86, 0.002_05)],
+    },
+    Element { symbol: "F", isotopes: &[(18.998_403_162_73, 1.0)] },
+    Element { symbol: "Na", isotopes: &[(22.989_769_282_0, 1.0)] },
+    Element {
+        symbol: "Si",
+        isotopes: &[(27.976_926_534_65, 0.922_23), (28.976_494_664_9, 0.046_85), (29.973_770_136, 0.030_92)],
+    },
+    Element { symbol: "P", isotopes: &[(30.973_761_998_42, 1.0)] },
+    Element {
+        symbol: "S",
+        isotopes: &[
+            (31.972_071_174_4, 0.9499),
+            (32.971_458_909_8, 0.0075),
+            (33.967_867_004, 0.0425),
+            (35.967_080_71, 0.0001),
+        ],
+    },
+    Element { symbol: "Cl", isotopes: &[(34.968_852_682, 0.7576), (36.965_902_602, 0.2424)] },
+    Element {
+        symbol: "K",
+        isotopes: &[(38.963_706_486_4, 0.932_581), (39.963_998_166, 0.000_117), (40.961_825_257_9, 0.067_302)],
+    },
+    Element { symbol: "Br", isotopes: &[(78.918_337_6, 0.5069), (80.916_289_7, 0.4931)] },
+    Element { symbol: "I", isotopes: &[(126.904_471_9, 1.0)] },
+];
+
+/// 按符号查找元素
+pub fn element(symbol: &str) -> Option<&'static Element> {
+    ELEMENTS.iter().find(|element| element.symbol == symbol)
+}
+
+/// 解析"C6H12O6"形式的分子式，返回(元素, 原子数)，同一元素出现多次时合并
+///
+/// 元素符号为大写字母加可选的小写字母，省略的原子数为1；不支持括号和电荷记号。
+pub fn parse_formula(formula: &str) -> CoreResult<Vec<(&'static Element, u32)>> {
+    let invalid = |reason: &str| CoreError::InvalidFormat(format!("Invalid formula '{}': {}", formula, reason));
+    let mut composition: Vec<(&'static Element, u32)> = Vec::new();
+    let mut chars = formula.trim().chars().peekable();
+    while let Some(first) = chars.next() {
+        if !first.is_ascii_uppercase() {
+            return Err(invalid(&format!("unexpected '{}'", first)));
+        }
+        let mut symbol = first.to_string();
+        while let Some(&next) = chars.peek().filter(|c| c.is_ascii_lowercase()) {
+            symbol.push(next);
+            chars.next();
+        }
+        let mut digits = String::new();
+        while let Some(&next) = chars.peek().filter(|c| c.is_ascii_digit()) {
+            digits.push(next);
+            chars.next();
+        }
+        let count = if digits.is_empty() { 1 } else { digits.parse().map_err(|_| invalid("atom count too large"))? };
+        let element = element(&symbol).ok_or_else(|| invalid(&format!("unknown element {}", symbol)))?;
+        match composition.iter_mut().find(|(known, _)| known.symbol == element.symbol) {
+            Some((_, total)) => *total += count,
+            None => composition.push((element, count)),
+        }
+    }
+    if composition.is_empty() {
+        return Err(invalid("no elements"));
+    }
+    Ok(composition)
+}
+
+/// 分子式的单同位素中性质量
+pub fn monoisotopic_mass(formula: &str) -> CoreResult<f64> {
+    Ok(parse_formula(formula)?.iter().map(|(element, count)| element.isotopes[0].0 * *count as f64).sum())
+}
+
+/// 按名义质量偏移排列的同位素分布：每项为(丰度, 丰度加权平均质量)
+type Distribution = Vec<(f64, f64)>;
+
+/// 卷积两个分布，只保留前`limit`个偏移
+fn convolve(a: &Distribution, b: &Distribution, limit: usize) -> Distribution {
+    let mut result = vec![(0.0, 0.0); (a.len() + b.len() - 1).min(limit)];
+    for (i, &(abundance_a, mass_a)) in a.iter().enumerate() {
+        for (j, &(abundance_b, mass_b)) in b.iter().enumerate().take(limit.saturating_sub(i)) {
+            let abundance = abundance_a * abundance_b;
+            result[i + j].0 += abundance;
+            result[i + j].1 += abundance * (mass_a + mass_b);
+        }
+    }
+    for entry in &mut result {
+        if entry.0 > 0.0 {
+            entry.1 /= entry.0;
+        }
+    }
+    result
+}
+
+/// 单个原子的同位素分布
+fn atom_distribution(element: &Element) -> Distribution {
+    let monoisotopic = element.isotopes[0].0;
+    let mut distribution = Vec::new();
+    for &(mass, abundance) in element.isotopes {
+        let offset = (mass - monoisotopic).round() as usize;
+        if distribution.len() <= offset {
+            distribution.resize(offset + 1, (0.0, 0.0));
+        }
+        distribution[offset] = (abundance, mass);
+    }
+    distribution
+}
+
+/// 元素组成的中性同位素分布，平方求幂计算每种元素`count`个原子的分布
+fn composition_distribution(composition: &[(&Element, u32)], limit: usize) -> Distribution {
+    let mut total: Distribution = vec![(1.0, 0.0)];
+    for &(element, count) in composition {
+        let mut power = atom_distribution(element);
+        let mut remaining = count;
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                total = convolve(&total, &power, limit);
+            }
+            remaining >>= 1;
+            if remaining > 0 {
+                power = convolve(&power, &power, limit);
+            }
+        }
+    }
+    total
+}
+
+/// 把中性分布换算为(m/z, 相对强度)，电荷为0时给出中性质量
+fn to_pattern(distribution: Distribution, charge: Charge) -> CoreResult<Vec<(f64, f64)>> {
+    let max = distribution.iter().map(|entry| entry.0).fold(0.0, f64::max);
+    distribution
+        .into_iter()
+        .map(|(abundance, mass)| {
+            let mz = if charge == 0 { mass } else { mz_from_neutral(mass, charge, default_adduct(charge))? };
+            Ok((mz, abundance / max))
+        })
+        .collect()
+}
+
+/// 由分子式预测前`n_isotopes`个同位素峰的(m/z, 相对强度)
+///
+/// 离子按质子化（负电荷为去质子化）计算，`charge`为0时返回中性质量。
+pub fn predict_pattern(formula: &str, charge: Charge, n_isotopes: usize) -> CoreResult<Vec<(f64, f64)>> {
+    let composition = parse_formula(formula)?;
+    to_pattern(composition_distribution(&composition, n_isotopes.max(1)), charge)
+}
+
+/// averagine模型下单同位素中性质量为`monoisotopic_mass`的分子的同位素峰型
+pub fn averagine_pattern(monoisotopic_mass: f64, charge: Charge, n_isotopes: usize) -> CoreResult<Vec<(f64, f64)>> {
+    if !(monoisotopic_mass.is_finite() && monoisotopic_mass > 0.0) {
+        return Err(CoreError::InvalidFormat(format!("Invalid monoisotopic mass: {}", monoisotopic_mass)));
+    }
+    let element_of = |symbol: &str| element(symbol).expect("averagine elements are in the element table");
+    let unit_mass: f64 = AVERAGINE.iter().map(|&(symbol, count)| element_of(symbol).isotopes[0].0 * count).sum();
+    let units = monoisotopic_mass / unit_mass;
+
+    let hydrogen = element_of("H");
+    let mut composition: Vec<(&Element, u32)> = AVERAGINE
+        .iter()
+        .filter(|(symbol, _)| *symbol != "H")
+        .map(|&(symbol, count)| (element_of(symbol), (count * units).round() as u32))
+        .collect();
+    let heavy_mass: f64 = composition.iter().map(|(element, count)| element.isotopes[0].0 * *count as f64).sum();
+    let hydrogens = ((monoisotopic_mass - heavy_mass) / hydrogen.isotopes[0].0).round().max(0.0) as u32;
+    composition.push((hydrogen, hydrogens));
+
+    let mut distribution = composition_distribution(&composition, n_isotopes.max(1));
+    let shift = monoisotopic_mass - distribution[0].1;
+    for entry in &mut distribution {
+        entry.1 += shift;
+    }
+    to_pattern(distribution, charge)
+}
+
+/// Python接口：由分子式预测同位素峰型，返回(m/z, 相对强度)列表
+#[cfg(feature = "python")]
+#[pyfunction(name = "predict_pattern")]
+#[pyo3(signature = (formula, charge=1, n_isotopes=DEFAULT_ISOTOPE_COUNT))]
+pub fn py_predict_pattern(formula: &str, charge: Charge, n_isotopes: usize) -> PyResult<Vec<(f64, f64)>> {
+    predict_pattern(formula, charge, n_isotopes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
+/// Python接口：averagine模型的同位素峰型，返回(m/z, 相对强度)列表
+#[cfg(feature = "python")]
+#[pyfunction(name = "averagine_pattern")]
+#[pyo3(signature = (monoisotopic_mass, charge=1, n_isotopes=DEFAULT_ISOTOPE_COUNT))]
+pub fn py_averagine_pattern(monoisotopic_mass: f64, charge: Charge, n_isotopes: usize) -> PyResult<Vec<(f64, f64)>> {
+    averagine_pattern(monoisotopic_mass, charge, n_isotopes)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::mass::PROTON_MASS;
+
+    fn assert_pattern(pattern: &[(f64, f64)], expected: &[f64]) {
+        assert_eq!(pattern.len(), expected.len());
+        for (&(_, relative), &reference) in pattern.iter().zip(expected) {
+            assert!((relative - reference).abs() < 0.01, "{:?} vs {:?}", pattern, expected);
+        }
+    }
+
+    #[test]
+    fn test_glucose_pattern() {
+        // 葡萄糖[M+H]+：100 : 6.86 : 1.43 : 0.09
+        let pattern = predict_pattern("C6H12O6", 1, 4).unwrap();
+        assert_pattern(&pattern, &[1.0, 0.0686, 0.0143, 0.0009]);
+        assert!((pattern[0].0 - (180.063_388 + PROTON_MASS)).abs() < 1e-5);
+        assert!((pattern[1].0 - pattern[0].0 - 1.0034).abs() < 1e-3);
+        assert!((monoisotopic_mass("C6H12O6").unwrap() - 180.063_388).abs() < 1e-5);
+
+        // 电荷为0时为中性质量，同一元素可以分开书写
+        let neutral = predict_pattern("CH3CH2OH", 0, 2).unwrap();
+        assert!((neutral[0].0 - 46.041_865).abs() < 1e-5);
+        let doubly = predict_pattern("C6H12O6", -2, 1).unwrap();
+        assert!((doubly[0].0 - (180.063_388 - 2.0 * PROTON_MASS) / 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_averagine_peptide_patterns() {
+        // 1000 Da的肽段M+1约为单同位素峰的54%；2000 Da时M+1成为最高峰
+        let pattern = averagine_pattern(1000.0, 1, 5).unwrap();
+        assert_pattern(&pattern, &[1.0, 0.5356, 0.1675, 0.0385, 0.0071]);
+        assert!((pattern[0].0 - (1000.0 + PROTON_MASS)).abs() < 1e-9);
+        let heavy = averagine_pattern(2000.0, 2, 4).unwrap();
+        assert_pattern(&heavy, &[0.9229, 1.0, 0.6293, 0.2902]);
+        assert!((heavy[1].0 - heavy[0].0 - 1.0028 / 2.0).abs() < 1e-3);
+        assert!(averagine_pattern(-1.0, 1, 3).is_err());
+    }
+
+    #[test]
+    fn test_formula_parsing_and_pruning() {
+        assert!(parse_formula("").is_err());
+        assert!(parse_formula("C6H12O6+").is_err());
+        assert!(parse_formula("c6").is_err());
+        assert!(parse_formula("Xe2").is_err());
+        let composition = parse_formula("NaCl2Cl").unwrap();
+        assert_eq!(composition.iter().map(|(element, count)| (element.symbol, *count)).collect::<Vec<_>>(), vec![("Na", 1), ("Cl", 3)]);
+
+        // 截断只影响偏移数：前几个峰与完整计算一致
+        let full = predict_pattern("C254H377N65O75S6", 1, 12).unwrap();
+        let pruned = predict_pattern("C254H377N65O75S6", 1, 3).unwrap();
+        let scale = full[..3].iter().map(|peak| peak.1).fold(0.0, f64::max);
+        for (a, b) in full.iter().zip(&pruned) {
+            assert!((a.0 - b.0).abs() < 1e-9 && (a.1 / scale - b.1).abs() < 1e-9);
+        }
+        // 氯的M+2峰约为M的32%
+        assert_pattern(&predict_pattern("Cl", 0, 3).unwrap(), &[1.0, 0.0, 0.32]);
+    }
+}
@@ -4,9 +4,38 @@
 //! mass spectrometry data with high performance memory management
 //! and optimized algorithms for common operations.
 
+use numpy::ndarray::Array2;
+use numpy::{AllowTypeChange, IntoPyArray, PyArray1, PyArray2, PyArrayLike1, PyReadonlyArray1};
 use pyo3::prelude::*;
-use pyo3::types::{PyList, PyTuple};
-use std::cmp::Ordering;
+use pyo3::types::PyList;
+use std::borrow::Cow;
+
+pub mod types;
+pub mod spectrum;
+pub mod store;
+pub mod filter;
+pub mod compare;
+pub mod peak_width;
+pub mod centroid;
+pub mod deisotope;
+pub mod scan_table;
+pub mod index_store;
+pub mod transform;
+pub mod fingerprint;
+pub mod quality;
+pub mod validation;
+pub mod noise;
+pub mod isotopes;
+pub mod vectorize;
+pub mod ms_object;
+
+#[cfg(test)]
+pub(crate) mod test_spectrum;
+
+pub use types::{CoreError, CoreResult};
+pub use filter::SpectrumFilter;
+pub use compare::SpectrumComparison;
+pub use transform::IntensityTransform;
 
 /// High-performance peak data structure
 ///
@@ -50,6 +79,8 @@ pub struct Spectrum {
     pub retention_time: f64,
     peaks: Vec<Peak>,
     sorted: bool,
+    /// Intensity transforms applied so far, in order
+    transforms: Vec<IntensityTransform>,
 }
 
 #[pymethods]
@@ -63,40 +94,26 @@ impl Spectrum {
             retention_time: 0.0,
             peaks: Vec::new(),
             sorted: true,
+            transforms: Vec::new(),
         }
     }
 
     /// Create a spectrum with peak data
+    ///
+    /// Accepts numpy arrays (read in place when contiguous) or any sequence of numbers.
     #[staticmethod]
-    fn with_peaks(level: u8, mz_array: Vec<f64>, intensity_array: Vec<f64>) -> PyResult<Self> {
-        if mz_array.len() != intensity_array.len() {
-            return Err(pyo3::exceptions::PyValueError::new_err(
-                "MZ and intensity arrays must have the same length"
-            ));
-        }
-
-        let mut peaks: Vec<Peak> = mz_array
-            .into_iter()
-            .zip(intensity_array.into_iter())
-            .map(|(mz, intensity)| Peak::new(mz, intensity))
-            .collect();
-
-        // Check if already sorted
-        let sorted = peaks.windows(2).all(|w| w[0].mz <= w[1].mz);
-
-        Ok(Self {
-            level,
-            scan_number: 0,
-            retention_time: 0.0,
-            peaks,
-            sorted,
-        })
+    fn with_peaks(
+        level: u8,
+        mz_array: PyArrayLike1<'_, f64, AllowTypeChange>,
+        intensity_array: PyArrayLike1<'_, f64, AllowTypeChange>,
+    ) -> PyResult<Self> {
+        Self::from_arrays(level, &contiguous(&mz_array), &contiguous(&intensity_array))
     }
 
     /// Get peak data as Python list of tuples
     #[getter]
     fn peaks(&self, py: Python) -> PyResult<Py<PyList>> {
-        let list = PyList::empty_bound(py);
+        let list = PyList::empty(py);
         for peak in &self.peaks {
             list.append((peak.mz, peak.intensity))?;
         }
@@ -105,13 +122,13 @@ impl Spectrum {
 
     /// Get number of peaks
     #[getter]
-    fn peak_count(&self) -> usize {
+    pub fn peak_count(&self) -> usize {
         self.peaks.len()
     }
 
     /// Get total ion current (sum of intensities)
     #[getter]
-    fn total_ion_current(&self) -> f64 {
+    pub fn total_ion_current(&self) -> f64 {
         self.peaks.iter().map(|peak| peak.intensity).sum()
     }
 
@@ -135,38 +152,35 @@ impl Spectrum {
     }
 
     /// Add a single peak to the spectrum
-    fn add_peak(&mut self, mz: f64, intensity: f64) {
+    ///
+    /// Peaks appended in ascending m/z order keep the spectrum sorted.
+    pub fn add_peak(&mut self, mz: f64, intensity: f64) {
+        self.sorted &= self.peaks.last().is_none_or(|last| last.mz <= mz);
         self.peaks.push(Peak::new(mz, intensity));
-        self.sorted = false;
     }
 
     /// Add multiple peaks efficiently
-    pub fn add_peaks(&mut self, mz_array: Vec<f64>, intensity_array: Vec<f64>) -> PyResult<()> {
-        if mz_array.len() != intensity_array.len() {
-            return Err(pyo3::exceptions::PyValueError::new_err(
-                "MZ and intensity arrays must have the same length"
-            ));
-        }
-
-        let new_peaks: Vec<Peak> = mz_array
-            .into_iter()
-            .zip(intensity_array.into_iter())
-            .map(|(mz, intensity)| Peak::new(mz, intensity))
-            .collect();
-
-        self.peaks.extend(new_peaks);
-        self.sorted = false;
-        Ok(())
+    pub fn add_peaks(
+        &mut self,
+        mz_array: PyArrayLike1<'_, f64, AllowTypeChange>,
+        intensity_array: PyArrayLike1<'_, f64, AllowTypeChange>,
+    ) -> PyResult<()> {
+        self.extend_from_arrays(&contiguous(&mz_array), &contiguous(&intensity_array))
     }
 
     /// Sort peaks by m/z (if not already sorted)
     pub fn sort_peaks(&mut self) {
         if !self.sorted {
-            self.peaks.sort_by(|a, b| a.mz.partial_cmp(&b.mz).unwrap());
+            self.peaks.sort_by(|a, b| a.mz.total_cmp(&b.mz));
             self.sorted = true;
         }
     }
 
+    /// Whether peaks are known to be sorted by m/z
+    pub fn is_sorted(&self) -> bool {
+        self.sorted
+    }
+
     /// Clear all peaks
     fn clear_peaks(&mut self) {
         self.peaks.clear();
@@ -174,6 +188,9 @@ impl Spectrum {
     }
 
     /// Filter peaks by intensity threshold
+    ///
+    /// Filters keep the relative order of the remaining peaks, so the sorted
+    /// flag is unchanged.
     fn filter_by_intensity(&mut self, threshold: f64) -> usize {
         let initial_count = self.peaks.len();
         self.peaks.retain(|peak| peak.intensity >= threshold);
@@ -187,13 +204,46 @@ impl Spectrum {
         initial_count - self.peaks.len()
     }
 
+    /// Keep the n most intense peaks, returning the number of removed peaks
+    ///
+    /// Ties at the cutoff are broken in favour of the earlier peak (lower m/z
+    /// when sorted), so exactly n peaks remain.
+    pub fn keep_top_n(&mut self, n: usize) -> usize {
+        let initial_count = self.peaks.len();
+        if n >= initial_count {
+            return 0;
+        }
+
+        let mut order: Vec<usize> = (0..initial_count).collect();
+        // Stable sort keeps the earlier peak first among equal intensities
+        order.sort_by(|&a, &b| self.peaks[b].intensity.total_cmp(&self.peaks[a].intensity));
+        let mut keep = vec![false; initial_count];
+        for &index in &order[..n] {
+            keep[index] = true;
+        }
+
+        let mut flags = keep.into_iter();
+        self.peaks.retain(|_| flags.next().unwrap_or(false));
+        initial_count - self.peaks.len()
+    }
+
+    /// Drop peaks below fraction * base peak intensity, returning the number
+    /// of removed peaks
+    ///
+    /// Peaks exactly at the threshold are kept. Raises ValueError unless
+    /// 0 <= fraction <= 1.
+    pub fn filter_by_relative_intensity(&mut self, fraction: f64) -> PyResult<usize> {
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "fraction must be between 0 and 1, got {}", fraction
+            )));
+        }
+        Ok(self.filter_by_intensity(fraction * self.base_peak_intensity()))
+    }
+
     /// Get peaks in m/z range (returns new spectrum)
     fn get_mz_range(&self, min_mz: f64, max_mz: f64) -> Spectrum {
-        let filtered_peaks: Vec<Peak> = self.peaks
-            .iter()
-            .filter(|peak| peak.mz >= min_mz && peak.mz <= max_mz)
-            .cloned()
-            .collect();
+        let filtered_peaks = self.peaks_in_range(min_mz, max_mz);
 
         Spectrum {
             level: self.level,
@@ -201,36 +251,45 @@ impl Spectrum {
             retention_time: self.retention_time,
             peaks: filtered_peaks,
             sorted: self.sorted, // Preserves sorted status
+            transforms: self.transforms.clone(),
         }
     }
 
     /// Find peaks within tolerance of target m/z
     fn find_peaks_in_tolerance(&self, target_mz: f64, tolerance: f64) -> Vec<(f64, f64)> {
-        self.peaks
-            .iter()
-            .filter(|peak| (peak.mz - target_mz).abs() <= tolerance)
+        self.peaks_in_range(target_mz - tolerance, target_mz + tolerance)
+            .into_iter()
             .map(|peak| (peak.mz, peak.intensity))
             .collect()
     }
 
-    /// Get m/z array
+    /// Find peaks within `ppm` parts-per-million of target m/z
+    ///
+    /// The window is converted to an absolute width at the target, so it
+    /// grows with m/z (10 ppm is 0.002 Da at m/z 200 but 0.02 Da at 2000).
+    fn find_peaks_in_ppm(&self, target_mz: f64, ppm: f64) -> Vec<(f64, f64)> {
+        let tolerance = types::Tolerance::PPM(ppm).tolerance_at_mz(target_mz);
+        self.find_peaks_in_tolerance(target_mz, tolerance)
+    }
+
+    /// Get m/z array as a numpy array
     #[getter]
-    fn mz_array(&self, py: Python) -> PyResult<Py<PyList>> {
-        let list = PyList::empty_bound(py);
-        for peak in &self.peaks {
-            list.append(peak.mz)?;
-        }
-        Ok(list.into())
+    fn mz_array<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        self.peaks.iter().map(|peak| peak.mz).collect::<Vec<_>>().into_pyarray(py)
     }
 
-    /// Get intensity array
+    /// Get intensity array as a numpy array
     #[getter]
-    fn intensity_array(&self, py: Python) -> PyResult<Py<PyList>> {
-        let list = PyList::empty_bound(py);
-        for peak in &self.peaks {
-            list.append(peak.intensity)?;
-        }
-        Ok(list.into())
+    fn intensity_array<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        self.peaks.iter().map(|peak| peak.intensity).collect::<Vec<_>>().into_pyarray(py)
+    }
+
+    /// Get peaks as an (N, 2) numpy array of (m/z, intensity) rows
+    fn peaks_numpy<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let flat: Vec<f64> = self.peaks.iter().flat_map(|peak| [peak.mz, peak.intensity]).collect();
+        Array2::from_shape_vec((self.peaks.len(), 2), flat)
+            .map(|array| array.into_pyarray(py))
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
     }
 
     /// Normalize spectrum to maximum intensity
@@ -244,6 +303,63 @@ impl Spectrum {
         max_intensity
     }
 
+    /// Transform intensities ("sqrt", "log2(x+1)" or "rank")
+    ///
+    /// Raises ValueError if a transform was already applied, unless force=True.
+    #[pyo3(signature = (method, force=false))]
+    fn transform_intensities(&mut self, method: &str, force: bool) -> PyResult<()> {
+        let transform = IntensityTransform::from_name(method)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        if !self.transforms.is_empty() && !force {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                CoreError::TransformAlreadyApplied {
+                    applied: self.intensity_transforms().join(","),
+                }.to_string()
+            ));
+        }
+
+        let mut intensities: Vec<f64> = self.peaks.iter().map(|peak| peak.intensity).collect();
+        transform.apply(&mut intensities);
+        for (peak, intensity) in self.peaks.iter_mut().zip(intensities) {
+            peak.intensity = intensity;
+        }
+        self.transforms.push(transform);
+        Ok(())
+    }
+
+    /// Undo the most recent intensity transform, returning its name
+    fn inverse_intensity_transform(&mut self) -> PyResult<String> {
+        let transform = *self.transforms.last().ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err("No intensity transform has been applied")
+        })?;
+
+        let mut intensities: Vec<f64> = self.peaks.iter().map(|peak| peak.intensity).collect();
+        transform.invert(&mut intensities)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        for (peak, intensity) in self.peaks.iter_mut().zip(intensities) {
+            peak.intensity = intensity;
+        }
+        self.transforms.pop();
+        Ok(transform.name().to_string())
+    }
+
+    /// Content hash robust to float noise, for duplicate detection
+    ///
+    /// Uses the top_n most intense peaks with m/z quantized to mz_precision_da
+    /// and intensities reduced to 8-bit levels; returns a SHA-1 hex string.
+    #[pyo3(signature = (mz_precision_da=0.01, top_n=30))]
+    fn fingerprint(&self, mz_precision_da: f64, top_n: usize) -> PyResult<String> {
+        let peaks: Vec<(f64, f64)> = self.peaks.iter().map(|peak| (peak.mz, peak.intensity)).collect();
+        fingerprint::fingerprint(&peaks, mz_precision_da, top_n)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// Names of the intensity transforms applied so far
+    #[getter]
+    fn intensity_transforms(&self) -> Vec<&'static str> {
+        self.transforms.iter().map(|t| t.name()).collect()
+    }
+
     /// String representation
     fn __repr__(&self) -> String {
         format!(
@@ -261,9 +377,33 @@ impl Spectrum {
 }
 
 impl Spectrum {
-    /// Check if peaks are sorted
-    pub fn is_sorted(&self) -> bool {
-        self.sorted
+    /// Create a spectrum from parallel m/z and intensity slices
+    pub fn from_arrays(level: u8, mz_array: &[f64], intensity_array: &[f64]) -> PyResult<Self> {
+        let mut spectrum = Self::new(level);
+        spectrum.extend_from_arrays(mz_array, intensity_array)?;
+        Ok(spectrum)
+    }
+
+    /// Append peaks from parallel m/z and intensity slices
+    pub fn extend_from_arrays(&mut self, mz_array: &[f64], intensity_array: &[f64]) -> PyResult<()> {
+        if mz_array.len() != intensity_array.len() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "MZ and intensity arrays must have the same length"
+            ));
+        }
+
+        if let (Some(last), Some(&first)) = (self.peaks.last(), mz_array.first()) {
+            self.sorted &= last.mz <= first;
+        }
+        self.sorted &= mz_array.windows(2).all(|pair| pair[0] <= pair[1]);
+        self.peaks.reserve(mz_array.len());
+        self.peaks.extend(
+            mz_array
+                .iter()
+                .zip(intensity_array)
+                .map(|(&mz, &intensity)| Peak::new(mz, intensity)),
+        );
+        Ok(())
     }
 
     /// Get internal peaks reference for efficient processing
@@ -277,28 +417,16 @@ impl Spectrum {
     }
 
     /// Binary search for peak range (requires sorted peaks)
+    ///
+    /// Returns the half-open index range `start..end` of peaks with
+    /// `min_mz <= mz <= max_mz`, or None if no peak falls in the range.
     pub fn find_peak_range(&self, min_mz: f64, max_mz: f64) -> Option<(usize, usize)> {
         if !self.sorted || self.peaks.is_empty() {
             return None;
         }
 
-        let start = self.peaks.binary_search_by(|peak| {
-            if peak.mz < min_mz {
-                Ordering::Less
-            } else if peak.mz >= min_mz {
-                Ordering::Greater
-            } else {
-                Ordering::Equal
-            }
-        }).unwrap_or_else(|idx| idx);
-
-        let end = self.peaks.binary_search_by(|peak| {
-            if peak.mz <= max_mz {
-                Ordering::Less
-            } else {
-                Ordering::Greater
-            }
-        }).unwrap_or_else(|idx| idx);
+        let start = self.peaks.partition_point(|peak| peak.mz < min_mz);
+        let end = self.peaks.partition_point(|peak| peak.mz <= max_mz);
 
         if start < end {
             Some((start, end))
@@ -306,11 +434,35 @@ impl Spectrum {
             None
         }
     }
+
+    /// Peaks with `min_mz <= mz <= max_mz`, using binary search when sorted
+    fn peaks_in_range(&self, min_mz: f64, max_mz: f64) -> Vec<Peak> {
+        if self.sorted {
+            return self
+                .find_peak_range(min_mz, max_mz)
+                .map_or_else(Vec::new, |(start, end)| self.peaks[start..end].to_vec());
+        }
+        self.peaks
+            .iter()
+            .filter(|peak| peak.mz >= min_mz && peak.mz <= max_mz)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Borrow the array data directly when contiguous, otherwise copy it
+fn contiguous<'a>(array: &'a PyReadonlyArray1<'_, f64>) -> Cow<'a, [f64]> {
+    match array.as_slice() {
+        Ok(slice) => Cow::Borrowed(slice),
+        Err(_) => Cow::Owned(array.as_array().to_vec()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use numpy::{PyArrayMethods, PyUntypedArrayMethods};
+    use crate::core::test_spectrum::pseudo_random;
 
     #[test]
     fn test_spectrum_creation() {
@@ -349,10 +501,185 @@ mod tests {
         let mz = vec![100.0, 200.0, 300.0];
         let intensity = vec![1000.0, 2000.0, 1500.0];
 
-        let spectrum = Spectrum::with_peaks(2, mz, intensity).unwrap();
+        let spectrum = Spectrum::from_arrays(2, &mz, &intensity).unwrap();
 
         assert_eq!(spectrum.peak_count(), 3);
         assert!(spectrum.is_sorted());
         assert_eq!(spectrum.total_ion_current(), 4500.0);
     }
-}
\ No newline at end of file
+
+    fn naive_range(peaks: &[Peak], min_mz: f64, max_mz: f64) -> Option<(usize, usize)> {
+        let indices: Vec<usize> = (0..peaks.len()).filter(|&i| peaks[i].mz >= min_mz && peaks[i].mz <= max_mz).collect();
+        indices.first().map(|&start| (start, indices[indices.len() - 1] + 1))
+    }
+
+    #[test]
+    fn test_find_peak_range_matches_linear_scan() {
+        let mut seed = 7;
+        for trial in 0..200 {
+            let count = trial % 25;
+            // Round to a coarse grid so duplicate m/z values are common
+            let mut mz: Vec<f64> = (0..count).map(|_| (100.0 + 20.0 * pseudo_random(&mut seed)).round()).collect();
+            mz.sort_by(|a, b| a.total_cmp(b));
+            let intensity = vec![1.0; count];
+            let spectrum = Spectrum::from_arrays(2, &mz, &intensity).unwrap();
+            assert!(spectrum.is_sorted());
+
+            for _ in 0..20 {
+                let a = 90.0 + 40.0 * pseudo_random(&mut seed);
+                let b = 90.0 + 40.0 * pseudo_random(&mut seed);
+                let (min_mz, max_mz) = if pseudo_random(&mut seed) < 0.3 { (a.round(), b.round()) } else { (a, b) };
+                let expected = naive_range(&spectrum.peaks, min_mz, max_mz);
+                assert_eq!(spectrum.find_peak_range(min_mz, max_mz), expected, "range {}..{} in {:?}", min_mz, max_mz, spectrum.peaks);
+
+                let expected_peaks: Vec<(f64, f64)> = spectrum.peaks.iter()
+                    .filter(|peak| peak.mz >= min_mz && peak.mz <= max_mz)
+                    .map(|peak| (peak.mz, peak.intensity))
+                    .collect();
+                let in_range: Vec<(f64, f64)> = spectrum.get_mz_range(min_mz, max_mz).peaks.iter().map(|peak| (peak.mz, peak.intensity)).collect();
+                assert_eq!(in_range, expected_peaks);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_peak_range_edges() {
+        let spectrum = Spectrum::from_arrays(2, &[100.0, 200.0, 200.0, 300.0], &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        // Bounds are inclusive on both ends, duplicates are all included
+        assert_eq!(spectrum.find_peak_range(200.0, 200.0), Some((1, 3)));
+        assert_eq!(spectrum.find_peak_range(100.0, 300.0), Some((0, 4)));
+        // Ranges fully outside the data, or between peaks
+        assert_eq!(spectrum.find_peak_range(10.0, 50.0), None);
+        assert_eq!(spectrum.find_peak_range(400.0, 500.0), None);
+        assert_eq!(spectrum.find_peak_range(210.0, 290.0), None);
+        assert_eq!(spectrum.find_peak_range(300.0, 100.0), None);
+
+        assert_eq!(spectrum.find_peaks_in_tolerance(200.5, 0.5), vec![(200.0, 2.0), (200.0, 3.0)]);
+        assert!(spectrum.find_peaks_in_tolerance(250.0, 10.0).is_empty());
+
+        // The same ppm window is ten times wider at m/z 2000 than at 200
+        let wide = Spectrum::from_arrays(2, &[200.0, 200.003, 2000.0, 2000.015], &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert_eq!(wide.find_peaks_in_ppm(200.0, 10.0), vec![(200.0, 1.0)]);
+        assert_eq!(wide.find_peaks_in_ppm(2000.0, 10.0), vec![(2000.0, 3.0), (2000.015, 4.0)]);
+
+        // Unsorted spectra fall back to a linear scan
+        let mut unsorted = Spectrum::new(2);
+        unsorted.add_peak(300.0, 1.0);
+        unsorted.add_peak(100.0, 2.0);
+        assert_eq!(unsorted.find_peak_range(0.0, 1000.0), None);
+        assert_eq!(unsorted.find_peaks_in_tolerance(100.0, 1.0), vec![(100.0, 2.0)]);
+        assert_eq!(unsorted.get_mz_range(50.0, 500.0).peak_count(), 2);
+    }
+
+    #[test]
+    fn test_sorted_flag_maintenance() {
+        let mut spectrum = Spectrum::new(2);
+        spectrum.add_peak(100.0, 1.0);
+        spectrum.add_peak(100.0, 2.0);
+        spectrum.extend_from_arrays(&[150.0, 200.0], &[3.0, 4.0]).unwrap();
+        assert!(spectrum.is_sorted());
+
+        assert_eq!(spectrum.filter_by_intensity(2.0), 1);
+        assert_eq!(spectrum.filter_by_mz_range(120.0, 250.0), 1);
+        assert!(spectrum.is_sorted());
+        assert_eq!(spectrum.find_peak_range(150.0, 200.0), Some((0, 2)));
+
+        spectrum.extend_from_arrays(&[300.0, 250.0], &[1.0, 1.0]).unwrap();
+        assert!(!spectrum.is_sorted());
+        // Filtering does not make an unsorted spectrum sorted
+        spectrum.filter_by_intensity(0.0);
+        assert!(!spectrum.is_sorted());
+        spectrum.sort_peaks();
+        spectrum.add_peak(50.0, 1.0);
+        assert!(!spectrum.is_sorted());
+    }
+
+    #[test]
+    fn test_unsorted_arrays_on_empty_spectrum() {
+        let mut spectrum = Spectrum::from_arrays(2, &[300.0, 100.0, 200.0], &[3.0, 1.0, 2.0]).unwrap();
+        assert!(!spectrum.is_sorted());
+        assert_eq!(spectrum.find_peaks_in_tolerance(100.0, 0.5), vec![(100.0, 1.0)]);
+        let range: Vec<f64> = spectrum.get_mz_range(150.0, 350.0).peaks_ref().iter().map(|peak| peak.mz).collect();
+        assert_eq!(range, vec![300.0, 200.0]);
+
+        spectrum.sort_peaks();
+        assert_eq!(spectrum.find_peak_range(150.0, 350.0), Some((1, 3)));
+    }
+
+    #[test]
+    fn test_keep_top_n_ties() {
+        let mz = vec![100.0, 200.0, 300.0, 400.0, 500.0];
+        let intensity = vec![5.0, 10.0, 5.0, 7.0, 5.0];
+
+        let mut spectrum = Spectrum::from_arrays(2, &mz, &intensity).unwrap();
+        // Cutoff falls inside the three-way tie at 5.0: the lowest m/z wins
+        assert_eq!(spectrum.keep_top_n(3), 2);
+        let kept: Vec<(f64, f64)> = spectrum.peaks_ref().iter().map(|peak| (peak.mz, peak.intensity)).collect();
+        assert_eq!(kept, vec![(100.0, 5.0), (200.0, 10.0), (400.0, 7.0)]);
+        assert!(spectrum.is_sorted());
+
+        let mut spectrum = Spectrum::from_arrays(2, &mz, &intensity).unwrap();
+        assert_eq!(spectrum.keep_top_n(10), 0);
+        assert_eq!(spectrum.keep_top_n(0), 5);
+        assert_eq!(spectrum.peak_count(), 0);
+
+        let mut spectrum = Spectrum::from_arrays(2, &mz, &intensity).unwrap();
+        // Peaks exactly at 0.5 * 10.0 are kept
+        assert_eq!(spectrum.filter_by_relative_intensity(0.5).unwrap(), 0);
+        assert_eq!(spectrum.filter_by_relative_intensity(0.6).unwrap(), 3);
+        assert_eq!(spectrum.peak_count(), 2);
+        assert!(spectrum.filter_by_relative_intensity(1.5).is_err());
+        assert!(spectrum.filter_by_relative_intensity(f64::NAN).is_err());
+    }
+
+    /// Run `f` with the GIL held, skipping when numpy is not installed
+    fn with_numpy(f: impl FnOnce(Python<'_>)) {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            if py.import("numpy").is_ok() {
+                f(py)
+            }
+        });
+    }
+
+    #[test]
+    fn test_numpy_round_trip_bit_exact() {
+        with_numpy(|py| {
+            let count = 100_000;
+            let mut seed = 11;
+            let mut mz: Vec<f64> = (0..count).map(|_| 100.0 + 1900.0 * pseudo_random(&mut seed)).collect();
+            mz.sort_by(|a, b| a.total_cmp(b));
+            let mut intensity: Vec<f64> = (0..count).map(|_| 1e7 * pseudo_random(&mut seed)).collect();
+            // Values that lose bits through any text or f32 conversion
+            intensity[..4].copy_from_slice(&[0.1, f64::MIN_POSITIVE / 2.0, -0.0, f64::MAX]);
+
+            let mz_input = PyArray1::from_slice(py, &mz);
+            let intensity_input = PyArray1::from_slice(py, &intensity);
+            let mut spectrum = Spectrum::with_peaks(2, mz_input.extract().unwrap(), intensity_input.extract().unwrap()).unwrap();
+            let mz_output = spectrum.mz_array(py);
+            let intensity_output = spectrum.intensity_array(py);
+            assert!(spectrum.is_sorted());
+
+            let bits = |values: &[f64]| values.iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(mz_output.readonly().as_slice().unwrap()), bits(&mz));
+            assert_eq!(bits(intensity_output.readonly().as_slice().unwrap()), bits(&intensity));
+
+            let peaks = spectrum.peaks_numpy(py).unwrap();
+            assert_eq!(peaks.shape(), [count, 2]);
+            let peaks = peaks.readonly();
+            assert_eq!(peaks.as_array()[[3, 1]].to_bits(), f64::MAX.to_bits());
+            assert_eq!(peaks.as_array()[[count - 1, 0]].to_bits(), mz[count - 1].to_bits());
+
+            // Strided views and integer lists are accepted too
+            let strided = py.eval(c"__import__('numpy').arange(10.0)[::2]", None, None).unwrap();
+            let integers = PyList::new(py, [1, 2, 3, 4, 5]).unwrap();
+            spectrum.clear_peaks();
+            spectrum.add_peaks(strided.extract().unwrap(), integers.extract().unwrap()).unwrap();
+            let added: Vec<(f64, f64)> = spectrum.peaks_ref().iter().map(|peak| (peak.mz, peak.intensity)).collect();
+            assert_eq!(added, vec![(0.0, 1.0), (2.0, 2.0), (4.0, 3.0), (6.0, 4.0), (8.0, 5.0)]);
+
+            let short = PyArray1::from_slice(py, &[1.0]);
+            assert!(spectrum.add_peaks(short.extract().unwrap(), integers.extract().unwrap()).is_err());
+        });
+    }
+}
@@ -5,12 +5,23 @@
 
 use crate::core::spectrum::{Spectrum, PrecursorInfo, ScanInfo};
 use crate::core::types::*;
-use std::collections::HashMap;
+use crate::core::transform::IntensityTransform;
+use crate::core::quality::QualityScore;
+use crate::core::noise::{denoise, estimate_noise, NoiseEstimate};
+use crate::core::deisotope::{deisotope_with_charges, DEFAULT_MAX_CHARGE};
 
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
 #[cfg(feature = "python")]
-use pyo3::types::{PyList, PyTuple, PyDict, PyAny};
+use pyo3::types::{PyList, PyDict};
+#[cfg(feature = "python")]
+use numpy::{IntoPyArray, PyArray1};
+#[cfg(feature = "python")]
+use crate::core::vectorize::py_binning;
+
+/// 以平行numpy数组返回的峰 (m/z, 强度)
+#[cfg(feature = "python")]
+type PeakArrays<'py> = (Bound<'py, PyArray1<f64>>, Bound<'py, PyArray1<f64>>);
 
 /// Python兼容的MSObject类
 #[cfg(feature = "python")]
@@ -74,12 +85,11 @@ impl MSObject {
     #[new]
     #[pyo3(signature = (level=1, peaks=None, precursor=None, scan=None, additional_info=None))]
     fn new(
-        py: Python,
         level: u8,
-        peaks: Option<&PyList>,
-        precursor: Option<&PyAny>,
-        scan: Option<&PyAny>,
-        additional_info: Option<&PyDict>,
+        peaks: Option<&Bound<'_, PyList>>,
+        precursor: Option<&Bound<'_, PyAny>>,
+        scan: Option<&Bound<'_, PyAny>>,
+        additional_info: Option<&Bound<'_, PyDict>>,
     ) -> PyResult<Self> {
         // 创建基础Spectrum对象
         let mut spectrum = Spectrum::new(level).map_err(|e| {
@@ -89,9 +99,7 @@ impl MSObject {
         // 解析peaks参数
         if let Some(peaks_list) = peaks {
             for item in peaks_list.iter() {
-                let tuple = item.downcast::<PyTuple>()?;
-                let mz = tuple.get_item(0)?.extract::<f64>()?;
-                let intensity = tuple.get_item(1)?.extract::<f64>()?;
+                let (mz, intensity) = extract_peak(&item)?;
                 spectrum.add_peak(mz, intensity).map_err(|e| {
                     PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
                 })?;
@@ -133,7 +141,7 @@ impl MSObject {
     /// 设置MS级别
     #[setter]
     fn set_level(&mut self, level: u8) -> PyResult<()> {
-        if level < constants::MIN_MS_LEVEL || level > constants::MAX_MS_LEVEL {
+        if !(constants::MIN_MS_LEVEL..=constants::MAX_MS_LEVEL).contains(&level) {
             return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
                 format!("Invalid MS level: {}. Must be between {} and {}", 
                        level, constants::MIN_MS_LEVEL, constants::MAX_MS_LEVEL)
@@ -146,8 +154,8 @@ impl MSObject {
     /// 获取质谱峰数据
     #[getter]
     fn peaks(&self, py: Python) -> PyResult<Py<PyList>> {
-        let list = PyList::empty_bound(py);
-        for (mz, intensity) in &self.spectrum.peaks {
+        let list = PyList::empty(py);
+        for (mz, intensity) in self.spectrum.peaks_iter() {
             list.append((mz, intensity))?;
         }
         Ok(list.into())
@@ -155,12 +163,10 @@ impl MSObject {
 
     /// 设置质谱峰数据
     #[setter]
-    fn set_peaks(&mut self, peaks: &PyList) -> PyResult<()> {
+    fn set_peaks(&mut self, peaks: &Bound<'_, PyList>) -> PyResult<()> {
         self.spectrum.clear_peaks();
         for item in peaks.iter() {
-            let tuple = item.downcast::<PyTuple>()?;
-            let mz = tuple.get_item(0)?.extract::<f64>()?;
-            let intensity = tuple.get_item(1)?.extract::<f64>()?;
+            let (mz, intensity) = extract_peak(&item)?;
             self.spectrum.add_peak(mz, intensity).map_err(|e| {
                 PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
             })?;
@@ -168,24 +174,33 @@ impl MSObject {
         Ok(())
     }
 
-    /// 获取前体离子信息
+    /// 获取前体离子信息，没有前体离子时为None
     #[getter]
-    fn precursor(&self, py: Python) -> PyResult<Py<PyAny>> {
-        if let Some(precursor) = &self.spectrum.precursor {
-            let py_precursor = Py::new(py, Precursor { precursor: **precursor.clone() })?;
-            Ok(py_precursor.into())
-        } else {
-            let empty_precursor = PrecursorInfo::default();
-            let py_precursor = Py::new(py, Precursor { precursor: empty_precursor })?;
-            Ok(py_precursor.into())
+    fn precursor(&self) -> Option<Precursor> {
+        self.spectrum.precursor.as_deref().map(|precursor| Precursor { precursor: precursor.clone() })
+    }
+
+    /// 设置前体离子信息，None清除前体离子
+    #[setter(precursor)]
+    fn set_precursor_object(&mut self, precursor: Option<&Bound<'_, PyAny>>) -> PyResult<()> {
+        match precursor.filter(|precursor| !precursor.is_none()) {
+            Some(precursor) => self.spectrum.set_precursor(parse_precursor_from_python(precursor)?),
+            None => self.spectrum.clear_precursor(),
         }
+        Ok(())
+    }
+
+    /// 前体离子的中性质量（电荷未知时为None）
+    #[getter]
+    fn precursor_neutral_mass(&self) -> Option<f64> {
+        self.spectrum.precursor_neutral_mass()
     }
 
     /// 获取扫描信息
     #[getter]
     fn scan(&self, py: Python) -> PyResult<Py<PyAny>> {
         let py_scan = Py::new(py, Scan { scan: self.spectrum.scan.clone() })?;
-        Ok(py_scan.into())
+        Ok(py_scan.into_any())
     }
 
     /// 获取扫描编号
@@ -200,6 +215,24 @@ impl MSObject {
         self.spectrum.set_scan_number(scan_number);
     }
 
+    /// 获取源文件中的谱图native id，未知时为空字符串
+    #[getter]
+    fn native_id(&self) -> &str {
+        &self.spectrum.scan.native_id
+    }
+
+    /// 设置谱图native id
+    #[setter]
+    fn set_native_id(&mut self, native_id: String) {
+        self.spectrum.scan.native_id = native_id;
+    }
+
+    /// 获取谱图在源文件中的下标
+    #[getter]
+    fn index(&self) -> Option<usize> {
+        self.spectrum.scan.source_index
+    }
+
     /// 获取保留时间
     #[getter]
     fn retention_time(&self) -> f64 {
@@ -214,19 +247,29 @@ impl MSObject {
         })
     }
 
+    /// 扫描极性："positive"、"negative"或"unknown"
+    #[getter]
+    fn polarity(&self) -> &'static str {
+        self.spectrum.scan.polarity.name()
+    }
+
+    /// 设置扫描极性
+    #[setter]
+    fn set_polarity(&mut self, polarity: &str) -> PyResult<()> {
+        self.spectrum.scan.polarity = Polarity::from_name(polarity)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(())
+    }
+
     /// 获取额外信息
     #[getter]
     fn additional_info(&self, py: Python) -> PyResult<Py<PyDict>> {
-        let dict = PyDict::new_bound(py);
-        for kv in &self.spectrum.additional_info {
-            dict.set_item(&kv.key, &kv.value)?;
-        }
-        Ok(dict.into())
+        Ok(info_to_dict(py, &self.spectrum.additional_info)?.unbind())
     }
 
     /// 设置额外信息
     #[setter]
-    fn set_additional_info(&mut self, info: &PyDict) -> PyResult<()> {
+    fn set_additional_info(&mut self, info: &Bound<'_, PyDict>) -> PyResult<()> {
         self.spectrum.clear_additional_info();
         for (key, value) in info.iter() {
             let key_str = key.extract::<String>()?;
@@ -256,15 +299,20 @@ impl MSObject {
     }
 
     /// 设置前体离子信息
+    ///
+    /// 只更新给出的字段，其余字段保留原值；所有参数都为None时（如`set_precursor(None)`）清除前体离子。
     #[pyo3(signature = (ref_scan_number=None, mz=None, charge=None, activation_method=None, activation_energy=None, isolation_window=None))]
     fn set_precursor(&mut self, ref_scan_number: Option<u32>, mz: Option<f64>, 
                     charge: Option<i8>, activation_method: Option<String>,
                     activation_energy: Option<f64>, isolation_window: Option<(f64, f64)>) -> PyResult<()> {
-        let mut precursor = if let Some(existing) = &self.spectrum.precursor {
-            **existing.clone()
-        } else {
-            PrecursorInfo::default()
-        };
+        if ref_scan_number.is_none() && mz.is_none() && charge.is_none() && activation_method.is_none()
+            && activation_energy.is_none() && isolation_window.is_none()
+        {
+            self.spectrum.clear_precursor();
+            return Ok(());
+        }
+
+        let mut precursor = self.spectrum.precursor.as_deref().cloned().unwrap_or_default();
 
         if let Some(val) = ref_scan_number { precursor.ref_scan_number = val; }
         if let Some(val) = mz { precursor.mz = val; }
@@ -319,21 +367,124 @@ impl MSObject {
     }
 
     /// 获取基峰
-    fn base_peak(&self, py: Python) -> PyResult<Py<PyAny>> {
-        if let Some((mz, intensity)) = self.spectrum.base_peak() {
-            Ok((mz, intensity).into_py(py))
-        } else {
-            Ok(py.None())
-        }
+    fn base_peak(&self) -> Option<(f64, f64)> {
+        self.spectrum.base_peak()
     }
 
     /// 获取m/z范围
-    fn mz_range(&self, py: Python) -> PyResult<Py<PyAny>> {
-        if let Some(range) = self.spectrum.mz_range() {
-            Ok((range.start, range.end).into_py(py))
-        } else {
-            Ok(py.None())
+    fn mz_range(&self) -> Option<(f64, f64)> {
+        self.spectrum.mz_range().map(|range| (range.start, range.end))
+    }
+
+    /// m/z在[min_mz, max_mz]内（两端都包含）的峰，返回(m/z数组, 强度数组)
+    fn get_peaks_in_range<'py>(&self, py: Python<'py>, min_mz: f64, max_mz: f64) -> PeakArrays<'py> {
+        let (mz, intensity) = self.spectrum.peaks_in_range(min_mz, max_mz);
+        (mz.into_pyarray(py), intensity.into_pyarray(py))
+    }
+
+    /// 在mz的ppm容差内（两端都包含）的峰，返回(m/z数组, 强度数组)
+    fn get_peaks_in_tolerance<'py>(&self, py: Python<'py>, mz: f64, ppm: f64) -> PeakArrays<'py> {
+        let (mz, intensity) = self.spectrum.peaks_in_tolerance(mz, Tolerance::PPM(ppm));
+        (mz.into_pyarray(py), intensity.into_pyarray(py))
+    }
+
+    /// 只含m/z在[min_mz, max_mz]内的峰的新MSObject，扫描和前体离子信息不变
+    fn extract_subspectrum(&self, min_mz: f64, max_mz: f64) -> MSObject {
+        MSObject { spectrum: self.spectrum.subspectrum(min_mz, max_mz) }
+    }
+
+    /// 估计轮廓峰的峰宽，返回 (apex_mz, fwhm, resolution) 列表
+    #[pyo3(signature = (min_intensity=0.0))]
+    fn peak_widths(&self, min_intensity: f64) -> Vec<(f64, f64, f64)> {
+        self.spectrum.peak_widths(min_intensity)
+    }
+
+    /// 中位分辨率（没有可测量的峰时为None）
+    fn median_resolution(&self) -> Option<f64> {
+        self.spectrum.median_resolution()
+    }
+
+    /// 谱图指纹（最强top_n个峰量化后的SHA-1十六进制字符串），用于查找重复谱图
+    #[pyo3(signature = (mz_precision_da=0.01, top_n=30))]
+    fn fingerprint(&self, mz_precision_da: f64, top_n: usize) -> PyResult<String> {
+        self.spectrum.fingerprint(mz_precision_da, top_n).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+        })
+    }
+
+    /// 变换峰强度（"sqrt"、"log2(x+1)"或"rank"），已变换过时需要force=True
+    #[pyo3(signature = (method, force=false))]
+    fn transform_intensities(&mut self, method: &str, force: bool) -> PyResult<()> {
+        let transform = IntensityTransform::from_name(method)
+            .and_then(|transform| self.spectrum.transform_intensities(transform, force));
+        transform.map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 撤销最近一次强度变换，返回被撤销的变换名称
+    fn inverse_intensity_transform(&mut self) -> PyResult<String> {
+        self.spectrum.invert_intensity_transform()
+            .map(|transform| transform.name().to_string())
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 把`[min_mz, max_mz)`内的峰按宽度为`bin`的箱转换为定长numpy向量，用于机器学习
+    ///
+    /// 同一箱内强度求和（`aggregation="max"`时取最大值），再做`transform`变换（None时不变换）
+    /// 和L2归一化；不修改谱图本身。
+    #[pyo3(signature = (min_mz=100.0, max_mz=1700.0, bin=1.0, transform=Some("sqrt"), aggregation="sum", normalize=true))]
+    #[allow(clippy::too_many_arguments)]
+    fn to_vector<'py>(
+        &self,
+        py: Python<'py>,
+        min_mz: f64,
+        max_mz: f64,
+        bin: f64,
+        transform: Option<&str>,
+        aggregation: &str,
+        normalize: bool,
+    ) -> PyResult<Bound<'py, PyArray1<f64>>> {
+        let binning = py_binning(min_mz, max_mz, bin, transform, aggregation, normalize)?;
+        Ok(binning.vectorize(&self.spectrum).into_pyarray(py))
+    }
+
+    /// 已应用的强度变换名称
+    #[getter]
+    fn intensity_transforms(&self) -> Vec<&'static str> {
+        self.spectrum.intensity_transforms().iter().map(|t| t.name()).collect()
+    }
+
+    /// 按离子注入时间归一化强度，返回所用系数（注入时间 / reference_ms）
+    #[pyo3(signature = (reference_ms=100.0))]
+    fn normalize_by_injection_time(&mut self, reference_ms: f64) -> PyResult<f64> {
+        self.spectrum.normalize_by_injection_time(reference_ms)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 去同位素，每个同位素簇合并为单同位素峰；annotate_charges=True时把各峰电荷写入额外信息"peak_charges"
+    #[pyo3(signature = (ppm=10.0, max_charge=DEFAULT_MAX_CHARGE, keep_unassigned=true, annotate_charges=false))]
+    fn deisotope(&mut self, ppm: f64, max_charge: u8, keep_unassigned: bool, annotate_charges: bool) {
+        let (mut spectrum, charges) =
+            deisotope_with_charges(&self.spectrum, Tolerance::PPM(ppm), max_charge, keep_unassigned);
+        if annotate_charges {
+            crate::core::deisotope::annotate_charges(&mut spectrum, &charges);
         }
+        self.spectrum = spectrum;
+    }
+
+    /// MS2谱图质量评分，返回合成分数(composite)及各分量
+    fn ms2_quality_score(&self, py: Python) -> PyResult<Py<PyDict>> {
+        Ok(quality_to_dict(py, &self.spectrum.ms2_quality_score())?.unbind())
+    }
+
+    /// 估计噪声，返回全局噪声(global)、窗口宽度(window)和各窗口的(中心m/z, 噪声)列表(local)
+    fn estimate_noise(&self, py: Python) -> PyResult<Py<PyDict>> {
+        Ok(noise_to_dict(py, &estimate_noise(&self.spectrum))?.unbind())
+    }
+
+    /// 去除强度低于snr倍局部噪声的峰，返回去除的峰数
+    #[pyo3(signature = (snr=3.0))]
+    fn denoise(&mut self, snr: f64) -> usize {
+        denoise(&mut self.spectrum, snr)
     }
 
     /// 验证质谱数据
@@ -353,7 +504,8 @@ impl MSObject {
         self.spectrum.is_ms2()
     }
 
-    /// 检查是否有前体离子信息
+    /// 是否有前体离子信息
+    #[getter]
     fn has_precursor(&self) -> bool {
         self.spectrum.has_precursor()
     }
@@ -382,7 +534,7 @@ impl Precursor {
         charge: i8,
         ref_scan_number: u32,
         isolation_window: Option<(f64, f64)>,
-        activation_method: String,
+        activation_method: &str,
         activation_energy: f64,
     ) -> Self {
         Self {
@@ -390,9 +542,10 @@ impl Precursor {
                 ref_scan_number,
                 mz,
                 charge,
-                activation_method,
+                activation_method: activation_method.to_string(),
                 activation_energy,
                 isolation_window: isolation_window.unwrap_or((0.0, 0.0)),
+                ..PrecursorInfo::default()
             },
         }
     }
@@ -447,6 +600,28 @@ impl Precursor {
         self.precursor.activation_energy = activation_energy;
     }
 
+    /// 碰撞能量 (eV)，文件未记录时为None
+    #[getter]
+    fn collision_energy_ev(&self) -> Option<f64> {
+        self.precursor.collision_energy_ev
+    }
+
+    #[setter]
+    fn set_collision_energy_ev(&mut self, energy: Option<f64>) {
+        self.precursor.collision_energy_ev = energy;
+    }
+
+    /// 归一化碰撞能量 (%)，文件未记录时为None
+    #[getter]
+    fn normalized_collision_energy(&self) -> Option<f64> {
+        self.precursor.normalized_collision_energy
+    }
+
+    #[setter]
+    fn set_normalized_collision_energy(&mut self, energy: Option<f64>) {
+        self.precursor.normalized_collision_energy = energy;
+    }
+
     #[getter]
     fn isolation_window(&self) -> (f64, f64) {
         self.precursor.isolation_window
@@ -457,6 +632,18 @@ impl Precursor {
         self.precursor.isolation_window = isolation_window;
     }
 
+    /// 分离窗口的目标m/z，文件未记录时为None
+    #[getter]
+    fn isolation_target_mz(&self) -> Option<f64> {
+        self.precursor.isolation_target_mz
+    }
+
+    /// 按记录的电荷计算中性质量（电荷未知时为None）
+    #[getter]
+    fn neutral_mass(&self) -> Option<f64> {
+        self.precursor.neutral_mass()
+    }
+
     fn __repr__(&self) -> String {
         format!("Precursor(mz={}, charge={})", self.precursor.mz, self.precursor.charge)
     }
@@ -466,21 +653,21 @@ impl Precursor {
 #[pymethods]
 impl Scan {
     #[new]
-    #[pyo3(signature = (scan_number=0, retention_time=0.0, drift_time=0.0, scan_window=None, additional_info=None))]
+    #[pyo3(signature = (scan_number=0, retention_time=0.0, drift_time=0.0, scan_window=None, additional_info=None, injection_time=None))]
     fn new(
-        py: Python,
         scan_number: u32,
         retention_time: f64,
         drift_time: f64,
         scan_window: Option<(f64, f64)>,
-        additional_info: Option<&PyDict>,
+        additional_info: Option<&Bound<'_, PyDict>>,
+        injection_time: Option<f64>,
     ) -> PyResult<Self> {
         let mut additional_info_vec = SmallKeyValueList::new();
         if let Some(info_dict) = additional_info {
             for (key, value) in info_dict.iter() {
                 let key_str = key.extract::<String>()?;
                 let value_str = value.extract::<String>()?;
-                additional_info_vec.push(KeyValue::new(key_str, value_str));
+                additional_info_vec.push(crate::core::types::KeyValue::new(key_str, value_str));
             }
         }
 
@@ -490,7 +677,9 @@ impl Scan {
                 retention_time,
                 drift_time,
                 scan_window: scan_window.unwrap_or((0.0, 0.0)),
+                injection_time,
                 additional_info: additional_info_vec,
+                ..ScanInfo::default()
             },
         })
     }
@@ -537,6 +726,41 @@ impl Scan {
         Ok(())
     }
 
+    /// 反向约化离子淌度1/K0（V·s/cm²，未记录时为None）
+    #[getter]
+    fn inverse_k0(&self) -> Option<f64> {
+        self.scan.inverse_k0
+    }
+
+    #[setter]
+    fn set_inverse_k0(&mut self, inverse_k0: Option<f64>) {
+        self.scan.inverse_k0 = inverse_k0;
+    }
+
+    /// 离子注入时间（毫秒，未记录时为None）
+    #[getter]
+    fn injection_time(&self) -> Option<f64> {
+        self.scan.injection_time
+    }
+
+    #[setter]
+    fn set_injection_time(&mut self, injection_time: Option<f64>) {
+        self.scan.injection_time = injection_time;
+    }
+
+    /// 扫描极性："positive"、"negative"或"unknown"
+    #[getter]
+    fn polarity(&self) -> &'static str {
+        self.scan.polarity.name()
+    }
+
+    #[setter]
+    fn set_polarity(&mut self, polarity: &str) -> PyResult<()> {
+        self.scan.polarity = Polarity::from_name(polarity)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(())
+    }
+
     #[getter]
     fn scan_window(&self) -> (f64, f64) {
         self.scan.scan_window
@@ -549,11 +773,7 @@ impl Scan {
 
     #[getter]
     fn additional_info(&self, py: Python) -> PyResult<Py<PyDict>> {
-        let dict = PyDict::new(py);
-        for kv in &self.scan.additional_info {
-            dict.set_item(&kv.key, &kv.value)?;
-        }
-        Ok(dict.into())
+        Ok(info_to_dict(py, &self.scan.additional_info)?.unbind())
     }
 
     fn __repr__(&self) -> String {
@@ -585,60 +805,221 @@ impl KeyValue {
     }
 }
 
+/// 前体离子对象中可识别的字段，也是[`precursor_to_dict`]输出的键
+#[cfg(feature = "python")]
+const PRECURSOR_FIELDS: &[&str] = &[
+    "mz", "intensity", "charge", "ref_scan_number", "activation_method", "activation_energy", "isolation_window",
+    "isolation_target_mz", "collision_energy_ev", "normalized_collision_energy",
+];
+
+/// 扫描对象中可识别的字段，也是[`scan_to_dict`]输出的键
+#[cfg(feature = "python")]
+const SCAN_FIELDS: &[&str] = &[
+    "scan_number", "retention_time", "drift_time", "inverse_k0", "scan_window", "injection_time", "polarity", "additional_info",
+];
+
+/// 从dict的键或对象的属性中读取字段
+#[cfg(feature = "python")]
+pub(crate) fn field<'py>(obj: &Bound<'py, PyAny>, name: &str) -> PyResult<Option<Bound<'py, PyAny>>> {
+    if let Ok(dict) = obj.downcast::<PyDict>() {
+        return dict.get_item(name);
+    }
+    Ok(obj.getattr(name).ok())
+}
+
+/// 读取字段并转换为`T`，缺少字段时为None，类型不符时抛出带有`record.name`的TypeError
+#[cfg(feature = "python")]
+pub(crate) fn extract_field<'py, T: FromPyObject<'py>>(obj: &Bound<'py, PyAny>, record: &str, name: &str) -> PyResult<Option<T>> {
+    let Some(value) = field(obj, name)? else {
+        return Ok(None);
+    };
+    value.extract().map(Some).map_err(|e| {
+        pyo3::exceptions::PyTypeError::new_err(format!("{}.{} has the wrong type: {}", record, name, e))
+    })
+}
+
+/// 检查对象是否为dict或至少带有一个可识别的字段，否则抛出TypeError
+#[cfg(feature = "python")]
+fn check_record(obj: &Bound<'_, PyAny>, argument: &str, class: &str, fields: &[&str]) -> PyResult<()> {
+    if obj.downcast::<PyDict>().is_ok() || fields.iter().any(|name| obj.hasattr(*name).unwrap_or(false)) {
+        return Ok(());
+    }
+    Err(pyo3::exceptions::PyTypeError::new_err(format!(
+        "{} must be a {}, a dict or an object with attributes ({}); got {}",
+        argument,
+        class,
+        fields.join(", "),
+        obj.get_type().name().map(|name| name.to_string()).unwrap_or_else(|_| "unknown".to_string()),
+    )))
+}
+
+/// 解析单个(mz, intensity)峰
+#[cfg(feature = "python")]
+pub(crate) fn extract_peak(item: &Bound<'_, PyAny>) -> PyResult<(f64, f64)> {
+    item.extract::<(f64, f64)>().map_err(|_| {
+        pyo3::exceptions::PyTypeError::new_err(format!(
+            "peaks must be a list of (mz, intensity) tuples of floats; got element {}",
+            item.repr().map(|r| r.to_string()).unwrap_or_default(),
+        ))
+    })
+}
+
+/// 将额外信息转换为dict
+#[cfg(feature = "python")]
+pub(crate) fn info_to_dict<'py>(py: Python<'py>, info: &[crate::core::types::KeyValue]) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    for kv in info {
+        dict.set_item(&kv.key, &kv.value)?;
+    }
+    Ok(dict)
+}
+
+/// 从dict解析额外信息，键和值都必须是字符串
+#[cfg(feature = "python")]
+pub(crate) fn parse_info_from_python(info: &Bound<'_, PyAny>) -> PyResult<SmallKeyValueList> {
+    let dict = info.downcast::<PyDict>().map_err(|_| {
+        pyo3::exceptions::PyTypeError::new_err("additional_info must be a dict of str to str")
+    })?;
+    dict.iter()
+        .map(|(key, value)| Ok(crate::core::types::KeyValue::new(key.extract::<String>()?, value.extract::<String>()?)))
+        .collect()
+}
+
+/// 将前体离子信息转换为dict，键见[`PRECURSOR_FIELDS`]
+#[cfg(feature = "python")]
+pub(crate) fn precursor_to_dict<'py>(py: Python<'py>, precursor: &PrecursorInfo) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("mz", precursor.mz)?;
+    dict.set_item("intensity", precursor.intensity)?;
+    dict.set_item("charge", precursor.charge)?;
+    dict.set_item("ref_scan_number", precursor.ref_scan_number)?;
+    dict.set_item("activation_method", &precursor.activation_method)?;
+    dict.set_item("activation_energy", precursor.activation_energy)?;
+    dict.set_item("isolation_window", precursor.isolation_window)?;
+    dict.set_item("isolation_target_mz", precursor.isolation_target_mz)?;
+    dict.set_item("collision_energy_ev", precursor.collision_energy_ev)?;
+    dict.set_item("normalized_collision_energy", precursor.normalized_collision_energy)?;
+    Ok(dict)
+}
+
+/// 将质量评分转换为dict
+#[cfg(feature = "python")]
+pub(crate) fn quality_to_dict<'py>(py: Python<'py>, score: &QualityScore) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("composite", score.composite)?;
+    dict.set_item("peaks_above_noise", score.peaks_above_noise)?;
+    dict.set_item("top_peak_tic_fraction", score.top_peak_tic_fraction)?;
+    dict.set_item("complementary_pairs", score.complementary_pairs)?;
+    dict.set_item("mz_coverage_entropy", score.mz_coverage_entropy)?;
+    dict.set_item("precursor_fraction", score.precursor_fraction)?;
+    Ok(dict)
+}
+
+/// 将噪声估计转换为dict
+#[cfg(feature = "python")]
+pub(crate) fn noise_to_dict<'py>(py: Python<'py>, estimate: &NoiseEstimate) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("global", estimate.global)?;
+    dict.set_item("window", estimate.window)?;
+    dict.set_item("local", &estimate.local)?;
+    Ok(dict)
+}
+
+/// 将扫描信息转换为dict，键见[`SCAN_FIELDS`]
+#[cfg(feature = "python")]
+pub(crate) fn scan_to_dict<'py>(py: Python<'py>, scan: &ScanInfo) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("scan_number", scan.scan_number)?;
+    dict.set_item("retention_time", scan.retention_time)?;
+    dict.set_item("drift_time", scan.drift_time)?;
+    dict.set_item("inverse_k0", scan.inverse_k0)?;
+    dict.set_item("scan_window", scan.scan_window)?;
+    dict.set_item("injection_time", scan.injection_time)?;
+    dict.set_item("polarity", scan.polarity.name())?;
+    dict.set_item("additional_info", info_to_dict(py, &scan.additional_info)?)?;
+    Ok(dict)
+}
+
 /// 从Python对象解析前体离子信息
-fn parse_precursor_from_python(prec_obj: &PyAny) -> PyResult<PrecursorInfo> {
-    let mut precursor = PrecursorInfo::default();
+///
+/// 接受Precursor实例、dict或带有同名属性的对象（如纯Python版MSObject的Precursor）。
+#[cfg(feature = "python")]
+pub(crate) fn parse_precursor_from_python(prec_obj: &Bound<'_, PyAny>) -> PyResult<PrecursorInfo> {
+    if let Ok(precursor) = prec_obj.downcast::<Precursor>() {
+        return Ok(precursor.borrow().precursor.clone());
+    }
+    check_record(prec_obj, "precursor", "Precursor", PRECURSOR_FIELDS)?;
 
-    // 尝试获取各个属性
-    if let Ok(mz) = prec_obj.getattr("mz") {
-        precursor.mz = mz.extract()?;
+    let mut precursor = PrecursorInfo::default();
+    if let Some(mz) = extract_field(prec_obj, "precursor", "mz")? {
+        precursor.mz = mz;
     }
-    if let Ok(charge) = prec_obj.getattr("charge") {
-        precursor.charge = charge.extract()?;
+    if let Some(intensity) = extract_field(prec_obj, "precursor", "intensity")? {
+        precursor.intensity = intensity;
     }
-    if let Ok(ref_scan_number) = prec_obj.getattr("ref_scan_number") {
-        precursor.ref_scan_number = ref_scan_number.extract()?;
+    if let Some(charge) = extract_field(prec_obj, "precursor", "charge")? {
+        precursor.charge = charge;
     }
-    if let Ok(activation_method) = prec_obj.getattr("activation_method") {
-        precursor.activation_method = activation_method.extract()?;
+    if let Some(ref_scan_number) = extract_field(prec_obj, "precursor", "ref_scan_number")? {
+        precursor.ref_scan_number = ref_scan_number;
     }
-    if let Ok(activation_energy) = prec_obj.getattr("activation_energy") {
-        precursor.activation_energy = activation_energy.extract()?;
+    if let Some(activation_method) = extract_field(prec_obj, "precursor", "activation_method")? {
+        precursor.activation_method = activation_method;
     }
-    if let Ok(isolation_window) = prec_obj.getattr("isolation_window") {
-        precursor.isolation_window = isolation_window.extract()?;
+    if let Some(activation_energy) = extract_field(prec_obj, "precursor", "activation_energy")? {
+        precursor.activation_energy = activation_energy;
+    }
+    if let Some(isolation_window) = extract_field(prec_obj, "precursor", "isolation_window")? {
+        precursor.isolation_window = isolation_window;
+    }
+    if let Some(target_mz) = extract_field(prec_obj, "precursor", "isolation_target_mz")? {
+        precursor.isolation_target_mz = target_mz;
+    }
+    if let Some(energy) = extract_field(prec_obj, "precursor", "collision_energy_ev")? {
+        precursor.collision_energy_ev = energy;
+    }
+    if let Some(energy) = extract_field(prec_obj, "precursor", "normalized_collision_energy")? {
+        precursor.normalized_collision_energy = energy;
     }
 
     Ok(precursor)
 }
 
 /// 从Python对象解析扫描信息
-fn parse_scan_from_python(scan_obj: &PyAny) -> PyResult<ScanInfo> {
-    let mut scan = ScanInfo::default();
+///
+/// 接受Scan实例、dict或带有同名属性的对象。
+#[cfg(feature = "python")]
+pub(crate) fn parse_scan_from_python(scan_obj: &Bound<'_, PyAny>) -> PyResult<ScanInfo> {
+    if let Ok(scan) = scan_obj.downcast::<Scan>() {
+        return Ok(scan.borrow().scan.clone());
+    }
+    check_record(scan_obj, "scan", "Scan", SCAN_FIELDS)?;
 
-    // 尝试获取各个属性
-    if let Ok(scan_number) = scan_obj.getattr("scan_number") {
-        scan.scan_number = scan_number.extract()?;
+    let mut scan = ScanInfo::default();
+    if let Some(scan_number) = extract_field(scan_obj, "scan", "scan_number")? {
+        scan.scan_number = scan_number;
     }
-    if let Ok(retention_time) = scan_obj.getattr("retention_time") {
-        scan.retention_time = retention_time.extract()?;
+    if let Some(retention_time) = extract_field(scan_obj, "scan", "retention_time")? {
+        scan.retention_time = retention_time;
     }
-    if let Ok(drift_time) = scan_obj.getattr("drift_time") {
-        scan.drift_time = drift_time.extract()?;
+    if let Some(drift_time) = extract_field(scan_obj, "scan", "drift_time")? {
+        scan.drift_time = drift_time;
     }
-    if let Ok(scan_window) = scan_obj.getattr("scan_window") {
-        scan.scan_window = scan_window.extract()?;
+    if let Some(inverse_k0) = extract_field(scan_obj, "scan", "inverse_k0")? {
+        scan.inverse_k0 = inverse_k0;
     }
-
-    // 处理additional_info
-    if let Ok(additional_info) = scan_obj.getattr("additional_info") {
-        if let Ok(info_dict) = additional_info.downcast::<PyDict>() {
-            for (key, value) in info_dict.iter() {
-                let key_str = key.extract::<String>()?;
-                let value_str = value.extract::<String>()?;
-                scan.additional_info.push(KeyValue::new(key_str, value_str));
-            }
-        }
+    if let Some(scan_window) = extract_field(scan_obj, "scan", "scan_window")? {
+        scan.scan_window = scan_window;
+    }
+    if let Some(injection_time) = extract_field(scan_obj, "scan", "injection_time")? {
+        scan.injection_time = injection_time;
+    }
+    if let Some(polarity) = extract_field::<String>(scan_obj, "scan", "polarity")? {
+        scan.polarity = Polarity::from_name(&polarity)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+    }
+    if let Some(additional_info) = field(scan_obj, "additional_info")? {
+        scan.additional_info = parse_info_from_python(&additional_info)?;
     }
 
     Ok(scan)
@@ -651,38 +1032,142 @@ mod tests {
 
     #[test]
     fn test_msobject_creation() {
-        Python::with_gil(|py| {
-            let ms_obj = MSObject::new(py, 1, None, None, None, None).unwrap();
-            assert_eq!(ms_obj.level(), 1);
-            assert_eq!(ms_obj.peak_count(), 0);
-        });
+        let ms_obj = MSObject::new(1, None, None, None, None).unwrap();
+        assert_eq!(ms_obj.level(), 1);
+        assert_eq!(ms_obj.peak_count(), 0);
     }
 
     #[test]
     fn test_msobject_with_peaks() {
+        pyo3::prepare_freethreaded_python();
         Python::with_gil(|py| {
-            let peaks = PyList::new(py, vec![(100.0, 1000.0), (200.0, 2000.0)]);
-            let ms_obj = MSObject::new(py, 1, Some(peaks), None, None, None).unwrap();
+            let peaks = PyList::new(py, vec![(100.0, 1000.0), (200.0, 2000.0)]).unwrap();
+            let ms_obj = MSObject::new(1, Some(&peaks), None, None, None).unwrap();
             assert_eq!(ms_obj.peak_count(), 2);
             assert_eq!(ms_obj.total_ion_current(), 3000.0);
         });
     }
 
+    #[test]
+    fn test_msobject_argument_types() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let precursor = Py::new(py, Precursor::new(500.0, 2, 10, None, "HCD", 30.0)).unwrap();
+            let scan = PyDict::new(py);
+            scan.set_item("scan_number", 11).unwrap();
+            scan.set_item("retention_time", 4.5).unwrap();
+            let ms_obj = MSObject::new(2, None, Some(precursor.bind(py).as_any()), Some(scan.as_any()), None).unwrap();
+            assert_eq!(ms_obj.spectrum.precursor.as_deref().unwrap().activation_method, "HCD");
+            assert_eq!(ms_obj.scan_number(), 11);
+
+            let bad = 5i32.into_pyobject(py).unwrap().into_any();
+            let err = MSObject::new(2, None, Some(&bad), None, None).unwrap_err();
+            assert!(err.is_instance_of::<pyo3::exceptions::PyTypeError>(py));
+            assert!(err.to_string().contains("precursor must be a Precursor, a dict"));
+
+            let peaks = PyList::new(py, vec!["100.0"]).unwrap();
+            let err = MSObject::new(1, Some(&peaks), None, None, None).unwrap_err();
+            assert!(err.is_instance_of::<pyo3::exceptions::PyTypeError>(py));
+        });
+    }
+
+    #[test]
+    fn test_msobject_precursor_optional() {
+        use crate::parsers::mzml::test_data::{build_mzml, write_temp_file, TestSpectrum};
+        use crate::parsers::mzml::MZMLParser;
+
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 10.0, vec![(400.0, 100.0)]),
+            TestSpectrum::new(2, 2, 12.5, vec![(150.0, 50.0)]).with_precursor(400.0, 2),
+        ];
+        let file = write_temp_file(&build_mzml(&spectra));
+        let mut parsed: Vec<MSObject> = MZMLParser::new()
+            .parse(file.path())
+            .unwrap()
+            .into_iter()
+            .map(|spectrum| MSObject { spectrum })
+            .collect();
+
+        assert!(parsed[0].precursor().is_none());
+        assert!(!parsed[0].has_precursor());
+        let precursor = parsed[1].precursor().unwrap();
+        assert!(parsed[1].has_precursor());
+        assert_eq!(precursor.mz(), 400.0);
+        assert_eq!(precursor.charge(), 2);
+
+        // 所有参数为None时清除前体离子
+        let ms2 = &mut parsed[1];
+        ms2.set_precursor(None, None, None, None, None, None).unwrap();
+        assert!(ms2.precursor().is_none());
+        ms2.set_precursor(None, Some(500.0), None, None, None, None).unwrap();
+        assert_eq!(ms2.precursor().unwrap().mz(), 500.0);
+        assert_eq!(ms2.precursor().unwrap().charge(), 0);
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            ms2.set_precursor_object(Some(&py.None().into_bound(py))).unwrap();
+            assert!(!ms2.has_precursor());
+            let precursor = Py::new(py, Precursor::new(600.0, 3, 1, None, "HCD", 30.0)).unwrap();
+            ms2.set_precursor_object(Some(precursor.bind(py).as_any())).unwrap();
+            assert_eq!(ms2.precursor().unwrap().charge(), 3);
+            ms2.set_precursor_object(None).unwrap();
+            assert!(ms2.precursor().is_none());
+        });
+    }
+
     #[test]
     fn test_precursor_creation() {
-        let precursor = Precursor::new(500.0, 2, 1000, None, "CID".to_string(), 35.0);
+        let precursor = Precursor::new(500.0, 2, 1000, None, "CID", 35.0);
         assert_eq!(precursor.mz(), 500.0);
         assert_eq!(precursor.charge(), 2);
         assert_eq!(precursor.ref_scan_number(), 1000);
+        assert!((precursor.neutral_mass().unwrap() - 997.985447066).abs() < 1e-6);
+
+        let unknown_charge = Precursor::new(500.0, 0, 1000, None, "CID", 35.0);
+        assert_eq!(unknown_charge.neutral_mass(), None);
+    }
+
+    #[test]
+    fn test_msobject_precursor_neutral_mass() {
+        let mut ms_obj = MSObject::new(2, None, None, None, None).unwrap();
+        assert_eq!(ms_obj.precursor_neutral_mass(), None);
+
+        ms_obj.set_precursor(None, Some(400.0), Some(-1), None, None, None).unwrap();
+        assert!((ms_obj.precursor_neutral_mass().unwrap() - 401.007276467).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_intensity_transforms() {
+        let mut ms_obj = MSObject::new(2, None, None, None, None).unwrap();
+        ms_obj.add_peak(100.0, 16.0).unwrap();
+
+        ms_obj.transform_intensities("sqrt", false).unwrap();
+        assert!(ms_obj.transform_intensities("sqrt", false).is_err());
+        assert!(ms_obj.transform_intensities("cube", true).is_err());
+        assert_eq!(ms_obj.intensity_transforms(), vec!["sqrt"]);
+        assert_eq!(ms_obj.spectrum.intensity_slice()[0], 4.0);
+
+        assert_eq!(ms_obj.inverse_intensity_transform().unwrap(), "sqrt");
+        assert_eq!(ms_obj.spectrum.intensity_slice()[0], 16.0);
+        assert!(ms_obj.inverse_intensity_transform().is_err());
     }
 
     #[test]
     fn test_scan_creation() {
+        let scan = Scan::new(100, 10.5, 0.1, None, None, Some(35.0)).unwrap();
+        assert_eq!(scan.scan_number(), 100);
+        assert_eq!(scan.retention_time(), 10.5);
+        assert_eq!(scan.drift_time(), 0.1);
+        assert_eq!(scan.injection_time(), Some(35.0));
+    }
+
+    #[test]
+    fn test_dict_keys_match_fields() {
+        pyo3::prepare_freethreaded_python();
         Python::with_gil(|py| {
-            let scan = Scan::new(py, 100, 10.5, 0.1, None, None).unwrap();
-            assert_eq!(scan.scan_number(), 100);
-            assert_eq!(scan.retention_time(), 10.5);
-            assert_eq!(scan.drift_time(), 0.1);
+            let keys = |dict: Bound<'_, PyDict>| -> Vec<String> { dict.keys().extract().unwrap() };
+            assert_eq!(keys(precursor_to_dict(py, &PrecursorInfo::default()).unwrap()), PRECURSOR_FIELDS);
+            assert_eq!(keys(scan_to_dict(py, &ScanInfo::default()).unwrap()), SCAN_FIELDS);
         });
     }
 }
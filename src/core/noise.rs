@@ -0,0 +1,171 @@
+//! 噪声估计与动态强度阈值
+//!
+//! 固定的强度阈值无法在不同仪器之间通用，这里由谱图自身估计噪声水平：
+//! - 全局噪声：把强度最低的四分之一峰视为噪声，取它们的中位数加上[`MAD_SCALE`]倍的
+//!   中位数绝对偏差（正态噪声下即中位数加一个标准差）
+//! - 局部噪声：沿m/z以宽度为`window`、步长为半个窗口的滑动窗口分别做同样的估计，
+//!   窗口中心之间线性插值；峰数少于[`MIN_WINDOW_PEAKS`]的窗口使用全局噪声
+//!
+//! 只使用强度为正的峰，补零的点不会把噪声压到0。
+
+use crate::core::spectrum::Spectrum;
+use crate::utils::helpers::median;
+
+/// 中位数绝对偏差换算为正态分布标准差的系数
+pub const MAD_SCALE: f64 = 1.4826;
+/// 局部噪声的默认窗口宽度 (Da)
+pub const DEFAULT_NOISE_WINDOW: f64 = 100.0;
+/// 单独估计局部噪声所需的最少峰数
+pub const MIN_WINDOW_PEAKS: usize = 8;
+
+/// 谱图的噪声估计
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NoiseEstimate {
+    /// 整张谱图的噪声水平，没有正强度峰时为0
+    pub global: f64,
+    /// 局部噪声的窗口宽度 (Da)
+    pub window: f64,
+    /// 各滑动窗口的(中心m/z, 噪声)，按m/z升序
+    pub local: Vec<(f64, f64)>,
+}
+
+impl NoiseEstimate {
+    /// `mz`处的局部噪声：相邻窗口中心之间线性插值，两端之外取端点窗口的值
+    pub fn noise_at(&self, mz: f64) -> f64 {
+        let position = self.local.partition_point(|&(center, _)| center < mz);
+        match (position.checked_sub(1).map(|i| self.local[i]), self.local.get(position)) {
+            (Some((left_mz, left)), Some(&(right_mz, right))) => {
+                left + (right - left) * (mz - left_mz) / (right_mz - left_mz)
+            }
+            (Some((_, noise)), None) | (None, Some(&(_, noise))) => noise,
+            (None, None) => self.global,
+        }
+    }
+}
+
+/// 由一组强度估计噪声水平：强度最低的四分之一正强度值的中位数加[`MAD_SCALE`]倍MAD
+///
+/// 没有正强度值时返回None。
+pub fn intensity_noise(intensities: &[f64]) -> Option<f64> {
+    let mut positive: Vec<f64> = intensities.iter().copied().filter(|&intensity| intensity > 0.0).collect();
+    if positive.is_empty() {
+        return None;
+    }
+    positive.sort_by(|a, b| a.total_cmp(b));
+    let lowest = &positive[..positive.len().div_ceil(4)];
+
+    let center = median(lowest)?;
+    let mut deviations: Vec<f64> = lowest.iter().map(|intensity| (intensity - center).abs()).collect();
+    deviations.sort_by(|a, b| a.total_cmp(b));
+    Some(center + MAD_SCALE * median(&deviations)?)
+}
+
+/// 以默认窗口宽度估计谱图的全局和局部噪声
+pub fn estimate_noise(spectrum: &Spectrum) -> NoiseEstimate {
+    estimate_noise_with_window(spectrum, DEFAULT_NOISE_WINDOW)
+}
+
+/// 以`window` (Da)宽的滑动窗口估计谱图的全局和局部噪声，`window`不是正数时只估计全局噪声
+pub fn estimate_noise_with_window(spectrum: &Spectrum, window: f64) -> NoiseEstimate {
+    let global = intensity_noise(spectrum.intensity_slice()).unwrap_or(0.0);
+    let mut estimate = NoiseEstimate { global, window, local: Vec::new() };
+    if !(window.is_finite() && window > 0.0) || spectrum.peak_count() == 0 {
+        return estimate;
+    }
+
+    let mut peaks: Vec<(f64, f64)> = spectrum.peaks_iter().collect();
+    peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (first_mz, last_mz) = (peaks[0].0, peaks[peaks.len() - 1].0);
+
+    let step = window / 2.0;
+    let mut start_mz = first_mz;
+    let (mut start, mut end) = (0, 0);
+    loop {
+        let end_mz = start_mz + window;
+        start += peaks[start..].partition_point(|peak| peak.0 < start_mz);
+        end += peaks[end..].partition_point(|peak| peak.0 < end_mz);
+        let intensities: Vec<f64> = peaks[start..end].iter().map(|peak| peak.1).collect();
+        let noise = match intensity_noise(&intensities) {
+            Some(noise) if intensities.len() >= MIN_WINDOW_PEAKS => noise,
+            _ => global,
+        };
+        estimate.local.push((start_mz + step, noise));
+        if end_mz > last_mz {
+            break;
+        }
+        start_mz += step;
+    }
+    estimate
+}
+
+/// 去除强度低于`snr_threshold`倍局部噪声的峰，返回去除的峰数
+pub fn denoise(spectrum: &mut Spectrum, snr_threshold: f64) -> usize {
+    let estimate = estimate_noise(spectrum);
+    let before = spectrum.peak_count();
+    spectrum.retain_peaks(|(mz, intensity)| intensity >= snr_threshold * estimate.noise_at(mz));
+    before - spectrum.peak_count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_spectrum::{gaussian_noise, pseudo_random};
+
+    /// 每0.5 Da一个噪声峰，强度服从均值为`floor(m/z)`、相对标准差10%的正态分布，
+    /// 另有少量远高于噪声的信号峰
+    fn noisy_spectrum(floor: impl Fn(f64) -> f64, seed: &mut u64) -> (Spectrum, usize) {
+        let mut spectrum = Spectrum::ms2().unwrap();
+        let mut signal = 0;
+        for i in 0..2000 {
+            let mz = 200.0 + 0.5 * i as f64;
+            let noise = floor(mz);
+            spectrum.add_peak(mz, (noise * (1.0 + 0.1 * gaussian_noise(seed))).max(1.0)).unwrap();
+            if i % 40 == 20 {
+                spectrum.add_peak(mz + 0.25, noise * (20.0 + 10.0 * pseudo_random(seed))).unwrap();
+                signal += 1;
+            }
+        }
+        (spectrum, signal)
+    }
+
+    #[test]
+    fn test_estimate_matches_injected_floor() {
+        let mut seed = 3;
+        let (spectrum, _) = noisy_spectrum(|_| 1000.0, &mut seed);
+        let estimate = estimate_noise(&spectrum);
+        assert!((estimate.global - 1000.0).abs() < 200.0, "global noise {}", estimate.global);
+        assert!(estimate.local.iter().all(|&(_, noise)| (noise - 1000.0).abs() < 200.0));
+        assert_eq!(intensity_noise(&[0.0, -1.0]), None);
+        assert_eq!(estimate_noise(&Spectrum::ms1().unwrap()), NoiseEstimate { window: DEFAULT_NOISE_WINDOW, ..Default::default() });
+    }
+
+    #[test]
+    fn test_local_noise_follows_rising_floor() {
+        // 噪声从m/z 200处的100线性升到m/z 1200处的2100
+        let floor = |mz: f64| 100.0 + 2.0 * (mz - 200.0);
+        let mut seed = 5;
+        let (spectrum, signal) = noisy_spectrum(floor, &mut seed);
+        let estimate = estimate_noise(&spectrum);
+        for mz in [300.0, 600.0, 900.0, 1100.0] {
+            let noise = estimate.noise_at(mz);
+            assert!((noise - floor(mz)).abs() < 0.2 * floor(mz), "noise {} at m/z {}", noise, mz);
+        }
+
+        // 全局阈值会保留高m/z处的噪声峰，局部阈值只保留信号峰
+        let above_global = spectrum.intensity_slice().iter().filter(|&&intensity| intensity >= 3.0 * estimate.global).count();
+        assert!(above_global > 2 * signal);
+        let mut denoised = spectrum.clone();
+        let removed = denoise(&mut denoised, 3.0);
+        assert_eq!(denoised.peak_count(), signal);
+        assert_eq!(removed, spectrum.peak_count() - signal);
+    }
+
+    #[test]
+    fn test_noise_at_interpolates() {
+        let estimate = NoiseEstimate { global: 5.0, window: 10.0, local: vec![(100.0, 10.0), (105.0, 20.0)] };
+        assert_eq!(estimate.noise_at(102.5), 15.0);
+        assert_eq!(estimate.noise_at(50.0), 10.0);
+        assert_eq!(estimate.noise_at(200.0), 20.0);
+        assert_eq!(NoiseEstimate { global: 5.0, ..Default::default() }.noise_at(100.0), 5.0);
+    }
+}
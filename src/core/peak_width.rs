@@ -0,0 +1,176 @@
+//! 峰宽与分辨率估计
+//!
+//! 对轮廓（profile）谱图中的每个局部极大值，在半高处两侧做线性插值得到FWHM，
+//! 并计算分辨率 m/Δm。用于仪器质控。
+
+use crate::core::spectrum::Spectrum;
+
+/// `median_resolution`只统计强度不低于基峰该比例的峰，避免噪声峰干扰
+const MEDIAN_RESOLUTION_MIN_RELATIVE_INTENSITY: f64 = 0.01;
+
+impl Spectrum {
+    /// 估计每个轮廓峰的峰宽
+    ///
+    /// 返回 `(apex_mz, fwhm, resolution)` 列表。
+    /// - 饱和的平顶峰以平台中点作为峰顶；
+    /// - 相邻峰重叠时，半高搜索在两峰之间的局部最小值处停止，以该点作为边界；
+    /// - 位于谱图边缘、某一侧没有数据点的峰被跳过。
+    pub fn peak_widths(&self, min_intensity: f64) -> Vec<(f64, f64, f64)> {
+        let mut peaks = self.peaks();
+        peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut widths = Vec::new();
+        let mut start = 0;
+        while start < peaks.len() {
+            // 相同强度的连续点视为一个平台
+            let height = peaks[start].1;
+            let mut end = start;
+            while end + 1 < peaks.len() && peaks[end + 1].1 == height {
+                end += 1;
+            }
+
+            let is_apex = start > 0
+                && end + 1 < peaks.len()
+                && peaks[start - 1].1 < height
+                && peaks[end + 1].1 < height;
+            if is_apex && height >= min_intensity && height > 0.0 {
+                let half = height / 2.0;
+                let left = half_max_crossing(&peaks, start, half, Direction::Left);
+                let right = half_max_crossing(&peaks, end, half, Direction::Right);
+                if let (Some(left), Some(right)) = (left, right) {
+                    let apex_mz = (peaks[start].0 + peaks[end].0) / 2.0;
+                    let fwhm = right - left;
+                    if fwhm > 0.0 {
+                        widths.push((apex_mz, fwhm, apex_mz / fwhm));
+                    }
+                }
+            }
+
+            start = end + 1;
+        }
+
+        widths
+    }
+
+    /// 中位分辨率
+    ///
+    /// 只统计强度不低于基峰1%的峰；没有可测量的峰时返回None。
+    pub fn median_resolution(&self) -> Option<f64> {
+        let (_, base_intensity) = self.base_peak()?;
+        let mut resolutions: Vec<f64> = self
+            .peak_widths(base_intensity * MEDIAN_RESOLUTION_MIN_RELATIVE_INTENSITY)
+            .into_iter()
+            .map(|(_, _, resolution)| resolution)
+            .collect();
+        if resolutions.is_empty() {
+            return None;
+        }
+
+        resolutions.sort_by(|a, b| a.total_cmp(b));
+        let mid = resolutions.len() / 2;
+        Some(if resolutions.len().is_multiple_of(2) {
+            (resolutions[mid - 1] + resolutions[mid]) / 2.0
+        } else {
+            resolutions[mid]
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Left,
+    Right,
+}
+
+/// 从峰顶`apex`向一侧寻找半高交叉点的m/z
+///
+/// 遇到强度回升（即越过两峰间的局部最小值）时，以最小值点作为边界。
+fn half_max_crossing(peaks: &[(f64, f64)], apex: usize, half: f64, direction: Direction) -> Option<f64> {
+    let step = |i: usize| match direction {
+        Direction::Left => i.checked_sub(1),
+        Direction::Right => Some(i + 1).filter(|&next| next < peaks.len()),
+    };
+
+    let mut inner = apex;
+    while let Some(outer) = step(inner) {
+        let (inner_mz, inner_intensity) = peaks[inner];
+        let (outer_mz, outer_intensity) = peaks[outer];
+
+        if outer_intensity <= half {
+            let fraction = (inner_intensity - half) / (inner_intensity - outer_intensity);
+            return Some(inner_mz + fraction * (outer_mz - inner_mz));
+        }
+        if outer_intensity > inner_intensity {
+            return Some(inner_mz);
+        }
+        inner = outer;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_spectrum::gaussian_profile;
+
+    const FWHM_PER_SIGMA: f64 = 2.354_820_045;
+
+    fn assert_within_2_percent(actual: f64, expected: f64) {
+        assert!(((actual - expected) / expected).abs() < 0.02, "{} vs {}", actual, expected);
+    }
+
+    #[test]
+    fn test_gaussian_fwhm() {
+        let sigma = 0.01;
+        let spectrum = gaussian_profile(&[(500.0, 1e6)], sigma, (499.9, 500.1), 0.002);
+        let widths = spectrum.peak_widths(1000.0);
+        assert_eq!(widths.len(), 1);
+
+        let (apex, fwhm, resolution) = widths[0];
+        assert!((apex - 500.0).abs() < 0.002);
+        assert_within_2_percent(fwhm, FWHM_PER_SIGMA * sigma);
+        assert_within_2_percent(resolution, 500.0 / (FWHM_PER_SIGMA * sigma));
+        assert_within_2_percent(spectrum.median_resolution().unwrap(), resolution);
+    }
+
+    #[test]
+    fn test_saturated_flat_top_uses_plateau_midpoint() {
+        let sigma = 0.01;
+        let mut spectrum = gaussian_profile(&[(500.0, 2e6)], sigma, (499.9, 500.1), 0.002);
+        for intensity in spectrum.intensity_slice_mut() {
+            *intensity = intensity.min(1e6);
+        }
+        let widths = spectrum.peak_widths(1000.0);
+        assert_eq!(widths.len(), 1);
+        assert!((widths[0].0 - 500.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_overlapping_peaks_stop_at_minimum() {
+        let sigma = 0.01;
+        let spectrum = gaussian_profile(&[(500.0, 1e6), (500.08, 1e6)], sigma, (499.9, 500.2), 0.002);
+        let widths = spectrum.peak_widths(1000.0);
+        assert_eq!(widths.len(), 2);
+        for (_, fwhm, _) in widths {
+            assert_within_2_percent(fwhm, FWHM_PER_SIGMA * sigma);
+        }
+
+        // 严重重叠时宽度受局部最小值限制，不会跨越到相邻峰
+        let merged = gaussian_profile(&[(500.0, 1e6), (500.025, 1e6)], sigma, (499.9, 500.2), 0.002);
+        for (apex, fwhm, _) in merged.peak_widths(1000.0) {
+            assert!(fwhm < 0.05, "peak at {} has width {}", apex, fwhm);
+        }
+    }
+
+    #[test]
+    fn test_threshold_and_edges() {
+        let spectrum = gaussian_profile(&[(500.0, 100.0)], 0.01, (499.9, 500.1), 0.002);
+        assert!(spectrum.peak_widths(1000.0).is_empty());
+
+        // 峰顶在边缘时没有左侧数据
+        let edge = gaussian_profile(&[(500.0, 1e6)], 0.01, (500.0, 500.1), 0.002);
+        assert!(edge.peak_widths(0.0).is_empty());
+        assert!(Spectrum::new(1).unwrap().median_resolution().is_none());
+    }
+}
@@ -0,0 +1,234 @@
+//! MS2谱图质量评分
+//!
+//! 在鉴定之前对MS2谱图做分诊：由五个分量合成一个0到1之间的质量分数，
+//! 各分量本身也都落在0到1之间，合成分数取可用分量的平均值。
+//!
+//! - 高于噪声的峰数：噪声取峰强度中位数，强度达到其[`SIGNAL_TO_NOISE`]倍记为信号峰，
+//!   峰数达到[`SATURATING_PEAK_COUNT`]时得满分
+//! - 最强[`TOP_PEAKS`]个峰占TIC的比例
+//! - 互补离子对：两个信号峰m/z之和等于前体离子中性质量加两个质子（单电荷b/y离子对），
+//!   对数达到[`SATURATING_PAIR_COUNT`]时得满分；需要前体离子m/z和电荷
+//! - m/z覆盖均匀度：把信号峰所在m/z范围四等分，峰数分布的熵除以ln 4
+//! - 前体离子残留：前体离子m/z附近未碎裂信号占TIC的比例，得分为1减该比例；需要前体离子信息
+
+use crate::core::spectrum::Spectrum;
+use crate::utils::helpers::median;
+use crate::utils::mass::PROTON_MASS;
+
+/// 计算TIC比例时取的最强峰数
+pub const TOP_PEAKS: usize = 20;
+/// 信号峰强度相对噪声（峰强度中位数）的最低倍数
+pub const SIGNAL_TO_NOISE: f64 = 3.0;
+/// 峰数得分达到满分所需的信号峰数
+pub const SATURATING_PEAK_COUNT: usize = 50;
+/// 互补离子对得分达到满分所需的对数
+pub const SATURATING_PAIR_COUNT: usize = 5;
+/// 互补离子对与前体离子残留的m/z容差 (Da)
+pub const PAIR_TOLERANCE: f64 = 0.02;
+
+/// MS2谱图质量评分及其分量
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct QualityScore {
+    /// 合成分数 (0–1)
+    pub composite: f64,
+    /// 高于噪声的峰数
+    pub peaks_above_noise: usize,
+    /// 最强峰占TIC的比例
+    pub top_peak_tic_fraction: f64,
+    /// 互补离子对数，缺少前体离子中性质量时为None
+    pub complementary_pairs: Option<usize>,
+    /// 归一化的m/z覆盖熵 (0–1)
+    pub mz_coverage_entropy: f64,
+    /// 前体离子残留信号占TIC的比例，没有前体离子信息时为None
+    pub precursor_fraction: Option<f64>,
+}
+
+impl QualityScore {
+    /// 各分量折算成的0–1得分，缺失的分量不计入
+    fn component_scores(&self) -> Vec<f64> {
+        let mut scores = vec![
+            (self.peaks_above_noise as f64 / SATURATING_PEAK_COUNT as f64).min(1.0),
+            self.top_peak_tic_fraction,
+            self.mz_coverage_entropy,
+        ];
+        if let Some(pairs) = self.complementary_pairs {
+            scores.push((pairs as f64 / SATURATING_PAIR_COUNT as f64).min(1.0));
+        }
+        if let Some(fraction) = self.precursor_fraction {
+            scores.push(1.0 - fraction);
+        }
+        scores
+    }
+}
+
+impl Spectrum {
+    /// 计算MS2谱图质量评分，空谱图的所有分量为0
+    pub fn ms2_quality_score(&self) -> QualityScore {
+        let tic = self.total_ion_current();
+        if self.peak_count() == 0 || tic <= 0.0 {
+            return QualityScore::default();
+        }
+
+        let mut intensities = self.intensity_slice().to_vec();
+        intensities.sort_by(|a, b| b.total_cmp(a));
+        let noise = median(&intensities).unwrap_or(0.0);
+        let top_peak_tic_fraction = intensities.iter().take(TOP_PEAKS).sum::<f64>() / tic;
+
+        let mut signal: Vec<f64> = self
+            .peaks_iter()
+            .filter(|peak| peak.1 > 0.0 && peak.1 >= SIGNAL_TO_NOISE * noise)
+            .map(|peak| peak.0)
+            .collect();
+        signal.sort_by(|a, b| a.total_cmp(b));
+
+        let complementary_pairs = self
+            .precursor_neutral_mass()
+            .map(|mass| complementary_pairs(&signal, mass + 2.0 * PROTON_MASS));
+        let precursor_fraction = self.precursor.as_ref().map(|precursor| {
+            let residual: f64 = self
+                .peaks_iter()
+                .filter(|peak| (peak.0 - precursor.mz).abs() <= PAIR_TOLERANCE)
+                .map(|peak| peak.1)
+                .sum();
+            residual / tic
+        });
+
+        let mut score = QualityScore {
+            composite: 0.0,
+            peaks_above_noise: signal.len(),
+            top_peak_tic_fraction,
+            complementary_pairs,
+            mz_coverage_entropy: coverage_entropy(&signal),
+            precursor_fraction,
+        };
+        let components = score.component_scores();
+        score.composite = components.iter().sum::<f64>() / components.len() as f64;
+        score
+    }
+}
+
+/// 按合成分数从高到低返回最好的`top_n`张MS2谱图的下标及评分
+pub fn rank_ms2(spectra: &[Spectrum], top_n: usize) -> Vec<(usize, QualityScore)> {
+    let mut scored: Vec<(usize, QualityScore)> = spectra
+        .iter()
+        .enumerate()
+        .filter(|(_, spectrum)| spectrum.is_ms2())
+        .map(|(index, spectrum)| (index, spectrum.ms2_quality_score()))
+        .collect();
+    scored.sort_by(|a, b| b.1.composite.total_cmp(&a.1.composite).then(a.0.cmp(&b.0)));
+    scored.truncate(top_n);
+    scored
+}
+
+/// 升序m/z中和为`target`的峰对数，每个峰最多参与一对
+fn complementary_pairs(mzs: &[f64], target: f64) -> usize {
+    let (mut low, mut high) = (0, mzs.len());
+    let mut pairs = 0;
+    while low + 1 < high {
+        let sum = mzs[low] + mzs[high - 1];
+        if (sum - target).abs() <= PAIR_TOLERANCE {
+            pairs += 1;
+            low += 1;
+            high -= 1;
+        } else if sum < target {
+            low += 1;
+        } else {
+            high -= 1;
+        }
+    }
+    pairs
+}
+
+/// 升序m/z在其范围四等分后的峰数分布熵，除以ln 4归一化
+fn coverage_entropy(mzs: &[f64]) -> f64 {
+    let (Some(&first), Some(&last)) = (mzs.first(), mzs.last()) else {
+        return 0.0;
+    };
+    let width = last - first;
+    if width <= 0.0 {
+        return 0.0;
+    }
+
+    let mut counts = [0usize; 4];
+    for &mz in mzs {
+        let quartile = (((mz - first) / width) * 4.0) as usize;
+        counts[quartile.min(3)] += 1;
+    }
+    let total = mzs.len() as f64;
+    let entropy: f64 = counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.ln()
+        })
+        .sum();
+    entropy / 4f64.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    /// 前体离子 [M+2H]2+ 600.3，中性质量约1198.585
+    fn rich_spectrum() -> Spectrum {
+        let mut spectrum = Spectrum::ms2().unwrap();
+        let target = 2.0 * 600.3;
+        // 40个弱噪声峰均匀分布在150–1100
+        spectrum.add_peaks((0..40).map(|i| (150.0 + i as f64 * 23.5, 100.0))).unwrap();
+        // 8对互补的b/y离子
+        for i in 0..8 {
+            let b = 200.05 + i as f64 * 97.3;
+            spectrum.add_peaks([(b, 5000.0 + i as f64 * 300.0), (target - b, 4000.0 + i as f64 * 200.0)]).unwrap();
+        }
+        spectrum.set_precursor(PrecursorInfo { mz: 600.3, charge: 2, ..Default::default() });
+        spectrum
+    }
+
+    fn noise_spectrum() -> Spectrum {
+        let mut spectrum = Spectrum::ms2().unwrap();
+        spectrum.add_peaks([(213.4, 110.0), (388.1, 95.0), (402.7, 120.0), (655.0, 100.0), (600.3, 105.0)]).unwrap();
+        spectrum.set_precursor(PrecursorInfo { mz: 600.3, charge: 2, ..Default::default() });
+        spectrum
+    }
+
+    #[test]
+    fn test_rich_spectrum_outscores_noise() {
+        let rich = rich_spectrum().ms2_quality_score();
+        assert_eq!(rich.peaks_above_noise, 16);
+        assert_eq!(rich.complementary_pairs, Some(8));
+        assert_eq!(rich.precursor_fraction, Some(0.0));
+        assert!(rich.mz_coverage_entropy > 0.9);
+
+        let noise = noise_spectrum().ms2_quality_score();
+        assert_eq!(noise.peaks_above_noise, 0);
+        assert_eq!(noise.complementary_pairs, Some(0));
+        assert!(noise.precursor_fraction.unwrap() > 0.1);
+
+        assert!(rich.composite > noise.composite);
+        assert!((0.0..=1.0).contains(&rich.composite));
+    }
+
+    #[test]
+    fn test_missing_precursor_components_skipped() {
+        let mut spectrum = Spectrum::ms2().unwrap();
+        spectrum.add_peaks([(100.0, 1.0), (200.0, 10.0), (300.0, 1.0)]).unwrap();
+        let score = spectrum.ms2_quality_score();
+        assert_eq!(score.complementary_pairs, None);
+        assert_eq!(score.precursor_fraction, None);
+        assert_eq!(score.peaks_above_noise, 1);
+        assert_eq!(score.top_peak_tic_fraction, 1.0);
+        assert!((score.composite - (1.0 / 50.0 + 1.0) / 3.0).abs() < 1e-12);
+
+        assert_eq!(Spectrum::ms2().unwrap().ms2_quality_score(), QualityScore::default());
+    }
+
+    #[test]
+    fn test_rank_ms2_orders_by_composite() {
+        let spectra = vec![noise_spectrum(), Spectrum::ms1().unwrap(), rich_spectrum()];
+        let ranked = rank_ms2(&spectra, 5);
+        assert_eq!(ranked.iter().map(|(index, _)| *index).collect::<Vec<_>>(), vec![2, 0]);
+        assert_eq!(rank_ms2(&spectra, 1).len(), 1);
+    }
+}
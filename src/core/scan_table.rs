@@ -0,0 +1,125 @@
+//! 扫描表
+//!
+//! 以列存储的形式汇总每个谱图的头信息（扫描编号、保留时间、MS级别、前体离子、
+//! 注入时间、TIC、基峰、峰数），供QC脚本直接使用，无需访问峰数据。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+
+/// 扫描表中的一行
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanRow {
+    pub scan_number: ScanNumber,
+    pub retention_time: RetentionTime,
+    pub ms_level: MSLevel,
+    pub precursor_mz: Option<f64>,
+    pub charge: Option<Charge>,
+    pub injection_time: Option<f64>,
+    pub total_ion_current: Option<f64>,
+    pub base_peak_mz: Option<f64>,
+    pub base_peak_intensity: Option<f64>,
+    pub peak_count: usize,
+}
+
+impl ScanRow {
+    /// 由已解析的谱图生成一行，TIC和基峰由峰列表计算
+    pub fn from_spectrum(spectrum: &Spectrum) -> Self {
+        let base_peak = spectrum.base_peak();
+        let precursor = spectrum.precursor.as_deref();
+        Self {
+            scan_number: spectrum.scan.scan_number,
+            retention_time: spectrum.scan.retention_time,
+            ms_level: spectrum.level,
+            precursor_mz: precursor.map(|p| p.mz),
+            charge: precursor.map(|p| p.charge).filter(|&charge| charge != 0),
+            injection_time: spectrum.scan.injection_time,
+            total_ion_current: Some(spectrum.total_ion_current()),
+            base_peak_mz: base_peak.map(|(mz, _)| mz),
+            base_peak_intensity: base_peak.map(|(_, intensity)| intensity),
+            peak_count: spectrum.peak_count(),
+        }
+    }
+}
+
+/// 列存储的扫描表，每列长度相同，每个谱图一项
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanTable {
+    pub scan_number: Vec<ScanNumber>,
+    pub retention_time: Vec<RetentionTime>,
+    pub ms_level: Vec<MSLevel>,
+    pub precursor_mz: Vec<Option<f64>>,
+    pub charge: Vec<Option<Charge>>,
+    pub injection_time: Vec<Option<f64>>,
+    pub total_ion_current: Vec<Option<f64>>,
+    pub base_peak_mz: Vec<Option<f64>>,
+    pub base_peak_intensity: Vec<Option<f64>>,
+    pub peak_count: Vec<usize>,
+}
+
+impl ScanTable {
+    /// 创建空表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 由已解析的谱图生成扫描表
+    pub fn from_spectra<'a>(spectra: impl IntoIterator<Item = &'a Spectrum>) -> Self {
+        let mut table = Self::new();
+        for spectrum in spectra {
+            table.push(ScanRow::from_spectrum(spectrum));
+        }
+        table
+    }
+
+    /// 追加一行
+    pub fn push(&mut self, row: ScanRow) {
+        self.scan_number.push(row.scan_number);
+        self.retention_time.push(row.retention_time);
+        self.ms_level.push(row.ms_level);
+        self.precursor_mz.push(row.precursor_mz);
+        self.charge.push(row.charge);
+        self.injection_time.push(row.injection_time);
+        self.total_ion_current.push(row.total_ion_current);
+        self.base_peak_mz.push(row.base_peak_mz);
+        self.base_peak_intensity.push(row.base_peak_intensity);
+        self.peak_count.push(row.peak_count);
+    }
+
+    /// 行数
+    pub fn len(&self) -> usize {
+        self.scan_number.len()
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.scan_number.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    #[test]
+    fn test_from_spectra() {
+        let mut ms1 = Spectrum::new(1).unwrap();
+        ms1.add_peaks([(100.0, 10.0), (200.0, 30.0)]).unwrap();
+        ms1.scan.injection_time = Some(25.0);
+
+        let mut ms2 = Spectrum::new(2).unwrap();
+        ms2.set_retention_time(1.5).unwrap();
+        ms2.set_precursor(PrecursorInfo { mz: 500.25, charge: 2, ..PrecursorInfo::default() });
+
+        let table = ScanTable::from_spectra(&[ms1, ms2]);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.ms_level, vec![1, 2]);
+        assert_eq!(table.retention_time, vec![0.0, 1.5]);
+        assert_eq!(table.precursor_mz, vec![None, Some(500.25)]);
+        assert_eq!(table.charge, vec![None, Some(2)]);
+        assert_eq!(table.injection_time, vec![Some(25.0), None]);
+        assert_eq!(table.total_ion_current, vec![Some(40.0), Some(0.0)]);
+        assert_eq!(table.base_peak_mz, vec![Some(200.0), None]);
+        assert_eq!(table.peak_count, vec![2, 0]);
+    }
+}
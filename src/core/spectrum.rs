@@ -4,13 +4,22 @@
 //! - Spectrum: 核心质谱数据结构
 //! - SpectrumBin: 用于索引的谱图bin
 //! - BinnedSpectraIndex: 二进制索引结构
+//! - PeakHit: 带来源谱图信息的峰搜索结果
+//! - SpectraIndex: BinnedSpectraIndex的Python封装
 
 use crate::core::types::*;
+use crate::core::validation::ValidationOptions;
+use crate::utils::mass;
+use crate::xic::simd_search::SIMDSearcher;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::ops::Range;
+use std::sync::Arc;
 
-#[cfg(test)]
-mod test_spectrum;
+#[cfg(feature = "python")]
+use crate::core::ms_object::MSObject;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
 
 /// 前体离子信息
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -25,10 +34,19 @@ pub struct PrecursorInfo {
     pub charge: Charge,
     /// 激活方法
     pub activation_method: String,
-    /// 激活能量
+    /// 激活能量（兼容字段）：有eV碰撞能量时取eV，否则取归一化碰撞能量
     pub activation_energy: f64,
-    /// 分离窗口
+    /// 分离窗口 (下限m/z, 上限m/z)，文件中未给出偏移时上下限均为目标m/z
     pub isolation_window: (f64, f64),
+    /// 分离窗口的目标m/z (MS:1000827)，可能与选中离子的m/z不同；文件未记录时为None
+    #[serde(default)]
+    pub isolation_target_mz: Option<f64>,
+    /// 碰撞能量 (eV，MS:1000045)
+    #[serde(default)]
+    pub collision_energy_ev: Option<f64>,
+    /// 归一化碰撞能量 (%，MS:1000138)
+    #[serde(default)]
+    pub normalized_collision_energy: Option<f64>,
 }
 
 impl Default for PrecursorInfo {
@@ -41,10 +59,25 @@ impl Default for PrecursorInfo {
             activation_method: "unknown".to_string(),
             activation_energy: 0.0,
             isolation_window: (0.0, 0.0),
+            isolation_target_mz: None,
+            collision_energy_ev: None,
+            normalized_collision_energy: None,
         }
     }
 }
 
+impl PrecursorInfo {
+    /// 按记录的电荷计算中性质量，电荷未知（0）时返回None
+    ///
+    /// 正电荷按[M+zH]z+、负电荷按[M-zH]z-计算。
+    pub fn neutral_mass(&self) -> Option<f64> {
+        if self.charge == 0 {
+            return None;
+        }
+        mass::neutral_mass(self.mz, self.charge, mass::default_adduct(self.charge)).ok()
+    }
+}
+
 /// 扫描信息
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ScanInfo {
@@ -54,10 +87,21 @@ pub struct ScanInfo {
     pub retention_time: RetentionTime,
     /// 漂移时间 (秒)
     pub drift_time: DriftTime,
+    /// 反向约化离子淌度1/K0 (V·s/cm²)，timsTOF等仪器记录；未知时为None
+    #[serde(default)]
+    pub inverse_k0: Option<f64>,
     /// 扫描窗口
     pub scan_window: (f64, f64),
+    /// 离子注入时间 (毫秒)
+    pub injection_time: Option<f64>,
+    /// 扫描极性
+    pub polarity: Polarity,
     /// 额外信息
     pub additional_info: SmallKeyValueList,
+    /// 源文件中的谱图native id（mzML的spectrum/@id），未知时为空
+    pub native_id: String,
+    /// 谱图在源文件中的下标（mzML的spectrum/@index）
+    pub source_index: Option<usize>,
 }
 
 impl Default for ScanInfo {
@@ -66,17 +110,27 @@ impl Default for ScanInfo {
             scan_number: constants::DEFAULT_SCAN_NUMBER,
             retention_time: constants::DEFAULT_RETENTION_TIME,
             drift_time: constants::DEFAULT_DRIFT_TIME,
+            inverse_k0: None,
             scan_window: (0.0, 0.0),
+            injection_time: None,
+            polarity: Polarity::Unknown,
             additional_info: SmallKeyValueList::new(),
+            native_id: String::new(),
+            source_index: None,
         }
     }
 }
 
 /// 核心质谱数据结构
+///
+/// 峰以两个平行数组（m/z和强度）存放，只扫描m/z的操作（XIC、范围查询）不必读入强度，
+/// 也便于向量化；按峰访问时使用[`Spectrum::peaks_iter`]等访问器。
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Spectrum {
-    /// 质谱峰数据 (m/z, intensity)
-    pub peaks: PeakList,
+    /// 峰m/z，与`intensity`等长
+    mz: Vec<f64>,
+    /// 峰强度
+    intensity: Vec<f64>,
     /// MS级别 (1, 2, 3...)
     pub level: MSLevel,
     /// 扫描信息
@@ -85,12 +139,20 @@ pub struct Spectrum {
     pub precursor: Option<Box<PrecursorInfo>>,
     /// 额外信息
     pub additional_info: SmallKeyValueList,
+    /// 是否允许负强度（基线校正后的厂商数据）
+    ///
+    /// 允许时TIC为含负值的代数和，基峰仍取最大强度；余弦相似度等计算将负强度视为0。
+    #[serde(default)]
+    pub allow_negative_intensities: bool,
+    /// 已知m/z升序；为false时只是未知，范围查询退回线性扫描
+    #[serde(skip)]
+    mz_sorted: bool,
 }
 
 impl Spectrum {
     /// 创建新的质谱对象
     pub fn new(level: MSLevel) -> CoreResult<Self> {
-        if level < constants::MIN_MS_LEVEL || level > constants::MAX_MS_LEVEL {
+        if !(constants::MIN_MS_LEVEL..=constants::MAX_MS_LEVEL).contains(&level) {
             return Err(CoreError::InvalidMSLevel {
                 level,
                 min: constants::MIN_MS_LEVEL,
@@ -99,11 +161,14 @@ impl Spectrum {
         }
 
         Ok(Self {
-            peaks: Vec::new(),
+            mz: Vec::new(),
+            intensity: Vec::new(),
             level,
             scan: ScanInfo::default(),
             precursor: None,
             additional_info: SmallKeyValueList::new(),
+            allow_negative_intensities: false,
+            mz_sorted: true,
         })
     }
 
@@ -118,11 +183,15 @@ impl Spectrum {
     }
 
     /// 添加质谱峰
+    ///
+    /// m/z必须非负；强度必须非负，除非设置了`allow_negative_intensities`。
     pub fn add_peak(&mut self, mz: f64, intensity: f64) -> CoreResult<()> {
-        if mz < 0.0 || intensity < 0.0 {
+        if mz < 0.0 || (intensity < 0.0 && !self.allow_negative_intensities) {
             return Err(CoreError::InvalidPeakData { mz, intensity });
         }
-        self.peaks.push((mz, intensity));
+        self.mz_sorted &= self.mz.last().is_none_or(|&last| last <= mz);
+        self.mz.push(mz);
+        self.intensity.push(intensity);
         Ok(())
     }
 
@@ -134,43 +203,167 @@ impl Spectrum {
         Ok(())
     }
 
+    /// 用`peaks`替换全部质谱峰，不做校验（用于写回已处理过的峰）
+    pub fn set_peaks(&mut self, peaks: impl IntoIterator<Item = Peak>) {
+        (self.mz, self.intensity) = peaks.into_iter().unzip();
+        self.mz_sorted = is_ascending(&self.mz);
+    }
+
+    /// 用平行的m/z和强度数组替换全部质谱峰，不做校验；两个数组长度不同时返回错误
+    pub fn set_peak_arrays(&mut self, mz: Vec<f64>, intensity: Vec<f64>) -> CoreResult<()> {
+        if mz.len() != intensity.len() {
+            return Err(CoreError::InvalidFormat(format!(
+                "m/z array has {} values but intensity array has {}",
+                mz.len(),
+                intensity.len()
+            )));
+        }
+        self.mz_sorted = is_ascending(&mz);
+        self.mz = mz;
+        self.intensity = intensity;
+        Ok(())
+    }
+
+    /// 只保留满足`keep`的峰，保持原有顺序
+    pub fn retain_peaks(&mut self, mut keep: impl FnMut(Peak) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.mz.len() {
+            if keep((self.mz[i], self.intensity[i])) {
+                self.mz[kept] = self.mz[i];
+                self.intensity[kept] = self.intensity[i];
+                kept += 1;
+            }
+        }
+        self.mz.truncate(kept);
+        self.intensity.truncate(kept);
+    }
+
     /// 清除所有质谱峰
     pub fn clear_peaks(&mut self) {
-        self.peaks.clear();
+        self.mz.clear();
+        self.intensity.clear();
+        self.mz_sorted = true;
     }
 
     /// 按m/z排序质谱峰
+    ///
+    /// 先对下标排序再按同一置换重排两个数组，m/z相同的峰保持原有顺序。
     pub fn sort_peaks(&mut self) {
-        self.peaks.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        if self.mz_sorted || is_ascending(&self.mz) {
+            self.mz_sorted = true;
+            return;
+        }
+        let mut order: Vec<usize> = (0..self.mz.len()).collect();
+        order.sort_by(|&a, &b| self.mz[a].partial_cmp(&self.mz[b]).unwrap());
+        self.mz = order.iter().map(|&i| self.mz[i]).collect();
+        self.intensity = order.iter().map(|&i| self.intensity[i]).collect();
+        self.mz_sorted = true;
+    }
+
+    /// 峰是否已知按m/z升序排列（反序列化得到的谱图在排序或替换峰之前视为未知）
+    pub fn is_sorted(&self) -> bool {
+        self.mz_sorted
+    }
+
+    /// 复制谱图的元数据（级别、扫描、前体离子、额外信息），峰替换为`peaks`
+    pub fn with_peaks(&self, peaks: impl IntoIterator<Item = Peak>) -> Self {
+        let (mz, intensity): (Vec<f64>, Vec<f64>) = peaks.into_iter().unzip();
+        Self {
+            mz_sorted: is_ascending(&mz),
+            mz,
+            intensity,
+            level: self.level,
+            scan: self.scan.clone(),
+            precursor: self.precursor.clone(),
+            additional_info: self.additional_info.clone(),
+            allow_negative_intensities: self.allow_negative_intensities,
+        }
+    }
+
+    /// 按(m/z, 强度)顺序遍历质谱峰
+    pub fn peaks_iter(&self) -> impl ExactSizeIterator<Item = Peak> + DoubleEndedIterator + Clone + '_ {
+        self.mz.iter().copied().zip(self.intensity.iter().copied())
+    }
+
+    /// 复制出(m/z, 强度)形式的峰列表，供接受[`PeakList`]的接口使用
+    pub fn peaks(&self) -> PeakList {
+        self.peaks_iter().collect()
+    }
+
+    /// 第`index`个峰
+    pub fn peak(&self, index: usize) -> Option<Peak> {
+        Some((*self.mz.get(index)?, self.intensity[index]))
+    }
+
+    /// 所有峰的m/z
+    pub fn mz_slice(&self) -> &[f64] {
+        &self.mz
+    }
+
+    /// 所有峰的强度
+    pub fn intensity_slice(&self) -> &[f64] {
+        &self.intensity
+    }
+
+    /// 所有峰的强度（可修改）
+    pub fn intensity_slice_mut(&mut self) -> &mut [f64] {
+        &mut self.intensity
     }
 
     /// 获取m/z范围
     pub fn mz_range(&self) -> Option<Range<f64>> {
-        if self.peaks.is_empty() {
+        if self.mz.is_empty() {
             return None;
         }
 
         let mut min_mz = f64::INFINITY;
         let mut max_mz = f64::NEG_INFINITY;
 
-        for (mz, _) in &self.peaks {
-            min_mz = min_mz.min(*mz);
-            max_mz = max_mz.max(*mz);
+        for &mz in &self.mz {
+            min_mz = min_mz.min(mz);
+            max_mz = max_mz.max(mz);
         }
 
         Some(min_mz..max_mz)
     }
 
+    /// m/z在`[min_mz, max_mz]`内（两端都包含）的峰，返回平行的m/z和强度数组
+    ///
+    /// 峰已排序时二分定位，否则线性扫描；结果保持峰在谱图中的顺序。
+    pub fn peaks_in_range(&self, min_mz: f64, max_mz: f64) -> (Vec<f64>, Vec<f64>) {
+        if self.mz_sorted {
+            let window = SIMDSearcher::new().window(&self.mz, min_mz, max_mz);
+            return (self.mz[window.clone()].to_vec(), self.intensity[window].to_vec());
+        }
+        self.peaks_iter().filter(|&(mz, _)| mz >= min_mz && mz <= max_mz).unzip()
+    }
+
+    /// 在`target_mz`的容差窗口内（两端都包含）的峰，见[`Spectrum::peaks_in_range`]
+    pub fn peaks_in_tolerance(&self, target_mz: f64, tolerance: impl MzTolerance) -> (Vec<f64>, Vec<f64>) {
+        let (low, high) = tolerance.window(target_mz);
+        self.peaks_in_range(low, high)
+    }
+
+    /// m/z在`[min_mz, max_mz]`内的峰组成的新谱图，扫描、前体离子等元数据不变
+    pub fn subspectrum(&self, min_mz: f64, max_mz: f64) -> Spectrum {
+        let (mz, intensity) = self.peaks_in_range(min_mz, max_mz);
+        self.with_peaks(mz.into_iter().zip(intensity))
+    }
+
     /// 获取总离子流
     pub fn total_ion_current(&self) -> f64 {
-        self.peaks.iter().map(|(_, intensity)| *intensity).sum()
+        self.intensity.iter().sum()
     }
 
     /// 获取基峰
     pub fn base_peak(&self) -> Option<Peak> {
-        self.peaks.iter()
+        self.peaks_iter()
             .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
-            .copied()
+    }
+
+    /// 前体离子的中性质量，无前体离子或电荷未知时返回None
+    pub fn precursor_neutral_mass(&self) -> Option<f64> {
+        self.precursor.as_ref().and_then(|precursor| precursor.neutral_mass())
     }
 
     /// 设置前体离子信息
@@ -236,32 +429,16 @@ impl Spectrum {
         self.additional_info.clear();
     }
 
-    /// 验证质谱数据
+    /// 验证质谱数据，使用默认的严格选项：空谱图、无效峰和超出范围的MS级别都返回错误
+    ///
+    /// 需要接受空扫描等情况时用[`Spectrum::validate_with`]。
     pub fn validate(&self) -> CoreResult<()> {
-        if self.peaks.is_empty() {
-            return Err(CoreError::EmptyPeakList);
-        }
-
-        for (mz, intensity) in &self.peaks {
-            if *mz < 0.0 || *intensity < 0.0 {
-                return Err(CoreError::InvalidPeakData { mz: *mz, intensity: *intensity });
-            }
-        }
-
-        if self.level < constants::MIN_MS_LEVEL || self.level > constants::MAX_MS_LEVEL {
-            return Err(CoreError::InvalidMSLevel {
-                level: self.level,
-                min: constants::MIN_MS_LEVEL,
-                max: constants::MAX_MS_LEVEL,
-            });
-        }
-
-        Ok(())
+        self.validate_with(&ValidationOptions::default())
     }
 
     /// 获取质谱峰数量
     pub fn peak_count(&self) -> usize {
-        self.peaks.len()
+        self.mz.len()
     }
 
     /// 检查是否为MS1谱图
@@ -280,6 +457,11 @@ impl Spectrum {
     }
 }
 
+/// m/z是否升序
+fn is_ascending(mz: &[f64]) -> bool {
+    mz.is_sorted_by(|a, b| a <= b)
+}
+
 impl Default for Spectrum {
     fn default() -> Self {
         Self::new(1).unwrap()
@@ -293,6 +475,12 @@ pub struct SpectrumBin {
     pub mz_range: Range<f64>,
     /// 峰索引列表 (避免数据复制)
     pub peak_indices: Vec<usize>,
+    /// bin内的最大峰强度，用于按强度预筛选
+    #[serde(default)]
+    pub max_intensity: f64,
+    /// bin内的峰强度总和
+    #[serde(default)]
+    pub total_intensity: f64,
 }
 
 impl SpectrumBin {
@@ -301,14 +489,23 @@ impl SpectrumBin {
         Self {
             mz_range,
             peak_indices: Vec::new(),
+            max_intensity: 0.0,
+            total_intensity: 0.0,
         }
     }
 
-    /// 添加峰索引
+    /// 添加峰索引（不更新强度统计）
     pub fn add_peak_index(&mut self, index: usize) {
         self.peak_indices.push(index);
     }
 
+    /// 添加峰索引并更新最大强度和强度总和
+    pub fn add_peak(&mut self, index: usize, intensity: f64) {
+        self.peak_indices.push(index);
+        self.max_intensity = self.max_intensity.max(intensity);
+        self.total_intensity += intensity;
+    }
+
     /// 检查m/z值是否在bin范围内
     pub fn contains_mz(&self, mz: f64) -> bool {
         mz >= self.mz_range.start && mz < self.mz_range.end
@@ -320,7 +517,31 @@ impl SpectrumBin {
     }
 }
 
+pub use crate::core::store::{SharedSpectra, SpectraStore};
+
+/// 范围搜索跨越的bin数超过该值时自动改用并行搜索
+pub const PARALLEL_BIN_SPAN: usize = 4096;
+
+/// 带来源信息的峰搜索结果
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeakHit {
+    /// 峰m/z
+    pub mz: f64,
+    /// 峰强度
+    pub intensity: f64,
+    /// 来源谱图在共享谱图数据中的下标
+    pub spectrum_index: usize,
+    /// 来源谱图的扫描编号
+    pub scan_number: ScanNumber,
+    /// 来源谱图的保留时间 (秒)
+    pub retention_time: RetentionTime,
+}
+
 /// 二进制谱图索引
+///
+/// 谱图存放在共享的[`SharedSpectra`]中，索引只持有其引用计数和自己的bin结构，
+/// 多个索引（如MS1和MS2各一个）可以建立在同一份谱图数据上而不复制谱图。
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BinnedSpectraIndex {
     /// bin大小
@@ -329,8 +550,12 @@ pub struct BinnedSpectraIndex {
     pub mz_range: (f64, f64),
     /// bin数据
     pub bins: Vec<SpectrumBin>,
-    /// 原始谱图数据引用
-    pub spectra: Vec<Spectrum>,
+    /// 共享的谱图数据
+    pub spectra: SharedSpectra,
+    /// 被索引的谱图在`spectra`中的下标
+    pub spectrum_indices: Vec<usize>,
+    /// 每张被索引谱图的第一个峰的全局峰序号，用于解码bin中的峰索引
+    peak_offsets: Vec<usize>,
 }
 
 impl BinnedSpectraIndex {
@@ -340,102 +565,268 @@ impl BinnedSpectraIndex {
             bin_size: constants::DEFAULT_BIN_SIZE,
             mz_range: (0.0, 0.0),
             bins: Vec::new(),
-            spectra: Vec::new(),
+            spectra: SharedSpectra::default(),
+            spectrum_indices: Vec::new(),
+            peak_offsets: Vec::new(),
         }
     }
 
     /// 从谱图列表创建索引
     pub fn new(spectra: Vec<Spectrum>, bin_size: f64) -> CoreResult<Self> {
-        if spectra.is_empty() {
-            return Ok(Self::empty());
+        let indices = (0..spectra.len()).collect();
+        Self::from_shared(SpectraStore::shared(spectra), indices, bin_size)
+    }
+
+    /// 在共享谱图数据上为`spectrum_indices`指定的谱图创建索引，不复制谱图
+    pub fn from_shared(spectra: SharedSpectra, spectrum_indices: Vec<usize>, bin_size: f64) -> CoreResult<Self> {
+        if let Some(&index) = spectrum_indices.iter().find(|&&index| index >= spectra.len()) {
+            return Err(CoreError::InvalidFormat(format!(
+                "Spectrum index {} out of range (0..{})",
+                index,
+                spectra.len()
+            )));
         }
 
-        // 计算全局m/z范围
+        let mut index = Self { bin_size, spectra, ..Self::empty() };
+        index.index_spectra(spectrum_indices);
+        Ok(index)
+    }
+
+    /// 追加谱图并增量更新索引
+    ///
+    /// 已有的峰不会重新分配bin：bin网格沿用已有的起点和bin大小，新峰超出当前m/z范围时
+    /// 只在两端补充bin。追加k个峰的代价为O(k + 新增bin数)，向下扩展时还要把已有的bin整体后移，
+    /// 为O(bin数)但不访问其中的峰；谱图数据仍与其他索引共享时，第一次追加会复制一次谱图列表。
+    /// 因此分批建立索引的总代价与一次建立相当，而不是每批都为O(总峰数)。
+    ///
+    /// 搜索到的峰与对所有谱图一次建立的索引相同，但网格起点可能不同，结果中峰的先后顺序可能不同。
+    pub fn add_spectra(&mut self, new: Vec<Spectrum>) {
+        let first = self.spectra.len();
+        Arc::make_mut(&mut self.spectra).extend(new);
+        self.index_spectra((first..self.spectra.len()).collect());
+    }
+
+    /// 把`spectra`中`indices`指定的谱图加入索引，必要时扩展bin网格
+    fn index_spectra(&mut self, indices: Vec<usize>) {
+        // 计算新谱图的m/z范围
         let mut min_mz = f64::INFINITY;
         let mut max_mz = f64::NEG_INFINITY;
 
-        for spectrum in &spectra {
-            if let Some(range) = spectrum.mz_range() {
+        for &index in &indices {
+            if let Some(range) = self.spectra[index].mz_range() {
                 min_mz = min_mz.min(range.start);
                 max_mz = max_mz.max(range.end);
             }
         }
 
-        if min_mz.is_infinite() || max_mz.is_infinite() {
-            return Ok(Self::empty());
+        if min_mz.is_finite() && max_mz.is_finite() {
+            if self.bins.is_empty() {
+                self.mz_range = (min_mz, max_mz);
+            }
+            self.extend_bins(min_mz, max_mz);
+        }
+
+        // 填充bins，峰以全局序号（之前所有被索引谱图的峰数 + 谱图内序号）标识
+        let mut offset = match (self.peak_offsets.last(), self.spectrum_indices.last()) {
+            (Some(&last_offset), Some(&last_index)) => last_offset + self.spectra[last_index].peak_count(),
+            _ => 0,
+        };
+        for index in indices {
+            self.peak_offsets.push(offset);
+            self.spectrum_indices.push(index);
+            for (peak_idx, (mz, intensity)) in self.spectra[index].peaks_iter().enumerate() {
+                let bin_idx = (((mz - self.mz_range.0) / self.bin_size) as usize).min(self.bins.len() - 1);
+                self.bins[bin_idx].add_peak(offset + peak_idx, intensity);
+            }
+            offset += self.spectra[index].peak_count();
         }
+    }
 
-        let mz_range = (min_mz, max_mz);
-        let num_bins = ((max_mz - min_mz) / bin_size).ceil() as usize;
+    /// 扩展bin网格使其覆盖`min_mz`到`max_mz`，已有的bin保持不变
+    fn extend_bins(&mut self, min_mz: f64, max_mz: f64) {
+        let bin_size = self.bin_size;
+        if min_mz < self.mz_range.0 {
+            let extra = ((self.mz_range.0 - min_mz) / bin_size).ceil() as usize;
+            let origin = self.mz_range.0 - extra as f64 * bin_size;
+            let mut bins: Vec<SpectrumBin> = (0..extra)
+                .map(|i| {
+                    let start = origin + (i as f64) * bin_size;
+                    SpectrumBin::new(start..start + bin_size)
+                })
+                .collect();
+            bins.append(&mut self.bins);
+            self.bins = bins;
+            self.mz_range.0 = origin;
+        }
 
-        // 创建bins
-        let mut bins = Vec::with_capacity(num_bins);
-        for i in 0..num_bins {
-            let start = min_mz + (i as f64) * bin_size;
-            let end = start + bin_size;
-            bins.push(SpectrumBin::new(start..end));
+        let num_bins = (((max_mz - self.mz_range.0) / bin_size).ceil() as usize).max(1);
+        for i in self.bins.len()..num_bins {
+            let start = self.mz_range.0 + (i as f64) * bin_size;
+            self.bins.push(SpectrumBin::new(start..start + bin_size));
         }
+        self.mz_range.1 = self.mz_range.1.max(max_mz);
+    }
 
-        // 填充bins
-        for (spectrum_idx, spectrum) in spectra.iter().enumerate() {
-            for (peak_idx, (mz, _)) in spectrum.peaks.iter().enumerate() {
-                let bin_idx = ((*mz - min_mz) / bin_size) as usize;
-                if bin_idx < bins.len() {
-                    // 使用复合索引来唯一标识峰
-                    let global_peak_index = spectrum_idx * spectrum.peaks.len() + peak_idx;
-                    bins[bin_idx].add_peak_index(global_peak_index);
+    /// 搜索m/z范围内的峰
+    ///
+    /// 结果按bin、谱图、峰的顺序排列；跨越超过[`PARALLEL_BIN_SPAN`]个bin时并行搜索，结果相同。
+    pub fn search_range(&self, mz_range: (f64, f64)) -> CoreResult<Vec<Peak>> {
+        Ok(self.dispatch_range(mz_range, f64::NEG_INFINITY))
+    }
+
+    /// 搜索m/z范围内强度不低于`min_intensity`的峰
+    ///
+    /// 最大强度低于阈值的bin直接跳过，不访问其中的峰，
+    /// 高阈值的稀疏查询只需遍历范围内的bin。
+    pub fn search_range_min_intensity(&self, mz_range: (f64, f64), min_intensity: f64) -> CoreResult<Vec<Peak>> {
+        Ok(self.dispatch_range(mz_range, min_intensity))
+    }
+
+    /// 用`threads`个工作线程并行搜索m/z范围内的峰，0表示使用rayon的全局线程池
+    ///
+    /// bin跨度被切成连续的块，每块的结果按顺序拼接，与串行搜索的结果完全一致。
+    pub fn search_range_parallel(&self, mz_range: (f64, f64), threads: usize) -> CoreResult<Vec<Peak>> {
+        Ok(self.collect_range_parallel(mz_range, f64::NEG_INFINITY, threads))
+    }
+
+    /// 按bin跨度选择串行或并行搜索
+    fn dispatch_range(&self, mz_range: (f64, f64), min_intensity: f64) -> Vec<Peak> {
+        match self.bin_span(mz_range) {
+            Some(span) if span.len() > PARALLEL_BIN_SPAN => self.collect_range_parallel(mz_range, min_intensity, 0),
+            _ => self.collect_range(mz_range, min_intensity).0,
+        }
+    }
+
+    /// 搜索m/z在`mz`容差内的峰，容差可以是[`Tolerance`]或[`ToleranceModel`]
+    pub fn search_mz(&self, mz: f64, tolerance: impl MzTolerance) -> CoreResult<Vec<Peak>> {
+        self.search_range(tolerance.window(mz))
+    }
+
+    /// 搜索m/z范围内的峰，返回(谱图在`spectra`中的下标, 峰下标)
+    ///
+    /// 与[`BinnedSpectraIndex::search_range`]访问相同的bin，但保留峰的来源，
+    /// 便于调用方按谱图分组（如XIC提取）。结果按bin、谱图、峰的顺序排列。
+    pub fn search_range_locations(&self, mz_range: (f64, f64)) -> Vec<(usize, usize)> {
+        let Some(span) = self.bin_span(mz_range) else {
+            return Vec::new();
+        };
+        let mut locations = Vec::new();
+        for bin in &self.bins[span] {
+            for &global_peak_index in &bin.peak_indices {
+                let (spectrum_idx, peak_idx) = self.decode_global_index(global_peak_index);
+                let mz = self.spectra[spectrum_idx].mz[peak_idx];
+                if mz >= mz_range.0 && mz <= mz_range.1 {
+                    locations.push((spectrum_idx, peak_idx));
                 }
             }
         }
+        locations
+    }
 
-        Ok(Self {
-            bin_size,
-            mz_range,
-            bins,
-            spectra,
-        })
+    /// 搜索m/z范围内的峰并附带来源谱图的信息，顺序与[`BinnedSpectraIndex::search_range_locations`]相同
+    pub fn search_range_detailed(&self, mz_range: (f64, f64)) -> Vec<PeakHit> {
+        self.search_range_locations(mz_range)
+            .into_iter()
+            .map(|(spectrum_index, peak_index)| {
+                let spectrum = &self.spectra[spectrum_index];
+                let (mz, intensity) = (spectrum.mz[peak_index], spectrum.intensity[peak_index]);
+                PeakHit {
+                    mz,
+                    intensity,
+                    spectrum_index,
+                    scan_number: spectrum.scan.scan_number,
+                    retention_time: spectrum.scan.retention_time,
+                }
+            })
+            .collect()
     }
 
-    /// 搜索m/z范围内的峰
-    pub fn search_range(&self, mz_range: (f64, f64)) -> CoreResult<Vec<Peak>> {
-        let mut results = Vec::new();
+    /// 最大强度不低于`threshold`的bin，返回(m/z范围, 最大强度)
+    pub fn bins_above(&self, threshold: f64) -> Vec<(Range<f64>, f64)> {
+        self.bins
+            .iter()
+            .filter(|bin| !bin.peak_indices.is_empty() && bin.max_intensity >= threshold)
+            .map(|bin| (bin.mz_range.clone(), bin.max_intensity))
+            .collect()
+    }
+
+    /// 与`mz_range`重叠的bin下标范围
+    fn bin_span(&self, mz_range: (f64, f64)) -> Option<Range<usize>> {
+        if self.bins.is_empty() {
+            return None;
+        }
 
         let start_bin = ((mz_range.0 - self.mz_range.0) / self.bin_size).floor() as isize;
         let end_bin = ((mz_range.1 - self.mz_range.0) / self.bin_size).ceil() as isize;
 
-        let start_bin = start_bin.max(0) as usize;
-        let end_bin = end_bin.min((self.bins.len() - 1) as isize) as usize;
+        // 边界上的峰可能因浮点舍入或末端归并落在前一个bin中，起始bin多取一个
+        let start_bin = (start_bin - 1).max(0) as usize;
+        let end_bin = end_bin.min((self.bins.len() - 1) as isize);
+        if end_bin < start_bin as isize {
+            return None;
+        }
+        Some(start_bin..end_bin as usize + 1)
+    }
+
+    /// 收集范围内强度不低于`min_intensity`的峰，同时返回实际访问的峰数
+    fn collect_range(&self, mz_range: (f64, f64), min_intensity: f64) -> (Vec<Peak>, usize) {
+        let mut results = Vec::new();
+        let inspected = match self.bin_span(mz_range) {
+            Some(span) => self.collect_bins(&self.bins[span], mz_range, min_intensity, &mut results),
+            None => 0,
+        };
+        (results, inspected)
+    }
+
+    /// 并行收集范围内强度不低于`min_intensity`的峰
+    fn collect_range_parallel(&self, mz_range: (f64, f64), min_intensity: f64, threads: usize) -> Vec<Peak> {
+        let Some(span) = self.bin_span(mz_range) else {
+            return Vec::new();
+        };
+        let bins = &self.bins[span];
+        let workers = if threads == 0 { rayon::current_num_threads() } else { threads };
+        let chunk_size = bins.len().div_ceil(workers).max(1);
+
+        let search = || -> Vec<Vec<Peak>> {
+            bins.par_chunks(chunk_size)
+                .map(|chunk| {
+                    let mut local = Vec::new();
+                    self.collect_bins(chunk, mz_range, min_intensity, &mut local);
+                    local
+                })
+                .collect()
+        };
+        let chunks = match threads {
+            0 => search(),
+            _ => match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+                Ok(pool) => pool.install(search),
+                Err(_) => search(),
+            },
+        };
+        chunks.concat()
+    }
 
-        for bin_idx in start_bin..=end_bin {
-            let bin = &self.bins[bin_idx];
+    /// 把`bins`中落在范围内且强度不低于`min_intensity`的峰追加到`results`，返回访问的峰数
+    fn collect_bins(&self, bins: &[SpectrumBin], mz_range: (f64, f64), min_intensity: f64, results: &mut Vec<Peak>) -> usize {
+        let mut inspected = 0;
+        for bin in bins.iter().filter(|bin| bin.max_intensity >= min_intensity) {
             for &global_peak_index in &bin.peak_indices {
-                // 从全局索引计算谱图索引和峰索引
+                inspected += 1;
                 let (spectrum_idx, peak_idx) = self.decode_global_index(global_peak_index);
-                
-                if let Some(spectrum) = self.spectra.get(spectrum_idx) {
-                    if let Some((mz, intensity)) = spectrum.peaks.get(peak_idx) {
-                        if *mz >= mz_range.0 && *mz <= mz_range.1 {
-                            results.push((*mz, *intensity));
-                        }
-                    }
+                let spectrum = &self.spectra[spectrum_idx];
+                let mz = spectrum.mz[peak_idx];
+                if mz >= mz_range.0 && mz <= mz_range.1 && spectrum.intensity[peak_idx] >= min_intensity {
+                    results.push((mz, spectrum.intensity[peak_idx]));
                 }
             }
         }
-
-        Ok(results)
+        inspected
     }
 
-    /// 根据全局索引解码谱图索引和峰索引
+    /// 根据全局峰序号解码谱图下标（在`spectra`中）和峰下标
     fn decode_global_index(&self, global_index: usize) -> (usize, usize) {
-        // 这是一个简化的实现，实际中可能需要更复杂的索引管理
-        let mut cumulative_peaks = 0;
-        for (spectrum_idx, spectrum) in self.spectra.iter().enumerate() {
-            if cumulative_peaks + spectrum.peaks.len() > global_index {
-                return (spectrum_idx, global_index - cumulative_peaks);
-            }
-            cumulative_peaks += spectrum.peaks.len();
-        }
-        (0, 0) // 默认值，不应该到达这里
+        let position = self.peak_offsets.partition_point(|&offset| offset <= global_index) - 1;
+        (self.spectrum_indices[position], global_index - self.peak_offsets[position])
     }
 
     /// 获取bin数量
@@ -443,26 +834,170 @@ impl BinnedSpectraIndex {
         self.bins.len()
     }
 
-    /// 获取谱图数量
+    /// 获取被索引的谱图数量
     pub fn spectrum_count(&self) -> usize {
-        self.spectra.len()
+        self.spectrum_indices.len()
     }
 
     /// 获取总峰数量
     pub fn total_peak_count(&self) -> usize {
-        self.spectra.iter().map(|s| s.peaks.len()).sum()
+        self.spectrum_indices.iter().map(|&index| self.spectra[index].peak_count()).sum()
+    }
+
+    /// 共享的谱图数据
+    pub fn shared_spectra(&self) -> &SharedSpectra {
+        &self.spectra
+    }
+
+    /// 检查内部结构是否自洽（谱图下标、峰偏移和bin中的峰序号均在范围内），
+    /// 用于校验从外部读入的索引，不一致时返回描述
+    pub(crate) fn check_consistency(&self) -> Result<(), String> {
+        if let Some(&index) = self.spectrum_indices.iter().find(|&&index| index >= self.spectra.len()) {
+            return Err(format!("spectrum index {} out of range (0..{})", index, self.spectra.len()));
+        }
+        if self.peak_offsets.len() != self.spectrum_indices.len() {
+            return Err(format!(
+                "{} peak offsets for {} indexed spectra",
+                self.peak_offsets.len(),
+                self.spectrum_indices.len()
+            ));
+        }
+        if let Some(index) = self.spectra.iter().position(|spectrum| spectrum.mz.len() != spectrum.intensity.len()) {
+            return Err(format!("spectrum {} has m/z and intensity arrays of different lengths", index));
+        }
+        let mut expected = 0;
+        for (&offset, &index) in self.peak_offsets.iter().zip(&self.spectrum_indices) {
+            if offset != expected {
+                return Err(format!("peak offset {} does not match expected {}", offset, expected));
+            }
+            expected += self.spectra[index].peak_count();
+        }
+        match self.bins.iter().flat_map(|bin| &bin.peak_indices).find(|&&peak| peak >= expected) {
+            Some(peak) => Err(format!("bin peak index {} out of range (0..{})", peak, expected)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Python可用的二进制谱图索引
+#[cfg(feature = "python")]
+#[pyclass]
+pub struct SpectraIndex {
+    pub index: BinnedSpectraIndex,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl SpectraIndex {
+    /// 为MSObject列表创建索引
+    #[new]
+    #[pyo3(signature = (ms_objects, bin_size=1.0))]
+    fn new(ms_objects: Vec<MSObject>, bin_size: f64) -> PyResult<Self> {
+        let spectra = ms_objects.into_iter().map(|ms_object| ms_object.spectrum).collect();
+        BinnedSpectraIndex::new(spectra, bin_size)
+            .map(|index| Self { index })
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// bin数量
+    #[getter]
+    fn bin_count(&self) -> usize {
+        self.index.bin_count()
+    }
+
+    /// 被索引的谱图数量
+    #[getter]
+    fn spectrum_count(&self) -> usize {
+        self.index.spectrum_count()
+    }
+
+    /// 总峰数量
+    #[getter]
+    fn total_peak_count(&self) -> usize {
+        self.index.total_peak_count()
+    }
+
+    /// 保存到文件，可用load读回
+    fn save(&self, path: std::path::PathBuf) -> PyResult<()> {
+        self.index
+            .save(path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 读入save写出的索引文件
+    #[staticmethod]
+    fn load(path: std::path::PathBuf) -> PyResult<Self> {
+        BinnedSpectraIndex::load(path)
+            .map(|index| Self { index })
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 追加MSObject列表并增量更新索引，已有的峰不重新分配bin
+    fn add_spectra(&mut self, ms_objects: Vec<MSObject>) {
+        self.index.add_spectra(ms_objects.into_iter().map(|ms_object| ms_object.spectrum).collect());
+    }
+
+    /// 搜索m/z范围内的峰
+    fn search_range(&self, mz_range: (f64, f64)) -> PyResult<Vec<Peak>> {
+        self.index
+            .search_range(mz_range)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 搜索m/z范围内的峰，每个结果带有来源谱图的下标、扫描编号和保留时间
+    fn search_range_detailed(&self, mz_range: (f64, f64)) -> Vec<PeakHit> {
+        self.index.search_range_detailed(mz_range)
+    }
+
+    /// 并行执行多个m/z范围查询，结果与ranges一一对应；num_threads为None时使用全局线程池
+    #[pyo3(signature = (ranges, num_threads=None))]
+    fn search_peaks_batch(&self, py: Python, ranges: Vec<(f64, f64)>, num_threads: Option<usize>) -> Vec<Vec<Peak>> {
+        let searcher = crate::search::ParallelRangeSearcher::new(&self.index);
+        py.allow_threads(|| searcher.search_many(&ranges, num_threads.unwrap_or(0)))
+    }
+
+    /// 搜索m/z在`mz`的`tolerance`（ToleranceModel）范围内的峰
+    fn search_mz(&self, mz: f64, tolerance: PyToleranceModel) -> PyResult<Vec<Peak>> {
+        self.index
+            .search_mz(mz, &tolerance.model)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 搜索m/z在`mz`容差内的峰，tolerance为数值(ppm)、("ppm"/"da", 数值)元组或ToleranceModel
+    fn search_around(&self, mz: f64, tolerance: &Bound<'_, PyAny>) -> PyResult<Vec<Peak>> {
+        let tolerance = PyToleranceModel::from_python(tolerance)?;
+        self.index
+            .search_mz(mz, &tolerance)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 搜索m/z范围内强度不低于`min_intensity`的峰，跳过最大强度低于阈值的bin
+    fn search_range_min_intensity(&self, mz_range: (f64, f64), min_intensity: f64) -> PyResult<Vec<Peak>> {
+        self.index
+            .search_range_min_intensity(mz_range, min_intensity)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 最大强度不低于`threshold`的bin，返回((起始m/z, 结束m/z), 最大强度)列表
+    fn bins_above(&self, threshold: f64) -> Vec<((f64, f64), f64)> {
+        self.index
+            .bins_above(threshold)
+            .into_iter()
+            .map(|(range, max_intensity)| ((range.start, range.end), max_intensity))
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::test_spectrum::pseudo_random;
 
     #[test]
     fn test_spectrum_creation() {
         let spectrum = Spectrum::ms1().unwrap();
         assert_eq!(spectrum.level, 1);
-        assert!(spectrum.peaks.is_empty());
+        assert_eq!(spectrum.peak_count(), 0);
         assert!(!spectrum.has_precursor());
     }
 
@@ -483,8 +1018,79 @@ mod tests {
         spectrum.add_peak(100.0, 1000.0).unwrap();
         
         spectrum.sort_peaks();
-        assert_eq!(spectrum.peaks[0].0, 100.0);
-        assert_eq!(spectrum.peaks[1].0, 200.0);
+        assert_eq!(spectrum.mz_slice(), &[100.0, 200.0]);
+        assert_eq!(spectrum.intensity_slice(), &[1000.0, 2000.0]);
+    }
+
+    #[test]
+    fn test_range_extraction_includes_both_boundaries() {
+        let mut seed = 31;
+        let mut peaks: Vec<Peak> = (0..100_000)
+            .map(|_| ((100.0 + 1900.0 * pseudo_random(&mut seed) * 1e4).round() / 1e4, pseudo_random(&mut seed)))
+            .collect();
+        peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut sorted = Spectrum::ms2().unwrap();
+        sorted.add_peaks(peaks.iter().copied()).unwrap();
+        sorted.set_precursor(PrecursorInfo { mz: 650.3, charge: 2, ..PrecursorInfo::default() });
+        sorted.set_scan_number(42);
+        assert!(sorted.is_sorted());
+        // 同样的峰以打乱的顺序存放，走线性扫描路径
+        let mut unsorted = sorted.clone();
+        unsorted.set_peaks(peaks.iter().rev().copied());
+        assert!(!unsorted.is_sorted());
+
+        let brute_force = |spectrum: &Spectrum, low: f64, high: f64| -> (Vec<f64>, Vec<f64>) {
+            spectrum.peaks_iter().filter(|&(mz, _)| low <= mz && mz <= high).unzip()
+        };
+        let first = peaks[0].0;
+        let last = peaks[peaks.len() - 1].0;
+        let mut windows = vec![(first, first), (last, last), (first, last), (first - 1.0, first), (last, last + 1.0)];
+        windows.extend([(0.0, 50.0), (2500.0, 3000.0), (700.0, 600.0)]);
+        for i in (0..peaks.len()).step_by(9973) {
+            // 上下限恰为峰的m/z，包括可能重复的m/z
+            windows.push((peaks[i].0, peaks[(i + 500).min(peaks.len() - 1)].0));
+        }
+        for (low, high) in windows {
+            let expected = brute_force(&sorted, low, high);
+            assert_eq!(sorted.peaks_in_range(low, high), expected, "[{}, {}]", low, high);
+            if low <= high && (first..=last).contains(&low) && peaks.iter().any(|peak| peak.0 == low) {
+                assert_eq!(expected.0.first(), Some(&low));
+            }
+            let (mut mz, _) = unsorted.peaks_in_range(low, high);
+            mz.sort_by(|a, b| a.total_cmp(b));
+            assert_eq!(mz, expected.0);
+        }
+
+        let center = peaks[50_000].0;
+        let (low, high) = Tolerance::PPM(10.0).window(center);
+        assert_eq!(sorted.peaks_in_tolerance(center, Tolerance::PPM(10.0)), brute_force(&sorted, low, high));
+        assert!(sorted.peaks_in_tolerance(center, Tolerance::PPM(10.0)).0.contains(&center));
+
+        let subspectrum = sorted.subspectrum(400.0, 500.0);
+        assert_eq!((subspectrum.mz_slice().to_vec(), subspectrum.intensity_slice().to_vec()), brute_force(&sorted, 400.0, 500.0));
+        assert!(subspectrum.is_sorted());
+        assert_eq!(subspectrum.scan, sorted.scan);
+        assert_eq!(subspectrum.precursor, sorted.precursor);
+        assert_eq!(subspectrum.level, 2);
+    }
+
+    #[test]
+    fn test_parallel_peak_arrays() {
+        let mut spectrum = Spectrum::ms2().unwrap();
+        spectrum.add_peaks([(300.0, 3.0), (100.0, 1.0), (200.0, 2.0), (100.0, 4.0)]).unwrap();
+        spectrum.sort_peaks();
+        assert_eq!(spectrum.peaks(), vec![(100.0, 1.0), (100.0, 4.0), (200.0, 2.0), (300.0, 3.0)]);
+        assert_eq!(spectrum.peak(3), Some((300.0, 3.0)));
+        assert_eq!(spectrum.peak(4), None);
+
+        spectrum.retain_peaks(|(mz, intensity)| mz > 100.0 || intensity > 2.0);
+        assert_eq!(spectrum.mz_slice(), &[100.0, 200.0, 300.0]);
+        assert_eq!(spectrum.intensity_slice(), &[4.0, 2.0, 3.0]);
+
+        assert!(spectrum.set_peak_arrays(vec![1.0, 2.0], vec![1.0]).is_err());
+        assert_eq!(spectrum.peak_count(), 3);
+        spectrum.set_peak_arrays(vec![5.0], vec![6.0]).unwrap();
+        assert_eq!(spectrum.peaks_iter().collect::<Vec<_>>(), vec![(5.0, 6.0)]);
     }
 
     #[test]
@@ -518,6 +1124,295 @@ mod tests {
         let results = index.search_range((90.0, 110.0)).unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].0, 100.5);
+
+        // 在同一份数据上只索引MS2，不复制谱图
+        let ms2 = BinnedSpectraIndex::from_shared(index.shared_spectra().clone(), vec![1], 50.0).unwrap();
+        assert!(Arc::ptr_eq(index.shared_spectra(), ms2.shared_spectra()));
+        assert_eq!(ms2.search_range((140.0, 160.0)).unwrap(), vec![(150.5, 1500.0)]);
+        assert!(ms2.search_range((90.0, 110.0)).unwrap().is_empty());
+        assert!(BinnedSpectraIndex::from_shared(ms2.spectra.clone(), vec![2], 50.0).is_err());
+
+        let hybrid = ToleranceModel::Hybrid { ppm: 10.0, min_da: 0.6 };
+        assert_eq!(index.search_mz(100.0, &hybrid).unwrap(), vec![(100.5, 1000.0)]);
+        assert!(index.search_mz(100.0, Tolerance::PPM(10.0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_ppm_search_widens_at_high_mz() {
+        // 两对峰相对目标的偏移都是15 ppm
+        let mut spectrum = Spectrum::ms2().unwrap();
+        spectrum.add_peaks([(200.0, 1.0), (200.003, 2.0), (3000.0, 3.0), (3000.045, 4.0)]).unwrap();
+        let index = BinnedSpectraIndex::new(vec![spectrum], 1.0).unwrap();
+
+        assert_eq!(index.search_mz(200.0, Tolerance::PPM(10.0)).unwrap(), vec![(200.0, 1.0)]);
+        assert_eq!(index.search_mz(200.0, Tolerance::PPM(20.0)).unwrap().len(), 2);
+        assert_eq!(index.search_mz(3000.0, Tolerance::PPM(10.0)).unwrap(), vec![(3000.0, 3.0)]);
+        assert_eq!(index.search_mz(3000.0, Tolerance::PPM(20.0)).unwrap().len(), 2);
+        // 0.01 Da的绝对窗口在m/z 200时宽于20 ppm，在m/z 3000时窄于10 ppm
+        assert_eq!(index.search_mz(200.0, Tolerance::Absolute(0.01)).unwrap().len(), 2);
+        assert_eq!(index.search_mz(3000.0, Tolerance::Absolute(0.01)).unwrap(), vec![(3000.0, 3.0)]);
+    }
+
+    #[test]
+    fn test_search_range_detailed_reports_provenance() {
+        let mut spectra = Vec::new();
+        for (scan, rt, peaks) in [(7, 12.5, vec![(100.5, 1000.0), (200.5, 2000.0)]), (9, 14.0, vec![(150.5, 1500.0), (201.0, 10.0)])] {
+            let mut spectrum = Spectrum::ms1().unwrap();
+            spectrum.add_peaks(peaks).unwrap();
+            spectrum.set_scan_number(scan);
+            spectrum.set_retention_time(rt).unwrap();
+            spectra.push(spectrum);
+        }
+        let index = BinnedSpectraIndex::new(spectra, 50.0).unwrap();
+
+        let range = (140.0, 210.0);
+        let mut hits = index.search_range_detailed(range);
+        assert_eq!(hits.iter().map(|hit| (hit.mz, hit.intensity)).collect::<Vec<_>>(), index.search_range(range).unwrap());
+        hits.sort_by(|a, b| a.mz.total_cmp(&b.mz));
+        assert_eq!(
+            hits,
+            vec![
+                PeakHit { mz: 150.5, intensity: 1500.0, spectrum_index: 1, scan_number: 9, retention_time: 14.0 },
+                PeakHit { mz: 200.5, intensity: 2000.0, spectrum_index: 0, scan_number: 7, retention_time: 12.5 },
+                PeakHit { mz: 201.0, intensity: 10.0, spectrum_index: 1, scan_number: 9, retention_time: 14.0 },
+            ]
+        );
+        assert!(index.search_range_detailed((500.0, 600.0)).is_empty());
+    }
+
+    #[test]
+    fn test_intensity_prefilter() {
+        // 1000个低强度bin，600-601之间有一个强峰
+        let mut spectrum = Spectrum::ms1().unwrap();
+        spectrum.add_peaks((0..1000).flat_map(|i| [(100.2 + i as f64, 1.0), (100.7 + i as f64, 2.0)])).unwrap();
+        spectrum.add_peak(600.5, 1e6).unwrap();
+        let index = BinnedSpectraIndex::new(vec![spectrum], 1.0).unwrap();
+
+        let hot_bin = &index.bins[500];
+        assert_eq!(hot_bin.max_intensity, 1e6);
+        assert_eq!(hot_bin.total_intensity, 1e6 + 3.0);
+        let above = index.bins_above(1000.0);
+        assert_eq!(above.len(), 1);
+        assert_eq!(above[0].1, 1e6);
+        assert!(above[0].0.contains(&600.5));
+
+        let range = (100.0, 1100.0);
+        assert_eq!(index.search_range_min_intensity(range, 1000.0).unwrap(), vec![(600.5, 1e6)]);
+        let (_, inspected) = index.collect_range(range, 1000.0);
+        let (naive, naive_inspected) = index.collect_range(range, f64::NEG_INFINITY);
+        assert_eq!(inspected, 3);
+        assert_eq!(naive_inspected, 2001);
+        assert_eq!(naive.len(), 2001);
+    }
+
+    fn random_index(spectra: usize, peaks: usize, bin_size: f64) -> BinnedSpectraIndex {
+        let mut seed = 42;
+        let spectra: Vec<Spectrum> = (0..spectra)
+            .map(|_| {
+                let mut spectrum = Spectrum::ms1().unwrap();
+                spectrum
+                    .add_peaks((0..peaks).map(|_| (100.0 + 1900.0 * pseudo_random(&mut seed), 1e4 * pseudo_random(&mut seed))))
+                    .unwrap();
+                spectrum
+            })
+            .collect();
+        BinnedSpectraIndex::new(spectra, bin_size).unwrap()
+    }
+
+    #[test]
+    fn test_parallel_range_search_matches_serial() {
+        let index = random_index(50, 400, 0.1);
+        for range in [(100.0, 2000.0), (512.3, 537.9), (1999.0, 2100.0), (50.0, 60.0)] {
+            let (serial, _) = index.collect_range(range, f64::NEG_INFINITY);
+            for threads in [0, 1, 3, 8] {
+                assert_eq!(index.search_range_parallel(range, threads).unwrap(), serial);
+            }
+            let (filtered, _) = index.collect_range(range, 5000.0);
+            assert_eq!(index.collect_range_parallel(range, 5000.0, 4), filtered);
+        }
+
+        // 宽范围查询自动走并行路径
+        let wide = (100.0, 2000.0);
+        assert!(index.bin_span(wide).unwrap().len() > PARALLEL_BIN_SPAN);
+        assert_eq!(index.search_range(wide).unwrap(), index.collect_range(wide, f64::NEG_INFINITY).0);
+        assert_eq!(index.search_range_min_intensity(wide, 9000.0).unwrap(), index.collect_range(wide, 9000.0).0);
+    }
+
+    #[test]
+    #[ignore = "timing benchmark; run with --ignored --nocapture"]
+    fn bench_parallel_range_search_1m_peaks() {
+        let index = random_index(1000, 1000, 0.01);
+        let range = (100.0, 2000.0);
+
+        let start = std::time::Instant::now();
+        let (serial, _) = index.collect_range(range, f64::NEG_INFINITY);
+        let serial_time = start.elapsed();
+        let start = std::time::Instant::now();
+        let parallel = index.search_range_parallel(range, 0).unwrap();
+        let parallel_time = start.elapsed();
+
+        assert_eq!(parallel, serial);
+        println!(
+            "{} peaks: serial {:?}, parallel {:?} ({} threads, {:.1}x)",
+            serial.len(), serial_time, parallel_time, rayon::current_num_threads(),
+            serial_time.as_secs_f64() / parallel_time.as_secs_f64(),
+        );
+    }
+
+    #[test]
+    #[ignore = "timing benchmark; run with --ignored --nocapture"]
+    fn bench_parallel_arrays_vs_tuples() {
+        let index = random_index(1000, 1000, 0.01);
+        // 以(m/z, 强度)元组存放的同一份峰数据作为对照
+        let tuples: Vec<PeakList> = index.spectra.iter().map(Spectrum::peaks).collect();
+        let time = |f: &mut dyn FnMut() -> f64| {
+            let start = std::time::Instant::now();
+            let mut checksum = 0.0;
+            for _ in 0..20 {
+                checksum += std::hint::black_box(f());
+            }
+            (start.elapsed() / 20, checksum)
+        };
+
+        let (arrays_tic, a) = time(&mut || index.spectra.iter().map(Spectrum::total_ion_current).sum());
+        let (tuples_tic, b) = time(&mut || tuples.iter().map(|peaks| peaks.iter().map(|peak| peak.1).sum::<f64>()).sum());
+        assert_eq!(a, b);
+
+        // 窄窗口范围查询：解码bin中的峰后只需比较m/z，命中时才读强度
+        let ranges: Vec<(f64, f64)> = (0..2000).map(|i| (100.0 + i as f64 * 0.95, 100.0 + i as f64 * 0.95 + 0.02)).collect();
+        let (arrays_range, a) = time(&mut || {
+            ranges.iter().map(|&range| index.collect_range(range, f64::NEG_INFINITY).0.len() as f64).sum()
+        });
+        let (tuples_range, b) = time(&mut || {
+            let mut found = 0;
+            for &range in &ranges {
+                let mut results = Vec::new();
+                for bin in &index.bins[index.bin_span(range).unwrap()] {
+                    for &global in &bin.peak_indices {
+                        let (spectrum, peak) = index.decode_global_index(global);
+                        let (mz, intensity) = tuples[spectrum][peak];
+                        if mz >= range.0 && mz <= range.1 {
+                            results.push((mz, intensity));
+                        }
+                    }
+                }
+                found += results.len();
+            }
+            found as f64
+        });
+        assert_eq!(a, b);
+
+        // XIC式的逐谱图m/z扫描：m/z连续存放时可整块载入
+        let searcher = crate::xic::simd_search::SIMDSearcher::new();
+        let targets: Vec<f64> = (0..20).map(|i| 150.0 + i as f64 * 91.3).collect();
+        let (arrays_scan, a) = time(&mut || {
+            let hits = |spectrum: &Spectrum| targets.iter().map(|&mz| searcher.find_mz_in_tolerance(spectrum.mz_slice(), mz, 0.01).len()).sum::<usize>();
+            index.spectra.iter().map(hits).sum::<usize>() as f64
+        });
+        let (tuples_scan, b) = time(&mut || {
+            let hits = |peaks: &PeakList| targets.iter().map(|&mz| searcher.find_in_tolerance(peaks, mz, 0.01).len()).sum::<usize>();
+            tuples.iter().map(hits).sum::<usize>() as f64
+        });
+        assert_eq!(a, b);
+
+        let ratio = |arrays: std::time::Duration, tuples: std::time::Duration| tuples.as_secs_f64() / arrays.as_secs_f64();
+        println!(
+            "arrays vs tuples: TIC {:?} / {:?} ({:.2}x), range queries {:?} / {:?} ({:.2}x), m/z scans {:?} / {:?} ({:.2}x)",
+            arrays_tic, tuples_tic, ratio(arrays_tic, tuples_tic),
+            arrays_range, tuples_range, ratio(arrays_range, tuples_range),
+            arrays_scan, tuples_scan, ratio(arrays_scan, tuples_scan),
+        );
+    }
+
+    #[test]
+    fn test_incremental_index_matches_one_shot() {
+        let mut seed = 7;
+        let mut batch = |low: f64, high: f64| -> Vec<Spectrum> {
+            (0..20)
+                .map(|_| {
+                    let mut spectrum = Spectrum::ms1().unwrap();
+                    spectrum
+                        .add_peaks((0..50).map(|_| (low + (high - low) * pseudo_random(&mut seed), 1e4 * pseudo_random(&mut seed))))
+                        .unwrap();
+                    spectrum
+                })
+                .collect()
+        };
+        // 第二批扩展到已有最小值以下，第三批扩展到最大值以上
+        let batches = [batch(500.0, 900.0), batch(150.0, 700.0), batch(800.0, 1300.0)];
+
+        let one_shot = BinnedSpectraIndex::new(batches.concat(), 0.5).unwrap();
+        let mut incremental = BinnedSpectraIndex::new(Vec::new(), 0.5).unwrap();
+        let snapshot = incremental.clone();
+        for batch in &batches {
+            incremental.add_spectra(batch.clone());
+        }
+        assert_eq!(snapshot.spectrum_count(), 0);
+        assert_eq!(incremental.spectrum_count(), one_shot.spectrum_count());
+        assert_eq!(incremental.total_peak_count(), one_shot.total_peak_count());
+        assert!(incremental.mz_range.0 <= one_shot.mz_range.0);
+        assert_eq!(incremental.mz_range.1, one_shot.mz_range.1);
+
+        let sorted = |mut peaks: Vec<Peak>| {
+            peaks.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+            peaks
+        };
+        for range in [(100.0, 1400.0), (150.0, 151.0), (499.0, 501.5), (612.25, 612.75), (1299.0, 1300.0)] {
+            let expected = sorted(one_shot.search_range(range).unwrap());
+            assert_eq!(sorted(incremental.search_range(range).unwrap()), expected);
+            assert_eq!(
+                sorted(incremental.search_range_min_intensity(range, 5000.0).unwrap()),
+                sorted(one_shot.search_range_min_intensity(range, 5000.0).unwrap())
+            );
+        }
+        for &mz in batches[1][0].mz_slice() {
+            assert_eq!(incremental.search_range((mz, mz)).unwrap(), one_shot.search_range((mz, mz)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_uneven_spectrum_sizes_match_brute_force() {
+        // 峰数相差悬殊且含空谱图，全局峰序号必须按累计峰数解码
+        let mut seed = 11;
+        let spectra: Vec<Spectrum> = [1, 0, 2000, 3, 0, 500, 17, 1]
+            .iter()
+            .map(|&count| {
+                let mut spectrum = Spectrum::ms1().unwrap();
+                spectrum
+                    .add_peaks((0..count).map(|_| (100.0 + 1400.0 * pseudo_random(&mut seed), 1e4 * pseudo_random(&mut seed))))
+                    .unwrap();
+                spectrum
+            })
+            .collect();
+        let full = BinnedSpectraIndex::new(spectra, 2.0).unwrap();
+        // 只索引部分谱图，且顺序与存储顺序不同
+        let subset = BinnedSpectraIndex::from_shared(full.shared_spectra().clone(), vec![6, 2, 0, 4, 7], 2.0).unwrap();
+
+        let sorted = |mut locations: Vec<(usize, usize)>| {
+            locations.sort_unstable();
+            locations
+        };
+        let ranges = [(100.0, 1500.0), (250.0, 251.5), (733.3, 790.1), (1499.0, 1500.0)];
+        for index in [&full, &subset] {
+            for range in ranges {
+                let expected: Vec<(usize, usize)> = index
+                    .spectrum_indices
+                    .iter()
+                    .flat_map(|&s| index.spectra[s].mz_slice().iter().enumerate().map(move |(p, &mz)| (s, p, mz)))
+                    .filter(|&(_, _, mz)| mz >= range.0 && mz <= range.1)
+                    .map(|(s, p, _)| (s, p))
+                    .collect();
+                let locations = index.search_range_locations(range);
+                assert_eq!(sorted(locations.clone()), sorted(expected.clone()), "{:?}", range);
+
+                let mut peaks = index.search_range(range).unwrap();
+                let mut expected_peaks: Vec<Peak> = expected.iter().map(|&(s, p)| index.spectra[s].peak(p).unwrap()).collect();
+                peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+                expected_peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+                assert_eq!(peaks, expected_peaks);
+            }
+        }
+        assert_eq!(full.search_range_locations((100.0, 1500.0)).len(), 2522);
     }
 
     #[test]
@@ -532,8 +1427,9 @@ mod tests {
         // 有效谱图应该通过
         assert!(spectrum.validate().is_ok());
         
-        // 无效峰应该失败
-        spectrum.add_peak(-1.0, 1000.0).unwrap();
+        // add_peak会拒绝无效峰，用set_peaks直接写入模拟损坏数据
+        assert!(spectrum.add_peak(-1.0, 1000.0).is_err());
+        spectrum.set_peaks([(100.0, 1000.0), (-1.0, 1000.0)]);
         assert!(spectrum.validate().is_err());
     }
 }
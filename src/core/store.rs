@@ -0,0 +1,288 @@
+//! 多个工具共用的谱图存储
+//!
+//! 一次运行的谱图加载后放入[`SpectraStore`]，由[`SharedSpectra`]（`Arc<SpectraStore>`）共享给
+//! XIC提取器、二进制索引、范围查询和离子迁移率分析，各工具只持有引用计数和自己的索引结构。
+//! 存储建立时把每张谱图的峰按m/z排序，并预先计算每张谱图的元数据和按保留时间的排列，
+//! 需要这些信息的工具不必再遍历峰或各自排序。
+//!
+//! 谱图保持加载顺序，下标在各工具间通用；按保留时间的顺序见[`SpectraStore::rt_order`]。
+//! 存储本身不可变，修改需经[`SpectraStore::update`]，仍被共享时先复制一份。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use serde::{Deserialize, Serialize, Serializer};
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+/// 多个索引和提取器共用的谱图数据
+///
+/// 克隆只增加引用计数。
+pub type SharedSpectra = Arc<SpectraStore>;
+
+/// 预先计算的单张谱图元数据
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectrumMeta {
+    /// MS级别
+    pub level: MSLevel,
+    /// 扫描编号
+    pub scan_number: ScanNumber,
+    /// 保留时间 (秒)
+    pub retention_time: RetentionTime,
+    /// 漂移时间，没有离子迁移率信息时为0
+    pub drift_time: DriftTime,
+    /// 峰数量
+    pub peak_count: usize,
+    /// 总离子流
+    pub total_ion_current: f64,
+    /// 最小和最大m/z，没有峰时为None
+    pub mz_range: Option<(f64, f64)>,
+}
+
+impl SpectrumMeta {
+    /// 由峰已按m/z排序的谱图计算
+    fn of(spectrum: &Spectrum) -> Self {
+        let mz = spectrum.mz_slice();
+        Self {
+            level: spectrum.level,
+            scan_number: spectrum.scan.scan_number,
+            retention_time: spectrum.scan.retention_time,
+            drift_time: spectrum.scan.drift_time,
+            peak_count: mz.len(),
+            total_ion_current: spectrum.total_ion_current(),
+            mz_range: mz.first().zip(mz.last()).map(|(&low, &high)| (low, high)),
+        }
+    }
+}
+
+/// 不可变的谱图存储
+///
+/// 解引用为`[Spectrum]`，按加载顺序访问谱图。序列化时只写出谱图，
+/// 元数据在读入时重新计算。
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(from = "Vec<Spectrum>")]
+pub struct SpectraStore {
+    /// 峰按m/z排序的谱图
+    spectra: Vec<Spectrum>,
+    /// 与`spectra`对应的元数据
+    metadata: Vec<SpectrumMeta>,
+    /// 谱图下标按保留时间排序，相同保留时间保持加载顺序
+    rt_order: Vec<usize>,
+}
+
+impl SpectraStore {
+    /// 由谱图列表创建，每张谱图的峰按m/z排序
+    pub fn new(spectra: Vec<Spectrum>) -> Self {
+        let mut store = Self::default();
+        store.extend(spectra);
+        store
+    }
+
+    /// 创建并包装为[`SharedSpectra`]
+    pub fn shared(spectra: Vec<Spectrum>) -> SharedSpectra {
+        Arc::new(Self::new(spectra))
+    }
+
+    /// 按加载顺序排列的谱图
+    pub fn spectra(&self) -> &[Spectrum] {
+        &self.spectra
+    }
+
+    /// 每张谱图的元数据，与[`SpectraStore::spectra`]一一对应
+    pub fn metadata(&self) -> &[SpectrumMeta] {
+        &self.metadata
+    }
+
+    /// 按保留时间排序的谱图下标
+    pub fn rt_order(&self) -> &[usize] {
+        &self.rt_order
+    }
+
+    /// 指定MS级别的谱图下标，按加载顺序
+    pub fn indices_of_level(&self, level: MSLevel) -> Vec<usize> {
+        (0..self.metadata.len()).filter(|&index| self.metadata[index].level == level).collect()
+    }
+
+    /// 保留时间范围 (秒)，没有谱图时为None
+    pub fn rt_range(&self) -> Option<(RetentionTime, RetentionTime)> {
+        let first = self.rt_order.first()?;
+        let last = self.rt_order.last()?;
+        Some((self.metadata[*first].retention_time, self.metadata[*last].retention_time))
+    }
+
+    /// 保留时间在`[rt_range.0, rt_range.1]`内的谱图在[`SpectraStore::rt_order`]中的位置
+    ///
+    /// `rt_range.0 > rt_range.1`时为空。
+    pub fn rt_span(&self, rt_range: (RetentionTime, RetentionTime)) -> Range<usize> {
+        let rt = |&index: &usize| self.metadata[index].retention_time;
+        let start = self.rt_order.partition_point(|index| rt(index) < rt_range.0);
+        let end = self.rt_order.partition_point(|index| rt(index) <= rt_range.1);
+        start..end.max(start)
+    }
+
+    /// 取出谱图列表
+    pub fn into_spectra(self) -> Vec<Spectrum> {
+        self.spectra
+    }
+
+    /// 在末尾追加谱图，峰按m/z排序并计算元数据
+    ///
+    /// 新谱图的保留时间都不早于已有谱图且自身有序时（按采集顺序追加）只追加排列，
+    /// 否则重新排列全部谱图。
+    pub fn extend(&mut self, new: impl IntoIterator<Item = Spectrum>) {
+        let first = self.spectra.len();
+        for mut spectrum in new {
+            spectrum.sort_peaks();
+            self.metadata.push(SpectrumMeta::of(&spectrum));
+            self.spectra.push(spectrum);
+        }
+
+        let mut previous = self.rt_order.last().map(|&last| self.metadata[last].retention_time);
+        let in_order = self.metadata[first..].iter().all(|meta| {
+            let ordered = previous.is_none_or(|rt| rt <= meta.retention_time);
+            previous = Some(meta.retention_time);
+            ordered
+        });
+        self.rt_order.extend(first..self.spectra.len());
+        if !in_order {
+            let metadata = &self.metadata;
+            self.rt_order.sort_by(|&a, &b| metadata[a].retention_time.total_cmp(&metadata[b].retention_time));
+        }
+    }
+
+    /// 修改共享存储中的谱图列表，之后重新计算元数据
+    ///
+    /// 存储仍被其他工具共享时先复制一份，已建立的索引继续使用修改前的数据。
+    pub fn update<R>(store: &mut SharedSpectra, modify: impl FnOnce(&mut Vec<Spectrum>) -> R) -> R {
+        let inner = Arc::make_mut(store);
+        let mut spectra = std::mem::take(&mut inner.spectra);
+        let result = modify(&mut spectra);
+        *inner = Self::new(spectra);
+        result
+    }
+}
+
+impl Deref for SpectraStore {
+    type Target = [Spectrum];
+
+    fn deref(&self) -> &[Spectrum] {
+        &self.spectra
+    }
+}
+
+impl From<Vec<Spectrum>> for SpectraStore {
+    fn from(spectra: Vec<Spectrum>) -> Self {
+        Self::new(spectra)
+    }
+}
+
+impl FromIterator<Spectrum> for SpectraStore {
+    fn from_iter<I: IntoIterator<Item = Spectrum>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+impl Serialize for SpectraStore {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.spectra.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectrum(level: MSLevel, rt: f64, peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::new(level).unwrap();
+        spectrum.set_retention_time(rt).unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum
+    }
+
+    #[test]
+    fn test_store_metadata_and_rt_order() {
+        let mut store = SpectraStore::new(vec![
+            spectrum(1, 20.0, &[(500.0, 10.0), (300.0, 5.0)]),
+            spectrum(2, 10.0, &[]),
+            spectrum(1, 10.0, &[(400.0, 1.0)]),
+        ]);
+        assert_eq!(store.len(), 3);
+        assert!(store.iter().all(Spectrum::is_sorted));
+        assert_eq!(store[0].mz_slice(), &[300.0, 500.0]);
+
+        let meta = store.metadata()[0];
+        assert_eq!((meta.level, meta.peak_count, meta.total_ion_current), (1, 2, 15.0));
+        assert_eq!(meta.mz_range, Some((300.0, 500.0)));
+        assert_eq!(store.metadata()[1].mz_range, None);
+
+        assert_eq!(store.rt_order(), &[1, 2, 0]);
+        assert_eq!(store.rt_range(), Some((10.0, 20.0)));
+        assert_eq!(store.rt_span((10.0, 15.0)), 0..2);
+        assert!(store.rt_span((15.0, 10.0)).is_empty());
+        assert_eq!(store.indices_of_level(1), vec![0, 2]);
+
+        // 按采集顺序追加时只追加排列，否则重新排列
+        store.extend(vec![spectrum(1, 30.0, &[])]);
+        assert_eq!(store.rt_order(), &[1, 2, 0, 3]);
+        store.extend(vec![spectrum(2, 5.0, &[])]);
+        assert_eq!(store.rt_order(), &[4, 1, 2, 0, 3]);
+    }
+
+    #[test]
+    fn test_update_copies_only_when_shared() {
+        let mut store = SpectraStore::shared(vec![spectrum(1, 1.0, &[(100.0, 1.0)])]);
+        let reader = store.clone();
+        assert_eq!(Arc::strong_count(&store), 2);
+
+        SpectraStore::update(&mut store, |spectra| spectra.push(spectrum(2, 0.5, &[])));
+        assert!(!Arc::ptr_eq(&store, &reader));
+        assert_eq!((store.len(), reader.len()), (2, 1));
+        assert_eq!(store.rt_order(), &[1, 0]);
+
+        // 不再共享时原地修改
+        let before = Arc::as_ptr(&store);
+        SpectraStore::update(&mut store, |spectra| spectra.truncate(1));
+        assert_eq!(Arc::as_ptr(&store), before);
+        assert_eq!(store.metadata().len(), 1);
+    }
+
+    #[test]
+    fn test_tools_share_one_store() {
+        use crate::core::spectrum::BinnedSpectraIndex;
+        use crate::ion_mobility::parser::IonMobilityAnalyzer;
+        use crate::search::range_query::RangeQueryEngine;
+        use crate::xic::extractor::XICSExtractor;
+
+        let mut frame = spectrum(1, 2.0, &[(500.0, 20.0)]);
+        frame.set_drift_time(0.02).unwrap();
+        let store = SpectraStore::shared(vec![spectrum(1, 1.0, &[(500.0, 10.0)]), spectrum(2, 1.5, &[(300.0, 1.0)]), frame]);
+        let peaks = store[0].mz_slice().as_ptr();
+
+        let xic = XICSExtractor::from_shared(store.clone(), 10.0, 1.0).unwrap();
+        let index = BinnedSpectraIndex::from_shared(store.clone(), store.indices_of_level(1), 1.0).unwrap();
+        let range_query = RangeQueryEngine::from_shared(store.clone());
+        let mobility = IonMobilityAnalyzer::from_store(&store, 1e-4).unwrap();
+
+        // 提取器自身和两个索引各一份，二进制索引和范围查询各一份，离子迁移率分析只借用
+        assert_eq!(Arc::strong_count(&store), 1 + 3 + 1 + 1);
+        for shared in [&xic.share_spectra(), index.shared_spectra(), range_query.shared_spectra()] {
+            assert!(Arc::ptr_eq(shared, &store));
+            assert_eq!(shared[0].mz_slice().as_ptr(), peaks);
+        }
+
+        assert_eq!(xic.extract_single_xic(500.0, 1, "", 0.0, 10.0).unwrap().intensity_array, vec![10.0, 20.0]);
+        assert_eq!(index.search_range((499.0, 501.0)).unwrap().len(), 2);
+        assert_eq!(range_query.query((0.0, 1000.0), (1.2, 3.0)).len(), 2);
+        assert_eq!((mobility.bins().count(), mobility.skipped_spectra()), (1, 2));
+    }
+
+    #[test]
+    fn test_serialized_as_spectrum_list() {
+        let store = SpectraStore::new(vec![spectrum(1, 1.0, &[(200.0, 2.0), (100.0, 1.0)])]);
+        let bytes = bincode::serialize(&store).unwrap();
+        assert_eq!(bytes, bincode::serialize(store.spectra()).unwrap());
+
+        let restored: SpectraStore = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(restored.metadata(), store.metadata());
+        assert!(restored[0].is_sorted());
+    }
+}
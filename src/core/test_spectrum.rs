@@ -1,18 +1,46 @@
 //! 谱图测试模块
 
-use crate::core::types::*;
+use crate::core::centroid::{PROFILE_SPECTRUM, SPECTRUM_TYPE_KEY};
 use crate::core::spectrum::Spectrum;
-use crate::core::CoreResult;
+
+/// 线性同余伪随机数，取值[0, 1)
+pub(crate) fn pseudo_random(seed: &mut u64) -> f64 {
+    *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    (*seed >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// 标准正态分布随机数（Box-Muller）
+pub(crate) fn gaussian_noise(seed: &mut u64) -> f64 {
+    let u = pseudo_random(seed).max(f64::MIN_POSITIVE);
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * pseudo_random(seed)).cos()
+}
+
+/// 在`range`内每隔`step`采样若干(中心, 高度)高斯峰之和，得到标记为轮廓数据的谱图
+pub(crate) fn gaussian_profile(centers: &[(f64, f64)], sigma: f64, range: (f64, f64), step: f64) -> Spectrum {
+    let mut spectrum = Spectrum::new(1).unwrap();
+    let points = ((range.1 - range.0) / step) as usize;
+    for i in 0..=points {
+        let mz = range.0 + i as f64 * step;
+        let intensity: f64 = centers
+            .iter()
+            .map(|(center, height)| height * (-(mz - center).powi(2) / (2.0 * sigma * sigma)).exp())
+            .sum();
+        spectrum.add_peak(mz, intensity).unwrap();
+    }
+    spectrum.add_additional_info(SPECTRUM_TYPE_KEY, PROFILE_SPECTRUM).unwrap();
+    spectrum
+}
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use crate::core::spectrum::Spectrum;
+    use crate::core::CoreResult;
 
     #[test]
     fn test_spectrum_creation() -> CoreResult<()> {
-        let mut spectrum = Spectrum::ms1()?;
+        let spectrum = Spectrum::ms1()?;
         assert_eq!(spectrum.level, 1);
-        assert_eq!(spectrum.peaks.len(), 0);
+        assert_eq!(spectrum.peak_count(), 0);
         Ok(())
     }
 
@@ -24,9 +52,9 @@ mod tests {
         spectrum.add_peak(100.0, 1000.0)?;
         spectrum.add_peak(200.0, 2000.0)?;
 
-        assert_eq!(spectrum.peaks.len(), 2);
-        assert_eq!(spectrum.peaks[0], (100.0, 1000.0));
-        assert_eq!(spectrum.peaks[1], (200.0, 2000.0));
+        assert_eq!(spectrum.peak_count(), 2);
+        assert_eq!(spectrum.peak(0).unwrap(), (100.0, 1000.0));
+        assert_eq!(spectrum.peak(1).unwrap(), (200.0, 2000.0));
 
         Ok(())
     }
@@ -59,7 +87,7 @@ mod tests {
         let mut spectrum = Spectrum::ms1()?;
 
         // 设置扫描信息
-        spectrum.set_scan_number(12345)?;
+        spectrum.set_scan_number(12345);
         spectrum.set_retention_time(60.5)?;
         spectrum.set_drift_time(12.3)?;
 
@@ -79,16 +107,16 @@ mod tests {
         spectrum.add_peak(100.0, 1000.0)?;
         spectrum.add_peak(200.0, 2000.0)?;
 
-        // 检查是否按m/z排序
-        assert_eq!(spectrum.peaks[0].0, 100.0);
-        assert_eq!(spectrum.peaks[1].0, 200.0);
-        assert_eq!(spectrum.peaks[2].0, 300.0);
+        // 添加时保持插入顺序
+        assert_eq!(spectrum.mz_slice()[0], 300.0);
+        assert_eq!(spectrum.mz_slice()[1], 100.0);
+        assert_eq!(spectrum.mz_slice()[2], 200.0);
 
         // 测试排序方法
         spectrum.sort_peaks();
-        assert_eq!(spectrum.peaks[0].0, 100.0);
-        assert_eq!(spectrum.peaks[1].0, 200.0);
-        assert_eq!(spectrum.peaks[2].0, 300.0);
+        assert_eq!(spectrum.mz_slice()[0], 100.0);
+        assert_eq!(spectrum.mz_slice()[1], 200.0);
+        assert_eq!(spectrum.mz_slice()[2], 300.0);
 
         Ok(())
     }
@@ -0,0 +1,295 @@
+//! 强度变换
+//!
+//! 谱库搜索和机器学习预处理常用的强度变换（平方根、log2(x+1)、排名），
+//! 并提供逆变换。已应用的变换按顺序记录在谱图的额外信息中，
+//! 用于检测并拒绝重复变换。离子注入时间归一化同样记录所用系数并拒绝重复执行。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+
+/// 记录已应用强度变换的额外信息键，值为逗号分隔的变换名称
+pub const INTENSITY_TRANSFORM_KEY: &str = "intensity_transform";
+
+/// 记录注入时间归一化系数（注入时间 / 参考时间）的额外信息键
+pub const INJECTION_TIME_FACTOR_KEY: &str = "injection_time_factor";
+
+/// 注入时间归一化的默认参考时间 (毫秒)
+pub const DEFAULT_REFERENCE_INJECTION_TIME_MS: f64 = 100.0;
+
+/// 强度变换方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntensityTransform {
+    /// 平方根
+    Sqrt,
+    /// log2(x + 1)
+    Log2p1,
+    /// 排名 / 峰数（最强峰为1.0，相同强度取平均排名）
+    Rank,
+}
+
+impl IntensityTransform {
+    /// 按名称解析变换方法
+    pub fn from_name(name: &str) -> CoreResult<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "sqrt" => Ok(Self::Sqrt),
+            "log2(x+1)" | "log2p1" | "log" => Ok(Self::Log2p1),
+            "rank" => Ok(Self::Rank),
+            _ => Err(CoreError::InvalidFormat(format!("Unknown intensity transform: {}", name))),
+        }
+    }
+
+    /// 变换名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sqrt => "sqrt",
+            Self::Log2p1 => "log2(x+1)",
+            Self::Rank => "rank",
+        }
+    }
+
+    /// 是否存在逆变换（排名变换丢失了原始强度，不可逆）
+    pub fn has_inverse(&self) -> bool {
+        !matches!(self, Self::Rank)
+    }
+
+    /// 对强度原地应用变换
+    pub fn apply(&self, intensities: &mut [f64]) {
+        match self {
+            Self::Sqrt => intensities.iter_mut().for_each(|x| *x = x.sqrt()),
+            Self::Log2p1 => intensities.iter_mut().for_each(|x| *x = (*x + 1.0).log2()),
+            Self::Rank => {
+                let n = intensities.len() as f64;
+                let mut order: Vec<usize> = (0..intensities.len()).collect();
+                order.sort_by(|&a, &b| intensities[a].total_cmp(&intensities[b]));
+
+                let mut ranks = vec![0.0; intensities.len()];
+                let mut start = 0;
+                while start < order.len() {
+                    let mut end = start;
+                    while end + 1 < order.len() && intensities[order[end + 1]] == intensities[order[start]] {
+                        end += 1;
+                    }
+                    // 排名从1开始，相同强度取平均排名
+                    let rank = (start + end) as f64 / 2.0 + 1.0;
+                    for &index in &order[start..=end] {
+                        ranks[index] = rank / n;
+                    }
+                    start = end + 1;
+                }
+                intensities.copy_from_slice(&ranks);
+            }
+        }
+    }
+
+    /// 对强度原地应用逆变换
+    pub fn invert(&self, intensities: &mut [f64]) -> CoreResult<()> {
+        match self {
+            Self::Sqrt => intensities.iter_mut().for_each(|x| *x = *x * *x),
+            Self::Log2p1 => intensities.iter_mut().for_each(|x| *x = x.exp2() - 1.0),
+            Self::Rank => {
+                return Err(CoreError::NoInverseTransform { transform: self.name().to_string() });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Spectrum {
+    /// 已应用的强度变换（按应用顺序）
+    pub fn intensity_transforms(&self) -> Vec<IntensityTransform> {
+        self.get_additional_info(INTENSITY_TRANSFORM_KEY)
+            .map(|chain| {
+                chain
+                    .split(',')
+                    .filter_map(|name| IntensityTransform::from_name(name).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 变换峰强度
+    ///
+    /// 已应用过变换时返回错误，除非`force`为true（变换在前一次结果上叠加）。
+    pub fn transform_intensities(&mut self, transform: IntensityTransform, force: bool) -> CoreResult<()> {
+        let mut applied = self.intensity_transforms();
+        if !applied.is_empty() && !force {
+            return Err(CoreError::TransformAlreadyApplied { applied: join_names(&applied) });
+        }
+
+        transform.apply(self.intensity_slice_mut());
+
+        applied.push(transform);
+        self.set_transform_chain(&applied);
+        Ok(())
+    }
+
+    /// 撤销最近一次强度变换，返回被撤销的变换
+    pub fn invert_intensity_transform(&mut self) -> CoreResult<IntensityTransform> {
+        let mut applied = self.intensity_transforms();
+        let transform = *applied.last().ok_or_else(|| CoreError::KeyNotFound {
+            key: INTENSITY_TRANSFORM_KEY.to_string(),
+        })?;
+
+        // 在副本上撤销，失败时谱图保持不变
+        let mut intensities = self.intensity_slice().to_vec();
+        transform.invert(&mut intensities)?;
+        self.intensity_slice_mut().copy_from_slice(&intensities);
+
+        applied.pop();
+        self.set_transform_chain(&applied);
+        Ok(transform)
+    }
+
+    /// 返回变换后的峰列表副本，不修改谱图本身
+    ///
+    /// 供相似度计算等需要临时变换的场景使用。
+    pub fn transformed_peaks(&self, transform: IntensityTransform) -> PeakList {
+        let mut intensities = self.intensity_slice().to_vec();
+        transform.apply(&mut intensities);
+        self.mz_slice().iter().copied().zip(intensities).collect()
+    }
+
+    /// 注入时间归一化系数：注入时间 / `reference_ms`
+    ///
+    /// 没有注入时间、注入时间或参考时间不为正时返回None。
+    pub fn injection_time_factor(&self, reference_ms: f64) -> Option<f64> {
+        self.scan
+            .injection_time
+            .filter(|&time| time > 0.0 && reference_ms > 0.0)
+            .map(|time| time / reference_ms)
+    }
+
+    /// 已应用的注入时间归一化系数
+    pub fn applied_injection_time_factor(&self) -> Option<f64> {
+        self.get_additional_info(INJECTION_TIME_FACTOR_KEY).and_then(|value| value.parse().ok())
+    }
+
+    /// 按离子注入时间归一化强度：所有强度除以(注入时间 / `reference_ms`)，返回所用系数
+    ///
+    /// 系数记录在额外信息[`INJECTION_TIME_FACTOR_KEY`]中；已归一化过的谱图返回错误，
+    /// 没有注入时间的谱图返回`KeyNotFound`。
+    pub fn normalize_by_injection_time(&mut self, reference_ms: f64) -> CoreResult<f64> {
+        if let Some(factor) = self.applied_injection_time_factor() {
+            return Err(CoreError::TransformAlreadyApplied {
+                applied: format!("injection time normalization (factor {})", factor),
+            });
+        }
+        if reference_ms <= 0.0 {
+            return Err(CoreError::InvalidFormat(format!(
+                "Reference injection time must be positive, got {}",
+                reference_ms
+            )));
+        }
+        let factor = self.injection_time_factor(reference_ms).ok_or_else(|| CoreError::KeyNotFound {
+            key: "injection_time".to_string(),
+        })?;
+
+        for intensity in self.intensity_slice_mut() {
+            *intensity /= factor;
+        }
+        self.additional_info.push(KeyValue::new(INJECTION_TIME_FACTOR_KEY, factor.to_string()));
+        Ok(factor)
+    }
+
+    /// 更新额外信息中记录的变换链，为空时移除该键
+    fn set_transform_chain(&mut self, applied: &[IntensityTransform]) {
+        self.additional_info.retain(|kv| kv.key != INTENSITY_TRANSFORM_KEY);
+        if !applied.is_empty() {
+            self.additional_info.push(KeyValue::new(INTENSITY_TRANSFORM_KEY, join_names(applied)));
+        }
+    }
+}
+
+fn join_names(transforms: &[IntensityTransform]) -> String {
+    transforms.iter().map(|t| t.name()).collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectrum(intensities: &[f64]) -> Spectrum {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum
+            .add_peaks(intensities.iter().enumerate().map(|(i, &intensity)| (100.0 + i as f64, intensity)))
+            .unwrap();
+        spectrum
+    }
+
+    #[test]
+    fn test_sqrt_then_inverse_restores_originals() {
+        let original = [0.0, 1.0, 17.5, 12345.678];
+        for transform in [IntensityTransform::Sqrt, IntensityTransform::Log2p1] {
+            let mut s = spectrum(&original);
+            s.transform_intensities(transform, false).unwrap();
+            assert_eq!(s.intensity_transforms(), vec![transform]);
+            assert_eq!(s.invert_intensity_transform().unwrap(), transform);
+            for (peak, expected) in s.peaks_iter().zip(original) {
+                assert!((peak.1 - expected).abs() < 1e-9 * expected.max(1.0));
+            }
+            assert!(s.get_additional_info(INTENSITY_TRANSFORM_KEY).is_none());
+        }
+    }
+
+    #[test]
+    fn test_double_transform_requires_force() {
+        let mut s = spectrum(&[16.0]);
+        s.transform_intensities(IntensityTransform::Sqrt, false).unwrap();
+        assert!(matches!(
+            s.transform_intensities(IntensityTransform::Sqrt, false),
+            Err(CoreError::TransformAlreadyApplied { .. })
+        ));
+        assert_eq!(s.intensity_slice()[0], 4.0);
+
+        s.transform_intensities(IntensityTransform::Sqrt, true).unwrap();
+        assert_eq!(s.intensity_slice()[0], 2.0);
+        assert_eq!(s.get_additional_info(INTENSITY_TRANSFORM_KEY), Some("sqrt,sqrt"));
+    }
+
+    #[test]
+    fn test_rank_transform() {
+        let mut s = spectrum(&[30.0, 10.0, 40.0, 20.0]);
+        s.transform_intensities(IntensityTransform::Rank, false).unwrap();
+        let intensities: Vec<f64> = s.peaks_iter().map(|p| p.1).collect();
+        assert_eq!(intensities, vec![0.75, 0.25, 1.0, 0.5]);
+        assert!(matches!(s.invert_intensity_transform(), Err(CoreError::NoInverseTransform { .. })));
+
+        let mut ties = vec![5.0, 5.0, 1.0];
+        IntensityTransform::Rank.apply(&mut ties);
+        assert_eq!(ties, vec![2.5 / 3.0, 2.5 / 3.0, 1.0 / 3.0]);
+    }
+
+    #[test]
+    fn test_injection_time_normalization() {
+        let mut fast = spectrum(&[500.0, 50.0]);
+        fast.scan.injection_time = Some(50.0);
+        let mut slow = spectrum(&[1000.0, 100.0]);
+        slow.scan.injection_time = Some(100.0);
+
+        assert_eq!(fast.normalize_by_injection_time(DEFAULT_REFERENCE_INJECTION_TIME_MS).unwrap(), 0.5);
+        assert_eq!(slow.normalize_by_injection_time(DEFAULT_REFERENCE_INJECTION_TIME_MS).unwrap(), 1.0);
+        assert_eq!(fast.peaks(), slow.peaks());
+        assert_eq!(fast.applied_injection_time_factor(), Some(0.5));
+        assert_eq!(fast.get_additional_info(INJECTION_TIME_FACTOR_KEY), Some("0.5"));
+
+        assert!(matches!(fast.normalize_by_injection_time(100.0), Err(CoreError::TransformAlreadyApplied { .. })));
+        assert_eq!(fast.intensity_slice()[0], 1000.0);
+
+        let mut missing = spectrum(&[1.0]);
+        assert!(matches!(missing.normalize_by_injection_time(100.0), Err(CoreError::KeyNotFound { .. })));
+        missing.scan.injection_time = Some(10.0);
+        assert!(missing.normalize_by_injection_time(0.0).is_err());
+        assert!(missing.applied_injection_time_factor().is_none());
+    }
+
+    #[test]
+    fn test_transformed_peaks_does_not_mutate() {
+        let s = spectrum(&[4.0, 9.0]);
+        let peaks = s.transformed_peaks(IntensityTransform::Sqrt);
+        assert_eq!(peaks, vec![(100.0, 2.0), (101.0, 3.0)]);
+        assert_eq!(s.intensity_slice()[1], 9.0);
+        assert!(s.intensity_transforms().is_empty());
+        assert!(IntensityTransform::from_name("LOG2(X+1)").is_ok());
+        assert!(IntensityTransform::from_name("cube").is_err());
+    }
+}
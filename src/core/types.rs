@@ -4,6 +4,9 @@
 
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
 /// 键值对类型，用于存储元数据
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct KeyValue {
@@ -29,14 +32,55 @@ pub type PeakList = Vec<Peak>;
 /// 小规模键值对列表类型（优化内存使用）
 pub type SmallKeyValueList = Vec<KeyValue>;
 
-impl SmallKeyValueList {
-    pub fn new() -> Self {
-        Vec::new()
+/// 扫描极性
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Polarity {
+    /// 未记录
+    #[default]
+    Unknown,
+    /// 正离子模式
+    Positive,
+    /// 负离子模式
+    Negative,
+}
+
+impl Polarity {
+    /// 极性名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Polarity::Unknown => "unknown",
+            Polarity::Positive => "positive",
+            Polarity::Negative => "negative",
+        }
+    }
+
+    /// 按名称解析极性，与[`Polarity::name`]互逆
+    pub fn from_name(name: &str) -> CoreResult<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "unknown" => Ok(Polarity::Unknown),
+            "positive" => Ok(Polarity::Positive),
+            "negative" => Ok(Polarity::Negative),
+            _ => Err(CoreError::InvalidFormat(format!(
+                "Unknown polarity: {} (expected positive, negative or unknown)",
+                name
+            ))),
+        }
+    }
+
+    /// 按极性给电荷加上符号
+    ///
+    /// mzML和mzXML的charge state只记录电荷数，负离子模式下取负值；极性未知时保持原值。
+    pub fn signed_charge(&self, charge: Charge) -> Charge {
+        match self {
+            Polarity::Unknown => charge,
+            Polarity::Positive => charge.saturating_abs(),
+            Polarity::Negative => -charge.saturating_abs(),
+        }
     }
 }
 
 /// 质量容差类型
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Tolerance {
     /// PPM容差
     PPM(f64),
@@ -60,10 +104,177 @@ impl Tolerance {
     }
 }
 
+/// 随m/z变化的容差模型
+///
+/// 单一ppm容差在宽m/z范围内并不总是合适（如TOF在m/z 200以下的绝对误差基本不变），
+/// 模型按m/z给出容差。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ToleranceModel {
+    /// 固定容差
+    Constant(Tolerance),
+    /// 分段容差：(m/z断点, 容差)按断点升序，m/z不低于某断点时使用该段容差，
+    /// 低于第一个断点时使用第一段
+    Piecewise(Vec<(f64, Tolerance)>),
+    /// ppm容差与绝对下限中的较大者
+    Hybrid { ppm: f64, min_da: f64 },
+}
+
+impl ToleranceModel {
+    /// 创建分段模型，断点按升序排序；分段为空时返回错误
+    pub fn piecewise(mut segments: Vec<(f64, Tolerance)>) -> CoreResult<Self> {
+        if segments.is_empty() {
+            return Err(CoreError::InvalidFormat("Piecewise tolerance needs at least one segment".to_string()));
+        }
+        segments.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(ToleranceModel::Piecewise(segments))
+    }
+
+    /// 计算给定m/z的质量容差 (Da)
+    pub fn tolerance_at_mz(&self, mz: f64) -> f64 {
+        match self {
+            ToleranceModel::Constant(tolerance) => tolerance.tolerance_at_mz(mz),
+            ToleranceModel::Piecewise(segments) => {
+                let position = segments.partition_point(|segment| segment.0 <= mz).max(1);
+                segments.get(position - 1).map_or(0.0, |segment| segment.1.tolerance_at_mz(mz))
+            }
+            ToleranceModel::Hybrid { ppm, min_da } => (mz * ppm * 1e-6).max(*min_da),
+        }
+    }
+
+    /// 检查两个m/z值是否在容差范围内
+    pub fn is_within_tolerance(&self, mz1: f64, mz2: f64) -> bool {
+        (mz1 - mz2).abs() <= self.tolerance_at_mz(mz1)
+    }
+}
+
+impl From<Tolerance> for ToleranceModel {
+    fn from(tolerance: Tolerance) -> Self {
+        ToleranceModel::Constant(tolerance)
+    }
+}
+
+/// 可按m/z给出质量容差的类型，接受容差参数的函数对[`Tolerance`]和[`ToleranceModel`]都适用
+pub trait MzTolerance {
+    /// 给定m/z的质量容差 (Da)
+    fn tolerance_at_mz(&self, mz: f64) -> f64;
+
+    /// 检查两个m/z值是否在容差范围内，容差按`mz1`计算
+    fn is_within_tolerance(&self, mz1: f64, mz2: f64) -> bool {
+        (mz1 - mz2).abs() <= self.tolerance_at_mz(mz1)
+    }
+
+    /// 以`mz`为中心的绝对m/z窗口 (下限, 上限)，ppm容差的窗口随m/z增大而变宽
+    fn window(&self, mz: f64) -> (f64, f64) {
+        let tolerance = self.tolerance_at_mz(mz);
+        (mz - tolerance, mz + tolerance)
+    }
+}
+
+impl MzTolerance for Tolerance {
+    fn tolerance_at_mz(&self, mz: f64) -> f64 {
+        Tolerance::tolerance_at_mz(self, mz)
+    }
+}
+
+impl MzTolerance for ToleranceModel {
+    fn tolerance_at_mz(&self, mz: f64) -> f64 {
+        ToleranceModel::tolerance_at_mz(self, mz)
+    }
+}
+
+impl<T: MzTolerance + ?Sized> MzTolerance for &T {
+    fn tolerance_at_mz(&self, mz: f64) -> f64 {
+        (**self).tolerance_at_mz(mz)
+    }
+}
+
+/// Python可用的容差模型
+#[cfg(feature = "python")]
+#[pyclass(name = "ToleranceModel")]
+#[derive(Clone)]
+pub struct PyToleranceModel {
+    pub model: ToleranceModel,
+}
+
+#[cfg(feature = "python")]
+impl PyToleranceModel {
+    fn tolerance_from_unit(value: f64, unit: &str) -> PyResult<Tolerance> {
+        match unit.to_ascii_lowercase().as_str() {
+            "ppm" => Ok(Tolerance::PPM(value)),
+            "da" => Ok(Tolerance::Absolute(value)),
+            _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Unknown tolerance unit: {} (expected 'ppm' or 'da')",
+                unit
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl PyToleranceModel {
+    /// 解析Python传入的容差：数值按ppm处理，也可以是("ppm"/"da", 数值)元组或ToleranceModel
+    pub(crate) fn from_python(obj: &Bound<'_, PyAny>) -> PyResult<ToleranceModel> {
+        if let Ok(model) = obj.extract::<PyToleranceModel>() {
+            return Ok(model.model);
+        }
+        if let Ok(ppm) = obj.extract::<f64>() {
+            return Ok(Tolerance::PPM(ppm).into());
+        }
+        if let Ok((unit, value)) = obj.extract::<(String, f64)>() {
+            return Ok(Self::tolerance_from_unit(value, &unit)?.into());
+        }
+        Err(pyo3::exceptions::PyTypeError::new_err(
+            "tolerance must be a float (ppm), a (\"ppm\" | \"da\", value) tuple or a ToleranceModel",
+        ))
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyToleranceModel {
+    /// 固定ppm容差
+    #[staticmethod]
+    fn ppm(ppm: f64) -> Self {
+        Self { model: Tolerance::PPM(ppm).into() }
+    }
+
+    /// 固定绝对容差 (Da)
+    #[staticmethod]
+    fn absolute(da: f64) -> Self {
+        Self { model: Tolerance::Absolute(da).into() }
+    }
+
+    /// ppm容差与绝对下限 (Da) 中的较大者
+    #[staticmethod]
+    fn hybrid(ppm: f64, min_da: f64) -> Self {
+        Self { model: ToleranceModel::Hybrid { ppm, min_da } }
+    }
+
+    /// 分段容差，每段为(m/z断点, 数值, 单位)，单位为"ppm"或"da"
+    #[staticmethod]
+    fn piecewise(segments: Vec<(f64, f64, String)>) -> PyResult<Self> {
+        let segments = segments
+            .into_iter()
+            .map(|(breakpoint, value, unit)| Ok((breakpoint, Self::tolerance_from_unit(value, &unit)?)))
+            .collect::<PyResult<Vec<_>>>()?;
+        ToleranceModel::piecewise(segments)
+            .map(|model| Self { model })
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// 给定m/z的质量容差 (Da)
+    #[pyo3(name = "tolerance_at_mz")]
+    fn py_tolerance_at_mz(&self, mz: f64) -> f64 {
+        self.model.tolerance_at_mz(mz)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ToleranceModel({:?})", self.model)
+    }
+}
+
 /// 常量定义
 pub mod constants {
-    use super::*;
-    
     /// 默认PPM容差
     pub const DEFAULT_PPM_TOLERANCE: f64 = 10.0;
     
@@ -133,6 +344,15 @@ pub enum CoreError {
 
     #[error("Invalid format: {0}")]
     InvalidFormat(String),
+
+    #[error("IO error on {path:?}: {source}")]
+    Io { path: std::path::PathBuf, source: std::io::Error },
+
+    #[error("Intensity transform already applied: {applied}")]
+    TransformAlreadyApplied { applied: String },
+
+    #[error("Intensity transform {transform} has no inverse")]
+    NoInverseTransform { transform: String },
 }
 
 /// 结果类型
@@ -164,6 +384,34 @@ mod tests {
         assert!(!tolerance.is_within_tolerance(1000.0, 1000.02));
     }
 
+    #[test]
+    fn test_tolerance_models() {
+        let hybrid = ToleranceModel::Hybrid { ppm: 10.0, min_da: 0.002 };
+        // m/z 100：10 ppm仅0.001 Da，使用0.002 Da下限；m/z 1000：10 ppm = 0.01 Da
+        assert_eq!(hybrid.tolerance_at_mz(100.0), 0.002);
+        assert!((hybrid.tolerance_at_mz(1000.0) - 0.01).abs() < 1e-12);
+
+        let piecewise = ToleranceModel::piecewise(vec![
+            (200.0, Tolerance::PPM(5.0)),
+            (0.0, Tolerance::Absolute(0.005)),
+        ])
+        .unwrap();
+        assert_eq!(piecewise.tolerance_at_mz(50.0), 0.005);
+        assert_eq!(piecewise.tolerance_at_mz(199.9), 0.005);
+        assert!((piecewise.tolerance_at_mz(1000.0) - 0.005).abs() < 1e-12);
+        assert!((piecewise.tolerance_at_mz(400.0) - 0.002).abs() < 1e-12);
+        assert!(ToleranceModel::piecewise(Vec::new()).is_err());
+
+        let constant: ToleranceModel = Tolerance::PPM(10.0).into();
+        assert!(MzTolerance::is_within_tolerance(&&constant, 1000.0, 1000.005));
+        assert!(!constant.is_within_tolerance(1000.0, 1000.02));
+
+        // ppm窗口随m/z线性变宽，绝对窗口不变
+        let (low, high) = Tolerance::PPM(10.0).window(3000.0);
+        assert!((low - 2999.97).abs() < 1e-9 && (high - 3000.03).abs() < 1e-9);
+        assert_eq!(Tolerance::Absolute(0.01).window(3000.0), (2999.99, 3000.01));
+    }
+
     #[test]
     fn test_key_value() {
         let kv = KeyValue::new("test", "value");
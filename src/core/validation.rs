@@ -0,0 +1,251 @@
+//! 谱图数据验证
+//!
+//! [`ValidationOptions`]决定哪些情况算作问题：真实仪器会产生没有峰的空扫描，
+//! 是否接受空谱图、是否要求峰按m/z排序、MS2是否必须有前体离子等都可以单独开关。
+//! 默认选项与[`Spectrum::validate`]一致：拒绝空谱图、无效峰和超出范围的MS级别，其余检查关闭。
+//!
+//! [`validate_run`]检查整个运行，统计所有问题而不是在第一个问题处失败。
+
+use std::collections::BTreeMap;
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+
+/// 验证选项，默认值为严格验证
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ValidationOptions {
+    /// 接受没有峰的谱图
+    pub allow_empty_peaks: bool,
+    /// 要求峰按m/z升序排列
+    pub require_sorted: bool,
+    /// m/z上限，超出时记为问题
+    pub max_mz: Option<f64>,
+    /// 要求MS2及以上级别的谱图有前体离子信息
+    pub require_precursor_for_ms2: bool,
+    /// 验证运行时要求保留时间不减
+    pub check_monotonic_rt: bool,
+}
+
+/// 验证发现的问题
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidationIssue {
+    /// 没有峰
+    EmptyPeakList,
+    /// 第一个m/z或强度无效的峰
+    InvalidPeak { mz: f64, intensity: f64 },
+    /// MS级别超出范围
+    InvalidMSLevel { level: MSLevel },
+    /// 峰未按m/z排序
+    UnsortedPeaks,
+    /// 最大m/z超过上限
+    MzAboveMax { mz: f64, max_mz: f64 },
+    /// MS2及以上级别的谱图缺少前体离子信息
+    MissingPrecursor,
+    /// 保留时间小于上一张谱图
+    NonMonotonicRt { previous: RetentionTime, retention_time: RetentionTime },
+}
+
+impl ValidationIssue {
+    /// 问题类别名称，用于统计
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ValidationIssue::EmptyPeakList => "empty_peak_list",
+            ValidationIssue::InvalidPeak { .. } => "invalid_peak",
+            ValidationIssue::InvalidMSLevel { .. } => "invalid_ms_level",
+            ValidationIssue::UnsortedPeaks => "unsorted_peaks",
+            ValidationIssue::MzAboveMax { .. } => "mz_above_max",
+            ValidationIssue::MissingPrecursor => "missing_precursor",
+            ValidationIssue::NonMonotonicRt { .. } => "non_monotonic_rt",
+        }
+    }
+}
+
+impl From<ValidationIssue> for CoreError {
+    fn from(issue: ValidationIssue) -> Self {
+        match issue {
+            ValidationIssue::EmptyPeakList => CoreError::EmptyPeakList,
+            ValidationIssue::InvalidPeak { mz, intensity } => CoreError::InvalidPeakData { mz, intensity },
+            ValidationIssue::InvalidMSLevel { level } => CoreError::InvalidMSLevel {
+                level,
+                min: constants::MIN_MS_LEVEL,
+                max: constants::MAX_MS_LEVEL,
+            },
+            ValidationIssue::UnsortedPeaks => CoreError::InvalidFormat("Peaks are not sorted by m/z".to_string()),
+            ValidationIssue::MzAboveMax { mz, max_mz } => {
+                CoreError::InvalidFormat(format!("Peak m/z {} exceeds the maximum of {}", mz, max_mz))
+            }
+            ValidationIssue::MissingPrecursor => {
+                CoreError::InvalidFormat("MSn spectrum has no precursor information".to_string())
+            }
+            ValidationIssue::NonMonotonicRt { previous, retention_time } => CoreError::InvalidFormat(format!(
+                "Retention time {} is earlier than the previous spectrum ({})",
+                retention_time, previous
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", CoreError::from(*self))
+    }
+}
+
+/// 整个运行的验证结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    /// 检查的谱图数
+    pub spectrum_count: usize,
+    /// 至少有一个问题的谱图数
+    pub invalid_spectrum_count: usize,
+    /// 所有问题，附带谱图在输入中的下标，按下标排列
+    pub issues: Vec<(usize, ValidationIssue)>,
+}
+
+impl ValidationReport {
+    /// 是否没有任何问题
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// 各类问题的数量
+    pub fn counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for (_, issue) in &self.issues {
+            *counts.entry(issue.kind()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+impl Spectrum {
+    /// 按选项检查谱图，返回所有问题；保留时间单调性只在[`validate_run`]中检查
+    pub fn validation_issues(&self, options: &ValidationOptions) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        if self.peak_count() == 0 && !options.allow_empty_peaks {
+            issues.push(ValidationIssue::EmptyPeakList);
+        }
+        let invalid_peak = self
+            .peaks_iter()
+            .find(|&(mz, intensity)| !mz.is_finite() || mz < 0.0 || (intensity < 0.0 && !self.allow_negative_intensities));
+        if let Some((mz, intensity)) = invalid_peak {
+            issues.push(ValidationIssue::InvalidPeak { mz, intensity });
+        }
+        if self.level < constants::MIN_MS_LEVEL || self.level > constants::MAX_MS_LEVEL {
+            issues.push(ValidationIssue::InvalidMSLevel { level: self.level });
+        }
+        if options.require_sorted && !self.mz_slice().windows(2).all(|pair| pair[0] <= pair[1]) {
+            issues.push(ValidationIssue::UnsortedPeaks);
+        }
+        if let Some(max_mz) = options.max_mz {
+            let highest = self.mz_slice().iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            if highest > max_mz {
+                issues.push(ValidationIssue::MzAboveMax { mz: highest, max_mz });
+            }
+        }
+        if options.require_precursor_for_ms2 && self.level > 1 && !self.has_precursor() {
+            issues.push(ValidationIssue::MissingPrecursor);
+        }
+        issues
+    }
+
+    /// 按选项验证谱图，遇到第一个问题时返回对应的错误
+    pub fn validate_with(&self, options: &ValidationOptions) -> CoreResult<()> {
+        match self.validation_issues(options).into_iter().next() {
+            Some(issue) => Err(issue.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 验证整个运行，统计所有谱图的问题
+///
+/// 开启`check_monotonic_rt`时，保留时间小于上一张谱图的谱图记一个问题。
+pub fn validate_run<'a>(spectra: impl IntoIterator<Item = &'a Spectrum>, options: &ValidationOptions) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let mut previous_rt: Option<RetentionTime> = None;
+    for (index, spectrum) in spectra.into_iter().enumerate() {
+        let mut issues = spectrum.validation_issues(options);
+        let retention_time = spectrum.scan.retention_time;
+        if options.check_monotonic_rt {
+            if let Some(previous) = previous_rt.filter(|&previous| retention_time < previous) {
+                issues.push(ValidationIssue::NonMonotonicRt { previous, retention_time });
+            }
+        }
+        previous_rt = Some(retention_time);
+
+        report.spectrum_count += 1;
+        if !issues.is_empty() {
+            report.invalid_spectrum_count += 1;
+            report.issues.extend(issues.into_iter().map(|issue| (index, issue)));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    fn spectrum(level: MSLevel, rt: f64, peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::new(level).unwrap();
+        spectrum.set_retention_time(rt).unwrap();
+        spectrum.set_peaks(peaks.iter().copied());
+        spectrum
+    }
+
+    #[test]
+    fn test_options_relax_single_checks() {
+        let empty = spectrum(1, 1.0, &[]);
+        assert!(matches!(empty.validate(), Err(CoreError::EmptyPeakList)));
+        let lenient = ValidationOptions { allow_empty_peaks: true, ..Default::default() };
+        assert!(empty.validate_with(&lenient).is_ok());
+
+        let unsorted = spectrum(2, 1.0, &[(300.0, 1.0), (200.0, 1.0)]);
+        assert!(unsorted.validate().is_ok());
+        let strict = ValidationOptions {
+            require_sorted: true,
+            max_mz: Some(250.0),
+            require_precursor_for_ms2: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            unsorted.validation_issues(&strict),
+            vec![
+                ValidationIssue::UnsortedPeaks,
+                ValidationIssue::MzAboveMax { mz: 300.0, max_mz: 250.0 },
+                ValidationIssue::MissingPrecursor,
+            ]
+        );
+        assert!(matches!(unsorted.validate_with(&strict), Err(CoreError::InvalidFormat(_))));
+
+        let mut ms2 = spectrum(2, 1.0, &[(-1.0, 1.0), (200.0, 1.0)]);
+        ms2.set_precursor(PrecursorInfo { mz: 400.0, ..Default::default() });
+        assert_eq!(ms2.validation_issues(&strict).len(), 1);
+        assert!(matches!(ms2.validate(), Err(CoreError::InvalidPeakData { .. })));
+    }
+
+    #[test]
+    fn test_validate_run_counts_issues() {
+        let spectra = vec![
+            spectrum(1, 10.0, &[(100.0, 1.0)]),
+            spectrum(1, 12.0, &[]),
+            spectrum(1, 11.0, &[]),
+            spectrum(1, 13.0, &[(100.0, 1.0)]),
+        ];
+
+        let report = validate_run(&spectra, &ValidationOptions::default());
+        assert!(!report.is_valid());
+        assert_eq!((report.spectrum_count, report.invalid_spectrum_count), (4, 2));
+        assert_eq!(report.counts(), BTreeMap::from([("empty_peak_list", 2)]));
+
+        let options = ValidationOptions { allow_empty_peaks: true, check_monotonic_rt: true, ..Default::default() };
+        let report = validate_run(&spectra, &options);
+        assert_eq!(report.issues, vec![(2, ValidationIssue::NonMonotonicRt { previous: 12.0, retention_time: 11.0 })]);
+        assert_eq!(report.issues[0].1.to_string(), "Invalid format: Retention time 11 is earlier than the previous spectrum (12)");
+
+        assert!(validate_run(&spectra[..1], &options).is_valid());
+        assert_eq!(validate_run(&[], &options), ValidationReport::default());
+    }
+}
@@ -0,0 +1,261 @@
+//! 谱图向量化
+//!
+//! 机器学习模型需要定长的输入，这里把谱图在`[min_mz, max_mz)`内按固定宽度分箱：
+//! - 第i个箱覆盖`[min_mz + i·bin_width, min_mz + (i+1)·bin_width)`，落在`max_mz`及以上的峰被忽略；
+//! - 同一箱内的强度按[`BinAggregation`]求和或取最大值；
+//! - 可选地对分箱后的强度做[`IntensityTransform`]变换，再做L2归一化。
+//!
+//! 整个运行按行优先拼成一个矩阵，各行并行计算。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::transform::IntensityTransform;
+use crate::core::types::*;
+use rayon::prelude::*;
+use std::borrow::Borrow;
+
+/// 同一箱内多个峰强度的合并方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BinAggregation {
+    /// 强度之和
+    #[default]
+    Sum,
+    /// 最大强度
+    Max,
+}
+
+impl BinAggregation {
+    /// 按名称解析合并方式
+    pub fn from_name(name: &str) -> CoreResult<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "sum" => Ok(Self::Sum),
+            "max" => Ok(Self::Max),
+            _ => Err(CoreError::InvalidFormat(format!("Unknown bin aggregation: {} (expected sum or max)", name))),
+        }
+    }
+
+    /// 合并方式名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sum => "sum",
+            Self::Max => "max",
+        }
+    }
+}
+
+/// 分箱设置
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectrumBinning {
+    /// 第一个箱的下限
+    pub min_mz: f64,
+    /// 最后一个箱的上限（不包含）
+    pub max_mz: f64,
+    /// 箱宽 (Da)
+    pub bin_width: f64,
+    /// 同一箱内强度的合并方式
+    pub aggregation: BinAggregation,
+    /// 分箱后的强度变换，None时保持原始强度
+    pub transform: Option<IntensityTransform>,
+    /// 是否把向量归一化为单位L2范数
+    pub normalize: bool,
+}
+
+impl SpectrumBinning {
+    /// 创建分箱设置，默认求和、不变换、不归一化
+    ///
+    /// 箱宽不是正数或m/z范围为空时返回错误。
+    pub fn new(min_mz: f64, max_mz: f64, bin_width: f64) -> CoreResult<Self> {
+        if !(bin_width.is_finite() && bin_width > 0.0) {
+            return Err(CoreError::InvalidFormat(format!("Bin width must be positive, got {}", bin_width)));
+        }
+        if !(min_mz.is_finite() && max_mz.is_finite() && min_mz < max_mz) {
+            return Err(CoreError::InvalidFormat(format!("Invalid m/z range for binning: {}..{}", min_mz, max_mz)));
+        }
+        Ok(Self { min_mz, max_mz, bin_width, aggregation: BinAggregation::Sum, transform: None, normalize: false })
+    }
+
+    /// 设置同一箱内强度的合并方式
+    pub fn with_aggregation(mut self, aggregation: BinAggregation) -> Self {
+        self.aggregation = aggregation;
+        self
+    }
+
+    /// 设置分箱后的强度变换
+    pub fn with_transform(mut self, transform: Option<IntensityTransform>) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// 设置是否做L2归一化
+    pub fn with_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// 箱数，最后一个箱不足一个箱宽时也计入
+    pub fn bin_count(&self) -> usize {
+        // 与bin_index相同，按边界min_mz + n·bin_width判断，不受除法舍入影响
+        let count = ((self.max_mz - self.min_mz) / self.bin_width).round() as usize;
+        if self.min_mz + count as f64 * self.bin_width < self.max_mz {
+            count + 1
+        } else {
+            count.max(1)
+        }
+    }
+
+    /// `mz`所在箱的下标，范围之外时为None
+    ///
+    /// 箱边界取`min_mz + i·bin_width`并直接与之比较，恰好落在边界上的峰归入以它为下限的箱。
+    pub fn bin_index(&self, mz: f64) -> Option<usize> {
+        if !(mz >= self.min_mz && mz < self.max_mz) {
+            return None;
+        }
+        let mut index = ((mz - self.min_mz) / self.bin_width).floor() as usize;
+        // 修正除法的舍入：1.0 / 0.1 得到9.999...
+        if self.min_mz + (index + 1) as f64 * self.bin_width <= mz {
+            index += 1;
+        } else if index > 0 && self.min_mz + index as f64 * self.bin_width > mz {
+            index -= 1;
+        }
+        Some(index.min(self.bin_count() - 1))
+    }
+
+    /// 把一张谱图转换为长度为[`SpectrumBinning::bin_count`]的向量
+    pub fn vectorize(&self, spectrum: &Spectrum) -> Vec<f64> {
+        let mut vector = vec![0.0; self.bin_count()];
+        self.fill(spectrum, &mut vector);
+        vector
+    }
+
+    /// 把多张谱图转换为行优先的矩阵，每张谱图一行，各行并行计算
+    pub fn matrix<S: Borrow<Spectrum> + Sync>(&self, spectra: &[S]) -> Vec<f64> {
+        let columns = self.bin_count();
+        let mut matrix = vec![0.0; spectra.len() * columns];
+        matrix
+            .par_chunks_mut(columns)
+            .zip(spectra.par_iter())
+            .for_each(|(row, spectrum)| self.fill(spectrum.borrow(), row));
+        matrix
+    }
+
+    /// 在已清零的`vector`中写入一张谱图的分箱强度
+    fn fill(&self, spectrum: &Spectrum, vector: &mut [f64]) {
+        for (mz, intensity) in spectrum.peaks_iter() {
+            let Some(index) = self.bin_index(mz) else {
+                continue;
+            };
+            match self.aggregation {
+                BinAggregation::Sum => vector[index] += intensity,
+                BinAggregation::Max => vector[index] = vector[index].max(intensity),
+            }
+        }
+        if let Some(transform) = self.transform {
+            transform.apply(vector);
+        }
+        if self.normalize {
+            let norm = vector.iter().map(|value| value * value).sum::<f64>().sqrt();
+            if norm > 0.0 {
+                vector.iter_mut().for_each(|value| *value /= norm);
+            }
+        }
+    }
+}
+
+impl Spectrum {
+    /// 按固定箱宽把`[min_mz, max_mz)`内的峰转换为定长强度向量，不做变换和归一化
+    ///
+    /// 需要强度变换或L2归一化时使用[`SpectrumBinning`]。
+    pub fn to_binned_vector(&self, min_mz: f64, max_mz: f64, bin_width: f64, aggregation: BinAggregation) -> CoreResult<Vec<f64>> {
+        Ok(SpectrumBinning::new(min_mz, max_mz, bin_width)?.with_aggregation(aggregation).vectorize(self))
+    }
+}
+
+/// 由Python参数创建分箱设置，`transform`为None或"none"时不变换
+#[cfg(feature = "python")]
+pub(crate) fn py_binning(
+    min_mz: f64,
+    max_mz: f64,
+    bin: f64,
+    transform: Option<&str>,
+    aggregation: &str,
+    normalize: bool,
+) -> pyo3::PyResult<SpectrumBinning> {
+    let binning = || -> CoreResult<SpectrumBinning> {
+        let transform = match transform.map(str::trim) {
+            None => None,
+            Some(name) if name.eq_ignore_ascii_case("none") => None,
+            Some(name) => Some(IntensityTransform::from_name(name)?),
+        };
+        Ok(SpectrumBinning::new(min_mz, max_mz, bin)?
+            .with_aggregation(BinAggregation::from_name(aggregation)?)
+            .with_transform(transform)
+            .with_normalize(normalize))
+    };
+    binning().map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectrum(peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::ms2().unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum
+    }
+
+    #[test]
+    fn test_boundary_peaks_land_in_correct_bin() {
+        let peaks = [(100.0, 1.0), (100.999, 2.0), (101.0, 4.0), (102.5, 8.0), (104.0, 16.0), (99.999, 32.0)];
+        let vector = spectrum(&peaks).to_binned_vector(100.0, 104.0, 1.0, BinAggregation::Sum).unwrap();
+        // 下限包含、上限不包含
+        assert_eq!(vector, vec![3.0, 4.0, 8.0, 0.0]);
+        let vector = spectrum(&peaks).to_binned_vector(100.0, 104.0, 1.0, BinAggregation::Max).unwrap();
+        assert_eq!(vector, vec![2.0, 4.0, 8.0, 0.0]);
+
+        // 除法舍入不会把边界上的峰放进前一个箱
+        let binning = SpectrumBinning::new(100.0, 101.05, 0.1).unwrap();
+        assert_eq!(binning.bin_count(), 11);
+        for i in 0..=10 {
+            let boundary = 100.0 + i as f64 * 0.1;
+            assert_eq!(binning.bin_index(boundary), Some(i), "boundary {}", boundary);
+        }
+        assert_eq!(binning.bin_index(101.04), Some(10));
+        assert_eq!(binning.bin_index(101.05), None);
+        assert_eq!(SpectrumBinning::new(100.0, 100.3, 0.1).unwrap().bin_count(), 3);
+
+        assert!(SpectrumBinning::new(100.0, 100.0, 1.0).is_err());
+        assert!(SpectrumBinning::new(100.0, 200.0, 0.0).is_err());
+        assert!(BinAggregation::from_name("mean").is_err());
+    }
+
+    #[test]
+    fn test_transform_and_normalization() {
+        let binning = SpectrumBinning::new(0.0, 4.0, 1.0)
+            .unwrap()
+            .with_transform(Some(IntensityTransform::Sqrt))
+            .with_normalize(true);
+        // sqrt后为[3, 0, 4, 0]，L2范数为5
+        let vector = binning.vectorize(&spectrum(&[(0.2, 4.0), (0.7, 5.0), (2.5, 16.0)]));
+        assert_eq!(vector, vec![0.6, 0.0, 0.8, 0.0]);
+        assert!((vector.iter().map(|value| value * value).sum::<f64>() - 1.0).abs() < 1e-12);
+
+        // 空谱图保持全零，不除以0
+        assert_eq!(binning.vectorize(&spectrum(&[])), vec![0.0; 4]);
+    }
+
+    #[test]
+    fn test_matrix_matches_rows() {
+        let spectra: Vec<Spectrum> = (0..50)
+            .map(|i| spectrum(&[(100.0 + i as f64, 1.0 + i as f64), (150.5, 2.0), (180.0 + 0.5 * i as f64, 3.0)]))
+            .collect();
+        let binning = SpectrumBinning::new(100.0, 200.0, 1.0).unwrap().with_normalize(true);
+        let matrix = binning.matrix(&spectra);
+        assert_eq!(matrix.len(), spectra.len() * binning.bin_count());
+        for (row, spectrum) in matrix.chunks(binning.bin_count()).zip(&spectra) {
+            assert_eq!(row, binning.vectorize(spectrum).as_slice());
+        }
+        let references: Vec<&Spectrum> = spectra.iter().collect();
+        assert_eq!(binning.matrix(&references), matrix);
+        assert!(binning.matrix::<Spectrum>(&[]).is_empty());
+    }
+}
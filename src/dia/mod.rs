@@ -0,0 +1,15 @@
+//! DIA数据处理模块
+//!
+//! 这个模块提供了数据非依赖采集(DIA)数据的处理功能：
+//! - windows：按分离窗口分组MS2谱图，重叠窗口方案中的前体离子分配
+//! - pseudo：按前体离子与碎片离子XIC的相关性生成伪MS2谱图
+//! - scheme：由MS2谱图检测分离窗口方案和循环时间
+
+pub mod windows;
+pub mod pseudo;
+pub mod scheme;
+
+// 重新导出主要类型
+pub use windows::*;
+pub use pseudo::*;
+pub use scheme::*;
@@ -0,0 +1,232 @@
+//! DIA伪MS2谱图
+//!
+//! 不借助搜索引擎对DIA数据去卷积：在每个分离窗口内，以该窗口MS2扫描的保留时间为
+//! 共享网格，提取窗口内各碎片离子的XIC，以及MS1中落在窗口m/z范围内的候选前体离子的XIC
+//! （线性插值到同一网格），按Pearson相关系数把每个碎片归到相关性最高的前体离子上，
+//! 相关系数达到阈值的碎片组成一张带前体离子m/z的伪MS2谱图。
+//!
+//! 候选离子在窗口内的所有扫描中按强度从高到低选取，容差内较弱的峰并入已有候选。
+//! 同位素峰会作为独立的候选前体离子出现。
+
+use crate::core::spectrum::{PrecursorInfo, Spectrum};
+use crate::core::types::*;
+use crate::dia::windows::{IsolationWindow, WindowMap};
+use crate::xic::dense::{dense_xic, pearson, RtGrid};
+
+#[cfg(feature = "python")]
+use crate::core::ms_object::MSObject;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 计算相关系数所需的最少MS2扫描数
+pub const MIN_POINTS: usize = 3;
+
+/// 伪谱图生成参数
+#[derive(Debug, Clone, Copy)]
+pub struct PseudoSpectrumParams {
+    /// 前体离子与碎片离子的m/z容差 (ppm)
+    pub ppm: f64,
+    /// 碎片归入前体离子所需的最低Pearson相关系数
+    pub min_corr: f64,
+}
+
+impl Default for PseudoSpectrumParams {
+    fn default() -> Self {
+        Self { ppm: 10.0, min_corr: 0.8 }
+    }
+}
+
+/// 在`tolerance`内合并峰，返回按m/z升序排列的候选m/z（取组内最强峰的m/z）
+fn candidate_mzs(peaks: impl Iterator<Item = Peak>, tolerance: &Tolerance) -> Vec<f64> {
+    let mut peaks: Vec<Peak> = peaks.filter(|&(_, intensity)| intensity > 0.0).collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut candidates: Vec<f64> = Vec::new();
+    for (mz, _) in peaks {
+        let position = candidates.partition_point(|&candidate| candidate < mz);
+        let near = |index: usize| candidates.get(index).is_some_and(|&candidate| tolerance.is_within_tolerance(candidate, mz));
+        let merged = near(position) || (position > 0 && near(position - 1));
+        if !merged {
+            candidates.insert(position, mz);
+        }
+    }
+    candidates
+}
+
+/// 为所有DIA窗口生成伪MS2谱图，按窗口和前体离子m/z排序，扫描编号从1起依次编号
+pub fn generate_pseudo_spectra(spectra: &[Spectrum], params: &PseudoSpectrumParams) -> Vec<Spectrum> {
+    let mut ms1: Vec<&Spectrum> = spectra.iter().filter(|spectrum| spectrum.is_ms1()).collect();
+    ms1.sort_by(|a, b| a.scan.retention_time.total_cmp(&b.scan.retention_time));
+
+    let mut pseudo = Vec::new();
+    for (window, indices) in WindowMap::from_spectra(spectra).windows() {
+        let ms2: Vec<&Spectrum> = indices.iter().map(|&index| &spectra[index]).collect();
+        pseudo.extend(window_pseudo_spectra(*window, &ms1, ms2, params));
+    }
+    for (offset, spectrum) in pseudo.iter_mut().enumerate() {
+        spectrum.set_scan_number(offset as ScanNumber + 1);
+    }
+    pseudo
+}
+
+/// 单个窗口的伪谱图；`ms1`按保留时间升序排列
+fn window_pseudo_spectra(
+    window: IsolationWindow,
+    ms1: &[&Spectrum],
+    mut ms2: Vec<&Spectrum>,
+    params: &PseudoSpectrumParams,
+) -> Vec<Spectrum> {
+    ms2.sort_by(|a, b| a.scan.retention_time.total_cmp(&b.scan.retention_time));
+    let grid = RtGrid::from_spectra(ms2.iter().copied());
+    let Some((start_rt, end_rt)) = grid.range() else {
+        return Vec::new();
+    };
+    if grid.len() < MIN_POINTS {
+        return Vec::new();
+    }
+
+    // 网格两端各多取一张MS1谱图，保证插值覆盖整个网格
+    let start = ms1.partition_point(|spectrum| spectrum.scan.retention_time < start_rt).saturating_sub(1);
+    let end = (ms1.partition_point(|spectrum| spectrum.scan.retention_time <= end_rt) + 1).min(ms1.len());
+    let ms1 = &ms1[start..end.max(start)];
+    let ms1_rts: Vec<RetentionTime> = ms1.iter().map(|spectrum| spectrum.scan.retention_time).collect();
+
+    let tolerance = Tolerance::PPM(params.ppm);
+    let precursors = candidate_mzs(
+        ms1.iter().flat_map(|spectrum| spectrum.peaks_iter()).filter(|&(mz, _)| window.contains(mz)),
+        &tolerance,
+    );
+    let precursor_traces: Vec<Vec<f64>> = precursors
+        .iter()
+        .map(|&mz| grid.resample(&ms1_rts, &dense_xic(ms1.iter().copied(), mz, tolerance)))
+        .collect();
+
+    let mut groups: Vec<Vec<Peak>> = vec![Vec::new(); precursors.len()];
+    for fragment_mz in candidate_mzs(ms2.iter().flat_map(|spectrum| spectrum.peaks_iter()), &tolerance) {
+        let trace = dense_xic(ms2.iter().copied(), fragment_mz, tolerance);
+        let best = precursor_traces
+            .iter()
+            .map(|precursor_trace| pearson(&trace, precursor_trace))
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((precursor, corr)) = best {
+            if corr >= params.min_corr {
+                let apex = trace.iter().copied().fold(0.0, f64::max);
+                groups[precursor].push((fragment_mz, apex));
+            }
+        }
+    }
+
+    groups
+        .into_iter()
+        .zip(precursors.iter().zip(&precursor_traces))
+        .filter(|(fragments, _)| !fragments.is_empty())
+        .filter_map(|(fragments, (&precursor_mz, trace))| {
+            let (apex_index, &apex_intensity) = trace.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1))?;
+            let mut spectrum = Spectrum::ms2().ok()?;
+            spectrum.add_peaks(fragments).ok()?;
+            spectrum.set_retention_time(grid.rts()[apex_index]).ok()?;
+            spectrum.set_precursor(PrecursorInfo {
+                mz: precursor_mz,
+                intensity: apex_intensity,
+                isolation_window: (window.lower, window.upper),
+                ..PrecursorInfo::default()
+            });
+            Some(spectrum)
+        })
+        .collect()
+}
+
+/// Python可用的DIA伪MS2谱图生成器
+#[cfg(feature = "python")]
+#[pyclass]
+pub struct DIAPseudoSpectrumGenerator {
+    params: PseudoSpectrumParams,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl DIAPseudoSpectrumGenerator {
+    /// 创建生成器，`ppm`为m/z容差，`min_corr`为碎片归入前体离子的最低相关系数
+    #[new]
+    #[pyo3(signature = (ppm=10.0, min_corr=0.8))]
+    fn new(ppm: f64, min_corr: f64) -> Self {
+        Self { params: PseudoSpectrumParams { ppm, min_corr } }
+    }
+
+    /// 由一次DIA运行的MSObject列表（MS1和MS2）生成伪MS2谱图
+    fn run(&self, ms_objects: Vec<PyRef<'_, MSObject>>) -> Vec<MSObject> {
+        let spectra: Vec<Spectrum> = ms_objects.iter().map(|ms_object| ms_object.spectrum.clone()).collect();
+        generate_pseudo_spectra(&spectra, &self.params)
+            .into_iter()
+            .map(|spectrum| MSObject { spectrum })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gaussian(rt: f64, apex: f64, height: f64) -> f64 {
+        height * (-(rt - apex).powi(2) / (2.0 * 2.0 * 2.0)).exp()
+    }
+
+    /// 500-525窗口：前体离子A (510.0) 在18 s洗脱，碎片300.1和400.2随A变化；
+    /// 前体离子B (515.0) 在24 s洗脱，碎片350.3随B变化；450.0为恒定背景
+    fn synthetic_run() -> Vec<Spectrum> {
+        let mut spectra = Vec::new();
+        for cycle in 0..21 {
+            let rt = 10.0 + cycle as f64;
+            let mut ms1 = Spectrum::ms1().unwrap();
+            ms1.add_peaks([
+                (490.0, 5e5),
+                (510.0, gaussian(rt, 18.0, 1e6)),
+                (515.0, gaussian(rt, 24.0, 8e5)),
+            ]).unwrap();
+            ms1.set_retention_time(rt).unwrap();
+            spectra.push(ms1);
+
+            let ms2_rt = rt + 0.5;
+            let mut ms2 = Spectrum::ms2().unwrap();
+            ms2.add_peaks([
+                (300.1, gaussian(ms2_rt, 18.0, 4e4)),
+                (350.3, gaussian(ms2_rt, 24.0, 3e4)),
+                (400.2, gaussian(ms2_rt, 18.0, 2e4)),
+                (450.0, 1e3),
+            ]).unwrap();
+            ms2.set_retention_time(ms2_rt).unwrap();
+            ms2.set_precursor(PrecursorInfo { mz: 512.5, isolation_window: (500.0, 525.0), ..PrecursorInfo::default() });
+            spectra.push(ms2);
+        }
+        spectra
+    }
+
+    #[test]
+    fn test_fragments_grouped_by_precursor() {
+        let pseudo = generate_pseudo_spectra(&synthetic_run(), &PseudoSpectrumParams::default());
+        assert_eq!(pseudo.len(), 2);
+
+        let a = &pseudo[0];
+        assert_eq!(a.precursor.as_deref().unwrap().mz, 510.0);
+        assert_eq!(a.peaks_iter().map(|p| p.0).collect::<Vec<_>>(), vec![300.1, 400.2]);
+        assert!((a.scan.retention_time - 18.0).abs() <= 0.5);
+        assert_eq!(a.scan.scan_number, 1);
+
+        let b = &pseudo[1];
+        assert_eq!(b.precursor.as_deref().unwrap().mz, 515.0);
+        assert_eq!(b.peaks_iter().map(|p| p.0).collect::<Vec<_>>(), vec![350.3]);
+        assert_eq!(b.precursor.as_deref().unwrap().isolation_window, (500.0, 525.0));
+    }
+
+    #[test]
+    fn test_non_dia_spectra_ignored() {
+        let mut spectra = synthetic_run();
+        for spectrum in &mut spectra {
+            if let Some(precursor) = spectrum.precursor.as_deref_mut() {
+                precursor.isolation_window = (512.5, 512.5);
+            }
+        }
+        assert!(generate_pseudo_spectra(&spectra, &PseudoSpectrumParams::default()).is_empty());
+    }
+}
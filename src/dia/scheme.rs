@@ -0,0 +1,225 @@
+//! DIA分离窗口方案检测
+//!
+//! 从MS2谱图前体离子信息中记录的分离窗口推断采集方案：上下限都在[`SCHEME_TOLERANCE`]内的
+//! 窗口聚为同一个窗口，统计每个窗口的扫描数，并用同一窗口相邻两次采集的保留时间间隔的
+//! 中位数作为循环时间。
+//!
+//! - 重叠窗口方案中各窗口分别列出，[`IsolationScheme::overlapping`]标记窗口之间有重叠
+//! - 交错方案（相邻循环的窗口错开半个窗口宽度）中两组窗口分别列出，
+//!   每个窗口的循环时间为它重复出现的间隔，即两个采集循环
+//! - DDA数据的分离窗口跟随各自的前体离子，很少重复：超过[`DDA_SINGLE_SCAN_FRACTION`]的
+//!   MS2扫描落在只出现一次的窗口中，或MS2扫描都没有记录窗口宽度时判定为DDA，不列出窗口
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::dia::windows::{IsolationWindow, WindowMap};
+use crate::utils::helpers::median;
+
+/// 聚类窗口边界的m/z容差
+pub const SCHEME_TOLERANCE: f64 = 0.05;
+/// 判定为DDA时只出现一次的窗口中MS2扫描所占的最低比例
+pub const DDA_SINGLE_SCAN_FRACTION: f64 = 0.5;
+
+/// 方案中的一个分离窗口
+#[derive(Debug, Clone, PartialEq)]
+pub struct IsolationWindowSummary {
+    /// 下限m/z（聚类内的平均值）
+    pub lower: f64,
+    /// 上限m/z（聚类内的平均值）
+    pub upper: f64,
+    /// 采集该窗口的MS2扫描数
+    pub scan_count: usize,
+    /// 同一窗口相邻两次采集的保留时间间隔的中位数 (秒)，只采集一次时为None
+    pub cycle_time: Option<RetentionTime>,
+}
+
+impl IsolationWindowSummary {
+    /// 窗口
+    pub fn window(&self) -> IsolationWindow {
+        IsolationWindow::new(self.lower, self.upper)
+    }
+}
+
+/// 检测到的分离窗口方案
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IsolationScheme {
+    /// 按下限、再按上限升序排列的窗口；判定为DDA时为空
+    pub windows: Vec<IsolationWindowSummary>,
+    /// 是否判定为DDA数据
+    pub is_dda: bool,
+    /// 窗口之间是否有重叠
+    pub overlapping: bool,
+}
+
+impl IsolationScheme {
+    /// 由一次运行的谱图检测窗口方案
+    pub fn detect(spectra: &[Spectrum]) -> Self {
+        let mut windows: Vec<(f64, f64, RetentionTime)> = Vec::new();
+        let mut ms2_count = 0;
+        for spectrum in spectra.iter().filter(|spectrum| spectrum.level > 1) {
+            let Some(precursor) = spectrum.precursor.as_deref() else {
+                continue;
+            };
+            ms2_count += 1;
+            let (lower, upper) = precursor.isolation_window;
+            if upper > lower {
+                windows.push((lower, upper, spectrum.scan.retention_time));
+            }
+        }
+        if windows.is_empty() {
+            return Self { is_dda: ms2_count > 0, ..Self::default() };
+        }
+
+        let summaries: Vec<IsolationWindowSummary> = cluster_windows(windows).iter().map(|cluster| summarize(cluster)).collect();
+        let single_scans = summaries.iter().filter(|summary| summary.scan_count == 1).count();
+        if single_scans as f64 > DDA_SINGLE_SCAN_FRACTION * ms2_count as f64 {
+            return Self { is_dda: true, ..Self::default() };
+        }
+
+        let overlapping = !WindowMap::from_windows(summaries.iter().map(|summary| summary.window()))
+            .overlap_regions()
+            .is_empty();
+        Self { windows: summaries, is_dda: false, overlapping }
+    }
+
+    /// 各窗口循环时间的中位数 (秒)，没有窗口重复采集时为None
+    pub fn cycle_time(&self) -> Option<RetentionTime> {
+        let mut cycle_times: Vec<f64> = self.windows.iter().filter_map(|summary| summary.cycle_time).collect();
+        cycle_times.sort_by(|a, b| a.total_cmp(b));
+        median(&cycle_times)
+    }
+}
+
+/// 检测DIA分离窗口方案，按下限升序返回窗口；DDA数据返回空列表
+pub fn detect_isolation_scheme(spectra: &[Spectrum]) -> Vec<IsolationWindowSummary> {
+    IsolationScheme::detect(spectra).windows
+}
+
+/// 把上下限都在[`SCHEME_TOLERANCE`]内的窗口聚为一组，组内按保留时间排列
+///
+/// 窗口按下限排序后扫描，只需要和下限相差不超过容差的已有聚类比较。
+fn cluster_windows(mut windows: Vec<(f64, f64, RetentionTime)>) -> Vec<Vec<(f64, f64, RetentionTime)>> {
+    windows.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)).then(a.2.total_cmp(&b.2)));
+    let mut clusters: Vec<Vec<(f64, f64, RetentionTime)>> = Vec::new();
+    let mut first_candidate = 0;
+    for window in windows {
+        while first_candidate < clusters.len() && window.0 - clusters[first_candidate][0].0 > SCHEME_TOLERANCE {
+            first_candidate += 1;
+        }
+        let existing = clusters[first_candidate..]
+            .iter()
+            .position(|cluster| (window.1 - cluster[0].1).abs() <= SCHEME_TOLERANCE);
+        match existing {
+            Some(offset) => clusters[first_candidate + offset].push(window),
+            None => clusters.push(vec![window]),
+        }
+    }
+    for cluster in &mut clusters {
+        cluster.sort_by(|a, b| a.2.total_cmp(&b.2));
+    }
+    clusters.sort_by(|a, b| a[0].0.total_cmp(&b[0].0).then(a[0].1.total_cmp(&b[0].1)));
+    clusters
+}
+
+/// 一组窗口的平均边界、扫描数和循环时间
+fn summarize(cluster: &[(f64, f64, RetentionTime)]) -> IsolationWindowSummary {
+    let count = cluster.len();
+    let mut gaps: Vec<f64> = cluster.windows(2).map(|pair| pair[1].2 - pair[0].2).collect();
+    gaps.sort_by(|a, b| a.total_cmp(b));
+    IsolationWindowSummary {
+        lower: cluster.iter().map(|window| window.0).sum::<f64>() / count as f64,
+        upper: cluster.iter().map(|window| window.1).sum::<f64>() / count as f64,
+        scan_count: count,
+        cycle_time: median(&gaps),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    fn ms2(rt: f64, window: (f64, f64)) -> Spectrum {
+        let mut spectrum = Spectrum::ms2().unwrap();
+        spectrum.set_retention_time(rt).unwrap();
+        spectrum.set_precursor(PrecursorInfo {
+            mz: (window.0 + window.1) / 2.0,
+            isolation_window: window,
+            ..PrecursorInfo::default()
+        });
+        spectrum
+    }
+
+    /// 每个循环先采一张MS1，再依次采集`windows`，循环时间`cycle`秒
+    fn dia_run(cycles: usize, cycle: f64, windows: impl Fn(usize) -> Vec<(f64, f64)>) -> Vec<Spectrum> {
+        let mut spectra = Vec::new();
+        for c in 0..cycles {
+            let start = c as f64 * cycle;
+            let mut ms1 = Spectrum::ms1().unwrap();
+            ms1.set_retention_time(start).unwrap();
+            spectra.push(ms1);
+            let scheme = windows(c);
+            for (i, &window) in scheme.iter().enumerate() {
+                spectra.push(ms2(start + (i + 1) as f64 * cycle / (scheme.len() + 1) as f64, window));
+            }
+        }
+        spectra
+    }
+
+    #[test]
+    fn test_fixed_window_scheme() {
+        // 边界有小的浮点抖动
+        let spectra = dia_run(5, 3.0, |c| {
+            let jitter = if c % 2 == 0 { 0.001 } else { -0.001 };
+            (0..4).map(|i| (400.0 + 25.0 * i as f64 + jitter, 425.0 + 25.0 * i as f64)).collect()
+        });
+        let scheme = IsolationScheme::detect(&spectra);
+        assert!(!scheme.is_dda && !scheme.overlapping);
+        assert_eq!(scheme.windows.len(), 4);
+        assert!((scheme.windows[1].lower - 425.0002).abs() < 1e-9);
+        assert!(scheme.windows.iter().all(|summary| summary.scan_count == 5));
+        assert!((scheme.cycle_time().unwrap() - 3.0).abs() < 1e-9);
+        assert_eq!(detect_isolation_scheme(&spectra), scheme.windows);
+    }
+
+    #[test]
+    fn test_overlapping_and_staggered_schemes() {
+        let overlapping = dia_run(4, 2.0, |_| (0..4).map(|i| (400.0 + 12.5 * i as f64, 425.0 + 12.5 * i as f64)).collect());
+        let scheme = IsolationScheme::detect(&overlapping);
+        assert!(scheme.overlapping && !scheme.is_dda);
+        assert_eq!(scheme.windows.len(), 4);
+
+        // 奇数循环的窗口错开半个宽度，每个窗口每两个循环重复一次
+        let staggered = dia_run(6, 2.0, |c| {
+            let offset = if c % 2 == 0 { 0.0 } else { 10.0 };
+            (0..3).map(|i| (400.0 + offset + 20.0 * i as f64, 420.0 + offset + 20.0 * i as f64)).collect()
+        });
+        let scheme = IsolationScheme::detect(&staggered);
+        assert_eq!(scheme.windows.len(), 6);
+        assert!(scheme.overlapping);
+        assert_eq!(
+            scheme.windows.iter().map(|summary| (summary.lower, summary.scan_count)).collect::<Vec<_>>(),
+            vec![(400.0, 3), (410.0, 3), (420.0, 3), (430.0, 3), (440.0, 3), (450.0, 3)]
+        );
+        assert_eq!(scheme.cycle_time(), Some(4.0));
+    }
+
+    #[test]
+    fn test_dda_run_is_flagged() {
+        // 每个前体离子有自己的1.6 m/z窗口
+        let spectra: Vec<Spectrum> = (0..50)
+            .map(|i| {
+                let mz = 400.0 + 7.3 * i as f64;
+                ms2(i as f64, (mz - 0.8, mz + 0.8))
+            })
+            .collect();
+        let scheme = IsolationScheme::detect(&spectra);
+        assert!(scheme.is_dda);
+        assert!(scheme.windows.is_empty());
+        assert_eq!(scheme.cycle_time(), None);
+
+        // 没有记录窗口宽度的MS2同样按DDA处理；没有MS2时不是DDA
+        assert!(IsolationScheme::detect(&[ms2(1.0, (0.0, 0.0))]).is_dda);
+        assert_eq!(IsolationScheme::detect(&[Spectrum::ms1().unwrap()]), IsolationScheme::default());
+    }
+}
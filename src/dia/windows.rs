@@ -0,0 +1,359 @@
+//! DIA分离窗口
+//!
+//! DIA方法在每个循环中依次扫描一组固定的分离窗口。[`WindowMap`]把MS2谱图按
+//! 前体离子信息中记录的窗口上下限分组，窗口按下限升序排列。
+//!
+//! 重叠窗口方案（如50%重叠）中一个前体离子落在两个窗口内，[`WindowMap::assign_unique`]
+//! 按去多路复用的惯例把它分配给中心最近的窗口。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use std::collections::HashMap;
+
+#[cfg(feature = "python")]
+use crate::core::ms_object::MSObject;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 窗口边界的比较精度 (m/z)，小于该值的差异视为同一边界
+pub const BOUNDARY_RESOLUTION: f64 = 1e-4;
+
+/// 一个分离窗口 (m/z)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IsolationWindow {
+    /// 下限m/z
+    pub lower: f64,
+    /// 上限m/z
+    pub upper: f64,
+}
+
+impl IsolationWindow {
+    /// 创建分离窗口
+    pub fn new(lower: f64, upper: f64) -> Self {
+        Self { lower, upper }
+    }
+
+    /// 窗口中心m/z
+    pub fn center(&self) -> f64 {
+        (self.lower + self.upper) / 2.0
+    }
+
+    /// 窗口宽度
+    pub fn width(&self) -> f64 {
+        self.upper - self.lower
+    }
+
+    /// m/z是否落在窗口内（含边界）
+    pub fn contains(&self, mz: f64) -> bool {
+        mz >= self.lower && mz <= self.upper
+    }
+
+    /// 分组用的键，上下限按[`BOUNDARY_RESOLUTION`]取整以消除浮点噪声
+    fn key(&self) -> (i64, i64) {
+        ((self.lower / BOUNDARY_RESOLUTION).round() as i64, (self.upper / BOUNDARY_RESOLUTION).round() as i64)
+    }
+}
+
+/// 两个及以上窗口共同覆盖的m/z区段
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlapRegion {
+    /// 区段下限m/z
+    pub lower: f64,
+    /// 区段上限m/z
+    pub upper: f64,
+    /// 覆盖该区段的窗口在[`WindowMap::windows`]中的下标
+    pub windows: Vec<usize>,
+}
+
+/// 按窗口池化MS2扫描的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowPooling {
+    /// 只使用[`WindowMap::assign_unique`]分配的窗口
+    #[default]
+    Assigned,
+    /// 使用包含前体离子的全部窗口
+    AllContaining,
+}
+
+/// 分离窗口到MS2谱图下标的映射
+#[derive(Debug, Clone, Default)]
+pub struct WindowMap {
+    windows: Vec<(IsolationWindow, Vec<usize>)>,
+}
+
+impl WindowMap {
+    /// 按分离窗口分组MS2谱图
+    ///
+    /// 没有前体离子信息或窗口宽度为0（文件中未记录偏移）的谱图被忽略。
+    pub fn from_spectra(spectra: &[Spectrum]) -> Self {
+        let mut windows: Vec<(IsolationWindow, Vec<usize>)> = Vec::new();
+        let mut positions: HashMap<(i64, i64), usize> = HashMap::new();
+        for (index, spectrum) in spectra.iter().enumerate() {
+            if !spectrum.is_ms2() {
+                continue;
+            }
+            let Some(precursor) = spectrum.precursor.as_deref() else {
+                continue;
+            };
+            let window = IsolationWindow::new(precursor.isolation_window.0, precursor.isolation_window.1);
+            if window.width() <= 0.0 {
+                continue;
+            }
+            let position = *positions.entry(window.key()).or_insert_with(|| {
+                windows.push((window, Vec::new()));
+                windows.len() - 1
+            });
+            windows[position].1.push(index);
+        }
+        windows.sort_by(|a, b| a.0.lower.total_cmp(&b.0.lower).then(a.0.upper.total_cmp(&b.0.upper)));
+        Self { windows }
+    }
+
+    /// 由窗口方案创建不含谱图的映射，用于检查方案本身；重复的窗口只保留一个
+    pub fn from_windows(windows: impl IntoIterator<Item = IsolationWindow>) -> Self {
+        let mut windows: Vec<IsolationWindow> = windows.into_iter().filter(|window| window.width() > 0.0).collect();
+        windows.sort_by(|a, b| a.lower.total_cmp(&b.lower).then(a.upper.total_cmp(&b.upper)));
+        windows.dedup_by_key(|window| window.key());
+        Self { windows: windows.into_iter().map(|window| (window, Vec::new())).collect() }
+    }
+
+    /// 窗口数
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// 是否没有窗口
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// 所有窗口及其MS2谱图下标（按谱图在输入中的顺序）
+    pub fn windows(&self) -> &[(IsolationWindow, Vec<usize>)] {
+        &self.windows
+    }
+
+    /// 包含`mz`的全部窗口（窗口可能重叠）
+    pub fn windows_containing(&self, mz: f64) -> impl Iterator<Item = &(IsolationWindow, Vec<usize>)> {
+        self.windows.iter().filter(move |(window, _)| window.contains(mz))
+    }
+
+    /// 把前体离子唯一地分配给一个窗口，返回窗口下标
+    ///
+    /// 在包含`precursor_mz`的窗口中选中心最近的一个；距离相同（差异小于
+    /// [`BOUNDARY_RESOLUTION`]）时选下限较低的窗口。没有窗口包含该m/z时返回None。
+    pub fn assign_unique(&self, precursor_mz: f64) -> Option<usize> {
+        let mut best: Option<(usize, f64)> = None;
+        for (index, (window, _)) in self.windows.iter().enumerate() {
+            if !window.contains(precursor_mz) {
+                continue;
+            }
+            let distance = (window.center() - precursor_mz).abs();
+            if best.is_none_or(|(_, best_distance)| distance < best_distance - BOUNDARY_RESOLUTION) {
+                best = Some((index, distance));
+            }
+        }
+        best.map(|(index, _)| index)
+    }
+
+    /// 按池化方式选出前体离子对应的窗口下标
+    pub fn windows_for_precursor(&self, precursor_mz: f64, pooling: WindowPooling) -> Vec<usize> {
+        match pooling {
+            WindowPooling::Assigned => self.assign_unique(precursor_mz).into_iter().collect(),
+            WindowPooling::AllContaining => (0..self.windows.len())
+                .filter(|&index| self.windows[index].0.contains(precursor_mz))
+                .collect(),
+        }
+    }
+
+    /// 枚举被两个及以上窗口覆盖的m/z区段，按m/z升序
+    ///
+    /// 覆盖窗口集合不同的相邻区段分别列出。
+    pub fn overlap_regions(&self) -> Vec<OverlapRegion> {
+        let mut boundaries: Vec<f64> = self.windows.iter().flat_map(|(window, _)| [window.lower, window.upper]).collect();
+        boundaries.sort_by(|a, b| a.total_cmp(b));
+        boundaries.dedup_by(|a, b| (*a - *b).abs() < BOUNDARY_RESOLUTION);
+
+        let mut regions: Vec<OverlapRegion> = Vec::new();
+        for segment in boundaries.windows(2) {
+            let (lower, upper) = (segment[0], segment[1]);
+            let middle = (lower + upper) / 2.0;
+            let covering: Vec<usize> = (0..self.windows.len())
+                .filter(|&index| self.windows[index].0.contains(middle))
+                .collect();
+            if covering.len() < 2 {
+                continue;
+            }
+            match regions.last_mut() {
+                Some(last) if last.windows == covering && (last.upper - lower).abs() < BOUNDARY_RESOLUTION => last.upper = upper,
+                _ => regions.push(OverlapRegion { lower, upper, windows: covering }),
+            }
+        }
+        regions
+    }
+
+    /// 方案中没有被任何窗口覆盖的m/z区间（首个窗口下限到最后一个窗口上限之间）
+    pub fn gaps(&self) -> Vec<(f64, f64)> {
+        let mut gaps = Vec::new();
+        let mut covered_upper: Option<f64> = None;
+        for (window, _) in &self.windows {
+            if let Some(upper) = covered_upper {
+                if window.lower > upper + BOUNDARY_RESOLUTION {
+                    gaps.push((upper, window.lower));
+                }
+            }
+            covered_upper = Some(covered_upper.map_or(window.upper, |upper| upper.max(window.upper)));
+        }
+        gaps
+    }
+
+    /// 检查窗口方案是否连续覆盖，有间隙时返回错误并列出间隙
+    pub fn validate(&self) -> CoreResult<()> {
+        let gaps = self.gaps();
+        if gaps.is_empty() {
+            return Ok(());
+        }
+        let listed: Vec<String> = gaps.iter().map(|(lower, upper)| format!("{:.4}-{:.4}", lower, upper)).collect();
+        Err(CoreError::InvalidFormat(format!("DIA window scheme has gaps at m/z {}", listed.join(", "))))
+    }
+}
+
+/// Python可用的DIA窗口映射
+#[cfg(feature = "python")]
+#[pyclass(name = "DIAWindowMap")]
+pub struct PyWindowMap {
+    pub map: WindowMap,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyWindowMap {
+    /// 按分离窗口分组MSObject列表中的MS2谱图
+    #[new]
+    fn new(ms_objects: Vec<PyRef<'_, MSObject>>) -> Self {
+        let spectra: Vec<Spectrum> = ms_objects.iter().map(|ms_object| ms_object.spectrum.clone()).collect();
+        Self { map: WindowMap::from_spectra(&spectra) }
+    }
+
+    /// 由(下限, 上限)列表描述的窗口方案创建
+    #[staticmethod]
+    fn from_windows(windows: Vec<(f64, f64)>) -> Self {
+        Self { map: WindowMap::from_windows(windows.into_iter().map(|(lower, upper)| IsolationWindow::new(lower, upper))) }
+    }
+
+    /// 所有窗口的(下限, 上限)，按下限升序
+    #[getter]
+    fn windows(&self) -> Vec<(f64, f64)> {
+        self.map.windows().iter().map(|(window, _)| (window.lower, window.upper)).collect()
+    }
+
+    /// 各窗口MS2谱图在输入列表中的下标
+    #[getter]
+    fn spectrum_indices(&self) -> Vec<Vec<usize>> {
+        self.map.windows().iter().map(|(_, indices)| indices.clone()).collect()
+    }
+
+    /// 包含`mz`的全部窗口
+    fn windows_containing(&self, mz: f64) -> Vec<(f64, f64)> {
+        self.map.windows_containing(mz).map(|(window, _)| (window.lower, window.upper)).collect()
+    }
+
+    /// 前体离子唯一分配到的窗口，没有窗口包含该m/z时为None
+    fn assign_unique(&self, precursor_mz: f64) -> Option<(f64, f64)> {
+        self.map.assign_unique(precursor_mz).map(|index| {
+            let window = self.map.windows()[index].0;
+            (window.lower, window.upper)
+        })
+    }
+
+    /// 被两个及以上窗口覆盖的m/z区段(下限, 上限)
+    fn overlap_regions(&self) -> Vec<(f64, f64)> {
+        self.map.overlap_regions().iter().map(|region| (region.lower, region.upper)).collect()
+    }
+
+    /// 未被任何窗口覆盖的m/z区间
+    fn gaps(&self) -> Vec<(f64, f64)> {
+        self.map.gaps()
+    }
+
+    /// 检查窗口方案是否连续覆盖，有间隙时抛出ValueError
+    fn validate(&self) -> PyResult<()> {
+        self.map.validate().map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    fn __len__(&self) -> usize {
+        self.map.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    fn ms2(window: (f64, f64)) -> Spectrum {
+        let mut spectrum = Spectrum::ms2().unwrap();
+        spectrum.set_precursor(PrecursorInfo { isolation_window: window, ..PrecursorInfo::default() });
+        spectrum
+    }
+
+    #[test]
+    fn test_group_by_window() {
+        let spectra = vec![
+            Spectrum::ms1().unwrap(),
+            ms2((525.0, 550.0)),
+            ms2((500.0, 525.0)),
+            ms2((525.0, 550.00000001)),
+            ms2((500.0, 525.0)),
+            // DDA谱图没有窗口宽度
+            ms2((600.0, 600.0)),
+        ];
+        let map = WindowMap::from_spectra(&spectra);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.windows()[0], (IsolationWindow::new(500.0, 525.0), vec![2, 4]));
+        assert_eq!(map.windows()[1].1, vec![1, 3]);
+        assert_eq!(map.windows_containing(525.0).count(), 2);
+        assert_eq!(map.windows_containing(530.0).next().unwrap().0.center(), 537.5);
+    }
+
+    /// 25 m/z宽、步长12.5 m/z的50%重叠方案
+    fn overlapping_scheme(count: usize) -> Vec<IsolationWindow> {
+        (0..count).map(|i| IsolationWindow::new(500.0 + 12.5 * i as f64, 525.0 + 12.5 * i as f64)).collect()
+    }
+
+    #[test]
+    fn test_assign_unique_in_overlapping_scheme() {
+        let map = WindowMap::from_windows(overlapping_scheme(4));
+        assert_eq!(map.len(), 4);
+        // 520落在前两个窗口内，第二个窗口中心525更近
+        assert_eq!(map.windows_containing(520.0).count(), 2);
+        assert_eq!(map.assign_unique(520.0), Some(1));
+        assert_eq!(map.assign_unique(515.0), Some(0));
+        // 与两个窗口中心等距时取下限较低的窗口
+        assert_eq!(map.assign_unique(518.75), Some(0));
+        assert_eq!(map.assign_unique(600.0), None);
+
+        assert_eq!(map.windows_for_precursor(520.0, WindowPooling::Assigned), vec![1]);
+        assert_eq!(map.windows_for_precursor(520.0, WindowPooling::AllContaining), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_overlap_regions_and_gaps() {
+        let map = WindowMap::from_windows(overlapping_scheme(4));
+        let regions = map.overlap_regions();
+        assert_eq!(
+            regions.iter().map(|region| (region.lower, region.upper)).collect::<Vec<_>>(),
+            vec![(512.5, 525.0), (525.0, 537.5), (537.5, 550.0)]
+        );
+        assert_eq!(regions[1].windows, vec![1, 2]);
+        assert!(map.gaps().is_empty());
+        assert!(map.validate().is_ok());
+
+        // 去掉525-550和537.5-562.5两个窗口后，537.5-550没有窗口覆盖
+        let mut scheme = overlapping_scheme(6);
+        scheme.remove(2);
+        scheme.remove(2);
+        let map = WindowMap::from_windows(scheme);
+        assert_eq!(map.gaps(), vec![(537.5, 550.0)]);
+        assert!(map.validate().unwrap_err().to_string().contains("537.5000-550.0000"));
+    }
+}
@@ -0,0 +1,224 @@
+//! 碰撞截面 (CCS) 计算与校准
+//!
+//! - 单场DTIMS：由漂移时间和漂移管条件得到约化迁移率K0，再按Mason-Schamp方程计算CCS
+//! - 校准：由已知CCS的校准离子拟合CCS与测得迁移率（TIMS的1/K0或DTIMS的漂移时间）的关系
+//!
+//! 按Mason-Schamp方程，`CCS·√μ / z`与`1/K0`成正比，因此校准在这个归一化量上做线性拟合，
+//! 不同电荷和质量的离子可以共用一条校准线。
+
+use crate::core::types::*;
+
+/// 元电荷 (C)
+const ELEMENTARY_CHARGE: f64 = 1.602176634e-19;
+/// 玻尔兹曼常数 (J/K)
+const BOLTZMANN: f64 = 1.380649e-23;
+/// 统一原子质量单位 (kg)
+const DALTON: f64 = 1.66053906660e-27;
+/// Loschmidt常数，标准状态 (273.15 K, 101.325 kPa) 下的气体数密度 (m⁻³)
+const LOSCHMIDT: f64 = 2.686780111e25;
+
+/// 标准温度 (K)
+pub const STANDARD_TEMPERATURE: f64 = 273.15;
+/// 标准压力 (Torr)
+pub const STANDARD_PRESSURE_TORR: f64 = 760.0;
+/// 氮气漂移气体的质量 (Da)
+pub const N2_GAS_MASS: f64 = 28.006148;
+/// 拟合校准曲线所需的最少校准离子数
+pub const MIN_CALIBRANTS: usize = 3;
+
+/// Agilent ESI调谐液的正离子 (m/z, 电荷, 氮气中的CCS Å²)
+///
+/// 数值取自Stow等 (Anal. Chem. 2017) 多实验室DTIMS测定的DTCCS(N2)。
+pub const AGILENT_TUNE_MIX: [(f64, Charge, f64); 10] = [
+    (118.086255, 1, 121.3),
+    (322.048121, 1, 153.7),
+    (622.028960, 1, 203.0),
+    (922.009798, 1, 243.6),
+    (1221.990637, 1, 282.2),
+    (1521.971475, 1, 317.0),
+    (1821.952313, 1, 351.2),
+    (2121.933152, 1, 383.0),
+    (2421.913990, 1, 413.0),
+    (2721.894829, 1, 441.2),
+];
+
+/// 离子与漂移气体的约化质量 (Da)，离子质量取`m/z × |z|`
+pub fn reduced_mass(mz: f64, charge: Charge, gas_mass: f64) -> f64 {
+    let ion_mass = mz * f64::from(charge.unsigned_abs());
+    ion_mass * gas_mass / (ion_mass + gas_mass)
+}
+
+/// Mason-Schamp方程：由约化迁移率K0 (cm²/(V·s)) 计算CCS (Å²)
+///
+/// `reduced_mass`为约化质量 (Da)，`temperature`为漂移气体温度 (K)。
+pub fn mason_schamp_ccs(k0: f64, charge: Charge, reduced_mass: f64, temperature: f64) -> f64 {
+    let q = f64::from(charge.unsigned_abs()) * ELEMENTARY_CHARGE;
+    let mu = reduced_mass * DALTON;
+    let k0_si = k0 * 1e-4;
+    let ccs_m2 = 3.0 * q / (16.0 * LOSCHMIDT) * (2.0 * std::f64::consts::PI / (mu * BOLTZMANN * temperature)).sqrt() / k0_si;
+    ccs_m2 * 1e20
+}
+
+/// Mason-Schamp方程的逆运算：由CCS (Å²) 计算约化迁移率K0 (cm²/(V·s))
+pub fn mason_schamp_k0(ccs: f64, charge: Charge, reduced_mass: f64, temperature: f64) -> f64 {
+    // CCS与K0互为反比，用K0 = 1时的CCS换算
+    mason_schamp_ccs(1.0, charge, reduced_mass, temperature) / ccs
+}
+
+/// 单场漂移管的实验条件
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriftTubeConditions {
+    /// 漂移管长度 (cm)
+    pub drift_length_cm: f64,
+    /// 漂移电压 (V)
+    pub drift_voltage: f64,
+    /// 漂移气体温度 (K)
+    pub temperature: f64,
+    /// 漂移气体压力 (Torr)
+    pub pressure_torr: f64,
+    /// 漂移气体质量 (Da)
+    pub gas_mass: f64,
+}
+
+impl DriftTubeConditions {
+    /// 由漂移时间 (秒) 计算约化迁移率K0 (cm²/(V·s))
+    ///
+    /// `K = L² / (V·t)`，再换算到标准温度和压力。
+    pub fn reduced_mobility(&self, drift_time: DriftTime) -> f64 {
+        let mobility = self.drift_length_cm.powi(2) / (self.drift_voltage * drift_time);
+        mobility * (self.pressure_torr / STANDARD_PRESSURE_TORR) * (STANDARD_TEMPERATURE / self.temperature)
+    }
+
+    /// 由漂移时间 (秒) 计算CCS (Å²)
+    pub fn ccs(&self, drift_time: DriftTime, mz: f64, charge: Charge) -> f64 {
+        let mu = reduced_mass(mz, charge, self.gas_mass);
+        mason_schamp_ccs(self.reduced_mobility(drift_time), charge, mu, self.temperature)
+    }
+}
+
+/// CCS校准曲线
+///
+/// `CCS·√μ / z = slope × 迁移率 + intercept`，迁移率为TIMS的1/K0或DTIMS的漂移时间。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CCSCalibration {
+    pub slope: f64,
+    pub intercept: f64,
+    pub r_squared: f64,
+    /// 漂移气体质量 (Da)
+    pub gas_mass: f64,
+}
+
+impl CCSCalibration {
+    /// 由校准离子 (m/z, 电荷, 参考CCS, 测得迁移率) 拟合，漂移气体为氮气
+    pub fn fit(calibrants: &[(f64, Charge, f64, f64)]) -> CoreResult<Self> {
+        Self::fit_with_gas_mass(calibrants, N2_GAS_MASS)
+    }
+
+    /// 由校准离子拟合，指定漂移气体质量 (Da)
+    ///
+    /// 至少需要[`MIN_CALIBRANTS`]个校准离子，电荷不能为0，迁移率不能全部相同。
+    pub fn fit_with_gas_mass(calibrants: &[(f64, Charge, f64, f64)], gas_mass: f64) -> CoreResult<Self> {
+        if calibrants.len() < MIN_CALIBRANTS {
+            return Err(CoreError::InvalidFormat(format!(
+                "CCS calibration needs at least {} calibrants, got {}",
+                MIN_CALIBRANTS,
+                calibrants.len()
+            )));
+        }
+        if let Some(&(mz, _, _, _)) = calibrants.iter().find(|calibrant| calibrant.1 == 0) {
+            return Err(CoreError::InvalidFormat(format!("CCS calibrant at m/z {} has no charge", mz)));
+        }
+
+        let points: Vec<(f64, f64)> = calibrants
+            .iter()
+            .map(|&(mz, charge, ccs, mobility)| (mobility, normalized_ccs(ccs, mz, charge, gas_mass)))
+            .collect();
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+        let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+        let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+        let syy: f64 = points.iter().map(|p| (p.1 - mean_y).powi(2)).sum();
+        if sxx <= 0.0 {
+            return Err(CoreError::InvalidFormat("CCS calibrants must span more than one mobility value".to_string()));
+        }
+
+        let slope = sxy / sxx;
+        let intercept = mean_y - slope * mean_x;
+        let r_squared = if syy > 0.0 { sxy * sxy / (sxx * syy) } else { 0.0 };
+        Ok(Self { slope, intercept, r_squared, gas_mass })
+    }
+
+    /// 由测得迁移率计算CCS (Å²)
+    pub fn apply(&self, mz: f64, charge: Charge, mobility: f64) -> f64 {
+        let mu = reduced_mass(mz, charge, self.gas_mass);
+        (self.slope * mobility + self.intercept) * f64::from(charge.unsigned_abs()) / mu.sqrt()
+    }
+}
+
+/// 按电荷和约化质量归一化的CCS：`CCS·√μ / z`
+fn normalized_ccs(ccs: f64, mz: f64, charge: Charge, gas_mass: f64) -> f64 {
+    ccs * reduced_mass(mz, charge, gas_mass).sqrt() / f64::from(charge.unsigned_abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMS_TEMPERATURE: f64 = 305.0;
+
+    #[test]
+    fn test_mason_schamp_single_field() {
+        // 约化迁移率1 cm²/(V·s)、298 K、氮气中m/z 622的单电荷离子，CCS约为数百Å²
+        let mu = reduced_mass(622.02896, 1, N2_GAS_MASS);
+        let ccs = mason_schamp_ccs(1.0, 1, mu, 298.0);
+        assert!((ccs - 18509.86 / (298.0 * mu).sqrt()).abs() / ccs < 1e-4);
+        assert!((mason_schamp_k0(ccs, 1, mu, 298.0) - 1.0).abs() < 1e-12);
+
+        // 由参考CCS反推漂移时间，再算回CCS
+        let conditions = DriftTubeConditions {
+            drift_length_cm: 78.1,
+            drift_voltage: 1500.0,
+            temperature: 300.0,
+            pressure_torr: 3.95,
+            gas_mass: N2_GAS_MASS,
+        };
+        let (mz, charge, reference) = AGILENT_TUNE_MIX[2];
+        let k0 = mason_schamp_k0(reference, charge, reduced_mass(mz, charge, N2_GAS_MASS), 300.0);
+        let k = k0 * (STANDARD_PRESSURE_TORR / 3.95) * (300.0 / STANDARD_TEMPERATURE);
+        let drift_time = 78.1f64.powi(2) / (1500.0 * k);
+        assert!((conditions.ccs(drift_time, mz, charge) - reference).abs() < 1e-9 * reference);
+        // 双电荷离子的CCS更大
+        assert!(conditions.ccs(drift_time, mz, 2) > conditions.ccs(drift_time, mz, 1));
+    }
+
+    #[test]
+    fn test_tims_calibration_round_trip() {
+        // 由参考CCS生成1/K0，加上±0.2%的偏差模拟测量误差
+        let calibrants: Vec<(f64, Charge, f64, f64)> = AGILENT_TUNE_MIX
+            .iter()
+            .enumerate()
+            .map(|(i, &(mz, charge, ccs))| {
+                let k0 = mason_schamp_k0(ccs, charge, reduced_mass(mz, charge, N2_GAS_MASS), TIMS_TEMPERATURE);
+                let error = if i % 2 == 0 { 1.002 } else { 0.998 };
+                (mz, charge, ccs, error / k0)
+            })
+            .collect();
+
+        let calibration = CCSCalibration::fit(&calibrants).unwrap();
+        assert!(calibration.r_squared > 0.999);
+        for &(mz, charge, ccs, inverse_k0) in &calibrants {
+            let predicted = calibration.apply(mz, charge, inverse_k0);
+            assert!((predicted - ccs).abs() / ccs < 0.005, "m/z {}: {} vs {}", mz, predicted, ccs);
+        }
+    }
+
+    #[test]
+    fn test_calibration_rejects_insufficient_points() {
+        let calibrants = [(118.086, 1, 121.3, 0.55), (322.048, 1, 153.7, 0.73)];
+        assert!(CCSCalibration::fit(&calibrants).is_err());
+        assert!(CCSCalibration::fit(&[]).is_err());
+        assert!(CCSCalibration::fit(&[(118.0, 1, 121.3, 0.6), (322.0, 0, 153.7, 0.7), (622.0, 1, 203.0, 0.9)]).is_err());
+        assert!(CCSCalibration::fit(&[(118.0, 1, 121.3, 0.6), (322.0, 1, 153.7, 0.6), (622.0, 1, 203.0, 0.6)]).is_err());
+    }
+}
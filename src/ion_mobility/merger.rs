@@ -3,16 +3,17 @@
 //! 提供基础的峰合并算法实现
 
 use crate::core::types::*;
-use std::collections::HashMap;
 
 /// 峰合并器
 pub struct PeakMerger {
     /// 合并策略
     merge_strategy: MergeStrategy,
+    /// 候选峰与当前组比较时使用的参考m/z
+    reference: GroupReference,
 }
 
 /// 合并策略
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MergeStrategy {
     /// 取最大强度
     MaxIntensity,
@@ -24,21 +25,61 @@ pub enum MergeStrategy {
     WeightedAverage,
 }
 
+/// 分组时候选峰与当前组比较的参考m/z
+///
+/// 只与组内最后一个峰比较时，间隔都在容差内的一长串峰会连成一组，跨度远超容差；
+/// 与固定的参考比较时一组的跨度不超过容差的两倍。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GroupReference {
+    /// 组内峰按强度加权的m/z中心，强度之和不为正时取算术平均
+    #[default]
+    Centroid,
+    /// 组内第一个（m/z最小的）峰
+    FirstPeak,
+}
+
+impl MergeStrategy {
+    /// 按名称解析合并策略
+    pub fn from_name(name: &str) -> CoreResult<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "max" => Ok(Self::MaxIntensity),
+            "average" | "mean" => Ok(Self::AverageIntensity),
+            "sum" => Ok(Self::SumIntensity),
+            "weighted" => Ok(Self::WeightedAverage),
+            _ => Err(CoreError::InvalidFormat(format!(
+                "Unknown merge strategy: {} (expected max, average, sum or weighted)",
+                name
+            ))),
+        }
+    }
+}
+
+impl Default for PeakMerger {
+    /// 默认取最大强度
+    fn default() -> Self {
+        Self::new(MergeStrategy::MaxIntensity)
+    }
+}
+
 impl PeakMerger {
-    /// 创建新的峰合并器
+    /// 创建新的峰合并器，分组时与组的强度加权中心比较
     pub fn new(strategy: MergeStrategy) -> Self {
         Self {
             merge_strategy: strategy,
+            reference: GroupReference::default(),
         }
     }
 
-    /// 使用默认策略创建峰合并器
-    pub fn default() -> Self {
-        Self::new(MergeStrategy::MaxIntensity)
+    /// 设置分组时的参考m/z
+    pub fn with_reference(mut self, reference: GroupReference) -> Self {
+        self.reference = reference;
+        self
     }
 
     /// 合并峰列表
-    pub fn merge_peaks(&self, peaks: Vec<Peak>, tolerance: f64) -> Vec<Peak> {
+    ///
+    /// 峰按m/z排序后分组：与当前组参考m/z之差不超过`tolerance`（按参考m/z计算）的峰并入该组。
+    pub fn merge_peaks(&self, peaks: Vec<Peak>, tolerance: impl MzTolerance) -> Vec<Peak> {
         if peaks.is_empty() {
             return Vec::new();
         }
@@ -49,33 +90,35 @@ impl PeakMerger {
 
         // 按m/z排序
         let mut sorted_peaks = peaks;
-        sorted_peaks.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        sorted_peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
 
-        // 使用标量合并算法
-        self.merge_peaks_scalar(sorted_peaks, tolerance)
+        self.merge_sorted(&sorted_peaks, |_, reference_mz| tolerance.tolerance_at_mz(reference_mz))
     }
 
-    /// 标量版本的峰合并
-    fn merge_peaks_scalar(&self, peaks: Vec<Peak>, tolerance: f64) -> Vec<Peak> {
+    /// 合并按m/z排序的峰，`tolerance_at(下标, 参考m/z)`给出第`下标`个峰并入当前组的容差 (Da)
+    fn merge_sorted(&self, peaks: &[Peak], tolerance_at: impl Fn(usize, f64) -> f64) -> Vec<Peak> {
         let mut merged = Vec::new();
-        let mut current_group = Vec::new();
+        let mut current_group: Vec<Peak> = Vec::new();
+        // 当前组的m/z之和、强度加权m/z之和与强度之和，用于计算中心
+        let (mut mz_sum, mut weighted_mz_sum, mut intensity_sum) = (0.0, 0.0, 0.0);
 
-        for &peak in &peaks {
-            if current_group.is_empty() {
-                current_group.push(peak);
-            } else {
-                let last_mz = current_group.last().unwrap().0;
-                if (peak.0 - last_mz) <= tolerance {
-                    current_group.push(peak);
-                } else {
-                    // 合并当前组
-                    if !current_group.is_empty() {
-                        merged.push(self.merge_group(&current_group));
-                    }
+        for (i, &peak) in peaks.iter().enumerate() {
+            if let Some(&(first_mz, _)) = current_group.first() {
+                let reference_mz = match self.reference {
+                    GroupReference::FirstPeak => first_mz,
+                    GroupReference::Centroid if intensity_sum > 0.0 => weighted_mz_sum / intensity_sum,
+                    GroupReference::Centroid => mz_sum / current_group.len() as f64,
+                };
+                if (peak.0 - reference_mz).abs() > tolerance_at(i, reference_mz) {
+                    merged.push(self.merge_group(&current_group));
                     current_group.clear();
-                    current_group.push(peak);
+                    (mz_sum, weighted_mz_sum, intensity_sum) = (0.0, 0.0, 0.0);
                 }
             }
+            current_group.push(peak);
+            mz_sum += peak.0;
+            weighted_mz_sum += peak.0 * peak.1;
+            intensity_sum += peak.1;
         }
 
         // 合并最后一组
@@ -86,7 +129,6 @@ impl PeakMerger {
         merged
     }
 
-    
     /// 合并一组峰
     fn merge_group(&self, group: &[Peak]) -> Peak {
         match self.merge_strategy {
@@ -121,7 +163,6 @@ impl PeakMerger {
                 (avg_mz, sum_intensity)
             }
             MergeStrategy::WeightedAverage => {
-                let total_intensity: f64 = group.iter().map(|(_, intensity)| *intensity).sum();
                 let max_intensity: f64 = group.iter().map(|(_, intensity)| *intensity).fold(0.0_f64, |a, b| a.max(b));
 
                 // 基于强度的加权平均
@@ -140,7 +181,7 @@ impl PeakMerger {
     }
 
     /// 批量合并多个峰列表
-    pub fn merge_multiple_peak_lists(&self, peak_lists: &[Vec<Peak>], tolerance: f64) -> Vec<Peak> {
+    pub fn merge_multiple_peak_lists(&self, peak_lists: &[Vec<Peak>], tolerance: impl MzTolerance) -> Vec<Peak> {
         let mut all_peaks = Vec::new();
 
         for peak_list in peak_lists {
@@ -151,7 +192,7 @@ impl PeakMerger {
     }
 
     /// 分级合并（先小容差合并，再大容差合并）
-    pub fn hierarchical_merge(&self, peaks: Vec<Peak>, tolerances: &[f64]) -> Vec<Peak> {
+    pub fn hierarchical_merge(&self, peaks: Vec<Peak>, tolerances: &[Tolerance]) -> Vec<Peak> {
         let mut current_peaks = peaks;
 
         for &tolerance in tolerances {
@@ -162,7 +203,9 @@ impl PeakMerger {
     }
 
     /// 基于密度的合并（在峰密度高的区域使用更小的容差）
-    pub fn density_based_merge(&self, peaks: Vec<Peak>, base_tolerance: f64) -> Vec<Peak> {
+    ///
+    /// `peaks`须按m/z排序。
+    pub fn density_based_merge(&self, peaks: Vec<Peak>, base_tolerance: impl MzTolerance) -> Vec<Peak> {
         if peaks.len() < 3 {
             return self.merge_peaks(peaks, base_tolerance);
         }
@@ -172,7 +215,7 @@ impl PeakMerger {
         let window_size = 5; // 使用5个最近的峰计算密度
 
         for i in 0..peaks.len() {
-            let start = if i >= window_size { i - window_size } else { 0 };
+            let start = i.saturating_sub(window_size);
             let end = (i + window_size).min(peaks.len() - 1);
 
             let window_range = peaks[end].0 - peaks[start].0;
@@ -185,40 +228,15 @@ impl PeakMerger {
         let min_density = densities.iter().fold(f64::INFINITY, |a, &b| a.min(b));
         let density_range = max_density - min_density;
 
-        let mut merged_peaks = Vec::new();
-        let mut current_group = Vec::new();
-
-        for (i, &peak) in peaks.iter().enumerate() {
+        // 密度高的区域使用更小的容差
+        self.merge_sorted(&peaks, |i, reference_mz| {
             let density_factor = if density_range > 0.0 {
                 (densities[i] - min_density) / density_range
             } else {
                 0.5
             };
-
-            // 密度高的区域使用更小的容差
-            let adjusted_tolerance = base_tolerance * (1.5 - density_factor);
-
-            if current_group.is_empty() {
-                current_group.push(peak);
-            } else {
-                let last_mz = current_group.last().unwrap().0;
-                if (peak.0 - last_mz) <= adjusted_tolerance {
-                    current_group.push(peak);
-                } else {
-                    if !current_group.is_empty() {
-                        merged_peaks.push(self.merge_group(&current_group));
-                    }
-                    current_group.clear();
-                    current_group.push(peak);
-                }
-            }
-        }
-
-        if !current_group.is_empty() {
-            merged_peaks.push(self.merge_group(&current_group));
-        }
-
-        merged_peaks
+            base_tolerance.tolerance_at_mz(reference_mz) * (1.5 - density_factor)
+        })
     }
 
     /// 获取合并统计信息
@@ -260,13 +278,14 @@ pub struct MergeStatistics {
 }
 
 /// 内部峰合并函数（供其他模块使用）
-pub fn merge_peaks_by_mz_internal(peaks: Vec<Peak>, mz_tolerance: f64) -> Vec<Peak> {
+pub fn merge_peaks_by_mz_internal(peaks: Vec<Peak>, mz_tolerance: impl MzTolerance) -> Vec<Peak> {
     let merger = PeakMerger::new(MergeStrategy::MaxIntensity);
     merger.merge_peaks(peaks, mz_tolerance)
 }
 
 /// 高级峰合并功能
 pub struct AdvancedPeakMerger {
+    #[allow(dead_code)]
     base_merger: PeakMerger,
 }
 
@@ -349,22 +368,23 @@ impl AdvancedPeakMerger {
     }
 
     /// 计算最佳容差
-    fn calculate_optimal_tolerance(&self, features: &PeakFeatures) -> f64 {
+    fn calculate_optimal_tolerance(&self, features: &PeakFeatures) -> Tolerance {
         // 基于峰密度和强度分布计算容差
         let base_tolerance = 0.01; // 10 ppm
 
-        if features.density > 20.0 {
+        Tolerance::Absolute(if features.density > 20.0 {
             base_tolerance * 0.5  // 密度高时减小容差
         } else if features.density < 1.0 {
             base_tolerance * 2.0  // 密度低时增大容差
         } else {
             base_tolerance
-        }
+        })
     }
 }
 
 /// 峰特征
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 struct PeakFeatures {
     count: usize,
     max_intensity: f64,
@@ -389,7 +409,7 @@ mod tests {
         ];
 
         let merger = PeakMerger::new(MergeStrategy::MaxIntensity);
-        let merged = merger.merge_peaks(peaks, 0.01);
+        let merged = merger.merge_peaks(peaks, Tolerance::Absolute(0.01));
 
         assert_eq!(merged.len(), 3); // 100.0和100.005应该合并
         assert!(merged.iter().any(|(mz, _)| (mz - 100.0).abs() < 0.001));
@@ -403,11 +423,11 @@ mod tests {
         ];
 
         let max_merger = PeakMerger::new(MergeStrategy::MaxIntensity);
-        let max_result = max_merger.merge_peaks(peaks.clone(), 0.01);
+        let max_result = max_merger.merge_peaks(peaks.clone(), Tolerance::Absolute(0.01));
         assert_eq!(max_result[0].1, 1000.0); // 应该取最大强度
 
         let avg_merger = PeakMerger::new(MergeStrategy::AverageIntensity);
-        let avg_result = avg_merger.merge_peaks(peaks, 0.01);
+        let avg_result = avg_merger.merge_peaks(peaks, Tolerance::Absolute(0.01));
         assert_eq!(avg_result[0].1, 900.0); // 应该取平均强度
     }
 
@@ -422,7 +442,7 @@ mod tests {
         ];
 
         let merger = PeakMerger::new(MergeStrategy::MaxIntensity);
-        let merged = merger.density_based_merge(peaks, 0.01);
+        let merged = merger.density_based_merge(peaks, Tolerance::Absolute(0.01));
 
         assert_eq!(merged.len(), 2); // 前4个峰密度高应该合并，第5个单独
     }
@@ -440,4 +460,38 @@ mod tests {
         assert_eq!(stats.reduction_ratio, 0.5);
         assert_eq!(stats.intensity_retention, 1.0);
     }
+
+    #[test]
+    fn test_evenly_spaced_peaks_do_not_chain() {
+        // 相邻间隔0.009都在容差内，但整串跨度0.171远超容差
+        let peaks: Vec<Peak> = (0..20).map(|i| (100.0 + 0.009 * i as f64, 10.0)).collect();
+        for reference in [GroupReference::Centroid, GroupReference::FirstPeak] {
+            let merger = PeakMerger::new(MergeStrategy::SumIntensity).with_reference(reference);
+            let merged = merger.merge_peaks(peaks.clone(), Tolerance::Absolute(0.01));
+            assert_eq!(merged.len(), 10);
+            assert!(merged.iter().all(|&(_, intensity)| intensity == 20.0));
+        }
+
+        // 强峰把中心拉向自己：第三个峰离中心约0.006，离第一个峰0.012
+        let peaks = vec![(200.0, 1.0), (200.006, 100.0), (200.012, 1.0)];
+        let centroid = PeakMerger::new(MergeStrategy::SumIntensity).merge_peaks(peaks.clone(), Tolerance::Absolute(0.01));
+        assert_eq!(centroid.len(), 1);
+        let first_peak = PeakMerger::new(MergeStrategy::SumIntensity)
+            .with_reference(GroupReference::FirstPeak)
+            .merge_peaks(peaks, Tolerance::Absolute(0.01));
+        assert_eq!(first_peak.len(), 2);
+    }
+
+    #[test]
+    fn test_ppm_tolerance_widens_with_mz() {
+        // 10 ppm：m/z 100处为0.001 Da，m/z 2000处为0.02 Da
+        let peaks = vec![(100.0, 1.0), (100.0008, 1.0), (100.01, 1.0), (2000.0, 1.0), (2000.015, 1.0)];
+        let merger = PeakMerger::new(MergeStrategy::SumIntensity);
+        let merged = merger.merge_peaks(peaks.clone(), Tolerance::PPM(10.0));
+        assert_eq!(merged.iter().map(|&(_, intensity)| intensity).collect::<Vec<_>>(), vec![2.0, 1.0, 2.0]);
+
+        // 同样0.005 Da的绝对容差在高m/z处合并不了
+        let merged = merger.merge_peaks(peaks, Tolerance::Absolute(0.005));
+        assert_eq!(merged.iter().map(|&(_, intensity)| intensity).collect::<Vec<_>>(), vec![2.0, 1.0, 1.0, 1.0]);
+    }
 }
@@ -3,10 +3,13 @@
 //! 这个模块提供了离子迁移率谱数据处理功能，包括：
 //! - 离子迁移率解析
 //! - 峰合并算法
+//! - CCS计算与校准
 
-pub mod parser;
+pub mod ccs;
 pub mod merger;
+pub mod parser;
 
 // 重新导出主要类型
-pub use parser::*;
+pub use ccs::*;
 pub use merger::*;
+pub use parser::*;
@@ -1,175 +1,260 @@
 //! 离子迁移率解析
 //!
-//! 提供离子迁移率数据解析和处理功能
+//! 提供离子迁移率数据解析和处理功能。谱图按漂移时间分箱，分箱key是漂移时间除以
+//! 分箱宽度后取整的整数，分箱内保留原始精度的平均漂移时间。
 
-use crate::core::{Spectrum, CoreResult};
+use crate::core::spectrum::{SpectraStore, Spectrum};
 use crate::core::types::*;
+use crate::ion_mobility::ccs::*;
 use crate::ion_mobility::merger::merge_peaks_by_mz_internal;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
+#[cfg(feature = "python")]
+use crate::xic::extractor::py_shared_spectra;
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
 #[cfg(feature = "python")]
-use pyo3::types::{PyDict, PyList, PyTuple, PyAny};
+use pyo3::types::{PyDict, PyList};
+#[cfg(feature = "python")]
+use log::warn;
+#[cfg(feature = "python")]
+use numpy::ndarray::Array2;
+#[cfg(feature = "python")]
+use numpy::{IntoPyArray, PyArray1, PyArray2};
+
+/// Python热图结果：(m/z轴, 漂移时间轴, 强度矩阵)
+#[cfg(feature = "python")]
+type HeatmapArrays<'py> = (Bound<'py, PyArray1<f64>>, Bound<'py, PyArray1<f64>>, Bound<'py, PyArray2<f64>>);
+
+/// 默认漂移时间分箱宽度 (秒)，即0.1毫秒
+pub const DEFAULT_DRIFT_BIN_WIDTH: f64 = 1e-4;
+
+/// 分箱内合并峰的默认m/z容差 (Da)
+pub const DEFAULT_MZ_TOLERANCE_DA: f64 = 10.0;
+
+/// 同一漂移时间分箱内合并的数据
+#[derive(Debug, Clone, PartialEq)]
+pub struct MobilityBin {
+    /// 分箱内谱图漂移时间的平均值，保持原始精度
+    pub drift_time: DriftTime,
+    /// 分箱内第一张谱图的保留时间 (秒)
+    pub retention_time: RetentionTime,
+    /// 合并后按m/z排序的峰
+    pub peaks: PeakList,
+    /// 分箱内的谱图数
+    pub spectrum_count: usize,
+}
+
+/// 漂移时间所在分箱的key
+pub fn drift_bin_key(drift_time: DriftTime, bin_width: f64) -> i64 {
+    (drift_time / bin_width).round() as i64
+}
 
 /// Python兼容的离子迁移率工具
 #[cfg(feature = "python")]
 #[pyclass]
 pub struct IonMobilityUtils;
 
+/// 解析Python传入的m/z容差：数值按Da处理，也可以是ToleranceModel
+#[cfg(feature = "python")]
+fn mz_tolerance_from_python(tolerance: &Bound<'_, PyAny>) -> PyResult<ToleranceModel> {
+    match tolerance.extract::<PyToleranceModel>() {
+        Ok(model) => Ok(model.model),
+        Err(_) => Ok(Tolerance::Absolute(tolerance.extract::<f64>()?).into()),
+    }
+}
+
 #[cfg(feature = "python")]
 #[pymethods]
 impl IonMobilityUtils {
     /// 解析离子迁移率数据
+    ///
+    /// `ms_object_list`为MSObject列表或MZMLObject；`mz_tolerance`为Da数值或ToleranceModel，
+    /// 默认10 Da。返回{漂移时间: [(m/z, 强度)]}，漂移时间为分箱内谱图的平均值。
     #[staticmethod]
-    #[pyo3(signature = (ms_object_list, rt_range=None, mz_tolerance=10, rt_tolerance=None))]
-    fn parse_ion_mobility(py: Python, ms_object_list: Vec<&PyAny>, rt_range: Option<(f64, f64)>,
-                         mz_tolerance: f64, rt_tolerance: Option<f64>) -> PyResult<Py<PyDict>> {
-        // 解析MSObject列表
-        let mut spectra = Vec::new();
-        for py_ms_object in ms_object_list {
-            let ms_object = py_ms_object.extract::<crate::core::ms_object::MSObject>()?;
-            spectra.push(ms_object.spectrum);
-        }
-
-        // 调用内部解析函数
-        let ion_mobility_data = parse_ion_mobility_internal(spectra, rt_range, mz_tolerance, rt_tolerance)
+    #[pyo3(signature = (ms_object_list, rt_range=None, mz_tolerance=None, rt_tolerance=None, bin_width=DEFAULT_DRIFT_BIN_WIDTH))]
+    fn parse_ion_mobility<'py>(
+        py: Python<'py>,
+        ms_object_list: &Bound<'py, PyAny>,
+        rt_range: Option<(f64, f64)>,
+        mz_tolerance: Option<&Bound<'py, PyAny>>,
+        rt_tolerance: Option<f64>,
+        bin_width: f64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let mz_tolerance = match mz_tolerance {
+            Some(tolerance) => mz_tolerance_from_python(tolerance)?,
+            None => Tolerance::Absolute(DEFAULT_MZ_TOLERANCE_DA).into(),
+        };
+        let spectra = py_shared_spectra(ms_object_list)?;
+        let ion_mobility_data = py
+            .allow_threads(|| parse_ion_mobility_internal(spectra.iter(), rt_range, &mz_tolerance, rt_tolerance, bin_width))
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
 
-        // 转换为Python字典
+        // 按漂移时间顺序写入Python字典
         let result_dict = PyDict::new(py);
-        for (drift_time_ms, peaks) in ion_mobility_data {
-            let py_peaks = PyList::empty(py);
-            for (mz, intensity) in peaks {
-                py_peaks.append((mz, intensity))?;
-            }
-            let drift_time = drift_time_ms as f64 / 1000.0; // 转换回秒
-            result_dict.set_item(drift_time, py_peaks)?;
+        for bin in ion_mobility_data.into_values() {
+            result_dict.set_item(bin.drift_time, bin.peaks)?;
         }
 
-        Ok(result_dict.into())
+        Ok(result_dict)
     }
 
-    /// 根据m/z容差合并峰
+    /// 提取m/z × 漂移时间强度矩阵
+    ///
+    /// `ms_object_list`为MSObject列表或MZMLObject。
+    /// 返回(m/z轴, 漂移时间轴, 矩阵)，矩阵形状为(漂移时间分箱数, m/z分箱数)。
     #[staticmethod]
-    fn merge_peaks_by_mz(py: Python, peaks: Vec<&PyAny>, mz_tolerance: f64) -> PyResult<Py<PyList>> {
-        // 解析峰列表
-        let mut peak_data = Vec::new();
-        for py_peak in peaks {
-            let tuple = py_peak.downcast::<PyTuple>()?;
-            let mz = tuple.get_item(0)?.extract::<f64>()?;
-            let intensity = tuple.get_item(1)?.extract::<f64>()?;
-            peak_data.push((mz, intensity));
-        }
-
-        // 合并峰
-        let merged_peaks = merge_peaks_by_mz_internal(peak_data, mz_tolerance);
-
-        // 转换为Python列表
-        let py_results = PyList::empty(py);
-        for (mz, intensity) in merged_peaks {
-            py_results.append((mz, intensity))?;
+    #[pyo3(signature = (ms_object_list, mz_range, mz_bin_size, dt_range, dt_bin_size, bin_width=DEFAULT_DRIFT_BIN_WIDTH))]
+    fn extract_heatmap<'py>(
+        py: Python<'py>,
+        ms_object_list: &Bound<'py, PyAny>,
+        mz_range: (f64, f64),
+        mz_bin_size: f64,
+        dt_range: (f64, f64),
+        dt_bin_size: f64,
+        bin_width: f64,
+    ) -> PyResult<HeatmapArrays<'py>> {
+        let spectra = py_shared_spectra(ms_object_list)?;
+        let heatmap = py
+            .allow_threads(|| extract_heatmap(&spectra, bin_width, mz_range, mz_bin_size, dt_range, dt_bin_size))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        if heatmap.skipped_spectra > 0 {
+            warn!("Skipped {} spectra without a drift time", heatmap.skipped_spectra);
         }
 
-        Ok(py_results.into())
+        let shape = (heatmap.drift_time_axis.len(), heatmap.mz_axis.len());
+        let matrix = Array2::from_shape_vec(shape, heatmap.intensities)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok((heatmap.mz_axis.into_pyarray(py), heatmap.drift_time_axis.into_pyarray(py), matrix.into_pyarray(py)))
     }
 
-    /// 计算离子迁移率校准曲线
+    /// 根据m/z容差合并峰，`mz_tolerance`为Da数值或ToleranceModel
+    ///
+    /// 峰与所在组的强度加权中心比较，相邻间隔都在容差内的一串峰不会连成一组。
     #[staticmethod]
-    fn calculate_calibration_curve(py: Python, calibration_points: &PyList) -> PyResult<Py<PyAny>> {
-        let mut points = Vec::new();
-        for point in calibration_points.iter() {
-            let tuple = point.downcast::<PyTuple>()?;
-            let known_mz = tuple.get_item(0)?.extract::<f64>()?;
-            let drift_time = tuple.get_item(1)?.extract::<f64>()?;
-            points.push((known_mz, drift_time));
-        }
+    fn merge_peaks_by_mz(peaks: Vec<Peak>, mz_tolerance: &Bound<'_, PyAny>) -> PyResult<Vec<Peak>> {
+        Ok(merge_peaks_by_mz_internal(peaks, mz_tolerance_from_python(mz_tolerance)?))
+    }
 
-        if points.len() < 2 {
+    /// 计算离子迁移率校准曲线，返回(斜率, 截距)
+    #[staticmethod]
+    fn calculate_calibration_curve(calibration_points: Vec<(f64, f64)>) -> PyResult<(f64, f64)> {
+        if calibration_points.len() < 2 {
             return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
                 "At least 2 calibration points are required"
             ));
         }
 
         // 简单线性校准 (实际中可能需要更复杂的模型)
-        let calibration = calculate_linear_calibration(&points);
-
-        // 返回校准参数
-        Ok((calibration.slope, calibration.intercept).into_py(py))
+        let calibration = calculate_linear_calibration(&calibration_points);
+        Ok((calibration.slope, calibration.intercept))
     }
 
     /// 应用校准曲线
     #[staticmethod]
-    fn apply_calibration(py: Python, drift_times: &PyList, slope: f64, intercept: f64) -> PyResult<Py<PyList>> {
-        let mut calibrated_mz = Vec::new();
-        for dt in drift_times.iter() {
-            let drift_time = dt.extract::<f64>()?;
-            let mz = slope * drift_time + intercept;
-            calibrated_mz.push(mz);
-        }
+    fn apply_calibration(drift_times: Vec<f64>, slope: f64, intercept: f64) -> Vec<f64> {
+        drift_times.into_iter().map(|drift_time| slope * drift_time + intercept).collect()
+    }
 
-        let py_results = PyList::empty(py);
-        for mz in calibrated_mz {
-            py_results.append(mz)?;
-        }
+    /// 单场DTIMS：由漂移时间 (秒) 按Mason-Schamp方程计算CCS (Å²)
+    #[staticmethod]
+    #[pyo3(signature = (drift_time, mz, charge, drift_length_cm, drift_voltage, temperature=298.15, pressure_torr=3.95, gas_mass=N2_GAS_MASS))]
+    #[allow(clippy::too_many_arguments)]
+    fn mason_schamp_ccs(
+        drift_time: f64,
+        mz: f64,
+        charge: Charge,
+        drift_length_cm: f64,
+        drift_voltage: f64,
+        temperature: f64,
+        pressure_torr: f64,
+        gas_mass: f64,
+    ) -> f64 {
+        let conditions = DriftTubeConditions { drift_length_cm, drift_voltage, temperature, pressure_torr, gas_mass };
+        conditions.ccs(drift_time, mz, charge)
+    }
 
-        Ok(py_results.into())
+    /// 由校准离子 (m/z, 电荷, 参考CCS, 测得迁移率) 拟合CCS校准曲线，返回(斜率, 截距)
+    #[staticmethod]
+    #[pyo3(signature = (calibrants, gas_mass=N2_GAS_MASS))]
+    fn fit_ccs_calibration(calibrants: Vec<(f64, Charge, f64, f64)>, gas_mass: f64) -> PyResult<(f64, f64)> {
+        CCSCalibration::fit_with_gas_mass(&calibrants, gas_mass)
+            .map(|calibration| (calibration.slope, calibration.intercept))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 用CCS校准曲线把测得迁移率换算为CCS (Å²)
+    #[staticmethod]
+    #[pyo3(signature = (calibration, mz, charge, mobility, gas_mass=N2_GAS_MASS))]
+    fn apply_ccs_calibration(calibration: (f64, f64), mz: f64, charge: Charge, mobility: f64, gas_mass: f64) -> f64 {
+        let (slope, intercept) = calibration;
+        CCSCalibration { slope, intercept, r_squared: 0.0, gas_mass }.apply(mz, charge, mobility)
+    }
+
+    /// Agilent调谐液校准离子 (m/z, 电荷, 氮气中的CCS)
+    #[staticmethod]
+    fn agilent_tune_mix() -> Vec<(f64, Charge, f64)> {
+        AGILENT_TUNE_MIX.to_vec()
     }
 
     /// 分析离子迁移率分布
     #[staticmethod]
-    fn analyze_mobility_distribution(py: Python, mobility_data: &PyDict) -> PyResult<Py<PyDict>> {
+    fn analyze_mobility_distribution<'py>(
+        py: Python<'py>,
+        mobility_data: &Bound<'py, PyDict>,
+    ) -> PyResult<Bound<'py, PyDict>> {
         let mut drift_times = Vec::new();
         let mut peak_counts = Vec::new();
 
         for (key, value) in mobility_data.iter() {
-            let drift_time = key.extract::<f64>()?;
-            let peaks = value.downcast::<PyList>()?;
-            let peak_count = peaks.len();
-
-            drift_times.push(drift_time);
-            peak_counts.push(peak_count);
+            drift_times.push(key.extract::<f64>()?);
+            peak_counts.push(value.downcast::<PyList>()?.len());
         }
 
+        let result_dict = PyDict::new(py);
         if drift_times.is_empty() {
-            return Ok(PyDict::new(py).into());
+            return Ok(result_dict);
         }
 
         // 计算统计指标
         let total_peaks: usize = peak_counts.iter().sum();
         let avg_peaks = total_peaks as f64 / peak_counts.len() as f64;
-        let max_peaks = peak_counts.iter().max().unwrap_or(&0);
-        let min_peaks = peak_counts.iter().min().unwrap_or(&0);
+        let max_peaks = peak_counts.iter().max().copied().unwrap_or(0);
+        let min_peaks = peak_counts.iter().min().copied().unwrap_or(0);
 
         // 找到峰最多的漂移时间
         let max_idx = peak_counts.iter()
-            .position(|&count| count == *max_peaks)
+            .position(|&count| count == max_peaks)
             .unwrap_or(0);
         let optimal_drift_time = drift_times[max_idx];
 
-        // 创建结果字典
-        let result_dict = PyDict::new(py);
         result_dict.set_item("total_peaks", total_peaks)?;
         result_dict.set_item("avg_peaks_per_drift_time", avg_peaks)?;
-        result_dict.set_item("max_peaks", *max_peaks)?;
-        result_dict.set_item("min_peaks", *min_peaks)?;
+        result_dict.set_item("max_peaks", max_peaks)?;
+        result_dict.set_item("min_peaks", min_peaks)?;
         result_dict.set_item("optimal_drift_time", optimal_drift_time)?;
         result_dict.set_item("drift_time_range",
             (drift_times.iter().fold(f64::INFINITY, |a, &b| a.min(b)),
              drift_times.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b))))?;
 
-        Ok(result_dict.into())
+        Ok(result_dict)
     }
 }
 
 /// 内部解析函数
-pub fn parse_ion_mobility_internal(
-    spectra: Vec<Spectrum>,
+///
+/// 按`bin_width`对漂移时间分箱（单位与谱图的漂移时间相同），返回按key排序的分箱。
+/// `bin_width`必须为正数。
+pub fn parse_ion_mobility_internal<'a>(
+    spectra: impl IntoIterator<Item = &'a Spectrum>,
     rt_range: Option<(f64, f64)>,
-    mz_tolerance: f64,
+    mz_tolerance: impl MzTolerance,
     rt_tolerance: Option<f64>,
-) -> CoreResult<HashMap<i32, Vec<Peak>>> {
-    let mut mobility_data = HashMap::new();
+    bin_width: f64,
+) -> CoreResult<BTreeMap<i64, MobilityBin>> {
+    check_bin_width(bin_width)?;
+
+    let mut mobility_data: BTreeMap<i64, MobilityBin> = BTreeMap::new();
 
     for spectrum in spectra {
         // 检查是否有漂移时间信息
@@ -185,55 +270,62 @@ pub fn parse_ion_mobility_internal(
             }
         }
 
-        let drift_time = spectrum.scan.drift_time;
-        let drift_time_ms = (drift_time * 1000.0) as i32; // 转换为毫秒作为整数key
-
-        // 检查保留时间容差（如果指定）
+        // 检查保留时间容差（如果指定）：需与已有分箱之一的保留时间接近
         if let Some(rt_tol) = rt_tolerance {
             let should_include = mobility_data
-                .keys()
-                .any(|&existing_dt| {
-                    let existing_rt = get_rt_for_drift_time(&mobility_data, existing_dt);
-                    (existing_rt - spectrum.scan.retention_time).abs() <= rt_tol
-                });
+                .values()
+                .any(|bin| (bin.retention_time - spectrum.scan.retention_time).abs() <= rt_tol);
 
             if !should_include && !mobility_data.is_empty() {
                 continue;
             }
         }
 
-        // 获取或创建该漂移时间的峰列表
-        let peaks = mobility_data.entry(drift_time_ms).or_insert_with(Vec::new);
+        // 获取或创建该漂移时间的分箱；drift_time暂存漂移时间之和
+        let drift_time = spectrum.scan.drift_time;
+        let bin = mobility_data
+            .entry(drift_bin_key(drift_time, bin_width))
+            .or_insert_with(|| MobilityBin {
+                drift_time: 0.0,
+                retention_time: spectrum.scan.retention_time,
+                peaks: Vec::new(),
+                spectrum_count: 0,
+            });
+        bin.drift_time += drift_time;
+        bin.spectrum_count += 1;
 
         // 添加当前谱图的峰
-        for &(mz, intensity) in &spectrum.peaks {
+        for (mz, intensity) in spectrum.peaks_iter() {
             // 检查是否与现有峰过于接近（避免重复）
-            let should_add = peaks.iter().all(|&(existing_mz, _)| {
-                (existing_mz - mz).abs() > mz_tolerance
+            let should_add = bin.peaks.iter().all(|&(existing_mz, _)| {
+                !mz_tolerance.is_within_tolerance(mz, existing_mz)
             });
 
             if should_add {
-                peaks.push((mz, intensity));
+                bin.peaks.push((mz, intensity));
             }
         }
     }
 
-    // 对每个漂移时间的峰进行合并和排序
-    for (_, peaks) in mobility_data.iter_mut() {
-        // 使用SIMD加速合并相近的峰
-        *peaks = merge_peaks_by_mz_internal(peaks.clone(), mz_tolerance);
-
-        // 按m/z排序
-        peaks.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    // 对每个分箱的峰进行合并和排序
+    for bin in mobility_data.values_mut() {
+        bin.drift_time /= bin.spectrum_count as f64;
+        bin.peaks = merge_peaks_by_mz_internal(std::mem::take(&mut bin.peaks), &mz_tolerance);
+        bin.peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
     }
 
     Ok(mobility_data)
 }
 
-/// 获取漂移时间对应的保留时间（简化实现）
-fn get_rt_for_drift_time(mobility_data: &HashMap<i32, Vec<Peak>>, drift_time_ms: i32) -> f64 {
-    // 这是一个简化的实现，实际中可能需要更复杂的映射
-    drift_time_ms as f64 / 1000.0 // 简化：使用漂移时间作为保留时间的代理
+/// 漂移时间分箱宽度必须为正数
+fn check_bin_width(bin_width: f64) -> CoreResult<()> {
+    if !(bin_width.is_finite() && bin_width > 0.0) {
+        return Err(CoreError::InvalidFormat(format!(
+            "Drift time bin width must be positive, got {}",
+            bin_width
+        )));
+    }
+    Ok(())
 }
 
 /// 线性校准参数
@@ -285,94 +377,198 @@ pub fn calculate_linear_calibration(points: &[(f64, f64)]) -> CalibrationCurve {
 
 /// 离子迁移率分析器
 pub struct IonMobilityAnalyzer {
-    mobility_data: HashMap<i32, Vec<Peak>>,
+    mobility_data: BTreeMap<i64, MobilityBin>,
     calibration: Option<CalibrationCurve>,
+    /// 没有漂移时间而被跳过的谱图数
+    skipped_spectra: usize,
+}
+
+/// m/z × 漂移时间强度矩阵
+#[derive(Debug, Clone, PartialEq)]
+pub struct MobilityHeatmap {
+    /// m/z轴，各列的中心
+    pub mz_axis: Vec<f64>,
+    /// 漂移时间轴，各行的中心
+    pub drift_time_axis: Vec<DriftTime>,
+    /// 行优先的累加强度，行为漂移时间、列为m/z
+    pub intensities: Vec<f64>,
+    /// 没有漂移时间而被跳过的谱图数
+    pub skipped_spectra: usize,
+}
+
+impl MobilityHeatmap {
+    /// 第`drift_index`行、第`mz_index`列的强度
+    pub fn value(&self, drift_index: usize, mz_index: usize) -> f64 {
+        self.intensities[drift_index * self.mz_axis.len() + mz_index]
+    }
+}
+
+/// 把`[range.0, range.1]`按`bin_size`等分后的分箱数，最后一个分箱可以不满
+fn axis_bin_count(range: (f64, f64), bin_size: f64, name: &str) -> CoreResult<usize> {
+    if !(bin_size.is_finite() && bin_size > 0.0) {
+        return Err(CoreError::InvalidFormat(format!("Heatmap {} bin size must be positive, got {}", name, bin_size)));
+    }
+    if !(range.0.is_finite() && range.1.is_finite() && range.0 < range.1) {
+        return Err(CoreError::InvalidFormat(format!(
+            "Invalid heatmap {} range: ({}, {})",
+            name, range.0, range.1
+        )));
+    }
+    // 容忍浮点误差，避免整除时多出一个空分箱
+    let bins = (range.1 - range.0) / bin_size;
+    let bins = if (bins - bins.round()).abs() < 1e-9 { bins.round() } else { bins.ceil() };
+    Ok((bins as usize).max(1))
+}
+
+/// 值所在的分箱下标，超出范围时为None；上限归入最后一个分箱
+fn axis_bin_index(value: f64, range: (f64, f64), bin_size: f64, bins: usize) -> Option<usize> {
+    (value >= range.0 && value <= range.1).then(|| (((value - range.0) / bin_size) as usize).min(bins - 1))
+}
+
+/// 分箱中心
+fn axis_centers(range: (f64, f64), bin_size: f64, bins: usize) -> Vec<f64> {
+    (0..bins).map(|i| range.0 + (i as f64 + 0.5) * bin_size).collect()
+}
+
+/// 由谱图提取m/z × 漂移时间强度矩阵
+///
+/// 谱图按`bin_width`对漂移时间分箱，所在行取分箱内漂移时间的平均值；每张谱图的峰
+/// 不经合并直接累加到所在格。两个范围都按各自的分箱宽度等分（含上下限），范围外的峰
+/// 和没有漂移时间的谱图忽略。
+pub fn extract_heatmap(
+    spectra: &[Spectrum],
+    bin_width: f64,
+    mz_range: (f64, f64),
+    mz_bin_size: f64,
+    dt_range: (DriftTime, DriftTime),
+    dt_bin_size: f64,
+) -> CoreResult<MobilityHeatmap> {
+    check_bin_width(bin_width)?;
+    let mz_bins = axis_bin_count(mz_range, mz_bin_size, "m/z")?;
+    let dt_bins = axis_bin_count(dt_range, dt_bin_size, "drift time")?;
+
+    // 各分箱的(漂移时间之和, 谱图数)
+    let mut drift_bins: BTreeMap<i64, (f64, usize)> = BTreeMap::new();
+    for spectrum in spectra.iter().filter(|spectrum| spectrum.scan.drift_time > 0.0) {
+        let bin = drift_bins.entry(drift_bin_key(spectrum.scan.drift_time, bin_width)).or_default();
+        bin.0 += spectrum.scan.drift_time;
+        bin.1 += 1;
+    }
+
+    let mut intensities = vec![0.0; mz_bins * dt_bins];
+    let mut skipped_spectra = 0;
+    for spectrum in spectra {
+        if spectrum.scan.drift_time <= 0.0 {
+            skipped_spectra += 1;
+            continue;
+        }
+        let (sum, count) = drift_bins[&drift_bin_key(spectrum.scan.drift_time, bin_width)];
+        let Some(row) = axis_bin_index(sum / count as f64, dt_range, dt_bin_size, dt_bins) else {
+            continue;
+        };
+        for (mz, intensity) in spectrum.peaks_iter() {
+            if let Some(column) = axis_bin_index(mz, mz_range, mz_bin_size, mz_bins) {
+                intensities[row * mz_bins + column] += intensity;
+            }
+        }
+    }
+
+    Ok(MobilityHeatmap {
+        mz_axis: axis_centers(mz_range, mz_bin_size, mz_bins),
+        drift_time_axis: axis_centers(dt_range, dt_bin_size, dt_bins),
+        intensities,
+        skipped_spectra,
+    })
 }
 
 impl IonMobilityAnalyzer {
-    /// 创建新的离子迁移率分析器
+    /// 创建新的离子迁移率分析器，使用默认分箱宽度
     pub fn new(spectra: Vec<Spectrum>) -> CoreResult<Self> {
-        let mobility_data = parse_ion_mobility_internal(spectra, None, 10.0, None)?;
+        Self::with_bin_width(spectra, DEFAULT_DRIFT_BIN_WIDTH)
+    }
+
+    /// 以指定的漂移时间分箱宽度创建
+    pub fn with_bin_width(spectra: Vec<Spectrum>, bin_width: f64) -> CoreResult<Self> {
+        Self::from_store(&SpectraStore::new(spectra), bin_width)
+    }
+
+    /// 由共享的谱图存储创建，只读取谱图而不复制
+    pub fn from_store(spectra: &SpectraStore, bin_width: f64) -> CoreResult<Self> {
+        let skipped_spectra = spectra.metadata().iter().filter(|meta| meta.drift_time <= 0.0).count();
+        let mobility_data = parse_ion_mobility_internal(spectra.iter(), None, Tolerance::Absolute(DEFAULT_MZ_TOLERANCE_DA), None, bin_width)?;
         Ok(Self {
             mobility_data,
             calibration: None,
+            skipped_spectra,
         })
     }
 
+    /// 没有漂移时间而被跳过的谱图数
+    pub fn skipped_spectra(&self) -> usize {
+        self.skipped_spectra
+    }
+
     /// 设置校准曲线
     pub fn set_calibration(&mut self, calibration: CalibrationCurve) {
         self.calibration = Some(calibration);
     }
 
+    /// 按漂移时间排序的分箱
+    pub fn bins(&self) -> impl Iterator<Item = &MobilityBin> {
+        self.mobility_data.values()
+    }
+
     /// 获取漂移时间范围
     pub fn get_drift_time_range(&self) -> Option<(f64, f64)> {
-        if self.mobility_data.is_empty() {
-            return None;
-        }
-
-        let min_dt_ms = self.mobility_data.keys().min().unwrap_or(&0);
-        let max_dt_ms = self.mobility_data.keys().max().unwrap_or(&0);
-        Some(((*min_dt_ms as f64) / 1000.0, (*max_dt_ms as f64) / 1000.0))
+        let first = self.mobility_data.values().next()?;
+        let last = self.mobility_data.values().next_back()?;
+        Some((first.drift_time, last.drift_time))
     }
 
-    /// 获取指定漂移时间的谱图
+    /// 获取与指定漂移时间最接近且在容差内的分箱的峰
     pub fn get_spectrum_at_drift_time(&self, drift_time: f64, tolerance: f64) -> Option<Vec<Peak>> {
-        let target_dt_ms = (drift_time * 1000.0) as i32;
-        let tolerance_ms = (tolerance * 1000.0) as i32;
-
-        for (&dt_ms, peaks) in &self.mobility_data {
-            if (dt_ms - target_dt_ms).abs() <= tolerance_ms {
-                return Some(peaks.clone());
-            }
-        }
-        None
+        self.mobility_data
+            .values()
+            .map(|bin| (bin, (bin.drift_time - drift_time).abs()))
+            .filter(|&(_, distance)| distance <= tolerance)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(bin, _)| bin.peaks.clone())
     }
 
-    /// 计算离子迁移率谱图的总离子流
-    pub fn calculate_total_ion_current(&self) -> std::collections::HashMap<i32, f64> {
-        let mut tic_map = std::collections::HashMap::new();
-        for (&drift_time_ms, peaks) in &self.mobility_data {
-            let tic = peaks.iter().map(|(_, intensity)| *intensity).sum();
-            tic_map.insert(drift_time_ms, tic);
-        }
-        tic_map
+    /// 计算每个漂移时间的总离子流，按漂移时间排序
+    pub fn calculate_total_ion_current(&self) -> Vec<(DriftTime, f64)> {
+        self.mobility_data
+            .values()
+            .map(|bin| (bin.drift_time, bin.peaks.iter().map(|(_, intensity)| *intensity).sum()))
+            .collect()
     }
 
-    /// 寻找最佳漂移时间（基于总离子流）
+    /// 寻找最佳漂移时间（基于总离子流），返回(漂移时间, 总离子流)
     pub fn find_optimal_drift_time(&self) -> Option<(f64, f64)> {
-        let tic_map = self.calculate_total_ion_current();
-        if tic_map.is_empty() {
-            return None;
-        }
-
-        let (optimal_ms, max_tic) = tic_map.iter()
-            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
-            .map(|(&ms, &tic)| (ms as f64 / 1000.0, tic))?; // 转换回秒
-
-        Some((optimal_ms, max_tic))
+        self.calculate_total_ion_current()
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
     }
 
     /// 提取离子迁移率色谱图
     pub fn extract_mobility_chromatogram(&self, target_mz: f64, tolerance: f64) -> Vec<(f64, f64)> {
         let mut chromatogram = Vec::new();
 
-        for (&drift_time_ms, peaks) in &self.mobility_data {
+        for bin in self.mobility_data.values() {
             // 搜索匹配的峰
-            let matching_indices = crate::utils::helpers::find_peaks_in_tolerance(peaks, target_mz, tolerance);
+            let matching_indices = crate::utils::helpers::find_peaks_in_tolerance(&bin.peaks, target_mz, tolerance);
 
             if !matching_indices.is_empty() {
                 // 计算总强度
                 let total_intensity: f64 = matching_indices
                     .iter()
-                    .map(|&idx| peaks[idx].1)
+                    .map(|&idx| bin.peaks[idx].1)
                     .sum();
 
-                chromatogram.push((drift_time_ms as f64 / 1000.0, total_intensity));
+                chromatogram.push((bin.drift_time, total_intensity));
             }
         }
 
-        // 按漂移时间排序
-        chromatogram.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
         chromatogram
     }
 }
@@ -382,18 +578,55 @@ mod tests {
     use super::*;
     use crate::core::spectrum::Spectrum;
 
+    fn im_spectrum(rt: f64, drift_time: f64, peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::ms1().unwrap();
+        spectrum.set_retention_time(rt).unwrap();
+        spectrum.set_drift_time(drift_time).unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum
+    }
+
     #[test]
     fn test_ion_mobility_parsing() {
-        let mut spectrum = Spectrum::ms1().unwrap();
-        spectrum.set_retention_time(10.0).unwrap();
-        spectrum.set_drift_time(5.0).unwrap();
-        spectrum.add_peak(100.0, 1000.0).unwrap();
-        spectrum.add_peak(200.0, 2000.0).unwrap();
+        let spectrum = im_spectrum(10.0, 5.0, &[(100.0, 1000.0), (200.0, 2000.0)]);
 
-        let result = parse_ion_mobility_internal(vec![spectrum], None, 10.0, None).unwrap();
+        let result = parse_ion_mobility_internal(&[spectrum], None, Tolerance::Absolute(10.0), None, DEFAULT_DRIFT_BIN_WIDTH).unwrap();
         assert_eq!(result.len(), 1);
-        assert!(result.contains_key(&5.0));
-        assert_eq!(result[&5.0].len(), 2);
+        let bin = &result[&drift_bin_key(5.0, DEFAULT_DRIFT_BIN_WIDTH)];
+        assert_eq!(bin.drift_time, 5.0);
+        assert_eq!(bin.peaks.len(), 2);
+    }
+
+    #[test]
+    fn test_sub_millisecond_drift_times_stay_distinct() {
+        // 25.04ms、25.1ms和25.4ms在0.1ms分箱下互不合并，25.0ms与25.02ms同一分箱
+        let spectra = vec![
+            im_spectrum(10.0, 0.02504, &[(100.0, 1.0)]),
+            im_spectrum(10.0, 0.0251, &[(100.0, 2.0)]),
+            im_spectrum(10.0, 0.0254, &[(100.0, 3.0)]),
+            im_spectrum(10.0, 0.0250, &[(300.0, 4.0)]),
+            im_spectrum(10.0, 0.02502, &[(400.0, 5.0)]),
+        ];
+        let result = parse_ion_mobility_internal(&spectra, None, Tolerance::Absolute(0.01), None, DEFAULT_DRIFT_BIN_WIDTH).unwrap();
+        let drift_times: Vec<f64> = result.values().map(|bin| bin.drift_time).collect();
+        assert_eq!(drift_times.len(), 3);
+        assert!((drift_times[0] - (0.02504 + 0.0250 + 0.02502) / 3.0).abs() < 1e-12);
+        assert_eq!(drift_times[1], 0.0251);
+        assert_eq!(drift_times[2], 0.0254);
+        assert_eq!(result.values().next().unwrap().spectrum_count, 3);
+        assert_eq!(result.values().next().unwrap().peaks.len(), 3);
+
+        // TIMS的1/K0在1.0附近不会塌缩到同一分箱
+        let tims = vec![
+            im_spectrum(10.0, 0.95, &[(100.0, 1.0)]),
+            im_spectrum(10.0, 1.0, &[(100.0, 1.0)]),
+            im_spectrum(10.0, 1.004, &[(100.0, 1.0)]),
+        ];
+        let result = parse_ion_mobility_internal(&tims, None, Tolerance::Absolute(0.01), None, 0.001).unwrap();
+        assert_eq!(result.values().map(|bin| bin.drift_time).collect::<Vec<_>>(), vec![0.95, 1.0, 1.004]);
+
+        assert!(parse_ion_mobility_internal(&[], None, Tolerance::Absolute(0.01), None, 0.0).is_err());
+        assert!(parse_ion_mobility_internal(&[], None, Tolerance::Absolute(0.01), None, f64::NAN).is_err());
     }
 
     #[test]
@@ -421,4 +654,47 @@ mod tests {
         assert!(optimal.is_some());
         assert_eq!(optimal.unwrap().0, 10.0); // 应该选择强度更高的漂移时间
     }
+
+    #[test]
+    fn test_extract_heatmap() {
+        // 三个漂移时间帧和一张没有漂移时间的谱图
+        let spectra = vec![
+            im_spectrum(10.0, 0.010, &[(100.5, 10.0), (150.2, 20.0)]),
+            im_spectrum(10.0, 0.020, &[(100.9, 5.0), (199.9, 7.0)]),
+            im_spectrum(10.0, 0.030, &[(130.0, 3.0), (250.0, 100.0)]),
+            Spectrum::ms1().unwrap(),
+        ];
+        let heatmap = extract_heatmap(&spectra, DEFAULT_DRIFT_BIN_WIDTH, (100.0, 200.0), 25.0, (0.005, 0.035), 0.01).unwrap();
+        assert_eq!(heatmap.mz_axis, vec![112.5, 137.5, 162.5, 187.5]);
+        assert_eq!(heatmap.drift_time_axis.len(), 3);
+        assert!((heatmap.drift_time_axis[1] - 0.02).abs() < 1e-12);
+        assert_eq!(heatmap.intensities.len(), 12);
+        assert_eq!(heatmap.skipped_spectra, 1);
+
+        assert_eq!(heatmap.value(0, 0), 10.0);
+        assert_eq!(heatmap.value(0, 2), 20.0);
+        assert_eq!(heatmap.value(1, 0), 5.0);
+        // m/z上限附近的峰归入最后一列
+        assert_eq!(heatmap.value(1, 3), 7.0);
+        assert_eq!(heatmap.value(2, 1), 3.0);
+        // 范围外的峰不计入
+        assert_eq!(heatmap.intensities.iter().sum::<f64>(), 45.0);
+
+        assert!(extract_heatmap(&spectra, DEFAULT_DRIFT_BIN_WIDTH, (200.0, 100.0), 25.0, (0.005, 0.035), 0.01).is_err());
+        assert!(extract_heatmap(&spectra, DEFAULT_DRIFT_BIN_WIDTH, (100.0, 200.0), 0.0, (0.005, 0.035), 0.01).is_err());
+        assert!(extract_heatmap(&spectra, 0.0, (100.0, 200.0), 25.0, (0.005, 0.035), 0.01).is_err());
+    }
+
+    #[test]
+    fn test_heatmap_keeps_close_peaks() {
+        // 峰间距远小于10 Da，两张谱图落在同一漂移时间分箱
+        let spectra = vec![
+            im_spectrum(10.0, 0.01, &[(100.0, 1.0), (100.2, 2.0), (101.0, 4.0), (104.0, 8.0)]),
+            im_spectrum(10.1, 0.01002, &[(100.1, 16.0), (103.9, 32.0)]),
+        ];
+        let heatmap = extract_heatmap(&spectra, DEFAULT_DRIFT_BIN_WIDTH, (100.0, 105.0), 1.0, (0.005, 0.015), 0.01).unwrap();
+        assert_eq!(heatmap.drift_time_axis.len(), 1);
+        assert_eq!(heatmap.intensities, vec![19.0, 4.0, 0.0, 32.0, 8.0]);
+        assert_eq!(heatmap.intensities.iter().sum::<f64>(), 63.0);
+    }
 }
@@ -1,23 +1,27 @@
 //! OpenMSUtils Spectra模块Rust重写
 //!
 //! 这个模块提供了质谱数据处理功能，与原Python接口1:1兼容：
-//! - MZML文件解析
+//! - MZML/mzXML文件解析
 //! - XIC提取
 //! - 谱图搜索和索引
 //! - 离子迁移率工具
 //! - 格式转换
+//! - DIA数据处理
+//!
+//! Python端所有类和函数都从顶层导出，同时按功能分组到`mzml`、`xic`、`search`等子模块，
+//! 见[`SUBMODULES`]。
 
 // 导入各个子模块
 pub mod test_module;
 pub mod core;
 pub mod parsers;
-
-// 导入各个子模块 - 即将实现
-// pub mod search;
-// pub mod xic;
-// pub mod conversion;
-// pub mod ion_mobility;
-// pub mod utils;
+pub mod utils;
+pub mod analysis;
+pub mod search;
+pub mod xic;
+pub mod conversion;
+pub mod dia;
+pub mod ion_mobility;
 
 // 重新导出测试接口
 #[cfg(feature = "python")]
@@ -43,7 +47,287 @@ fn _openms_utils_rust(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<core::Spectrum>()?;
     m.add_class::<parsers::MZMLParser>()?;
     m.add_class::<parsers::MZMLUtils>()?;
+    m.add_class::<parsers::mzml::MZMLReader>()?;
+    m.add_class::<parsers::mzml::MZMLObject>()?;
+    m.add_class::<parsers::mzml::MZMLSpectrumIterator>()?;
+    m.add_class::<parsers::mzml::MZMLChromatogram>()?;
+    m.add_class::<parsers::mzml::SpectrumHeader>()?;
+    m.add_class::<parsers::mzml::MZMLFileInfo>()?;
+    m.add_class::<parsers::mzxml::MZXMLReader>()?;
+    m.add_class::<core::spectrum::SpectraIndex>()?;
+    m.add_class::<search::binned_index::BinnedSpectra>()?;
+    m.add_class::<core::spectrum::PeakHit>()?;
+    m.add_class::<core::types::PyToleranceModel>()?;
+
+    // MSObject兼容层
+    m.add_class::<core::ms_object::MSObject>()?;
+    m.add_class::<core::ms_object::Precursor>()?;
+    m.add_class::<core::ms_object::Scan>()?;
+    m.add_class::<core::ms_object::KeyValue>()?;
+
+    // 格式转换
+    m.add_class::<conversion::SpectraConverter>()?;
+
+    // 分析工具
+    m.add_class::<analysis::clustering::SpectraClusterer>()?;
+    m.add_class::<analysis::targeted::TargetedExtractor>()?;
+    m.add_class::<analysis::rt_align::PyRtAlignment>()?;
+    m.add_class::<analysis::calibration::PyMassCalibration>()?;
+    m.add_class::<search::similarity::SpectrumSimilarity>()?;
+    m.add_class::<search::library::PySpectralLibrary>()?;
+    m.add_class::<search::library::LibraryHit>()?;
+    m.add_class::<search::range_query::PyRangeQuery>()?;
+    m.add_function(wrap_pyfunction!(search::grouping::py_group_and_merge_ms2, m)?)?;
+
+    // XIC
+    m.add_class::<xic::XICSExtractor>()?;
+    m.add_class::<xic::XICResult>()?;
+    m.add_class::<xic::ChromPeak>()?;
+    m.add_class::<xic::PyXICTargetBuilder>()?;
+
+    // DIA
+    m.add_class::<dia::DIAPseudoSpectrumGenerator>()?;
+    m.add_class::<dia::PyWindowMap>()?;
+
+    // 离子迁移率
+    m.add_class::<ion_mobility::IonMobilityUtils>()?;
+
+    // 质量换算工具
+    m.add_function(wrap_pyfunction!(utils::mass::py_neutral_mass, m)?)?;
+    m.add_function(wrap_pyfunction!(utils::mass::py_mz_from_neutral, m)?)?;
+    m.add_function(wrap_pyfunction!(utils::mass::py_ppm_diff, m)?)?;
+    m.add_function(wrap_pyfunction!(utils::mass::py_within_ppm, m)?)?;
+
+    // 同位素峰型
+    m.add_function(wrap_pyfunction!(core::isotopes::py_predict_pattern, m)?)?;
+    m.add_function(wrap_pyfunction!(core::isotopes::py_averagine_pattern, m)?)?;
+
+    // 谱图标题与MGF
+    m.add_function(wrap_pyfunction!(parsers::title::py_format_spectrum_title, m)?)?;
+    m.add_function(wrap_pyfunction!(parsers::title::py_parse_spectrum_title, m)?)?;
+    m.add_function(wrap_pyfunction!(parsers::mgf::py_read_mgf, m)?)?;
+    m.add_class::<parsers::mgf::PyMGFReader>()?;
+    m.add_class::<parsers::mgf::PyMGFWriter>()?;
+
+    // 按格式自动选择读取器
+    m.add_function(wrap_pyfunction!(parsers::open::py_read, m)?)?;
+
+    // 日志
+    m.add_function(wrap_pyfunction!(utils::logging::py_set_log_level, m)?)?;
 
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    add_submodules(m)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Python子模块及其成员，成员与顶层导出的是同一个对象
+#[cfg(feature = "python")]
+pub const SUBMODULES: &[(&str, &[&str])] = &[
+    ("core", &[
+        "TestMSObject", "Spectrum", "MSObject", "Precursor", "Scan", "KeyValue", "ToleranceModel", "predict_pattern",
+        "averagine_pattern",
+    ]),
+    ("mzml", &[
+        "MZMLParser", "MZMLUtils", "MZMLReader", "MZMLObject", "MZMLSpectrumIterator",
+        "MZMLChromatogram", "SpectrumHeader", "MZMLFileInfo",
+    ]),
+    ("mzxml", &["MZXMLReader"]),
+    ("mgf", &["MGFReader", "MGFWriter", "read_mgf", "format_spectrum_title", "parse_spectrum_title"]),
+    ("io", &["read"]),
+    ("conversion", &["SpectraConverter"]),
+    ("search", &[
+        "SpectraIndex", "BinnedSpectra", "PeakHit", "RangeQuery", "SpectrumSimilarity",
+        "SpectralLibrary", "LibraryHit", "group_and_merge_ms2",
+    ]),
+    ("analysis", &["SpectraClusterer", "TargetedExtractor", "RtAlignment", "MassCalibration"]),
+    ("xic", &["XICSExtractor", "XICResult", "ChromPeak", "XICTargetBuilder"]),
+    ("dia", &["DIAPseudoSpectrumGenerator", "DIAWindowMap"]),
+    ("ion_mobility", &["IonMobilityUtils"]),
+    ("utils", &["neutral_mass", "mz_from_neutral", "ppm_diff", "within_ppm", "set_log_level"]),
+];
+
+/// 按[`SUBMODULES`]创建子模块，并登记到`sys.modules`以支持`import 模块.子模块`
+#[cfg(feature = "python")]
+fn add_submodules(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    let modules = py.import("sys")?.getattr("modules")?;
+    let parent = m.name()?;
+    for &(name, members) in SUBMODULES {
+        let submodule = PyModule::new(py, name)?;
+        for &member in members {
+            submodule.add(member, m.getattr(member)?)?;
+        }
+        m.add_submodule(&submodule)?;
+        modules.set_item(format!("{}.{}", parent, name), submodule)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "python"))]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    /// 手写的类型存根，随扩展模块一起发布
+    const STUB: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/stubs/_openms_utils_rust.pyi"));
+
+    /// 对照运行时模块检查存根：导出的类、函数、方法和属性都必须在存根中声明，
+    /// 且参数名与`__text_signature__`一致
+    const CHECK_STUB: &str = r#"
+import ast
+import inspect
+
+declared = {}
+functions = {}
+for node in ast.parse(stub).body:
+    if isinstance(node, ast.ClassDef):
+        members = {}
+        for item in node.body:
+            if isinstance(item, ast.FunctionDef):
+                members[item.name] = [a.arg for a in item.args.args]
+            elif isinstance(item, ast.AnnAssign):
+                members[item.target.id] = None
+        declared[node.name] = members
+    elif isinstance(node, ast.FunctionDef):
+        functions[node.name] = [a.arg for a in node.args.args]
+
+def runtime_params(obj):
+    try:
+        return list(inspect.signature(obj).parameters)
+    except (TypeError, ValueError):
+        return None
+
+problems = []
+for name in dir(module):
+    if name.startswith("__"):
+        continue
+    obj = getattr(module, name)
+    if not isinstance(obj, type):
+        if functions.get(name) != runtime_params(obj):
+            problems.append(f"function {name}: stub {functions.get(name)} != runtime {runtime_params(obj)}")
+        continue
+    if name not in declared:
+        problems.append(f"class {name} is not declared")
+        continue
+    members = declared[name]
+    if obj.__text_signature__ is not None:
+        init = members.get("__init__")
+        if init is None or init[1:] != runtime_params(obj):
+            problems.append(f"{name}.__init__: stub {init} != runtime {runtime_params(obj)}")
+    for attr in vars(obj):
+        if attr.startswith("_") and attr not in ("__iter__", "__len__"):
+            continue
+        if attr not in members:
+            problems.append(f"{name}.{attr} is not declared")
+            continue
+        value = getattr(obj, attr)
+        if members[attr] is not None and callable(value):
+            params = runtime_params(value)
+            if params is not None and params != members[attr]:
+                problems.append(f"{name}.{attr}: stub {members[attr]} != runtime {params}")
+for name in list(declared) + list(functions):
+    if not hasattr(module, name):
+        problems.append(f"{name} is declared but not exported")
+"#;
+
+    #[test]
+    fn test_stub_matches_module() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = pyo3::wrap_pymodule!(_openms_utils_rust)(py);
+            let locals = pyo3::types::PyDict::new(py);
+            locals.set_item("module", module).unwrap();
+            locals.set_item("stub", STUB).unwrap();
+            py.run(&CString::new(CHECK_STUB).unwrap(), Some(&locals), None).unwrap();
+
+            let problems: Vec<String> = locals.get_item("problems").unwrap().unwrap().extract().unwrap();
+            assert!(problems.is_empty(), "stub out of date:\n{}", problems.join("\n"));
+        });
+    }
+
+    /// 构造每个导出的类并调用主要方法的冒烟测试脚本
+    const SMOKE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/test/smoke_bindings.py"));
+
+    #[test]
+    fn test_smoke_bindings() {
+        use crate::parsers::mzml::test_data::{build_mzml, write_temp_file, TestSpectrum};
+
+        let file = write_temp_file(&build_mzml(&[
+            TestSpectrum::new(1, 1, 10.0, vec![(500.0, 1000.0), (501.0, 400.0)]),
+            TestSpectrum::new(2, 2, 12.0, vec![(150.0, 50.0), (250.0, 20.0)]).with_precursor(500.0, 2),
+            TestSpectrum::new(3, 1, 14.0, vec![(500.0, 1200.0), (501.0, 500.0)]),
+        ]));
+        let work_dir = tempfile::tempdir().unwrap();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = pyo3::wrap_pymodule!(_openms_utils_rust)(py);
+            let locals = pyo3::types::PyDict::new(py);
+            py.run(&CString::new(SMOKE).unwrap(), Some(&locals), None).unwrap();
+            let used: Vec<String> = locals
+                .get_item("run")
+                .unwrap()
+                .unwrap()
+                .call1((module.bind(py), file.path(), work_dir.path()))
+                .unwrap_or_else(|e| panic!("smoke test failed: {}", e))
+                .extract()
+                .unwrap();
+
+            let exported: Vec<String> = module.bind(py).dir().unwrap().extract().unwrap();
+            let missing: Vec<&String> = exported
+                .iter()
+                .filter(|name| !name.starts_with("__") && !used.contains(name))
+                .filter(|name| !SUBMODULES.iter().any(|(submodule, _)| submodule == name))
+                .collect();
+            assert!(missing.is_empty(), "not exercised by test/smoke_bindings.py: {:?}", missing);
+        });
+    }
+
+    /// 导入每个子模块，并检查顶层导出的每个类和函数都属于某个子模块
+    const CHECK_SUBMODULES: &str = r#"
+import importlib
+import types
+
+grouped = set()
+for name, members in submodules:
+    submodule = importlib.import_module(f"{module.__name__}.{name}")
+    assert getattr(module, name) is submodule, name
+    for member in members:
+        assert getattr(submodule, member) is getattr(module, member), f"{name}.{member}"
+    grouped.update(members)
+
+ungrouped = [
+    name for name in dir(module)
+    if not name.startswith("__") and not isinstance(getattr(module, name), types.ModuleType) and name not in grouped
+]
+"#;
+
+    #[test]
+    fn test_import_submodules() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = pyo3::wrap_pymodule!(_openms_utils_rust)(py);
+            let locals = pyo3::types::PyDict::new(py);
+            locals.set_item("module", module).unwrap();
+            locals.set_item("submodules", SUBMODULES.to_vec()).unwrap();
+            py.run(&CString::new(CHECK_SUBMODULES).unwrap(), Some(&locals), None).unwrap();
+
+            let ungrouped: Vec<String> = locals.get_item("ungrouped").unwrap().unwrap().extract().unwrap();
+            assert!(ungrouped.is_empty(), "not in any submodule: {:?}", ungrouped);
+        });
+    }
+
+    #[test]
+    fn test_reader_signature() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = pyo3::wrap_pymodule!(_openms_utils_rust)(py);
+            let read = module.bind(py).getattr("MZMLReader").unwrap().getattr("read").unwrap();
+            let signature = py.import("inspect").unwrap().call_method1("signature", (read,)).unwrap();
+            assert_eq!(
+                signature.str().unwrap().to_string(),
+                "(self, /, filename, parse_spectra=True, parallel=False, num_processes=None, centroid=False, infer_charges=False, use_cache=False, lenient=False, progress_callback=None, progress_every=100)"
+            );
+        });
+    }
+}
@@ -2,9 +2,13 @@
 //! 
 //! 这个模块提供了所有解析器共用的工具函数和数据结构
 
-use crate::core::types::*;
+use crate::parsers::numpress::Numpress;
+use crate::utils::path::extended_length_path;
+use log::warn;
 use serde::{Deserialize, Serialize};
+use std::fs::File;
 use std::io;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 /// 解析错误类型
@@ -12,9 +16,31 @@ use thiserror::Error;
 pub enum ParseError {
     #[error("IO error: {0}")]
     Io(#[from] io::Error),
+
+    #[error("IO error on {path:?}: {source}")]
+    File { path: PathBuf, source: io::Error },
     
-    #[error("XML parsing error: {0}")]
-    Xml(String),
+    /// XML语法错误，文件无法继续读取；位置信息在已知时给出
+    #[error("XML parsing error{}: {message}", xml_location(*.byte_offset, .spectrum_id.as_deref(), .element.as_deref()))]
+    Xml {
+        message: String,
+        /// 出错处在文件中的字节偏移
+        byte_offset: Option<u64>,
+        /// 出错时正在解析的谱图的native id
+        spectrum_id: Option<String>,
+        /// 出错时正在解析的元素名
+        element: Option<String>,
+    },
+
+    /// 单个谱图的可恢复错误（二进制解码失败、属性格式错误、无效参数等）及该谱图在文件中的位置
+    #[error("Spectrum '{spectrum_id}'{} at byte {byte_offset}: {source}", .index.map(|index| format!(" (index {})", index)).unwrap_or_default())]
+    InSpectrum {
+        spectrum_id: String,
+        index: Option<usize>,
+        /// `<spectrum>`开始标签在文件中的字节偏移
+        byte_offset: u64,
+        source: Box<ParseError>,
+    },
     
     #[error("Invalid format: {0}")]
     InvalidFormat(String),
@@ -51,6 +77,62 @@ pub enum ParseError {
     
     #[error("Corrupted data: {0}")]
     CorruptedData(String),
+
+    #[error("Parsing stopped by callback: {0}")]
+    Interrupted(String),
+}
+
+impl ParseError {
+    /// 不带位置信息的XML错误
+    pub fn xml(message: impl ToString) -> Self {
+        ParseError::Xml { message: message.to_string(), byte_offset: None, spectrum_id: None, element: None }
+    }
+
+    /// 为XML错误补充位置信息，已有的信息不被覆盖；其他错误原样返回
+    pub fn with_xml_context(self, byte_offset: u64, spectrum_id: Option<&str>, element: Option<&str>) -> Self {
+        match self {
+            ParseError::Xml { message, byte_offset: offset, spectrum_id: id, element: name } => ParseError::Xml {
+                message,
+                byte_offset: offset.or(Some(byte_offset)),
+                spectrum_id: id.or_else(|| spectrum_id.map(str::to_string)),
+                element: name.or_else(|| element.map(str::to_string)),
+            },
+            other => other,
+        }
+    }
+
+    /// XML和IO错误使文件无法继续读取，其他错误只影响单个谱图
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, ParseError::Xml { .. } | ParseError::Io(_) | ParseError::File { .. })
+    }
+}
+
+/// XML错误信息中的位置描述，如" at byte 1024 in spectrum 'scan=5' (<binaryDataArray>)"
+fn xml_location(byte_offset: Option<u64>, spectrum_id: Option<&str>, element: Option<&str>) -> String {
+    let mut location = String::new();
+    if let Some(offset) = byte_offset {
+        location.push_str(&format!(" at byte {}", offset));
+    }
+    if let Some(id) = spectrum_id {
+        location.push_str(&format!(" in spectrum '{}'", id));
+    }
+    if let Some(element) = element {
+        location.push_str(&format!(" (<{}>)", element));
+    }
+    location
+}
+
+/// 按[`SpectrumErrorPolicy::Skip`]跳过的谱图
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedSpectrum {
+    /// 谱图的native id，开始标签无法解析时为空
+    pub spectrum_id: String,
+    /// 谱图的index属性
+    pub index: Option<usize>,
+    /// `<spectrum>`开始标签在文件中的字节偏移
+    pub byte_offset: u64,
+    /// 错误信息
+    pub error: String,
 }
 
 impl From<crate::core::types::CoreError> for ParseError {
@@ -62,6 +144,121 @@ impl From<crate::core::types::CoreError> for ParseError {
 /// 解析结果类型
 pub type ParseResult<T> = Result<T, ParseError>;
 
+/// 打开文件用于读取，错误信息中保留完整路径
+pub fn open_file(path: &Path) -> ParseResult<File> {
+    File::open(extended_length_path(path)).map_err(|source| ParseError::File { path: path.to_path_buf(), source })
+}
+
+/// 创建（或截断）文件用于写入，错误信息中保留完整路径
+pub fn create_file(path: &Path) -> ParseResult<File> {
+    File::create(extended_length_path(path)).map_err(|source| ParseError::File { path: path.to_path_buf(), source })
+}
+
+/// 单个谱图解析失败时的处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SpectrumErrorPolicy {
+    /// 任一谱图出错即中止解析
+    #[default]
+    Fail,
+    /// 跳过出错的谱图并记录警告
+    Skip,
+}
+
+/// 负强度峰的处理策略
+///
+/// 部分厂商格式经基线校正后会输出略小于0的强度。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum NegativeIntensityPolicy {
+    /// 视为无效峰数据，按谱图错误策略处理
+    Reject,
+    /// 将负强度截断为0并记录警告
+    #[default]
+    ClampToZero,
+    /// 保留负强度，谱图标记为允许负强度
+    Keep,
+}
+
+/// 扫描开始时间（MS:1000016）的单位
+///
+/// 不同转换工具分别以分钟或秒记录保留时间，解析后统一换算为秒。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RtUnit {
+    /// 忽略文件中的单位，按秒读取
+    Seconds,
+    /// 忽略文件中的单位，按分钟读取
+    Minutes,
+    /// 按文件中记录的单位换算，未记录单位时按秒处理
+    #[default]
+    Auto,
+}
+
+/// 解析选项
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParseOptions {
+    /// 谱图错误处理策略
+    pub error_policy: SpectrumErrorPolicy,
+    /// 负强度峰处理策略
+    #[serde(default)]
+    pub negative_intensity: NegativeIntensityPolicy,
+    /// 二进制数组的元素个数与defaultArrayLength不符时作为谱图错误处理，否则只警告
+    #[serde(default)]
+    pub strict_array_length: bool,
+    /// 解析时将轮廓谱图质心化，质心谱图和未记录类型的谱图保持不变
+    #[serde(default)]
+    pub centroid: bool,
+    /// 扫描开始时间的单位
+    #[serde(default)]
+    pub rt_units: RtUnit,
+}
+
+impl ParseOptions {
+    /// 创建默认解析选项
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置谱图错误处理策略
+    pub fn with_error_policy(mut self, error_policy: SpectrumErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// 设置负强度峰处理策略
+    pub fn with_negative_intensity_policy(mut self, policy: NegativeIntensityPolicy) -> Self {
+        self.negative_intensity = policy;
+        self
+    }
+
+    /// 设置二进制数组长度不符时是否作为谱图错误处理
+    pub fn with_strict_array_length(mut self, strict: bool) -> Self {
+        self.strict_array_length = strict;
+        self
+    }
+
+    /// 设置解析时是否将轮廓谱图质心化
+    pub fn with_centroid(mut self, centroid: bool) -> Self {
+        self.centroid = centroid;
+        self
+    }
+
+    /// 设置扫描开始时间的单位
+    pub fn with_rt_units(mut self, rt_units: RtUnit) -> Self {
+        self.rt_units = rt_units;
+        self
+    }
+
+    /// 宽松模式：无法解析的谱图被跳过并记录，而不是中止整个文件，等同于[`SpectrumErrorPolicy::Skip`]
+    pub fn with_lenient(mut self, lenient: bool) -> Self {
+        self.error_policy = if lenient { SpectrumErrorPolicy::Skip } else { SpectrumErrorPolicy::Fail };
+        self
+    }
+
+    /// 是否为宽松模式
+    pub fn is_lenient(&self) -> bool {
+        self.error_policy == SpectrumErrorPolicy::Skip
+    }
+}
+
 /// 二进制数据编码类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BinaryDataEncoding {
@@ -120,6 +317,17 @@ impl BinaryDataEncoding {
             BinaryDataEncoding::Int32Little | BinaryDataEncoding::Int64Little
         )
     }
+
+    /// mzML中对应的CV访问号和名称；mzML只支持小端序，大端序编码返回None
+    pub fn cv_term(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            BinaryDataEncoding::Float32Little => Some(("MS:1000521", "32-bit float")),
+            BinaryDataEncoding::Float64Little => Some(("MS:1000523", "64-bit float")),
+            BinaryDataEncoding::Int32Little => Some(("MS:1000519", "32-bit integer")),
+            BinaryDataEncoding::Int64Little => Some(("MS:1000522", "64-bit integer")),
+            _ => None,
+        }
+    }
 }
 
 /// CV参数（控制词汇表参数）
@@ -133,8 +341,15 @@ pub struct CVParam {
     pub value: String,
     /// 数据类型
     pub data_type: Option<String>,
-    /// 单位
-    pub unit: Option<String>,
+    /// 单位所属的CV（unitCvRef，如UO）
+    #[serde(default)]
+    pub unit_cv_ref: Option<String>,
+    /// 单位访问号（unitAccession，如UO:0000010）
+    #[serde(default)]
+    pub unit_accession: Option<String>,
+    /// 单位名称（unitName，如second）
+    #[serde(default)]
+    pub unit_name: Option<String>,
 }
 
 impl CVParam {
@@ -146,7 +361,9 @@ impl CVParam {
             name: name.into(),
             value: value.into(),
             data_type: None,
-            unit: None,
+            unit_cv_ref: None,
+            unit_accession: None,
+            unit_name: None,
         }
     }
 
@@ -156,12 +373,34 @@ impl CVParam {
         self
     }
 
-    /// 设置单位
-    pub fn with_unit(mut self, unit: impl Into<String>) -> Self {
-        self.unit = Some(unit.into());
+    /// 设置单位所属的CV
+    pub fn with_unit_cv_ref(mut self, unit_cv_ref: impl Into<String>) -> Self {
+        self.unit_cv_ref = Some(unit_cv_ref.into());
+        self
+    }
+
+    /// 设置单位访问号
+    pub fn with_unit_accession(mut self, unit_accession: impl Into<String>) -> Self {
+        self.unit_accession = Some(unit_accession.into());
         self
     }
 
+    /// 设置单位名称
+    pub fn with_unit_name(mut self, unit_name: impl Into<String>) -> Self {
+        self.unit_name = Some(unit_name.into());
+        self
+    }
+
+    /// 单位访问号是否为给定值
+    pub fn unit_is(&self, accession: &str) -> bool {
+        self.unit_accession.as_deref() == Some(accession)
+    }
+
+    /// 用于显示的单位：优先单位名称，缺少时为单位访问号
+    pub fn unit(&self) -> Option<&str> {
+        self.unit_name.as_deref().or(self.unit_accession.as_deref())
+    }
+
     /// 获取浮点数值
     pub fn as_f64(&self) -> ParseResult<f64> {
         self.value.parse::<f64>()
@@ -205,8 +444,15 @@ pub struct UserParam {
     pub value: String,
     /// 数据类型
     pub data_type: Option<String>,
-    /// 单位
-    pub unit: Option<String>,
+    /// 单位所属的CV（unitCvRef，如UO）
+    #[serde(default)]
+    pub unit_cv_ref: Option<String>,
+    /// 单位访问号（unitAccession，如UO:0000010）
+    #[serde(default)]
+    pub unit_accession: Option<String>,
+    /// 单位名称（unitName，如second）
+    #[serde(default)]
+    pub unit_name: Option<String>,
 }
 
 impl UserParam {
@@ -216,7 +462,9 @@ impl UserParam {
             name: name.into(),
             value: value.into(),
             data_type: None,
-            unit: None,
+            unit_cv_ref: None,
+            unit_accession: None,
+            unit_name: None,
         }
     }
 
@@ -226,18 +474,44 @@ impl UserParam {
         self
     }
 
-    /// 设置单位
-    pub fn with_unit(mut self, unit: impl Into<String>) -> Self {
-        self.unit = Some(unit.into());
+    /// 设置单位所属的CV
+    pub fn with_unit_cv_ref(mut self, unit_cv_ref: impl Into<String>) -> Self {
+        self.unit_cv_ref = Some(unit_cv_ref.into());
+        self
+    }
+
+    /// 设置单位访问号
+    pub fn with_unit_accession(mut self, unit_accession: impl Into<String>) -> Self {
+        self.unit_accession = Some(unit_accession.into());
+        self
+    }
+
+    /// 设置单位名称
+    pub fn with_unit_name(mut self, unit_name: impl Into<String>) -> Self {
+        self.unit_name = Some(unit_name.into());
         self
     }
+
+    /// 单位访问号是否为给定值
+    pub fn unit_is(&self, accession: &str) -> bool {
+        self.unit_accession.as_deref() == Some(accession)
+    }
+
+    /// 用于显示的单位：优先单位名称，缺少时为单位访问号
+    pub fn unit(&self) -> Option<&str> {
+        self.unit_name.as_deref().or(self.unit_accession.as_deref())
+    }
 }
 
 /// 二进制数据数组
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BinaryDataArray {
-    /// 数组长度
-    pub length: usize,
+    /// 元素个数的期望值（如谱图的defaultArrayLength），只用于核对；
+    /// 实际个数由解压后的字节数和编码大小推算
+    pub expected_length: Option<usize>,
+    /// 实际个数与期望值不符时返回错误而不是警告
+    #[serde(default)]
+    pub strict: bool,
     /// 编码类型
     pub encoding: BinaryDataEncoding,
     /// 压缩类型
@@ -257,6 +531,10 @@ pub enum CompressionType {
     Zlib,
     /// Gzip压缩
     Gzip,
+    /// MS-Numpress压缩
+    Numpress(Numpress),
+    /// MS-Numpress压缩后再zlib压缩
+    NumpressZlib(Numpress),
 }
 
 impl CompressionType {
@@ -271,13 +549,71 @@ impl CompressionType {
             ))),
         }
     }
+
+    /// 从mzML的CV访问号解析压缩类型
+    pub fn from_accession(accession: &str) -> Option<Self> {
+        match accession {
+            "MS:1000576" => Some(CompressionType::None),
+            "MS:1000574" => Some(CompressionType::Zlib),
+            "MS:1002312" => Some(CompressionType::Numpress(Numpress::Linear)),
+            "MS:1002313" => Some(CompressionType::Numpress(Numpress::Pic)),
+            "MS:1002314" => Some(CompressionType::Numpress(Numpress::Slof)),
+            "MS:1002746" => Some(CompressionType::NumpressZlib(Numpress::Linear)),
+            "MS:1002747" => Some(CompressionType::NumpressZlib(Numpress::Pic)),
+            "MS:1002748" => Some(CompressionType::NumpressZlib(Numpress::Slof)),
+            _ => None,
+        }
+    }
+
+    /// 合并同一数组中出现的多个压缩参数
+    ///
+    /// 有的文件把Numpress和zlib写成两个cvParam，合并为NumpressZlib；
+    /// "no compression"不覆盖已有的压缩方式。
+    pub fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (CompressionType::Numpress(numpress), CompressionType::Zlib)
+            | (CompressionType::Zlib, CompressionType::Numpress(numpress)) => CompressionType::NumpressZlib(numpress),
+            (CompressionType::NumpressZlib(_), CompressionType::Zlib) | (_, CompressionType::None) => self,
+            _ => other,
+        }
+    }
+
+    /// mzML中对应的CV访问号和名称，mzML不支持的gzip返回None
+    pub fn cv_term(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            CompressionType::None => Some(("MS:1000576", "no compression")),
+            CompressionType::Zlib => Some(("MS:1000574", "zlib compression")),
+            CompressionType::Gzip => None,
+            CompressionType::Numpress(Numpress::Linear) => Some(("MS:1002312", "MS-Numpress linear prediction compression")),
+            CompressionType::Numpress(Numpress::Pic) => Some(("MS:1002313", "MS-Numpress positive integer compression")),
+            CompressionType::Numpress(Numpress::Slof) => Some(("MS:1002314", "MS-Numpress short logged float compression")),
+            CompressionType::NumpressZlib(Numpress::Linear) => {
+                Some(("MS:1002746", "MS-Numpress linear prediction compression followed by zlib compression"))
+            }
+            CompressionType::NumpressZlib(Numpress::Pic) => {
+                Some(("MS:1002747", "MS-Numpress positive integer compression followed by zlib compression"))
+            }
+            CompressionType::NumpressZlib(Numpress::Slof) => {
+                Some(("MS:1002748", "MS-Numpress short logged float compression followed by zlib compression"))
+            }
+        }
+    }
+
+    /// Numpress压缩方式，其他压缩类型为None
+    pub fn numpress(&self) -> Option<Numpress> {
+        match self {
+            CompressionType::Numpress(numpress) | CompressionType::NumpressZlib(numpress) => Some(*numpress),
+            _ => None,
+        }
+    }
 }
 
 impl BinaryDataArray {
     /// 创建新的二进制数据数组
-    pub fn new(length: usize, encoding: BinaryDataEncoding, data: Vec<u8>) -> Self {
+    pub fn new(encoding: BinaryDataEncoding, data: Vec<u8>) -> Self {
         Self {
-            length,
+            expected_length: None,
+            strict: false,
             encoding,
             compression: None,
             precision: None,
@@ -285,6 +621,18 @@ impl BinaryDataArray {
         }
     }
 
+    /// 设置元素个数的期望值
+    pub fn with_expected_length(mut self, expected_length: usize) -> Self {
+        self.expected_length = Some(expected_length);
+        self
+    }
+
+    /// 设置元素个数不符时是否返回错误
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
     /// 设置压缩类型
     pub fn with_compression(mut self, compression: CompressionType) -> Self {
         self.compression = Some(compression);
@@ -306,6 +654,9 @@ impl BinaryDataArray {
             });
         }
 
+        if let Some(values) = self.decode_numpress()? {
+            return Ok(values);
+        }
         let decompressed = self.decompress()?;
         self.decode_to_f64(&decompressed)
     }
@@ -319,6 +670,9 @@ impl BinaryDataArray {
             });
         }
 
+        if let Some(values) = self.decode_numpress()? {
+            return Ok(values.into_iter().map(|value| value as f32).collect());
+        }
         let decompressed = self.decompress()?;
         self.decode_to_f32(&decompressed)
     }
@@ -332,6 +686,9 @@ impl BinaryDataArray {
             });
         }
 
+        if let Some(values) = self.decode_numpress()? {
+            return Ok(values.into_iter().map(|value| value.round() as i64).collect());
+        }
         let decompressed = self.decompress()?;
         self.decode_to_i64(&decompressed)
     }
@@ -345,15 +702,18 @@ impl BinaryDataArray {
             });
         }
 
+        if let Some(values) = self.decode_numpress()? {
+            return Ok(values.into_iter().map(|value| value.round() as i32).collect());
+        }
         let decompressed = self.decompress()?;
         self.decode_to_i32(&decompressed)
     }
 
-    /// 解压缩数据
+    /// 解压缩数据；Numpress数组只去掉外层的zlib压缩
     fn decompress(&self) -> ParseResult<Vec<u8>> {
         match self.compression {
-            Some(CompressionType::None) => Ok(self.data.clone()),
-            Some(CompressionType::Zlib) => {
+            Some(CompressionType::None | CompressionType::Numpress(_)) | None => Ok(self.data.clone()),
+            Some(CompressionType::Zlib | CompressionType::NumpressZlib(_)) => {
                 use flate2::read::ZlibDecoder;
                 use std::io::Read;
                 
@@ -373,20 +733,51 @@ impl BinaryDataArray {
                     .map_err(|e| ParseError::ZlibDecompress(e.to_string()))?;
                 Ok(decompressed)
             }
-            None => Ok(self.data.clone()),
         }
     }
 
-    /// 解码为f64数组（内部方法）
-    fn decode_to_f64(&self, data: &[u8]) -> ParseResult<Vec<f64>> {
-        let expected_size = self.length * self.encoding.size();
-        if data.len() < expected_size {
-            return Err(ParseError::CorruptedData(format!(
-                "Data size mismatch: expected {}, got {}", expected_size, data.len()
-            )));
+    /// Numpress压缩的数组解码为f64，其他压缩类型返回None
+    ///
+    /// Numpress的输出与声明的浮点/整数类型无关，元素个数由解码结果决定。
+    fn decode_numpress(&self) -> ParseResult<Option<Vec<f64>>> {
+        let Some(numpress) = self.compression.and_then(|compression| compression.numpress()) else {
+            return Ok(None);
+        };
+        let values = numpress.decode(&self.decompress()?)?;
+        self.check_length(values.len(), 0)?;
+        Ok(Some(values))
+    }
+
+    /// 由字节数推算元素个数，与期望值不符或有多余字节时警告（strict时返回错误）
+    fn element_count(&self, data: &[u8]) -> ParseResult<usize> {
+        let size = self.encoding.size();
+        self.check_length(data.len() / size, data.len() % size)
+    }
+
+    /// 核对解码出的元素个数，与期望值不符或有多余字节时警告（strict时返回错误）
+    fn check_length(&self, count: usize, trailing: usize) -> ParseResult<usize> {
+        let mismatch = match self.expected_length {
+            Some(expected) if expected != count => Some(format!(
+                "Array length mismatch: expected {} values, decoded {}", expected, count
+            )),
+            _ if trailing != 0 => Some(format!(
+                "Array length mismatch: {} trailing bytes after {} values", trailing, count
+            )),
+            _ => None,
+        };
+        match mismatch {
+            Some(message) if self.strict => Err(ParseError::CorruptedData(message)),
+            Some(message) => {
+                warn!("{}", message);
+                Ok(count)
+            }
+            None => Ok(count),
         }
+    }
 
-        let mut result = Vec::with_capacity(self.length);
+    /// 解码为f64数组（内部方法）
+    fn decode_to_f64(&self, data: &[u8]) -> ParseResult<Vec<f64>> {
+        let mut result = Vec::with_capacity(self.element_count(data)?);
         let chunk_size = self.encoding.size();
 
         for chunk in data.chunks_exact(chunk_size) {
@@ -419,14 +810,7 @@ impl BinaryDataArray {
 
     /// 解码为f32数组（内部方法）
     fn decode_to_f32(&self, data: &[u8]) -> ParseResult<Vec<f32>> {
-        let expected_size = self.length * self.encoding.size();
-        if data.len() < expected_size {
-            return Err(ParseError::CorruptedData(format!(
-                "Data size mismatch: expected {}, got {}", expected_size, data.len()
-            )));
-        }
-
-        let mut result = Vec::with_capacity(self.length);
+        let mut result = Vec::with_capacity(self.element_count(data)?);
         let chunk_size = self.encoding.size();
 
         for chunk in data.chunks_exact(chunk_size) {
@@ -459,14 +843,7 @@ impl BinaryDataArray {
 
     /// 解码为i64数组（内部方法）
     fn decode_to_i64(&self, data: &[u8]) -> ParseResult<Vec<i64>> {
-        let expected_size = self.length * self.encoding.size();
-        if data.len() < expected_size {
-            return Err(ParseError::CorruptedData(format!(
-                "Data size mismatch: expected {}, got {}", expected_size, data.len()
-            )));
-        }
-
-        let mut result = Vec::with_capacity(self.length);
+        let mut result = Vec::with_capacity(self.element_count(data)?);
         let chunk_size = self.encoding.size();
 
         for chunk in data.chunks_exact(chunk_size) {
@@ -499,14 +876,7 @@ impl BinaryDataArray {
 
     /// 解码为i32数组（内部方法）
     fn decode_to_i32(&self, data: &[u8]) -> ParseResult<Vec<i32>> {
-        let expected_size = self.length * self.encoding.size();
-        if data.len() < expected_size {
-            return Err(ParseError::CorruptedData(format!(
-                "Data size mismatch: expected {}, got {}", expected_size, data.len()
-            )));
-        }
-
-        let mut result = Vec::with_capacity(self.length);
+        let mut result = Vec::with_capacity(self.element_count(data)?);
         let chunk_size = self.encoding.size();
 
         for chunk in data.chunks_exact(chunk_size) {
@@ -562,10 +932,32 @@ mod tests {
         assert!(param.is_name("m/z array"));
     }
 
+    #[test]
+    fn test_cv_param_units_round_trip() {
+        let param = CVParam::new("MS:1000016", "scan start time", "12.5")
+            .with_unit_cv_ref("UO")
+            .with_unit_accession("UO:0000031")
+            .with_unit_name("minute");
+        assert!(param.unit_is("UO:0000031"));
+        assert!(!param.unit_is("UO:0000010"));
+        assert_eq!(param.unit(), Some("minute"));
+        assert_eq!(CVParam::new("MS:1000016", "", "1").with_unit_accession("UO:0000010").unit(), Some("UO:0000010"));
+
+        let bytes = bincode::serialize(&param).unwrap();
+        let restored: CVParam = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(restored.unit_cv_ref.as_deref(), Some("UO"));
+        assert_eq!(restored.unit_accession.as_deref(), Some("UO:0000031"));
+        assert_eq!(restored.unit_name.as_deref(), Some("minute"));
+
+        let user = UserParam::new("drift gas", "N2").with_unit_accession("UO:0000010");
+        let restored: UserParam = bincode::deserialize(&bincode::serialize(&user).unwrap()).unwrap();
+        assert!(restored.unit_is("UO:0000010"));
+    }
+
     #[test]
     fn test_binary_data_array() {
         let data = vec![0x00, 0x00, 0x28, 0x42]; // 42.0 in f32 little endian
-        let array = BinaryDataArray::new(1, BinaryDataEncoding::Float32Little, data);
+        let array = BinaryDataArray::new(BinaryDataEncoding::Float32Little, data).with_expected_length(1);
         
         let decoded = array.decode_f32().unwrap();
         assert_eq!(decoded.len(), 1);
@@ -579,5 +971,35 @@ mod tests {
         
         let compression = CompressionType::from_string("none").unwrap();
         assert_eq!(compression, CompressionType::None);
+
+        // CV访问号往返
+        for compression in [
+            CompressionType::None,
+            CompressionType::Zlib,
+            CompressionType::Numpress(Numpress::Slof),
+            CompressionType::NumpressZlib(Numpress::Linear),
+        ] {
+            let (accession, _) = compression.cv_term().unwrap();
+            assert_eq!(CompressionType::from_accession(accession), Some(compression));
+        }
+        assert_eq!(CompressionType::Gzip.cv_term(), None);
+        assert_eq!(BinaryDataEncoding::Float32Little.cv_term(), Some(("MS:1000521", "32-bit float")));
+        assert_eq!(BinaryDataEncoding::Float64Big.cv_term(), None);
+    }
+
+    #[test]
+    fn test_file_error_keeps_path() {
+        let error = open_file(Path::new("missing/样品.mzML")).unwrap_err();
+        assert!(matches!(&error, ParseError::File { path, .. } if path == Path::new("missing/样品.mzML")));
+        assert!(error.to_string().contains("\"missing/样品.mzML\""));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_error_non_utf8_path() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"missing/run_\xff.mzML"));
+        // Debug格式转义无效字节而不是替换为U+FFFD
+        assert!(open_file(path).unwrap_err().to_string().contains(r#""missing/run_\xFF.mzML""#));
     }
 }
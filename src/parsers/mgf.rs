@@ -0,0 +1,470 @@
+//! MGF读写
+//!
+//! 将MS2谱图写为Mascot Generic Format文本：每张谱图一个`BEGIN IONS`/`END IONS`块，
+//! 包含TITLE、PEPMASS、CHARGE、RTINSECONDS、SCANS和碰撞能量，随后是峰列表。
+//! 读取时没有SCANS或CHARGE的谱图从TITLE中解析扫描号和电荷（见[`parse_title`]）。
+
+use crate::core::spectrum::{PrecursorInfo, Spectrum};
+use crate::core::types::*;
+use crate::parsers::common::{create_file, open_file, ParseError, ParseResult};
+use crate::parsers::title::{parse_title, SpectrumTitleFormatter, TitleContext};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+#[cfg(feature = "python")]
+use crate::core::ms_object::MSObject;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 读取时保存TITLE的额外信息键
+pub const TITLE_KEY: &str = "title";
+
+/// MGF写入器
+pub struct MGFWriter<W: Write> {
+    inner: W,
+    /// 已写出的谱图数
+    count: usize,
+}
+
+impl MGFWriter<io::BufWriter<std::fs::File>> {
+    /// 创建写入到文件的写入器
+    pub fn create(filename: impl AsRef<Path>) -> ParseResult<Self> {
+        let file = create_file(filename.as_ref())?;
+        Ok(Self::new(io::BufWriter::new(file)))
+    }
+
+    /// 将谱图中的MS2谱图写入文件，返回写出的谱图数
+    ///
+    /// 读取时保存的TITLE（[`TITLE_KEY`]）原样写回，其余谱图用默认标题模板生成TITLE。
+    pub fn write(spectra: &[Spectrum], filename: impl AsRef<Path>) -> ParseResult<usize> {
+        let filename = filename.as_ref();
+        let file_name = filename.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        let context = TitleContext { file: file_name, ..TitleContext::default() };
+        let formatter = SpectrumTitleFormatter::default();
+
+        let mut writer = Self::create(filename)?;
+        for spectrum in spectra.iter().filter(|spectrum| spectrum.is_ms2()) {
+            match spectrum.get_additional_info(TITLE_KEY) {
+                Some(title) => writer.write_spectrum(title, spectrum)?,
+                None => writer.write_formatted(&formatter, &context, spectrum)?,
+            }
+        }
+        let count = writer.spectrum_count();
+        writer.finish()?;
+        Ok(count)
+    }
+}
+
+impl<W: Write> MGFWriter<W> {
+    /// 创建新的写入器
+    pub fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    /// 已写出的谱图数
+    pub fn spectrum_count(&self) -> usize {
+        self.count
+    }
+
+    /// 写出一张谱图
+    ///
+    /// 没有前体离子信息时不写PEPMASS和CHARGE，电荷未知（0）时不写CHARGE。
+    /// 碰撞能量优先写eV，否则写归一化碰撞能量，并标注单位。
+    pub fn write_spectrum(&mut self, title: &str, spectrum: &Spectrum) -> ParseResult<()> {
+        self.write_block(title, spectrum).map_err(ParseError::Io)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn write_block(&mut self, title: &str, spectrum: &Spectrum) -> io::Result<()> {
+        let out = &mut self.inner;
+        writeln!(out, "BEGIN IONS")?;
+        // TITLE到行尾为止，换行符会破坏格式
+        writeln!(out, "TITLE={}", title.replace(['\r', '\n'], " "))?;
+        if let Some(precursor) = spectrum.precursor.as_deref() {
+            if precursor.intensity > 0.0 {
+                writeln!(out, "PEPMASS={} {}", precursor.mz, precursor.intensity)?;
+            } else {
+                writeln!(out, "PEPMASS={}", precursor.mz)?;
+            }
+            if precursor.charge != 0 {
+                let sign = if precursor.charge > 0 { '+' } else { '-' };
+                writeln!(out, "CHARGE={}{}", precursor.charge.unsigned_abs(), sign)?;
+            }
+            if let Some(energy) = precursor.collision_energy_ev {
+                writeln!(out, "COLLISION_ENERGY={} eV", energy)?;
+            } else if let Some(energy) = precursor.normalized_collision_energy {
+                writeln!(out, "COLLISION_ENERGY={} NCE", energy)?;
+            }
+        }
+        writeln!(out, "RTINSECONDS={}", spectrum.scan.retention_time)?;
+        writeln!(out, "SCANS={}", spectrum.scan.scan_number)?;
+        for (mz, intensity) in spectrum.peaks_iter() {
+            writeln!(out, "{} {}", mz, intensity)?;
+        }
+        writeln!(out, "END IONS")?;
+        writeln!(out)
+    }
+
+    /// 用标题模板生成TITLE并写出谱图
+    pub fn write_formatted(
+        &mut self,
+        formatter: &SpectrumTitleFormatter,
+        context: &TitleContext,
+        spectrum: &Spectrum,
+    ) -> ParseResult<()> {
+        self.write_spectrum(&formatter.format(spectrum, context), spectrum)
+    }
+
+    /// 刷新输出并返回底层写入目标
+    pub fn finish(mut self) -> ParseResult<W> {
+        self.inner.flush().map_err(ParseError::Io)?;
+        Ok(self.inner)
+    }
+}
+
+/// MGF解析器
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MGFParser;
+
+impl MGFParser {
+    /// 创建新的解析器
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// 解析MGF文件，见[`read_mgf`]
+    pub fn parse(&self, filename: impl AsRef<Path>) -> ParseResult<Vec<Spectrum>> {
+        read_mgf(filename)
+    }
+
+    /// 从任意输入解析MGF，见[`read_mgf_from`]
+    pub fn parse_from<R: BufRead>(&self, reader: R) -> ParseResult<Vec<Spectrum>> {
+        read_mgf_from(reader)
+    }
+}
+
+/// 读取MGF文件
+pub fn read_mgf(filename: impl AsRef<Path>) -> ParseResult<Vec<Spectrum>> {
+    let file = open_file(filename.as_ref())?;
+    read_mgf_from(io::BufReader::new(file))
+}
+
+/// 从任意输入读取MGF，谱图均为MS2，TITLE保存在额外信息[`TITLE_KEY`]中
+pub fn read_mgf_from<R: BufRead>(reader: R) -> ParseResult<Vec<Spectrum>> {
+    let mut spectra = Vec::new();
+    let mut current: Option<MGFBlock> = None;
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line.map_err(ParseError::Io)?;
+        let line = line.trim();
+        let invalid = |message: &str| ParseError::InvalidFormat(format!("MGF line {}: {}", line_number + 1, message));
+
+        if line.is_empty() || line.starts_with(['#', ';', '!', '/']) {
+            continue;
+        }
+        if line.eq_ignore_ascii_case("BEGIN IONS") {
+            if current.is_some() {
+                return Err(invalid("BEGIN IONS inside an open block"));
+            }
+            current = Some(MGFBlock::default());
+            continue;
+        }
+        let Some(block) = current.as_mut() else {
+            // 块外的全局参数（如COM=、CHARGE=）不影响单张谱图
+            continue;
+        };
+        if line.eq_ignore_ascii_case("END IONS") {
+            spectra.push(current.take().unwrap_or_default().into_spectrum()?);
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            block.set(&key.trim().to_ascii_uppercase(), value.trim()).map_err(|e| invalid(&e))?;
+        } else {
+            let mut columns = line.split_whitespace();
+            let mz = columns.next().and_then(|v| v.parse::<f64>().ok());
+            let intensity = columns.next().map_or(Some(0.0), |v| v.parse::<f64>().ok());
+            match (mz, intensity) {
+                (Some(mz), Some(intensity)) => block.peaks.push((mz, intensity)),
+                _ => return Err(invalid(&format!("invalid peak line '{}'", line))),
+            }
+        }
+    }
+
+    if current.is_some() {
+        return Err(ParseError::InvalidFormat("MGF ended inside a BEGIN IONS block".to_string()));
+    }
+    Ok(spectra)
+}
+
+/// 读取中的单个`BEGIN IONS`块
+#[derive(Debug, Default)]
+struct MGFBlock {
+    title: Option<String>,
+    pepmass: Option<(f64, f64)>,
+    charge: Option<Charge>,
+    rt: Option<RetentionTime>,
+    scan_number: Option<ScanNumber>,
+    collision_energy_ev: Option<f64>,
+    normalized_collision_energy: Option<f64>,
+    peaks: Vec<Peak>,
+}
+
+impl MGFBlock {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = |value: &str| value.parse::<f64>().map_err(|_| format!("invalid {} value '{}'", key, value));
+        match key {
+            "TITLE" => self.title = Some(value.to_string()),
+            "PEPMASS" => {
+                let mut parts = value.split_whitespace();
+                let mz = number(parts.next().unwrap_or(""))?;
+                let intensity = parts.next().map_or(Ok(0.0), number)?;
+                self.pepmass = Some((mz, intensity));
+            }
+            // "2+"、"3-"，或"2+ and 3+"时取第一个
+            "CHARGE" => {
+                let first = value.split_whitespace().next().unwrap_or("");
+                let (digits, sign) = match first.strip_suffix('-') {
+                    Some(digits) => (digits, -1),
+                    None => (first.trim_end_matches('+'), 1),
+                };
+                let charge = digits.parse::<Charge>().map_err(|_| format!("invalid CHARGE value '{}'", value))?;
+                self.charge = Some(charge * sign);
+            }
+            "RTINSECONDS" => self.rt = Some(number(value.split('-').next().unwrap_or(""))?),
+            // 合并谱图写作"100-105"，取第一个扫描号
+            "SCANS" => {
+                let first = value.split(['-', ',']).next().unwrap_or("");
+                self.scan_number = Some(first.trim().parse().map_err(|_| format!("invalid SCANS value '{}'", value))?);
+            }
+            "COLLISION_ENERGY" => {
+                let mut parts = value.split_whitespace();
+                let energy = number(parts.next().unwrap_or(""))?;
+                match parts.next() {
+                    Some(unit) if unit.eq_ignore_ascii_case("nce") || unit == "%" => {
+                        self.normalized_collision_energy = Some(energy)
+                    }
+                    _ => self.collision_energy_ev = Some(energy),
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn into_spectrum(self) -> ParseResult<Spectrum> {
+        let title_info = self.title.as_deref().map(parse_title).unwrap_or_default();
+        let mut spectrum = Spectrum::ms2()?;
+        spectrum.add_peaks(self.peaks)?;
+        if let Some(rt) = self.rt {
+            spectrum.set_retention_time(rt)?;
+        }
+        if let Some(scan_number) = self.scan_number.or(title_info.scan_number) {
+            spectrum.set_scan_number(scan_number);
+        }
+        if let Some((mz, intensity)) = self.pepmass {
+            let collision_energy_ev = self.collision_energy_ev;
+            let normalized_collision_energy = self.normalized_collision_energy;
+            spectrum.set_precursor(PrecursorInfo {
+                mz,
+                intensity,
+                charge: self.charge.or(title_info.charge).unwrap_or(0),
+                activation_energy: collision_energy_ev.or(normalized_collision_energy).unwrap_or(0.0),
+                collision_energy_ev,
+                normalized_collision_energy,
+                ..PrecursorInfo::default()
+            });
+        }
+        if let Some(title) = self.title {
+            spectrum.add_additional_info(TITLE_KEY, title)?;
+        }
+        Ok(spectrum)
+    }
+}
+
+/// Python接口：读取MGF文件
+#[cfg(feature = "python")]
+#[pyfunction(name = "read_mgf")]
+pub fn py_read_mgf(path: std::path::PathBuf) -> PyResult<Vec<MSObject>> {
+    read_mgf(path)
+        .map(|spectra| spectra.into_iter().map(|spectrum| MSObject { spectrum }).collect())
+        .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))
+}
+
+/// Python兼容的MGF读取器
+#[cfg(feature = "python")]
+#[pyclass(name = "MGFReader")]
+pub struct PyMGFReader {
+    parser: MGFParser,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyMGFReader {
+    /// 创建新的MGF读取器
+    #[new]
+    fn new() -> Self {
+        Self { parser: MGFParser::new() }
+    }
+
+    /// 读取MGF文件并返回MSObject列表
+    fn read(&self, filename: std::path::PathBuf) -> PyResult<Vec<MSObject>> {
+        self.parser
+            .parse(filename)
+            .map(|spectra| spectra.into_iter().map(|spectrum| MSObject { spectrum }).collect())
+            .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))
+    }
+}
+
+/// Python兼容的MGF写入器
+#[cfg(feature = "python")]
+#[pyclass(name = "MGFWriter")]
+pub struct PyMGFWriter;
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyMGFWriter {
+    /// 创建新的MGF写入器
+    #[new]
+    fn new() -> Self {
+        Self
+    }
+
+    /// 将MS2谱图写入MGF文件，返回写出的谱图数，非MS2谱图被跳过
+    fn write(&self, spectra: Vec<PyRef<'_, MSObject>>, filename: std::path::PathBuf) -> PyResult<usize> {
+        let spectra: Vec<Spectrum> = spectra.iter().map(|ms_object| ms_object.spectrum.clone()).collect();
+        MGFWriter::write(&spectra, filename).map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    #[test]
+    fn test_write_block() {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.add_peaks([(150.5, 10.0), (300.25, 42.0)]).unwrap();
+        spectrum.set_scan_number(7);
+        spectrum.set_retention_time(61.5).unwrap();
+        spectrum.set_precursor(PrecursorInfo {
+            mz: 500.25,
+            charge: 2,
+            normalized_collision_energy: Some(27.0),
+            ..PrecursorInfo::default()
+        });
+
+        let mut writer = MGFWriter::new(Vec::new());
+        writer.write_spectrum("target 1\nscan 7", &spectrum).unwrap();
+        assert_eq!(writer.spectrum_count(), 1);
+        let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(
+            text,
+            "BEGIN IONS\nTITLE=target 1 scan 7\nPEPMASS=500.25\nCHARGE=2+\nCOLLISION_ENERGY=27 NCE\n\
+             RTINSECONDS=61.5\nSCANS=7\n150.5 10\n300.25 42\nEND IONS\n\n"
+        );
+    }
+
+    #[test]
+    fn test_read_restores_scan_from_title() {
+        let text = "COM=test\nBEGIN IONS\nTITLE=run01.1234.1234.2\nPEPMASS=500.25 1200\nRTINSECONDS=61.5\n\
+                    COLLISION_ENERGY=30 eV\n150.5 10\n300.25 42\nEND IONS\n\n\
+                    BEGIN IONS\nTITLE=RawFile: run01 Index: 77 Charge: 3+\nPEPMASS=400.1\nCHARGE=2+\nSCANS=80\n100 1\nEND IONS\n";
+        let spectra = read_mgf_from(text.as_bytes()).unwrap();
+        assert_eq!(spectra.len(), 2);
+
+        let first = &spectra[0];
+        assert_eq!(first.scan.scan_number, 1234);
+        assert_eq!(first.scan.retention_time, 61.5);
+        assert_eq!(first.peaks(), vec![(150.5, 10.0), (300.25, 42.0)]);
+        let precursor = first.precursor.as_deref().unwrap();
+        assert_eq!((precursor.mz, precursor.intensity, precursor.charge), (500.25, 1200.0, 2));
+        assert_eq!(precursor.collision_energy_ev, Some(30.0));
+        assert_eq!(first.get_additional_info(TITLE_KEY), Some("run01.1234.1234.2"));
+
+        // SCANS和CHARGE优先于标题
+        let second = &spectra[1];
+        assert_eq!(second.scan.scan_number, 80);
+        assert_eq!(second.precursor.as_deref().unwrap().charge, 2);
+
+        assert!(read_mgf_from("BEGIN IONS\n100 abc\nEND IONS\n".as_bytes()).is_err());
+        assert!(read_mgf_from("BEGIN IONS\n100 1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_formatted_round_trip() {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.add_peak(150.5, 10.0).unwrap();
+        spectrum.set_scan_number(42);
+        spectrum.set_precursor(PrecursorInfo { mz: 500.25, charge: 3, ..PrecursorInfo::default() });
+
+        let formatter = SpectrumTitleFormatter::default();
+        let mut writer = MGFWriter::new(Vec::new());
+        let context = TitleContext { file: "run01.mzML", ..TitleContext::default() };
+        writer.write_formatted(&formatter, &context, &spectrum).unwrap();
+        let text = writer.finish().unwrap();
+
+        // 去掉SCANS和CHARGE，只靠TITLE恢复
+        let text: String = String::from_utf8(text)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with("SCANS=") && !line.starts_with("CHARGE="))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let read = read_mgf_from(text.as_bytes()).unwrap();
+        assert_eq!(read[0].scan.scan_number, 42);
+        assert_eq!(read[0].precursor.as_deref().unwrap().charge, 3);
+    }
+
+    #[test]
+    fn test_parse_edge_cases() {
+        // Windows换行、缺少CHARGE、空峰列表和科学计数法强度
+        let text = "BEGIN IONS\r\nTITLE=no charge\r\nPEPMASS=500.25 1.2E4\r\nRTINSECONDS=12.5\r\nSCANS=3\r\n\
+                    100.5 1.5e3\r\n200.25 2.5E-1\r\nEND IONS\r\n\r\n\
+                    BEGIN IONS\r\nTITLE=empty\r\nPEPMASS=600.5\r\nCHARGE=3+\r\nEND IONS\r\n";
+        let spectra = MGFParser::new().parse_from(text.as_bytes()).unwrap();
+        assert_eq!(spectra.len(), 2);
+
+        let first = &spectra[0];
+        assert_eq!(first.get_additional_info(TITLE_KEY), Some("no charge"));
+        assert_eq!(first.peaks(), vec![(100.5, 1500.0), (200.25, 0.25)]);
+        let precursor = first.precursor.as_deref().unwrap();
+        assert_eq!((precursor.mz, precursor.intensity, precursor.charge), (500.25, 12000.0, 0));
+
+        let second = &spectra[1];
+        assert!(second.peak_count() == 0);
+        assert_eq!(second.precursor.as_deref().unwrap().charge, 3);
+    }
+
+    #[test]
+    fn test_write_file_round_trip() {
+        let mut ms1 = Spectrum::new(1).unwrap();
+        ms1.add_peak(400.0, 1.0).unwrap();
+        let mut titled = Spectrum::new(2).unwrap();
+        titled.add_peak(150.5, 1.5e7).unwrap();
+        titled.set_scan_number(5);
+        titled.set_precursor(PrecursorInfo { mz: 500.25, intensity: 3.0e6, charge: 2, ..PrecursorInfo::default() });
+        titled.add_additional_info(TITLE_KEY, "kept title").unwrap();
+        let mut untitled = Spectrum::new(2).unwrap();
+        untitled.set_scan_number(9);
+        untitled.set_retention_time(30.0).unwrap();
+
+        let file = tempfile::Builder::new().suffix(".mgf").tempfile().unwrap();
+        let written = MGFWriter::write(&[ms1, titled, untitled], file.path()).unwrap();
+        assert_eq!(written, 2);
+
+        let spectra = MGFParser::new().parse(file.path()).unwrap();
+        assert_eq!(spectra.len(), 2);
+        assert_eq!(spectra[0].get_additional_info(TITLE_KEY), Some("kept title"));
+        assert_eq!(spectra[0].peaks(), vec![(150.5, 1.5e7)]);
+        let precursor = spectra[0].precursor.as_deref().unwrap();
+        assert_eq!((precursor.mz, precursor.intensity, precursor.charge), (500.25, 3.0e6, 2));
+
+        assert_eq!(spectra[1].scan.scan_number, 9);
+        assert_eq!(spectra[1].scan.retention_time, 30.0);
+        assert!(spectra[1].precursor.is_none());
+        assert!(spectra[1].peak_count() == 0);
+    }
+}
@@ -3,9 +3,19 @@
 //! This module provides efficient parsing for various mass spectrometry
 //! file formats, starting with basic MZML support.
 
-use crate::core::Spectrum;
+use crate::core::SpectrumFilter;
+use crate::core::ms_object::MSObject;
 use pyo3::prelude::*;
-use std::path::Path;
+use crate::utils::path::extended_length_path;
+use std::path::PathBuf;
+
+pub mod common;
+pub mod mzml;
+pub mod mzxml;
+pub mod mgf;
+pub mod numpress;
+pub mod open;
+pub mod title;
 
 #[derive(Debug)]
 pub enum MZMLError {
@@ -20,53 +30,81 @@ pub enum MZMLError {
 /// processing data in chunks and using streaming XML parsing
 #[pyclass]
 pub struct MZMLParser {
-    file_path: String,
+    file_path: PathBuf,
     version: Option<String>,
+    cancelled: bool,
 }
 
 #[pymethods]
 impl MZMLParser {
     /// Create a new MZML parser for the specified file
+    ///
+    /// Accepts a str or any os.PathLike (e.g. pathlib.Path).
     #[new]
-    fn new(file_path: String) -> PyResult<Self> {
+    fn new(file_path: PathBuf) -> PyResult<Self> {
         // Validate file exists
-        if !Path::new(&file_path).exists() {
+        if !extended_length_path(&file_path).exists() {
             return Err(pyo3::exceptions::PyFileNotFoundError::new_err(
-                format!("MZML file not found: {}", file_path)
+                format!("MZML file not found: {:?}", file_path)
             ));
         }
 
         Ok(Self {
             file_path,
             version: None,
+            cancelled: false,
         })
     }
 
-    /// Parse the entire MZML file and return all spectra
-    fn parse_all_spectra(&mut self) -> PyResult<Vec<Spectrum>> {
-        // For now, return empty vector as placeholder
-        // Full MZML parsing implementation will be added later
-        println!("MZML parsing not yet implemented - returning empty spectra list");
-        Ok(Vec::new())
+    /// Parse the entire MZML file and return all spectra as MSObjects
+    ///
+    /// Also records the `<mzML version=...>` attribute in `version`.
+    /// Unreadable or malformed files raise an IOError.
+    fn parse_all_spectra(&mut self) -> PyResult<Vec<MSObject>> {
+        let parser = mzml::MZMLParser::new();
+        self.version = parser
+            .read_version(&self.file_path)
+            .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
+
+        let spectra = parser
+            .parse_sequential(&self.file_path)
+            .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
+        Ok(spectra.into_iter().map(|spectrum| MSObject { spectrum }).collect())
     }
 
-    /// Parse spectra with optional progress callback
+    /// Parse spectra with an optional progress callback
+    ///
+    /// The callback is called as `callback(spectra_parsed, bytes_read, total_bytes)`
+    /// every `every` spectra and once more when the whole file has been read.
+    /// The GIL is released while parsing and only taken to call the callback.
+    /// Returning False cancels parsing: the spectra parsed so far are returned
+    /// and `cancelled` is set. Exceptions raised by the callback propagate.
+    #[pyo3(signature = (callback=None, every=100))]
     fn parse_spectra_with_callback(
         &mut self,
         py: Python,
         callback: Option<PyObject>,
-    ) -> PyResult<Vec<Spectrum>> {
-        // Placeholder implementation
-        println!("MZML parsing with callback not yet implemented - returning empty spectra list");
-        if let Some(cb) = callback {
-            let _ = cb.call1(py, (0, 0.0));
-        }
-        Ok(Vec::new())
+        every: usize,
+    ) -> PyResult<Vec<MSObject>> {
+        let parser = mzml::MZMLParser::new();
+        let parsed = match callback {
+            Some(callback) => {
+                mzml::reader::parse_with_progress_callback(py, &parser, &self.file_path, &callback, every)?
+            }
+            None => mzml::ParsedSpectra {
+                spectra: py
+                    .allow_threads(|| parser.parse_sequential(&self.file_path))
+                    .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?,
+                cancelled: false,
+            },
+        };
+        self.cancelled = parsed.cancelled;
+        Ok(parsed.spectra.into_iter().map(|spectrum| MSObject { spectrum }).collect())
     }
 
     /// Get file metadata
     #[getter]
-    fn file_path(&self) -> String {
+    fn file_path(&self) -> PathBuf {
         self.file_path.clone()
     }
 
@@ -76,9 +114,15 @@ impl MZMLParser {
         self.version.clone()
     }
 
+    /// Whether the last `parse_spectra_with_callback` was cancelled by its callback
+    #[getter]
+    fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+
     /// Check if file exists and is readable
     fn validate_file(&self) -> bool {
-        Path::new(&self.file_path).exists() && Path::new(&self.file_path).is_file()
+        extended_length_path(&self.file_path).is_file()
     }
 }
 
@@ -90,44 +134,157 @@ pub struct MZMLUtils;
 impl MZMLUtils {
     /// Quick check if file is valid MZML
     #[staticmethod]
-    fn is_valid_mzml(file_path: String) -> bool {
+    fn is_valid_mzml(file_path: PathBuf) -> bool {
         // For now, just check if file exists and has .mzml extension
-        Path::new(&file_path).exists() &&
-        Path::new(&file_path).extension()
+        extended_length_path(&file_path).exists() &&
+        file_path.extension()
             .map(|ext| ext.to_string_lossy().to_lowercase() == "mzml")
             .unwrap_or(false)
     }
 
-    /// Get MZML file information without parsing spectra
+    /// Get MZML file information without decoding peak data
+    ///
+    /// Besides filesystem metadata, a header-only pass reports the mzML
+    /// version, instrument model, whether an indexedmzML wrapper is present,
+    /// spectrum counts per MS level, the retention time range in seconds and
+    /// whether the data is centroided ("centroid", "profile", "mixed" or None).
     #[staticmethod]
-    fn get_file_info(file_path: String) -> PyResult<PyObject> {
+    fn get_file_info(file_path: PathBuf) -> PyResult<PyObject> {
         pyo3::Python::with_gil(|py| {
-            if !Path::new(&file_path).exists() {
+            if !extended_length_path(&file_path).exists() {
                 return Err(pyo3::exceptions::PyFileNotFoundError::new_err(
-                    format!("File not found: {}", file_path)
+                    format!("File not found: {:?}", file_path)
                 ));
             }
 
-            let metadata = std::fs::metadata(&file_path)
-                .map_err(|e| pyo3::exceptions::PyOSError::new_err(e.to_string()))?;
+            let metadata = std::fs::metadata(extended_length_path(&file_path))
+                .map_err(|e| pyo3::exceptions::PyOSError::new_err(format!("{:?}: {}", file_path, e)))?;
 
             let info = pyo3::types::PyDict::new(py);
-            info.set_item("file_path", file_path)?;
+            info.set_item("file_path", &file_path)?;
             info.set_item("file_size", metadata.len())?;
             info.set_item("modified", metadata.modified().ok())?;
 
+            let summary = py
+                .allow_threads(|| mzml::MZMLParser::new().run_summary(&file_path))
+                .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
+            info.set_item("version", summary.version.as_deref())?;
+            info.set_item("instrument_model", summary.instrument_model.as_deref())?;
+            info.set_item("indexed", summary.indexed)?;
+            info.set_item("spectrum_count", summary.spectrum_count)?;
+            info.set_item("ms_level_counts", &summary.ms_level_counts)?;
+            info.set_item("rt_range", summary.rt_range)?;
+            info.set_item("spectrum_type", summary.spectrum_type())?;
+
             Ok(info.into())
         })
     }
+
+    /// Extract spectra matching the given ranges into a new indexed mzML file
+    ///
+    /// Kept spectra are copied through verbatim (binary arrays are not decoded),
+    /// their index is renumbered and native ids are preserved. Returns a dict
+    /// with the number of kept and dropped spectra.
+    #[staticmethod]
+    #[pyo3(signature = (input_path, output_path, rt_range=None, scan_range=None, ms_levels=None))]
+    fn extract_subset(
+        py: Python,
+        input_path: PathBuf,
+        output_path: PathBuf,
+        rt_range: Option<(f64, f64)>,
+        scan_range: Option<(u32, u32)>,
+        ms_levels: Option<Vec<u8>>,
+    ) -> PyResult<PyObject> {
+        let filter = SpectrumFilter {
+            rt_range,
+            scan_range,
+            ms_levels,
+        };
+
+        let summary = mzml::extract_subset(&input_path, &output_path, &filter)
+            .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
+
+        let result = pyo3::types::PyDict::new(py);
+        result.set_item("kept", summary.kept)?;
+        result.set_item("dropped", summary.dropped)?;
+        Ok(result.into())
+    }
+
+    /// Compare two mzML files spectrum by spectrum
+    ///
+    /// Spectra are paired by native id. Returns a dict with aggregate counts,
+    /// the worst-case m/z deviation, unmatched ids and a capped list of
+    /// per-spectrum difference summaries.
+    #[staticmethod]
+    #[pyo3(signature = (path_a, path_b, mz_tolerance_ppm=5.0, intensity_rel_tol=0.01))]
+    fn diff_files(
+        py: Python,
+        path_a: PathBuf,
+        path_b: PathBuf,
+        mz_tolerance_ppm: f64,
+        intensity_rel_tol: f64,
+    ) -> PyResult<PyObject> {
+        let report = mzml::diff_files(&path_a, &path_b, mz_tolerance_ppm, intensity_rel_tol)
+            .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
+
+        let differences = pyo3::types::PyList::empty(py);
+        for diff in &report.differences {
+            let item = pyo3::types::PyDict::new(py);
+            item.set_item("id", &diff.id)?;
+            item.set_item("metadata", &diff.comparison.metadata_differences)?;
+            item.set_item("peak_count_a", diff.comparison.peak_counts.0)?;
+            item.set_item("peak_count_b", diff.comparison.peak_counts.1)?;
+            item.set_item("unmatched_peaks", diff.comparison.unmatched_peaks)?;
+            item.set_item("intensity_mismatches", diff.comparison.intensity_mismatches)?;
+            item.set_item("max_mz_deviation_ppm", diff.comparison.max_mz_deviation_ppm)?;
+            differences.append(item)?;
+        }
+
+        let result = pyo3::types::PyDict::new(py);
+        result.set_item("compared", report.compared)?;
+        result.set_item("identical", report.identical)?;
+        result.set_item("metadata_differences", report.metadata_differences)?;
+        result.set_item("peak_differences", report.peak_differences)?;
+        result.set_item("max_mz_deviation_ppm", report.max_mz_deviation_ppm)?;
+        result.set_item("differences", differences)?;
+        result.set_item("only_in_a", &report.only_in_a)?;
+        result.set_item("only_in_b", &report.only_in_b)?;
+        Ok(result.into())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::Spectrum;
 
     #[test]
     fn test_mzml_utils() {
-        assert!(MZMLUtils::is_valid_mzml("nonexistent.mzml") == false);
+        assert!(!MZMLUtils::is_valid_mzml("nonexistent.mzml".into()));
+    }
+
+    #[test]
+    fn test_parse_all_spectra() {
+        use crate::parsers::mzml::test_data::{build_mzml, write_temp_file, TestSpectrum};
+
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 10.0, vec![(400.0, 100.0), (500.0, 200.0)]),
+            TestSpectrum::new(2, 2, 12.5, vec![(150.0, 50.0)]).with_precursor(500.0, 2),
+        ];
+        let file = write_temp_file(&build_mzml(&spectra));
+
+        let mut parser = MZMLParser::new(file.path().to_path_buf()).unwrap();
+        assert_eq!(parser.version(), None);
+        let parsed = parser.parse_all_spectra().unwrap();
+        assert_eq!(parser.version().as_deref(), Some("1.1.0"));
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].spectrum.peaks(), vec![(400.0, 100.0), (500.0, 200.0)]);
+        assert_eq!(parsed[1].spectrum.scan.retention_time, 12.5);
+        assert!(parsed[1].spectrum.is_ms2());
+
+        let broken = write_temp_file("<mzML version=\"1.1.0\"><run><spectrumList><spectrum id=\"x\"></run>");
+        let mut parser = MZMLParser::new(broken.path().to_path_buf()).unwrap();
+        assert!(parser.parse_all_spectra().is_err());
     }
 
     #[test]
@@ -0,0 +1,128 @@
+//! MZML色谱图数据结构
+//!
+//! 这个模块定义了`<chromatogram>`元素解析后的数据结构，如仪器记录的TIC、基峰色谱图以及SRM跃迁
+
+use crate::parsers::common::{CVParam, UserParam};
+use crate::parsers::mzml::spectrum::{MZMLIsolationWindow, MZMLPrecursor};
+use serde::{Deserialize, Serialize};
+
+/// 色谱图类型的CV访问号
+const CHROMATOGRAM_TYPES: [&str; 6] = [
+    "MS:1000235", // total ion current chromatogram
+    "MS:1000628", // basepeak chromatogram
+    "MS:1000627", // selected ion current chromatogram
+    "MS:1001473", // selected reaction monitoring chromatogram
+    "MS:1000810", // ion current chromatogram
+    "MS:1003019", // pressure chromatogram
+];
+
+/// MZML色谱图
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Chromatogram {
+    /// 色谱图ID
+    pub id: String,
+    /// 索引
+    pub index: Option<usize>,
+    /// 默认数组长度
+    pub default_array_length: usize,
+    /// CV参数列表
+    pub cv_params: Vec<CVParam>,
+    /// 用户参数列表
+    pub user_params: Vec<UserParam>,
+    /// 前体离子（SRM等）
+    pub precursor: Option<MZMLPrecursor>,
+    /// 产物离子的分离窗口（SRM等）
+    pub product: Option<MZMLIsolationWindow>,
+    /// 时间数组，单位见`time_unit`
+    pub time_array: Vec<f64>,
+    /// 强度数组；没有强度数组的色谱图（压力等）取第一个非时间数组
+    pub intensity_array: Vec<f64>,
+    /// 时间数组的单位（如minute、second），文件中未记录时为None
+    pub time_unit: Option<String>,
+}
+
+impl Chromatogram {
+    /// 创建新的色谱图
+    pub fn new(id: String, default_array_length: usize) -> Self {
+        Self {
+            id,
+            default_array_length,
+            ..Default::default()
+        }
+    }
+
+    /// 添加CV参数
+    pub fn add_cv_param(&mut self, param: CVParam) {
+        self.cv_params.push(param);
+    }
+
+    /// 添加用户参数
+    pub fn add_user_param(&mut self, param: UserParam) {
+        self.user_params.push(param);
+    }
+
+    /// 色谱图类型的CV名称，如"total ion current chromatogram"
+    pub fn chromatogram_type(&self) -> Option<&str> {
+        self.cv_params
+            .iter()
+            .find(|param| CHROMATOGRAM_TYPES.contains(&param.accession.as_str()))
+            .map(|param| param.name.as_str())
+    }
+
+    /// 是否为总离子流色谱图
+    pub fn is_tic(&self) -> bool {
+        self.cv_params.iter().any(|param| param.is_accession("MS:1000235"))
+    }
+
+    /// 是否为基峰色谱图
+    pub fn is_base_peak(&self) -> bool {
+        self.cv_params.iter().any(|param| param.is_accession("MS:1000628"))
+    }
+
+    /// 前体离子的分离窗口目标m/z
+    pub fn precursor_mz(&self) -> Option<f64> {
+        self.precursor
+            .as_ref()
+            .and_then(|precursor| precursor.isolation_windows.first())
+            .and_then(|window| window.get_isolation_window_target_mz())
+    }
+
+    /// 产物离子的分离窗口目标m/z
+    pub fn product_mz(&self) -> Option<f64> {
+        self.product.as_ref().and_then(|window| window.get_isolation_window_target_mz())
+    }
+
+    /// 换算为秒的时间数组；单位为minute时乘以60，其余按秒处理
+    pub fn times_in_seconds(&self) -> Vec<f64> {
+        match self.time_unit.as_deref() {
+            Some("minute") => self.time_array.iter().map(|time| time * 60.0).collect(),
+            _ => self.time_array.clone(),
+        }
+    }
+
+    /// 数据点数
+    pub fn len(&self) -> usize {
+        self.time_array.len()
+    }
+
+    /// 是否没有数据点
+    pub fn is_empty(&self) -> bool {
+        self.time_array.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chromatogram_type() {
+        let mut chromatogram = Chromatogram::new("TIC".to_string(), 0);
+        assert_eq!(chromatogram.chromatogram_type(), None);
+        chromatogram.add_cv_param(CVParam::new("MS:1000235", "total ion current chromatogram", ""));
+        assert_eq!(chromatogram.chromatogram_type(), Some("total ion current chromatogram"));
+        assert!(chromatogram.is_tic());
+        assert!(!chromatogram.is_base_peak());
+        assert!(chromatogram.is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+//! mzML文件逐谱图比较
+//!
+//! 用于验证写入器和转换器的输出：按native id配对两个文件中的谱图，
+//! 对每一对调用[`Spectrum::compare`]并汇总差异。
+//! 第一个文件的谱图保存在内存中用于配对，第二个文件流式读取。
+
+use crate::core::compare::SpectrumComparison;
+use crate::core::spectrum::Spectrum;
+use crate::parsers::common::ParseResult;
+use crate::parsers::mzml::parser::MZMLParser;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 报告中最多保留的单谱图差异条数
+pub const MAX_REPORTED_DIFFERENCES: usize = 100;
+
+/// 单个谱图的差异摘要
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectrumDiff {
+    /// 谱图的native id
+    pub id: String,
+    /// 比较结果
+    pub comparison: SpectrumComparison,
+}
+
+/// 两个文件的比较报告
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiffReport {
+    /// 成功配对并比较的谱图数
+    pub compared: usize,
+    /// 完全一致的谱图数
+    pub identical: usize,
+    /// 存在元数据差异的谱图数
+    pub metadata_differences: usize,
+    /// 存在峰差异的谱图数
+    pub peak_differences: usize,
+    /// 所有匹配峰中最大的m/z偏差 (ppm)
+    pub max_mz_deviation_ppm: f64,
+    /// 单谱图差异摘要（最多[`MAX_REPORTED_DIFFERENCES`]条）
+    pub differences: Vec<SpectrumDiff>,
+    /// 只出现在第一个文件中的native id
+    pub only_in_a: Vec<String>,
+    /// 只出现在第二个文件中的native id
+    pub only_in_b: Vec<String>,
+}
+
+impl DiffReport {
+    /// 两个文件的谱图完全一致
+    pub fn is_identical(&self) -> bool {
+        self.identical == self.compared && self.only_in_a.is_empty() && self.only_in_b.is_empty()
+    }
+}
+
+/// 比较两个mzML文件
+///
+/// 谱图按native id配对；缺少id的谱图按其在文件中的序号配对。
+pub fn diff_files(
+    path_a: impl AsRef<Path>,
+    path_b: impl AsRef<Path>,
+    mz_tolerance_ppm: f64,
+    intensity_rel_tol: f64,
+) -> ParseResult<DiffReport> {
+    let parser = MZMLParser::new();
+
+    let mut order_a = Vec::new();
+    let mut spectra_a: HashMap<String, Spectrum> = HashMap::new();
+    parser.for_each_spectrum(path_a, |id, spectrum| {
+        let key = pairing_key(id, order_a.len());
+        order_a.push(key.clone());
+        spectra_a.insert(key, spectrum);
+        Ok(())
+    })?;
+
+    let mut report = DiffReport::default();
+    let mut ordinal_b = 0;
+    parser.for_each_spectrum(path_b, |id, spectrum_b| {
+        let key = pairing_key(id, ordinal_b);
+        ordinal_b += 1;
+
+        let Some(spectrum_a) = spectra_a.remove(&key) else {
+            report.only_in_b.push(key);
+            return Ok(());
+        };
+
+        let comparison = spectrum_a.compare(&spectrum_b, mz_tolerance_ppm, intensity_rel_tol);
+        report.compared += 1;
+        report.max_mz_deviation_ppm = report.max_mz_deviation_ppm.max(comparison.max_mz_deviation_ppm);
+        if comparison.is_identical() {
+            report.identical += 1;
+            return Ok(());
+        }
+        if comparison.has_metadata_differences() {
+            report.metadata_differences += 1;
+        }
+        if comparison.has_peak_differences() {
+            report.peak_differences += 1;
+        }
+        if report.differences.len() < MAX_REPORTED_DIFFERENCES {
+            report.differences.push(SpectrumDiff { id: key, comparison });
+        }
+        Ok(())
+    })?;
+
+    report.only_in_a = order_a
+        .into_iter()
+        .filter(|key| spectra_a.contains_key(key))
+        .collect();
+
+    Ok(report)
+}
+
+/// 配对用的键：native id，缺失时使用序号
+fn pairing_key(id: &str, ordinal: usize) -> String {
+    if id.is_empty() {
+        format!("index={}", ordinal)
+    } else {
+        id.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::mzml::test_data::{build_mzml, TestSpectrum};
+    use crate::parsers::mzml::writer::MZMLWriter;
+    use quick_xml::events::Event;
+    use quick_xml::reader::Reader;
+
+    fn generated_run() -> Vec<TestSpectrum> {
+        (1..=5u32)
+            .map(|scan| {
+                let peaks = vec![(100.0 + scan as f64, 1000.0), (250.5, 10.0 * scan as f64)];
+                TestSpectrum::new(scan, 1, scan as f64, peaks)
+            })
+            .collect()
+    }
+
+    /// 通过MZMLWriter写出indexedmzML文件
+    fn write_with_writer(spectra: &[TestSpectrum]) -> tempfile::NamedTempFile {
+        let file = tempfile::Builder::new().suffix(".mzML").tempfile().unwrap();
+        let xml = build_mzml(spectra);
+        let mut reader = Reader::from_str(&xml);
+        let mut writer = MZMLWriter::create(file.path().to_str().unwrap()).unwrap();
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Eof => break,
+                Event::Start(e) if e.name().as_ref() == b"mzML" => {
+                    writer.start_indexed().unwrap();
+                    writer.write_event(Event::Start(e)).unwrap();
+                }
+                event => writer.write_event(event).unwrap(),
+            }
+        }
+        writer.finish().unwrap();
+        file
+    }
+
+    #[test]
+    fn test_diff_pinpoints_perturbed_intensity() {
+        let original = generated_run();
+        let mut perturbed = original.clone();
+        perturbed[2].peaks[1].1 *= 1.5;
+
+        let file_a = write_with_writer(&original);
+        let file_b = write_with_writer(&perturbed);
+        let report = diff_files(
+            file_a.path().to_str().unwrap(),
+            file_b.path().to_str().unwrap(),
+            5.0,
+            0.01,
+        ).unwrap();
+
+        assert_eq!(report.compared, 5);
+        assert_eq!(report.identical, 4);
+        assert_eq!(report.peak_differences, 1);
+        assert_eq!(report.metadata_differences, 0);
+        assert_eq!(report.differences.len(), 1);
+        assert_eq!(report.differences[0].id, "controllerType=0 controllerNumber=1 scan=3");
+        assert_eq!(report.differences[0].comparison.intensity_mismatches, 1);
+        assert!(!report.is_identical());
+    }
+
+    #[test]
+    fn test_diff_reports_unmatched_ids() {
+        let original = generated_run();
+        let file_a = write_with_writer(&original);
+        let file_b = write_with_writer(&original[1..]);
+
+        let report = diff_files(
+            file_a.path().to_str().unwrap(),
+            file_b.path().to_str().unwrap(),
+            5.0,
+            0.01,
+        ).unwrap();
+        assert_eq!(report.compared, 4);
+        assert_eq!(report.identical, 4);
+        assert_eq!(report.only_in_a, vec!["controllerType=0 controllerNumber=1 scan=1".to_string()]);
+        assert!(report.only_in_b.is_empty());
+
+        let same = diff_files(
+            file_a.path().to_str().unwrap(),
+            file_a.path().to_str().unwrap(),
+            5.0,
+            0.01,
+        ).unwrap();
+        assert!(same.is_identical());
+    }
+}
@@ -0,0 +1,66 @@
+//! 谱图头信息
+//!
+//! 只读取谱图元数据得到的精简记录，不解码二进制数组。用于快速统计谱图数，
+//! 或先按MS级别、保留时间、前体离子筛选，再按native id读取需要的谱图。
+
+use crate::core::types::{constants, MSLevel, RetentionTime, ScanNumber};
+use crate::parsers::common::ParseResult;
+use crate::parsers::mzml::spectrum::MZMLSpectrum;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 谱图头信息
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectrumHeader {
+    /// 谱图的native id
+    pub id: String,
+    /// 谱图在文件中的index属性
+    pub index: Option<usize>,
+    /// 扫描编号
+    pub scan_number: ScanNumber,
+    /// MS级别
+    pub ms_level: MSLevel,
+    /// 保留时间，未记录时为默认值
+    pub retention_time: RetentionTime,
+    /// 第一个前体离子的m/z（仅MS2+）
+    pub precursor_mz: Option<f64>,
+    /// 第一个前体离子的电荷（仅MS2+），未记录或为0时为None
+    pub charge: Option<i8>,
+    /// 峰数，取自defaultArrayLength
+    pub peak_count: usize,
+}
+
+impl SpectrumHeader {
+    /// 由只解析了元数据的谱图生成头信息
+    pub fn from_mzml(mzml_spectrum: &MZMLSpectrum) -> ParseResult<Self> {
+        let ms_level = mzml_spectrum.get_ms_level()?;
+        let precursor = if ms_level > 1 { mzml_spectrum.precursors.first() } else { None };
+
+        Ok(Self {
+            id: mzml_spectrum.id.clone(),
+            index: mzml_spectrum.index,
+            scan_number: mzml_spectrum.get_scan_number().unwrap_or(constants::DEFAULT_SCAN_NUMBER),
+            ms_level,
+            retention_time: mzml_spectrum.get_scan_start_time().unwrap_or(constants::DEFAULT_RETENTION_TIME),
+            precursor_mz: precursor.and_then(|p| p.get_precursor_mz()),
+            charge: precursor
+                .and_then(|p| p.get_precursor_charge())
+                .filter(|&charge| charge != 0)
+                .map(|charge| mzml_spectrum.get_polarity().signed_charge(charge)),
+            peak_count: mzml_spectrum.default_array_length,
+        })
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl SpectrumHeader {
+    fn __repr__(&self) -> String {
+        format!(
+            "SpectrumHeader(id='{}', ms_level={}, retention_time={}, peak_count={})",
+            self.id, self.ms_level, self.retention_time, self.peak_count
+        )
+    }
+}
@@ -0,0 +1,248 @@
+//! indexedmzML索引
+//!
+//! indexedmzML在文件末尾记录`<indexListOffset>`，指向`<indexList>`中按native id列出的
+//! 每个spectrum/chromatogram元素的字节偏移。读取索引后可以直接定位到单个谱图，
+//! 不必从头解析整个文件。索引缺失或损坏时[`MZMLIndex::read`]返回None，调用方回退到流式读取。
+
+use crate::parsers::common::{open_file, ParseError, ParseResult};
+use log::{debug, warn};
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::collections::HashMap;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str;
+
+/// 在文件末尾查找`<indexListOffset>`时读取的字节数
+const TAIL_SIZE: u64 = 4096;
+
+/// indexedmzML的偏移表
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MZMLIndex {
+    /// 按文件顺序排列的(谱图native id, 字节偏移)
+    spectra: Vec<(String, u64)>,
+    /// 谱图native id到其在`spectra`中位置的映射
+    spectrum_positions: HashMap<String, usize>,
+    /// 按文件顺序排列的(色谱图id, 字节偏移)
+    chromatograms: Vec<(String, u64)>,
+}
+
+impl MZMLIndex {
+    /// 读取文件末尾的索引，文件不是indexedmzML或索引损坏时返回None
+    ///
+    /// 只有无法打开文件时返回错误；损坏的索引记录一条警告后忽略。
+    pub fn read(filename: impl AsRef<Path>) -> ParseResult<Option<Self>> {
+        let filename = filename.as_ref();
+        let mut file = open_file(filename)?;
+        match Self::read_from(&mut file) {
+            Ok(Some(index)) => {
+                debug!("Read index of {} spectra from {}", index.len(), filename.display());
+                Ok(Some(index))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                warn!("Ignoring corrupt mzML index in {}: {}", filename.display(), e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// 从文件末尾定位并解析`<indexList>`，没有`<indexListOffset>`时返回None
+    fn read_from<R: Read + Seek>(file: &mut R) -> ParseResult<Option<Self>> {
+        let length = file.seek(SeekFrom::End(0))?;
+        let Some(index_list_offset) = Self::read_index_list_offset(file, length)? else {
+            return Ok(None);
+        };
+        if index_list_offset >= length {
+            return Err(ParseError::InvalidFormat(format!(
+                "indexListOffset {} is beyond the end of the file ({} bytes)", index_list_offset, length
+            )));
+        }
+
+        file.seek(SeekFrom::Start(index_list_offset))?;
+        let mut xml_reader = Reader::from_reader(BufReader::new(file.take(length - index_list_offset)));
+        xml_reader.config_mut().trim_text(true);
+        let index = Self::parse_index_list(&mut xml_reader)?;
+
+        if let Some((id, offset)) = index.spectra.iter().chain(&index.chromatograms).find(|(_, offset)| *offset >= index_list_offset) {
+            return Err(ParseError::InvalidFormat(format!(
+                "offset {} of '{}' is beyond the spectrum list", offset, id
+            )));
+        }
+        Ok(Some(index))
+    }
+
+    /// 读取文件末尾`<indexListOffset>`的值
+    fn read_index_list_offset<R: Read + Seek>(file: &mut R, length: u64) -> ParseResult<Option<u64>> {
+        let tail_start = length.saturating_sub(TAIL_SIZE);
+        file.seek(SeekFrom::Start(tail_start))?;
+        let mut tail = Vec::with_capacity((length - tail_start) as usize);
+        file.read_to_end(&mut tail)?;
+        let tail = String::from_utf8_lossy(&tail);
+
+        let Some(start) = tail.rfind("<indexListOffset>") else {
+            return Ok(None);
+        };
+        let value = &tail[start + "<indexListOffset>".len()..];
+        let value = value.find("</indexListOffset>").map(|end| value[..end].trim()).ok_or_else(|| {
+            ParseError::InvalidFormat("unterminated indexListOffset".to_string())
+        })?;
+        value
+            .parse()
+            .map(Some)
+            .map_err(|_| ParseError::InvalidFormat(format!("invalid indexListOffset '{}'", value)))
+    }
+
+    /// 解析`<indexList>`元素，读取器应位于该元素的开始标签之前
+    fn parse_index_list<R: std::io::BufRead>(xml_reader: &mut Reader<R>) -> ParseResult<Self> {
+        let mut buf = Vec::new();
+        let mut index = Self::default();
+        // 当前<index>的name属性
+        let mut current_list: Option<String> = None;
+        // 当前<offset>的idRef属性
+        let mut current_id: Option<String> = None;
+        let mut started = false;
+
+        loop {
+            match xml_reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) => match e.name().as_ref() {
+                    b"indexList" => started = true,
+                    _ if !started => {
+                        return Err(ParseError::InvalidFormat("indexListOffset does not point at <indexList>".to_string()));
+                    }
+                    b"index" => current_list = Self::attribute(e, b"name")?,
+                    b"offset" => current_id = Self::attribute(e, b"idRef")?,
+                    _ => {}
+                },
+                Ok(Event::Text(ref e)) => {
+                    if let Some(id) = current_id.take() {
+                        let text = str::from_utf8(e).unwrap_or("").trim();
+                        let offset: u64 = text
+                            .parse()
+                            .map_err(|_| ParseError::InvalidFormat(format!("invalid offset '{}' for '{}'", text, id)))?;
+                        match current_list.as_deref() {
+                            Some("spectrum") => {
+                                index.spectrum_positions.insert(id.clone(), index.spectra.len());
+                                index.spectra.push((id, offset));
+                            }
+                            Some("chromatogram") => index.chromatograms.push((id, offset)),
+                            _ => {}
+                        }
+                    }
+                }
+                Ok(Event::End(ref e)) => match e.name().as_ref() {
+                    b"indexList" => return Ok(index),
+                    b"index" => current_list = None,
+                    _ => {}
+                },
+                Ok(Event::Eof) if started => {
+                    return Err(ParseError::InvalidFormat("unterminated indexList".to_string()));
+                }
+                Ok(Event::Eof) => {
+                    return Err(ParseError::InvalidFormat("indexListOffset does not point at <indexList>".to_string()));
+                }
+                Err(e) => return Err(ParseError::xml(e)),
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+
+    fn attribute(event: &quick_xml::events::BytesStart, name: &[u8]) -> ParseResult<Option<String>> {
+        for attr in event.attributes() {
+            let attr = attr.map_err(ParseError::xml)?;
+            if attr.key.into_inner() == name {
+                return Ok(Some(str::from_utf8(&attr.value).unwrap_or("").to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 索引中的谱图数
+    pub fn len(&self) -> usize {
+        self.spectra.len()
+    }
+
+    /// 索引中是否没有谱图
+    pub fn is_empty(&self) -> bool {
+        self.spectra.is_empty()
+    }
+
+    /// 第`position`个谱图的native id和字节偏移
+    pub fn spectrum(&self, position: usize) -> Option<(&str, u64)> {
+        self.spectra.get(position).map(|(id, offset)| (id.as_str(), *offset))
+    }
+
+    /// 按native id查找谱图的位置
+    pub fn position_of(&self, native_id: &str) -> Option<usize> {
+        self.spectrum_positions.get(native_id).copied()
+    }
+
+    /// 按native id查找谱图的字节偏移
+    pub fn offset_of(&self, native_id: &str) -> Option<u64> {
+        self.position_of(native_id).map(|position| self.spectra[position].1)
+    }
+
+    /// 按文件顺序排列的谱图native id
+    pub fn spectrum_ids(&self) -> impl Iterator<Item = &str> {
+        self.spectra.iter().map(|(id, _)| id.as_str())
+    }
+
+    /// 按文件顺序排列的(色谱图id, 字节偏移)
+    pub fn chromatograms(&self) -> &[(String, u64)] {
+        &self.chromatograms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::mzml::test_data::{build_mzml, write_temp_file, TestSpectrum};
+    use crate::parsers::mzml::writer::write_spectra;
+    use crate::parsers::mzml::MZMLParser;
+
+    fn indexed_file() -> tempfile::NamedTempFile {
+        let spectra: Vec<TestSpectrum> = (1..=4u32)
+            .map(|scan| TestSpectrum::new(scan, 1, scan as f64, vec![(100.0 * scan as f64, 10.0)]))
+            .collect();
+        let plain = write_temp_file(&build_mzml(&spectra));
+        let parsed = MZMLParser::new().parse_sequential(plain.path()).unwrap();
+
+        let file = tempfile::Builder::new().suffix(".mzML").tempfile().unwrap();
+        write_spectra(file.path(), &parsed).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_read_index_offsets() {
+        let file = indexed_file();
+        let index = MZMLIndex::read(file.path()).unwrap().unwrap();
+        assert_eq!(index.len(), 4);
+
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        let id = "controllerType=0 controllerNumber=1 scan=3";
+        assert_eq!(index.position_of(id), Some(2));
+        let (position_id, offset) = index.spectrum(2).unwrap();
+        assert_eq!(position_id, id);
+        assert_eq!(index.offset_of(id), Some(offset));
+        assert!(content[offset as usize..].starts_with("<spectrum "));
+        assert!(content[offset as usize..].contains(id));
+        assert_eq!(index.spectrum_ids().count(), 4);
+        assert!(index.spectrum(4).is_none());
+    }
+
+    #[test]
+    fn test_missing_or_corrupt_index_is_none() {
+        let plain = write_temp_file(&build_mzml(&[TestSpectrum::new(1, 1, 1.0, vec![(100.0, 1.0)])]));
+        assert_eq!(MZMLIndex::read(plain.path()).unwrap(), None);
+
+        let content = std::fs::read_to_string(indexed_file().path()).unwrap();
+        let start = content.find("<indexListOffset>").unwrap() + "<indexListOffset>".len();
+        let end = content.find("</indexListOffset>").unwrap();
+        for bad_offset in ["12", "not a number", "99999999"] {
+            let corrupted = format!("{}{}{}", &content[..start], bad_offset, &content[end..]);
+            let file = write_temp_file(&corrupted);
+            assert_eq!(MZMLIndex::read(file.path()).unwrap(), None, "{}", bad_offset);
+        }
+    }
+}
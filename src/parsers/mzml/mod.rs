@@ -4,13 +4,37 @@
 //! - MZMLReader：Python兼容的mzML读取器
 //! - MZMLParser：核心解析逻辑
 //! - MZMLSpectrum：mzML特定的谱图数据结构
+//! - Chromatogram：mzML中的色谱图（TIC、基峰色谱图等）
+//! - SpectrumHeader：不解码峰数据的谱图头信息
+//! - MZMLIndex：indexedmzML的偏移索引，用于随机访问单个谱图
+//! - RunSummary：只读取元数据得到的运行概要
+//! - scan_number_from_native_id：从谱图native id中提取扫描号
 
 pub mod reader;
 pub mod parser;
 pub mod spectrum;
+pub mod chromatogram;
+pub mod writer;
+pub mod subset;
+pub mod diff;
+pub mod index;
+pub mod header;
+pub mod native_id;
+pub mod summary;
+
+#[cfg(test)]
+pub(crate) mod test_data;
 
 // 重新导出主要类型
 #[cfg(feature = "python")]
-pub use reader::{MZMLReader};
-pub use parser::{MZMLParser};
+pub use reader::{MZMLReader, MZMLObject, MZMLFileInfo, MZMLSpectrumIterator, MZMLChromatogram};
+pub use parser::{MZMLParser, ParseProgress, ParsedSpectra, SpectrumIter, FileSpectrumIter};
 pub use spectrum::{MZMLSpectrum, MZMLScanList, MZMLBinaryDataArray};
+pub use chromatogram::Chromatogram;
+pub use writer::{write_spectra, MZMLWriter};
+pub use subset::{extract_subset, SubsetSummary};
+pub use diff::{diff_files, DiffReport, SpectrumDiff};
+pub use index::MZMLIndex;
+pub use header::SpectrumHeader;
+pub use native_id::scan_number_from_native_id;
+pub use summary::RunSummary;
@@ -0,0 +1,59 @@
+//! mzML谱图native id解析
+//!
+//! 大多数mzML文件的`<scan>`没有scanNumber属性，扫描号只出现在谱图id（native id）中。
+//! 这里按常见的native id格式提取扫描号，也用于解析前体离子的spectrumRef。
+
+use crate::core::types::ScanNumber;
+
+/// 从native id中提取扫描号
+///
+/// 支持的格式（按优先级）：
+/// - Thermo：`controllerType=0 controllerNumber=1 scan=2345`，以及Waters的
+///   `function=2 process=0 scan=345`、Bruker/Agilent的`scan=345`，取`scan=`
+/// - `scanId=345`，取`scanId=`
+/// - AB Sciex：`sample=1 period=1 cycle=123 experiment=2`，取`cycle=`；
+///   同一循环内的不同experiment共用一个扫描号
+/// - 峰列表：`index=0`，下标从0开始，扫描号为下标加1（与msconvert写mzXML时一致）
+/// - 只有数字的id：`2345`
+///
+/// 无法识别时返回None。
+pub fn scan_number_from_native_id(id: &str) -> Option<ScanNumber> {
+    let id = id.trim();
+    native_id_value(id, "scan")
+        .or_else(|| native_id_value(id, "scanId"))
+        .or_else(|| native_id_value(id, "cycle"))
+        .or_else(|| native_id_value(id, "index").and_then(|index| index.checked_add(1)))
+        .or_else(|| id.parse().ok())
+}
+
+/// 取native id中`key=value`项的整数值，各项以空白分隔
+fn native_id_value(id: &str, key: &str) -> Option<ScanNumber> {
+    id.split_whitespace()
+        .filter_map(|term| term.split_once('='))
+        .find(|(name, _)| *name == key)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vendor_native_ids() {
+        assert_eq!(scan_number_from_native_id("controllerType=0 controllerNumber=1 scan=2345"), Some(2345));
+        assert_eq!(scan_number_from_native_id("function=2 process=0 scan=345"), Some(345));
+        assert_eq!(scan_number_from_native_id("sample=1 period=1 cycle=123 experiment=2"), Some(123));
+        assert_eq!(scan_number_from_native_id("scanId=77"), Some(77));
+        assert_eq!(scan_number_from_native_id("index=0"), Some(1));
+        assert_eq!(scan_number_from_native_id(" 42 "), Some(42));
+    }
+
+    #[test]
+    fn test_unrecognized_native_ids() {
+        assert_eq!(scan_number_from_native_id(""), None);
+        assert_eq!(scan_number_from_native_id("file=run01.raw"), None);
+        assert_eq!(scan_number_from_native_id("controllerType=0 controllerNumber=1 scan=abc"), None);
+        // 键必须完全匹配
+        assert_eq!(scan_number_from_native_id("prescan=12"), None);
+    }
+}
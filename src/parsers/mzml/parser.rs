@@ -2,22 +2,204 @@
 //! 
 //! 这个模块提供了mzML文件的核心解析逻辑，包括XML解析和二进制数据处理
 
+use crate::core::centroid::{centroid_spectrum, DEFAULT_CENTROID_WINDOW, SPECTRUM_TYPE_KEY};
 use crate::core::spectrum::{Spectrum, PrecursorInfo, ScanInfo};
-use crate::core::types::*;
-use crate::parsers::common::{ParseResult, ParseError, CVParam, UserParam, BinaryDataArray, BinaryDataEncoding, CompressionType};
+use crate::core::scan_table::{ScanRow, ScanTable};
+use crate::core::types::{constants, KeyValue};
+use crate::parsers::common::{open_file, ParseResult, ParseError, ParseOptions, SkippedSpectrum, SpectrumErrorPolicy, NegativeIntensityPolicy, RtUnit, CVParam, UserParam, BinaryDataArray, BinaryDataEncoding, CompressionType};
+use crate::parsers::mzml::chromatogram::Chromatogram;
+use crate::parsers::mzml::header::SpectrumHeader;
+use crate::parsers::mzml::index::MZMLIndex;
+use crate::parsers::mzml::native_id::scan_number_from_native_id;
+use crate::parsers::mzml::summary::RunSummary;
 use crate::parsers::mzml::spectrum::{MZMLSpectrum, MZMLScan, MZMLPrecursor, MZMLIsolationWindow, MZMLActivation, MZMLBinaryDataArray, MZMLScanList};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{debug, info, warn};
 use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::QName;
 use quick_xml::reader::Reader;
-use std::io::BufRead;
+use rayon::prelude::*;
+use std::io::{BufRead, Read, Seek, SeekFrom};
 use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// referenceableParamGroup ID到其CV参数的映射
+type ParamGroups = HashMap<String, Vec<CVParam>>;
+
+/// 读到的一个谱图：位置及解析结果，谱图内部的可恢复错误放在结果中
+type SpectrumRead = (SpectrumLocation, ParseResult<MZMLSpectrum>);
+
+/// 并行解析中一个谱图的位置及转换结果
+type SpectrumConversion = (SpectrumLocation, ParseResult<Spectrum>);
+
+/// 谱图在文件中的位置：原生id、index属性和`<spectrum>`开始标签的字节偏移
+#[derive(Debug, Clone, Default)]
+struct SpectrumLocation {
+    id: String,
+    index: Option<usize>,
+    byte_offset: u64,
+    /// `</spectrum>`结束标签之后的字节偏移
+    end_offset: u64,
+}
+
+impl SpectrumLocation {
+    /// 给谱图内部的错误加上谱图位置
+    fn wrap(&self, error: ParseError) -> ParseError {
+        ParseError::InSpectrum {
+            spectrum_id: self.id.clone(),
+            index: self.index,
+            byte_offset: self.byte_offset,
+            source: Box::new(error),
+        }
+    }
+
+    /// 按错误策略跳过该谱图时的记录
+    fn skipped(&self, error: &ParseError) -> SkippedSpectrum {
+        SkippedSpectrum {
+            spectrum_id: self.id.clone(),
+            index: self.index,
+            byte_offset: self.byte_offset,
+            error: error.to_string(),
+        }
+    }
+}
+
+/// 并行解析时每个线程分到的谱图块数
+const PARALLEL_CHUNKS_PER_THREAD: usize = 4;
+
+/// 解析进度，由[`MZMLParser::parse_with_progress`]报告
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseProgress {
+    /// 已解析的谱图数（不含按错误策略跳过的谱图）
+    pub spectra_parsed: usize,
+    /// 已读取的字节数
+    pub bytes_read: u64,
+    /// 文件总字节数
+    pub total_bytes: u64,
+}
+
+/// 可被进度回调取消的解析结果
+#[derive(Debug, Clone, Default)]
+pub struct ParsedSpectra {
+    /// 已解析的谱图，取消时为取消前解析的部分
+    pub spectra: Vec<Spectrum>,
+    /// 解析是否被进度回调取消
+    pub cancelled: bool,
+}
+
+/// 文件中记录的扫描开始时间单位在扫描额外信息中的键
+pub const RT_UNIT_KEY: &str = "scan_start_time_unit";
+
+/// FAIMS补偿电压（伏特）在额外信息中的键
+pub const FAIMS_CV_KEY: &str = "faims_compensation_voltage";
+
+/// 前体离子有多个分离窗口时，在额外信息中记录窗口数的键（只使用第一个窗口）
+pub const ISOLATION_WINDOW_COUNT_KEY: &str = "isolation_window_count";
+
+/// 逐个读取`<spectrum>`元素的游标，保存XML读取器和已读到的referenceableParamGroup
+struct SpectrumCursor<B> {
+    xml_reader: Reader<B>,
+    buf: Vec<u8>,
+    param_groups: ParamGroups,
+    /// 读取器起点在文件中的字节偏移，用于给出谱图和错误在文件中的位置
+    base_offset: u64,
+}
+
+impl<B> SpectrumCursor<B> {
+    fn new(xml_reader: Reader<B>) -> Self {
+        Self::at_offset(xml_reader, 0)
+    }
+
+    /// 读取器从文件第`base_offset`字节开始读取
+    fn at_offset(xml_reader: Reader<B>, base_offset: u64) -> Self {
+        Self {
+            xml_reader,
+            buf: Vec::new(),
+            param_groups: ParamGroups::new(),
+            base_offset,
+        }
+    }
+}
+
+/// 逐个产出谱图的迭代器，由[`MZMLParser::iter_spectra`]创建
+///
+/// 每次只在内存中保留一个谱图，内存占用与文件大小无关。单个谱图的错误（base64解码、
+/// zlib解压、无效参数等）作为该项的`Err`（[`ParseError::InSpectrum`]）产出，之后继续读取
+/// 下一个谱图；错误策略为[`SpectrumErrorPolicy::Skip`]时这些谱图被跳过并记录到
+/// [`MZMLParser::skipped_spectra`]。XML和IO错误使文件无法继续读取，产出`Err`后迭代结束。
+pub struct SpectrumIter<B> {
+    parser: MZMLParser,
+    cursor: SpectrumCursor<B>,
+    /// 最近读到的谱图的原生id，XML或IO错误后为空
+    last_id: String,
+    finished: bool,
+}
+
+/// 从mzML文件读取谱图的迭代器
+pub type FileSpectrumIter = SpectrumIter<std::io::BufReader<std::fs::File>>;
+
+impl<B> SpectrumIter<B> {
+    /// 最近产出（或出错）的谱图的原生id，XML或IO错误后为空
+    pub fn last_id(&self) -> &str {
+        &self.last_id
+    }
+}
+
+impl<B: BufRead> Iterator for SpectrumIter<B> {
+    type Item = ParseResult<Spectrum>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let (location, result) = match self.parser.next_spectrum(&mut self.cursor, true) {
+                Ok(Some(read)) => read,
+                Ok(None) => {
+                    self.finished = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.finished = true;
+                    self.last_id.clear();
+                    return Some(Err(e));
+                }
+            };
+            self.last_id.clone_from(&location.id);
+
+            match result.and_then(|mzml_spectrum| self.parser.convert_mzml_to_spectrum(mzml_spectrum)) {
+                Ok(spectrum) => return Some(Ok(spectrum)),
+                Err(e) => {
+                    if let Err(e) = self.parser.handle_spectrum_error(&location, e) {
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
 
 /// MZML解析器
+#[derive(Debug, Clone)]
 pub struct MZMLParser {
     /// 是否启用并行处理
     parallel: bool,
     /// 线程数
     num_threads: usize,
+    /// 解析选项
+    options: ParseOptions,
+    /// 最近一次解析中扫描开始时间未记录单位的谱图数
+    missing_rt_units: Arc<AtomicUsize>,
+    /// 最近一次解析中按错误策略跳过的谱图
+    skipped: Arc<Mutex<Vec<SkippedSpectrum>>>,
+}
+
+impl Default for MZMLParser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MZMLParser {
@@ -26,129 +208,1199 @@ impl MZMLParser {
         Self {
             parallel: false,
             num_threads: 1,
+            options: ParseOptions::default(),
+            missing_rt_units: Arc::default(),
+            skipped: Arc::default(),
         }
     }
 
-    /// 创建并行MZML解析器
-    pub fn new_parallel(num_threads: usize) -> Self {
-        Self {
-            parallel: true,
-            num_threads,
+    /// 创建并行MZML解析器
+    pub fn new_parallel(num_threads: usize) -> Self {
+        Self {
+            parallel: true,
+            num_threads,
+            options: ParseOptions::default(),
+            missing_rt_units: Arc::default(),
+            skipped: Arc::default(),
+        }
+    }
+
+    /// 设置解析选项
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// 获取解析选项
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// 最近一次完整解析中扫描开始时间未记录单位、按秒读取的谱图数
+    ///
+    /// 只在[`RtUnit::Auto`]下统计；非0时保留时间可能实际以分钟记录，
+    /// 可用[`ParseOptions::with_rt_units`]指定单位后重新解析。
+    pub fn missing_rt_unit_count(&self) -> usize {
+        self.missing_rt_units.load(Ordering::Relaxed)
+    }
+
+    /// 最近一次完整解析中按错误策略（[`ParseOptions::with_lenient`]）跳过的谱图
+    ///
+    /// 每条记录给出谱图的原生id、index属性、`<spectrum>`元素的字节偏移和错误信息。
+    pub fn skipped_spectra(&self) -> Vec<SkippedSpectrum> {
+        self.skipped.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// 开始一次完整解析前清零保留时间单位计数和跳过的谱图记录
+    fn reset_parse_stats(&self) {
+        self.missing_rt_units.store(0, Ordering::Relaxed);
+        self.skipped.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    /// 完整解析结束后汇总报告未记录单位的保留时间
+    fn report_missing_rt_units(&self, filename: &Path) {
+        let missing = self.missing_rt_unit_count();
+        if missing > 0 {
+            warn!(
+                "{} spectra in {} have no scan start time unit and were read as seconds",
+                missing, filename.display()
+            );
+        }
+    }
+
+    /// 按解析器配置（顺序或并行）解析MZML文件
+    pub fn parse(&self, filename: impl AsRef<Path>) -> ParseResult<Vec<Spectrum>> {
+        if self.parallel {
+            self.parse_parallel(filename, self.num_threads)
+        } else {
+            self.parse_sequential(filename)
+        }
+    }
+
+    /// 顺序解析MZML文件
+    pub fn parse_sequential(&self, filename: impl AsRef<Path>) -> ParseResult<Vec<Spectrum>> {
+        let mut spectra = Vec::new();
+        self.for_each_spectrum(filename, |_, spectrum| {
+            spectra.push(spectrum);
+            Ok(())
+        })?;
+        Ok(spectra)
+    }
+
+    /// 返回逐个读取谱图的迭代器，不在内存中保留已产出的谱图
+    pub fn iter_spectra(&self, filename: impl AsRef<Path>) -> ParseResult<FileSpectrumIter> {
+        let filename = filename.as_ref();
+        info!("Iterating spectra in mzML file {}", filename.display());
+        Ok(SpectrumIter {
+            parser: self.clone(),
+            cursor: SpectrumCursor::new(Self::open_reader(filename)?),
+            last_id: String::new(),
+            finished: false,
+        })
+    }
+
+    /// 流式解析，每解析完一个谱图调用一次`on_spectrum(native_id, spectrum)`
+    ///
+    /// 不在内存中保留已处理的谱图，适合逐个比较或写出的场景。
+    pub fn for_each_spectrum<F>(&self, filename: impl AsRef<Path>, mut on_spectrum: F) -> ParseResult<()>
+    where
+        F: FnMut(&str, Spectrum) -> ParseResult<()>,
+    {
+        let filename = filename.as_ref();
+        info!("Parsing mzML file {}", filename.display());
+        let xml_reader = Self::open_reader(filename)?;
+        let mut parsed = 0;
+        let mut conversion_skipped = 0;
+        self.reset_parse_stats();
+
+        let read_skipped = self.read_spectra(xml_reader, true, |location, mzml_spectrum| {
+            match self.convert_mzml_to_spectrum(mzml_spectrum) {
+                Ok(spectrum) => {
+                    parsed += 1;
+                    on_spectrum(&location.id, spectrum)?;
+                }
+                Err(e) => {
+                    self.handle_spectrum_error(location, e)?;
+                    conversion_skipped += 1;
+                }
+            }
+            Ok(())
+        })?;
+
+        info!(
+            "Parsed {} spectra from {} ({} skipped)",
+            parsed, filename.display(), read_skipped + conversion_skipped
+        );
+        self.report_missing_rt_units(filename);
+        Ok(())
+    }
+
+    /// 顺序解析文件并报告进度
+    ///
+    /// 每读完`every`个谱图（含跳过的谱图）以当前进度调用一次`on_progress`，读完文件后再以
+    /// `bytes_read == total_bytes`调用一次；文件总字节数取自文件元数据。`on_progress`返回false时
+    /// 停止解析，返回已解析的谱图并标记`cancelled`。
+    pub fn parse_with_progress<F>(
+        &self,
+        filename: impl AsRef<Path>,
+        every: usize,
+        mut on_progress: F,
+    ) -> ParseResult<ParsedSpectra>
+    where
+        F: FnMut(ParseProgress) -> bool,
+    {
+        let filename = filename.as_ref();
+        info!("Parsing mzML file {} with progress reporting", filename.display());
+        let total_bytes = open_file(filename)?.metadata()?.len();
+        let xml_reader = Self::open_reader(filename)?;
+        let every = every.max(1);
+        let mut parsed = ParsedSpectra::default();
+        let mut read = 0usize;
+        self.reset_parse_stats();
+
+        let result = self.read_spectra(xml_reader, true, |location, mzml_spectrum| {
+            match self.convert_mzml_to_spectrum(mzml_spectrum) {
+                Ok(spectrum) => parsed.spectra.push(spectrum),
+                Err(e) => self.handle_spectrum_error(location, e)?,
+            }
+            read += 1;
+            if read.is_multiple_of(every) {
+                let progress = ParseProgress {
+                    spectra_parsed: parsed.spectra.len(),
+                    bytes_read: location.end_offset,
+                    total_bytes,
+                };
+                if !on_progress(progress) {
+                    parsed.cancelled = true;
+                    return Err(ParseError::Interrupted(format!("cancelled after {} spectra", read)));
+                }
+            }
+            Ok(())
+        });
+        match result {
+            Err(ParseError::Interrupted(_)) if parsed.cancelled => {
+                info!("Parsing of {} cancelled after {} spectra", filename.display(), parsed.spectra.len());
+            }
+            result => {
+                let skipped = result?;
+                on_progress(ParseProgress { spectra_parsed: parsed.spectra.len(), bytes_read: total_bytes, total_bytes });
+                info!("Parsed {} spectra from {} ({} skipped)", parsed.spectra.len(), filename.display(), skipped);
+            }
+        }
+        self.report_missing_rt_units(filename);
+        Ok(parsed)
+    }
+
+    /// 只解析谱图的元数据（CV参数、扫描、前体离子），跳过二进制数组的解码
+    ///
+    /// 返回的MZMLSpectrum中binaryDataArray只保留CV参数，`binary`为None。
+    pub fn parse_headers(&self, filename: impl AsRef<Path>) -> ParseResult<Vec<MZMLSpectrum>> {
+        let filename = filename.as_ref();
+        info!("Reading spectrum headers from mzML file {}", filename.display());
+        let xml_reader = Self::open_reader(filename)?;
+        let mut spectra = Vec::new();
+
+        let skipped = self.read_spectra(xml_reader, false, |_, mzml_spectrum| {
+            spectra.push(mzml_spectrum);
+            Ok(())
+        })?;
+
+        info!("Read {} spectrum headers from {} ({} skipped)", spectra.len(), filename.display(), skipped);
+        Ok(spectra)
+    }
+
+    /// 只读取谱图元数据生成头信息列表，跳过二进制数组的解码
+    ///
+    /// 无法生成头信息的谱图（如缺少MS级别）按错误策略处理。
+    pub fn spectrum_headers(&self, filename: impl AsRef<Path>) -> ParseResult<Vec<SpectrumHeader>> {
+        let filename = filename.as_ref();
+        info!("Reading spectrum headers from mzML file {}", filename.display());
+        let xml_reader = Self::open_reader(filename)?;
+        let mut headers = Vec::new();
+
+        let skipped = self.read_spectra(xml_reader, false, |location, mzml_spectrum| {
+            match SpectrumHeader::from_mzml(&mzml_spectrum) {
+                Ok(header) => headers.push(header),
+                Err(e) => self.handle_spectrum_error(location, e)?,
+            }
+            Ok(())
+        })?;
+
+        info!("Read {} spectrum headers from {} ({} skipped)", headers.len(), filename.display(), skipped);
+        Ok(headers)
+    }
+
+    /// 读取根元素`<mzML>`的version属性，读到该元素即停止，不解析谱图
+    pub fn read_version(&self, filename: impl AsRef<Path>) -> ParseResult<Option<String>> {
+        let mut xml_reader = Self::open_reader(filename.as_ref())?;
+        let mut buf = Vec::new();
+        loop {
+            match xml_reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e) | Event::Empty(ref e)) if e.name().as_ref() == b"mzML" => {
+                    return Self::attribute_value(e, "version");
+                }
+                Ok(Event::Eof) => return Ok(None),
+                Err(e) => return Err(ParseError::xml(e)),
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+
+    /// 只读取元数据生成运行概要，不解码任何二进制数组
+    ///
+    /// 版本、仪器型号和indexedmzML外层元素取自`<run>`之前的部分，之后逐个读取谱图的元数据；
+    /// 无法统计的谱图（如缺少MS级别）按错误策略处理。
+    pub fn run_summary(&self, filename: impl AsRef<Path>) -> ParseResult<RunSummary> {
+        let filename = filename.as_ref();
+        info!("Reading run summary from mzML file {}", filename.display());
+        let mut xml_reader = Self::open_reader(filename)?;
+        let mut summary = RunSummary::default();
+        let mut param_groups = ParamGroups::new();
+        let mut buf = Vec::new();
+
+        loop {
+            match xml_reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) => match e.name().as_ref() {
+                    b"indexedmzML" => summary.indexed = true,
+                    b"mzML" => summary.version = Self::attribute_value(e, "version")?,
+                    b"referenceableParamGroupList" => param_groups = self.parse_param_group_list(&mut xml_reader)?,
+                    b"instrumentConfiguration" => {
+                        let model = self.parse_instrument_model(&mut xml_reader, &param_groups)?;
+                        if summary.instrument_model.is_none() {
+                            summary.instrument_model = model;
+                        }
+                    }
+                    b"run" => break,
+                    _ => {}
+                },
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(ParseError::xml(e)),
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        let mut cursor = SpectrumCursor::new(xml_reader);
+        cursor.param_groups = param_groups;
+        let skipped = self.read_cursor_spectra(cursor, false, |location, mzml_spectrum| {
+            if let Err(e) = summary.add_spectrum(&mzml_spectrum) {
+                self.handle_spectrum_error(location, e)?;
+            }
+            Ok(())
+        })?;
+
+        info!("Summarized {} spectra from {} ({} skipped)", summary.spectrum_count, filename.display(), skipped);
+        Ok(summary)
+    }
+
+    /// 读取`<instrumentConfiguration>`中的仪器型号，读取器停在该元素结束之后
+    ///
+    /// 取直接子元素（含referenceableParamGroupRef引用的组）中第一个不是仪器序列号的cvParam，
+    /// componentList等嵌套元素中的参数不算型号。
+    fn parse_instrument_model<B: BufRead>(
+        &self,
+        reader: &mut Reader<B>,
+        param_groups: &ParamGroups,
+    ) -> ParseResult<Option<String>> {
+        let mut model = None;
+        // 当前位于instrumentConfiguration内部的嵌套深度，0表示直接子元素
+        let mut depth = 0usize;
+        let mut buf = Vec::new();
+
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) => {
+                    if depth == 0 && model.is_none() {
+                        model = self.instrument_model_param(e, param_groups)?;
+                    }
+                    depth += 1;
+                }
+                Ok(Event::Empty(ref e)) if depth == 0 && model.is_none() => {
+                    model = self.instrument_model_param(e, param_groups)?;
+                }
+                Ok(Event::End(ref e)) => {
+                    if depth == 0 && e.name().as_ref() == b"instrumentConfiguration" {
+                        break;
+                    }
+                    depth = depth.saturating_sub(1);
+                }
+                Ok(Event::Eof) => {
+                    return Err(ParseError::InvalidFormat(
+                        "Unexpected end of file in instrumentConfiguration".to_string()
+                    ));
+                }
+                Err(e) => return Err(ParseError::xml(e)),
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(model)
+    }
+
+    /// instrumentConfiguration直接子元素给出的仪器型号
+    fn instrument_model_param(&self, event: &BytesStart, param_groups: &ParamGroups) -> ParseResult<Option<String>> {
+        let params = match event.name().as_ref() {
+            b"cvParam" => vec![self.parse_cv_param(event)?],
+            b"referenceableParamGroupRef" => self.resolve_param_group_ref(event, param_groups)?,
+            _ => return Ok(None),
+        };
+        Ok(params
+            .into_iter()
+            .find(|param| !param.is_accession("MS:1000529"))
+            .map(|param| param.name))
+    }
+
+    /// 读取文件中第`position`个谱图，超出范围时返回None
+    ///
+    /// 文件带有indexedmzML索引时直接定位到该谱图，否则（或索引与文件内容不符时）流式读取。
+    pub fn read_spectrum(&self, filename: impl AsRef<Path>, position: usize) -> ParseResult<Option<Spectrum>> {
+        let filename = filename.as_ref();
+        if let Some(index) = MZMLIndex::read(filename)? {
+            let Some((native_id, offset)) = index.spectrum(position) else {
+                return Ok(None);
+            };
+            if let Some((location, result)) = self.read_spectrum_at(filename, offset, native_id) {
+                return result
+                    .and_then(|mzml_spectrum| self.convert_mzml_to_spectrum(mzml_spectrum))
+                    .map(Some)
+                    .map_err(|e| location.wrap(e));
+            }
+        }
+        self.iter_spectra(filename)?.nth(position).transpose()
+    }
+
+    /// 按native id读取单个谱图，文件中没有该谱图时返回None
+    ///
+    /// 文件带有indexedmzML索引时直接定位到该谱图，否则（或索引与文件内容不符时）流式读取。
+    pub fn read_spectrum_by_id(&self, filename: impl AsRef<Path>, native_id: &str) -> ParseResult<Option<Spectrum>> {
+        let filename = filename.as_ref();
+        if let Some(index) = MZMLIndex::read(filename)? {
+            let Some(offset) = index.offset_of(native_id) else {
+                return Ok(None);
+            };
+            if let Some((location, result)) = self.read_spectrum_at(filename, offset, native_id) {
+                return result
+                    .and_then(|mzml_spectrum| self.convert_mzml_to_spectrum(mzml_spectrum))
+                    .map(Some)
+                    .map_err(|e| location.wrap(e));
+            }
+        }
+        for spectrum in self.iter_spectra(filename)? {
+            let spectrum = spectrum?;
+            if spectrum.scan.native_id == native_id {
+                return Ok(Some(spectrum));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 文件中的谱图数，有indexedmzML索引时直接取自索引，否则扫描文件（不解码谱图内容）
+    pub fn spectrum_count(&self, filename: impl AsRef<Path>) -> ParseResult<usize> {
+        let filename = filename.as_ref();
+        match MZMLIndex::read(filename)? {
+            Some(index) => Ok(index.len()),
+            None => Ok(self.locate_spectra(filename)?.1.len()),
+        }
+    }
+
+    /// 从索引给出的字节偏移处读取一个谱图
+    ///
+    /// 偏移处读不到id为`native_id`的谱图（XML错误、id不符）时记录警告并返回None，
+    /// 由调用方回退到流式读取；谱图自身的解析错误与谱图位置一起放在返回的结果中。
+    fn read_spectrum_at(&self, filename: &Path, offset: u64, native_id: &str) -> Option<SpectrumRead> {
+        let read = || -> ParseResult<Option<SpectrumRead>> {
+            let param_groups = self.read_param_groups(filename)?;
+            let mut file = open_file(filename)?;
+            file.seek(SeekFrom::Start(offset))?;
+            let mut xml_reader = Reader::from_reader(std::io::BufReader::new(file));
+            xml_reader.config_mut().trim_text(true);
+            let mut cursor = SpectrumCursor::at_offset(xml_reader, offset);
+            cursor.param_groups = param_groups;
+            self.next_spectrum(&mut cursor, true)
+        };
+
+        match read() {
+            Ok(Some((location, result))) if location.id == native_id => Some((location, result)),
+            Ok(Some((location, _))) => {
+                warn!(
+                    "mzML index points at spectrum '{}' instead of '{}', reading sequentially",
+                    location.id, native_id
+                );
+                None
+            }
+            Ok(None) => {
+                warn!("No spectrum at indexed offset {} of '{}', reading sequentially", offset, native_id);
+                None
+            }
+            Err(e) => {
+                warn!("Failed to read spectrum '{}' at indexed offset {} ({}), reading sequentially", native_id, offset, e);
+                None
+            }
+        }
+    }
+
+    /// 读取`<run>`之前的referenceableParamGroup
+    fn read_param_groups(&self, filename: &Path) -> ParseResult<ParamGroups> {
+        let mut xml_reader = Self::open_reader(filename)?;
+        let mut buf = Vec::new();
+        loop {
+            match xml_reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) => match e.name().as_ref() {
+                    b"referenceableParamGroupList" => return self.parse_param_group_list(&mut xml_reader),
+                    b"run" => return Ok(ParamGroups::new()),
+                    _ => {}
+                },
+                Ok(Event::Eof) => return Ok(ParamGroups::new()),
+                Err(e) => return Err(ParseError::xml(e)),
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+
+    /// 只读取谱图头信息生成扫描表（不解码峰数据）
+    ///
+    /// TIC和基峰取自谱图的cvParam，文件中未记录时为None；峰数取自defaultArrayLength。
+    pub fn scan_table(&self, filename: impl AsRef<Path>) -> ParseResult<ScanTable> {
+        let filename = filename.as_ref();
+        info!("Reading scan table from mzML file {}", filename.display());
+        let xml_reader = Self::open_reader(filename)?;
+        let mut table = ScanTable::new();
+
+        let skipped = self.read_spectra(xml_reader, false, |location, mzml_spectrum| {
+            match Self::header_scan_row(&mzml_spectrum) {
+                Ok(row) => table.push(row),
+                Err(e) => self.handle_spectrum_error(location, e)?,
+            }
+            Ok(())
+        })?;
+
+        info!("Read {} scan table rows from {} ({} skipped)", table.len(), filename.display(), skipped);
+        Ok(table)
+    }
+
+    /// 解析文件中的所有色谱图（TIC、基峰色谱图、SRM跃迁等），跳过谱图
+    ///
+    /// 单个色谱图的解析错误按错误策略处理。
+    pub fn parse_chromatograms(&self, filename: impl AsRef<Path>) -> ParseResult<Vec<Chromatogram>> {
+        let filename = filename.as_ref();
+        info!("Reading chromatograms from mzML file {}", filename.display());
+        let mut xml_reader = Self::open_reader(filename)?;
+        let mut buf = Vec::new();
+        let mut skip_buf = Vec::new();
+        let mut param_groups = ParamGroups::new();
+        let mut chromatograms = Vec::new();
+        let mut skipped = 0;
+
+        loop {
+            match xml_reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) => match e.name().as_ref() {
+                    b"referenceableParamGroupList" => param_groups = self.parse_param_group_list(&mut xml_reader)?,
+                    b"spectrum" => {
+                        let end_name = e.name().as_ref().to_vec();
+                        xml_reader
+                            .read_to_end_into(QName(&end_name), &mut skip_buf)
+                            .map_err(ParseError::xml)?;
+                        skip_buf.clear();
+                    }
+                    b"chromatogram" => {
+                        let id = Self::attribute_value(e, "id")?.unwrap_or_default();
+                        match self.parse_chromatogram(&mut xml_reader, e, &param_groups) {
+                            Ok(chromatogram) => chromatograms.push(chromatogram),
+                            Err(error) if error.is_fatal() || self.options.error_policy == SpectrumErrorPolicy::Fail => {
+                                return Err(error);
+                            }
+                            Err(error) => {
+                                warn!("Skipping corrupt chromatogram '{}': {}", id, error);
+                                skipped += 1;
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(ParseError::xml(e)),
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        info!("Read {} chromatograms from {} ({} skipped)", chromatograms.len(), filename.display(), skipped);
+        Ok(chromatograms)
+    }
+
+    /// 由谱图头信息生成扫描表的一行
+    fn header_scan_row(mzml_spectrum: &MZMLSpectrum) -> ParseResult<ScanRow> {
+        let ms_level = mzml_spectrum.get_ms_level()?;
+        let precursor = if ms_level > 1 { mzml_spectrum.precursors.first() } else { None };
+
+        Ok(ScanRow {
+            scan_number: mzml_spectrum.get_scan_number().unwrap_or(constants::DEFAULT_SCAN_NUMBER),
+            retention_time: mzml_spectrum.get_scan_start_time().unwrap_or(constants::DEFAULT_RETENTION_TIME),
+            ms_level,
+            precursor_mz: precursor.and_then(|p| p.get_precursor_mz()),
+            charge: precursor
+                .and_then(|p| p.get_precursor_charge())
+                .filter(|&charge| charge != 0)
+                .map(|charge| mzml_spectrum.get_polarity().signed_charge(charge)),
+            injection_time: mzml_spectrum.get_ion_injection_time(),
+            total_ion_current: mzml_spectrum.get_total_ion_current(),
+            base_peak_mz: mzml_spectrum.get_base_peak_mz(),
+            base_peak_intensity: mzml_spectrum.get_base_peak_intensity(),
+            peak_count: mzml_spectrum.default_array_length,
+        })
+    }
+
+    /// 按错误策略处理单个谱图的解析错误
+    ///
+    /// XML和IO错误意味着文件无法继续读取，总是原样返回；其余错误在`Fail`策略下加上谱图位置
+    /// 后返回，在`Skip`策略下记录到[`Self::skipped_spectra`]。
+    fn handle_spectrum_error(&self, location: &SpectrumLocation, error: ParseError) -> ParseResult<()> {
+        if error.is_fatal() {
+            return Err(error);
+        }
+        match self.options.error_policy {
+            SpectrumErrorPolicy::Fail => Err(location.wrap(error)),
+            SpectrumErrorPolicy::Skip => {
+                warn!(
+                    "Skipping corrupt spectrum '{}' at byte {}: {}",
+                    location.id, location.byte_offset, error
+                );
+                self.skipped.lock().unwrap_or_else(|e| e.into_inner()).push(location.skipped(&error));
+                Ok(())
+            }
+        }
+    }
+
+    /// 打开mzML文件并创建XML读取器
+    fn open_reader(filename: &Path) -> ParseResult<Reader<std::io::BufReader<std::fs::File>>> {
+        let file = open_file(filename)?;
+        let reader = std::io::BufReader::new(file);
+
+        let mut xml_reader = Reader::from_reader(reader);
+        xml_reader.config_mut().trim_text(true);
+        Ok(xml_reader)
+    }
+
+    /// 逐个读取文件中的谱图，每读完一个`<spectrum>`调用一次`on_spectrum`
+    ///
+    /// `decode_binary`为false时跳过base64解码与解压缩。
+    /// 返回按错误策略跳过的谱图数。
+    fn read_spectra<B, F>(
+        &self,
+        xml_reader: Reader<B>,
+        decode_binary: bool,
+        on_spectrum: F,
+    ) -> ParseResult<usize>
+    where
+        B: BufRead,
+        F: FnMut(&SpectrumLocation, MZMLSpectrum) -> ParseResult<()>,
+    {
+        self.read_cursor_spectra(SpectrumCursor::new(xml_reader), decode_binary, on_spectrum)
+    }
+
+    /// 从游标位置逐个读取谱图，参见[`Self::read_spectra`]
+    fn read_cursor_spectra<B, F>(
+        &self,
+        mut cursor: SpectrumCursor<B>,
+        decode_binary: bool,
+        mut on_spectrum: F,
+    ) -> ParseResult<usize>
+    where
+        B: BufRead,
+        F: FnMut(&SpectrumLocation, MZMLSpectrum) -> ParseResult<()>,
+    {
+        let mut skipped = 0;
+
+        while let Some((location, result)) = self.next_spectrum(&mut cursor, decode_binary)? {
+            match result {
+                Ok(mzml_spectrum) => {
+                    debug!("Parsed spectrum '{}' (index {:?})", mzml_spectrum.id, mzml_spectrum.index);
+                    on_spectrum(&location, mzml_spectrum)?;
+                }
+                Err(error) => {
+                    self.handle_spectrum_error(&location, error)?;
+                    skipped += 1;
+                }
+            }
+        }
+
+        Ok(skipped)
+    }
+
+    /// 从游标位置读取下一个`<spectrum>`元素，文件结束时返回None
+    ///
+    /// 谱图内部的可恢复错误（二进制解码失败、无效参数等）放在返回的结果中，
+    /// 游标停在该谱图之后，可以继续读取；XML和IO错误直接返回，XML错误带有出错处的字节偏移、
+    /// 所在谱图的native id和元素名。
+    fn next_spectrum<B: BufRead>(
+        &self,
+        cursor: &mut SpectrumCursor<B>,
+        decode_binary: bool,
+    ) -> ParseResult<Option<SpectrumRead>> {
+        let mut location = SpectrumLocation::default();
+        let mut element = None;
+        self.read_next_spectrum(cursor, decode_binary, &mut location, &mut element)
+            .map_err(|error| {
+                let reader = &cursor.xml_reader;
+                // 语法错误给出出错处，其他XML错误（如文件提前结束）给出当前读取位置
+                let position = match reader.error_position() {
+                    0 => reader.buffer_position(),
+                    position => position,
+                };
+                let spectrum_id = Some(location.id.as_str()).filter(|id| !id.is_empty());
+                error.with_xml_context(cursor.base_offset + position, spectrum_id, element.as_deref())
+            })
+    }
+
+    /// [`Self::next_spectrum`]的实现，读取过程中更新当前谱图的位置和最近打开的元素名
+    fn read_next_spectrum<B: BufRead>(
+        &self,
+        cursor: &mut SpectrumCursor<B>,
+        decode_binary: bool,
+        location: &mut SpectrumLocation,
+        element: &mut Option<String>,
+    ) -> ParseResult<Option<SpectrumRead>> {
+        let SpectrumCursor { xml_reader, buf, param_groups, base_offset } = cursor;
+        let mut in_spectrum = false;
+        // 当前位于spectrum内部的嵌套深度，0表示spectrum的直接子元素
+        let mut spectrum_depth = 0usize;
+        let mut current_spectrum: Option<MZMLSpectrum> = None;
+        // 当前谱图中第一个可恢复的错误（二进制解码失败、无效参数等）
+        let mut spectrum_error: Option<ParseError> = None;
+
+        loop {
+            buf.clear();
+            match xml_reader.read_event_into(buf) {
+                Ok(Event::Start(ref e)) => {
+                    let current_element = str::from_utf8(e.name().into_inner())
+                        .unwrap_or("")
+                        .to_string();
+                    *element = Some(current_element.clone());
+
+                    let result = match current_element.as_str() {
+                        "referenceableParamGroupList" => {
+                            *param_groups = self.parse_param_group_list(xml_reader)?;
+                            debug!("Read {} referenceableParamGroups", param_groups.len());
+                            Ok(())
+                        }
+                        "spectrum" => {
+                            in_spectrum = true;
+                            spectrum_depth = 0;
+                            // 开始标签之后的位置减去标签长度（含尖括号）即为`<spectrum`的偏移
+                            let end = *base_offset + xml_reader.buffer_position();
+                            *location = SpectrumLocation {
+                                id: Self::attribute_value(e, "id").ok().flatten().unwrap_or_default(),
+                                index: Self::attribute_value(e, "index")
+                                    .ok()
+                                    .flatten()
+                                    .and_then(|index| index.parse().ok()),
+                                byte_offset: end.saturating_sub(e.len() as u64 + 2),
+                                end_offset: end,
+                            };
+                            self.parse_spectrum_start(e).map(|spectrum| {
+                                current_spectrum = Some(spectrum);
+                            })
+                        }
+                        "binaryDataArray" if in_spectrum => match current_spectrum {
+                            Some(ref mut spectrum) => self
+                                .parse_binary_data_array(
+                                    xml_reader, e, spectrum.default_array_length, param_groups, decode_binary,
+                                )
+                                .map(|binary_array| spectrum.add_binary_data_array(binary_array)),
+                            None => Ok(()),
+                        },
+                        "scanList" if in_spectrum => match current_spectrum {
+                            Some(ref mut spectrum) => self
+                                .parse_scan_list(xml_reader, e, param_groups)
+                                .map(|scan_list| spectrum.scan_list = scan_list),
+                            None => Ok(()),
+                        },
+                        "precursorList" if in_spectrum => match current_spectrum {
+                            Some(ref mut spectrum) => self
+                                .parse_precursor_list(xml_reader, e)
+                                .map(|precursors| {
+                                    for precursor in precursors {
+                                        spectrum.add_precursor(precursor);
+                                    }
+                                }),
+                            None => Ok(()),
+                        },
+                        _ if in_spectrum => {
+                            let result = match current_spectrum {
+                                Some(ref mut spectrum) if spectrum_depth == 0 => {
+                                    self.parse_spectrum_param(spectrum, e, param_groups)
+                                }
+                                _ => Ok(()),
+                            };
+                            spectrum_depth += 1;
+                            result
+                        }
+                        _ => Ok(()),
+                    };
+                    Self::record_spectrum_error(&mut spectrum_error, result)?;
+                }
+                Ok(Event::Empty(ref e)) if in_spectrum && spectrum_depth == 0 => {
+                    *element = Some(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+                    if let Some(ref mut spectrum) = current_spectrum {
+                        let result = self.parse_spectrum_param(spectrum, e, param_groups);
+                        Self::record_spectrum_error(&mut spectrum_error, result)?;
+                    }
+                }
+                Ok(Event::End(ref e)) => {
+                    let element_name = str::from_utf8(e.name().into_inner())
+                        .unwrap_or("");
+                    
+                    if element_name == "spectrum" && in_spectrum {
+                        in_spectrum = false;
+                        location.end_offset = *base_offset + xml_reader.buffer_position();
+                        match (current_spectrum.take(), spectrum_error.take()) {
+                            (_, Some(error)) => return Ok(Some((std::mem::take(location), Err(error)))),
+                            (Some(mzml_spectrum), None) => {
+                                return Ok(Some((std::mem::take(location), Ok(mzml_spectrum))))
+                            }
+                            (None, None) => {}
+                        }
+                    } else if in_spectrum {
+                        spectrum_depth = spectrum_depth.saturating_sub(1);
+                    }
+                }
+                Ok(Event::Eof) if in_spectrum => {
+                    return Err(ParseError::xml("Unexpected end of file in spectrum"));
+                }
+                Ok(Event::Eof) => return Ok(None),
+                Err(e) => return Err(ParseError::xml(e)),
+                _ => {}
+            }
+        }
+    }
+
+    /// 记录谱图内部的可恢复错误，XML和IO错误直接返回
+    fn record_spectrum_error(slot: &mut Option<ParseError>, result: ParseResult<()>) -> ParseResult<()> {
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) if e.is_fatal() => Err(e),
+            Err(e) => {
+                slot.get_or_insert(e);
+                Ok(())
+            }
+        }
+    }
+
+    /// 用`num_threads`个工作线程并行解析MZML文件，0表示使用rayon的全局线程池
+    ///
+    /// 先顺序扫描一遍文件，只定位每个`<spectrum>`元素的字节范围（跳过其内容，不解码），
+    /// 同时读取referenceableParamGroup；再把谱图分成连续的块，由线程池中的线程各自读取
+    /// 对应的字节范围，完成XML解析、base64解码、解压和转换。结果按原文件顺序合并，
+    /// 错误策略与顺序解析相同，返回的谱图与[`parse_sequential`](Self::parse_sequential)一致。
+    pub fn parse_parallel(&self, filename: impl AsRef<Path>, num_threads: usize) -> ParseResult<Vec<Spectrum>> {
+        let filename = filename.as_ref();
+        info!("Parsing mzML file {} in parallel", filename.display());
+        let (param_groups, ranges) = self.locate_spectra(filename)?;
+        self.reset_parse_stats();
+
+        let workers = if num_threads == 0 { rayon::current_num_threads() } else { num_threads };
+        // 每个线程分几块，避免谱图大小不均时个别线程拖慢整体
+        let chunk_size = ranges.len().div_ceil(workers * PARALLEL_CHUNKS_PER_THREAD).max(1);
+        let parse_chunks = || -> Vec<ParseResult<Vec<SpectrumConversion>>> {
+            ranges
+                .par_chunks(chunk_size)
+                .map(|chunk| self.parse_spectrum_range(filename, &param_groups, chunk[0].start..chunk[chunk.len() - 1].end))
+                .collect()
+        };
+        let chunks = match num_threads {
+            0 => parse_chunks(),
+            _ => match rayon::ThreadPoolBuilder::new().num_threads(num_threads).build() {
+                Ok(pool) => pool.install(parse_chunks),
+                Err(e) => {
+                    warn!("Failed to build a {}-thread pool ({}), using the global pool", num_threads, e);
+                    parse_chunks()
+                }
+            },
+        };
+
+        let mut spectra = Vec::with_capacity(ranges.len());
+        let mut skipped = 0;
+        for chunk in chunks {
+            for (location, result) in chunk? {
+                match result {
+                    Ok(spectrum) => spectra.push(spectrum),
+                    Err(error) => {
+                        self.handle_spectrum_error(&location, error)?;
+                        skipped += 1;
+                    }
+                }
+            }
+        }
+
+        info!(
+            "Parsed {} spectra from {} with {} threads ({} skipped)",
+            spectra.len(), filename.display(), workers, skipped
+        );
+        self.report_missing_rt_units(filename);
+        Ok(spectra)
+    }
+
+    /// 扫描文件，返回referenceableParamGroup和每个`<spectrum>`元素的字节范围
+    fn locate_spectra(&self, filename: &Path) -> ParseResult<(ParamGroups, Vec<Range<u64>>)> {
+        let mut xml_reader = Self::open_reader(filename)?;
+        let mut buf = Vec::new();
+        let mut skip_buf = Vec::new();
+        let mut param_groups = ParamGroups::new();
+        let mut ranges = Vec::new();
+
+        loop {
+            let start = xml_reader.buffer_position();
+            match xml_reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) => match e.name().as_ref() {
+                    b"referenceableParamGroupList" => param_groups = self.parse_param_group_list(&mut xml_reader)?,
+                    b"spectrum" => {
+                        let end_name = e.name().as_ref().to_vec();
+                        xml_reader
+                            .read_to_end_into(QName(&end_name), &mut skip_buf)
+                            .map_err(ParseError::xml)?;
+                        skip_buf.clear();
+                        ranges.push(start..xml_reader.buffer_position());
+                    }
+                    _ => {}
+                },
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(ParseError::xml(e)),
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        debug!("Located {} spectra in {}", ranges.len(), filename.display());
+        Ok((param_groups, ranges))
+    }
+
+    /// 解析文件中`range`字节范围内的谱图，谱图级别的错误与其原生id一起返回
+    fn parse_spectrum_range(
+        &self,
+        filename: &Path,
+        param_groups: &ParamGroups,
+        range: Range<u64>,
+    ) -> ParseResult<Vec<SpectrumConversion>> {
+        let mut file = open_file(filename)?;
+        file.seek(SeekFrom::Start(range.start))?;
+        let mut bytes = Vec::with_capacity((range.end - range.start) as usize);
+        file.take(range.end - range.start).read_to_end(&mut bytes)?;
+
+        let mut xml_reader = Reader::from_reader(bytes.as_slice());
+        xml_reader.config_mut().trim_text(true);
+        let mut cursor = SpectrumCursor::at_offset(xml_reader, range.start);
+        cursor.param_groups = param_groups.clone();
+
+        let mut results = Vec::new();
+        while let Some((location, result)) = self.next_spectrum(&mut cursor, true)? {
+            let converted = result.and_then(|mzml_spectrum| self.convert_mzml_to_spectrum(mzml_spectrum));
+            results.push((location, converted));
+        }
+        Ok(results)
+    }
+
+    /// 解析谱图开始元素
+    fn parse_spectrum_start(&self, event: &BytesStart) -> ParseResult<MZMLSpectrum> {
+        let mut id = String::new();
+        let mut default_array_length = 0;
+        let mut index = None;
+
+        for attr in event.attributes() {
+            let attr = attr.map_err(|e| Self::malformed_attribute(event, e))?;
+            let key = str::from_utf8(attr.key.into_inner()).unwrap_or("");
+            let value = str::from_utf8(&attr.value).unwrap_or("");
+
+            match key {
+                "id" => {
+                    id = attr.unescape_value().map_err(|e| Self::malformed_attribute(event, e))?.into_owned();
+                }
+                "defaultArrayLength" => {
+                    default_array_length = value.parse()
+                        .map_err(|_| ParseError::InvalidDataType {
+                            expected: "integer".to_string(),
+                            actual: format!("'{}'", value),
+                        })?;
+                }
+                "index" => {
+                    index = Some(value.parse().map_err(|_| {
+                        ParseError::InvalidDataType {
+                            expected: "integer".to_string(),
+                            actual: format!("'{}'", value),
+                        }
+                    })?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(MZMLSpectrum::new(id, default_array_length).with_index(index))
+    }
+
+    /// 解析referenceableParamGroupList，返回 组ID -> CV参数 的映射
+    fn parse_param_group_list<B: BufRead>(&self, reader: &mut Reader<B>) -> ParseResult<ParamGroups> {
+        let mut groups = ParamGroups::new();
+        let mut current_group: Option<(String, Vec<CVParam>)> = None;
+        let mut buf = Vec::new();
+
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) => {
+                    let element_name = str::from_utf8(e.name().into_inner()).unwrap_or("");
+
+                    match element_name {
+                        "referenceableParamGroup" => {
+                            let id = Self::attribute_value(e, "id")?.unwrap_or_default();
+                            current_group = Some((id, Vec::new()));
+                        }
+                        "cvParam" => {
+                            if let Some((_, ref mut params)) = current_group {
+                                params.push(self.parse_cv_param(e)?);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(Event::Empty(ref e)) => {
+                    let element_name = str::from_utf8(e.name().into_inner()).unwrap_or("");
+
+                    match element_name {
+                        // 空元素的组没有参数，直接登记
+                        "referenceableParamGroup" => {
+                            let id = Self::attribute_value(e, "id")?.unwrap_or_default();
+                            groups.insert(id, Vec::new());
+                        }
+                        "cvParam" => {
+                            if let Some((_, ref mut params)) = current_group {
+                                params.push(self.parse_cv_param(e)?);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(Event::End(ref e)) => {
+                    let element_name = str::from_utf8(e.name().into_inner()).unwrap_or("");
+
+                    match element_name {
+                        "referenceableParamGroup" => {
+                            if let Some((id, params)) = current_group.take() {
+                                groups.insert(id, params);
+                            }
+                        }
+                        "referenceableParamGroupList" => break,
+                        _ => {}
+                    }
+                }
+                Ok(Event::Eof) => {
+                    return Err(ParseError::InvalidFormat(
+                        "Unexpected end of file in referenceableParamGroupList".to_string()
+                    ));
+                }
+                Err(e) => return Err(ParseError::xml(e)),
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(groups)
+    }
+
+    /// 解析referenceableParamGroupRef，返回被引用组中的CV参数
+    fn resolve_param_group_ref(&self, event: &BytesStart, param_groups: &ParamGroups) -> ParseResult<Vec<CVParam>> {
+        let group_ref = Self::attribute_value(event, "ref")?.ok_or_else(|| ParseError::MissingField {
+            field: "referenceableParamGroupRef/@ref".to_string(),
+        })?;
+
+        param_groups.get(&group_ref).cloned().ok_or_else(|| {
+            ParseError::InvalidFormat(format!("Unknown referenceableParamGroup: {}", group_ref))
+        })
+    }
+
+    /// 处理spectrum直接子元素中的参数
+    fn parse_spectrum_param(
+        &self,
+        spectrum: &mut MZMLSpectrum,
+        event: &BytesStart,
+        param_groups: &ParamGroups,
+    ) -> ParseResult<()> {
+        match str::from_utf8(event.name().into_inner()).unwrap_or("") {
+            "cvParam" => spectrum.add_cv_param(self.parse_cv_param(event)?),
+            "userParam" => spectrum.add_user_param(self.parse_user_param(event)?),
+            "referenceableParamGroupRef" => {
+                for param in self.resolve_param_group_ref(event, param_groups)? {
+                    spectrum.add_cv_param(param);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// 属性格式错误（缺少引号、重复属性等）只影响所在元素，作为可恢复错误处理
+    fn malformed_attribute(event: &BytesStart, error: impl std::fmt::Display) -> ParseError {
+        ParseError::InvalidFormat(format!(
+            "Malformed attribute in <{}>: {}",
+            String::from_utf8_lossy(event.name().as_ref()),
+            error
+        ))
+    }
+
+    /// 读取元素的指定属性
+    fn attribute_value(event: &BytesStart, name: &str) -> ParseResult<Option<String>> {
+        for attr in event.attributes() {
+            let attr = attr.map_err(|e| Self::malformed_attribute(event, e))?;
+            if attr.key.into_inner() == name.as_bytes() {
+                return Ok(Some(str::from_utf8(&attr.value).unwrap_or("").to_string()));
+            }
         }
+        Ok(None)
     }
 
-    /// 顺序解析MZML文件
-    pub fn parse_sequential(&self, filename: &str) -> ParseResult<Vec<Spectrum>> {
-        let file = std::fs::File::open(filename)
-            .map_err(ParseError::Io)?;
-        let reader = std::io::BufReader::new(file);
-        
-        let mut xml_reader = Reader::from_reader(reader);
-        xml_reader.trim_text(true);
-        
+    /// 解析`<chromatogram>`元素，读取器停在该元素结束之后
+    ///
+    /// 可恢复的错误（二进制解码失败、无效参数等）在读完整个元素后返回，XML和IO错误直接返回。
+    fn parse_chromatogram<B: BufRead>(
+        &self,
+        reader: &mut Reader<B>,
+        event: &BytesStart,
+        param_groups: &ParamGroups,
+    ) -> ParseResult<Chromatogram> {
+        let id = Self::attribute_value(event, "id")?.unwrap_or_default();
+        let default_array_length = Self::attribute_value(event, "defaultArrayLength")?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        let mut chromatogram = Chromatogram::new(id, default_array_length);
+        chromatogram.index = Self::attribute_value(event, "index")?.and_then(|value| value.parse().ok());
+
+        let mut arrays = Vec::new();
+        let mut error: Option<ParseError> = None;
+        // 当前位于chromatogram内部的嵌套深度，0表示chromatogram的直接子元素
+        let mut depth = 0usize;
         let mut buf = Vec::new();
-        let mut spectra = Vec::new();
-        let mut current_element = String::new();
-        let mut in_spectrum = false;
-        let mut current_spectrum: Option<MZMLSpectrum> = None;
 
         loop {
-            match xml_reader.read_event_into(&mut buf) {
+            match reader.read_event_into(&mut buf) {
                 Ok(Event::Start(ref e)) => {
-                    current_element = str::from_utf8(e.name().into_inner())
-                        .unwrap_or("")
-                        .to_string();
-
-                    match current_element.as_str() {
-                        "spectrum" => {
-                            in_spectrum = true;
-                            current_spectrum = Some(self.parse_spectrum_start(e)?);
-                        }
-                        "binaryDataArray" if in_spectrum => {
-                            if let Some(ref mut spectrum) = current_spectrum {
-                                let binary_array = self.parse_binary_data_array(&mut xml_reader, e)?;
-                                spectrum.add_binary_data_array(binary_array);
-                            }
-                        }
-                        "scanList" if in_spectrum => {
-                            if let Some(ref mut spectrum) = current_spectrum {
-                                let scan_list = self.parse_scan_list(&mut xml_reader, e)?;
-                                spectrum.scan_list = scan_list;
-                            }
-                        }
-                        "precursorList" if in_spectrum => {
-                            if let Some(ref mut spectrum) = current_spectrum {
-                                let precursors = self.parse_precursor_list(&mut xml_reader, e)?;
-                                for precursor in precursors {
-                                    spectrum.add_precursor(precursor);
-                                }
-                            }
+                    let result = match e.name().as_ref() {
+                        b"precursor" => self
+                            .parse_precursor(reader, e)
+                            .map(|precursor| chromatogram.precursor = Some(precursor)),
+                        b"product" => self.parse_product(reader).map(|window| chromatogram.product = window),
+                        b"binaryDataArray" => self
+                            .parse_binary_data_array(reader, e, default_array_length, param_groups, true)
+                            .map(|array| arrays.push(array)),
+                        _ => {
+                            let result = match depth {
+                                0 => self.parse_chromatogram_param(&mut chromatogram, e, param_groups),
+                                _ => Ok(()),
+                            };
+                            depth += 1;
+                            result
                         }
-                        _ => {}
-                    }
+                    };
+                    Self::record_spectrum_error(&mut error, result)?;
+                }
+                Ok(Event::Empty(ref e)) if depth == 0 => {
+                    let result = self.parse_chromatogram_param(&mut chromatogram, e, param_groups);
+                    Self::record_spectrum_error(&mut error, result)?;
                 }
                 Ok(Event::End(ref e)) => {
-                    let element_name = str::from_utf8(e.name().into_inner())
-                        .unwrap_or("");
-                    
-                    if element_name == "spectrum" && in_spectrum {
-                        if let Some(mzml_spectrum) = current_spectrum.take() {
-                            let spectrum = self.convert_mzml_to_spectrum(mzml_spectrum)?;
-                            spectra.push(spectrum);
-                        }
-                        in_spectrum = false;
+                    if e.name().as_ref() == b"chromatogram" {
+                        break;
                     }
+                    depth = depth.saturating_sub(1);
                 }
-                Ok(Event::Eof) => break,
-                Err(e) => return Err(ParseError::Xml(e.to_string())),
+                Ok(Event::Eof) => {
+                    return Err(ParseError::xml(format!("Unexpected end of file in chromatogram '{}'", chromatogram.id)));
+                }
+                Err(e) => return Err(ParseError::xml(e)),
                 _ => {}
             }
             buf.clear();
         }
 
-        Ok(spectra)
+        if let Some(error) = error {
+            return Err(error);
+        }
+        Self::decode_chromatogram_arrays(&mut chromatogram, &arrays)?;
+        Ok(chromatogram)
     }
 
-    /// 并行解析MZML文件
-    pub fn parse_parallel(&self, filename: &str, num_threads: usize) -> ParseResult<Vec<Spectrum>> {
-        // 简化实现：目前使用顺序解析
-        // 在实际实现中，可以将文件分块并行处理
-        self.parse_sequential(filename)
+    /// 解析色谱图的直接子元素中的参数
+    fn parse_chromatogram_param(
+        &self,
+        chromatogram: &mut Chromatogram,
+        event: &BytesStart,
+        param_groups: &ParamGroups,
+    ) -> ParseResult<()> {
+        match event.name().as_ref() {
+            b"cvParam" => chromatogram.add_cv_param(self.parse_cv_param(event)?),
+            b"userParam" => chromatogram.add_user_param(self.parse_user_param(event)?),
+            b"referenceableParamGroupRef" => {
+                for param in self.resolve_param_group_ref(event, param_groups)? {
+                    chromatogram.add_cv_param(param);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
     }
 
-    /// 解析谱图开始元素
-    fn parse_spectrum_start(&self, event: &BytesStart) -> ParseResult<MZMLSpectrum> {
-        let mut id = String::new();
-        let mut default_array_length = 0;
-        let mut index = None;
-
-        for attr in event.attributes() {
-            let attr = attr.map_err(|e| ParseError::Xml(e.to_string()))?;
-            let key = str::from_utf8(attr.key.into_inner()).unwrap_or("");
-            let value = str::from_utf8(&attr.value).unwrap_or("");
+    /// 解析`<product>`元素中的分离窗口
+    fn parse_product<B: BufRead>(&self, reader: &mut Reader<B>) -> ParseResult<Option<MZMLIsolationWindow>> {
+        let mut window = None;
+        let mut buf = Vec::new();
 
-            match key {
-                "id" => id = value.to_string(),
-                "defaultArrayLength" => {
-                    default_array_length = value.parse()
-                        .map_err(|_| ParseError::InvalidDataType {
-                            expected: "integer".to_string(),
-                            actual: format!("'{}'", value),
-                        })?;
-                }
-                "index" => {
-                    index = Some(value.parse().map_err(|_| {
-                        ParseError::InvalidDataType {
-                            expected: "integer".to_string(),
-                            actual: format!("'{}'", value),
-                        }
-                    })?);
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) if e.name().as_ref() == b"isolationWindow" => {
+                    window = Some(self.parse_isolation_window(reader, e)?);
                 }
+                Ok(Event::End(ref e)) if e.name().as_ref() == b"product" => break,
+                Ok(Event::Eof) => return Err(ParseError::xml("Unexpected end of file in product")),
+                Err(e) => return Err(ParseError::xml(e)),
                 _ => {}
             }
+            buf.clear();
         }
 
-        Ok(MZMLSpectrum::new(id, default_array_length).with_index(index))
+        Ok(window)
+    }
+
+    /// 解码色谱图的时间和强度数组
+    ///
+    /// 没有强度数组的色谱图（压力、流速等）取第一个非时间数组。
+    fn decode_chromatogram_arrays(chromatogram: &mut Chromatogram, arrays: &[MZMLBinaryDataArray]) -> ParseResult<()> {
+        let time = arrays.iter().find(|array| array.is_time_array()).ok_or_else(|| ParseError::MissingField {
+            field: "time array".to_string(),
+        })?;
+        let intensity = arrays
+            .iter()
+            .find(|array| array.is_intensity_array())
+            .or_else(|| arrays.iter().find(|array| !array.is_time_array()))
+            .ok_or_else(|| ParseError::MissingField {
+                field: "intensity array".to_string(),
+            })?;
+
+        let time_array = time.decode_f64()?;
+        let intensity_array = intensity.decode_f64()?;
+        if time_array.len() != intensity_array.len() {
+            return Err(ParseError::CorruptedData(format!(
+                "time array length ({}) != intensity array length ({})",
+                time_array.len(), intensity_array.len()
+            )));
+        }
+
+        chromatogram.time_unit = time
+            .cv_params
+            .iter()
+            .find(|param| param.is_accession("MS:1000595"))
+            .and_then(|param| param.unit().map(str::to_string));
+        chromatogram.time_array = time_array;
+        chromatogram.intensity_array = intensity_array;
+        Ok(())
     }
 
     /// 解析二进制数据数组
@@ -156,16 +1408,22 @@ impl MZMLParser {
         &self,
         reader: &mut Reader<B>,
         event: &BytesStart,
+        default_array_length: usize,
+        param_groups: &ParamGroups,
+        decode_binary: bool,
     ) -> ParseResult<MZMLBinaryDataArray> {
         let mut array = MZMLBinaryDataArray::new();
+        // 数组元素个数，arrayLength缺省时使用谱图的defaultArrayLength
+        // （encodedLength是base64字符数，不能作为元素个数）
+        array.length = Some(default_array_length);
         
         // 解析属性
         for attr in event.attributes() {
-            let attr = attr.map_err(|e| ParseError::Xml(e.to_string()))?;
+            let attr = attr.map_err(|e| Self::malformed_attribute(event, e))?;
             let key = str::from_utf8(attr.key.into_inner()).unwrap_or("");
             let value = str::from_utf8(&attr.value).unwrap_or("");
 
-            if key == "encodedLength" {
+            if key == "arrayLength" {
                 if let Ok(length) = value.parse::<usize>() {
                     array.length = Some(length);
                 }
@@ -178,7 +1436,7 @@ impl MZMLParser {
 
         loop {
             match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(ref e)) => {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
                     let element_name = str::from_utf8(e.name().into_inner()).unwrap_or("");
                     
                     match element_name {
@@ -190,16 +1448,19 @@ impl MZMLParser {
                             let user_param = self.parse_user_param(e)?;
                             array.add_user_param(user_param);
                         }
+                        "referenceableParamGroupRef" => {
+                            for param in self.resolve_param_group_ref(e, param_groups)? {
+                                array.add_cv_param(param);
+                            }
+                        }
                         "binary" => {
                             in_binary = true;
                         }
                         _ => {}
                     }
                 }
-                Ok(Event::Text(ref e)) => {
-                    if in_binary {
-                        binary_data.push_str(str::from_utf8(e).unwrap_or(""));
-                    }
+                Ok(Event::Text(ref e)) if in_binary && decode_binary => {
+                    binary_data.push_str(str::from_utf8(e).unwrap_or(""));
                 }
                 Ok(Event::End(ref e)) => {
                     let element_name = str::from_utf8(e.name().into_inner()).unwrap_or("");
@@ -210,7 +1471,8 @@ impl MZMLParser {
                         break;
                     }
                 }
-                Err(e) => return Err(ParseError::Xml(e.to_string())),
+                Ok(Event::Eof) => return Err(ParseError::xml("Unexpected end of file in binaryDataArray")),
+                Err(e) => return Err(ParseError::xml(e)),
                 _ => {}
             }
             buf.clear();
@@ -230,6 +1492,7 @@ impl MZMLParser {
         &self,
         reader: &mut Reader<B>,
         _event: &BytesStart,
+        param_groups: &ParamGroups,
     ) -> ParseResult<MZMLScanList> {
         let mut scan_list = MZMLScanList::new();
         let mut buf = Vec::new();
@@ -240,7 +1503,7 @@ impl MZMLParser {
                     let element_name = str::from_utf8(e.name().into_inner()).unwrap_or("");
                     
                     if element_name == "scan" {
-                        let scan = self.parse_scan(reader, e)?;
+                        let scan = self.parse_scan(reader, e, param_groups)?;
                         scan_list.add_scan(scan);
                     }
                 }
@@ -251,7 +1514,8 @@ impl MZMLParser {
                         break;
                     }
                 }
-                Err(e) => return Err(ParseError::Xml(e.to_string())),
+                Ok(Event::Eof) => return Err(ParseError::xml("Unexpected end of file in scanList")),
+                Err(e) => return Err(ParseError::xml(e)),
                 _ => {}
             }
             buf.clear();
@@ -265,12 +1529,13 @@ impl MZMLParser {
         &self,
         reader: &mut Reader<B>,
         event: &BytesStart,
+        param_groups: &ParamGroups,
     ) -> ParseResult<MZMLScan> {
         let mut scan = MZMLScan::new();
         
         // 解析属性
         for attr in event.attributes() {
-            let attr = attr.map_err(|e| ParseError::Xml(e.to_string()))?;
+            let attr = attr.map_err(|e| Self::malformed_attribute(event, e))?;
             let key = str::from_utf8(attr.key.into_inner()).unwrap_or("");
             let value = str::from_utf8(&attr.value).unwrap_or("");
 
@@ -292,7 +1557,7 @@ impl MZMLParser {
 
         loop {
             match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(ref e)) => {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
                     let element_name = str::from_utf8(e.name().into_inner()).unwrap_or("");
                     
                     match element_name {
@@ -304,6 +1569,11 @@ impl MZMLParser {
                             let user_param = self.parse_user_param(e)?;
                             scan.add_user_param(user_param);
                         }
+                        "referenceableParamGroupRef" => {
+                            for param in self.resolve_param_group_ref(e, param_groups)? {
+                                scan.add_cv_param(param);
+                            }
+                        }
                         _ => {}
                     }
                 }
@@ -314,7 +1584,8 @@ impl MZMLParser {
                         break;
                     }
                 }
-                Err(e) => return Err(ParseError::Xml(e.to_string())),
+                Ok(Event::Eof) => return Err(ParseError::xml("Unexpected end of file in scan")),
+                Err(e) => return Err(ParseError::xml(e)),
                 _ => {}
             }
             buf.clear();
@@ -349,7 +1620,8 @@ impl MZMLParser {
                         break;
                     }
                 }
-                Err(e) => return Err(ParseError::Xml(e.to_string())),
+                Ok(Event::Eof) => return Err(ParseError::xml("Unexpected end of file in precursorList")),
+                Err(e) => return Err(ParseError::xml(e)),
                 _ => {}
             }
             buf.clear();
@@ -368,7 +1640,7 @@ impl MZMLParser {
         
         // 解析属性
         for attr in event.attributes() {
-            let attr = attr.map_err(|e| ParseError::Xml(e.to_string()))?;
+            let attr = attr.map_err(|e| Self::malformed_attribute(event, e))?;
             let key = str::from_utf8(attr.key.into_inner()).unwrap_or("");
             let value = str::from_utf8(&attr.value).unwrap_or("");
 
@@ -404,6 +1676,21 @@ impl MZMLParser {
                         _ => {}
                     }
                 }
+                Ok(Event::Empty(ref e)) => {
+                    let element_name = str::from_utf8(e.name().into_inner()).unwrap_or("");
+
+                    match element_name {
+                        "cvParam" => {
+                            let cv_param = self.parse_cv_param(e)?;
+                            precursor.add_cv_param(cv_param);
+                        }
+                        "userParam" => {
+                            let user_param = self.parse_user_param(e)?;
+                            precursor.add_user_param(user_param);
+                        }
+                        _ => {}
+                    }
+                }
                 Ok(Event::End(ref e)) => {
                     let element_name = str::from_utf8(e.name().into_inner()).unwrap_or("");
                     
@@ -411,7 +1698,8 @@ impl MZMLParser {
                         break;
                     }
                 }
-                Err(e) => return Err(ParseError::Xml(e.to_string())),
+                Ok(Event::Eof) => return Err(ParseError::xml("Unexpected end of file in precursor")),
+                Err(e) => return Err(ParseError::xml(e)),
                 _ => {}
             }
             buf.clear();
@@ -431,7 +1719,7 @@ impl MZMLParser {
 
         loop {
             match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(ref e)) => {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
                     let element_name = str::from_utf8(e.name().into_inner()).unwrap_or("");
                     
                     match element_name {
@@ -453,7 +1741,8 @@ impl MZMLParser {
                         break;
                     }
                 }
-                Err(e) => return Err(ParseError::Xml(e.to_string())),
+                Ok(Event::Eof) => return Err(ParseError::xml("Unexpected end of file in isolationWindow")),
+                Err(e) => return Err(ParseError::xml(e)),
                 _ => {}
             }
             buf.clear();
@@ -473,7 +1762,7 @@ impl MZMLParser {
 
         loop {
             match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(ref e)) => {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
                     let element_name = str::from_utf8(e.name().into_inner()).unwrap_or("");
                     
                     match element_name {
@@ -495,7 +1784,8 @@ impl MZMLParser {
                         break;
                     }
                 }
-                Err(e) => return Err(ParseError::Xml(e.to_string())),
+                Ok(Event::Eof) => return Err(ParseError::xml("Unexpected end of file in activation")),
+                Err(e) => return Err(ParseError::xml(e)),
                 _ => {}
             }
             buf.clear();
@@ -509,10 +1799,12 @@ impl MZMLParser {
         let mut accession = String::new();
         let mut name = String::new();
         let mut value = String::new();
-        let mut unit = None;
+        let mut unit_cv_ref = None;
+        let mut unit_accession = None;
+        let mut unit_name = None;
 
         for attr in event.attributes() {
-            let attr = attr.map_err(|e| ParseError::Xml(e.to_string()))?;
+            let attr = attr.map_err(|e| Self::malformed_attribute(event, e))?;
             let key = str::from_utf8(attr.key.into_inner()).unwrap_or("");
             let value_str = str::from_utf8(&attr.value).unwrap_or("");
 
@@ -520,15 +1812,17 @@ impl MZMLParser {
                 "accession" => accession = value_str.to_string(),
                 "name" => name = value_str.to_string(),
                 "value" => value = value_str.to_string(),
-                "unitCvRef" | "unitName" => unit = Some(value_str.to_string()),
+                "unitCvRef" => unit_cv_ref = Some(value_str.to_string()),
+                "unitAccession" => unit_accession = Some(value_str.to_string()),
+                "unitName" => unit_name = Some(value_str.to_string()),
                 _ => {}
             }
         }
 
         let mut cv_param = CVParam::new(accession, name, value);
-        if let Some(unit_str) = unit {
-            cv_param = cv_param.with_unit(unit_str);
-        }
+        cv_param.unit_cv_ref = unit_cv_ref;
+        cv_param.unit_accession = unit_accession;
+        cv_param.unit_name = unit_name;
 
         Ok(cv_param)
     }
@@ -537,25 +1831,29 @@ impl MZMLParser {
     fn parse_user_param(&self, event: &BytesStart) -> ParseResult<UserParam> {
         let mut name = String::new();
         let mut value = String::new();
-        let mut unit = None;
+        let mut unit_cv_ref = None;
+        let mut unit_accession = None;
+        let mut unit_name = None;
 
         for attr in event.attributes() {
-            let attr = attr.map_err(|e| ParseError::Xml(e.to_string()))?;
+            let attr = attr.map_err(|e| Self::malformed_attribute(event, e))?;
             let key = str::from_utf8(attr.key.into_inner()).unwrap_or("");
             let value_str = str::from_utf8(&attr.value).unwrap_or("");
 
             match key {
                 "name" => name = value_str.to_string(),
                 "value" => value = value_str.to_string(),
-                "unitCvRef" | "unitName" => unit = Some(value_str.to_string()),
+                "unitCvRef" => unit_cv_ref = Some(value_str.to_string()),
+                "unitAccession" => unit_accession = Some(value_str.to_string()),
+                "unitName" => unit_name = Some(value_str.to_string()),
                 _ => {}
             }
         }
 
         let mut user_param = UserParam::new(name, value);
-        if let Some(unit_str) = unit {
-            user_param = user_param.with_unit(unit_str);
-        }
+        user_param.unit_cv_ref = unit_cv_ref;
+        user_param.unit_accession = unit_accession;
+        user_param.unit_name = unit_name;
 
         Ok(user_param)
     }
@@ -563,39 +1861,76 @@ impl MZMLParser {
     /// 解析二进制数据
     fn parse_binary_data(&self, array: &MZMLBinaryDataArray, binary_data: &str) -> ParseResult<BinaryDataArray> {
         // 解码base64
-        let decoded_data = base64::decode(binary_data.trim())?;
+        let decoded_data = STANDARD.decode(binary_data.trim())?;
         
         // 获取编码类型
         let mut encoding = BinaryDataEncoding::Float64Little;
         let mut compression = None;
-        let mut length = array.length.unwrap_or(0);
 
         for param in &array.cv_params {
             if param.is_accession("MS:1000523") { // 64-bit float
                 encoding = BinaryDataEncoding::Float64Little;
             } else if param.is_accession("MS:1000521") { // 32-bit float
                 encoding = BinaryDataEncoding::Float32Little;
-            } else if param.is_accession("MS:1000576") { // zlib compression
-                compression = Some(CompressionType::Zlib);
-            } else if param.is_accession("MS:1000574") { // no compression
-                compression = Some(CompressionType::None);
+            } else if param.is_accession("MS:1000522") { // 64-bit integer
+                encoding = BinaryDataEncoding::Int64Little;
+            } else if param.is_accession("MS:1000519") { // 32-bit integer
+                encoding = BinaryDataEncoding::Int32Little;
+            } else if let Some(next) = CompressionType::from_accession(&param.accession) {
+                // zlib、Numpress及其组合，分开写出的Numpress和zlib合并
+                compression = Some(compression.map_or(next, |current: CompressionType| current.merge(next)));
+            } else if param.name.contains("compression") || param.name.contains("Numpress") {
+                warn!(
+                    "Unsupported binary compression {} ({}), treating data as uncompressed",
+                    param.accession, param.name
+                );
+            } else if !param.name.ends_with("array") {
+                warn!("Unknown cvParam {} ({}) in binaryDataArray", param.accession, param.name);
             }
         }
 
-        let mut binary_array = BinaryDataArray::new(length, encoding, decoded_data);
-        if let Some(comp) = compression {
-            binary_array = binary_array.with_compression(comp);
+        let mut binary_array = BinaryDataArray::new(encoding, decoded_data).with_strict(self.options.strict_array_length);
+        if let Some(length) = array.length {
+            binary_array = binary_array.with_expected_length(length);
+        }
+        match compression {
+            Some(comp) => binary_array = binary_array.with_compression(comp),
+            None => warn!("binaryDataArray has no compression cvParam, assuming no compression"),
         }
 
         Ok(binary_array)
     }
 
+    /// 按负强度策略处理峰列表
+    ///
+    /// Reject时保持原样，由`add_peak`报告无效峰数据。
+    fn apply_negative_intensity_policy(&self, id: &str, spectrum: &mut Spectrum, peaks: &mut [(f64, f64)]) {
+        let negative = peaks.iter().filter(|peak| peak.1 < 0.0).count();
+        if negative == 0 {
+            return;
+        }
+        match self.options.negative_intensity {
+            NegativeIntensityPolicy::Reject => {}
+            NegativeIntensityPolicy::ClampToZero => {
+                for peak in peaks.iter_mut().filter(|peak| peak.1 < 0.0) {
+                    peak.1 = 0.0;
+                }
+                warn!("Clamped {} negative intensities to zero in spectrum '{}'", negative, id);
+            }
+            NegativeIntensityPolicy::Keep => {
+                spectrum.allow_negative_intensities = true;
+                debug!("Keeping {} negative intensities in spectrum '{}'", negative, id);
+            }
+        }
+    }
+
     /// 将MZML谱图转换为标准Spectrum
     fn convert_mzml_to_spectrum(&self, mzml_spectrum: MZMLSpectrum) -> ParseResult<Spectrum> {
         let ms_level = mzml_spectrum.get_ms_level()?;
-        let peaks = mzml_spectrum.get_peaks()?;
+        let mut peaks = mzml_spectrum.get_peaks()?;
         
         let mut spectrum = Spectrum::new(ms_level)?;
+        self.apply_negative_intensity_policy(&mzml_spectrum.id, &mut spectrum, &mut peaks);
         
         // 添加质谱峰
         for (mz, intensity) in peaks {
@@ -604,32 +1939,54 @@ impl MZMLParser {
 
         // 设置扫描信息
         let mut scan_info = ScanInfo::default();
-        if let Some(scan) = mzml_spectrum.scan_list.first_scan() {
-            if let Some(scan_number) = scan.scan_number {
-                scan_info.scan_number = scan_number;
-            }
-            if let Some(rt) = scan.get_scan_start_time() {
-                scan_info.retention_time = rt;
-            }
-            if let Some(window) = scan.get_scan_window() {
-                scan_info.scan_window = window;
+        if let Some(scan_number) = mzml_spectrum.get_scan_number() {
+            scan_info.scan_number = scan_number;
+        }
+        let rt_units = self.options.rt_units;
+        if let Some(rt) = mzml_spectrum.get_scan_start_time_as(rt_units) {
+            scan_info.retention_time = rt;
+            match mzml_spectrum.get_scan_start_time_unit() {
+                Some(unit) => scan_info.additional_info.push(KeyValue::new(RT_UNIT_KEY, unit)),
+                None if rt_units == RtUnit::Auto => {
+                    self.missing_rt_units.fetch_add(1, Ordering::Relaxed);
+                }
+                None => {}
             }
         }
+        if let Some(window) = mzml_spectrum.scan_list.first_scan().and_then(|scan| scan.get_scan_window()) {
+            scan_info.scan_window = window;
+        }
+        if let Some(drift_time) = mzml_spectrum.get_ion_mobility_drift_time() {
+            scan_info.drift_time = drift_time;
+        }
+        scan_info.inverse_k0 = mzml_spectrum.get_inverse_reduced_ion_mobility();
+        scan_info.injection_time = mzml_spectrum.get_ion_injection_time();
+        let polarity = mzml_spectrum.get_polarity();
+        scan_info.polarity = polarity;
+        scan_info.native_id = mzml_spectrum.id.clone();
+        scan_info.source_index = mzml_spectrum.index;
         spectrum.set_scan_info(scan_info);
+        if let Some(voltage) = mzml_spectrum.get_faims_compensation_voltage() {
+            spectrum.add_additional_info(FAIMS_CV_KEY, voltage.to_string())?;
+        }
 
         // 设置前体离子信息（仅MS2+）
         if ms_level > 1 {
-            for precursor in &mzml_spectrum.precursors {
+            // 只取第一个前体离子
+            if let Some(precursor) = mzml_spectrum.precursors.first() {
                 let mut precursor_info = PrecursorInfo::default();
                 
                 if let Some(mz) = precursor.get_precursor_mz() {
                     precursor_info.mz = mz;
                 }
                 if let Some(charge) = precursor.get_precursor_charge() {
-                    precursor_info.charge = charge;
+                    precursor_info.charge = polarity.signed_charge(charge);
                 }
                 if let Some(intensity) = precursor.get_precursor_intensity() {
-                    precursor_info.intensity = Some(intensity);
+                    precursor_info.intensity = intensity;
+                }
+                if let Some(ref_scan) = precursor.spectrum_ref.as_deref().and_then(scan_number_from_native_id) {
+                    precursor_info.ref_scan_number = ref_scan;
                 }
                 
                 // 获取激活信息
@@ -637,26 +1994,34 @@ impl MZMLParser {
                     if let Some(method) = activation.get_activation_method() {
                         precursor_info.activation_method = method;
                     }
-                    if let Some(energy) = activation.get_collision_energy() {
+                    precursor_info.collision_energy_ev = activation.get_collision_energy();
+                    precursor_info.normalized_collision_energy = activation.get_normalized_collision_energy();
+                    if let Some(energy) = precursor_info.collision_energy_ev.or(precursor_info.normalized_collision_energy) {
                         precursor_info.activation_energy = energy;
                     }
                 }
 
-                // 获取分离窗口
-                for window in &precursor.isolation_windows {
-                    if let Some(target_mz) = window.get_isolation_window_target_mz() {
-                        precursor_info.isolation_window = (target_mz, target_mz);
-                    }
+                // 获取分离窗口，记录为(目标 - 下偏移, 目标 + 上偏移)，缺少偏移时按0处理；
+                // 有多个窗口时使用第一个，并记录窗口数
+                let first_window = precursor.isolation_windows.first();
+                if let Some(target_mz) = first_window.and_then(|window| window.get_isolation_window_target_mz()) {
+                    let window = first_window.unwrap();
+                    let lower = window.get_isolation_window_lower_offset().unwrap_or(0.0);
+                    let upper = window.get_isolation_window_upper_offset().unwrap_or(0.0);
+                    precursor_info.isolation_window = (target_mz - lower, target_mz + upper);
+                    precursor_info.isolation_target_mz = Some(target_mz);
+                }
+                if precursor.isolation_windows.len() > 1 {
+                    spectrum.add_additional_info(ISOLATION_WINDOW_COUNT_KEY, precursor.isolation_windows.len().to_string())?;
                 }
 
                 spectrum.set_precursor(precursor_info);
-                break; // 只取第一个前体离子
             }
         }
 
         // 添加额外信息
         if let Some(spectrum_type) = mzml_spectrum.get_spectrum_type() {
-            spectrum.add_additional_info("spectrum_type".to_string(), spectrum_type)?;
+            spectrum.add_additional_info(SPECTRUM_TYPE_KEY.to_string(), spectrum_type)?;
         }
         if let Some(tic) = mzml_spectrum.get_total_ion_current() {
             spectrum.add_additional_info("total_ion_current".to_string(), tic.to_string())?;
@@ -668,6 +2033,10 @@ impl MZMLParser {
             spectrum.add_additional_info("base_peak_intensity".to_string(), base_peak_intensity.to_string())?;
         }
 
+        if self.options.centroid && spectrum.is_profile() {
+            spectrum = centroid_spectrum(&spectrum, 0.0, DEFAULT_CENTROID_WINDOW);
+        }
+
         Ok(spectrum)
     }
 }
@@ -683,6 +2052,9 @@ impl MZMLSpectrum {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parsers::mzml::test_data::{build_mzml, corrupt_spectrum_file, encode_f64, write_temp_file, TestSpectrum};
+    use crate::core::types::{Charge, Polarity};
+    use crate::utils::logging::test_logger;
 
     #[test]
     fn test_parser_creation() {
@@ -704,11 +2076,789 @@ mod tests {
         let mut reader = Reader::from_str(xml);
         let mut buf = Vec::new();
         
-        if let Ok(Event::Start(e)) = reader.read_event_into(&mut buf) {
-            let cv_param = parser.parse_cv_param(&e).unwrap();
-            assert_eq!(cv_param.accession, "MS:1000511");
-            assert_eq!(cv_param.name, "ms level");
-            assert_eq!(cv_param.value, "2");
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Empty(e)) => {
+                let cv_param = parser.parse_cv_param(&e).unwrap();
+                assert_eq!(cv_param.accession, "MS:1000511");
+                assert_eq!(cv_param.name, "ms level");
+                assert_eq!(cv_param.value, "2");
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    fn encode_f32(values: &[f32]) -> String {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        STANDARD.encode(bytes)
+    }
+
+    #[test]
+    fn test_referenceable_param_groups() {
+        // 精度、数组类型和MS级别只存在于referenceableParamGroup中
+        let xml = format!(r#"<?xml version="1.0" encoding="utf-8"?>
+<mzML xmlns="http://psi.hupo.org/ms/mzml" version="1.1.0">
+  <referenceableParamGroupList count="3">
+    <referenceableParamGroup id="ms2_spectrum">
+      <cvParam cvRef="MS" accession="MS:1000511" name="ms level" value="2"/>
+    </referenceableParamGroup>
+    <referenceableParamGroup id="mz_params">
+      <cvParam cvRef="MS" accession="MS:1000523" name="64-bit float" value=""/>
+      <cvParam cvRef="MS" accession="MS:1000576" name="no compression" value=""/>
+      <cvParam cvRef="MS" accession="MS:1000514" name="m/z array" value=""/>
+    </referenceableParamGroup>
+    <referenceableParamGroup id="intensity_params">
+      <cvParam cvRef="MS" accession="MS:1000521" name="32-bit float" value=""/>
+      <cvParam cvRef="MS" accession="MS:1000576" name="no compression" value=""/>
+      <cvParam cvRef="MS" accession="MS:1000515" name="intensity array" value=""/>
+    </referenceableParamGroup>
+  </referenceableParamGroupList>
+  <run id="run1">
+    <spectrumList count="1">
+      <spectrum index="0" id="scan=7" defaultArrayLength="3">
+        <referenceableParamGroupRef ref="ms2_spectrum"/>
+        <scanList count="1">
+          <scan>
+            <cvParam cvRef="MS" accession="MS:1000016" name="scan start time" value="12.5" unitCvRef="UO" unitAccession="UO:0000031" unitName="minute"/>
+          </scan>
+        </scanList>
+        <binaryDataArrayList count="2">
+          <binaryDataArray encodedLength="32">
+            <referenceableParamGroupRef ref="mz_params"/>
+            <binary>{}</binary>
+          </binaryDataArray>
+          <binaryDataArray encodedLength="16">
+            <referenceableParamGroupRef ref="intensity_params"/>
+            <binary>{}</binary>
+          </binaryDataArray>
+        </binaryDataArrayList>
+      </spectrum>
+    </spectrumList>
+  </run>
+</mzML>
+"#, encode_f64(&[100.5, 200.25, 300.125]), encode_f32(&[10.0, 20.0, 30.0]));
+        let file = write_temp_file(&xml);
+
+        let spectra = MZMLParser::new()
+            .parse_sequential(file.path().to_str().unwrap())
+            .unwrap();
+        let parallel = MZMLParser::new().parse_parallel(file.path(), 2).unwrap();
+        assert_eq!(format!("{:?}", parallel), format!("{:?}", spectra));
+
+        assert_eq!(spectra.len(), 1);
+        let spectrum = &spectra[0];
+        assert_eq!(spectrum.level, 2);
+        // scan start time以分钟记录
+        assert_eq!(spectrum.scan.retention_time, 750.0);
+        let headers = MZMLParser::new().parse_headers(file.path()).unwrap();
+        let start_time = &headers[0].scan_list.scans[0].cv_params[0];
+        assert_eq!(start_time.unit_cv_ref.as_deref(), Some("UO"));
+        assert_eq!(start_time.unit_accession.as_deref(), Some("UO:0000031"));
+        assert_eq!(start_time.unit_name.as_deref(), Some("minute"));
+        assert_eq!(
+            spectrum.peaks().as_slice(),
+            &[(100.5, 10.0), (200.25, 20.0), (300.125, 30.0)]
+        );
+    }
+
+    /// 生成`count`个谱图的文件，MS1与MS2交替，峰数各不相同
+    fn many_spectra_file(count: u32) -> tempfile::NamedTempFile {
+        let spectra: Vec<TestSpectrum> = (1..=count)
+            .map(|scan| {
+                let peaks = (0..(scan % 50 + 1)).map(|i| (100.0 + i as f64 * 7.5 + scan as f64 * 1e-3, (scan * 10 + i) as f64)).collect();
+                let spectrum = TestSpectrum::new(scan, if scan % 4 == 1 { 1 } else { 2 }, scan as f64 * 0.5, peaks);
+                if scan % 4 == 1 { spectrum } else { spectrum.with_precursor(400.0 + scan as f64, 2) }
+            })
+            .collect();
+        write_temp_file(&build_mzml(&spectra))
+    }
+
+    #[test]
+    fn test_parallel_parse_matches_sequential() {
+        let file = many_spectra_file(200);
+        let parser = MZMLParser::new();
+        let expected = format!("{:?}", parser.parse_sequential(file.path()).unwrap());
+
+        for threads in [0, 1, 3, 8] {
+            let spectra = parser.parse_parallel(file.path(), threads).unwrap();
+            assert_eq!(spectra.len(), 200);
+            assert_eq!(format!("{:?}", spectra), expected, "{} threads", threads);
+        }
+        let spectra = MZMLParser::new_parallel(2).parse(file.path()).unwrap();
+        assert_eq!(format!("{:?}", spectra), expected);
+    }
+
+    #[test]
+    fn test_parse_with_progress_reports_and_cancels() {
+        let file = many_spectra_file(25);
+        let total_bytes = std::fs::metadata(file.path()).unwrap().len();
+        let parser = MZMLParser::new();
+
+        let mut calls = Vec::new();
+        let parsed = parser
+            .parse_with_progress(file.path(), 10, |progress| {
+                calls.push(progress);
+                true
+            })
+            .unwrap();
+        assert!(!parsed.cancelled);
+        assert_eq!(parsed.spectra.len(), 25);
+        let counts: Vec<usize> = calls.iter().map(|progress| progress.spectra_parsed).collect();
+        assert_eq!(counts, vec![10, 20, 25]);
+        assert!(calls.iter().all(|progress| progress.total_bytes == total_bytes));
+        assert!(calls[0].bytes_read < calls[1].bytes_read && calls[1].bytes_read < total_bytes);
+        assert_eq!(calls[2].bytes_read, total_bytes);
+
+        let mut cancelling_calls = 0;
+        let cancelled = parser
+            .parse_with_progress(file.path(), 10, |_| {
+                cancelling_calls += 1;
+                false
+            })
+            .unwrap();
+        assert!(cancelled.cancelled);
+        assert_eq!(cancelling_calls, 1);
+        let sequential = parser.parse_sequential(file.path()).unwrap();
+        let rts: Vec<f64> = cancelled.spectra.iter().map(|s| s.scan.retention_time).collect();
+        let expected: Vec<f64> = sequential[..10].iter().map(|s| s.scan.retention_time).collect();
+        assert_eq!(rts, expected);
+    }
+
+    #[test]
+    fn test_parallel_parse_error_policies() {
+        let file = corrupt_spectrum_file("corrupt_spectrum_parallel_marker");
+        assert!(MZMLParser::new().parse_parallel(file.path(), 2).is_err());
+
+        let parser = MZMLParser::new()
+            .with_options(ParseOptions::new().with_error_policy(SpectrumErrorPolicy::Skip));
+        let rts: Vec<f64> = parser
+            .parse_parallel(file.path(), 2)
+            .unwrap()
+            .iter()
+            .map(|s| s.scan.retention_time)
+            .collect();
+        assert_eq!(rts, vec![1.0, 3.0]);
+    }
+
+    #[test]
+    #[ignore = "benchmark; run with --ignored --nocapture"]
+    fn bench_parallel_parse() {
+        let file = many_spectra_file(20_000);
+        let parser = MZMLParser::new();
+
+        let start = std::time::Instant::now();
+        let sequential = parser.parse_sequential(file.path()).unwrap();
+        let sequential_time = start.elapsed();
+        let start = std::time::Instant::now();
+        let parallel = parser.parse_parallel(file.path(), 0).unwrap();
+        let parallel_time = start.elapsed();
+
+        println!(
+            "sequential {:?}, parallel {:?} ({} threads)",
+            sequential_time, parallel_time, rayon::current_num_threads()
+        );
+        assert_eq!(format!("{:?}", parallel), format!("{:?}", sequential));
+    }
+
+    #[test]
+    fn test_random_access_with_and_without_index() {
+        let plain = many_spectra_file(12);
+        let parser = MZMLParser::new();
+        let expected = parser.parse_sequential(plain.path()).unwrap();
+        let indexed = tempfile::Builder::new().suffix(".mzML").tempfile().unwrap();
+        crate::parsers::mzml::write_spectra(indexed.path(), &expected).unwrap();
+        assert!(MZMLIndex::read(indexed.path()).unwrap().is_some());
+
+        // 索引中的偏移被整体平移后指向错误的谱图，应回退到流式读取
+        let content = std::fs::read_to_string(indexed.path()).unwrap();
+        let shifted = content.replace("<offset idRef=\"controllerType=0 controllerNumber=1 scan=5\">", "<offset idRef=\"controllerType=0 controllerNumber=1 scan=5\">1");
+        let stale = write_temp_file(&shifted);
+
+        for file in [plain.path(), indexed.path(), stale.path()] {
+            assert_eq!(parser.spectrum_count(file).unwrap(), 12);
+            let spectrum = parser.read_spectrum(file, 4).unwrap().unwrap();
+            assert_eq!(spectrum.scan.native_id, expected[4].scan.native_id);
+            assert_eq!(spectrum.peaks(), expected[4].peaks());
+            assert!(parser.read_spectrum(file, 12).unwrap().is_none());
+
+            let by_id = parser.read_spectrum_by_id(file, &expected[7].scan.native_id).unwrap().unwrap();
+            assert_eq!(by_id.peaks(), expected[7].peaks());
+            assert_eq!(by_id.precursor.map(|p| p.mz), expected[7].precursor.as_ref().map(|p| p.mz));
+            assert!(parser.read_spectrum_by_id(file, "scan=999").unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_parse_chromatograms() {
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 1.0, vec![(100.0, 10.0)]),
+            TestSpectrum::new(2, 1, 2.0, vec![(200.0, 20.0)]),
+        ];
+        let time_array = |unit: &str| format!(
+            r#"<cvParam cvRef="MS" accession="MS:1000595" name="time array" value="" unitCvRef="UO" unitAccession="UO:0000031" unitName="{}"/>"#,
+            unit
+        );
+        let array = |param: &str, values: &[f64]| format!(
+            r#"<binaryDataArray encodedLength="0"><cvParam cvRef="MS" accession="MS:1000523" name="64-bit float" value=""/><cvParam cvRef="MS" accession="MS:1000576" name="no compression" value=""/>{}<binary>{}</binary></binaryDataArray>"#,
+            param, encode_f64(values)
+        );
+        let intensity_array = r#"<cvParam cvRef="MS" accession="MS:1000515" name="intensity array" value=""/>"#;
+        let chromatograms = format!(
+            r#"    <chromatogramList count="2" defaultDataProcessingRef="dp">
+      <chromatogram index="0" id="TIC" defaultArrayLength="2">
+        <cvParam cvRef="MS" accession="MS:1000235" name="total ion current chromatogram" value=""/>
+        <binaryDataArrayList count="2">{}{}</binaryDataArrayList>
+      </chromatogram>
+      <chromatogram index="1" id="SRM SIC Q1=500.3 Q3=600.4" defaultArrayLength="1">
+        <cvParam cvRef="MS" accession="MS:1001473" name="selected reaction monitoring chromatogram" value=""/>
+        <precursor>
+          <isolationWindow><cvParam cvRef="MS" accession="MS:1000827" name="isolation window target m/z" value="500.3"/></isolationWindow>
+          <activation><cvParam cvRef="MS" accession="MS:1000133" name="collision-induced dissociation" value=""/></activation>
+        </precursor>
+        <product>
+          <isolationWindow><cvParam cvRef="MS" accession="MS:1000827" name="isolation window target m/z" value="600.4"/></isolationWindow>
+        </product>
+        <binaryDataArrayList count="2">{}{}</binaryDataArrayList>
+      </chromatogram>
+    </chromatogramList>
+"#,
+            array(&time_array("minute"), &[1.0, 2.0]),
+            array(intensity_array, &[10.0, 20.0]),
+            array(&time_array("second"), &[30.0]),
+            array(intensity_array, &[5.0]),
+        );
+        let xml = build_mzml(&spectra).replace("  </run>", &format!("{}  </run>", chromatograms));
+        let file = write_temp_file(&xml);
+
+        let parser = MZMLParser::new();
+        let chromatograms = parser.parse_chromatograms(file.path()).unwrap();
+        assert_eq!(chromatograms.len(), 2);
+        let tic = &chromatograms[0];
+        assert!(tic.is_tic());
+        assert_eq!(tic.id, "TIC");
+        assert_eq!(tic.time_array, vec![1.0, 2.0]);
+        assert_eq!(tic.intensity_array, vec![10.0, 20.0]);
+        assert_eq!(tic.time_unit.as_deref(), Some("minute"));
+        assert!(tic.precursor.is_none());
+
+        let srm = &chromatograms[1];
+        assert_eq!(srm.index, Some(1));
+        assert_eq!(srm.chromatogram_type(), Some("selected reaction monitoring chromatogram"));
+        assert_eq!(srm.precursor_mz(), Some(500.3));
+        assert_eq!(srm.product_mz(), Some(600.4));
+        assert_eq!(srm.time_array, vec![30.0]);
+        assert!(srm.cv_params.iter().all(|param| param.accession != "MS:1000827"));
+
+        // 谱图解析不受色谱图影响
+        assert_eq!(parser.parse_sequential(file.path()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_spectrum_headers_match_full_parse() {
+        let file = many_spectra_file(9);
+        let parser = MZMLParser::new();
+        let headers = parser.spectrum_headers(file.path()).unwrap();
+        let spectra = parser.parse_sequential(file.path()).unwrap();
+
+        assert_eq!(headers.len(), spectra.len());
+        for (header, spectrum) in headers.iter().zip(&spectra) {
+            assert_eq!(header.id, spectrum.scan.native_id);
+            assert_eq!(header.index, spectrum.scan.source_index);
+            assert_eq!(header.ms_level, spectrum.level);
+            assert_eq!(header.retention_time, spectrum.scan.retention_time);
+            assert_eq!(header.precursor_mz, spectrum.precursor.as_ref().map(|p| p.mz));
+            assert_eq!(header.peak_count, spectrum.peak_count());
+        }
+        assert_eq!(headers[1].charge, Some(2));
+        assert_eq!(headers.iter().filter(|header| header.ms_level == 1).count(), 3);
+    }
+
+    #[test]
+    fn test_unknown_param_group_ref() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<mzML xmlns="http://psi.hupo.org/ms/mzml" version="1.1.0">
+  <run id="run1">
+    <spectrumList count="1">
+      <spectrum index="0" id="scan=1" defaultArrayLength="0">
+        <referenceableParamGroupRef ref="missing"/>
+      </spectrum>
+    </spectrumList>
+  </run>
+</mzML>
+"#;
+        let file = write_temp_file(xml);
+
+        let result = MZMLParser::new().parse_sequential(file.path().to_str().unwrap());
+        match result {
+            Err(ParseError::InSpectrum { spectrum_id, index, source, .. }) => {
+                assert_eq!(spectrum_id, "scan=1");
+                assert_eq!(index, Some(0));
+                assert!(matches!(*source, ParseError::InvalidFormat(_)));
+            }
+            other => panic!("expected a spectrum error, got {:?}", other.map(|spectra| spectra.len())),
+        }
+    }
+
+    #[test]
+    fn test_scan_table_matches_full_parse() {
+        let spectra: Vec<TestSpectrum> = (1..=6u32)
+            .map(|scan| {
+                let peaks = vec![(100.0 + scan as f64, 10.0 * scan as f64), (300.0, 5.0)];
+                let spectrum = TestSpectrum::new(scan, if scan % 3 == 1 { 1 } else { 2 }, scan as f64 * 2.5, peaks)
+                    .with_injection_time(20.0 + scan as f64);
+                if scan % 3 == 1 { spectrum } else { spectrum.with_precursor(400.0 + scan as f64, 2) }
+            })
+            .collect();
+        let file = write_temp_file(&build_mzml(&spectra));
+        let path = file.path().to_str().unwrap();
+
+        let parser = MZMLParser::new();
+        let table = parser.scan_table(path).unwrap();
+        let expected = ScanTable::from_spectra(&parser.parse_sequential(path).unwrap());
+
+        assert_eq!(table.len(), spectra.len());
+        assert_eq!(table, expected);
+        assert_eq!(table.injection_time[0], Some(21.0));
+        assert_eq!(table.precursor_mz[1], Some(402.0));
+    }
+
+    #[test]
+    fn test_skip_corrupt_spectrum_logs_warning() {
+        test_logger::install();
+        let marker = "corrupt_spectrum_skip_marker";
+        let file = corrupt_spectrum_file(marker);
+
+        let parser = MZMLParser::new()
+            .with_options(ParseOptions::new().with_error_policy(SpectrumErrorPolicy::Skip));
+        let spectra = parser.parse_sequential(file.path().to_str().unwrap()).unwrap();
+        let rts: Vec<f64> = spectra.iter().map(|s| s.scan.retention_time).collect();
+        assert_eq!(rts, vec![1.0, 3.0]);
+
+        let records = test_logger::records_containing(marker);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].0, log::Level::Warn);
+    }
+
+    #[test]
+    fn test_fail_on_corrupt_spectrum_by_default() {
+        let file = corrupt_spectrum_file("corrupt_spectrum_fail_marker");
+        let result = MZMLParser::new().parse_sequential(file.path().to_str().unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_iter_spectra_continues_after_corrupt_spectrum() {
+        let marker = "corrupt_spectrum_iter_marker";
+        let file = corrupt_spectrum_file(marker);
+
+        let mut iter = MZMLParser::new().iter_spectra(file.path()).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().scan.retention_time, 1.0);
+        assert!(iter.next().unwrap().is_err());
+        assert_eq!(iter.last_id(), marker);
+        assert_eq!(iter.next().unwrap().unwrap().scan.retention_time, 3.0);
+        assert!(iter.next().is_none());
+
+        let skipping = MZMLParser::new().with_options(ParseOptions::new().with_error_policy(SpectrumErrorPolicy::Skip));
+        let rts: Vec<f64> = skipping
+            .iter_spectra(file.path())
+            .unwrap()
+            .map(|spectrum| spectrum.unwrap().scan.retention_time)
+            .collect();
+        assert_eq!(rts, vec![1.0, 3.0]);
+    }
+
+    /// 10个谱图，第4个谱图的base64被截断，第7个谱图的cvParam属性缺少引号
+    fn damaged_spectra_xml() -> String {
+        let spectra: Vec<TestSpectrum> = (1..=10)
+            .map(|scan| TestSpectrum::new(scan, 1, scan as f64, vec![(100.0 * scan as f64, 10.0)]))
+            .collect();
+        let mut xml = build_mzml(&spectra);
+
+        let truncated = xml.find("scan=4\"").unwrap();
+        let binary_end = truncated + xml[truncated..].find("</binary>").unwrap();
+        xml.replace_range(binary_end - 3..binary_end, "");
+
+        let broken = xml.find("scan=7\"").unwrap();
+        let accession = broken + xml[broken..].find("accession=\"MS:1000511\"").unwrap();
+        xml.replace_range(accession..accession + "accession=\"MS:1000511\"".len(), "accession=MS:1000511");
+        xml
+    }
+
+    #[test]
+    fn test_spectrum_errors_carry_location() {
+        let xml = damaged_spectra_xml();
+        let file = write_temp_file(&xml);
+
+        let error = MZMLParser::new().parse_sequential(file.path()).unwrap_err();
+        let ParseError::InSpectrum { spectrum_id, index, byte_offset, .. } = &error else {
+            panic!("expected a spectrum error, got {}", error);
+        };
+        assert!(spectrum_id.ends_with("scan=4"));
+        assert_eq!(*index, Some(3));
+        assert!(xml[*byte_offset as usize..].starts_with("<spectrum index=\"3\""));
+        assert!(error.to_string().contains(&format!("at byte {}", byte_offset)));
+
+        let errors: Vec<ParseError> = MZMLParser::new()
+            .iter_spectra(file.path())
+            .unwrap()
+            .filter_map(Result::err)
+            .collect();
+        assert_eq!(errors.len(), 2);
+        match &errors[1] {
+            ParseError::InSpectrum { index, byte_offset, source, .. } => {
+                assert_eq!(*index, Some(6));
+                assert!(xml[*byte_offset as usize..].starts_with("<spectrum index=\"6\""));
+                assert!(source.to_string().contains("Malformed attribute in <cvParam>"));
+            }
+            other => panic!("expected a spectrum error, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_lenient_parse_records_skipped_spectra() {
+        let xml = damaged_spectra_xml();
+        let file = write_temp_file(&xml);
+        let parser = MZMLParser::new().with_options(ParseOptions::new().with_lenient(true));
+
+        let spectra = parser.parse_sequential(file.path()).unwrap();
+        assert_eq!(spectra.len(), 8);
+        let skipped = parser.skipped_spectra();
+        let indices: Vec<Option<usize>> = skipped.iter().map(|skipped| skipped.index).collect();
+        assert_eq!(indices, vec![Some(3), Some(6)]);
+        for record in &skipped {
+            assert!(xml[record.byte_offset as usize..].starts_with("<spectrum"));
+            assert!(xml[record.byte_offset as usize..].contains(&record.spectrum_id));
+            assert!(!record.error.is_empty());
+        }
+
+        let parallel = parser.parse_parallel(file.path(), 2).unwrap();
+        assert_eq!(parallel.len(), 8);
+        assert_eq!(parser.skipped_spectra(), skipped);
+    }
+
+    #[test]
+    fn test_truncated_file_reports_xml_position() {
+        let xml = damaged_spectra_xml();
+        let spectrum = xml.find("scan=5\"").unwrap();
+        let cut = spectrum + xml[spectrum..].find("<binary>").unwrap() + "<binary>".len() + 2;
+        let file = write_temp_file(&xml[..cut]);
+
+        let parser = MZMLParser::new().with_options(ParseOptions::new().with_lenient(true));
+        match parser.parse_sequential(file.path()) {
+            Err(ParseError::Xml { byte_offset, spectrum_id, element, .. }) => {
+                assert!(byte_offset.is_some_and(|offset| offset as usize <= cut));
+                assert!(spectrum_id.is_some_and(|id| id.ends_with("scan=5")));
+                assert_eq!(element.as_deref(), Some("binaryDataArray"));
+            }
+            other => panic!("expected an XML error, got {:?}", other.map(|spectra| spectra.len())),
+        }
+    }
+
+    #[test]
+    fn test_iter_spectra_matches_sequential_parse() {
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 1.0, vec![(100.0, 10.0), (150.0, 15.0)]),
+            TestSpectrum::new(2, 2, 2.0, vec![(200.0, 20.0)]).with_precursor(150.0, 2),
+            TestSpectrum::new(3, 1, 3.0, vec![(300.0, 30.0)]),
+        ];
+        let xml = build_mzml(&spectra);
+        let file = write_temp_file(&xml);
+
+        let parser = MZMLParser::new();
+        let expected = parser.parse_sequential(file.path()).unwrap();
+        let iterated: Vec<Spectrum> = parser.iter_spectra(file.path()).unwrap().collect::<ParseResult<_>>().unwrap();
+        assert_eq!(iterated.len(), expected.len());
+        for (iterated, expected) in iterated.iter().zip(&expected) {
+            assert_eq!(iterated.peaks(), expected.peaks());
+            assert_eq!(iterated.scan.native_id, expected.scan.native_id);
+            assert_eq!(iterated.precursor, expected.precursor);
+        }
+
+        // XML截断后产出错误并结束迭代
+        let truncated = write_temp_file(&xml[..xml.rfind("<spectrum ").unwrap() + 40]);
+        let results: Vec<_> = parser.iter_spectra(truncated.path()).unwrap().collect();
+        assert_eq!(results.len(), 3);
+        assert!(results[..2].iter().all(|result| result.is_ok()));
+        assert!(results[2].is_err());
+    }
+
+    #[test]
+    fn test_numpress_arrays() {
+        use crate::parsers::numpress::Numpress;
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let mz = vec![150.0012, 301.5521, 455.2873, 902.4471];
+        let intensity = vec![12.0, 5000.0, 873.0, 1.0e6];
+        let xml = build_mzml(&[TestSpectrum::new(1, 1, 1.0, mz.iter().copied().zip(intensity.iter().copied()).collect())]);
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(&Numpress::Linear.encode(&mz).unwrap()).unwrap();
+        let linear_zlib = STANDARD.encode(zlib.finish().unwrap());
+        let slof = STANDARD.encode(Numpress::Slof.encode(&intensity).unwrap());
+        let no_compression = r#"accession="MS:1000576" name="no compression""#;
+        let xml = xml
+            .replacen(&encode_f64(&mz), &linear_zlib, 1)
+            .replacen(&encode_f64(&intensity), &slof, 1)
+            .replacen(no_compression, r#"accession="MS:1002746" name="MS-Numpress linear prediction compression followed by zlib compression""#, 1)
+            .replacen(no_compression, r#"accession="MS:1002314" name="MS-Numpress short logged float compression""#, 1);
+        let file = write_temp_file(&xml);
+
+        let spectra = MZMLParser::new().with_options(ParseOptions::new().with_strict_array_length(true)).parse_sequential(file.path()).unwrap();
+        let peaks = &spectra[0].peaks();
+        assert_eq!(peaks.len(), 4);
+        for ((decoded_mz, decoded_intensity), (mz, intensity)) in peaks.iter().zip(mz.iter().zip(&intensity)) {
+            assert!((decoded_mz - mz).abs() < 1e-6);
+            assert!((decoded_intensity - intensity).abs() <= intensity * 2e-4);
+        }
+    }
+
+    #[test]
+    fn test_negative_intensity_policies() {
+        test_logger::install();
+        let marker = "negative_intensity_marker";
+        let mut spectrum = TestSpectrum::new(1, 1, 1.0, vec![(100.0, 10.0), (150.0, -0.5), (200.0, 20.0)]);
+        spectrum.id = marker.to_string();
+        let file = write_temp_file(&build_mzml(&[spectrum]));
+        let path = file.path().to_str().unwrap();
+
+        // 默认截断为0，并记录一条包含数量的警告
+        let spectra = MZMLParser::new().parse_sequential(path).unwrap();
+        assert_eq!(spectra[0].peaks(), vec![(100.0, 10.0), (150.0, 0.0), (200.0, 20.0)]);
+        let warnings: Vec<_> = test_logger::records_containing(marker)
+            .into_iter()
+            .filter(|(level, _)| *level == log::Level::Warn)
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].1.contains("Clamped 1 negative"));
+
+        let reject = ParseOptions::new().with_negative_intensity_policy(NegativeIntensityPolicy::Reject);
+        assert!(MZMLParser::new().with_options(reject).parse_sequential(path).is_err());
+
+        let keep = ParseOptions::new().with_negative_intensity_policy(NegativeIntensityPolicy::Keep);
+        let spectra = MZMLParser::new().with_options(keep).parse_sequential(path).unwrap();
+        assert_eq!(spectra[0].peak(1).unwrap(), (150.0, -0.5));
+        assert!(spectra[0].allow_negative_intensities);
+        assert!(spectra[0].validate().is_ok());
+        assert_eq!(spectra[0].total_ion_current(), 29.5);
+    }
+
+    #[test]
+    fn test_array_length_not_taken_from_encoded_length() {
+        let peaks: Vec<(f64, f64)> = (0..7).map(|i| (100.0 + i as f64, 10.0 * i as f64)).collect();
+        let file = write_temp_file(&build_mzml(&[TestSpectrum::new(1, 1, 1.0, peaks.clone())]));
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        assert!(content.contains("encodedLength=\"76\""));
+
+        let spectra = MZMLParser::new().parse_sequential(file.path()).unwrap();
+        assert_eq!(spectra[0].peaks(), peaks);
+    }
+
+    #[test]
+    fn test_default_array_length_mismatch_warns_or_fails() {
+        test_logger::install();
+        let xml = build_mzml(&[TestSpectrum::new(1, 1, 1.0, vec![(100.0, 10.0), (200.0, 20.0)])])
+            .replace("defaultArrayLength=\"2\"", "defaultArrayLength=\"987\"");
+        let file = write_temp_file(&xml);
+
+        // 按实际数据解码，并记录期望与实际个数
+        let spectra = MZMLParser::new().parse_sequential(file.path()).unwrap();
+        assert_eq!(spectra[0].peaks(), vec![(100.0, 10.0), (200.0, 20.0)]);
+        let warnings = test_logger::records_containing("expected 987 values, decoded 2");
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings.iter().all(|(level, _)| *level == log::Level::Warn));
+
+        let strict = ParseOptions::new().with_strict_array_length(true);
+        let error = MZMLParser::new().with_options(strict).parse_sequential(file.path()).unwrap_err();
+        match error {
+            ParseError::InSpectrum { source, .. } => {
+                assert!(matches!(*source, ParseError::CorruptedData(ref message) if message.contains("expected 987")));
+            }
+            other => panic!("expected a spectrum error, got {}", other),
         }
     }
+
+    #[test]
+    fn test_normalized_and_absolute_collision_energy() {
+        let spectra = vec![
+            TestSpectrum::new(1, 2, 1.0, vec![(200.0, 10.0)])
+                .with_precursor(500.0, 2)
+                .with_activation_param("MS:1000138", "normalized collision energy", 27.0, "percent"),
+            TestSpectrum::new(2, 2, 2.0, vec![(200.0, 10.0)])
+                .with_precursor(600.0, 2)
+                .with_activation_param("MS:1000045", "collision energy", 35.0, "electronvolt"),
+        ];
+        let file = write_temp_file(&build_mzml(&spectra));
+        let parsed = MZMLParser::new().parse_sequential(file.path().to_str().unwrap()).unwrap();
+
+        let thermo = parsed[0].precursor.as_deref().unwrap();
+        assert_eq!(thermo.normalized_collision_energy, Some(27.0));
+        assert_eq!(thermo.collision_energy_ev, None);
+        assert_eq!(thermo.activation_energy, 27.0);
+
+        let sciex = parsed[1].precursor.as_deref().unwrap();
+        assert_eq!(sciex.collision_energy_ev, Some(35.0));
+        assert_eq!(sciex.normalized_collision_energy, None);
+        assert_eq!(sciex.activation_energy, 35.0);
+    }
+
+    #[test]
+    fn test_isolation_window_bounds() {
+        let spectra = vec![
+            TestSpectrum::new(1, 2, 1.0, vec![(200.0, 10.0)])
+                .with_precursor(512.5, 0)
+                .with_isolation_window(512.5, 12.5, 12.5),
+            TestSpectrum::new(2, 2, 2.0, vec![(200.0, 10.0)]).with_precursor(600.0, 2),
+        ];
+        let file = write_temp_file(&build_mzml(&spectra));
+        let parsed = MZMLParser::new().parse_sequential(file.path()).unwrap();
+
+        assert_eq!(parsed[0].precursor.as_deref().unwrap().isolation_window, (500.0, 525.0));
+        assert_eq!(parsed[0].precursor.as_deref().unwrap().isolation_target_mz, Some(512.5));
+        assert_eq!(parsed[1].precursor.as_deref().unwrap().isolation_window, (0.0, 0.0));
+        assert_eq!(parsed[1].precursor.as_deref().unwrap().isolation_target_mz, None);
+    }
+
+    #[test]
+    fn test_scan_numbers_from_native_ids() {
+        let mut spectra = vec![
+            TestSpectrum::new(101, 1, 1.0, vec![(200.0, 10.0)]),
+            TestSpectrum::new(102, 2, 2.0, vec![(200.0, 10.0)]).with_precursor(500.0, 2),
+            TestSpectrum::new(103, 2, 3.0, vec![(200.0, 10.0)]).with_precursor(600.0, 2),
+            TestSpectrum::new(104, 2, 4.0, vec![(200.0, 10.0)]).with_precursor(700.0, 2),
+        ];
+        spectra[2].id = "sample=1 period=1 cycle=57 experiment=2".to_string();
+        spectra[3].id = "index=3".to_string();
+        let xml = build_mzml(&spectra).replacen(
+            "<precursor>",
+            r#"<precursor spectrumRef="controllerType=0 controllerNumber=1 scan=101">"#,
+            1,
+        );
+        let file = write_temp_file(&xml);
+        let parsed = MZMLParser::new().parse_sequential(file.path()).unwrap();
+
+        let scan_numbers: Vec<_> = parsed.iter().map(|spectrum| spectrum.scan.scan_number).collect();
+        assert_eq!(scan_numbers, vec![101, 102, 57, 4]);
+        assert_eq!(parsed[1].precursor.as_deref().unwrap().ref_scan_number, 101);
+        assert_eq!(parsed[2].precursor.as_deref().unwrap().ref_scan_number, constants::DEFAULT_SCAN_NUMBER);
+
+        // 头信息与完整解析一致
+        let headers = MZMLParser::new().spectrum_headers(file.path()).unwrap();
+        assert_eq!(headers.iter().map(|header| header.scan_number).collect::<Vec<_>>(), scan_numbers);
+    }
+
+    #[test]
+    fn test_retention_time_units() {
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 1.5, vec![(200.0, 10.0)]),
+            TestSpectrum::new(2, 1, 2.5, vec![(200.0, 10.0)]),
+            TestSpectrum::new(3, 1, 90.0, vec![(200.0, 10.0)]),
+        ];
+        let second_unit = r#" unitCvRef="UO" unitAccession="UO:0000010" unitName="second""#;
+        // 第1个谱图只给出分钟的单位访问号，第2个谱图未记录单位，第3个谱图以秒记录
+        let xml = build_mzml(&spectra)
+            .replacen(second_unit, r#" unitCvRef="UO" unitAccession="UO:0000031""#, 1)
+            .replacen(second_unit, "", 1);
+        let file = write_temp_file(&xml);
+        let rt_unit = |spectrum: &Spectrum| {
+            spectrum.scan.additional_info.iter().find(|kv| kv.key == RT_UNIT_KEY).map(|kv| kv.value.clone())
+        };
+
+        let parser = MZMLParser::new();
+        let parsed = parser.parse_sequential(file.path()).unwrap();
+        let rts: Vec<f64> = parsed.iter().map(|spectrum| spectrum.scan.retention_time).collect();
+        assert_eq!(rts, vec![90.0, 2.5, 90.0]);
+        assert_eq!(rt_unit(&parsed[0]).as_deref(), Some("UO:0000031"));
+        assert_eq!(rt_unit(&parsed[1]), None);
+        assert_eq!(rt_unit(&parsed[2]).as_deref(), Some("second"));
+        assert_eq!(parser.missing_rt_unit_count(), 1);
+
+        parser.parse_parallel(file.path(), 2).unwrap();
+        assert_eq!(parser.missing_rt_unit_count(), 1);
+
+        // 指定单位时忽略文件中的单位，也不再统计缺失单位
+        let minutes = MZMLParser::new().with_options(ParseOptions::new().with_rt_units(RtUnit::Minutes));
+        let rts: Vec<f64> = minutes
+            .parse_sequential(file.path())
+            .unwrap()
+            .iter()
+            .map(|spectrum| spectrum.scan.retention_time)
+            .collect();
+        assert_eq!(rts, vec![90.0, 150.0, 5400.0]);
+        assert_eq!(minutes.missing_rt_unit_count(), 0);
+
+        let seconds = MZMLParser::new().with_options(ParseOptions::new().with_rt_units(RtUnit::Seconds));
+        assert_eq!(seconds.parse_sequential(file.path()).unwrap()[0].scan.retention_time, 1.5);
+    }
+
+    #[test]
+    fn test_ion_mobility_scan_params() {
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 1.0, vec![(200.0, 10.0)]),
+            TestSpectrum::new(2, 1, 2.0, vec![(200.0, 10.0)]),
+        ];
+        let mobility = r#"<cvParam cvRef="MS" accession="MS:1002476" name="ion mobility drift time" value="25.0" unitCvRef="UO" unitAccession="UO:0000028" unitName="millisecond"/>
+            <cvParam cvRef="MS" accession="MS:1002815" name="inverse reduced ion mobility" value="0.987" unitCvRef="MS" unitAccession="MS:1002814" unitName="volt-second per square centimeter"/>
+            <cvParam cvRef="MS" accession="MS:1001581" name="FAIMS compensation voltage" value="-45.0" unitCvRef="UO" unitAccession="UO:0000218" unitName="volt"/>
+          </scan>"#;
+        let xml = build_mzml(&spectra).replacen("</scan>", mobility, 1);
+        let file = write_temp_file(&xml);
+        let parsed = MZMLParser::new().parse_sequential(file.path()).unwrap();
+
+        assert!((parsed[0].scan.drift_time - 0.025).abs() < 1e-12);
+        assert_eq!(parsed[0].scan.inverse_k0, Some(0.987));
+        assert!(parsed[0]
+            .additional_info
+            .iter()
+            .any(|kv| kv.key == FAIMS_CV_KEY && kv.value == "-45"));
+        assert_eq!(parsed[1].scan.drift_time, 0.0);
+        assert_eq!(parsed[1].scan.inverse_k0, None);
+        assert!(parsed[1].additional_info.iter().all(|kv| kv.key != FAIMS_CV_KEY));
+    }
+
+    #[test]
+    fn test_negative_mode_precursor_charge() {
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 1.0, vec![(600.0, 10.0)]).with_polarity(false),
+            TestSpectrum::new(2, 2, 2.0, vec![(200.0, 10.0)]).with_precursor(600.5, 2).with_polarity(false),
+            TestSpectrum::new(3, 2, 3.0, vec![(200.0, 10.0)]).with_precursor(600.5, 2).with_polarity(true),
+            TestSpectrum::new(4, 2, 4.0, vec![(200.0, 10.0)]).with_precursor(600.5, 2),
+        ];
+        let file = write_temp_file(&build_mzml(&spectra));
+        let parser = MZMLParser::new();
+        let parsed = parser.parse_sequential(file.path()).unwrap();
+
+        assert_eq!(parsed[1].scan.polarity, Polarity::Negative);
+        let charges: Vec<Charge> = parsed[1..].iter().map(|s| s.precursor.as_deref().unwrap().charge).collect();
+        assert_eq!(charges, vec![-2, 2, 2]);
+        assert_eq!(parser.scan_table(file.path()).unwrap().charge, vec![None, Some(-2), Some(2), Some(2)]);
+
+        // 写出时charge state为电荷数，符号由极性保留
+        let output = tempfile::Builder::new().suffix(".mzML").tempfile().unwrap();
+        crate::parsers::mzml::writer::write_spectra(output.path(), &parsed).unwrap();
+        let reparsed = parser.parse_sequential(output.path()).unwrap();
+        assert_eq!(reparsed[1].precursor.as_deref().unwrap().charge, -2);
+        assert!(!std::fs::read_to_string(output.path()).unwrap().contains("value=\"-2\""));
+    }
+
+    #[test]
+    fn test_dia_isolation_window_uses_first_window_target() {
+        // DIA窗口中心与选中离子m/z不同，且额外写入第二个窗口
+        let spectra = vec![TestSpectrum::new(1, 2, 1.0, vec![(200.0, 10.0)])
+            .with_precursor(405.2, 0)
+            .with_isolation_window(412.5, 12.5, 12.5)];
+        let second_window = r#"<isolationWindow><cvParam cvRef="MS" accession="MS:1000827" name="isolation window target m/z" value="437.5"/></isolationWindow>
+            <selectedIonList"#;
+        let xml = build_mzml(&spectra).replacen("<selectedIonList", second_window, 1);
+        let file = write_temp_file(&xml);
+        let parsed = MZMLParser::new().parse_sequential(file.path()).unwrap();
+
+        let precursor = parsed[0].precursor.as_deref().unwrap();
+        assert_eq!(precursor.mz, 405.2);
+        assert_eq!(precursor.isolation_window, (400.0, 425.0));
+        assert_eq!(precursor.isolation_target_mz, Some(412.5));
+        assert!(parsed[0]
+            .additional_info
+            .iter()
+            .any(|kv| kv.key == ISOLATION_WINDOW_COUNT_KEY && kv.value == "2"));
+    }
 }
@@ -3,16 +3,47 @@
 //! 这个模块提供了与原Python MZMLReader完全兼容的接口
 
 #[cfg(feature = "python")]
-use crate::core::ms_object::MSObject;
-use crate::core::spectrum::Spectrum;
-use crate::parsers::mzml::parser::MZMLParser;
-use crate::parsers::common::ParseResult;
+use crate::core::ms_object::{quality_to_dict, MSObject};
+#[cfg(feature = "python")]
+use crate::core::quality;
+#[cfg(feature = "python")]
+use crate::core::scan_table::ScanTable;
+#[cfg(feature = "python")]
+use crate::core::spectrum::{BinnedSpectraIndex, SharedSpectra, SpectraIndex, SpectraStore, Spectrum};
+#[cfg(feature = "python")]
+use crate::analysis::precursor_correction;
+#[cfg(feature = "python")]
+use crate::analysis::charge;
+#[cfg(feature = "python")]
+use crate::conversion::cache::{self, CacheStamp};
+#[cfg(feature = "python")]
+use crate::analysis::fragment_search::{self, FragmentMatch};
+#[cfg(feature = "python")]
+use crate::dia::scheme::IsolationScheme;
+use crate::analysis::segments::{self, SegmentBy};
+use crate::parsers::mzml::parser::{MZMLParser, ParsedSpectra};
+#[cfg(feature = "python")]
+use crate::parsers::mzml::chromatogram::Chromatogram;
+#[cfg(feature = "python")]
+use crate::parsers::mzml::header::SpectrumHeader;
+#[cfg(feature = "python")]
+use crate::parsers::mzml::parser::FileSpectrumIter;
+
+#[cfg(feature = "python")]
+use crate::xic::{build_bpc, build_tic, StreamingXICExtractor, XICResult, XICSExtractor, DEFAULT_REORDER_WINDOW};
+#[cfg(feature = "python")]
+use crate::parsers::common::{ParseError, ParseOptions, SkippedSpectrum, SpectrumErrorPolicy};
+#[cfg(feature = "python")]
+use crate::core::types::{PyToleranceModel, Tolerance};
+#[cfg(feature = "python")]
+use std::collections::HashMap;
+#[cfg(feature = "python")]
+use std::path::{Path, PathBuf};
 
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
 #[cfg(feature = "python")]
-use pyo3::types::{PyList, PyAny};
-use std::sync::Arc;
+use pyo3::types::{PyDict, PyList, PyAny};
 
 /// Python兼容的MZML读取器
 #[cfg(feature = "python")]
@@ -22,20 +53,36 @@ pub struct MZMLReader {
 }
 
 /// Python兼容的MZML对象
+///
+/// 谱图以[`SharedSpectra`]共享保存，由它创建的XIC提取器等工具复用同一份数据。
 #[cfg(feature = "python")]
 #[pyclass]
 pub struct MZMLObject {
-    pub spectra: Vec<MSObject>,
+    pub spectra: SharedSpectra,
     pub file_info: MZMLFileInfo,
 }
 
+/// 逐个读取mzML文件中谱图的迭代器，由`MZMLReader.iter`创建
+#[cfg(feature = "python")]
+#[pyclass]
+pub struct MZMLSpectrumIterator {
+    spectra: FileSpectrumIter,
+}
+
+/// mzML文件中的色谱图，由`MZMLReader.read_chromatograms`创建
+#[cfg(feature = "python")]
+#[pyclass]
+pub struct MZMLChromatogram {
+    chromatogram: Chromatogram,
+}
+
 /// MZML文件信息
 #[cfg(feature = "python")]
 #[pyclass]
 #[derive(Debug, Clone)]
 pub struct MZMLFileInfo {
     #[pyo3(get)]
-    pub file_path: String,
+    pub file_path: PathBuf,
     #[pyo3(get)]
     pub spectrum_count: usize,
     #[pyo3(get)]
@@ -46,86 +93,271 @@ pub struct MZMLFileInfo {
     pub file_format: String,
     #[pyo3(get)]
     pub version: Option<String>,
+    /// 按宽松模式跳过的谱图
+    pub skipped_spectra: Vec<SkippedSpectrum>,
+    /// 解析是否被进度回调取消（此时只包含取消前解析的谱图）
+    #[pyo3(get)]
+    pub cancelled: bool,
 }
 
 #[cfg(feature = "python")]
 impl MZMLFileInfo {
-    pub fn new(file_path: String) -> Self {
+    pub fn new(file_path: impl Into<PathBuf>) -> Self {
         Self {
-            file_path,
+            file_path: file_path.into(),
             spectrum_count: 0,
             ms1_count: 0,
             ms2_count: 0,
             file_format: "mzML".to_string(),
             version: None,
+            skipped_spectra: Vec::new(),
+            cancelled: false,
         }
     }
+
+    /// 按谱图列表重新统计谱图数和各级别谱图数
+    pub fn update_counts(&mut self, spectra: &[Spectrum]) {
+        self.spectrum_count = spectra.len();
+        self.ms1_count = spectra.iter().filter(|spectrum| spectrum.is_ms1()).count();
+        self.ms2_count = spectra.iter().filter(|spectrum| spectrum.is_ms2()).count();
+    }
+
+    /// 按谱图头信息统计谱图数和各级别谱图数
+    pub fn update_counts_from_headers(&mut self, headers: &[SpectrumHeader]) {
+        self.spectrum_count = headers.len();
+        self.ms1_count = headers.iter().filter(|header| header.ms_level == 1).count();
+        self.ms2_count = headers.iter().filter(|header| header.ms_level == 2).count();
+    }
 }
 
 #[cfg(feature = "python")]
-#[pymethods]
-impl MZMLReader {
-    /// 创建新的MZML读取器
-    #[new]
-    fn new() -> Self {
+impl MZMLObject {
+    /// 修改谱图列表并同步文件信息中的计数；谱图仍被提取器等共享时先复制一份
+    fn modify_spectra<R>(&mut self, modify: impl FnOnce(&mut Vec<Spectrum>) -> R) -> R {
+        let result = SpectraStore::update(&mut self.spectra, modify);
+        self.file_info.update_counts(&self.spectra);
+        result
+    }
+}
+
+/// 将扫描编号依次重新编号为`start`, `start + 1`, ...
+///
+/// 前体离子的参考扫描编号指向之前出现的同号谱图时同步更新。
+#[cfg(feature = "python")]
+fn renumber(spectra: &mut [Spectrum], start: u32) {
+    let mut renumbered = HashMap::new();
+    for (offset, spectrum) in spectra.iter_mut().enumerate() {
+        if let Some(precursor) = spectrum.precursor.as_deref_mut() {
+            if let Some(&new_ref) = renumbered.get(&precursor.ref_scan_number) {
+                precursor.ref_scan_number = new_ref;
+            }
+        }
+        let new_number = start + offset as u32;
+        renumbered.insert(spectrum.scan.scan_number, new_number);
+        spectrum.scan.scan_number = new_number;
+    }
+}
+
+/// 经由`<filename>.omsu`缓存解析：缓存与源文件的大小、修改时间和质心化选项一致时直接读入，
+/// 否则用`parse`解析源文件并重新写出缓存；缓存写入失败只记录警告
+///
+/// 宽松模式下跳过了谱图或解析被取消时不写出缓存，缓存中总是文件的全部谱图。
+#[cfg(feature = "python")]
+fn parse_with_cache(
+    parser: &MZMLParser,
+    filename: &Path,
+    parse: impl FnOnce() -> PyResult<ParsedSpectra>,
+) -> PyResult<ParsedSpectra> {
+    let stamp = CacheStamp::of(filename, parser.options().centroid as u32)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+    let cache_path = cache::cache_path(filename);
+    if let Some(spectra) = cache::load_fresh_run(&cache_path, stamp) {
+        return Ok(ParsedSpectra { spectra, cancelled: false });
+    }
+
+    let parsed = parse()?;
+    if parsed.cancelled || !parser.skipped_spectra().is_empty() {
+        return Ok(parsed);
+    }
+    if let Err(e) = cache::save_run_with_stamp(&parsed.spectra, &cache_path, stamp) {
+        log::warn!("Failed to write cache {}: {}", cache_path.display(), e);
+    }
+    Ok(parsed)
+}
+
+/// 顺序解析文件，每读完`every`个谱图以(已解析谱图数, 已读取字节数, 文件总字节数)调用`callback`，
+/// 读完文件后再调用一次
+///
+/// 解析期间释放GIL，只在调用回调时获取。`callback`返回False时取消解析，返回已解析的谱图；
+/// `callback`抛出的异常中止解析并原样抛出。
+#[cfg(feature = "python")]
+pub(crate) fn parse_with_progress_callback(
+    py: Python,
+    parser: &MZMLParser,
+    filename: &Path,
+    callback: &PyObject,
+    every: usize,
+) -> PyResult<ParsedSpectra> {
+    let mut callback_error = None;
+    let result = py.allow_threads(|| {
+        parser.parse_with_progress(filename, every, |progress| {
+            Python::with_gil(|py| {
+                match callback.call1(py, (progress.spectra_parsed, progress.bytes_read, progress.total_bytes)) {
+                    Ok(value) => !matches!(value.extract::<bool>(py), Ok(false)),
+                    Err(e) => {
+                        callback_error = Some(e);
+                        false
+                    }
+                }
+            })
+        })
+    });
+    if let Some(e) = callback_error {
+        return Err(e);
+    }
+    result.map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))
+}
+
+/// [`MZMLReader::read`]的选项，默认值与Python端的参数默认值一致
+#[cfg(feature = "python")]
+#[derive(Debug)]
+pub(crate) struct ReadOptions {
+    /// 为False时不解析谱图，只由头信息统计谱图数
+    pub parse_spectra: bool,
+    /// 是否并行解析
+    pub parallel: bool,
+    /// 并行解析的线程数，None时使用全部CPU
+    pub num_processes: Option<usize>,
+    /// 质心化、宽松模式等解析选项
+    pub parse: ParseOptions,
+    /// 是否为电荷为0的MS2谱图推断前体离子电荷
+    pub infer_charges: bool,
+    /// 是否经由`<filename>.omsu`缓存读取
+    pub use_cache: bool,
+    /// 解析进度回调
+    pub progress_callback: Option<PyObject>,
+    /// 每解析多少个谱图调用一次进度回调
+    pub progress_every: usize,
+}
+
+#[cfg(feature = "python")]
+impl Default for ReadOptions {
+    fn default() -> Self {
         Self {
-            parser: MZMLParser::new(),
+            parse_spectra: true,
+            parallel: false,
+            num_processes: None,
+            parse: ParseOptions::default(),
+            infer_charges: false,
+            use_cache: false,
+            progress_callback: None,
+            progress_every: 100,
         }
     }
+}
 
-    /// 读取MZML文件并返回MZMLObject
-    #[pyo3(signature = (filename, parse_spectra=true, parallel=false, num_processes=None))]
-    fn read(
-        &self,
-        py: Python,
-        filename: &str,
-        parse_spectra: bool,
-        parallel: bool,
-        num_processes: Option<usize>,
-    ) -> PyResult<Py<PyAny>> {
+#[cfg(feature = "python")]
+impl MZMLReader {
+    /// 按`options`读取MZML文件并返回MZMLObject
+    pub(crate) fn read_with_options(&self, py: Python, filename: PathBuf, options: ReadOptions) -> PyResult<Py<PyAny>> {
         // 创建解析器
-        let parser = if parallel {
-            let num_threads = num_processes.unwrap_or_else(|| num_cpus::get());
+        let parser = if options.parallel {
+            let num_threads = options.num_processes.unwrap_or_else(num_cpus::get);
             MZMLParser::new_parallel(num_threads)
         } else {
             MZMLParser::new()
         };
+        let parser = parser.with_options(options.parse);
 
         // 解析文件
-        let spectra = if parse_spectra {
-            parser.parse_sequential(filename)
-                .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?
+        let parse = || match &options.progress_callback {
+            Some(callback) => parse_with_progress_callback(py, &parser, &filename, callback, options.progress_every),
+            None => parser.parse(&filename)
+                .map(|spectra| ParsedSpectra { spectra, cancelled: false })
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string())),
+        };
+        let ParsedSpectra { mut spectra, cancelled } = if options.parse_spectra && options.use_cache {
+            parse_with_cache(&parser, &filename, parse)?
+        } else if options.parse_spectra {
+            parse()?
         } else {
-            Vec::new()
+            ParsedSpectra::default()
         };
+        if options.infer_charges {
+            let tolerance = Tolerance::PPM(charge::DEFAULT_CHARGE_TOLERANCE_PPM);
+            charge::infer_missing_charges(&mut spectra, tolerance, charge::DEFAULT_MAX_CHARGE);
+        }
 
-        // 创建文件信息
-        let mut file_info = MZMLFileInfo::new(filename.to_string());
-        file_info.spectrum_count = spectra.len();
-        
-        for spectrum in &spectra {
-            match spectrum.level {
-                1 => file_info.ms1_count += 1,
-                2 => file_info.ms2_count += 1,
-                _ => {}
-            }
+        // 创建文件信息，不解析谱图时由头信息统计谱图数
+        let mut file_info = MZMLFileInfo::new(&filename);
+        if options.parse_spectra {
+            file_info.update_counts(&spectra);
+        } else {
+            let headers = parser.spectrum_headers(&filename)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+            file_info.update_counts_from_headers(&headers);
         }
+        file_info.skipped_spectra = parser.skipped_spectra();
+        file_info.cancelled = cancelled;
 
         // 创建MSObject列表
-        let ms_objects: Result<Vec<MSObject>, PyErr> = spectra
-            .into_iter()
-            .map(|spectrum| {
-                let ms_object = MSObject { spectrum };
-                Ok(ms_object)
-            })
-            .collect();
-
         let mzml_object = MZMLObject {
-            spectra: ms_objects?,
+            spectra: SpectraStore::shared(spectra),
             file_info,
         };
 
-        Ok(Py::new(py, mzml_object)?.into())
+        Ok(Py::new(py, mzml_object)?.into_any())
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl MZMLReader {
+    /// 创建新的MZML读取器
+    #[new]
+    fn new() -> Self {
+        Self {
+            parser: MZMLParser::new(),
+        }
+    }
+
+    /// 读取MZML文件并返回MZMLObject
+    ///
+    /// `centroid=True`时在解析过程中将轮廓谱图质心化。
+    /// `infer_charges=True`时为前体离子电荷为0的MS2谱图由之前最近一张MS1的同位素间距推断电荷。
+    /// `use_cache=True`时经由源文件旁的`<filename>.omsu`缓存读取，缓存缺失或过期时解析后重新写出。
+    /// `lenient=True`时跳过无法解析的谱图，记录在`file_info.skipped_spectra`中；
+    /// 默认遇到损坏的谱图抛出IOError，信息中给出谱图id、index和字节偏移。
+    /// 给出`progress_callback`时按顺序解析（忽略`parallel`），每读完`progress_every`个谱图以
+    /// (已解析谱图数, 已读取字节数, 文件总字节数)调用一次，读完文件后再调用一次；回调返回False时
+    /// 取消解析，返回已解析的谱图并置`file_info.cancelled`。缓存命中时不调用回调。
+    #[pyo3(signature = (filename, parse_spectra=true, parallel=false, num_processes=None, centroid=false, infer_charges=false, use_cache=false, lenient=false, progress_callback=None, progress_every=100))]
+    #[allow(clippy::too_many_arguments)]
+    fn read(
+        &self,
+        py: Python,
+        filename: PathBuf,
+        parse_spectra: bool,
+        parallel: bool,
+        num_processes: Option<usize>,
+        centroid: bool,
+        infer_charges: bool,
+        use_cache: bool,
+        lenient: bool,
+        progress_callback: Option<PyObject>,
+        progress_every: usize,
+    ) -> PyResult<Py<PyAny>> {
+        let options = ReadOptions {
+            parse_spectra,
+            parallel,
+            num_processes,
+            parse: ParseOptions::new().with_centroid(centroid).with_lenient(lenient),
+            infer_charges,
+            use_cache,
+            progress_callback,
+            progress_every,
+        };
+        self.read_with_options(py, filename, options)
     }
 
     /// 读取MZML文件并返回MSObject列表
@@ -133,20 +365,20 @@ impl MZMLReader {
     fn read_to_msobjects(
         &self,
         py: Python,
-        filename: &str,
+        filename: PathBuf,
         parallel: bool,
         num_processes: Option<usize>,
     ) -> PyResult<Py<PyList>> {
         // 创建解析器
         let parser = if parallel {
-            let num_threads = num_processes.unwrap_or_else(|| num_cpus::get());
+            let num_threads = num_processes.unwrap_or_else(num_cpus::get);
             MZMLParser::new_parallel(num_threads)
         } else {
             MZMLParser::new()
         };
 
         // 解析文件
-        let spectra = parser.parse_sequential(filename)
+        let spectra = parser.parse(&filename)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
 
         // 转换为MSObject列表
@@ -159,78 +391,161 @@ impl MZMLReader {
         Ok(ms_objects.into())
     }
 
-    /// 读取单个谱图
-    fn read_spectrum(&self, py: Python, filename: &str, spectrum_index: usize) -> PyResult<Py<PyAny>> {
-        let spectra = self.parser.parse_sequential(filename)
+    /// 读取单个谱图，文件带有indexedmzML索引时直接定位，不解析整个文件
+    fn read_spectrum(&self, py: Python, filename: PathBuf, spectrum_index: usize) -> PyResult<Py<PyAny>> {
+        let spectrum = self.parser.read_spectrum(&filename, spectrum_index)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
 
-        if spectrum_index >= spectra.len() {
+        let Some(spectrum) = spectrum else {
+            let count = self.parser.spectrum_count(&filename)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
             return Err(PyErr::new::<pyo3::exceptions::PyIndexError, _>(
-                format!("Spectrum index {} out of range (0..{})", spectrum_index, spectra.len())
+                format!("Spectrum index {} out of range (0..{})", spectrum_index, count)
             ));
-        }
+        };
 
-        let spectrum = spectra.into_iter().nth(spectrum_index).unwrap();
         let ms_object = MSObject { spectrum };
-        Ok(Py::new(py, ms_object)?.into())
+        Ok(Py::new(py, ms_object)?.into_any())
     }
 
-    /// 获取文件信息
-    fn get_file_info(&self, py: Python, filename: &str) -> PyResult<Py<PyAny>> {
-        let spectra = self.parser.parse_sequential(filename)
+    /// 按native id读取单个谱图，文件带有indexedmzML索引时直接定位
+    fn read_spectrum_by_id(&self, py: Python, filename: PathBuf, native_id: &str) -> PyResult<Py<PyAny>> {
+        let spectrum = self.parser.read_spectrum_by_id(&filename, native_id)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?
+            .ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("No spectrum found with native id '{}'", native_id))
+            })?;
+        Ok(Py::new(py, MSObject { spectrum })?.into_any())
+    }
+
+    /// 读取文件中的色谱图（仪器记录的TIC、基峰色谱图等），不解析谱图
+    fn read_chromatograms(&self, py: Python, filename: PathBuf) -> PyResult<Py<PyList>> {
+        let chromatograms = self.parser.parse_chromatograms(&filename)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
 
-        let mut file_info = MZMLFileInfo::new(filename.to_string());
-        file_info.spectrum_count = spectra.len();
-        
-        for spectrum in &spectra {
-            match spectrum.level {
-                1 => file_info.ms1_count += 1,
-                2 => file_info.ms2_count += 1,
-                _ => {}
+        let list = PyList::empty(py);
+        for chromatogram in chromatograms {
+            list.append(Py::new(py, MZMLChromatogram { chromatogram })?)?;
+        }
+        Ok(list.into())
+    }
+
+    /// 获取文件信息，只读取谱图头信息，不解码峰数据
+    fn get_file_info(&self, py: Python, filename: PathBuf) -> PyResult<Py<PyAny>> {
+        let headers = self.parser.spectrum_headers(&filename)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+
+        let mut file_info = MZMLFileInfo::new(filename);
+        file_info.update_counts_from_headers(&headers);
+        Ok(Py::new(py, file_info)?.into_any())
+    }
+
+    /// 读取所有谱图的头信息（native id、MS级别、保留时间、前体离子m/z等），不解码峰数据
+    ///
+    /// 可以先按头信息筛选，再用`read_spectrum_by_id`读取需要的谱图。
+    fn read_headers(&self, filename: PathBuf) -> PyResult<Vec<SpectrumHeader>> {
+        self.parser.spectrum_headers(&filename)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))
+    }
+
+    /// 只读取谱图头信息生成扫描表，返回列名到列表的字典
+    ///
+    /// 不解码峰数据；TIC和基峰取自文件中记录的cvParam，未记录时为None。
+    fn scan_table(&self, py: Python, filename: PathBuf) -> PyResult<Py<PyDict>> {
+        let table = self.parser.scan_table(&filename)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        scan_table_to_dict(py, &table)
+    }
+
+    /// 逐个读取谱图，返回MSObject迭代器，内存占用与文件大小无关
+    ///
+    /// 损坏的谱图在迭代到它时抛出IOError，捕获后可以继续迭代后面的谱图；
+    /// `skip_errors`为True时记录警告并跳过损坏的谱图。
+    #[pyo3(signature = (filename, skip_errors=false))]
+    fn iter(&self, filename: PathBuf, skip_errors: bool) -> PyResult<MZMLSpectrumIterator> {
+        let policy = if skip_errors { SpectrumErrorPolicy::Skip } else { SpectrumErrorPolicy::Fail };
+        let options = self.parser.options().clone().with_error_policy(policy);
+        let spectra = self.parser.clone().with_options(options).iter_spectra(&filename)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        Ok(MZMLSpectrumIterator { spectra })
+    }
+
+    /// 边解析边提取MS1 XIC，返回最终的曲线
+    ///
+    /// 每解析`every`张谱图以(已解析谱图数, 曲线列表)调用一次`callback`，解析结束时再调用一次；
+    /// 每条曲线是带mz、rt和intensity的dict。`callback`抛出的异常会中止解析并原样抛出。
+    #[pyo3(signature = (filename, targets, ppm=10.0, callback=None, every=100, reorder_window=DEFAULT_REORDER_WINDOW))]
+    #[allow(clippy::too_many_arguments)]
+    fn stream_xics(
+        &self,
+        py: Python,
+        filename: PathBuf,
+        targets: Vec<f64>,
+        ppm: f64,
+        callback: Option<PyObject>,
+        every: usize,
+        reorder_window: usize,
+    ) -> PyResult<Py<PyList>> {
+        let mut extractor = StreamingXICExtractor::new(targets, ppm).with_reorder_window(reorder_window);
+        let every = every.max(1);
+        let mut callback_error = None;
+
+        let result = self.parser.for_each_spectrum(&filename, |_, spectrum| {
+            extractor.ingest(&spectrum);
+            if let Some(callback) = &callback {
+                if extractor.ingested().is_multiple_of(every) {
+                    let traces = xic_results_to_list(py, &extractor.snapshot())
+                        .and_then(|traces| callback.call1(py, (extractor.ingested(), traces)));
+                    if let Err(e) = traces {
+                        let message = e.to_string();
+                        callback_error = Some(e);
+                        return Err(ParseError::Interrupted(message));
+                    }
+                }
             }
+            Ok(())
+        });
+        if let Some(e) = callback_error {
+            return Err(e);
         }
+        result.map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
 
-        Ok(Py::new(py, file_info)?.into())
+        let ingested = extractor.ingested();
+        let traces = xic_results_to_list(py, &extractor.finish())?;
+        if let Some(callback) = &callback {
+            if !ingested.is_multiple_of(every) {
+                callback.call1(py, (ingested, traces.clone_ref(py)))?;
+            }
+        }
+        Ok(traces)
     }
 
     /// 验证MZML文件
-    fn validate_file(&self, filename: &str) -> PyResult<bool> {
-        match self.parser.parse_sequential(filename) {
+    fn validate_file(&self, filename: PathBuf) -> PyResult<bool> {
+        match self.parser.parse_sequential(&filename) {
             Ok(_) => Ok(true),
             Err(_) => Ok(false),
         }
     }
 
-    /// 获取谱图数量
-    fn get_spectrum_count(&self, filename: &str) -> PyResult<usize> {
-        let spectra = self.parser.parse_sequential(filename)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
-        Ok(spectra.len())
+    /// 获取谱图数量，有indexedmzML索引时不读取谱图
+    fn get_spectrum_count(&self, filename: PathBuf) -> PyResult<usize> {
+        self.parser.spectrum_count(&filename)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))
     }
 
     /// 获取MS1谱图数量
-    fn get_ms1_count(&self, filename: &str) -> PyResult<usize> {
-        let spectra = self.parser.parse_sequential(filename)
+    fn get_ms1_count(&self, filename: PathBuf) -> PyResult<usize> {
+        let headers = self.parser.spectrum_headers(&filename)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
-        
-        let ms1_count = spectra.iter()
-            .filter(|s| s.level == 1)
-            .count();
-        
-        Ok(ms1_count)
+        Ok(headers.iter().filter(|header| header.ms_level == 1).count())
     }
 
     /// 获取MS2谱图数量
-    fn get_ms2_count(&self, filename: &str) -> PyResult<usize> {
-        let spectra = self.parser.parse_sequential(filename)
+    fn get_ms2_count(&self, filename: PathBuf) -> PyResult<usize> {
+        let headers = self.parser.spectrum_headers(&filename)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
-        
-        let ms2_count = spectra.iter()
-            .filter(|s| s.level == 2)
-            .count();
-        
-        Ok(ms2_count)
+        Ok(headers.iter().filter(|header| header.ms_level == 2).count())
     }
 }
 
@@ -247,9 +562,9 @@ impl MZMLObject {
     #[getter]
     fn ms1_spectra(&self, py: Python) -> PyResult<Py<PyList>> {
         let ms1_list = PyList::empty(py);
-        for spectrum in &self.spectra {
+        for spectrum in self.spectra.iter() {
             if spectrum.is_ms1() {
-                ms1_list.append(Py::new(py, spectrum.clone())?)?;
+                ms1_list.append(Py::new(py, MSObject { spectrum: spectrum.clone() })?)?;
             }
         }
         Ok(ms1_list.into())
@@ -259,9 +574,9 @@ impl MZMLObject {
     #[getter]
     fn ms2_spectra(&self, py: Python) -> PyResult<Py<PyList>> {
         let ms2_list = PyList::empty(py);
-        for spectrum in &self.spectra {
+        for spectrum in self.spectra.iter() {
             if spectrum.is_ms2() {
-                ms2_list.append(Py::new(py, spectrum.clone())?)?;
+                ms2_list.append(Py::new(py, MSObject { spectrum: spectrum.clone() })?)?;
             }
         }
         Ok(ms2_list.into())
@@ -271,8 +586,8 @@ impl MZMLObject {
     #[getter]
     fn spectra(&self, py: Python) -> PyResult<Py<PyList>> {
         let spectra_list = PyList::empty(py);
-        for spectrum in &self.spectra {
-            spectra_list.append(Py::new(py, spectrum.clone())?)?;
+        for spectrum in self.spectra.iter() {
+            spectra_list.append(Py::new(py, MSObject { spectrum: spectrum.clone() })?)?;
         }
         Ok(spectra_list.into())
     }
@@ -284,14 +599,14 @@ impl MZMLObject {
                 format!("Index {} out of range", index)
             ));
         }
-        Ok(Py::new(py, self.spectra[index].clone())?.into())
+        Ok(Py::new(py, MSObject { spectrum: self.spectra[index].clone() })?.into_any())
     }
 
     /// 按扫描编号获取谱图
     fn get_spectrum_by_scan_number(&self, py: Python, scan_number: u32) -> PyResult<Py<PyAny>> {
-        for spectrum in &self.spectra {
-            if spectrum.scan_number() == scan_number {
-                return Ok(Py::new(py, spectrum.clone())?.into());
+        for spectrum in self.spectra.iter() {
+            if spectrum.scan.scan_number == scan_number {
+                return Ok(Py::new(py, MSObject { spectrum: spectrum.clone() })?.into_any());
             }
         }
         Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
@@ -299,13 +614,21 @@ impl MZMLObject {
         ))
     }
 
+    /// 按源文件中的native id获取谱图
+    fn get_spectrum_by_native_id(&self, py: Python, native_id: &str) -> PyResult<Py<PyAny>> {
+        let spectrum = self.spectra.iter().find(|spectrum| spectrum.scan.native_id == native_id).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("No spectrum found with native id '{}'", native_id))
+        })?;
+        Ok(Py::new(py, MSObject { spectrum: spectrum.clone() })?.into_any())
+    }
+
     /// 按保留时间范围获取谱图
     fn get_spectra_by_rt_range(&self, py: Python, rt_min: f64, rt_max: f64) -> PyResult<Py<PyList>> {
         let spectra_list = PyList::empty(py);
-        for spectrum in &self.spectra {
-            let rt = spectrum.retention_time();
+        for spectrum in self.spectra.iter() {
+            let rt = spectrum.scan.retention_time;
             if rt >= rt_min && rt <= rt_max {
-                spectra_list.append(Py::new(py, spectrum.clone())?)?;
+                spectra_list.append(Py::new(py, MSObject { spectrum: spectrum.clone() })?)?;
             }
         }
         Ok(spectra_list.into())
@@ -314,20 +637,224 @@ impl MZMLObject {
     /// 按m/z范围获取谱图
     fn get_spectra_by_mz_range(&self, py: Python, mz_min: f64, mz_max: f64) -> PyResult<Py<PyList>> {
         let spectra_list = PyList::empty(py);
-        for spectrum in &self.spectra {
-            let peaks = spectrum.peaks(py)?;
-            for peak in peaks.iter()? {
-                let tuple = peak?.downcast::<pyo3::types::PyTuple>()?;
-                let mz = tuple.get_item(0)?.extract::<f64>()?;
-                if mz >= mz_min && mz <= mz_max {
-                    spectra_list.append(Py::new(py, spectrum.clone())?)?;
-                    break;
-                }
+        for spectrum in self.spectra.iter() {
+            if spectrum.mz_slice().iter().any(|mz| (mz_min..=mz_max).contains(mz)) {
+                spectra_list.append(Py::new(py, MSObject { spectrum: spectrum.clone() })?)?;
             }
         }
         Ok(spectra_list.into())
     }
 
+    /// 由已解析的谱图生成扫描表，列与`MZMLReader.scan_table`一致
+    ///
+    /// TIC和基峰由峰列表计算。
+    fn scan_table(&self, py: Python) -> PyResult<Py<PyDict>> {
+        let table = ScanTable::from_spectra(self.spectra.iter());
+        scan_table_to_dict(py, &table)
+    }
+
+    /// 检测DIA分离窗口方案
+    ///
+    /// 返回dict：`is_dda`、`overlapping`、`cycle_time`（各窗口循环时间的中位数，秒）和`windows`，
+    /// `windows`为按下限排列的dict列表（`lower`、`upper`、`scan_count`、`cycle_time`），DDA数据为空列表。
+    fn dia_scheme(&self, py: Python) -> PyResult<Py<PyDict>> {
+        let scheme = IsolationScheme::detect(&self.spectra);
+        let windows = PyList::empty(py);
+        for summary in &scheme.windows {
+            let window = PyDict::new(py);
+            window.set_item("lower", summary.lower)?;
+            window.set_item("upper", summary.upper)?;
+            window.set_item("scan_count", summary.scan_count)?;
+            window.set_item("cycle_time", summary.cycle_time)?;
+            windows.append(window)?;
+        }
+
+        let result = PyDict::new(py);
+        result.set_item("is_dda", scheme.is_dda)?;
+        result.set_item("overlapping", scheme.overlapping)?;
+        result.set_item("cycle_time", scheme.cycle_time())?;
+        result.set_item("windows", windows)?;
+        Ok(result.unbind())
+    }
+
+    /// 根据MS1同位素峰簇校正MS2的前体离子m/z，返回被校正的谱图数
+    #[pyo3(signature = (ppm=10.0, max_shift=3))]
+    fn correct_precursors(&mut self, ppm: f64, max_shift: usize) -> usize {
+        self.modify_spectra(|spectra| {
+            let ms1: Vec<Spectrum> = spectra.iter().filter(|spectrum| spectrum.is_ms1()).cloned().collect();
+
+            let (ms2_indices, mut ms2): (Vec<usize>, Vec<Spectrum>) = spectra
+                .iter_mut()
+                .enumerate()
+                .filter(|(_, spectrum)| !spectrum.is_ms1())
+                .map(|(index, spectrum)| (index, std::mem::take(spectrum)))
+                .unzip();
+
+            let corrected = precursor_correction::correct(&ms1, &mut ms2, ppm, max_shift);
+            for (index, spectrum) in ms2_indices.into_iter().zip(ms2) {
+                spectra[index] = spectrum;
+            }
+            corrected
+        })
+    }
+
+    /// 按采集分段拆分运行
+    ///
+    /// `by`可为"scan_window"、"polarity"或"ms_level_pattern"，新属性需连续出现
+    /// `hysteresis`张谱图才视为分段切换。返回(各分段的MZMLObject, 分段边界的保留时间)。
+    #[pyo3(signature = (by="scan_window", hysteresis=3))]
+    fn split_segments(&self, by: &str, hysteresis: usize) -> PyResult<(Vec<MZMLObject>, Vec<f64>)> {
+        let by = SegmentBy::from_name(by).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+        })?;
+        let segments = segments::split_segments(self.spectra.iter(), by, hysteresis);
+
+        let boundaries = segments.iter().skip(1).map(|segment| segment.start_rt).collect();
+        let objects = segments
+            .into_iter()
+            .map(|segment| {
+                let spectra = SpectraStore::shared(self.spectra[segment.range].to_vec());
+                let mut file_info = self.file_info.clone();
+                file_info.update_counts(&spectra);
+                MZMLObject { spectra, file_info }
+            })
+            .collect();
+        Ok((objects, boundaries))
+    }
+
+    /// 在末尾添加一张谱图
+    fn append(&mut self, ms_object: MSObject) {
+        self.modify_spectra(|spectra| spectra.push(ms_object.spectrum));
+    }
+
+    /// 在末尾添加多张谱图
+    fn extend(&mut self, ms_objects: Vec<MSObject>) {
+        self.modify_spectra(|spectra| spectra.extend(ms_objects.into_iter().map(|ms_object| ms_object.spectrum)));
+    }
+
+    /// 移除并返回指定位置的谱图
+    fn remove(&mut self, index: usize) -> PyResult<MSObject> {
+        if index >= self.spectra.len() {
+            return Err(PyErr::new::<pyo3::exceptions::PyIndexError, _>(
+                format!("Index {} out of range", index)
+            ));
+        }
+        let spectrum = self.modify_spectra(|spectra| spectra.remove(index));
+        Ok(MSObject { spectrum })
+    }
+
+    /// 按保留时间稳定排序
+    fn sort_by_rt(&mut self) {
+        self.modify_spectra(|spectra| spectra.sort_by(|a, b| a.scan.retention_time.total_cmp(&b.scan.retention_time)));
+    }
+
+    /// 按当前顺序将扫描编号重新编为`start`起的连续整数，前体离子的参考扫描编号同步更新
+    #[pyo3(signature = (start=1))]
+    fn renumber_scans(&mut self, start: u32) {
+        self.modify_spectra(|spectra| renumber(spectra, start));
+    }
+
+    /// 合并多个运行
+    ///
+    /// `offset_scan_numbers`为True时，每个运行的扫描编号（及前体离子参考扫描编号）
+    /// 加上之前所有运行的最大扫描编号，避免编号冲突。文件信息取自第一个运行。
+    #[staticmethod]
+    #[pyo3(signature = (objects, offset_scan_numbers=false))]
+    fn concat(objects: Vec<PyRef<'_, MZMLObject>>, offset_scan_numbers: bool) -> MZMLObject {
+        let mut spectra: Vec<Spectrum> = Vec::with_capacity(objects.iter().map(|object| object.spectra.len()).sum());
+        let mut offset = 0;
+        for object in &objects {
+            let start = spectra.len();
+            spectra.extend(object.spectra.iter().cloned());
+            if offset_scan_numbers {
+                for spectrum in &mut spectra[start..] {
+                    spectrum.scan.scan_number += offset;
+                    if let Some(precursor) = spectrum.precursor.as_deref_mut() {
+                        if precursor.ref_scan_number != 0 {
+                            precursor.ref_scan_number += offset;
+                        }
+                    }
+                }
+            }
+            offset = spectra.iter().map(|spectrum| spectrum.scan.scan_number).max().unwrap_or(offset);
+        }
+
+        let mut file_info = objects
+            .first()
+            .map(|object| object.file_info.clone())
+            .unwrap_or_else(|| MZMLFileInfo::new(PathBuf::new()));
+        file_info.update_counts(&spectra);
+        MZMLObject { spectra: SpectraStore::shared(spectra), file_info }
+    }
+
+    /// 在本对象的谱图上创建XIC提取器，多个提取器共用同一份谱图数据
+    ///
+    /// 给定`tolerance`（ToleranceModel）时代替`ppm_tolerance`。
+    #[pyo3(signature = (ppm_tolerance=10.0, bin_size=1.0, tolerance=None))]
+    fn xic_extractor(&self, ppm_tolerance: f64, bin_size: f64, tolerance: Option<PyToleranceModel>) -> PyResult<XICSExtractor> {
+        let mut extractor = XICSExtractor::from_shared(self.spectra.clone(), ppm_tolerance, bin_size)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        if let Some(tolerance) = tolerance {
+            extractor.set_tolerance_model(tolerance.model);
+        }
+        Ok(extractor)
+    }
+
+    /// 总离子流色谱图，返回(保留时间列表, 强度列表, 因缺少保留时间跳过的谱图数)
+    #[pyo3(signature = (ms_level=1, rt_range=None))]
+    fn tic(&self, ms_level: u8, rt_range: Option<(f64, f64)>) -> (Vec<f64>, Vec<f64>, usize) {
+        let chromatogram = build_tic(self.spectra.iter(), ms_level, rt_range);
+        (chromatogram.rt_array, chromatogram.intensity_array, chromatogram.skipped)
+    }
+
+    /// 基峰色谱图，返回值同`tic`
+    #[pyo3(signature = (ms_level=1, rt_range=None))]
+    fn bpc(&self, ms_level: u8, rt_range: Option<(f64, f64)>) -> (Vec<f64>, Vec<f64>, usize) {
+        let chromatogram = build_bpc(self.spectra.iter(), ms_level, rt_range);
+        (chromatogram.rt_array, chromatogram.intensity_array, chromatogram.skipped)
+    }
+
+    /// 在本对象的谱图上创建二进制索引，`ms_level`为None时索引全部谱图
+    #[pyo3(signature = (bin_size=1.0, ms_level=None))]
+    fn spectra_index(&self, bin_size: f64, ms_level: Option<u8>) -> PyResult<SpectraIndex> {
+        let indices = (0..self.spectra.len())
+            .filter(|&index| ms_level.is_none_or(|level| self.spectra[index].level == level))
+            .collect();
+        BinnedSpectraIndex::from_shared(self.spectra.clone(), indices, bin_size)
+            .map(|index| SpectraIndex { index })
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 查找含有指定碎片离子的MS2谱图，相对强度低于`min_rel_intensity`的命中被丢弃
+    #[pyo3(signature = (mz, ppm=20.0, min_rel_intensity=0.05))]
+    fn find_fragment(&self, py: Python, mz: f64, ppm: f64, min_rel_intensity: f64) -> PyResult<Py<PyList>> {
+        let hits = fragment_search::find_spectra_with_fragment(&self.spectra, mz, Tolerance::PPM(ppm), min_rel_intensity);
+        fragment_matches_to_list(py, &self.spectra, &hits)
+    }
+
+    /// 查找在前体离子m/z减去中性丢失处有峰的MS2谱图
+    #[pyo3(signature = (mass, ppm=20.0))]
+    fn find_neutral_loss(&self, py: Python, mass: f64, ppm: f64) -> PyResult<Py<PyList>> {
+        let hits = fragment_search::find_spectra_with_neutral_loss(&self.spectra, mass, Tolerance::PPM(ppm));
+        fragment_matches_to_list(py, &self.spectra, &hits)
+    }
+
+    /// 按质量评分返回最好的`top_n`张MS2谱图，每项为带评分分量的dict
+    #[pyo3(signature = (top_n=100))]
+    fn rank_ms2(&self, py: Python, top_n: usize) -> PyResult<Py<PyList>> {
+        let list = PyList::empty(py);
+        for (index, score) in quality::rank_ms2(&self.spectra, top_n) {
+            let spectrum = &self.spectra[index];
+            let dict = quality_to_dict(py, &score)?;
+            dict.set_item("index", index)?;
+            dict.set_item("scan_number", spectrum.scan.scan_number)?;
+            dict.set_item("retention_time", spectrum.scan.retention_time)?;
+            dict.set_item("precursor_mz", spectrum.precursor.as_ref().map(|precursor| precursor.mz))?;
+            list.append(dict)?;
+        }
+        Ok(list.unbind())
+    }
+
     /// 获取文件信息
     #[getter]
     fn file_info(&self) -> MZMLFileInfo {
@@ -338,7 +865,8 @@ impl MZMLObject {
     fn __iter__(&self, py: Python) -> PyResult<Py<PyAny>> {
         use pyo3::types::PyIterator;
         let spectra_list = self.spectra(py)?;
-        PyIterator::from_object(spectra_list)
+        PyIterator::from_object(spectra_list.bind(py))
+            .map(|iterator| iterator.into_any().unbind())
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyTypeError, _>(e.to_string()))
     }
 
@@ -361,12 +889,140 @@ impl MZMLObject {
     }
 }
 
+/// 将碎片命中转换为字典列表，附带扫描编号和保留时间
+#[cfg(feature = "python")]
+fn fragment_matches_to_list(py: Python, spectra: &[Spectrum], hits: &[FragmentMatch]) -> PyResult<Py<PyList>> {
+    let list = PyList::empty(py);
+    for hit in hits {
+        let spectrum = &spectra[hit.spectrum_index];
+        let dict = PyDict::new(py);
+        dict.set_item("index", hit.spectrum_index)?;
+        dict.set_item("scan_number", spectrum.scan.scan_number)?;
+        dict.set_item("retention_time", spectrum.scan.retention_time)?;
+        dict.set_item("precursor_mz", spectrum.precursor.as_ref().map(|precursor| precursor.mz))?;
+        dict.set_item("mz", hit.mz)?;
+        dict.set_item("intensity", hit.intensity)?;
+        dict.set_item("relative_intensity", hit.relative_intensity)?;
+        list.append(dict)?;
+    }
+    Ok(list.unbind())
+}
+
+/// 将XIC列表转换为dict列表，每项带mz、rt、intensity、mz_observed和ppm_error
+#[cfg(feature = "python")]
+fn xic_results_to_list(py: Python, results: &[XICResult]) -> PyResult<Py<PyList>> {
+    let list = PyList::empty(py);
+    for result in results {
+        let dict = PyDict::new(py);
+        dict.set_item("mz", result.mz)?;
+        dict.set_item("rt", &result.rt_array)?;
+        dict.set_item("intensity", &result.intensity_array)?;
+        dict.set_item("mz_observed", &result.mz_observed_array)?;
+        dict.set_item("ppm_error", result.ppm_error)?;
+        list.append(dict)?;
+    }
+    Ok(list.unbind())
+}
+
+/// 将扫描表转换为列名到列表的字典
+#[cfg(feature = "python")]
+fn scan_table_to_dict(py: Python, table: &ScanTable) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("scan_number", &table.scan_number)?;
+    dict.set_item("retention_time", &table.retention_time)?;
+    dict.set_item("ms_level", &table.ms_level)?;
+    dict.set_item("precursor_mz", &table.precursor_mz)?;
+    dict.set_item("charge", &table.charge)?;
+    dict.set_item("injection_time", &table.injection_time)?;
+    dict.set_item("total_ion_current", &table.total_ion_current)?;
+    dict.set_item("base_peak_mz", &table.base_peak_mz)?;
+    dict.set_item("base_peak_intensity", &table.base_peak_intensity)?;
+    dict.set_item("peak_count", &table.peak_count)?;
+    Ok(dict.unbind())
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl MZMLSpectrumIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// 下一个谱图；损坏的谱图抛出IOError，之后仍可继续迭代
+    fn __next__(&mut self) -> PyResult<Option<MSObject>> {
+        match self.spectra.next() {
+            None => Ok(None),
+            Some(Ok(spectrum)) => Ok(Some(MSObject { spectrum })),
+            Some(Err(e)) => Err(PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string())),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl MZMLChromatogram {
+    #[getter]
+    fn id(&self) -> &str {
+        &self.chromatogram.id
+    }
+
+    #[getter]
+    fn index(&self) -> Option<usize> {
+        self.chromatogram.index
+    }
+
+    /// 色谱图类型的CV名称，如"total ion current chromatogram"
+    #[getter]
+    fn chromatogram_type(&self) -> Option<&str> {
+        self.chromatogram.chromatogram_type()
+    }
+
+    #[getter]
+    fn time_array(&self) -> Vec<f64> {
+        self.chromatogram.time_array.clone()
+    }
+
+    #[getter]
+    fn intensity_array(&self) -> Vec<f64> {
+        self.chromatogram.intensity_array.clone()
+    }
+
+    /// 时间数组的单位，如"minute"
+    #[getter]
+    fn time_unit(&self) -> Option<&str> {
+        self.chromatogram.time_unit.as_deref()
+    }
+
+    #[getter]
+    fn precursor_mz(&self) -> Option<f64> {
+        self.chromatogram.precursor_mz()
+    }
+
+    #[getter]
+    fn product_mz(&self) -> Option<f64> {
+        self.chromatogram.product_mz()
+    }
+
+    fn __len__(&self) -> usize {
+        self.chromatogram.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MZMLChromatogram(id='{}', type={}, points={})",
+            self.chromatogram.id,
+            self.chromatogram.chromatogram_type().unwrap_or("unknown"),
+            self.chromatogram.len()
+        )
+    }
+}
+
 #[cfg(feature = "python")]
 #[pymethods]
 impl MZMLFileInfo {
     /// 字符串表示
     fn __repr__(&self) -> String {
-        format!("MZMLFileInfo(file='{}', spectra={}, ms1={}, ms2={})",
+        format!("MZMLFileInfo(file={:?}, spectra={}, ms1={}, ms2={})",
                 self.file_path,
                 self.spectrum_count,
                 self.ms1_count,
@@ -377,41 +1033,533 @@ impl MZMLFileInfo {
     fn __str__(&self) -> String {
         self.__repr__()
     }
+
+    /// 按宽松模式跳过的谱图，每项为含spectrum_id、index、byte_offset和error的字典
+    #[getter]
+    fn skipped_spectra(&self, py: Python) -> PyResult<Vec<Py<PyDict>>> {
+        self.skipped_spectra
+            .iter()
+            .map(|skipped| {
+                let dict = PyDict::new(py);
+                dict.set_item("spectrum_id", &skipped.spectrum_id)?;
+                dict.set_item("index", skipped.index)?;
+                dict.set_item("byte_offset", skipped.byte_offset)?;
+                dict.set_item("error", &skipped.error)?;
+                Ok(dict.unbind())
+            })
+            .collect()
+    }
 }
 
 #[cfg(all(test, feature = "python"))]
 mod tests {
     use super::*;
+    use crate::parsers::mzml::test_data::{build_mzml, corrupt_spectrum_file, write_temp_file, TestSpectrum};
+    use crate::core::spectrum::PrecursorInfo;
     use pyo3::Python;
+    use crate::search::range_query::PyRangeQuery;
+    use std::sync::Arc;
 
     #[test]
     fn test_mzml_reader_creation() {
-        Python::with_gil(|py| {
-            let reader = MZMLReader::new();
-            // 基本创建测试
-            assert!(true); // 如果能创建就通过
-        });
+        let reader = MZMLReader::new();
+        assert!(reader.validate_file("nonexistent.mzML".into()).map(|valid| !valid).unwrap_or(true));
     }
 
     #[test]
     fn test_mzml_file_info() {
         let file_info = MZMLFileInfo::new("test.mzML".to_string());
-        assert_eq!(file_info.file_path, "test.mzML");
+        assert_eq!(file_info.file_path, PathBuf::from("test.mzML"));
         assert_eq!(file_info.file_format, "mzML");
         assert_eq!(file_info.spectrum_count, 0);
     }
 
     #[test]
     fn test_mzml_object_creation() {
+        let file_info = MZMLFileInfo::new("test.mzML".to_string());
+        let mzml_object = MZMLObject {
+            spectra: SharedSpectra::default(),
+            file_info,
+        };
+
+        assert_eq!(mzml_object.spectrum_count(), 0);
+        assert_eq!(mzml_object.__len__(), 0);
+    }
+
+    #[test]
+    fn test_scan_table_reader_and_object_agree() {
+        let spectra: Vec<TestSpectrum> = (1..=4u32)
+            .map(|scan| {
+                TestSpectrum::new(scan, if scan == 1 { 1 } else { 2 }, scan as f64, vec![(100.0, scan as f64), (200.0, 1.0)])
+                    .with_injection_time(10.0)
+            })
+            .collect();
+        let file = write_temp_file(&build_mzml(&spectra));
+        let path = file.path().to_path_buf();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let reader = MZMLReader::new();
+            let from_headers = reader.scan_table(py, path.clone()).unwrap();
+            let object = reader.read_with_options(py, path, ReadOptions::default()).unwrap();
+            let object = object.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+            let from_spectra = object.scan_table(py).unwrap();
+
+            let from_headers = from_headers.bind(py);
+            let from_spectra = from_spectra.bind(py);
+            for column in ["scan_number", "retention_time", "ms_level", "precursor_mz", "charge",
+                           "injection_time", "total_ion_current", "base_peak_mz", "peak_count"] {
+                let expected = from_spectra.get_item(column).unwrap().unwrap();
+                let actual = from_headers.get_item(column).unwrap().unwrap();
+                assert!(actual.eq(&expected).unwrap(), "column {} differs", column);
+                assert_eq!(actual.len().unwrap(), 4);
+            }
+        });
+    }
+
+    #[test]
+    fn test_counts_from_headers() {
+        let spectra: Vec<TestSpectrum> = (1..=5u32)
+            .map(|scan| {
+                let spectrum = TestSpectrum::new(scan, if scan % 2 == 1 { 1 } else { 2 }, scan as f64, vec![(100.0, 1.0)]);
+                if scan % 2 == 1 { spectrum } else { spectrum.with_precursor(500.0, 2) }
+            })
+            .collect();
+        let file = write_temp_file(&build_mzml(&spectra));
+        let path = file.path().to_path_buf();
+
+        let reader = MZMLReader::new();
+        assert_eq!(reader.get_ms1_count(path.clone()).unwrap(), 3);
+        assert_eq!(reader.get_ms2_count(path.clone()).unwrap(), 2);
+        let headers = reader.read_headers(path.clone()).unwrap();
+        assert_eq!(headers[1].precursor_mz, Some(500.0));
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let options = ReadOptions { parse_spectra: false, ..ReadOptions::default() };
+            let object = reader.read_with_options(py, path, options).unwrap();
+            let object = object.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+            assert_eq!(object.spectrum_count(), 0);
+            let info = object.file_info();
+            assert_eq!((info.spectrum_count, info.ms1_count, info.ms2_count), (5, 3, 2));
+        });
+    }
+
+    #[test]
+    fn test_split_segments_by_polarity() {
+        let spectra: Vec<TestSpectrum> = (0..300u32)
+            .map(|scan| {
+                // 第150张谱图的极性标记错误
+                let positive = (scan / 100) % 2 == 0;
+                let positive = if scan == 150 { !positive } else { positive };
+                TestSpectrum::new(scan + 1, 1, scan as f64, vec![(100.0, 1.0)]).with_polarity(positive)
+            })
+            .collect();
+        let file = write_temp_file(&build_mzml(&spectra));
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let object = MZMLReader::new().read_with_options(py, file.path().to_path_buf(), ReadOptions::default()).unwrap();
+            let object = object.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+
+            let (segments, boundaries) = object.split_segments("polarity", 3).unwrap();
+            assert_eq!(segments.iter().map(|s| s.file_info.spectrum_count).collect::<Vec<_>>(), vec![100, 100, 100]);
+            assert_eq!(segments[1].file_info.ms1_count, 100);
+            assert_eq!(boundaries, vec![100.0, 200.0]);
+            assert!(object.split_segments("charge", 3).is_err());
+        });
+    }
+
+    #[test]
+    fn test_xic_extractors_reuse_object_spectra() {
+        let spectra: Vec<TestSpectrum> = (1..=3u32)
+            .map(|scan| TestSpectrum::new(scan, 1, scan as f64, vec![(400.0, scan as f64 * 10.0)]))
+            .collect();
+        let file = write_temp_file(&build_mzml(&spectra));
+
+        pyo3::prepare_freethreaded_python();
         Python::with_gil(|py| {
-            let file_info = MZMLFileInfo::new("test.mzML".to_string());
-            let mzml_object = MZMLObject {
-                spectra: Vec::new(),
-                file_info,
+            let object = MZMLReader::new().read_with_options(py, file.path().to_path_buf(), ReadOptions::default()).unwrap();
+            let bound = object.bind(py).downcast::<MZMLObject>().unwrap();
+
+            // XICSExtractor.from_mzml和RangeQuery(mzml_object)都共用对象的谱图存储
+            let from_mzml = py.get_type::<XICSExtractor>().call_method1("from_mzml", (bound,)).unwrap();
+            let from_mzml = from_mzml.downcast::<XICSExtractor>().unwrap().borrow();
+            let range_query = py.get_type::<PyRangeQuery>().call1((bound,)).unwrap();
+            let range_query = range_query.downcast::<PyRangeQuery>().unwrap().borrow();
+
+            let object = bound.borrow();
+            assert!(Arc::ptr_eq(&from_mzml.share_spectra(), &object.spectra));
+            assert!(Arc::ptr_eq(range_query.engine.shared_spectra(), &object.spectra));
+
+            let first = object.xic_extractor(10.0, 1.0, None).unwrap();
+            let second = object.xic_extractor(20.0, 0.5, None).unwrap();
+            assert!(Arc::ptr_eq(&first.share_spectra(), &object.spectra));
+            assert!(Arc::ptr_eq(&second.share_spectra(), &object.spectra));
+            assert_eq!(first.extract_single_xic(400.0, 1, "", 0.0, 10.0).unwrap().intensity_array, vec![10.0, 20.0, 30.0]);
+
+            let (rts, tic, skipped) = object.tic(1, None);
+            assert_eq!((rts.len(), tic, skipped), (3, vec![10.0, 20.0, 30.0], 0));
+            assert_eq!(object.bpc(2, None), (Vec::new(), Vec::new(), 0));
+        });
+    }
+
+    #[test]
+    fn test_read_centroids_profile_spectra() {
+        let profile: Vec<(f64, f64)> = (0..=100)
+            .map(|i| {
+                let mz = 499.95 + i as f64 * 0.001;
+                (mz, 1e5 * (-(mz - 500.0004f64).powi(2) / (2.0 * 0.004f64.powi(2))).exp())
+            })
+            .collect();
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 1.0, profile.clone()).with_profile(),
+            TestSpectrum::new(2, 1, 2.0, vec![(300.0, 10.0), (300.001, 20.0), (300.002, 5.0)]),
+        ];
+        let file = write_temp_file(&build_mzml(&spectra));
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let reader = MZMLReader::new();
+            let raw = reader.read_with_options(py, file.path().to_path_buf(), ReadOptions::default()).unwrap();
+            let raw = raw.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+            assert!(raw.spectra[0].is_profile());
+            assert_eq!(raw.spectra[0].peak_count(), profile.len());
+
+            let options = ReadOptions { parse: ParseOptions::new().with_centroid(true), ..ReadOptions::default() };
+            let centroided = reader.read_with_options(py, file.path().to_path_buf(), options).unwrap();
+            let centroided = centroided.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+            assert!(!centroided.spectra[0].is_profile());
+            assert_eq!(centroided.spectra[0].peak_count(), 1);
+            assert!((centroided.spectra[0].mz_slice()[0] - 500.0004).abs() < 1e-4);
+            // 已是质心谱图的不再处理
+            assert_eq!(centroided.spectra[1].peak_count(), 3);
+        });
+    }
+
+    #[test]
+    fn test_read_through_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.mzML");
+        std::fs::write(&path, build_mzml(&[TestSpectrum::new(1, 1, 1.0, vec![(300.0, 10.0)])])).unwrap();
+        let cache_path = cache::cache_path(&path);
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let reader = MZMLReader::new();
+            let read = |centroid: bool| {
+                let options = ReadOptions {
+                    use_cache: true,
+                    parse: ParseOptions::new().with_centroid(centroid),
+                    ..ReadOptions::default()
+                };
+                let object = reader.read_with_options(py, path.clone(), options).unwrap();
+                let object = object.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+                object.spectra.iter().map(|spectrum| spectrum.peaks()).collect::<Vec<_>>()
             };
-            
-            assert_eq!(mzml_object.spectrum_count(), 0);
-            assert_eq!(mzml_object.__len__(), 0);
+
+            assert_eq!(read(false), vec![vec![(300.0, 10.0)]]);
+            assert!(cache_path.is_file());
+            // 第二次读取来自缓存
+            let stamp = CacheStamp::of(&path, 0).unwrap();
+            cache::save_run_with_stamp(&[Spectrum::ms1().unwrap()], &cache_path, stamp).unwrap();
+            assert_eq!(read(false), vec![vec![]]);
+            // 解析选项不同时不使用缓存
+            assert_eq!(read(true), vec![vec![(300.0, 10.0)]]);
+
+            // 源文件修改后缓存过期，重新解析并写出
+            std::fs::write(&path, build_mzml(&[
+                TestSpectrum::new(1, 1, 1.0, vec![(300.0, 10.0)]),
+                TestSpectrum::new(2, 1, 2.0, vec![(400.0, 20.0)]),
+            ])).unwrap();
+            assert_eq!(read(false), vec![vec![(300.0, 10.0)], vec![(400.0, 20.0)]]);
+            assert_eq!(cache::load_fresh_run(&cache_path, CacheStamp::of(&path, 0).unwrap()).unwrap().len(), 2);
+        });
+    }
+
+    #[test]
+    fn test_concat_sort_and_renumber() {
+        let run = |rts: [f64; 3]| -> MZMLObject {
+            let spectra: Vec<Spectrum> = rts
+                .iter()
+                .enumerate()
+                .map(|(i, &rt)| {
+                    let mut spectrum = Spectrum::new(if i == 0 { 1 } else { 2 }).unwrap();
+                    spectrum.set_scan_number(i as u32 + 1);
+                    spectrum.set_retention_time(rt).unwrap();
+                    if i > 0 {
+                        spectrum.set_precursor(PrecursorInfo { ref_scan_number: 1, mz: 500.0, ..PrecursorInfo::default() });
+                    }
+                    spectrum
+                })
+                .collect();
+            let mut file_info = MZMLFileInfo::new("run.mzML".to_string());
+            file_info.update_counts(&spectra);
+            MZMLObject { spectra: SpectraStore::shared(spectra), file_info }
+        };
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let first = Py::new(py, run([10.0, 30.0, 50.0])).unwrap();
+            let second = Py::new(py, run([20.0, 40.0, 60.0])).unwrap();
+            let mut merged = MZMLObject::concat(vec![first.borrow(py), second.borrow(py)], true);
+            let scans: Vec<u32> = merged.spectra.iter().map(|s| s.scan.scan_number).collect();
+            assert_eq!(scans, vec![1, 2, 3, 4, 5, 6]);
+            assert_eq!(merged.spectra[4].precursor.as_ref().unwrap().ref_scan_number, 4);
+            assert_eq!((merged.file_info.spectrum_count, merged.file_info.ms1_count, merged.file_info.ms2_count), (6, 2, 4));
+
+            merged.sort_by_rt();
+            let rts: Vec<f64> = merged.spectra.iter().map(|s| s.scan.retention_time).collect();
+            assert_eq!(rts, vec![10.0, 20.0, 30.0, 40.0, 50.0, 60.0]);
+
+            merged.renumber_scans(101);
+            // RT 40的谱图原为扫描5，其参考的MS1（扫描4）排序后重新编号为102
+            assert_eq!(merged.spectra[3].precursor.as_ref().unwrap().ref_scan_number, 102);
+            let found = merged.get_spectrum_by_scan_number(py, 104).unwrap();
+            let found = found.bind(py).downcast::<MSObject>().unwrap().borrow();
+            assert_eq!(found.spectrum.scan.retention_time, 40.0);
+            assert!(merged.get_spectrum_by_scan_number(py, 1).is_err());
+
+            let removed = merged.remove(0).unwrap();
+            assert!(removed.spectrum.is_ms1());
+            merged.append(removed);
+            assert_eq!((merged.file_info.spectrum_count, merged.file_info.ms1_count), (6, 2));
+            assert!(merged.remove(6).is_err());
+        });
+    }
+
+    #[test]
+    fn test_find_fragment_and_neutral_loss() {
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 10.0, vec![(126.1277, 1e6)]),
+            TestSpectrum::new(2, 2, 12.0, vec![(126.1278, 500.0), (300.0, 1000.0)]).with_precursor(600.0, 1),
+            TestSpectrum::new(3, 2, 14.0, vec![(126.1277, 10.0), (502.0231, 1000.0)]).with_precursor(600.0, 1),
+        ];
+        let file = write_temp_file(&build_mzml(&spectra));
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let object = MZMLReader::new().read_with_options(py, file.path().to_path_buf(), ReadOptions::default()).unwrap();
+            let object = object.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+
+            let hits = object.find_fragment(py, 126.1277, 20.0, 0.05).unwrap();
+            let hits = hits.bind(py);
+            assert_eq!(hits.len(), 1);
+            let hit = hits.get_item(0).unwrap();
+            assert_eq!(hit.get_item("index").unwrap().extract::<usize>().unwrap(), 1);
+            assert_eq!(hit.get_item("retention_time").unwrap().extract::<f64>().unwrap(), 12.0);
+            assert_eq!(hit.get_item("relative_intensity").unwrap().extract::<f64>().unwrap(), 0.5);
+
+            let hits = object.find_neutral_loss(py, 97.9769, 20.0).unwrap();
+            let hits = hits.bind(py);
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits.get_item(0).unwrap().get_item("index").unwrap().extract::<usize>().unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn test_iter_yields_spectra_and_recovers_from_corrupt_one() {
+        let file = corrupt_spectrum_file("corrupt_iter_spectrum");
+
+        let reader = MZMLReader::new();
+        let mut iter = reader.iter(file.path().to_path_buf(), false).unwrap();
+        assert_eq!(iter.__next__().unwrap().unwrap().spectrum.scan.retention_time, 1.0);
+        let error = iter.__next__().unwrap_err();
+        assert!(error.to_string().contains("corrupt_iter_spectrum"));
+        assert_eq!(iter.__next__().unwrap().unwrap().spectrum.scan.retention_time, 3.0);
+        assert!(iter.__next__().unwrap().is_none());
+
+        let mut skipping = reader.iter(file.path().to_path_buf(), true).unwrap();
+        let mut rts = Vec::new();
+        while let Some(ms_object) = skipping.__next__().unwrap() {
+            rts.push(ms_object.spectrum.scan.retention_time);
+        }
+        assert_eq!(rts, vec![1.0, 3.0]);
+    }
+
+    #[test]
+    fn test_lenient_read_reports_skipped_spectra() {
+        let file = corrupt_spectrum_file("corrupt_read_spectrum");
+        let offset = std::fs::read_to_string(file.path()).unwrap().find("<spectrum index=\"1\"").unwrap();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let reader = MZMLReader::new();
+            let error = reader.read_with_options(py, file.path().to_path_buf(), ReadOptions::default()).unwrap_err();
+            assert!(error.to_string().contains(&format!("'corrupt_read_spectrum' (index 1) at byte {}", offset)));
+
+            let options = ReadOptions { parse: ParseOptions::new().with_lenient(true), ..ReadOptions::default() };
+            let object = reader.read_with_options(py, file.path().to_path_buf(), options).unwrap();
+            let object = object.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+            assert_eq!(object.file_info.spectrum_count, 2);
+            let skipped = object.file_info.skipped_spectra(py).unwrap();
+            assert_eq!(skipped.len(), 1);
+            let record = skipped[0].bind(py);
+            assert_eq!(record.get_item("spectrum_id").unwrap().unwrap().extract::<String>().unwrap(), "corrupt_read_spectrum");
+            assert_eq!(record.get_item("byte_offset").unwrap().unwrap().extract::<usize>().unwrap(), offset);
+        });
+    }
+
+    #[test]
+    fn test_read_progress_callback_counts_and_cancels() {
+        let spectra: Vec<TestSpectrum> = (1..=7)
+            .map(|scan| TestSpectrum::new(scan, 1, scan as f64, vec![(400.0, 10.0 * scan as f64)]))
+            .collect();
+        let file = write_temp_file(&build_mzml(&spectra));
+        let total_bytes = std::fs::metadata(file.path()).unwrap().len();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let calls = PyList::empty(py);
+            let append = calls.getattr("append").unwrap();
+            let counting = py.eval(c"lambda append: lambda *progress: append(progress)", None, None)
+                .unwrap()
+                .call1((append,))
+                .unwrap();
+            let reader = MZMLReader::new();
+            let options = ReadOptions { progress_callback: Some(counting.unbind()), progress_every: 3, ..ReadOptions::default() };
+            let object = reader.read_with_options(py, file.path().to_path_buf(), options).unwrap();
+            let object = object.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+            assert_eq!(object.file_info.spectrum_count, 7);
+            assert!(!object.file_info.cancelled);
+            let calls = calls.extract::<Vec<(usize, u64, u64)>>().unwrap();
+            let counts: Vec<usize> = calls.iter().map(|call| call.0).collect();
+            assert_eq!(counts, vec![3, 6, 7]);
+            assert!(calls[0].1 < calls[1].1 && calls[1].1 < total_bytes);
+            assert_eq!(calls[2].1, total_bytes);
+            assert!(calls.iter().all(|call| call.2 == total_bytes));
+
+            // 回调返回False时取消解析，返回已解析的谱图
+            let cancelling = py.eval(c"lambda parsed, read, total: parsed < 4", None, None).unwrap();
+            let options = ReadOptions { progress_callback: Some(cancelling.unbind()), progress_every: 2, ..ReadOptions::default() };
+            let object = reader.read_with_options(py, file.path().to_path_buf(), options).unwrap();
+            let object = object.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+            assert!(object.file_info.cancelled);
+            assert_eq!(object.file_info.spectrum_count, 4);
+
+            let failing = py.eval(c"lambda parsed, read, total: 1 / 0", None, None).unwrap();
+            let options = ReadOptions { progress_callback: Some(failing.unbind()), progress_every: 1, ..ReadOptions::default() };
+            let error = reader.read_with_options(py, file.path().to_path_buf(), options).unwrap_err();
+            assert!(error.is_instance_of::<pyo3::exceptions::PyZeroDivisionError>(py));
+        });
+    }
+
+    #[test]
+    fn test_stream_xics_callback() {
+        let spectra: Vec<TestSpectrum> = (1..=5)
+            .map(|scan| TestSpectrum::new(scan, 1, scan as f64, vec![(400.0, 10.0 * scan as f64)]))
+            .collect();
+        let file = write_temp_file(&build_mzml(&spectra));
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let calls = PyList::empty(py);
+            let append = calls.getattr("append").unwrap();
+            let callback = py.eval(c"lambda append: lambda count, traces: append((count, len(traces[0]['rt'])))", None, None)
+                .unwrap()
+                .call1((append,))
+                .unwrap();
+
+            let traces = MZMLReader::new()
+                .stream_xics(py, file.path().to_path_buf(), vec![400.0, 500.0], 10.0, Some(callback.unbind()), 2, 0)
+                .unwrap();
+            let traces = traces.bind(py);
+            assert_eq!(traces.get_item(0).unwrap().get_item("intensity").unwrap().extract::<Vec<f64>>().unwrap(), vec![10.0, 20.0, 30.0, 40.0, 50.0]);
+            assert!(traces.get_item(1).unwrap().get_item("rt").unwrap().extract::<Vec<f64>>().unwrap().is_empty());
+            assert_eq!(calls.extract::<Vec<(usize, usize)>>().unwrap(), vec![(2, 2), (4, 4), (5, 5)]);
+
+            // 回调抛出的异常中止解析
+            let failing = py.eval(c"lambda count, traces: 1 / 0", None, None).unwrap();
+            let error = MZMLReader::new()
+                .stream_xics(py, file.path().to_path_buf(), vec![400.0], 10.0, Some(failing.unbind()), 1, 0)
+                .unwrap_err();
+            assert!(error.is_instance_of::<pyo3::exceptions::PyZeroDivisionError>(py));
+        });
+    }
+
+    #[test]
+    fn test_get_spectrum_by_native_id() {
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 10.0, vec![(400.0, 10.0)]),
+            TestSpectrum::new(2, 2, 12.0, vec![(150.0, 5.0)]).with_precursor(400.0, 2),
+        ];
+        let file = write_temp_file(&build_mzml(&spectra));
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let object = MZMLReader::new().read_with_options(py, file.path().to_path_buf(), ReadOptions::default()).unwrap();
+            let object = object.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+
+            let spectrum = object.get_spectrum_by_native_id(py, "controllerType=0 controllerNumber=1 scan=2").unwrap();
+            let spectrum = spectrum.bind(py);
+            assert_eq!(spectrum.getattr("index").unwrap().extract::<Option<usize>>().unwrap(), Some(1));
+            assert_eq!(spectrum.getattr("level").unwrap().extract::<u8>().unwrap(), 2);
+            assert!(object.get_spectrum_by_native_id(py, "scan=2").unwrap_err().is_instance_of::<pyo3::exceptions::PyKeyError>(py));
+        });
+    }
+
+    #[test]
+    fn test_rank_ms2() {
+        // 扫描3含有互补的b/y离子对 (和为2 × 500.0)，扫描2只有噪声
+        let mut rich: Vec<(f64, f64)> = (0..10).map(|i| (150.0 + i as f64 * 70.0, 10.0)).collect();
+        rich.extend((0..4).flat_map(|i| {
+            let b = 220.1 + i as f64 * 110.0;
+            [(b, 1000.0), (1000.0 - b, 800.0)]
+        }));
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 10.0, vec![(500.0, 1e6)]),
+            TestSpectrum::new(2, 2, 12.0, vec![(210.0, 10.0), (330.0, 12.0), (500.0, 11.0)]).with_precursor(500.0, 2),
+            TestSpectrum::new(3, 2, 14.0, rich).with_precursor(500.0, 2),
+        ];
+        let file = write_temp_file(&build_mzml(&spectra));
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let object = MZMLReader::new().read_with_options(py, file.path().to_path_buf(), ReadOptions::default()).unwrap();
+            let object = object.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+
+            let ranked = object.rank_ms2(py, 10).unwrap();
+            let ranked = ranked.bind(py);
+            assert_eq!(ranked.len(), 2);
+            let best = ranked.get_item(0).unwrap();
+            assert_eq!(best.get_item("index").unwrap().extract::<usize>().unwrap(), 2);
+            assert_eq!(best.get_item("complementary_pairs").unwrap().extract::<usize>().unwrap(), 4);
+            assert_eq!(object.rank_ms2(py, 1).unwrap().bind(py).len(), 1);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_pathlib_unicode_filename() {
+        let spectra = vec![
+            TestSpectrum::new(1, 1, 10.0, vec![(400.0, 10.0)]),
+            TestSpectrum::new(2, 2, 12.0, vec![(150.0, 5.0)]).with_precursor(400.0, 2),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("样品_Müller_α.mzML");
+        std::fs::write(&path, build_mzml(&spectra)).unwrap();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let reader = Py::new(py, MZMLReader::new()).unwrap();
+            let reader = reader.bind(py);
+            // 与Python端os.fsdecode得到的路径一致；测试中的嵌入解释器可能使用ASCII文件系统编码
+            let fsdecode = |path: &std::path::Path| {
+                use std::os::unix::ffi::OsStrExt;
+                let bytes = pyo3::types::PyBytes::new(py, path.as_os_str().as_bytes());
+                py.import("os").unwrap().call_method1("fsdecode", (bytes,)).unwrap()
+            };
+            let py_path = py.import("pathlib").unwrap().getattr("Path").unwrap().call1((fsdecode(&path),)).unwrap();
+
+            let object = reader.call_method1("read", (&py_path,)).unwrap();
+            let object = object.downcast::<MZMLObject>().unwrap().borrow();
+            assert_eq!(object.spectra.len(), 2);
+            assert_eq!(object.file_info.file_path, path);
+            assert_eq!(reader.call_method1("get_ms2_count", (&py_path,)).unwrap().extract::<usize>().unwrap(), 1);
+
+            let missing = dir.path().join("缺失.mzML");
+            let error = reader.call_method1("read", (fsdecode(&missing),)).unwrap_err();
+            assert!(error.to_string().contains(&format!("{:?}", missing)));
         });
     }
 }
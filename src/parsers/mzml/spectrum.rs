@@ -2,10 +2,11 @@
 //! 
 //! 这个模块定义了mzML格式特有的谱图数据结构
 
-use crate::core::types::*;
-use crate::parsers::common::{CVParam, UserParam, BinaryDataArray, ParseResult, ParseError};
+use crate::core::centroid::{CENTROID_SPECTRUM, PROFILE_SPECTRUM};
+use crate::core::types::{Polarity, ScanNumber};
+use crate::parsers::common::{CVParam, UserParam, BinaryDataArray, ParseResult, ParseError, RtUnit};
+use crate::parsers::mzml::native_id::scan_number_from_native_id;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 /// MZML谱图数据结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,11 +79,14 @@ impl MZMLSpectrum {
         })
     }
 
-    /// 获取谱图类型
+    /// 获取谱图类型："centroid spectrum"（MS:1000127）或"profile spectrum"（MS:1000128）
     pub fn get_spectrum_type(&self) -> Option<String> {
         for param in &self.cv_params {
-            if param.is_accession("MS:1000510") {
-                return Some(param.value.clone());
+            if param.is_accession("MS:1000127") {
+                return Some(CENTROID_SPECTRUM.to_string());
+            }
+            if param.is_accession("MS:1000128") {
+                return Some(PROFILE_SPECTRUM.to_string());
             }
         }
         None
@@ -101,7 +105,7 @@ impl MZMLSpectrum {
     /// 获取基峰强度
     pub fn get_base_peak_intensity(&self) -> Option<f64> {
         for param in &self.cv_params {
-            if param.is_accession("MS:1000284") {
+            if param.is_accession("MS:1000505") {
                 return param.as_f64().ok();
             }
         }
@@ -111,21 +115,78 @@ impl MZMLSpectrum {
     /// 获取基峰m/z
     pub fn get_base_peak_mz(&self) -> Option<f64> {
         for param in &self.cv_params {
-            if param.is_accession("MS:1000283") {
+            if param.is_accession("MS:1000504") {
                 return param.as_f64().ok();
             }
         }
         None
     }
 
-    /// 获取扫描开始时间
-    pub fn get_scan_start_time(&self) -> Option<f64> {
-        for scan in &self.scan_list.scans {
-            if let Some(time) = scan.get_scan_start_time() {
-                return Some(time);
+    /// 获取扫描极性
+    pub fn get_polarity(&self) -> Polarity {
+        for param in &self.cv_params {
+            if param.is_accession("MS:1000130") {
+                return Polarity::Positive;
+            }
+            if param.is_accession("MS:1000129") {
+                return Polarity::Negative;
             }
         }
-        None
+        Polarity::Unknown
+    }
+
+    /// 获取扫描开始时间（秒），按文件中记录的单位换算
+    pub fn get_scan_start_time(&self) -> Option<f64> {
+        self.get_scan_start_time_as(RtUnit::Auto)
+    }
+
+    /// 按指定单位读取扫描开始时间，换算为秒
+    pub fn get_scan_start_time_as(&self, rt_units: RtUnit) -> Option<f64> {
+        self.scan_list.scans.iter().find_map(|scan| scan.get_scan_start_time_as(rt_units))
+    }
+
+    /// 文件中记录的扫描开始时间单位，取自第一个带扫描开始时间的扫描
+    pub fn get_scan_start_time_unit(&self) -> Option<&str> {
+        self.scan_list
+            .scans
+            .iter()
+            .find(|scan| scan.get_scan_start_time().is_some())
+            .and_then(|scan| scan.get_scan_start_time_unit())
+    }
+
+    /// 获取扫描编号
+    ///
+    /// 优先使用第一个扫描的scanNumber属性，没有时从谱图id中提取。
+    pub fn get_scan_number(&self) -> Option<ScanNumber> {
+        self.scan_list
+            .first_scan()
+            .and_then(|scan| scan.scan_number)
+            .or_else(|| scan_number_from_native_id(&self.id))
+    }
+
+    /// 获取离子注入时间（毫秒，来自第一个扫描）
+    pub fn get_ion_injection_time(&self) -> Option<f64> {
+        self.scan_list.first_scan().and_then(|scan| scan.get_ion_injection_time())
+    }
+
+    /// 获取离子淌度漂移时间（秒，来自第一个扫描）
+    pub fn get_ion_mobility_drift_time(&self) -> Option<f64> {
+        self.scan_list.first_scan().and_then(|scan| scan.get_ion_mobility_drift_time())
+    }
+
+    /// 获取反向约化离子淌度1/K0（V·s/cm²，来自第一个扫描）
+    pub fn get_inverse_reduced_ion_mobility(&self) -> Option<f64> {
+        self.scan_list.first_scan().and_then(|scan| scan.get_inverse_reduced_ion_mobility())
+    }
+
+    /// 获取FAIMS补偿电压（伏特）
+    ///
+    /// 优先取第一个扫描中的值，部分转换工具将其写在谱图级cvParam中。
+    pub fn get_faims_compensation_voltage(&self) -> Option<f64> {
+        self.scan_list
+            .first_scan()
+            .and_then(|scan| scan.get_faims_compensation_voltage())
+            .or_else(|| find_f64(&self.cv_params, "MS:1001581"))
     }
 
     /// 获取m/z数组
@@ -161,7 +222,7 @@ impl MZMLSpectrum {
                         mz.len(), intensity.len()
                     )));
                 }
-                Ok(mz.into_iter().zip(intensity.into_iter()).collect())
+                Ok(mz.into_iter().zip(intensity).collect())
             }
             _ => Err(ParseError::MissingField {
                 field: "m/z or intensity array".to_string(),
@@ -200,6 +261,12 @@ pub struct MZMLScanList {
     pub scans: Vec<MZMLScan>,
 }
 
+impl Default for MZMLScanList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MZMLScanList {
     /// 创建新的扫描列表
     pub fn new() -> Self {
@@ -232,6 +299,12 @@ pub struct MZMLScan {
     pub user_params: Vec<UserParam>,
 }
 
+impl Default for MZMLScan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MZMLScan {
     /// 创建新的扫描
     pub fn new() -> Self {
@@ -253,16 +326,69 @@ impl MZMLScan {
         self.user_params.push(param);
     }
 
-    /// 获取扫描开始时间
+    /// 获取扫描开始时间（秒）
+    ///
+    /// 按cvParam的单位换算（UO:0000031分钟、UO:0000010秒），未记录单位时按秒处理。
     pub fn get_scan_start_time(&self) -> Option<f64> {
+        self.get_scan_start_time_as(RtUnit::Auto)
+    }
+
+    /// 按指定单位读取扫描开始时间，换算为秒
+    pub fn get_scan_start_time_as(&self, rt_units: RtUnit) -> Option<f64> {
+        let param = self.scan_start_time_param()?;
+        let value = param.as_f64().ok()?;
+        Some(match rt_units {
+            RtUnit::Seconds => value,
+            RtUnit::Minutes => value * 60.0,
+            RtUnit::Auto if is_unit(param, "minute", "UO:0000031") => value * 60.0,
+            RtUnit::Auto => value,
+        })
+    }
+
+    /// 文件中记录的扫描开始时间单位
+    pub fn get_scan_start_time_unit(&self) -> Option<&str> {
+        self.scan_start_time_param()?.unit()
+    }
+
+    fn scan_start_time_param(&self) -> Option<&CVParam> {
+        self.cv_params.iter().find(|param| param.is_accession("MS:1000016"))
+    }
+
+    /// 获取离子注入时间（毫秒）
+    pub fn get_ion_injection_time(&self) -> Option<f64> {
         for param in &self.cv_params {
-            if param.is_accession("MS:1000016") {
+            if param.is_accession("MS:1000927") {
                 return param.as_f64().ok();
             }
         }
         None
     }
 
+    /// 获取离子淌度漂移时间（秒）
+    ///
+    /// MS:1002476按单位换算：毫秒除以1000，分钟乘以60；未给出单位时按该术语的默认单位毫秒处理。
+    pub fn get_ion_mobility_drift_time(&self) -> Option<f64> {
+        let param = self.cv_params.iter().find(|param| param.is_accession("MS:1002476"))?;
+        let value = param.as_f64().ok()?;
+        Some(if is_unit(param, "second", "UO:0000010") {
+            value
+        } else if is_unit(param, "minute", "UO:0000031") {
+            value * 60.0
+        } else {
+            value / 1000.0
+        })
+    }
+
+    /// 获取反向约化离子淌度1/K0（V·s/cm²，MS:1002815）
+    pub fn get_inverse_reduced_ion_mobility(&self) -> Option<f64> {
+        find_f64(&self.cv_params, "MS:1002815")
+    }
+
+    /// 获取FAIMS补偿电压（伏特，MS:1001581）
+    pub fn get_faims_compensation_voltage(&self) -> Option<f64> {
+        find_f64(&self.cv_params, "MS:1001581")
+    }
+
     /// 获取扫描窗口下限
     pub fn get_scan_window_lower_limit(&self) -> Option<f64> {
         for param in &self.cv_params {
@@ -307,6 +433,12 @@ pub struct MZMLPrecursor {
     pub activation: Option<MZMLActivation>,
 }
 
+impl Default for MZMLPrecursor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MZMLPrecursor {
     /// 创建新的前体离子
     pub fn new() -> Self {
@@ -379,6 +511,12 @@ pub struct MZMLIsolationWindow {
     pub user_params: Vec<UserParam>,
 }
 
+impl Default for MZMLIsolationWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MZMLIsolationWindow {
     /// 创建新的分离窗口
     pub fn new() -> Self {
@@ -438,6 +576,12 @@ pub struct MZMLActivation {
     pub user_params: Vec<UserParam>,
 }
 
+impl Default for MZMLActivation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MZMLActivation {
     /// 创建新的激活信息
     pub fn new() -> Self {
@@ -464,24 +608,59 @@ impl MZMLActivation {
                param.is_accession("MS:1000134") || // HCD
                param.is_accession("MS:1000135") || // ETD
                param.is_accession("MS:1000136") || // ECD
-               param.is_accession("MS:1000137") { // PQD
+               param.is_accession("MS:1000137") || // PQD
+               param.is_accession("MS:1000422") || // beam-type CID (HCD)
+               param.is_accession("MS:1000598") || // electron transfer dissociation
+               param.is_accession("MS:1000250") || // electron capture dissociation
+               param.is_accession("MS:1000599") { // pulsed q dissociation
                 return Some(param.value.clone());
             }
         }
         None
     }
 
-    /// 获取碰撞能量
+    /// 获取碰撞能量 (eV)
+    ///
+    /// 读取MS:1000045 (collision energy)。部分转换工具把归一化碰撞能量也写成
+    /// MS:1000045并标注百分比单位，这种情况不作为eV返回。
     pub fn get_collision_energy(&self) -> Option<f64> {
-        for param in &self.cv_params {
-            if param.is_accession("MS:1000045") {
-                return param.as_f64().ok();
-            }
-        }
-        None
+        self.cv_params
+            .iter()
+            .find(|param| param.is_accession("MS:1000045") && !is_percent_unit(param))
+            .and_then(|param| param.as_f64().ok())
+    }
+
+    /// 获取归一化碰撞能量 (NCE，%)
+    ///
+    /// 读取MS:1000138 (normalized collision energy)，以及带百分比单位的MS:1000045。
+    pub fn get_normalized_collision_energy(&self) -> Option<f64> {
+        self.cv_params
+            .iter()
+            .find(|param| {
+                param.is_accession("MS:1000138") || (param.is_accession("MS:1000045") && is_percent_unit(param))
+            })
+            .and_then(|param| param.as_f64().ok())
     }
 }
 
+/// 取指定访问号的第一个CV参数的数值
+fn find_f64(params: &[CVParam], accession: &str) -> Option<f64> {
+    params
+        .iter()
+        .find(|param| param.is_accession(accession))
+        .and_then(|param| param.as_f64().ok())
+}
+
+/// CV参数的单位是否为指定单位，缺少单位访问号时按单位名称比较
+fn is_unit(param: &CVParam, name: &str, accession: &str) -> bool {
+    param.unit_is(accession) || param.unit_name.as_deref().is_some_and(|unit| unit.eq_ignore_ascii_case(name))
+}
+
+/// CV参数的单位是否为百分比（UO:0000187）
+fn is_percent_unit(param: &CVParam) -> bool {
+    is_unit(param, "percent", "UO:0000187")
+}
+
 /// MZML二进制数据数组
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MZMLBinaryDataArray {
@@ -495,6 +674,12 @@ pub struct MZMLBinaryDataArray {
     pub binary: Option<BinaryDataArray>,
 }
 
+impl Default for MZMLBinaryDataArray {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MZMLBinaryDataArray {
     /// 创建新的二进制数据数组
     pub fn new() -> Self {
@@ -541,10 +726,18 @@ impl MZMLBinaryDataArray {
         false
     }
 
+    /// 检查是否为时间数组
+    pub fn is_time_array(&self) -> bool {
+        self.cv_params.iter().any(|param| param.is_accession("MS:1000595"))
+    }
+
     /// 解码为f64数组
+    ///
+    /// 长度为0的数组不压缩时`<binary>`为空，解码为空数组。
     pub fn decode_f64(&self) -> ParseResult<Vec<f64>> {
         match &self.binary {
             Some(binary) => binary.decode_f64(),
+            None if self.length == Some(0) => Ok(Vec::new()),
             None => Err(ParseError::EmptyDataArray),
         }
     }
@@ -553,6 +746,7 @@ impl MZMLBinaryDataArray {
     pub fn decode_f32(&self) -> ParseResult<Vec<f32>> {
         match &self.binary {
             Some(binary) => binary.decode_f32(),
+            None if self.length == Some(0) => Ok(Vec::new()),
             None => Err(ParseError::EmptyDataArray),
         }
     }
@@ -585,6 +779,28 @@ mod tests {
         assert_eq!(scan.get_scan_start_time().unwrap(), 10.5);
     }
 
+    #[test]
+    fn test_ion_mobility_drift_time_units() {
+        let drift_time = |value: &str, unit: Option<&str>| {
+            let mut param = CVParam::new("MS:1002476", "ion mobility drift time", value);
+            if let Some(unit) = unit {
+                param = param.with_unit_name(unit);
+            }
+            let mut scan = MZMLScan::new();
+            scan.add_cv_param(param);
+            scan.get_ion_mobility_drift_time().unwrap()
+        };
+
+        assert!((drift_time("25.0", Some("millisecond")) - 0.025).abs() < 1e-12);
+        assert_eq!(drift_time("0.025", Some("second")), 0.025);
+        assert!((drift_time("25.0", None) - 0.025).abs() < 1e-12);
+        assert!(MZMLScan::new().get_ion_mobility_drift_time().is_none());
+
+        let mut scan = MZMLScan::new();
+        scan.add_cv_param(CVParam::new("MS:1002476", "ion mobility drift time", "0.025").with_unit_accession("UO:0000010"));
+        assert_eq!(scan.get_ion_mobility_drift_time(), Some(0.025));
+    }
+
     #[test]
     fn test_precursor_creation() {
         let mut precursor = MZMLPrecursor::new();
@@ -0,0 +1,248 @@
+//! mzML子集提取
+//!
+//! 按保留时间、扫描编号和MS级别从mzML文件中截取部分谱图写出为新的indexedmzML。
+//! 保留的谱图按原样复制（包括base64编码的二进制数据，不做解码），
+//! 仅重新编号index属性并重建索引；仪器、运行等元数据全部保留。
+
+use crate::core::filter::SpectrumFilter;
+use crate::parsers::common::{open_file, ParseError, ParseResult};
+use crate::parsers::mzml::parser::MZMLParser;
+use crate::parsers::mzml::spectrum::MZMLSpectrum;
+use crate::parsers::mzml::writer::MZMLWriter;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use std::path::Path;
+
+/// 子集提取结果统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubsetSummary {
+    /// 保留的谱图数
+    pub kept: usize,
+    /// 丢弃的谱图数
+    pub dropped: usize,
+}
+
+/// 从`input`中提取满足`filter`的谱图写入`output`
+pub fn extract_subset(input: impl AsRef<Path>, output: impl AsRef<Path>, filter: &SpectrumFilter) -> ParseResult<SubsetSummary> {
+    let input = input.as_ref();
+    // 第一遍：只读取谱图头信息决定去留
+    let keep: Vec<bool> = MZMLParser::new()
+        .parse_headers(input)?
+        .iter()
+        .map(|spectrum| header_matches(filter, spectrum))
+        .collect();
+
+    // 第二遍：流式复制
+    let file = open_file(input)?;
+    let mut reader = Reader::from_reader(std::io::BufReader::new(file));
+    let mut writer = MZMLWriter::create(output)?;
+
+    let kept = keep.iter().filter(|&&k| k).count();
+    let mut buf = Vec::new();
+    let mut spectrum_ordinal = 0usize;
+    // 正在跳过的元素名及其嵌套深度
+    let mut skipping: Option<(Vec<u8>, usize)> = None;
+
+    loop {
+        let event = reader.read_event_into(&mut buf).map_err(ParseError::xml)?;
+
+        if let Some((ref name, ref mut depth)) = skipping {
+            match event {
+                Event::Start(ref e) if e.name().as_ref() == name.as_slice() => *depth += 1,
+                Event::End(ref e) if e.name().as_ref() == name.as_slice() => {
+                    *depth -= 1;
+                    if *depth == 0 {
+                        skipping = None;
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+            continue;
+        }
+
+        match event {
+            Event::Eof => break,
+            // 原文件的索引包装和索引内容全部重新生成
+            Event::Start(ref e) if e.name().as_ref() == b"indexedmzML" => {}
+            Event::End(ref e) if e.name().as_ref() == b"indexedmzML" => {}
+            Event::Start(ref e) if is_index_element(e.name().as_ref()) => {
+                skipping = Some((e.name().as_ref().to_vec(), 1));
+            }
+            Event::Empty(ref e) if is_index_element(e.name().as_ref()) => {}
+            Event::Start(ref e) if e.name().as_ref() == b"mzML" => {
+                writer.start_indexed()?;
+                writer.write_event(event.borrow())?;
+            }
+            Event::Start(ref e) if e.name().as_ref() == b"spectrumList" => {
+                let start = with_attribute(e, "count", &kept.to_string())?;
+                writer.write_event(Event::Start(start))?;
+            }
+            Event::Start(ref e) if e.name().as_ref() == b"spectrum" => {
+                let ordinal = spectrum_ordinal;
+                spectrum_ordinal += 1;
+
+                if keep.get(ordinal).copied().unwrap_or(false) {
+                    let index = writer.spectrum_count().to_string();
+                    let start = with_attribute(e, "index", &index)?;
+                    writer.write_event(Event::Start(start))?;
+                } else {
+                    skipping = Some((b"spectrum".to_vec(), 1));
+                }
+            }
+            // 没有内容的空谱图无法被过滤条件判断，直接丢弃
+            Event::Empty(ref e) if e.name().as_ref() == b"spectrum" => {}
+            _ => writer.write_event(event.borrow())?,
+        }
+        buf.clear();
+    }
+
+    writer.finish()?;
+
+    Ok(SubsetSummary {
+        kept,
+        dropped: keep.len() - kept,
+    })
+}
+
+/// 检查谱图头信息是否满足过滤条件
+fn header_matches(filter: &SpectrumFilter, spectrum: &MZMLSpectrum) -> bool {
+    // 缺少MS级别的谱图只在未按级别过滤时保留
+    let level = match spectrum.get_ms_level() {
+        Ok(level) => level,
+        Err(_) if filter.ms_levels.is_some() => return false,
+        Err(_) => 0,
+    };
+
+    filter.matches(
+        level,
+        spectrum.get_scan_number().unwrap_or(0),
+        spectrum.get_scan_start_time().unwrap_or(0.0),
+    )
+}
+
+/// indexedmzML中需要重新生成的索引元素
+fn is_index_element(name: &[u8]) -> bool {
+    matches!(name, b"indexList" | b"indexListOffset" | b"fileChecksum")
+}
+
+/// 复制起始标签并替换（或追加）一个属性
+fn with_attribute<'a>(event: &BytesStart, key: &str, value: &str) -> ParseResult<BytesStart<'a>> {
+    let name = std::str::from_utf8(event.name().as_ref())
+        .map_err(ParseError::xml)?
+        .to_string();
+    let mut start = BytesStart::new(name);
+    let mut replaced = false;
+
+    for attr in event.attributes() {
+        let attr = attr.map_err(ParseError::xml)?;
+        if attr.key.as_ref() == key.as_bytes() {
+            start.push_attribute((key, value));
+            replaced = true;
+        } else {
+            start.push_attribute(attr);
+        }
+    }
+    if !replaced {
+        start.push_attribute((key, value));
+    }
+
+    Ok(start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::mzml::test_data::{build_mzml, write_temp_file, TestSpectrum};
+
+    fn generated_run() -> Vec<TestSpectrum> {
+        (0..10u32)
+            .map(|i| {
+                let rt = 10.0 * i as f64;
+                let peaks = vec![(100.0 + i as f64, 1000.0), (200.5, 50.0 * (i + 1) as f64)];
+                if i % 2 == 0 {
+                    TestSpectrum::new(i + 1, 1, rt, peaks)
+                } else {
+                    TestSpectrum::new(i + 1, 2, rt, peaks).with_precursor(500.25, 2)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_extract_rt_range() {
+        let input = write_temp_file(&build_mzml(&generated_run()));
+        let output = tempfile::Builder::new().suffix(".mzML").tempfile().unwrap();
+        let input_path = input.path().to_str().unwrap();
+        let output_path = output.path().to_str().unwrap();
+
+        let filter = SpectrumFilter::new().with_rt_range(20.0, 40.0);
+        let summary = extract_subset(input_path, output_path, &filter).unwrap();
+        assert_eq!(summary, SubsetSummary { kept: 3, dropped: 7 });
+
+        let parser = MZMLParser::new();
+        let originals = parser.parse_sequential(input_path).unwrap();
+        let subset = parser.parse_sequential(output_path).unwrap();
+        let expected: Vec<_> = filter.apply(&originals).into_iter().cloned().collect();
+        assert_eq!(subset.len(), 3);
+        for (position, (actual, expected)) in subset.iter().zip(&expected).enumerate() {
+            assert_eq!(actual.level, expected.level);
+            // 源文件下标随index重新编号
+            assert_eq!(actual.scan.source_index, Some(position));
+            let mut scan = actual.scan.clone();
+            scan.source_index = expected.scan.source_index;
+            assert_eq!(scan, expected.scan);
+            assert_eq!(actual.peaks(), expected.peaks());
+            assert_eq!(actual.precursor, expected.precursor);
+        }
+
+        // 原始native id保留，index重新编号，元数据保留
+        let headers = parser.parse_headers(output_path).unwrap();
+        let ids: Vec<&str> = headers.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec![
+            "controllerType=0 controllerNumber=1 scan=3",
+            "controllerType=0 controllerNumber=1 scan=4",
+            "controllerType=0 controllerNumber=1 scan=5",
+        ]);
+        let indices: Vec<Option<usize>> = headers.iter().map(|s| s.index).collect();
+        assert_eq!(indices, vec![Some(0), Some(1), Some(2)]);
+
+        let content = std::fs::read_to_string(output_path).unwrap();
+        assert!(content.contains("<spectrumList count=\"3\""));
+        assert!(content.contains("LTQ Orbitrap Velos"));
+        assert!(content.contains("defaultInstrumentConfigurationRef=\"IC1\""));
+    }
+
+    #[test]
+    fn test_output_is_indexed() {
+        let input = write_temp_file(&build_mzml(&generated_run()));
+        let output = tempfile::Builder::new().suffix(".mzML").tempfile().unwrap();
+        let output_path = output.path().to_str().unwrap();
+
+        let filter = SpectrumFilter::new().with_ms_levels(vec![2]);
+        let summary = extract_subset(input.path().to_str().unwrap(), output_path, &filter).unwrap();
+        assert_eq!(summary.kept, 5);
+
+        let content = std::fs::read_to_string(output_path).unwrap();
+        assert!(content.starts_with("<?xml"));
+        assert!(content.contains("<indexedmzML"));
+        assert_eq!(content.matches("<offset idRef=").count(), 5);
+
+        let marker = "<offset idRef=\"controllerType=0 controllerNumber=1 scan=2\">";
+        let start = content.find(marker).unwrap() + marker.len();
+        let end = start + content[start..].find('<').unwrap();
+        let offset: usize = content[start..end].parse().unwrap();
+        assert!(content[offset..].starts_with("<spectrum index=\"0\" id=\"controllerType=0 controllerNumber=1 scan=2\""));
+
+        // 再次提取已索引的文件：旧索引被替换而不是重复
+        let second = tempfile::Builder::new().suffix(".mzML").tempfile().unwrap();
+        let second_path = second.path().to_str().unwrap();
+        let summary = extract_subset(output_path, second_path, &SpectrumFilter::new()).unwrap();
+        assert_eq!(summary, SubsetSummary { kept: 5, dropped: 0 });
+        let content = std::fs::read_to_string(second_path).unwrap();
+        assert_eq!(content.matches("<indexList ").count(), 1);
+        assert_eq!(content.matches("<indexedmzML").count(), 1);
+        assert_eq!(MZMLParser::new().parse_sequential(second_path).unwrap().len(), 5);
+    }
+}
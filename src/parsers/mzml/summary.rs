@@ -0,0 +1,139 @@
+//! 运行概要
+//!
+//! 只读取元数据得到的文件级统计：各MS级别的谱图数、保留时间范围、质心/轮廓谱图数，
+//! 以及`<run>`之前记录的mzML版本、仪器型号和是否有indexedmzML外层元素。
+//! 由[`MZMLParser::run_summary`](crate::parsers::mzml::MZMLParser::run_summary)生成，不解码任何二进制数组。
+
+use std::collections::BTreeMap;
+
+use crate::core::centroid::{CENTROID_SPECTRUM, PROFILE_SPECTRUM};
+use crate::core::types::{MSLevel, RetentionTime};
+use crate::parsers::common::ParseResult;
+use crate::parsers::mzml::spectrum::MZMLSpectrum;
+
+/// mzML文件的运行概要
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunSummary {
+    /// 根元素`<mzML>`的version属性
+    pub version: Option<String>,
+    /// 第一个`<instrumentConfiguration>`中记录的仪器型号（cvParam名称）
+    pub instrument_model: Option<String>,
+    /// 是否有indexedmzML外层元素
+    pub indexed: bool,
+    /// 谱图数，不含按错误策略跳过的谱图
+    pub spectrum_count: usize,
+    /// 各MS级别的谱图数
+    pub ms_level_counts: BTreeMap<MSLevel, usize>,
+    /// 保留时间范围 (秒)，没有谱图记录扫描开始时间时为None
+    pub rt_range: Option<(RetentionTime, RetentionTime)>,
+    /// 标记为质心谱图的谱图数
+    pub centroid_count: usize,
+    /// 标记为轮廓谱图的谱图数
+    pub profile_count: usize,
+}
+
+impl RunSummary {
+    /// 统计一个只解析了元数据的谱图，缺少MS级别时返回错误且不计入
+    pub fn add_spectrum(&mut self, mzml_spectrum: &MZMLSpectrum) -> ParseResult<()> {
+        let ms_level = mzml_spectrum.get_ms_level()?;
+        self.spectrum_count += 1;
+        *self.ms_level_counts.entry(ms_level).or_insert(0) += 1;
+
+        if let Some(rt) = mzml_spectrum.get_scan_start_time() {
+            self.rt_range = Some(match self.rt_range {
+                Some((min, max)) => (min.min(rt), max.max(rt)),
+                None => (rt, rt),
+            });
+        }
+        match mzml_spectrum.get_spectrum_type().as_deref() {
+            Some(CENTROID_SPECTRUM) => self.centroid_count += 1,
+            Some(PROFILE_SPECTRUM) => self.profile_count += 1,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// 数据类型："centroid"、"profile"，两者都有时为"mixed"，没有谱图标记类型时为None
+    pub fn spectrum_type(&self) -> Option<&'static str> {
+        match (self.centroid_count > 0, self.profile_count > 0) {
+            (true, true) => Some("mixed"),
+            (true, false) => Some("centroid"),
+            (false, true) => Some("profile"),
+            (false, false) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::mzml::parser::MZMLParser;
+    use crate::parsers::mzml::test_data::{build_mzml, write_temp_file, TestSpectrum};
+
+    fn fixture() -> String {
+        build_mzml(&[
+            TestSpectrum::new(1, 1, 12.5, vec![(400.0, 10.0), (500.0, 20.0)]).with_profile(),
+            TestSpectrum::new(2, 2, 13.0, vec![(150.0, 5.0)]).with_precursor(500.0, 2),
+            TestSpectrum::new(3, 2, 14.0, vec![]).with_precursor(400.0, 2),
+            TestSpectrum::new(4, 1, 10.0, vec![(400.0, 10.0)]).with_profile(),
+            TestSpectrum::new(5, 3, 15.25, vec![(120.0, 1.0)]).with_precursor(150.0, 1),
+        ])
+    }
+
+    #[test]
+    fn test_run_summary_fields() {
+        let file = write_temp_file(&fixture());
+        let summary = MZMLParser::new().run_summary(file.path()).unwrap();
+        assert_eq!(
+            summary,
+            RunSummary {
+                version: Some("1.1.0".to_string()),
+                instrument_model: Some("LTQ Orbitrap Velos".to_string()),
+                indexed: false,
+                spectrum_count: 5,
+                ms_level_counts: BTreeMap::from([(1, 2), (2, 2), (3, 1)]),
+                rt_range: Some((10.0, 15.25)),
+                centroid_count: 3,
+                profile_count: 2,
+            }
+        );
+        assert_eq!(summary.spectrum_type(), Some("mixed"));
+    }
+
+    #[test]
+    fn test_indexed_wrapper_and_param_group_model() {
+        // 仪器型号通过referenceableParamGroup引用，序列号和componentList中的参数不算型号
+        let xml = fixture()
+            .replace(
+                r#"<mzML xmlns="http://psi.hupo.org/ms/mzml" version="1.1.0" id="test_run">"#,
+                r#"<indexedmzML xmlns="http://psi.hupo.org/ms/mzml"><mzML xmlns="http://psi.hupo.org/ms/mzml" version="1.1.0" id="test_run">"#,
+            )
+            .replace(
+                "  <softwareList",
+                r#"  <referenceableParamGroupList count="1">
+    <referenceableParamGroup id="CommonInstrumentParams">
+      <cvParam cvRef="MS" accession="MS:1000529" name="instrument serial number" value="SN1"/>
+      <cvParam cvRef="MS" accession="MS:1002732" name="Orbitrap Fusion Lumos" value=""/>
+    </referenceableParamGroup>
+  </referenceableParamGroupList>
+  <softwareList"#,
+            )
+            .replace(
+                r#"      <cvParam cvRef="MS" accession="MS:1001742" name="LTQ Orbitrap Velos" value=""/>"#,
+                r#"      <componentList count="1">
+        <source order="1"><cvParam cvRef="MS" accession="MS:1000073" name="electrospray ionization" value=""/></source>
+      </componentList>
+      <referenceableParamGroupRef ref="CommonInstrumentParams"/>"#,
+            )
+            .replace("</mzML>", "</mzML>\n</indexedmzML>");
+        let file = write_temp_file(&xml);
+        let summary = MZMLParser::new().run_summary(file.path()).unwrap();
+        assert!(summary.indexed);
+        assert_eq!(summary.instrument_model.as_deref(), Some("Orbitrap Fusion Lumos"));
+        assert_eq!(summary.spectrum_count, 5);
+
+        let centroided = write_temp_file(&build_mzml(&[TestSpectrum::new(1, 1, 1.0, vec![(100.0, 1.0)])]));
+        assert_eq!(MZMLParser::new().run_summary(centroided.path()).unwrap().spectrum_type(), Some("centroid"));
+        assert_eq!(RunSummary::default().spectrum_type(), None);
+    }
+}
@@ -0,0 +1,199 @@
+//! mzML测试数据生成
+//!
+//! 为解析器、写入器等模块的测试生成小型mzML文件
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::io::Write;
+
+/// 测试用谱图描述
+#[derive(Debug, Clone)]
+pub(crate) struct TestSpectrum {
+    pub id: String,
+    pub ms_level: u8,
+    /// 保留时间（秒）
+    pub rt: f64,
+    pub peaks: Vec<(f64, f64)>,
+    pub precursor: Option<(f64, i8)>,
+    /// 分离窗口：(目标m/z, 下偏移, 上偏移)
+    pub isolation_window: Option<(f64, f64, f64)>,
+    /// 离子注入时间（毫秒）
+    pub injection_time: Option<f64>,
+    /// 极性，None时不写出极性cvParam
+    pub positive: Option<bool>,
+    /// 前体离子activation中额外的cvParam：(accession, name, value, unitName)
+    pub activation_params: Vec<(&'static str, &'static str, f64, &'static str)>,
+    /// 是否标记为轮廓谱图，否则标记为质心谱图
+    pub profile: bool,
+}
+
+impl TestSpectrum {
+    pub fn new(scan: u32, ms_level: u8, rt: f64, peaks: Vec<(f64, f64)>) -> Self {
+        Self {
+            id: format!("controllerType=0 controllerNumber=1 scan={}", scan),
+            ms_level,
+            rt,
+            peaks,
+            precursor: None,
+            isolation_window: None,
+            injection_time: None,
+            positive: None,
+            activation_params: Vec::new(),
+            profile: false,
+        }
+    }
+
+    pub fn with_precursor(mut self, mz: f64, charge: i8) -> Self {
+        self.precursor = Some((mz, charge));
+        self
+    }
+
+    pub fn with_isolation_window(mut self, target_mz: f64, lower_offset: f64, upper_offset: f64) -> Self {
+        self.isolation_window = Some((target_mz, lower_offset, upper_offset));
+        self
+    }
+
+    pub fn with_injection_time(mut self, injection_time: f64) -> Self {
+        self.injection_time = Some(injection_time);
+        self
+    }
+
+    pub fn with_polarity(mut self, positive: bool) -> Self {
+        self.positive = Some(positive);
+        self
+    }
+
+    pub fn with_profile(mut self) -> Self {
+        self.profile = true;
+        self
+    }
+
+    pub fn with_activation_param(mut self, accession: &'static str, name: &'static str, value: f64, unit: &'static str) -> Self {
+        self.activation_params.push((accession, name, value, unit));
+        self
+    }
+}
+
+pub(crate) fn encode_f64(values: &[f64]) -> String {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    STANDARD.encode(bytes)
+}
+
+/// 生成包含仪器和运行元数据的mzML文档
+pub(crate) fn build_mzml(spectra: &[TestSpectrum]) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="utf-8"?>
+<mzML xmlns="http://psi.hupo.org/ms/mzml" version="1.1.0" id="test_run">
+  <cvList count="2">
+    <cv id="MS" fullName="Proteomics Standards Initiative Mass Spectrometry Ontology" version="4.1.0" URI="https://raw.githubusercontent.com/HUPO-PSI/psi-ms-CV/master/psi-ms.obo"/>
+    <cv id="UO" fullName="Unit Ontology" URI="http://ontologies.berkeleybop.org/uo.obo"/>
+  </cvList>
+  <fileDescription>
+    <fileContent>
+      <cvParam cvRef="MS" accession="MS:1000579" name="MS1 spectrum" value=""/>
+    </fileContent>
+  </fileDescription>
+  <softwareList count="1">
+    <software id="test" version="1.0"/>
+  </softwareList>
+  <instrumentConfigurationList count="1">
+    <instrumentConfiguration id="IC1">
+      <cvParam cvRef="MS" accession="MS:1001742" name="LTQ Orbitrap Velos" value=""/>
+    </instrumentConfiguration>
+  </instrumentConfigurationList>
+  <dataProcessingList count="1">
+    <dataProcessing id="dp">
+      <processingMethod order="0" softwareRef="test"/>
+    </dataProcessing>
+  </dataProcessingList>
+  <run id="test_run" defaultInstrumentConfigurationRef="IC1">
+"#);
+    xml.push_str(&format!("    <spectrumList count=\"{}\" defaultDataProcessingRef=\"dp\">\n", spectra.len()));
+
+    for (index, spectrum) in spectra.iter().enumerate() {
+        let mz: Vec<f64> = spectrum.peaks.iter().map(|p| p.0).collect();
+        let intensity: Vec<f64> = spectrum.peaks.iter().map(|p| p.1).collect();
+
+        xml.push_str(&format!(
+            "      <spectrum index=\"{}\" id=\"{}\" defaultArrayLength=\"{}\">\n",
+            index, spectrum.id, spectrum.peaks.len()
+        ));
+        xml.push_str(&format!(
+            "        <cvParam cvRef=\"MS\" accession=\"MS:1000511\" name=\"ms level\" value=\"{}\"/>\n",
+            spectrum.ms_level
+        ));
+        if spectrum.profile {
+            xml.push_str("        <cvParam cvRef=\"MS\" accession=\"MS:1000128\" name=\"profile spectrum\" value=\"\"/>\n");
+        } else {
+            xml.push_str("        <cvParam cvRef=\"MS\" accession=\"MS:1000127\" name=\"centroid spectrum\" value=\"\"/>\n");
+        }
+        match spectrum.positive {
+            Some(true) => xml.push_str("        <cvParam cvRef=\"MS\" accession=\"MS:1000130\" name=\"positive scan\" value=\"\"/>\n"),
+            Some(false) => xml.push_str("        <cvParam cvRef=\"MS\" accession=\"MS:1000129\" name=\"negative scan\" value=\"\"/>\n"),
+            None => {}
+        }
+        if let Some(&(base_mz, base_intensity)) = spectrum.peaks.iter().max_by(|a, b| a.1.total_cmp(&b.1)) {
+            xml.push_str(&format!(
+                "        <cvParam cvRef=\"MS\" accession=\"MS:1000285\" name=\"total ion current\" value=\"{}\"/>\n        <cvParam cvRef=\"MS\" accession=\"MS:1000504\" name=\"base peak m/z\" value=\"{}\"/>\n        <cvParam cvRef=\"MS\" accession=\"MS:1000505\" name=\"base peak intensity\" value=\"{}\"/>\n",
+                intensity.iter().sum::<f64>(), base_mz, base_intensity
+            ));
+        }
+        let injection_time = spectrum.injection_time.map_or(String::new(), |time| format!(
+            "            <cvParam cvRef=\"MS\" accession=\"MS:1000927\" name=\"ion injection time\" value=\"{}\" unitCvRef=\"UO\" unitAccession=\"UO:0000028\" unitName=\"millisecond\"/>\n",
+            time
+        ));
+        xml.push_str(&format!(
+            "        <scanList count=\"1\">\n          <cvParam cvRef=\"MS\" accession=\"MS:1000795\" name=\"no combination\" value=\"\"/>\n          <scan>\n            <cvParam cvRef=\"MS\" accession=\"MS:1000016\" name=\"scan start time\" value=\"{}\" unitCvRef=\"UO\" unitAccession=\"UO:0000010\" unitName=\"second\"/>\n{}          </scan>\n        </scanList>\n",
+            spectrum.rt, injection_time
+        ));
+        if let Some((precursor_mz, charge)) = spectrum.precursor {
+            let activation_params: String = spectrum.activation_params.iter().map(|(accession, name, value, unit)| format!(
+                "              <cvParam cvRef=\"MS\" accession=\"{}\" name=\"{}\" value=\"{}\" unitCvRef=\"UO\" unitName=\"{}\"/>\n",
+                accession, name, value, unit
+            )).collect();
+            let isolation_window = spectrum.isolation_window.map_or(String::new(), |(target, lower, upper)| format!(
+                "            <isolationWindow>\n              <cvParam cvRef=\"MS\" accession=\"MS:1000827\" name=\"isolation window target m/z\" value=\"{}\"/>\n              <cvParam cvRef=\"MS\" accession=\"MS:1000828\" name=\"isolation window lower offset\" value=\"{}\"/>\n              <cvParam cvRef=\"MS\" accession=\"MS:1000829\" name=\"isolation window upper offset\" value=\"{}\"/>\n            </isolationWindow>\n",
+                target, lower, upper
+            ));
+            xml.push_str(&format!(
+                "        <precursorList count=\"1\">\n          <precursor>\n{}            <selectedIonList count=\"1\">\n              <selectedIon>\n                <cvParam cvRef=\"MS\" accession=\"MS:1000744\" name=\"selected ion m/z\" value=\"{}\"/>\n                <cvParam cvRef=\"MS\" accession=\"MS:1000041\" name=\"charge state\" value=\"{}\"/>\n              </selectedIon>\n            </selectedIonList>\n            <activation>\n              <cvParam cvRef=\"MS\" accession=\"MS:1000133\" name=\"collision-induced dissociation\" value=\"\"/>\n{}            </activation>\n          </precursor>\n        </precursorList>\n",
+                isolation_window, precursor_mz, charge, activation_params
+            ));
+        }
+        xml.push_str("        <binaryDataArrayList count=\"2\">\n");
+        for (values, accession, name) in [(&mz, "MS:1000514", "m/z array"), (&intensity, "MS:1000515", "intensity array")] {
+            let encoded = encode_f64(values);
+            xml.push_str(&format!(
+                "          <binaryDataArray encodedLength=\"{}\">\n            <cvParam cvRef=\"MS\" accession=\"MS:1000523\" name=\"64-bit float\" value=\"\"/>\n            <cvParam cvRef=\"MS\" accession=\"MS:1000576\" name=\"no compression\" value=\"\"/>\n            <cvParam cvRef=\"MS\" accession=\"{}\" name=\"{}\" value=\"\"/>\n            <binary>{}</binary>\n          </binaryDataArray>\n",
+                encoded.len(), accession, name, encoded
+            ));
+        }
+        xml.push_str("        </binaryDataArrayList>\n      </spectrum>\n");
+    }
+
+    xml.push_str("    </spectrumList>\n  </run>\n</mzML>\n");
+    xml
+}
+
+/// 将内容写入临时文件
+pub(crate) fn write_temp_file(content: &str) -> tempfile::NamedTempFile {
+    let mut file = tempfile::Builder::new().suffix(".mzML").tempfile().unwrap();
+    file.write_all(content.as_bytes()).unwrap();
+    file.flush().unwrap();
+    file
+}
+
+/// 生成第二个谱图的base64数据损坏的文件
+pub(crate) fn corrupt_spectrum_file(corrupt_id: &str) -> tempfile::NamedTempFile {
+    let mut spectra = vec![
+        TestSpectrum::new(1, 1, 1.0, vec![(100.0, 10.0)]),
+        TestSpectrum::new(2, 1, 2.0, vec![(200.0, 20.0)]),
+        TestSpectrum::new(3, 1, 3.0, vec![(300.0, 30.0)]),
+    ];
+    spectra[1].id = corrupt_id.to_string();
+    let xml = build_mzml(&spectra);
+
+    let spectrum_start = xml.find(corrupt_id).unwrap();
+    let binary_start = spectrum_start + xml[spectrum_start..].find("<binary>").unwrap() + "<binary>".len();
+    let mut corrupted = xml.clone();
+    corrupted.insert_str(binary_start, "!!not base64!!");
+    write_temp_file(&corrupted)
+}
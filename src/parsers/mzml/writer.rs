@@ -0,0 +1,459 @@
+//! MZML写入器
+//!
+//! 这个模块提供了indexedmzML格式的输出：写入的XML事件原样转发到底层输出，
+//! 同时记录每个spectrum/chromatogram元素的字节偏移，结束时追加indexList、
+//! indexListOffset和SHA-1文件校验和。
+//!
+//! [`MZMLWriter::write_spectrum`]和[`write_spectra`]把[`Spectrum`]序列化为mzML：
+//! 谱图带有源文件的native id时原样写出，否则按扫描编号生成`scan=N`。
+//! 完整文档（cvList、元数据、编码选项）由[`crate::conversion::MZMLWriter`]生成。
+
+use crate::conversion::Encoder;
+use crate::core::spectrum::{PrecursorInfo, Spectrum};
+use crate::core::types::Polarity;
+use crate::parsers::common::{create_file, BinaryDataArray, CompressionType, ParseError, ParseResult};
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesDecl, BytesStart, Event};
+use quick_xml::writer::Writer;
+use sha1::{Digest, Sha1};
+use std::io::{self, Write};
+use std::path::Path;
+use std::str;
+
+/// indexedmzML根元素的命名空间声明
+const INDEXED_MZML_START: &str = r#"<indexedmzML xmlns="http://psi.hupo.org/ms/mzml" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://psi.hupo.org/ms/mzml http://psidev.info/files/ms/mzML/xsd/mzML1.1.2_idx.xsd">"#;
+
+/// 记录字节位置并计算SHA-1的输出包装
+struct IndexingSink<W: Write> {
+    inner: W,
+    position: u64,
+    hasher: Sha1,
+}
+
+impl<W: Write> Write for IndexingSink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// indexedmzML写入器
+pub struct MZMLWriter<W: Write> {
+    writer: Writer<IndexingSink<W>>,
+    /// 谱图native id及其起始标签的字节偏移
+    spectrum_offsets: Vec<(String, u64)>,
+    /// 色谱图native id及其起始标签的字节偏移
+    chromatogram_offsets: Vec<(String, u64)>,
+    /// 是否已写出indexedmzML根元素
+    indexed: bool,
+}
+
+impl MZMLWriter<io::BufWriter<std::fs::File>> {
+    /// 创建写入到文件的写入器
+    pub fn create(filename: impl AsRef<Path>) -> ParseResult<Self> {
+        let file = create_file(filename.as_ref())?;
+        Ok(Self::new(io::BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> MZMLWriter<W> {
+    /// 创建新的写入器
+    pub fn new(inner: W) -> Self {
+        Self {
+            writer: Writer::new(IndexingSink {
+                inner,
+                position: 0,
+                hasher: Sha1::new(),
+            }),
+            spectrum_offsets: Vec::new(),
+            chromatogram_offsets: Vec::new(),
+            indexed: false,
+        }
+    }
+
+    /// 当前已写出的字节数
+    pub fn position(&self) -> u64 {
+        self.writer.get_ref().position
+    }
+
+    /// 已写出的谱图数量
+    pub fn spectrum_count(&self) -> usize {
+        self.spectrum_offsets.len()
+    }
+
+    /// 已写出的色谱图数量
+    pub fn chromatogram_count(&self) -> usize {
+        self.chromatogram_offsets.len()
+    }
+
+    /// 写出XML声明
+    pub fn write_declaration(&mut self) -> ParseResult<()> {
+        self.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
+        self.write_raw("\n")
+    }
+
+    /// 写出indexedmzML根元素的起始标签，必须在`<mzML>`之前调用
+    pub fn start_indexed(&mut self) -> ParseResult<()> {
+        if !self.indexed {
+            self.write_raw(INDEXED_MZML_START)?;
+            self.write_raw("\n")?;
+            self.indexed = true;
+        }
+        Ok(())
+    }
+
+    /// 写出一个XML事件
+    ///
+    /// spectrum和chromatogram的起始标签会自动记录偏移，用于生成索引。
+    pub fn write_event<'a>(&mut self, event: Event<'a>) -> ParseResult<()> {
+        if let Event::Start(ref e) | Event::Empty(ref e) = event {
+            match e.name().as_ref() {
+                b"spectrum" => {
+                    let id = Self::native_id(e)?;
+                    self.spectrum_offsets.push((id, self.position()));
+                }
+                b"chromatogram" => {
+                    let id = Self::native_id(e)?;
+                    self.chromatogram_offsets.push((id, self.position()));
+                }
+                _ => {}
+            }
+        }
+
+        self.writer.write_event(event).map_err(ParseError::Io)
+    }
+
+    /// 原样写出一段文本（调用者负责其XML合法性）
+    pub fn write_raw(&mut self, content: &str) -> ParseResult<()> {
+        self.writer.get_mut().write_all(content.as_bytes()).map_err(ParseError::Io)
+    }
+
+    /// 写出一个spectrum元素，`index`为它在spectrumList中的位置
+    ///
+    /// id取谱图的native id，为空时按扫描编号生成`scan=N`。峰数组为不压缩的64位浮点数。
+    pub fn write_spectrum(&mut self, index: usize, spectrum: &Spectrum) -> ParseResult<()> {
+        self.write_encoded_spectrum(index, spectrum, &Encoder::new().with_compression(None))
+    }
+
+    /// 写出一个spectrum元素，峰数组用`encoder`编码
+    pub fn write_encoded_spectrum(&mut self, index: usize, spectrum: &Spectrum, encoder: &Encoder) -> ParseResult<()> {
+        // 先编码，编码失败时不留下半个spectrum元素
+        let body = format_spectrum_body(spectrum, encoder)?;
+        let native_id = if spectrum.scan.native_id.is_empty() {
+            format!("scan={}", spectrum.scan.scan_number)
+        } else {
+            spectrum.scan.native_id.clone()
+        };
+
+        self.write_raw("      ")?;
+        let mut start = BytesStart::new("spectrum");
+        start.push_attribute(("index", index.to_string().as_str()));
+        start.push_attribute(("id", native_id.as_str()));
+        start.push_attribute(("defaultArrayLength", spectrum.peak_count().to_string().as_str()));
+        self.write_event(Event::Start(start))?;
+        self.write_raw(&body)
+    }
+
+    /// 结束写入，追加索引和校验和并返回底层输出
+    ///
+    /// 如果没有调用过[`start_indexed`](Self::start_indexed)，则输出为普通mzML，不追加索引。
+    pub fn finish(mut self) -> ParseResult<W> {
+        if self.indexed {
+            self.write_index()?;
+        }
+
+        let mut sink = self.writer.into_inner();
+        sink.flush().map_err(ParseError::Io)?;
+        Ok(sink.inner)
+    }
+
+    /// 写出indexList、indexListOffset和fileChecksum
+    fn write_index(&mut self) -> ParseResult<()> {
+        self.write_raw("\n  ")?;
+        let index_list_offset = self.position();
+
+        let has_chromatograms = !self.chromatogram_offsets.is_empty();
+        let index_count = if has_chromatograms { 2 } else { 1 };
+        let mut index = format!("<indexList count=\"{}\">\n", index_count);
+        Self::format_index(&mut index, "spectrum", &self.spectrum_offsets);
+        if has_chromatograms {
+            Self::format_index(&mut index, "chromatogram", &self.chromatogram_offsets);
+        }
+        index.push_str("  </indexList>\n");
+        index.push_str(&format!("  <indexListOffset>{}</indexListOffset>\n", index_list_offset));
+        // 校验和覆盖从文件开头到<fileChecksum>标签（含）为止的所有字节
+        index.push_str("  <fileChecksum>");
+        self.write_raw(&index)?;
+
+        let digest = self.writer.get_ref().hasher.clone().finalize();
+        let checksum: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        self.write_raw(&checksum)?;
+        self.write_raw("</fileChecksum>\n</indexedmzML>\n")
+    }
+
+    /// 格式化一个index元素
+    fn format_index(output: &mut String, name: &str, offsets: &[(String, u64)]) {
+        output.push_str(&format!("    <index name=\"{}\">\n", name));
+        for (id, offset) in offsets {
+            output.push_str(&format!("      <offset idRef=\"{}\">{}</offset>\n", escape(id.as_str()), offset));
+        }
+        output.push_str("    </index>\n");
+    }
+
+    /// 读取元素的id属性（保持XML中的原始形式并反转义）
+    fn native_id(event: &BytesStart) -> ParseResult<String> {
+        for attr in event.attributes() {
+            let attr = attr.map_err(ParseError::xml)?;
+            if attr.key.as_ref() == b"id" {
+                let value = attr.unescape_value().map_err(ParseError::xml)?;
+                return Ok(value.into_owned());
+            }
+        }
+        Err(ParseError::MissingField {
+            field: format!("{}/@id", str::from_utf8(event.name().as_ref()).unwrap_or("")),
+        })
+    }
+}
+
+/// 把谱图写成完整的indexedmzML文件，峰数组为不压缩的64位浮点数
+pub fn write_spectra(filename: impl AsRef<Path>, spectra: &[Spectrum]) -> ParseResult<()> {
+    crate::conversion::MZMLWriter::new()
+        .with_compression(None)
+        .write(filename, spectra)
+        .map(|_| ())
+}
+
+/// 格式化一个cvParam元素，`unit`为(unitCvRef, unitAccession, unitName)
+fn cv_param(indent: usize, accession: &str, name: &str, value: &str, unit: Option<(&str, &str, &str)>) -> String {
+    let unit = unit.map_or(String::new(), |(cv, accession, name)| {
+        format!(" unitCvRef=\"{}\" unitAccession=\"{}\" unitName=\"{}\"", cv, accession, name)
+    });
+    format!(
+        "{}<cvParam cvRef=\"MS\" accession=\"{}\" name=\"{}\" value=\"{}\"{}/>\n",
+        " ".repeat(indent), accession, name, value, unit
+    )
+}
+
+/// 格式化spectrum起始标签之后的内容（含结束标签）
+fn format_spectrum_body(spectrum: &Spectrum, encoder: &Encoder) -> ParseResult<String> {
+    const SECOND: Option<(&str, &str, &str)> = Some(("UO", "UO:0000010", "second"));
+    const MILLISECOND: Option<(&str, &str, &str)> = Some(("UO", "UO:0000028", "millisecond"));
+    const MZ: Option<(&str, &str, &str)> = Some(("MS", "MS:1000040", "m/z"));
+
+    let mut xml = String::from("\n");
+    xml.push_str(&cv_param(8, "MS:1000511", "ms level", &spectrum.level.to_string(), None));
+    if spectrum.is_ms1() {
+        xml.push_str(&cv_param(8, "MS:1000579", "MS1 spectrum", "", None));
+    } else {
+        xml.push_str(&cv_param(8, "MS:1000580", "MSn spectrum", "", None));
+    }
+    match spectrum.scan.polarity {
+        Polarity::Positive => xml.push_str(&cv_param(8, "MS:1000130", "positive scan", "", None)),
+        Polarity::Negative => xml.push_str(&cv_param(8, "MS:1000129", "negative scan", "", None)),
+        Polarity::Unknown => {}
+    }
+
+    xml.push_str("        <scanList count=\"1\">\n");
+    xml.push_str(&cv_param(10, "MS:1000795", "no combination", "", None));
+    xml.push_str("          <scan>\n");
+    xml.push_str(&cv_param(12, "MS:1000016", "scan start time", &spectrum.scan.retention_time.to_string(), SECOND));
+    if let Some(injection_time) = spectrum.scan.injection_time {
+        xml.push_str(&cv_param(12, "MS:1000927", "ion injection time", &injection_time.to_string(), MILLISECOND));
+    }
+    let (lower, upper) = spectrum.scan.scan_window;
+    if upper > lower {
+        xml.push_str("            <scanWindowList count=\"1\">\n              <scanWindow>\n");
+        xml.push_str(&cv_param(16, "MS:1000501", "scan window lower limit", &lower.to_string(), MZ));
+        xml.push_str(&cv_param(16, "MS:1000500", "scan window upper limit", &upper.to_string(), MZ));
+        xml.push_str("              </scanWindow>\n            </scanWindowList>\n");
+    }
+    xml.push_str("          </scan>\n        </scanList>\n");
+
+    if let Some(precursor) = spectrum.precursor.as_deref() {
+        xml.push_str("        <precursorList count=\"1\">\n          <precursor>\n");
+        let (lower, upper) = precursor.isolation_window;
+        if upper > lower || precursor.isolation_target_mz.is_some() {
+            let target = precursor.isolation_target_mz.unwrap_or(precursor.mz);
+            xml.push_str("            <isolationWindow>\n");
+            xml.push_str(&cv_param(14, "MS:1000827", "isolation window target m/z", &target.to_string(), MZ));
+            xml.push_str(&cv_param(14, "MS:1000828", "isolation window lower offset", &(target - lower).to_string(), MZ));
+            xml.push_str(&cv_param(14, "MS:1000829", "isolation window upper offset", &(upper - target).to_string(), MZ));
+            xml.push_str("            </isolationWindow>\n");
+        }
+        xml.push_str("            <selectedIonList count=\"1\">\n              <selectedIon>\n");
+        xml.push_str(&cv_param(16, "MS:1000744", "selected ion m/z", &precursor.mz.to_string(), MZ));
+        if precursor.charge != 0 {
+            // 电荷符号由扫描极性表示，charge state只写电荷数
+            xml.push_str(&cv_param(16, "MS:1000041", "charge state", &precursor.charge.unsigned_abs().to_string(), None));
+        }
+        if precursor.intensity > 0.0 {
+            xml.push_str(&cv_param(16, "MS:1000042", "peak intensity", &precursor.intensity.to_string(), None));
+        }
+        xml.push_str("              </selectedIon>\n            </selectedIonList>\n            <activation>\n");
+        // 解析时激活方法取自cvParam的value，这里把方法名写回value
+        match activation_term(precursor) {
+            Some((accession, name)) => xml.push_str(&cv_param(14, accession, name, &precursor.activation_method, None)),
+            None => xml.push_str(&cv_param(14, "MS:1000044", "dissociation method", "", None)),
+        }
+        if let Some(energy) = precursor.collision_energy_ev {
+            xml.push_str(&cv_param(14, "MS:1000045", "collision energy", &energy.to_string(), Some(("UO", "UO:0000266", "electronvolt"))));
+        }
+        if let Some(energy) = precursor.normalized_collision_energy {
+            xml.push_str(&cv_param(14, "MS:1000138", "normalized collision energy", &energy.to_string(), Some(("UO", "UO:0000187", "percent"))));
+        }
+        xml.push_str("            </activation>\n          </precursor>\n        </precursorList>\n");
+    }
+
+    let arrays = [
+        (encoder.encode_mz_array(spectrum.mz_slice())?, "MS:1000514", "m/z array"),
+        (encoder.encode_intensity_array(spectrum.intensity_slice())?, "MS:1000515", "intensity array"),
+    ];
+    xml.push_str("        <binaryDataArrayList count=\"2\">\n");
+    for (array, accession, name) in &arrays {
+        let (encoding_accession, encoding_name) = array_encoding_term(array)?;
+        let (compression_accession, compression_name) = array_compression_term(array)?;
+        let encoded = encoder.encode_to_base64(&array.data);
+        xml.push_str(&format!("          <binaryDataArray encodedLength=\"{}\">\n", encoded.len()));
+        xml.push_str(&cv_param(12, encoding_accession, encoding_name, "", None));
+        xml.push_str(&cv_param(12, compression_accession, compression_name, "", None));
+        xml.push_str(&cv_param(12, accession, name, "", None));
+        xml.push_str(&format!("            <binary>{}</binary>\n          </binaryDataArray>\n", encoded));
+    }
+    xml.push_str("        </binaryDataArrayList>\n      </spectrum>\n");
+    Ok(xml)
+}
+
+/// 激活方法对应的CV访问号和名称，未知方法返回None
+fn activation_term(precursor: &PrecursorInfo) -> Option<(&'static str, &'static str)> {
+    match precursor.activation_method.to_ascii_uppercase().as_str() {
+        "CID" => Some(("MS:1000133", "collision-induced dissociation")),
+        "HCD" => Some(("MS:1000422", "beam-type collision-induced dissociation")),
+        "ETD" => Some(("MS:1000598", "electron transfer dissociation")),
+        "ECD" => Some(("MS:1000250", "electron capture dissociation")),
+        "PQD" => Some(("MS:1000599", "pulsed q dissociation")),
+        _ => None,
+    }
+}
+
+/// 数组编码的CV参数，mzML不支持的编码返回错误
+fn array_encoding_term(array: &BinaryDataArray) -> ParseResult<(&'static str, &'static str)> {
+    array.encoding.cv_term().ok_or_else(|| {
+        ParseError::InvalidBinaryEncoding(format!("{:?} cannot be written to mzML", array.encoding))
+    })
+}
+
+/// 数组压缩方式的CV参数，mzML不支持的压缩方式返回错误
+fn array_compression_term(array: &BinaryDataArray) -> ParseResult<(&'static str, &'static str)> {
+    let compression = array.compression.unwrap_or(CompressionType::None);
+    compression.cv_term().ok_or_else(|| {
+        ParseError::InvalidFormat(format!("{:?} compression cannot be written to mzML", compression))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finish_to_string(writer: MZMLWriter<Vec<u8>>) -> String {
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_index_offsets_point_at_elements() {
+        let mut writer = MZMLWriter::new(Vec::new());
+        writer.write_declaration().unwrap();
+        writer.start_indexed().unwrap();
+        writer.write_raw("<mzML><run id=\"r\"><spectrumList count=\"2\">").unwrap();
+        for id in ["scan=1", "scan=2"] {
+            let mut start = BytesStart::new("spectrum");
+            start.push_attribute(("id", id));
+            writer.write_event(Event::Start(start)).unwrap();
+            writer.write_raw("</spectrum>").unwrap();
+        }
+        writer.write_raw("</spectrumList></run></mzML>").unwrap();
+        assert_eq!(writer.spectrum_count(), 2);
+
+        let output = finish_to_string(writer);
+        for id in ["scan=1", "scan=2"] {
+            let marker = format!("<offset idRef=\"{}\">", id);
+            let start = output.find(&marker).unwrap() + marker.len();
+            let end = start + output[start..].find('<').unwrap();
+            let offset: usize = output[start..end].parse().unwrap();
+            assert!(output[offset..].starts_with(&format!("<spectrum id=\"{}\"", id)));
+        }
+        assert!(!output.contains("name=\"chromatogram\""));
+    }
+
+    #[test]
+    fn test_index_list_offset_and_checksum() {
+        let mut writer = MZMLWriter::new(Vec::new());
+        writer.start_indexed().unwrap();
+        writer.write_raw("<mzML></mzML>").unwrap();
+        let output = finish_to_string(writer);
+
+        let start = output.find("<indexListOffset>").unwrap() + "<indexListOffset>".len();
+        let end = start + output[start..].find('<').unwrap();
+        let offset: usize = output[start..end].parse().unwrap();
+        assert!(output[offset..].starts_with("<indexList "));
+
+        let checksum_start = output.find("<fileChecksum>").unwrap() + "<fileChecksum>".len();
+        let checksum_end = output.find("</fileChecksum>").unwrap();
+        let digest = Sha1::digest(&output.as_bytes()[..checksum_start]);
+        let expected: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(&output[checksum_start..checksum_end], expected);
+    }
+
+    #[test]
+    fn test_write_spectra_round_trips_native_ids() {
+        use crate::parsers::mzml::parser::MZMLParser;
+        use crate::parsers::mzml::test_data::{build_mzml, write_temp_file, TestSpectrum};
+
+        let mut source = vec![
+            TestSpectrum::new(1, 1, 10.0, vec![(400.0, 10.0), (401.0, 5.0)]),
+            TestSpectrum::new(2, 2, 12.5, vec![(150.0, 3.0)]).with_precursor(400.2, 2).with_isolation_window(400.0, 1.0, 1.5),
+            TestSpectrum::new(3, 2, 13.0, vec![(175.0, 4.0)]),
+        ];
+        source[2].id = "sample=1 period=1 cycle=7 experiment=2 &amp; more".to_string();
+        let file = write_temp_file(&build_mzml(&source));
+        let parser = MZMLParser::new();
+        let parsed = parser.parse_sequential(file.path()).unwrap();
+        assert_eq!(parsed[2].scan.native_id, "sample=1 period=1 cycle=7 experiment=2 & more");
+        assert_eq!(parsed[1].scan.source_index, Some(1));
+
+        let output = tempfile::Builder::new().suffix(".mzML").tempfile().unwrap();
+        let mut written = parsed.clone();
+        written[0].scan.native_id.clear();
+        written[0].scan.scan_number = 9;
+        write_spectra(output.path(), &written).unwrap();
+
+        let reparsed = parser.parse_sequential(output.path()).unwrap();
+        let ids: Vec<&str> = reparsed.iter().map(|spectrum| spectrum.scan.native_id.as_str()).collect();
+        assert_eq!(ids, vec![
+            "scan=9",
+            "controllerType=0 controllerNumber=1 scan=2",
+            "sample=1 period=1 cycle=7 experiment=2 & more",
+        ]);
+        assert_eq!(reparsed[1].peaks(), parsed[1].peaks());
+        assert_eq!(reparsed[1].scan.retention_time, 12.5);
+        assert_eq!(reparsed[1].precursor.as_deref().unwrap().isolation_window, (399.0, 401.5));
+        assert_eq!(reparsed[1].precursor.as_deref().unwrap().isolation_target_mz, Some(400.0));
+        assert_eq!(reparsed[1].precursor.as_deref().unwrap().charge, 2);
+
+        let content = std::fs::read_to_string(output.path()).unwrap();
+        assert!(content.contains("<offset idRef=\"sample=1 period=1 cycle=7 experiment=2 &amp; more\">"));
+    }
+
+    #[test]
+    fn test_plain_mzml_without_index() {
+        let mut writer = MZMLWriter::new(Vec::new());
+        writer.write_raw("<mzML></mzML>").unwrap();
+        let output = finish_to_string(writer);
+        assert_eq!(output, "<mzML></mzML>");
+    }
+}
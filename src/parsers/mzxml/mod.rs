@@ -0,0 +1,13 @@
+//! mzXML解析器模块
+//!
+//! 这个模块提供了旧版mzXML格式文件的解析功能，转换为与mzML相同的核心谱图结构：
+//! - MZXMLReader：与MZMLReader接口一致的Python读取器
+//! - MZXMLParser：核心解析逻辑
+
+pub mod parser;
+#[cfg(feature = "python")]
+pub mod reader;
+
+pub use parser::{parse_duration, MZXMLParser, MZXMLPeaks, MZXMLPrecursor, MZXMLScan};
+#[cfg(feature = "python")]
+pub use reader::MZXMLReader;
@@ -0,0 +1,662 @@
+//! mzXML核心解析器
+//!
+//! 解析`<scan>`元素（包括MS2扫描嵌套在MS1扫描内的写法），先读出未解码的扫描，
+//! 再把peaks元素中的base64数据用[`BinaryDataArray`]解码为m/z-强度对，转换为核心[`Spectrum`]。
+
+use crate::core::centroid::{CENTROID_SPECTRUM, PROFILE_SPECTRUM, SPECTRUM_TYPE_KEY};
+use crate::core::spectrum::{PrecursorInfo, ScanInfo, Spectrum};
+use crate::core::types::{constants, Polarity};
+use crate::parsers::common::{
+    open_file, BinaryDataArray, BinaryDataEncoding, CompressionType, ParseError, ParseOptions, ParseResult,
+    SpectrumErrorPolicy,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{info, warn};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use rayon::prelude::*;
+use std::io::BufRead;
+use std::path::Path;
+use std::str;
+
+/// 从`<scan>`元素读出的扫描，峰数据尚未解码
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MZXMLScan {
+    /// 扫描编号（num属性）
+    pub num: Option<u32>,
+    /// MS级别
+    pub ms_level: u8,
+    /// 保留时间（秒）
+    pub retention_time: Option<f64>,
+    /// 扫描极性
+    pub polarity: Polarity,
+    /// 峰数（peaksCount属性）
+    pub peaks_count: usize,
+    /// 是否为centroid数据，未记录时为None
+    pub centroided: Option<bool>,
+    /// 碰撞能量 (eV)
+    pub collision_energy: Option<f64>,
+    /// 总离子流
+    pub total_ion_current: Option<f64>,
+    /// 基峰m/z
+    pub base_peak_mz: Option<f64>,
+    /// 基峰强度
+    pub base_peak_intensity: Option<f64>,
+    /// 前体离子（仅MS2+）
+    pub precursor: Option<MZXMLPrecursor>,
+    /// 峰数据
+    pub peaks: Option<MZXMLPeaks>,
+}
+
+/// `<precursorMz>`元素
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MZXMLPrecursor {
+    /// 前体离子m/z
+    pub mz: f64,
+    /// 电荷，未记录时为None
+    pub charge: Option<i8>,
+    /// 前体离子强度
+    pub intensity: Option<f64>,
+    /// 激活方法，如CID、HCD
+    pub activation_method: Option<String>,
+    /// 分离窗口宽度 (Th)
+    pub window_wideness: Option<f64>,
+    /// 前体离子所在扫描的编号
+    pub scan_num: Option<u32>,
+}
+
+/// `<peaks>`元素：编码参数和base64数据
+#[derive(Debug, Clone, PartialEq)]
+pub struct MZXMLPeaks {
+    /// 浮点精度，32或64
+    pub precision: u8,
+    /// 是否为大端序（byteOrder="network"）
+    pub big_endian: bool,
+    /// 压缩方式
+    pub compression: CompressionType,
+    /// 数据内容，只支持交错的m/z-强度对（"m/z-int"）
+    pub content_type: String,
+    /// base64编码的数据
+    pub data: String,
+}
+
+impl Default for MZXMLPeaks {
+    fn default() -> Self {
+        Self {
+            precision: 32,
+            big_endian: true,
+            compression: CompressionType::None,
+            content_type: "m/z-int".to_string(),
+            data: String::new(),
+        }
+    }
+}
+
+impl MZXMLPeaks {
+    /// 解码为(m/z, 强度)对，`peaks_count`只用于核对长度
+    pub fn decode(&self, peaks_count: usize, strict: bool) -> ParseResult<Vec<(f64, f64)>> {
+        let data = self.data.trim();
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.content_type != "m/z-int" {
+            return Err(ParseError::InvalidFormat(format!(
+                "Unsupported peaks contentType '{}'", self.content_type
+            )));
+        }
+
+        let encoding = match (self.precision, self.big_endian) {
+            (32, true) => BinaryDataEncoding::Float32Big,
+            (64, true) => BinaryDataEncoding::Float64Big,
+            (32, false) => BinaryDataEncoding::Float32Little,
+            (64, false) => BinaryDataEncoding::Float64Little,
+            (precision, _) => {
+                return Err(ParseError::InvalidDataType {
+                    expected: "precision 32 or 64".to_string(),
+                    actual: precision.to_string(),
+                })
+            }
+        };
+        let values = BinaryDataArray::new(encoding, STANDARD.decode(data)?)
+            .with_compression(self.compression)
+            .with_expected_length(peaks_count * 2)
+            .with_strict(strict)
+            .decode_f64()?;
+        if !values.len().is_multiple_of(2) {
+            return Err(ParseError::CorruptedData(format!(
+                "odd number of values ({}) in m/z-int peaks", values.len()
+            )));
+        }
+        Ok(values.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect())
+    }
+}
+
+impl MZXMLScan {
+    /// 谱图的native id，按msconvert的约定为"scan=编号"
+    pub fn native_id(&self) -> String {
+        format!("scan={}", self.num.unwrap_or(constants::DEFAULT_SCAN_NUMBER))
+    }
+}
+
+/// 解析xs:duration格式的时间（如"PT12.5S"、"PT1M30S"），返回秒数
+pub fn parse_duration(value: &str) -> ParseResult<f64> {
+    let invalid = || ParseError::InvalidFormat(format!("Invalid retentionTime '{}'", value));
+    let rest = value.trim().strip_prefix("PT").ok_or_else(invalid)?;
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    for c in rest.chars() {
+        let scale = match c {
+            'H' => 3600.0,
+            'M' => 60.0,
+            'S' => 1.0,
+            _ => {
+                number.push(c);
+                continue;
+            }
+        };
+        seconds += number.parse::<f64>().map_err(|_| invalid())? * scale;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(seconds)
+}
+
+/// 读取中的扫描：(在结果中的位置, native id, 解析结果)
+type OpenScan = (usize, String, ParseResult<MZXMLScan>);
+
+/// 当前正在收集文本内容的元素
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TextTarget {
+    PrecursorMz,
+    Peaks,
+}
+
+/// mzXML解析器
+#[derive(Debug, Clone)]
+pub struct MZXMLParser {
+    /// 是否并行解码峰数据
+    parallel: bool,
+    /// 线程数，0表示使用rayon的全局线程池
+    num_threads: usize,
+    /// 解析选项
+    options: ParseOptions,
+}
+
+impl Default for MZXMLParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MZXMLParser {
+    /// 创建新的mzXML解析器
+    pub fn new() -> Self {
+        Self {
+            parallel: false,
+            num_threads: 1,
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// 创建并行解码峰数据的mzXML解析器，XML本身仍顺序读取
+    pub fn new_parallel(num_threads: usize) -> Self {
+        Self {
+            parallel: true,
+            num_threads,
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// 设置解析选项
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// 获取解析选项
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// 解析mzXML文件中的所有扫描
+    pub fn parse(&self, filename: impl AsRef<Path>) -> ParseResult<Vec<Spectrum>> {
+        let filename = filename.as_ref();
+        info!("Parsing mzXML file {}", filename.display());
+        let scans = self.read_scans(filename)?;
+        let spectra = self.convert_scans(scans)?;
+        info!("Parsed {} spectra from {}", spectra.len(), filename.display());
+        Ok(spectra)
+    }
+
+    /// 从任意输入解析mzXML
+    pub fn parse_from<B: BufRead>(&self, reader: B) -> ParseResult<Vec<Spectrum>> {
+        let scans = self.read_scans_from(reader)?;
+        self.convert_scans(scans)
+    }
+
+    /// 读取文件中的扫描，不解码峰数据
+    pub fn read_scans(&self, filename: impl AsRef<Path>) -> ParseResult<Vec<MZXMLScan>> {
+        let file = open_file(filename.as_ref())?;
+        self.read_scans_from(std::io::BufReader::new(file))
+    }
+
+    /// 从任意输入读取扫描，按开始标签的顺序返回（嵌套的扫描排在外层扫描之后）
+    ///
+    /// 属性无效等单个扫描的错误按错误策略处理，XML错误直接返回。
+    pub fn read_scans_from<B: BufRead>(&self, reader: B) -> ParseResult<Vec<MZXMLScan>> {
+        let mut xml_reader = Reader::from_reader(reader);
+        xml_reader.config_mut().trim_text(true);
+        let mut buf = Vec::new();
+        // 按开始标签顺序排列的扫描，结束标签读到后填入
+        let mut slots: Vec<Option<(String, ParseResult<MZXMLScan>)>> = Vec::new();
+        // 尚未结束的扫描，嵌套的扫描在栈顶
+        let mut open: Vec<OpenScan> = Vec::new();
+        let mut text_target = None;
+        let mut text = String::new();
+        let mut pending_precursor = None;
+        let mut pending_peaks = None;
+
+        loop {
+            match xml_reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) => match e.name().as_ref() {
+                    b"scan" => {
+                        let scan = Self::parse_scan_start(e);
+                        let id = scan.as_ref().map_or_else(|_| Self::scan_id(e), MZXMLScan::native_id);
+                        open.push((slots.len(), id, scan));
+                        slots.push(None);
+                    }
+                    b"precursorMz" => {
+                        pending_precursor = Some(Self::parse_precursor_start(e));
+                        text_target = Some(TextTarget::PrecursorMz);
+                        text.clear();
+                    }
+                    b"peaks" => {
+                        pending_peaks = Some(Self::parse_peaks_start(e));
+                        text_target = Some(TextTarget::Peaks);
+                        text.clear();
+                    }
+                    _ => {}
+                },
+                Ok(Event::Empty(ref e)) => match e.name().as_ref() {
+                    b"scan" => {
+                        let scan = Self::parse_scan_start(e);
+                        let id = scan.as_ref().map_or_else(|_| Self::scan_id(e), MZXMLScan::native_id);
+                        slots.push(Some((id, scan)));
+                    }
+                    b"peaks" => {
+                        let peaks = Self::parse_peaks_start(e);
+                        Self::update_scan(&mut open, |scan| {
+                            scan.peaks = Some(peaks?);
+                            Ok(())
+                        });
+                    }
+                    _ => {}
+                },
+                Ok(Event::Text(ref e)) if text_target.is_some() => {
+                    text.push_str(str::from_utf8(e).unwrap_or(""));
+                }
+                Ok(Event::End(ref e)) => match e.name().as_ref() {
+                    b"precursorMz" => {
+                        text_target = None;
+                        let precursor = pending_precursor.take().unwrap_or_else(|| Ok(MZXMLPrecursor::default()));
+                        let mz = text.trim().parse::<f64>().map_err(|_| {
+                            ParseError::InvalidFormat(format!("Invalid precursorMz '{}'", text.trim()))
+                        });
+                        Self::update_scan(&mut open, |scan| {
+                            let mut precursor = precursor?;
+                            precursor.mz = mz?;
+                            // 只保留第一个前体离子
+                            scan.precursor.get_or_insert(precursor);
+                            Ok(())
+                        });
+                    }
+                    b"peaks" => {
+                        text_target = None;
+                        let peaks = pending_peaks.take().unwrap_or_else(|| Ok(MZXMLPeaks::default()));
+                        let data = std::mem::take(&mut text);
+                        Self::update_scan(&mut open, |scan| {
+                            scan.peaks = Some(MZXMLPeaks { data, ..peaks? });
+                            Ok(())
+                        });
+                    }
+                    b"scan" => {
+                        if let Some((slot, id, scan)) = open.pop() {
+                            slots[slot] = Some((id, scan));
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(ParseError::xml(e)),
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        if let Some((_, id, _)) = open.last() {
+            return Err(ParseError::xml(format!("Unexpected end of file in scan '{}'", id)));
+        }
+
+        let mut scans = Vec::with_capacity(slots.len());
+        for (id, result) in slots.into_iter().flatten() {
+            match result {
+                Ok(scan) => scans.push(scan),
+                Err(error) => self.handle_scan_error(&id, error)?,
+            }
+        }
+        Ok(scans)
+    }
+
+    /// 解码峰数据并转换为核心谱图，并行解析器在线程池中解码
+    pub fn convert_scans(&self, scans: Vec<MZXMLScan>) -> ParseResult<Vec<Spectrum>> {
+        let convert = || -> Vec<(String, ParseResult<Spectrum>)> {
+            if self.parallel {
+                scans.par_iter().map(|scan| (scan.native_id(), self.convert_scan(scan))).collect()
+            } else {
+                scans.iter().map(|scan| (scan.native_id(), self.convert_scan(scan))).collect()
+            }
+        };
+        let results = match (self.parallel, self.num_threads) {
+            (true, threads) if threads > 0 => match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+                Ok(pool) => pool.install(convert),
+                Err(e) => {
+                    warn!("Failed to build a {}-thread pool ({}), using the global pool", threads, e);
+                    convert()
+                }
+            },
+            _ => convert(),
+        };
+
+        let mut spectra = Vec::with_capacity(results.len());
+        for (id, result) in results {
+            match result {
+                Ok(spectrum) => spectra.push(spectrum),
+                Err(error) => self.handle_scan_error(&id, error)?,
+            }
+        }
+        Ok(spectra)
+    }
+
+    /// 把一个扫描转换为核心谱图
+    pub fn convert_scan(&self, scan: &MZXMLScan) -> ParseResult<Spectrum> {
+        let peaks = match &scan.peaks {
+            Some(peaks) => peaks.decode(scan.peaks_count, self.options.strict_array_length)?,
+            None => Vec::new(),
+        };
+
+        let mut spectrum = Spectrum::new(scan.ms_level)?;
+        spectrum.add_peaks(peaks)?;
+        spectrum.set_scan_info(ScanInfo {
+            scan_number: scan.num.unwrap_or(constants::DEFAULT_SCAN_NUMBER),
+            retention_time: scan.retention_time.unwrap_or(constants::DEFAULT_RETENTION_TIME),
+            polarity: scan.polarity,
+            native_id: scan.native_id(),
+            ..Default::default()
+        });
+
+        if scan.ms_level > 1 {
+            if let Some(precursor) = &scan.precursor {
+                let mut precursor_info = PrecursorInfo {
+                    ref_scan_number: precursor.scan_num.unwrap_or(constants::DEFAULT_SCAN_NUMBER),
+                    mz: precursor.mz,
+                    intensity: precursor.intensity.unwrap_or(0.0),
+                    charge: scan.polarity.signed_charge(precursor.charge.unwrap_or(constants::DEFAULT_CHARGE)),
+                    collision_energy_ev: scan.collision_energy,
+                    activation_energy: scan.collision_energy.unwrap_or(0.0),
+                    ..Default::default()
+                };
+                if let Some(method) = &precursor.activation_method {
+                    precursor_info.activation_method = method.clone();
+                }
+                if let Some(width) = precursor.window_wideness {
+                    precursor_info.isolation_window = (precursor.mz - width / 2.0, precursor.mz + width / 2.0);
+                }
+                spectrum.set_precursor(precursor_info);
+            }
+        }
+
+        if let Some(centroided) = scan.centroided {
+            let spectrum_type = if centroided { CENTROID_SPECTRUM } else { PROFILE_SPECTRUM };
+            spectrum.add_additional_info(SPECTRUM_TYPE_KEY.to_string(), spectrum_type.to_string())?;
+        }
+        if let Some(tic) = scan.total_ion_current {
+            spectrum.add_additional_info("total_ion_current".to_string(), tic.to_string())?;
+        }
+        if let Some(base_peak_mz) = scan.base_peak_mz {
+            spectrum.add_additional_info("base_peak_mz".to_string(), base_peak_mz.to_string())?;
+        }
+        if let Some(base_peak_intensity) = scan.base_peak_intensity {
+            spectrum.add_additional_info("base_peak_intensity".to_string(), base_peak_intensity.to_string())?;
+        }
+
+        Ok(spectrum)
+    }
+
+    /// 按错误策略处理单个扫描的解析错误，XML和IO错误总是返回
+    fn handle_scan_error(&self, id: &str, error: ParseError) -> ParseResult<()> {
+        if error.is_fatal() {
+            return Err(error);
+        }
+        match self.options.error_policy {
+            SpectrumErrorPolicy::Fail => Err(ParseError::InvalidFormat(format!("scan '{}': {}", id, error))),
+            SpectrumErrorPolicy::Skip => {
+                warn!("Skipping corrupt scan '{}': {}", id, error);
+                Ok(())
+            }
+        }
+    }
+
+    /// 对栈顶的扫描应用修改，出错时把该扫描标记为失败（只保留第一个错误）
+    fn update_scan(open: &mut [OpenScan], update: impl FnOnce(&mut MZXMLScan) -> ParseResult<()>) {
+        if let Some((_, _, result)) = open.last_mut() {
+            if let Ok(scan) = result {
+                if let Err(error) = update(scan) {
+                    *result = Err(error);
+                }
+            }
+        }
+    }
+
+    /// 解析失败的扫描的native id
+    fn scan_id(event: &BytesStart) -> String {
+        format!("scan={}", Self::attribute(event, "num").unwrap_or_default())
+    }
+
+    fn parse_scan_start(event: &BytesStart) -> ParseResult<MZXMLScan> {
+        let mut scan = MZXMLScan::default();
+        for attr in event.attributes() {
+            let attr = attr.map_err(ParseError::xml)?;
+            let value = str::from_utf8(&attr.value).unwrap_or("");
+            match attr.key.as_ref() {
+                b"num" => scan.num = Some(Self::parse_value(value, "num")?),
+                b"msLevel" => scan.ms_level = Self::parse_value(value, "msLevel")?,
+                b"retentionTime" => scan.retention_time = Some(parse_duration(value)?),
+                b"peaksCount" => scan.peaks_count = Self::parse_value(value, "peaksCount")?,
+                b"polarity" => {
+                    scan.polarity = match value {
+                        "+" => Polarity::Positive,
+                        "-" => Polarity::Negative,
+                        _ => Polarity::Unknown,
+                    }
+                }
+                b"centroided" => scan.centroided = Some(value == "1" || value == "true"),
+                b"collisionEnergy" => scan.collision_energy = Some(Self::parse_value(value, "collisionEnergy")?),
+                b"totIonCurrent" => scan.total_ion_current = Some(Self::parse_value(value, "totIonCurrent")?),
+                b"basePeakMz" => scan.base_peak_mz = Some(Self::parse_value(value, "basePeakMz")?),
+                b"basePeakIntensity" => {
+                    scan.base_peak_intensity = Some(Self::parse_value(value, "basePeakIntensity")?)
+                }
+                _ => {}
+            }
+        }
+        if scan.ms_level == 0 {
+            return Err(ParseError::MissingField { field: "scan/@msLevel".to_string() });
+        }
+        Ok(scan)
+    }
+
+    fn parse_precursor_start(event: &BytesStart) -> ParseResult<MZXMLPrecursor> {
+        let mut precursor = MZXMLPrecursor::default();
+        for attr in event.attributes() {
+            let attr = attr.map_err(ParseError::xml)?;
+            let value = str::from_utf8(&attr.value).unwrap_or("");
+            match attr.key.as_ref() {
+                b"precursorCharge" => precursor.charge = Some(Self::parse_value(value, "precursorCharge")?),
+                b"precursorIntensity" => precursor.intensity = Some(Self::parse_value(value, "precursorIntensity")?),
+                b"activationMethod" => precursor.activation_method = Some(value.to_string()),
+                b"windowWideness" => precursor.window_wideness = Some(Self::parse_value(value, "windowWideness")?),
+                b"precursorScanNum" => precursor.scan_num = Some(Self::parse_value(value, "precursorScanNum")?),
+                _ => {}
+            }
+        }
+        Ok(precursor)
+    }
+
+    fn parse_peaks_start(event: &BytesStart) -> ParseResult<MZXMLPeaks> {
+        let mut peaks = MZXMLPeaks::default();
+        for attr in event.attributes() {
+            let attr = attr.map_err(ParseError::xml)?;
+            let value = str::from_utf8(&attr.value).unwrap_or("");
+            match attr.key.as_ref() {
+                b"precision" => peaks.precision = Self::parse_value(value, "precision")?,
+                b"byteOrder" => peaks.big_endian = value != "little",
+                b"compressionType" => peaks.compression = CompressionType::from_string(value)?,
+                b"contentType" | b"pairOrder" => peaks.content_type = value.to_string(),
+                _ => {}
+            }
+        }
+        Ok(peaks)
+    }
+
+    fn attribute(event: &BytesStart, name: &str) -> Option<String> {
+        event
+            .attributes()
+            .flatten()
+            .find(|attr| attr.key.as_ref() == name.as_bytes())
+            .map(|attr| str::from_utf8(&attr.value).unwrap_or("").to_string())
+    }
+
+    fn parse_value<T: str::FromStr>(value: &str, name: &str) -> ParseResult<T> {
+        value.trim().parse().map_err(|_| ParseError::InvalidFormat(format!("Invalid {} '{}'", name, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::mzml::test_data::write_temp_file;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    /// 按mzXML的约定把峰编码为网络字节序的交错m/z-强度对
+    fn encode_peaks(peaks: &[(f64, f64)], precision: u8, zlib: bool) -> String {
+        let mut bytes = Vec::new();
+        for &(mz, intensity) in peaks {
+            for value in [mz, intensity] {
+                match precision {
+                    32 => bytes.extend((value as f32).to_be_bytes()),
+                    _ => bytes.extend(value.to_be_bytes()),
+                }
+            }
+        }
+        if zlib {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&bytes).unwrap();
+            bytes = encoder.finish().unwrap();
+        }
+        STANDARD.encode(bytes)
+    }
+
+    fn sample_mzxml() -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="ISO-8859-1"?>
+<mzXML xmlns="http://sashimi.sourceforge.net/schema_revision/mzXML_3.2">
+  <msRun scanCount="3" startTime="PT1.5S" endTime="PT3.25S">
+    <scan num="1" msLevel="1" peaksCount="3" polarity="+" centroided="1" retentionTime="PT1.5S" totIonCurrent="60" basePeakMz="300.5" basePeakIntensity="30">
+      <peaks precision="32" byteOrder="network" contentType="m/z-int" compressionType="none" compressedLen="0">{}</peaks>
+      <scan num="2" msLevel="2" peaksCount="2" polarity="+" retentionTime="PT1M0.5S" collisionEnergy="35">
+        <precursorMz precursorScanNum="1" precursorIntensity="1500" precursorCharge="2" activationMethod="HCD" windowWideness="2.0">445.12</precursorMz>
+        <peaks precision="64" byteOrder="network" contentType="m/z-int" compressionType="zlib" compressedLen="0">{}</peaks>
+      </scan>
+    </scan>
+    <scan num="3" msLevel="1" peaksCount="0" retentionTime="PT3.25S">
+      <peaks precision="32" byteOrder="network" contentType="m/z-int" compressionType="none" compressedLen="0"></peaks>
+    </scan>
+  </msRun>
+</mzXML>
+"#,
+            encode_peaks(&[(100.25, 10.0), (200.5, 20.0), (300.5, 30.0)], 32, false),
+            encode_peaks(&[(150.123456789, 5.5), (250.987654321, 7.25)], 64, true),
+        )
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("PT12.5S").unwrap(), 12.5);
+        assert_eq!(parse_duration("PT1M30S").unwrap(), 90.0);
+        assert_eq!(parse_duration("PT1H0M1S").unwrap(), 3601.0);
+        assert!(parse_duration("12.5").is_err());
+        assert!(parse_duration("PT12.5").is_err());
+    }
+
+    #[test]
+    fn test_parse_nested_scans() {
+        let spectra = MZXMLParser::new().parse_from(sample_mzxml().as_bytes()).unwrap();
+        assert_eq!(spectra.len(), 3);
+
+        let ms1 = &spectra[0];
+        assert_eq!(ms1.level, 1);
+        assert_eq!(ms1.scan.scan_number, 1);
+        assert_eq!(ms1.scan.native_id, "scan=1");
+        assert_eq!(ms1.scan.retention_time, 1.5);
+        assert_eq!(ms1.scan.polarity, Polarity::Positive);
+        assert_eq!(ms1.peaks().as_slice(), &[(100.25, 10.0), (200.5, 20.0), (300.5, 30.0)]);
+
+        let ms2 = &spectra[1];
+        assert_eq!(ms2.level, 2);
+        assert_eq!(ms2.scan.retention_time, 60.5);
+        assert_eq!(ms2.peaks().as_slice(), &[(150.123456789, 5.5), (250.987654321, 7.25)]);
+        let precursor = ms2.precursor.as_ref().unwrap();
+        assert_eq!(precursor.mz, 445.12);
+        assert_eq!(precursor.charge, 2);
+        assert_eq!(precursor.ref_scan_number, 1);
+        assert_eq!(precursor.activation_method, "HCD");
+        assert_eq!(precursor.collision_energy_ev, Some(35.0));
+        assert_eq!(precursor.isolation_window, (444.12, 446.12));
+
+        assert_eq!(spectra[2].scan.scan_number, 3);
+        assert!(spectra[2].peaks().is_empty());
+    }
+
+    #[test]
+    fn test_parallel_and_file_parse_match() {
+        let file = write_temp_file(&sample_mzxml());
+        let sequential = MZXMLParser::new().parse(file.path()).unwrap();
+        let parallel = MZXMLParser::new_parallel(2).parse(file.path()).unwrap();
+        assert_eq!(format!("{:?}", sequential), format!("{:?}", parallel));
+
+        let scans = MZXMLParser::new().read_scans(file.path()).unwrap();
+        assert_eq!(scans.iter().map(|scan| scan.num).collect::<Vec<_>>(), vec![Some(1), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn test_corrupt_scan_error_policy() {
+        let xml = sample_mzxml().replace("retentionTime=\"PT3.25S\"", "retentionTime=\"3.25\"");
+        let error = MZXMLParser::new().parse_from(xml.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("scan=3"), "{}", error);
+
+        let skipping = MZXMLParser::new()
+            .with_options(ParseOptions::new().with_error_policy(SpectrumErrorPolicy::Skip));
+        assert_eq!(skipping.parse_from(xml.as_bytes()).unwrap().len(), 2);
+
+        let xml = sample_mzxml();
+        let truncated = &xml[..xml.find("<peaks").unwrap()];
+        assert!(matches!(MZXMLParser::new().parse_from(truncated.as_bytes()), Err(ParseError::Xml { .. })));
+    }
+}
@@ -0,0 +1,147 @@
+//! mzXML Python读取器
+//!
+//! 接口与MZMLReader一致，读取结果同样是MZMLObject和MSObject
+
+use crate::core::ms_object::MSObject;
+use crate::core::spectrum::SpectraStore;
+use crate::parsers::mzml::{MZMLFileInfo, MZMLObject};
+use crate::parsers::mzxml::parser::MZXMLParser;
+use std::path::PathBuf;
+
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyList};
+
+/// Python兼容的mzXML读取器
+#[pyclass]
+pub struct MZXMLReader {
+    parser: MZXMLParser,
+}
+
+impl MZXMLReader {
+    fn parser(parallel: bool, num_processes: Option<usize>) -> MZXMLParser {
+        if parallel {
+            MZXMLParser::new_parallel(num_processes.unwrap_or_else(num_cpus::get))
+        } else {
+            MZXMLParser::new()
+        }
+    }
+}
+
+#[pymethods]
+impl MZXMLReader {
+    /// 创建新的mzXML读取器
+    #[new]
+    fn new() -> Self {
+        Self {
+            parser: MZXMLParser::new(),
+        }
+    }
+
+    /// 读取mzXML文件并返回MZMLObject
+    ///
+    /// `parallel`为True时在线程池中解码峰数据，XML本身顺序读取。
+    #[pyo3(signature = (filename, parse_spectra=true, parallel=false, num_processes=None))]
+    fn read(
+        &self,
+        py: Python,
+        filename: PathBuf,
+        parse_spectra: bool,
+        parallel: bool,
+        num_processes: Option<usize>,
+    ) -> PyResult<Py<PyAny>> {
+        let parser = Self::parser(parallel, num_processes);
+        let scans = parser.read_scans(&filename)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+
+        let mut file_info = MZMLFileInfo::new(&filename);
+        file_info.file_format = "mzXML".to_string();
+        file_info.spectrum_count = scans.len();
+        file_info.ms1_count = scans.iter().filter(|scan| scan.ms_level == 1).count();
+        file_info.ms2_count = scans.iter().filter(|scan| scan.ms_level == 2).count();
+
+        let spectra = if parse_spectra {
+            let spectra = parser.convert_scans(scans)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+            file_info.update_counts(&spectra);
+            spectra
+        } else {
+            Vec::new()
+        };
+
+        let mzml_object = MZMLObject {
+            spectra: SpectraStore::shared(spectra),
+            file_info,
+        };
+        Ok(Py::new(py, mzml_object)?.into_any())
+    }
+
+    /// 读取mzXML文件并返回MSObject列表
+    #[pyo3(signature = (filename, parallel=false, num_processes=None))]
+    fn read_to_msobjects(
+        &self,
+        py: Python,
+        filename: PathBuf,
+        parallel: bool,
+        num_processes: Option<usize>,
+    ) -> PyResult<Py<PyList>> {
+        let spectra = Self::parser(parallel, num_processes).parse(&filename)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+
+        let ms_objects = PyList::empty(py);
+        for spectrum in spectra {
+            ms_objects.append(Py::new(py, MSObject { spectrum })?)?;
+        }
+        Ok(ms_objects.into())
+    }
+
+    /// 获取谱图数量，不解码峰数据
+    fn get_spectrum_count(&self, filename: PathBuf) -> PyResult<usize> {
+        let scans = self.parser.read_scans(&filename)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        Ok(scans.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::mzml::test_data::write_temp_file;
+
+    #[test]
+    fn test_read_mzxml_object() {
+        let xml = r#"<?xml version="1.0" encoding="ISO-8859-1"?>
+<mzXML xmlns="http://sashimi.sourceforge.net/schema_revision/mzXML_3.2">
+  <msRun scanCount="2">
+    <scan num="1" msLevel="1" peaksCount="1" retentionTime="PT1S">
+      <peaks precision="32" byteOrder="network" contentType="m/z-int" compressionType="none">Q8gAAEEgAAA=</peaks>
+      <scan num="2" msLevel="2" peaksCount="0" retentionTime="PT2S">
+        <precursorMz precursorCharge="2">400.5</precursorMz>
+        <peaks precision="32" byteOrder="network" contentType="m/z-int" compressionType="none"></peaks>
+      </scan>
+    </scan>
+  </msRun>
+</mzXML>
+"#;
+        let file = write_temp_file(xml);
+        let path = file.path().to_path_buf();
+        let reader = MZXMLReader::new();
+        assert_eq!(reader.get_spectrum_count(path.clone()).unwrap(), 2);
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let object = reader.read(py, path.clone(), true, false, None).unwrap();
+            let object = object.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+            assert_eq!(object.spectra[0].peaks().as_slice(), &[(400.0, 10.0)]);
+            assert_eq!(object.file_info.file_format, "mzXML");
+            assert_eq!((object.file_info.ms1_count, object.file_info.ms2_count), (1, 1));
+
+            let headers_only = reader.read(py, path.clone(), false, false, None).unwrap();
+            let headers_only = headers_only.bind(py).downcast::<MZMLObject>().unwrap().borrow();
+            assert!(headers_only.spectra.is_empty());
+            assert_eq!(headers_only.file_info.spectrum_count, 2);
+
+            let objects = reader.read_to_msobjects(py, path, true, Some(2)).unwrap();
+            assert_eq!(objects.bind(py).len(), 2);
+        });
+    }
+}
@@ -0,0 +1,357 @@
+//! MS-Numpress编解码
+//!
+//! 纯Rust实现的MS-Numpress压缩（与参考实现ms-numpress的字节格式一致）：
+//! - 线性预测（linear, MS:1002312）：定点化后用前两个值线性外推，只保存预测残差，用于m/z
+//! - 正整数（pic, MS:1002313）：四舍五入为非负整数，用于离子计数类强度
+//! - 短对数浮点（slof, MS:1002314）：ln(x + 1)定点化为16位整数，用于强度
+//!
+//! 线性预测和正整数压缩中的整数用半字节变长编码：第一个半字节给出省略的前导0（0–8）
+//! 或前导F（9–15，减8）的个数，其余半字节从低位到高位依次存放。
+
+use crate::parsers::common::{ParseError, ParseResult};
+use serde::{Deserialize, Serialize};
+
+/// MS-Numpress压缩方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Numpress {
+    /// 线性预测
+    Linear,
+    /// 正整数
+    Pic,
+    /// 短对数浮点
+    Slof,
+}
+
+impl Numpress {
+    /// 按压缩方式编码，定点系数取最优值
+    pub fn encode(&self, values: &[f64]) -> ParseResult<Vec<u8>> {
+        match self {
+            Numpress::Linear => encode_linear(values, optimal_linear_fixed_point(values)),
+            Numpress::Pic => encode_pic(values),
+            Numpress::Slof => encode_slof(values, optimal_slof_fixed_point(values)),
+        }
+    }
+
+    /// 按压缩方式解码
+    pub fn decode(&self, data: &[u8]) -> ParseResult<Vec<f64>> {
+        match self {
+            Numpress::Linear => decode_linear(data),
+            Numpress::Pic => decode_pic(data),
+            Numpress::Slof => decode_slof(data),
+        }
+    }
+}
+
+/// 线性预测压缩的最优定点系数：使最大残差恰好落在32位有符号整数范围内
+pub fn optimal_linear_fixed_point(values: &[f64]) -> f64 {
+    match values {
+        [] => 0.0,
+        [first] => (i32::MAX as f64 / first).floor(),
+        [first, second, ..] => {
+            let mut max_value = first.max(*second);
+            for window in values.windows(3) {
+                let extrapolated = window[1] + (window[1] - window[0]);
+                let diff = window[2] - extrapolated;
+                max_value = max_value.max((diff.abs() + 1.0).ceil());
+            }
+            (i32::MAX as f64 / max_value).floor()
+        }
+    }
+}
+
+/// 短对数浮点压缩的最优定点系数：使最大的ln(x + 1)恰好落在16位无符号整数范围内
+pub fn optimal_slof_fixed_point(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let max_value = values.iter().fold(1.0f64, |max, &value| max.max((value + 1.0).ln()));
+    (u16::MAX as f64 / max_value).floor()
+}
+
+/// 线性预测压缩
+///
+/// 格式：8字节大端定点系数，前两个定点值各4字节（小端），之后是半字节编码的预测残差。
+pub fn encode_linear(values: &[f64], fixed_point: f64) -> ParseResult<Vec<u8>> {
+    let mut result = fixed_point.to_be_bytes().to_vec();
+    let to_fixed = |value: f64| (value * fixed_point + 0.5) as i64;
+
+    let mut ints = [0i64; 3];
+    for (i, &value) in values.iter().take(2).enumerate() {
+        ints[i + 1] = to_fixed(value);
+        result.extend_from_slice(&(ints[i + 1] as u32).to_le_bytes());
+    }
+
+    let mut half_bytes = HalfBytes::default();
+    for &value in values.iter().skip(2) {
+        ints = [ints[1], ints[2], to_fixed(value)];
+        let extrapolated = ints[1] + (ints[1] - ints[0]);
+        let diff = ints[2] - extrapolated;
+        if diff > i32::MAX as i64 || diff < i32::MIN as i64 {
+            return Err(ParseError::InvalidFormat(format!(
+                "Numpress linear residual {} exceeds 32-bit range, use a smaller fixed point", diff
+            )));
+        }
+        half_bytes.push_int(diff as i32 as u32, &mut result);
+    }
+    half_bytes.finish(&mut result);
+    Ok(result)
+}
+
+/// 线性预测解压
+pub fn decode_linear(data: &[u8]) -> ParseResult<Vec<f64>> {
+    let fixed_point = decode_fixed_point(data)?;
+    let body = &data[8..];
+    if body.is_empty() {
+        return Ok(Vec::new());
+    }
+    if body.len() < 4 || (body.len() > 4 && body.len() < 8) {
+        return Err(corrupt("linear", "truncated initial values"));
+    }
+
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap()) as i64;
+    let mut ints = [0, read_u32(&body[..4]), 0];
+    let mut result = vec![ints[1] as f64 / fixed_point];
+    if body.len() == 4 {
+        return Ok(result);
+    }
+    ints[2] = read_u32(&body[4..8]);
+    result.push(ints[2] as f64 / fixed_point);
+
+    let mut reader = HalfByteReader::new(&body[8..]);
+    while let Some(diff) = reader.next_int()? {
+        ints = [ints[1], ints[2], 0];
+        let extrapolated = ints[1] + (ints[1] - ints[0]);
+        ints[2] = extrapolated + diff as i32 as i64;
+        result.push(ints[2] as f64 / fixed_point);
+    }
+    Ok(result)
+}
+
+/// 正整数压缩，值四舍五入为非负整数
+pub fn encode_pic(values: &[f64]) -> ParseResult<Vec<u8>> {
+    let mut result = Vec::new();
+    let mut half_bytes = HalfBytes::default();
+    for &value in values {
+        if value + 0.5 > i32::MAX as f64 || value < -0.5 {
+            return Err(ParseError::InvalidFormat(format!(
+                "Numpress pic cannot encode {}, values must be in 0..=i32::MAX", value
+            )));
+        }
+        half_bytes.push_int((value + 0.5) as u32, &mut result);
+    }
+    half_bytes.finish(&mut result);
+    Ok(result)
+}
+
+/// 正整数解压
+pub fn decode_pic(data: &[u8]) -> ParseResult<Vec<f64>> {
+    let mut result = Vec::new();
+    let mut reader = HalfByteReader::new(data);
+    while let Some(value) = reader.next_int()? {
+        result.push(value as f64);
+    }
+    Ok(result)
+}
+
+/// 短对数浮点压缩
+///
+/// 格式：8字节大端定点系数，之后每个值为2字节小端的round(ln(x + 1) × 定点系数)。
+pub fn encode_slof(values: &[f64], fixed_point: f64) -> ParseResult<Vec<u8>> {
+    let mut result = Vec::with_capacity(8 + 2 * values.len());
+    result.extend_from_slice(&fixed_point.to_be_bytes());
+    for &value in values {
+        let scaled = (value + 1.0).ln() * fixed_point;
+        if scaled.is_nan() || scaled + 0.5 >= u16::MAX as f64 + 1.0 {
+            return Err(ParseError::InvalidFormat(format!(
+                "Numpress slof cannot encode {} with fixed point {}", value, fixed_point
+            )));
+        }
+        result.extend_from_slice(&((scaled + 0.5) as u16).to_le_bytes());
+    }
+    Ok(result)
+}
+
+/// 短对数浮点解压
+pub fn decode_slof(data: &[u8]) -> ParseResult<Vec<f64>> {
+    let fixed_point = decode_fixed_point(data)?;
+    let body = &data[8..];
+    if !body.len().is_multiple_of(2) {
+        return Err(corrupt("slof", "odd number of value bytes"));
+    }
+    Ok(body
+        .chunks_exact(2)
+        .map(|chunk| (u16::from_le_bytes([chunk[0], chunk[1]]) as f64 / fixed_point).exp() - 1.0)
+        .collect())
+}
+
+/// 读取开头8字节大端存放的定点系数
+fn decode_fixed_point(data: &[u8]) -> ParseResult<f64> {
+    data.get(..8)
+        .map(|bytes| f64::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| corrupt("fixed point", "fewer than 8 bytes"))
+}
+
+fn corrupt(codec: &str, reason: &str) -> ParseError {
+    ParseError::CorruptedData(format!("Numpress {} data: {}", codec, reason))
+}
+
+/// 半字节写出缓冲，凑满两个半字节写出一个字节（高半字节在前）
+#[derive(Default)]
+struct HalfBytes {
+    pending: Option<u8>,
+}
+
+impl HalfBytes {
+    fn push(&mut self, half_byte: u8, out: &mut Vec<u8>) {
+        match self.pending.take() {
+            Some(high) => out.push((high << 4) | (half_byte & 0xf)),
+            None => self.pending = Some(half_byte & 0xf),
+        }
+    }
+
+    /// 半字节变长编码一个32位整数
+    fn push_int(&mut self, x: u32, out: &mut Vec<u8>) {
+        let nibble = |i: u32| ((x >> (4 * i)) & 0xf) as u8;
+        // 从最高位起连续的0或F半字节个数，全为0时省略全部8个，全为F时保留最低一个
+        let (head, skipped) = match x >> 28 {
+            0 => {
+                let zeros = (0..8).rev().take_while(|&i| nibble(i) == 0).count() as u8;
+                (zeros, zeros)
+            }
+            0xf => {
+                let ones = ((0..8).rev().take_while(|&i| nibble(i) == 0xf).count() as u8).min(7);
+                (ones + 8, ones)
+            }
+            _ => (0, 0),
+        };
+        self.push(head, out);
+        for i in 0..(8 - skipped as u32) {
+            self.push(nibble(i), out);
+        }
+    }
+
+    /// 写出剩余的半字节，低半字节补0
+    fn finish(self, out: &mut Vec<u8>) {
+        if let Some(high) = self.pending {
+            out.push(high << 4);
+        }
+    }
+}
+
+/// 半字节读取器
+struct HalfByteReader<'a> {
+    data: &'a [u8],
+    /// 下一个半字节的位置（半字节为单位）
+    position: usize,
+}
+
+impl<'a> HalfByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn next_half_byte(&mut self) -> Option<u8> {
+        let byte = *self.data.get(self.position / 2)?;
+        let half_byte = if self.position.is_multiple_of(2) { byte >> 4 } else { byte & 0xf };
+        self.position += 1;
+        Some(half_byte)
+    }
+
+    /// 读取下一个半字节编码的整数，数据结束（含末尾补的0半字节）时返回None
+    fn next_int(&mut self) -> ParseResult<Option<u32>> {
+        let remaining = self.data.len() * 2 - self.position;
+        if remaining == 0 || (remaining == 1 && self.data[self.data.len() - 1] & 0xf == 0) {
+            return Ok(None);
+        }
+
+        let head = self.next_half_byte().unwrap();
+        let (skipped, mut value) = if head <= 8 {
+            (head as u32, 0u32)
+        } else {
+            let ones = head as u32 - 8;
+            (ones, (0..ones).fold(0, |value, i| value | (0xf000_0000 >> (4 * i))))
+        };
+
+        for i in 0..(8 - skipped) {
+            let half_byte = self.next_half_byte().ok_or_else(|| corrupt("integer", "truncated value"))?;
+            value |= (half_byte as u32) << (4 * i);
+        }
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_byte_sequences() {
+        // 定点系数1000：100000和100500原样保存，之后的残差为0和200
+        let linear = [
+            0x40, 0x8f, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xa0, 0x86, 0x01, 0x00,
+            0x94, 0x88, 0x01, 0x00,
+            0x86, 0x8c,
+        ];
+        let mz = [100.0, 100.5, 101.0, 101.7];
+        assert_eq!(encode_linear(&mz, 1000.0).unwrap(), linear);
+        assert_eq!(decode_linear(&linear).unwrap(), mz);
+
+        // 0编码为单个半字节8，300 = 0x12C编码为5 C 2 1，末尾补0
+        let pic = [0x85, 0xc2, 0x10];
+        assert_eq!(encode_pic(&[0.0, 300.0]).unwrap(), pic);
+        assert_eq!(decode_pic(&pic).unwrap(), vec![0.0, 300.0]);
+        assert_eq!(decode_pic(&[0x71, 0x72, 0x73]).unwrap(), vec![1.0, 2.0, 3.0]);
+
+        // 定点系数100：ln(1) = 0, ln(e) = 1
+        let slof = [0x40, 0x59, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x64, 0x00];
+        let intensities = [0.0, std::f64::consts::E - 1.0];
+        assert_eq!(encode_slof(&intensities, 100.0).unwrap(), slof);
+        let decoded = decode_slof(&slof).unwrap();
+        assert_eq!(decoded[0], 0.0);
+        assert!((decoded[1] - intensities[1]).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_negative_residuals_round_trip() {
+        // 间距变小时残差为负，以前导F的形式编码
+        let mz = [500.0, 500.9, 501.3, 501.35, 501.36, 900.0, 100.0];
+        let fixed_point = optimal_linear_fixed_point(&mz);
+        let decoded = decode_linear(&encode_linear(&mz, fixed_point).unwrap()).unwrap();
+        assert_eq!(decoded.len(), mz.len());
+        for (decoded, original) in decoded.iter().zip(mz) {
+            assert!((decoded - original).abs() <= 0.5 / fixed_point + 1e-12);
+        }
+
+        let mut half_bytes = HalfBytes::default();
+        let mut out = Vec::new();
+        half_bytes.push_int(-3i32 as u32, &mut out);
+        half_bytes.push_int(u32::MAX, &mut out);
+        half_bytes.finish(&mut out);
+        assert_eq!(out, vec![0xfd, 0xff]);
+        let mut reader = HalfByteReader::new(&out);
+        assert_eq!(reader.next_int().unwrap(), Some(-3i32 as u32));
+        assert_eq!(reader.next_int().unwrap(), Some(u32::MAX));
+        assert_eq!(reader.next_int().unwrap(), None);
+    }
+
+    #[test]
+    fn test_codecs_round_trip_within_precision() {
+        let intensities: Vec<f64> = (0..200).map(|i| ((i * 7919) % 1013) as f64 * 37.5).collect();
+        let mzs: Vec<f64> = (0..200).map(|i| 150.0 + i as f64 * 3.217 + (i % 7) as f64 * 0.013).collect();
+
+        let decoded = Numpress::Linear.decode(&Numpress::Linear.encode(&mzs).unwrap()).unwrap();
+        assert!(decoded.iter().zip(&mzs).all(|(a, b)| (a - b).abs() < 1e-6));
+
+        let decoded = Numpress::Pic.decode(&Numpress::Pic.encode(&intensities).unwrap()).unwrap();
+        assert!(decoded.iter().zip(&intensities).all(|(a, b)| (a - b).abs() <= 0.5));
+
+        let decoded = Numpress::Slof.decode(&Numpress::Slof.encode(&intensities).unwrap()).unwrap();
+        assert!(decoded.iter().zip(&intensities).all(|(a, b)| (a - b).abs() <= b * 2e-4 + 1e-9));
+
+        assert!(Numpress::Linear.decode(&Numpress::Linear.encode(&[]).unwrap()).unwrap().is_empty());
+        assert!(decode_linear(&[0x40, 0x8f, 0x40, 0, 0, 0, 0, 0, 0xa0, 0x86]).is_err());
+        assert!(decode_pic(&[0x05, 0x12]).is_err());
+        assert!(encode_pic(&[-3.0]).is_err());
+    }
+}
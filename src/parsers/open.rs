@@ -0,0 +1,466 @@
+//! 按文件格式自动选择读取器
+//!
+//! [`open_ms_file`]根据扩展名和文件开头的内容识别格式，返回统一的[`SpectraSource`]：
+//! - mzML：XML根元素为`<mzML>`或`<indexedmzML>`；
+//! - mzXML：XML根元素为`<mzXML>`；
+//! - MGF：文本中出现`BEGIN IONS`行。
+//!
+//! 内容优先于扩展名：扩展名为未知格式时只按内容识别，扩展名与内容识别出的格式不一致时返回错误。
+
+use crate::core::spectrum::Spectrum;
+use crate::parsers::common::{open_file, ParseError, ParseOptions, ParseResult};
+use crate::parsers::mgf::read_mgf;
+use crate::parsers::mzml::MZMLParser;
+use crate::parsers::mzxml::MZXMLParser;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// 识别格式时读取的文件开头字节数
+const SNIFF_BYTES: usize = 8192;
+
+/// 支持的质谱文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MSFileFormat {
+    MzML,
+    MzXML,
+    Mgf,
+}
+
+impl MSFileFormat {
+    /// 格式名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MzML => "mzML",
+            Self::MzXML => "mzXML",
+            Self::Mgf => "MGF",
+        }
+    }
+
+    /// 按扩展名（不区分大小写）判断格式，未知扩展名为None
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "mzml" => Some(Self::MzML),
+            "mzxml" => Some(Self::MzXML),
+            "mgf" => Some(Self::Mgf),
+            _ => None,
+        }
+    }
+}
+
+/// 统一的谱图来源信息
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceInfo {
+    pub file_path: PathBuf,
+    pub format: MSFileFormat,
+    /// mzML根元素的version属性，其他格式为None
+    pub version: Option<String>,
+    pub spectrum_count: usize,
+    pub ms1_count: usize,
+    pub ms2_count: usize,
+}
+
+/// 不区分文件格式的谱图来源，由[`open_ms_file`]创建
+pub trait SpectraSource: Send + Sync {
+    /// 文件格式
+    fn format(&self) -> MSFileFormat;
+
+    /// 文件路径
+    fn path(&self) -> &Path;
+
+    /// 逐个读取谱图，单个谱图的错误作为迭代项返回
+    fn iter_spectra(&self) -> ParseResult<Box<dyn Iterator<Item = ParseResult<Spectrum>> + Send>>;
+
+    /// 谱图数和各级别谱图数，尽量不解码峰数据
+    fn file_info(&self) -> ParseResult<SourceInfo>;
+
+    /// 读取所有谱图
+    fn read_all(&self) -> ParseResult<Vec<Spectrum>> {
+        self.iter_spectra()?.collect()
+    }
+}
+
+/// 识别文件格式并以默认解析选项打开
+pub fn open_ms_file(path: impl AsRef<Path>) -> ParseResult<Box<dyn SpectraSource>> {
+    open_ms_file_with(path, ParseOptions::default(), None)
+}
+
+/// 识别文件格式并打开，`num_threads`不为None时mzML和mzXML并行解码峰数据
+///
+/// 解析选项只作用于mzML和mzXML，MGF没有对应的设置。
+pub fn open_ms_file_with(
+    path: impl AsRef<Path>,
+    options: ParseOptions,
+    num_threads: Option<usize>,
+) -> ParseResult<Box<dyn SpectraSource>> {
+    let path = path.as_ref().to_path_buf();
+    Ok(match detect_format(&path)? {
+        MSFileFormat::MzML => {
+            let parser = match num_threads {
+                Some(threads) => MZMLParser::new_parallel(threads),
+                None => MZMLParser::new(),
+            };
+            Box::new(MzMLSource { path, parser: parser.with_options(options) })
+        }
+        MSFileFormat::MzXML => {
+            let parser = match num_threads {
+                Some(threads) => MZXMLParser::new_parallel(threads),
+                None => MZXMLParser::new(),
+            };
+            Box::new(MzXMLSource { path, parser: parser.with_options(options) })
+        }
+        MSFileFormat::Mgf => Box::new(MgfSource { path }),
+    })
+}
+
+/// 按文件开头的内容识别格式，并检查与扩展名是否一致
+pub fn detect_format(path: impl AsRef<Path>) -> ParseResult<MSFileFormat> {
+    let path = path.as_ref();
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    open_file(path)?.take(SNIFF_BYTES as u64).read_to_end(&mut head).map_err(ParseError::Io)?;
+
+    let detected = sniff(&head);
+    match (detected, MSFileFormat::from_extension(path)) {
+        (Ok(format), Some(expected)) if format != expected => Err(ParseError::InvalidFormat(format!(
+            "{}: extension indicates {} but the content looks like {}",
+            path.display(), expected.name(), format.name()
+        ))),
+        (Ok(format), _) => Ok(format),
+        (Err(found), Some(expected)) => Err(ParseError::InvalidFormat(format!(
+            "{}: extension indicates {} but the content is {}",
+            path.display(), expected.name(), found
+        ))),
+        (Err(found), None) => Err(ParseError::InvalidFormat(format!(
+            "{}: unsupported file format, content is {} (expected mzML, mzXML or MGF)",
+            path.display(), found
+        ))),
+    }
+}
+
+/// 按文件开头的字节识别格式，无法识别时返回对内容的描述
+fn sniff(head: &[u8]) -> Result<MSFileFormat, String> {
+    if head.starts_with(&[0x1f, 0x8b]) {
+        return Err("gzip-compressed data".to_string());
+    }
+    let head = head.strip_prefix(b"\xef\xbb\xbf").unwrap_or(head);
+    if head.iter().all(u8::is_ascii_whitespace) {
+        return Err("empty".to_string());
+    }
+    // 截断处可能落在多字节字符中间，只取有效的UTF-8前缀
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default(),
+        Err(_) => return Err("binary data".to_string()),
+    };
+    if text.contains('\0') {
+        return Err("binary data".to_string());
+    }
+
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('<') {
+        return match xml_root(trimmed) {
+            Some("mzML" | "indexedmzML") => Ok(MSFileFormat::MzML),
+            Some("mzXML") => Ok(MSFileFormat::MzXML),
+            Some(root) => Err(format!("an XML document with root element <{}>", root)),
+            None => Err(format!("an XML document without a root element in the first {} bytes", SNIFF_BYTES)),
+        };
+    }
+    if text.lines().any(|line| line.trim().eq_ignore_ascii_case("BEGIN IONS")) {
+        return Ok(MSFileFormat::Mgf);
+    }
+    let first_line = trimmed.lines().next().unwrap_or_default();
+    Err(format!("text starting with '{}'", first_line.chars().take(40).collect::<String>()))
+}
+
+/// XML文档根元素的本地名称，跳过声明、注释、处理指令和DOCTYPE
+fn xml_root(mut text: &str) -> Option<&str> {
+    loop {
+        text = text.trim_start();
+        let rest = text.strip_prefix('<')?;
+        let skip_to = if rest.starts_with("!--") {
+            "-->"
+        } else if rest.starts_with('?') || rest.starts_with('!') {
+            ">"
+        } else {
+            let name = rest.split(|c: char| c.is_whitespace() || c == '>' || c == '/').next()?;
+            // 带命名空间前缀时取本地名称
+            return Some(name.rsplit(':').next().unwrap_or(name)).filter(|name| !name.is_empty());
+        };
+        let end = text.find(skip_to)?;
+        text = &text[end + skip_to.len()..];
+    }
+}
+
+/// 由头信息统计谱图数
+fn count_levels(levels: impl Iterator<Item = u8>) -> (usize, usize, usize) {
+    levels.fold((0, 0, 0), |(total, ms1, ms2), level| (total + 1, ms1 + (level == 1) as usize, ms2 + (level == 2) as usize))
+}
+
+struct MzMLSource {
+    path: PathBuf,
+    parser: MZMLParser,
+}
+
+impl SpectraSource for MzMLSource {
+    fn format(&self) -> MSFileFormat {
+        MSFileFormat::MzML
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn iter_spectra(&self) -> ParseResult<Box<dyn Iterator<Item = ParseResult<Spectrum>> + Send>> {
+        Ok(Box::new(self.parser.iter_spectra(&self.path)?))
+    }
+
+    fn file_info(&self) -> ParseResult<SourceInfo> {
+        let headers = self.parser.spectrum_headers(&self.path)?;
+        let (spectrum_count, ms1_count, ms2_count) = count_levels(headers.iter().map(|header| header.ms_level));
+        Ok(SourceInfo {
+            file_path: self.path.clone(),
+            format: MSFileFormat::MzML,
+            version: self.parser.read_version(&self.path)?,
+            spectrum_count,
+            ms1_count,
+            ms2_count,
+        })
+    }
+
+    fn read_all(&self) -> ParseResult<Vec<Spectrum>> {
+        self.parser.parse(&self.path)
+    }
+}
+
+struct MzXMLSource {
+    path: PathBuf,
+    parser: MZXMLParser,
+}
+
+impl SpectraSource for MzXMLSource {
+    fn format(&self) -> MSFileFormat {
+        MSFileFormat::MzXML
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// XML一次读入，峰数据在迭代时逐个解码
+    fn iter_spectra(&self) -> ParseResult<Box<dyn Iterator<Item = ParseResult<Spectrum>> + Send>> {
+        let scans = self.parser.read_scans(&self.path)?;
+        let parser = self.parser.clone();
+        Ok(Box::new(scans.into_iter().map(move |scan| parser.convert_scan(&scan))))
+    }
+
+    fn file_info(&self) -> ParseResult<SourceInfo> {
+        let scans = self.parser.read_scans(&self.path)?;
+        let (spectrum_count, ms1_count, ms2_count) = count_levels(scans.iter().map(|scan| scan.ms_level));
+        Ok(SourceInfo {
+            file_path: self.path.clone(),
+            format: MSFileFormat::MzXML,
+            version: None,
+            spectrum_count,
+            ms1_count,
+            ms2_count,
+        })
+    }
+
+    fn read_all(&self) -> ParseResult<Vec<Spectrum>> {
+        self.parser.parse(&self.path)
+    }
+}
+
+struct MgfSource {
+    path: PathBuf,
+}
+
+impl SpectraSource for MgfSource {
+    fn format(&self) -> MSFileFormat {
+        MSFileFormat::Mgf
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn iter_spectra(&self) -> ParseResult<Box<dyn Iterator<Item = ParseResult<Spectrum>> + Send>> {
+        Ok(Box::new(read_mgf(&self.path)?.into_iter().map(Ok)))
+    }
+
+    /// MGF中的谱图均为MS2
+    fn file_info(&self) -> ParseResult<SourceInfo> {
+        let spectrum_count = read_mgf(&self.path)?.len();
+        Ok(SourceInfo {
+            file_path: self.path.clone(),
+            format: MSFileFormat::Mgf,
+            version: None,
+            spectrum_count,
+            ms1_count: 0,
+            ms2_count: spectrum_count,
+        })
+    }
+
+    fn read_all(&self) -> ParseResult<Vec<Spectrum>> {
+        read_mgf(&self.path)
+    }
+}
+
+/// Python接口：按格式自动选择读取器，返回MZMLObject
+///
+/// `parse_spectra=False`时只统计谱图数；`parallel`只对mzML和mzXML生效；
+/// `infer_charges=True`时为前体离子电荷为0的MS2谱图由之前最近一张MS1的同位素间距推断电荷。
+/// 无法识别或扩展名与内容不符的文件抛出ValueError。
+#[cfg(feature = "python")]
+#[pyo3::pyfunction(name = "read")]
+#[pyo3(signature = (path, parse_spectra=true, parallel=false, num_processes=None, infer_charges=false))]
+pub fn py_read(
+    py: pyo3::Python,
+    path: PathBuf,
+    parse_spectra: bool,
+    parallel: bool,
+    num_processes: Option<usize>,
+    infer_charges: bool,
+) -> pyo3::PyResult<crate::parsers::mzml::MZMLObject> {
+    use crate::analysis::charge;
+    use crate::core::spectrum::SpectraStore;
+    use crate::core::types::Tolerance;
+    use crate::parsers::mzml::MZMLFileInfo;
+
+    let to_py_err = |e: ParseError| match e {
+        ParseError::InvalidFormat(_) => pyo3::exceptions::PyValueError::new_err(e.to_string()),
+        _ => pyo3::exceptions::PyIOError::new_err(e.to_string()),
+    };
+    let num_threads = parallel.then(|| num_processes.unwrap_or_else(num_cpus::get));
+    let source = open_ms_file_with(&path, ParseOptions::default(), num_threads).map_err(to_py_err)?;
+
+    let (spectra, file_info) = py
+        .allow_threads(|| -> ParseResult<_> {
+            let mut file_info = MZMLFileInfo::new(source.path());
+            file_info.file_format = source.format().name().to_string();
+            if !parse_spectra {
+                let info = source.file_info()?;
+                file_info.version = info.version;
+                file_info.spectrum_count = info.spectrum_count;
+                file_info.ms1_count = info.ms1_count;
+                file_info.ms2_count = info.ms2_count;
+                return Ok((Vec::new(), file_info));
+            }
+            let mut spectra = source.read_all()?;
+            if infer_charges {
+                let tolerance = Tolerance::PPM(charge::DEFAULT_CHARGE_TOLERANCE_PPM);
+                charge::infer_missing_charges(&mut spectra, tolerance, charge::DEFAULT_MAX_CHARGE);
+            }
+            if source.format() == MSFileFormat::MzML {
+                file_info.version = MZMLParser::new().read_version(source.path())?;
+            }
+            file_info.update_counts(&spectra);
+            Ok((spectra, file_info))
+        })
+        .map_err(to_py_err)?;
+
+    Ok(crate::parsers::mzml::MZMLObject { spectra: SpectraStore::shared(spectra), file_info })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::mzml::test_data::{build_mzml, TestSpectrum};
+    use std::io::Write;
+
+    const MZXML: &str = r#"<?xml version="1.0" encoding="ISO-8859-1"?>
+<mzXML xmlns="http://sashimi.sourceforge.net/schema_revision/mzXML_3.2">
+  <msRun scanCount="2">
+    <scan num="1" msLevel="1" peaksCount="1" retentionTime="PT1S">
+      <peaks precision="32" byteOrder="network" contentType="m/z-int" compressionType="none">Q8gAAEEgAAA=</peaks>
+      <scan num="2" msLevel="2" peaksCount="0" retentionTime="PT2S">
+        <precursorMz precursorCharge="2">400.5</precursorMz>
+        <peaks precision="32" byteOrder="network" contentType="m/z-int" compressionType="none"></peaks>
+      </scan>
+    </scan>
+  </msRun>
+</mzXML>
+"#;
+
+    const MGF: &str = "COM=global parameters\nBEGIN IONS\nTITLE=a\nPEPMASS=500.25\nCHARGE=2+\n150.0 10.0\n250.0 20.0\nEND IONS\n\
+                       BEGIN IONS\nTITLE=b\nPEPMASS=600.3\n200.0 5.0\nEND IONS\n";
+
+    fn temp_file(content: &[u8], suffix: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+        file.write_all(content).unwrap();
+        file.flush().unwrap();
+        file
+    }
+
+    fn mzml() -> String {
+        build_mzml(&[
+            TestSpectrum::new(1, 1, 10.0, vec![(400.0, 100.0), (500.0, 200.0)]),
+            TestSpectrum::new(2, 2, 12.5, vec![(150.0, 50.0)]).with_precursor(500.0, 2),
+            TestSpectrum::new(3, 2, 13.0, vec![(160.0, 40.0)]).with_precursor(400.0, 2),
+        ])
+    }
+
+    #[test]
+    fn test_open_each_format() {
+        let cases = [
+            (temp_file(mzml().as_bytes(), ".mzML"), MSFileFormat::MzML, (3, 1, 2)),
+            (temp_file(MZXML.as_bytes(), ".mzXML"), MSFileFormat::MzXML, (2, 1, 1)),
+            (temp_file(MGF.as_bytes(), ".mgf"), MSFileFormat::Mgf, (2, 0, 2)),
+            // 未知扩展名时按内容识别
+            (temp_file(MGF.as_bytes(), ".txt"), MSFileFormat::Mgf, (2, 0, 2)),
+        ];
+        for (file, format, counts) in cases {
+            let source = open_ms_file(file.path()).unwrap();
+            assert_eq!(source.format(), format);
+            assert_eq!(source.path(), file.path());
+
+            let info = source.file_info().unwrap();
+            assert_eq!((info.spectrum_count, info.ms1_count, info.ms2_count), counts, "{}", format.name());
+            assert_eq!(info.version.is_some(), format == MSFileFormat::MzML);
+
+            let spectra = source.read_all().unwrap();
+            assert_eq!(spectra.len(), counts.0);
+            let iterated: Vec<Spectrum> = source.iter_spectra().unwrap().collect::<ParseResult<_>>().unwrap();
+            assert_eq!(iterated.len(), spectra.len());
+            for (a, b) in iterated.iter().zip(&spectra) {
+                assert_eq!((a.peaks(), &a.scan.native_id), (b.peaks(), &b.scan.native_id));
+            }
+        }
+
+        let file = temp_file(MZXML.as_bytes(), ".mzXML");
+        let spectra = open_ms_file_with(file.path(), ParseOptions::default(), Some(2)).unwrap().read_all().unwrap();
+        assert_eq!(spectra[0].peaks(), vec![(400.0, 10.0)]);
+        assert_eq!(spectra[1].precursor.as_deref().unwrap().charge, 2);
+    }
+
+    #[test]
+    fn test_sniff_content() {
+        let indexed = "\u{feff}<?xml version=\"1.0\"?>\n<!-- written by a <converter> -->\n<indexedmzML xmlns=\"x\"><mzML>";
+        assert_eq!(sniff(indexed.as_bytes()), Ok(MSFileFormat::MzML));
+        assert_eq!(sniff(b"<ns:mzXML xmlns:ns=\"x\">"), Ok(MSFileFormat::MzXML));
+        assert_eq!(sniff(b"# comment\n\nbegin ions\n"), Ok(MSFileFormat::Mgf));
+        assert_eq!(sniff(b"<?xml version=\"1.0\"?><!DOCTYPE html><html>"), Err("an XML document with root element <html>".to_string()));
+        assert_eq!(sniff(b"  \n"), Err("empty".to_string()));
+        assert_eq!(sniff(&[0x1f, 0x8b, 0x08, 0x00]), Err("gzip-compressed data".to_string()));
+        assert_eq!(sniff(&[0x00, 0x01, 0xff, 0xfe, 0x00]), Err("binary data".to_string()));
+        assert_eq!(sniff(b"scan\tmz\tintensity\n1\t2\t3\n"), Err("text starting with 'scan\tmz\tintensity'".to_string()));
+    }
+
+    #[test]
+    fn test_misidentified_files_are_rejected() {
+        let message = |file: &tempfile::NamedTempFile| match open_ms_file(file.path()) {
+            Err(ParseError::InvalidFormat(message)) => message,
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(source) => panic!("opened as {}", source.format().name()),
+        };
+
+        let renamed = temp_file(MZXML.as_bytes(), ".mzML");
+        assert!(message(&renamed).ends_with("extension indicates mzML but the content looks like mzXML"));
+        let truncated = temp_file(b"", ".mgf");
+        assert!(message(&truncated).ends_with("extension indicates MGF but the content is empty"));
+        let raw = temp_file(&[0x01, 0xa1, 0x00, 0x00, 0xff], ".raw");
+        assert!(message(&raw).ends_with("unsupported file format, content is binary data (expected mzML, mzXML or MGF)"));
+
+        assert!(matches!(open_ms_file("does/not/exist.mzML"), Err(ParseError::File { .. })));
+    }
+}
@@ -0,0 +1,385 @@
+//! 谱图标题生成与解析
+//!
+//! 导出格式（MGF的TITLE、MSP的Name）需要可读且可复现的谱图标识。
+//! [`SpectrumTitleFormatter`]按模板生成标题，模板中的占位符写作`{name}`或
+//! `{name:.Nf}`（保留N位小数），`{{`和`}}`表示字面的花括号。可用占位符：
+//!
+//! - `file`：文件名（不含目录和扩展名）
+//! - `scan`：扫描号
+//! - `native_id`：原始谱图ID
+//! - `charge`：前体离子电荷，未知时为0或省略
+//! - `mz`：前体离子m/z，没有前体离子时为空
+//! - `rt`：保留时间（秒）
+//! - `target`：靶向提取的目标序号，没有时为空
+//!
+//! [`parse_title`]反向从常见标题写法中取回扫描号和电荷，读取MGF时用于恢复扫描号。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::parsers::common::{ParseError, ParseResult};
+use std::fmt::Write;
+use std::path::Path;
+
+#[cfg(feature = "python")]
+use crate::core::ms_object::MSObject;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 默认标题模板，即ProteoWizard/TPP风格的`文件.起始扫描.结束扫描.电荷`
+pub const DEFAULT_TITLE_TEMPLATE: &str = "{file}.{scan}.{scan}.{charge}";
+
+/// 模板占位符
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    File,
+    Scan,
+    NativeId,
+    Charge,
+    Mz,
+    Rt,
+    Target,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "file" => Some(Field::File),
+            "scan" => Some(Field::Scan),
+            "native_id" => Some(Field::NativeId),
+            "charge" => Some(Field::Charge),
+            "mz" => Some(Field::Mz),
+            "rt" => Some(Field::Rt),
+            "target" => Some(Field::Target),
+            _ => None,
+        }
+    }
+}
+
+/// 模板片段
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Field { field: Field, precision: Option<usize> },
+}
+
+/// 生成标题时谱图本身不包含的信息
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TitleContext<'a> {
+    /// 来源文件路径或文件名
+    pub file: &'a str,
+    /// 原始谱图ID
+    pub native_id: &'a str,
+    /// 靶向提取的目标序号
+    pub target: Option<usize>,
+}
+
+/// 谱图标题生成器
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectrumTitleFormatter {
+    segments: Vec<Segment>,
+    /// 电荷未知（0）时省略而不是输出"0"
+    omit_unknown_charge: bool,
+}
+
+impl Default for SpectrumTitleFormatter {
+    fn default() -> Self {
+        Self::new(DEFAULT_TITLE_TEMPLATE).expect("default title template is valid")
+    }
+}
+
+impl SpectrumTitleFormatter {
+    /// 解析模板，未知占位符、不支持的格式或不成对的花括号返回错误
+    pub fn new(template: &str) -> ParseResult<Self> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => {
+                                return Err(ParseError::InvalidFormat(format!(
+                                    "Unclosed placeholder in title template: {}",
+                                    template
+                                )))
+                            }
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Self::parse_placeholder(&placeholder)?);
+                }
+                '}' => {
+                    return Err(ParseError::InvalidFormat(format!(
+                        "Unmatched '}}' in title template: {}",
+                        template
+                    )))
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Self { segments, omit_unknown_charge: false })
+    }
+
+    /// 电荷未知时省略`{charge}`（默认输出"0"）
+    pub fn with_omit_unknown_charge(mut self, omit: bool) -> Self {
+        self.omit_unknown_charge = omit;
+        self
+    }
+
+    fn parse_placeholder(placeholder: &str) -> ParseResult<Segment> {
+        let (name, spec) = match placeholder.split_once(':') {
+            Some((name, spec)) => (name.trim(), Some(spec.trim())),
+            None => (placeholder.trim(), None),
+        };
+        let field = Field::from_name(name)
+            .ok_or_else(|| ParseError::InvalidFormat(format!("Unknown title placeholder: {{{}}}", name)))?;
+        let precision = match spec {
+            None => None,
+            Some(spec) => {
+                let digits = spec.strip_prefix('.').and_then(|rest| rest.strip_suffix('f'));
+                match digits.and_then(|digits| digits.parse::<usize>().ok()) {
+                    Some(precision) if matches!(field, Field::Mz | Field::Rt) => Some(precision),
+                    _ => {
+                        return Err(ParseError::InvalidFormat(format!(
+                            "Unsupported format '{}' for {{{}}} (only mz and rt accept '.Nf')",
+                            spec, name
+                        )))
+                    }
+                }
+            }
+        };
+        Ok(Segment::Field { field, precision })
+    }
+
+    /// 为谱图生成标题
+    pub fn format(&self, spectrum: &Spectrum, context: &TitleContext) -> String {
+        let mut title = String::new();
+        let precursor = spectrum.precursor.as_deref();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => title.push_str(text),
+                Segment::Field { field, precision } => {
+                    let float = |value: f64| match precision {
+                        Some(precision) => format!("{:.*}", precision, value),
+                        None => value.to_string(),
+                    };
+                    let _ = match field {
+                        Field::File => write!(title, "{}", file_stem(context.file)),
+                        Field::Scan => write!(title, "{}", spectrum.scan.scan_number),
+                        Field::NativeId => write!(title, "{}", context.native_id),
+                        Field::Charge => {
+                            let charge = precursor.map_or(0, |p| p.charge);
+                            if charge == 0 && self.omit_unknown_charge {
+                                Ok(())
+                            } else {
+                                write!(title, "{}", charge)
+                            }
+                        }
+                        Field::Mz => match precursor {
+                            Some(precursor) => write!(title, "{}", float(precursor.mz)),
+                            None => Ok(()),
+                        },
+                        Field::Rt => write!(title, "{}", float(spectrum.scan.retention_time)),
+                        Field::Target => match context.target {
+                            Some(target) => write!(title, "{}", target),
+                            None => Ok(()),
+                        },
+                    };
+                }
+            }
+        }
+        title
+    }
+}
+
+/// 文件名去掉目录和扩展名；`.mzML.gz`这类双扩展名也一并去掉
+fn file_stem(file: &str) -> &str {
+    let name = Path::new(file).file_name().and_then(|name| name.to_str()).unwrap_or(file);
+    let name = name.strip_suffix(".gz").unwrap_or(name);
+    match name.rfind('.') {
+        Some(position) if position > 0 => &name[..position],
+        _ => name,
+    }
+}
+
+/// 从标题中解析出的信息
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TitleInfo {
+    /// 扫描号
+    pub scan_number: Option<ScanNumber>,
+    /// 电荷
+    pub charge: Option<Charge>,
+}
+
+/// 从常见标题写法中解析扫描号和电荷
+///
+/// 支持的写法（按优先级）：
+/// - msconvert：`... NativeID:"controllerType=0 controllerNumber=1 scan=1234"`，扫描号取`scan=`
+/// - ProteoWizard/TPP：`run.1234.1234.2`，首个空白分隔的词以`.起始.结束.电荷`结尾
+/// - MaxQuant：`RawFile: run Index: 1234 Charge: 2`，扫描号取`Index:`或`Scan:`
+pub fn parse_title(title: &str) -> TitleInfo {
+    let mut info = TitleInfo::default();
+
+    // ProteoWizard/TPP的点分写法
+    if let Some(first) = title.split_whitespace().next() {
+        let parts: Vec<&str> = first.rsplitn(4, '.').collect();
+        if parts.len() == 4 {
+            if let (Ok(charge), Ok(_), Ok(start)) =
+                (parts[0].parse::<Charge>(), parts[1].parse::<ScanNumber>(), parts[2].parse::<ScanNumber>())
+            {
+                info.scan_number = Some(start);
+                info.charge = Some(charge);
+            }
+        }
+    }
+
+    // msconvert的NativeID中的scan=
+    if let Some(scan) = value_after(title, "scan=", false) {
+        info.scan_number = Some(scan);
+    }
+
+    // MaxQuant的"键: 值"写法
+    if info.scan_number.is_none() {
+        info.scan_number = value_after(title, "index:", true).or_else(|| value_after(title, "scan:", true));
+    }
+    if info.charge.is_none() {
+        info.charge = value_after(title, "charge:", true);
+    }
+
+    info
+}
+
+/// 查找`key`（ASCII大小写不敏感）后紧跟的整数
+fn value_after<T: std::str::FromStr>(text: &str, key: &str, skip_spaces: bool) -> Option<T> {
+    let lower = text.to_ascii_lowercase();
+    let mut search_from = 0;
+    while let Some(found) = lower[search_from..].find(key) {
+        let start = search_from + found;
+        // 键必须是一个词的开头，避免"prescan=12"之类的误匹配
+        let at_boundary = text[..start].chars().next_back().is_none_or(|c| !c.is_ascii_alphanumeric());
+        let rest = &text[start + key.len()..];
+        let rest = if skip_spaces { rest.trim_start() } else { rest };
+        let end = rest.find(|c: char| !(c.is_ascii_digit() || c == '+' || c == '-')).unwrap_or(rest.len());
+        if at_boundary {
+            if let Ok(value) = rest[..end].trim_end_matches('+').parse::<T>() {
+                return Some(value);
+            }
+        }
+        search_from = start + key.len();
+    }
+    None
+}
+
+/// Python接口：按模板生成谱图标题
+#[cfg(feature = "python")]
+#[pyfunction(name = "format_spectrum_title")]
+#[pyo3(signature = (ms_object, template=DEFAULT_TITLE_TEMPLATE, file="", native_id="", omit_unknown_charge=false))]
+pub fn py_format_spectrum_title(
+    ms_object: PyRef<'_, MSObject>,
+    template: &str,
+    file: &str,
+    native_id: &str,
+    omit_unknown_charge: bool,
+) -> PyResult<String> {
+    let formatter = SpectrumTitleFormatter::new(template)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?
+        .with_omit_unknown_charge(omit_unknown_charge);
+    Ok(formatter.format(&ms_object.spectrum, &TitleContext { file, native_id, target: None }))
+}
+
+/// Python接口：从标题解析(扫描号, 电荷)，无法识别的部分为None
+#[cfg(feature = "python")]
+#[pyfunction(name = "parse_spectrum_title")]
+pub fn py_parse_spectrum_title(title: &str) -> (Option<ScanNumber>, Option<Charge>) {
+    let info = parse_title(title);
+    (info.scan_number, info.charge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    fn spectrum(charge: Charge) -> Spectrum {
+        let mut spectrum = Spectrum::ms2().unwrap();
+        spectrum.set_scan_number(1234);
+        spectrum.set_retention_time(605.27).unwrap();
+        spectrum.set_precursor(PrecursorInfo { mz: 523.77421, charge, ..PrecursorInfo::default() });
+        spectrum
+    }
+
+    #[test]
+    fn test_format_title() {
+        let context = TitleContext { file: "/data/run01.mzML", native_id: "scan=1234", target: None };
+        let formatter = SpectrumTitleFormatter::default();
+        assert_eq!(formatter.format(&spectrum(2), &context), "run01.1234.1234.2");
+        assert_eq!(formatter.format(&spectrum(0), &context), "run01.1234.1234.0");
+
+        let formatter = SpectrumTitleFormatter::new("{file}.{scan}.{charge} mz={mz:.2f} rt={rt:.1f} {{{native_id}}}")
+            .unwrap()
+            .with_omit_unknown_charge(true);
+        assert_eq!(formatter.format(&spectrum(3), &context), "run01.1234.3 mz=523.77 rt=605.3 {scan=1234}");
+        assert_eq!(formatter.format(&spectrum(0), &context), "run01.1234. mz=523.77 rt=605.3 {scan=1234}");
+
+        let mut ms1 = Spectrum::ms1().unwrap();
+        ms1.set_scan_number(7);
+        let formatter = SpectrumTitleFormatter::new("t{target}|{mz}|{scan}").unwrap();
+        assert_eq!(formatter.format(&ms1, &TitleContext { target: Some(4), ..context }), "t4||7");
+
+        assert!(SpectrumTitleFormatter::new("{unknown}").is_err());
+        assert!(SpectrumTitleFormatter::new("{scan:.2f}").is_err());
+        assert!(SpectrumTitleFormatter::new("{scan").is_err());
+        assert!(SpectrumTitleFormatter::new("scan}").is_err());
+        assert_eq!(file_stem("run01.mzML.gz"), "run01");
+    }
+
+    #[test]
+    fn test_parse_title_dialects() {
+        // ProteoWizard/TPP
+        assert_eq!(
+            parse_title("run01.1234.1234.2"),
+            TitleInfo { scan_number: Some(1234), charge: Some(2) }
+        );
+        // msconvert
+        assert_eq!(
+            parse_title(r#"run01.1234.1234.3 File:"run01.raw", NativeID:"controllerType=0 controllerNumber=1 scan=1234""#),
+            TitleInfo { scan_number: Some(1234), charge: Some(3) }
+        );
+        assert_eq!(
+            parse_title(r#"File:"run01.raw", NativeID:"controllerType=0 controllerNumber=1 scan=88""#),
+            TitleInfo { scan_number: Some(88), charge: None }
+        );
+        // MaxQuant
+        assert_eq!(
+            parse_title("RawFile: 20100611_Velos1_HeLa Index: 5021 Charge: 2+"),
+            TitleInfo { scan_number: Some(5021), charge: Some(2) }
+        );
+        assert_eq!(parse_title("unknown spectrum"), TitleInfo::default());
+    }
+
+    #[test]
+    fn test_format_then_parse_round_trip() {
+        let title = SpectrumTitleFormatter::default().format(&spectrum(2), &TitleContext { file: "a.b.mzML", ..TitleContext::default() });
+        assert_eq!(title, "a.b.1234.1234.2");
+        assert_eq!(parse_title(&title), TitleInfo { scan_number: Some(1234), charge: Some(2) });
+    }
+}
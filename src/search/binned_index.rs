@@ -2,18 +2,17 @@
 //!
 //! 提供与原Python接口1:1兼容的二进制索引功能
 
-use crate::core::{Spectrum, BinnedSpectraIndex, CoreResult};
+use crate::core::spectrum::Spectrum;
 use crate::core::types::*;
 use std::collections::HashMap;
 
 #[cfg(feature = "python")]
-use pyo3::prelude::*;
+use crate::core::ms_object::MSObject;
 #[cfg(feature = "python")]
-use pyo3::types::{PyList, PyAny};
+use pyo3::prelude::*;
 
 /// Python兼容的二进制谱图索引
-#[cfg(feature = "python")]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct BinnedSpectra {
     pub spectra: Vec<Peak>,
     pub bin_size: f64,
@@ -24,26 +23,11 @@ pub struct BinnedSpectra {
 #[pymethods]
 impl BinnedSpectra {
     #[new]
-    fn new(spectra_list: Vec<&PyAny>, bin_size: f64) -> PyResult<Self> {
-        let mut peaks = Vec::new();
-
-        for py_spectrum in spectra_list {
-            // 尝试从MSObject提取峰数据
-            if let Ok(ms_object) = py_spectrum.extract::<crate::core::ms_object::MSObject>() {
-                peaks.extend_from_slice(&ms_object.spectrum.peaks);
-            }
-            // 尝试从元组列表提取峰数据
-            else if let Ok(peak_list) = py_spectrum.extract::<Vec<(f64, f64)>>() {
-                peaks.extend(peak_list);
-            } else {
-                return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
-                    "unsupported spectra type, expected MSObject or list of (mz, intensity) tuples"
-                ));
-            }
-        }
+    fn new(spectra_list: Vec<Bound<'_, PyAny>>, bin_size: f64) -> PyResult<Self> {
+        let mut peaks = extract_peaks(&spectra_list)?;
 
         // 排序峰数据
-        peaks.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
 
         let mut instance = Self {
             spectra: peaks,
@@ -52,85 +36,60 @@ impl BinnedSpectra {
         };
 
         // 生成bin索引
-        instance.bin_indices = instance._generate_bin_indices();
+        instance.bin_indices = instance.generate_bin_indices();
 
         Ok(instance)
     }
 
-    /// 搜索指定mz范围内的峰值
-    fn search_peaks(&self, py: Python, mz_range: (f64, f64)) -> PyResult<Py<PyList>> {
-        let (mz_low, mz_high) = mz_range;
-        let bin_low = (mz_low / self.bin_size) as i32;
-        let bin_high = (mz_high / self.bin_size) as i32;
-
-        let mut low_index = -1isize;
-        let mut high_index = -1isize;
-
-        for bin_index in bin_low..=bin_high {
-            if let Some((start, end)) = self.bin_indices.get(&bin_index) {
-                if low_index == -1 {
-                    low_index = *start as isize;
-                }
-                high_index = *end as isize;
-            }
-        }
-
-        let results = if low_index == -1 || high_index == -1 {
-            Vec::new()
-        } else {
-            let start_idx = low_index as usize;
-            let end_idx = high_index as usize;
-            let mut filtered = Vec::new();
-
-            for i in start_idx..=end_idx.min(self.spectra.len() - 1) {
-                let (mz, intensity) = self.spectra[i];
-                if mz >= mz_low && mz <= mz_high {
-                    filtered.push((mz, intensity));
-                }
-            }
-            filtered
-        };
-
-        let py_results = PyList::empty(py);
-        for (mz, intensity) in results {
-            py_results.append((mz, intensity))?;
-        }
+    /// 追加谱图，只对新增的峰排序后与已有的峰归并
+    #[pyo3(name = "add_spectra")]
+    fn py_add_spectra(&mut self, spectra_list: Vec<Bound<'_, PyAny>>) -> PyResult<()> {
+        let peaks = extract_peaks(&spectra_list)?;
+        self.merge_peaks(peaks);
+        Ok(())
+    }
 
-        Ok(py_results.into())
+    /// 搜索指定mz范围内的峰值
+    fn search_peaks(&self, mz_range: (f64, f64)) -> Vec<Peak> {
+        self.collect_range(mz_range)
     }
 
     /// 生成bin索引（内部方法，但保留以供Python调用）
     fn _generate_bin_indices(&self) -> HashMap<i32, (usize, usize)> {
-        let mut mz_to_index = HashMap::new();
-
-        if self.spectra.is_empty() {
-            return mz_to_index;
-        }
+        self.generate_bin_indices()
+    }
+}
 
-        for (index, &(mz, _)) in self.spectra.iter().enumerate() {
-            let bin_index = (mz / self.bin_size) as i32;
+/// 从MSObject或(mz, intensity)元组列表中提取峰数据
+#[cfg(feature = "python")]
+fn extract_peaks(spectra_list: &[Bound<'_, PyAny>]) -> PyResult<Vec<Peak>> {
+    let mut peaks = Vec::new();
 
-            if let Some((start, end)) = mz_to_index.get_mut(&bin_index) {
-                *end = index;
-            } else {
-                mz_to_index.insert(bin_index, (index, index));
-            }
+    for py_spectrum in spectra_list {
+        // 尝试从MSObject提取峰数据
+        if let Ok(ms_object) = py_spectrum.downcast::<MSObject>() {
+            peaks.extend(ms_object.borrow().spectrum.peaks_iter());
+        }
+        // 尝试从元组列表提取峰数据
+        else if let Ok(peak_list) = py_spectrum.extract::<Vec<(f64, f64)>>() {
+            peaks.extend(peak_list);
+        } else {
+            return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "unsupported spectra type, expected MSObject or list of (mz, intensity) tuples"
+            ));
         }
-
-        mz_to_index
     }
+
+    Ok(peaks)
 }
 
 impl BinnedSpectra {
     /// 创建新的二进制谱图索引（Rust接口）
     pub fn from_spectra(spectra: Vec<Spectrum>, bin_size: f64) -> CoreResult<Self> {
-        let mut peaks = Vec::new();
-        for spectrum in spectra {
-            peaks.extend_from_slice(&spectrum.peaks);
-        }
+        let mut peaks: Vec<Peak> = spectra.iter().flat_map(|spectrum| spectrum.peaks_iter()).collect();
 
         // 排序峰数据
-        peaks.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
 
         let mut instance = Self {
             spectra: peaks,
@@ -139,45 +98,80 @@ impl BinnedSpectra {
         };
 
         // 生成bin索引
-        instance.bin_indices = instance._generate_bin_indices();
+        instance.bin_indices = instance.generate_bin_indices();
 
         Ok(instance)
     }
 
-    /// 搜索m/z范围内的峰（Rust接口）
-    pub fn search_range(&self, mz_range: (f64, f64)) -> CoreResult<Vec<Peak>> {
-        let (mz_low, mz_high) = mz_range;
-        let bin_low = (mz_low / self.bin_size) as i32;
-        let bin_high = (mz_high / self.bin_size) as i32;
-
-        let mut low_index = -1isize;
-        let mut high_index = -1isize;
-
-        for bin_index in bin_low..=bin_high {
-            if let Some((start, end)) = self.bin_indices.get(&bin_index) {
-                if low_index == -1 {
-                    low_index = *start as isize;
-                }
-                high_index = *end as isize;
+    /// 追加谱图（Rust接口）
+    pub fn add_spectra(&mut self, new: Vec<Spectrum>) {
+        let peaks = new.iter().flat_map(|spectrum| spectrum.peaks_iter()).collect();
+        self.merge_peaks(peaks);
+    }
+
+    /// 把新增的峰归并到已排序的峰列表中
+    ///
+    /// 只对新增的k个峰排序，再与已有的n个峰归并；m/z相同时已有的峰在前，
+    /// 与对全部峰一次排序的结果相同。bin索引随后重新生成，每次追加的代价为O(k log k + n)。
+    fn merge_peaks(&mut self, mut new: Vec<Peak>) {
+        new.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let existing = std::mem::take(&mut self.spectra);
+        let mut merged = Vec::with_capacity(existing.len() + new.len());
+        let mut new = new.into_iter().peekable();
+        for peak in existing {
+            while let Some(next) = new.next_if(|next| next.0 < peak.0) {
+                merged.push(next);
             }
+            merged.push(peak);
         }
+        merged.extend(new);
+
+        self.spectra = merged;
+        self.bin_indices = self.generate_bin_indices();
+    }
+
+    /// 生成bin索引：每个非空bin对应的峰下标范围（首尾均包含）
+    fn generate_bin_indices(&self) -> HashMap<i32, (usize, usize)> {
+        let mut mz_to_index = HashMap::new();
 
-        if low_index == -1 || high_index == -1 {
-            return Ok(Vec::new());
+        for (index, &(mz, _)) in self.spectra.iter().enumerate() {
+            let bin_index = (mz / self.bin_size) as i32;
+            mz_to_index
+                .entry(bin_index)
+                .and_modify(|(_, end): &mut (usize, usize)| *end = index)
+                .or_insert((index, index));
         }
 
-        let start_idx = low_index as usize;
-        let end_idx = high_index as usize;
-        let mut filtered = Vec::new();
+        mz_to_index
+    }
 
-        for i in start_idx..=end_idx.min(self.spectra.len() - 1) {
-            let (mz, intensity) = self.spectra[i];
-            if mz >= mz_low && mz <= mz_high {
-                filtered.push((mz, intensity));
-            }
+    /// 搜索m/z范围内的峰（Rust接口）
+    pub fn search_range(&self, mz_range: (f64, f64)) -> CoreResult<Vec<Peak>> {
+        Ok(self.collect_range(mz_range))
+    }
+
+    /// 收集m/z范围内的峰
+    ///
+    /// 只访问范围内实际存在的bin，各bin的峰按m/z顺序依次取出，中间缺失的bin不会被扫描。
+    /// 查询的bin范围先截断到数据所在的bin，范围远超数据时不必逐个查找空bin。
+    fn collect_range(&self, mz_range: (f64, f64)) -> Vec<Peak> {
+        let (mz_low, mz_high) = mz_range;
+        let (Some(&(first_mz, _)), Some(&(last_mz, _))) = (self.spectra.first(), self.spectra.last()) else {
+            return Vec::new();
+        };
+        if mz_low > mz_high || mz_high < first_mz || mz_low > last_mz {
+            return Vec::new();
         }
 
-        Ok(filtered)
+        let bin_low = (mz_low.max(first_mz) / self.bin_size) as i32;
+        let bin_high = (mz_high.min(last_mz) / self.bin_size) as i32;
+        (bin_low..=bin_high)
+            .filter_map(|bin_index| self.bin_indices.get(&bin_index))
+            .flat_map(|&(start, end)| &self.spectra[start..=end])
+            .filter(|&&(mz, _)| mz >= mz_low && mz <= mz_high)
+            .copied()
+            .collect()
     }
 }
 
@@ -187,25 +181,91 @@ mod tests {
 
     #[test]
     fn test_binned_spectra_creation() {
-        let peaks = vec![(100.0, 1000.0), (200.0, 2000.0)];
         let binned = BinnedSpectra::from_spectra(Vec::new(), 10.0).unwrap();
-        // 基本创建测试
+        assert!(binned.spectra.is_empty());
+        assert!(binned.bin_indices.is_empty());
     }
 
     #[test]
     fn test_search_range() {
-        let mut peaks = vec![(100.0, 1000.0), (200.0, 2000.0), (300.0, 1500.0)];
-        peaks.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let peaks = vec![(100.0, 1000.0), (200.0, 2000.0), (300.0, 1500.0)];
 
         let mut binned = BinnedSpectra {
             spectra: peaks,
             bin_size: 10.0,
             bin_indices: HashMap::new(),
         };
-        binned.bin_indices = binned._generate_bin_indices();
+        binned.bin_indices = binned.generate_bin_indices();
 
         let results = binned.search_range((90.0, 110.0)).unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].0, 100.0);
     }
+
+    fn index_of(peaks: &[Peak], bin_size: f64) -> BinnedSpectra {
+        let mut spectrum = Spectrum::ms1().unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        BinnedSpectra::from_spectra(vec![spectrum], bin_size).unwrap()
+    }
+
+    #[test]
+    fn test_search_empty_index() {
+        let binned = BinnedSpectra::from_spectra(Vec::new(), 10.0).unwrap();
+        assert!(binned.search_range((0.0, 1000.0)).unwrap().is_empty());
+        assert!(binned.search_range((100.0, 100.0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_outside_data() {
+        let binned = index_of(&[(100.0, 1.0), (105.0, 2.0), (230.0, 3.0)], 10.0);
+        assert!(binned.search_range((10.0, 99.9)).unwrap().is_empty());
+        assert!(binned.search_range((230.1, 5000.0)).unwrap().is_empty());
+        // 下限大于上限
+        assert!(binned.search_range((200.0, 100.0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_across_gap_between_bins() {
+        // 110-200之间没有峰，对应的bin不存在
+        let peaks = [(100.0, 1.0), (105.0, 2.0), (201.0, 3.0), (205.0, 4.0), (301.0, 5.0)];
+        let binned = index_of(&peaks, 10.0);
+
+        assert_eq!(binned.search_range((104.0, 202.0)).unwrap(), vec![(105.0, 2.0), (201.0, 3.0)]);
+        assert_eq!(binned.search_range((150.0, 199.0)).unwrap(), vec![]);
+        assert_eq!(binned.search_range((0.0, 1e6)).unwrap(), peaks.to_vec());
+    }
+
+    #[test]
+    fn test_search_single_peak() {
+        let binned = index_of(&[(500.25, 7.0)], 0.5);
+        assert_eq!(binned.search_range((500.25, 500.25)).unwrap(), vec![(500.25, 7.0)]);
+        assert_eq!(binned.search_range((499.0, 501.0)).unwrap(), vec![(500.25, 7.0)]);
+        assert!(binned.search_range((500.26, 501.0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_add_spectra_matches_one_shot() {
+        let batch = |peaks: &[(f64, f64)]| {
+            let mut spectrum = Spectrum::ms1().unwrap();
+            spectrum.add_peaks(peaks.iter().copied()).unwrap();
+            vec![spectrum]
+        };
+        let batches = [
+            batch(&[(500.0, 1.0), (520.5, 2.0), (700.0, 3.0)]),
+            batch(&[(120.0, 4.0), (520.5, 5.0), (610.0, 6.0)]),
+            batch(&[(90.0, 7.0), (905.0, 8.0)]),
+        ];
+
+        let one_shot = BinnedSpectra::from_spectra(batches.concat(), 10.0).unwrap();
+        let mut incremental = BinnedSpectra::from_spectra(Vec::new(), 10.0).unwrap();
+        for batch in &batches {
+            incremental.add_spectra(batch.clone());
+        }
+
+        assert_eq!(incremental.spectra, one_shot.spectra);
+        assert_eq!(incremental.bin_indices, one_shot.bin_indices);
+        for range in [(0.0, 1000.0), (85.0, 125.0), (520.0, 521.0)] {
+            assert_eq!(incremental.search_range(range).unwrap(), one_shot.search_range(range).unwrap());
+        }
+    }
 }
@@ -0,0 +1,192 @@
+//! 按前体离子合并MS2谱图
+//!
+//! DDA运行中同一前体离子常在相邻时间内被重复碎裂。这里把前体离子m/z在容差内、
+//! 保留时间在窗口内的MS2谱图归为一组，再用[`PeakMerger`]把每组合并为一张共识谱图。
+//! 与[`cluster_ms2`](crate::analysis::clustering::cluster_ms2)不同，分组不要求碎片谱图相似。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::ion_mobility::merger::{MergeStrategy, PeakMerger};
+
+#[cfg(feature = "python")]
+use crate::core::ms_object::MSObject;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 共识谱图中记录成员扫描号的额外信息键，值为按保留时间排列、逗号分隔的扫描号
+pub const MERGED_SCANS_KEY: &str = "merged_scans";
+
+/// 合并碎片峰时的m/z容差 (Da)
+pub const FRAGMENT_MERGE_TOLERANCE_DA: f64 = 0.02;
+
+/// 按前体离子m/z和保留时间对MS2谱图分组
+///
+/// 谱图按保留时间顺序处理，每组以最早的谱图为锚点：前体离子m/z与锚点在容差内、
+/// 保留时间与锚点相差不超过`rt_window`、且电荷相同（任一方未知时不比较）的谱图归入该组，
+/// 有多个候选组时取锚点m/z最接近的。MS1谱图和没有前体离子信息的谱图不参与分组。
+/// 返回每组谱图在输入中的下标（按保留时间排列），各组按锚点的保留时间排列。
+pub fn group_ms2_by_precursor(spectra: &[Spectrum], precursor_tol: impl MzTolerance, rt_window: f64) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..spectra.len())
+        .filter(|&i| spectra[i].is_ms2() && spectra[i].precursor.is_some())
+        .collect();
+    order.sort_by(|&a, &b| spectra[a].scan.retention_time.total_cmp(&spectra[b].scan.retention_time));
+
+    let precursor = |i: usize| spectra[i].precursor.as_deref().unwrap();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    // 锚点仍在保留时间窗口内的组
+    let mut open: Vec<usize> = Vec::new();
+    for i in order {
+        let rt = spectra[i].scan.retention_time;
+        open.retain(|&group| rt - spectra[groups[group][0]].scan.retention_time <= rt_window);
+
+        let current = precursor(i);
+        let best = open
+            .iter()
+            .filter_map(|&group| {
+                let anchor = precursor(groups[group][0]);
+                let charges_agree = anchor.charge == 0 || current.charge == 0 || anchor.charge == current.charge;
+                (charges_agree && precursor_tol.is_within_tolerance(anchor.mz, current.mz))
+                    .then(|| (group, (anchor.mz - current.mz).abs()))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(group, _)| group);
+        match best {
+            Some(group) => groups[group].push(i),
+            None => {
+                open.push(groups.len());
+                groups.push(vec![i]);
+            }
+        }
+    }
+
+    groups
+}
+
+/// 按前体离子分组MS2谱图，每组合并为一张共识谱图
+///
+/// 分组规则见[`group_ms2_by_precursor`]。碎片峰按`strategy`在
+/// [`FRAGMENT_MERGE_TOLERANCE_DA`]内合并，前体离子信息和其余元数据来自组内总离子流
+/// 最高的谱图，成员扫描号记录在额外信息[`MERGED_SCANS_KEY`]中。只有一张谱图的组原样保留峰。
+pub fn group_and_merge_ms2(
+    spectra: Vec<Spectrum>,
+    precursor_tol: impl MzTolerance,
+    rt_window: f64,
+    strategy: MergeStrategy,
+) -> Vec<Spectrum> {
+    let merger = PeakMerger::new(strategy);
+    group_ms2_by_precursor(&spectra, precursor_tol, rt_window)
+        .iter()
+        .map(|group| merge_group(&spectra, group, &merger))
+        .collect()
+}
+
+/// 合并一组谱图
+fn merge_group(spectra: &[Spectrum], group: &[usize], merger: &PeakMerger) -> Spectrum {
+    let representative = group
+        .iter()
+        .map(|&i| &spectra[i])
+        .max_by(|a, b| a.total_ion_current().total_cmp(&b.total_ion_current()))
+        .expect("groups are never empty");
+
+    let mut consensus = if group.len() == 1 {
+        representative.clone()
+    } else {
+        let peak_lists: Vec<PeakList> = group.iter().map(|&i| spectra[i].peaks()).collect();
+        representative.with_peaks(merger.merge_multiple_peak_lists(&peak_lists, Tolerance::Absolute(FRAGMENT_MERGE_TOLERANCE_DA)))
+    };
+
+    let scans: Vec<String> = group.iter().map(|&i| spectra[i].scan.scan_number.to_string()).collect();
+    consensus.additional_info.retain(|kv| kv.key != MERGED_SCANS_KEY);
+    consensus.additional_info.push(KeyValue::new(MERGED_SCANS_KEY, scans.join(",")));
+    consensus
+}
+
+/// Python接口：按前体离子分组并合并MS2谱图
+///
+/// `strategy`为max、average、sum或weighted。
+#[cfg(feature = "python")]
+#[pyfunction(name = "group_and_merge_ms2")]
+#[pyo3(signature = (ms_objects, precursor_ppm=10.0, rt_window=30.0, strategy="max"))]
+pub fn py_group_and_merge_ms2(
+    py: Python<'_>,
+    ms_objects: Vec<PyRef<'_, MSObject>>,
+    precursor_ppm: f64,
+    rt_window: f64,
+    strategy: &str,
+) -> PyResult<Vec<MSObject>> {
+    let strategy = MergeStrategy::from_name(strategy)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let spectra: Vec<Spectrum> = ms_objects.iter().map(|ms_object| ms_object.spectrum.clone()).collect();
+    let merged = py.allow_threads(|| group_and_merge_ms2(spectra, Tolerance::PPM(precursor_ppm), rt_window, strategy));
+    Ok(merged.into_iter().map(|spectrum| MSObject { spectrum }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    fn ms2(scan: ScanNumber, rt: f64, precursor_mz: f64, charge: Charge, peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::ms2().unwrap();
+        spectrum.scan.scan_number = scan;
+        spectrum.scan.retention_time = rt;
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum.set_precursor(PrecursorInfo { mz: precursor_mz, charge, ..Default::default() });
+        spectrum
+    }
+
+    fn merged_scans(spectrum: &Spectrum) -> &str {
+        &spectrum.additional_info.iter().find(|kv| kv.key == MERGED_SCANS_KEY).unwrap().value
+    }
+
+    #[test]
+    fn test_group_and_merge_three_precursors() {
+        let mut ms1 = Spectrum::ms1().unwrap();
+        ms1.add_peak(500.25, 1e6).unwrap();
+        let spectra = vec![
+            ms1,
+            ms2(1, 10.0, 500.2500, 2, &[(300.0, 100.0), (400.0, 10.0)]),
+            ms2(2, 15.0, 650.3000, 3, &[(350.0, 80.0)]),
+            ms2(3, 20.0, 500.2520, 2, &[(300.005, 50.0), (450.0, 20.0)]),
+            ms2(4, 22.0, 800.4000, 2, &[(420.0, 30.0)]),
+            ms2(5, 25.0, 650.3030, 3, &[(350.01, 40.0), (500.0, 5.0)]),
+        ];
+
+        let merged = group_and_merge_ms2(spectra, Tolerance::PPM(10.0), 30.0, MergeStrategy::SumIntensity);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged.iter().map(merged_scans).collect::<Vec<_>>(), vec!["1,3", "2,5", "4"]);
+
+        // 前体离子信息来自总离子流最高的成员
+        let first = &merged[0];
+        assert_eq!(first.precursor.as_deref().unwrap().mz, 500.25);
+        assert_eq!(first.scan.scan_number, 1);
+        let peaks = first.peaks();
+        assert_eq!(peaks.len(), 3);
+        assert!((peaks[0].0 - (300.0 * 100.0 + 300.005 * 50.0) / 150.0).abs() < 1e-9);
+        assert_eq!(peaks[0].1, 150.0);
+
+        assert_eq!(merged[1].peaks().len(), 2);
+        assert_eq!(merged[2].peaks(), vec![(420.0, 30.0)]);
+    }
+
+    #[test]
+    fn test_grouping_respects_window_and_charge() {
+        let spectra = vec![
+            ms2(1, 10.0, 500.25, 2, &[(300.0, 1.0)]),
+            // 超出保留时间窗口
+            ms2(2, 45.0, 500.25, 2, &[(300.0, 1.0)]),
+            // 电荷不同
+            ms2(3, 12.0, 500.25, 3, &[(300.0, 1.0)]),
+            // 电荷未知时不比较
+            ms2(4, 14.0, 500.2501, 0, &[(300.0, 1.0)]),
+            // 超出m/z容差
+            ms2(5, 16.0, 500.27, 2, &[(300.0, 1.0)]),
+        ];
+
+        let groups = group_ms2_by_precursor(&spectra, Tolerance::PPM(10.0), 30.0);
+        assert_eq!(groups, vec![vec![0, 3], vec![2], vec![4], vec![1]]);
+        assert!(group_ms2_by_precursor(&[], Tolerance::PPM(10.0), 30.0).is_empty());
+        assert_eq!(MergeStrategy::from_name(" Mean ").unwrap(), MergeStrategy::AverageIntensity);
+        assert!(MergeStrategy::from_name("median").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! 谱库检索
+//!
+//! 谱库按前体离子m/z保存大量MS2谱图及其元数据。检索时先在按m/z排序的索引上
+//! 二分查找前体离子容差内的候选，再用[`cosine_match`]对候选打分，返回得分最高的命中。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::search::similarity::{cosine_match, IntensityScaling};
+
+#[cfg(feature = "python")]
+use crate::core::ms_object::{info_to_dict, parse_info_from_python, MSObject};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::PyDict;
+
+/// 谱库条目
+#[derive(Debug, Clone)]
+pub struct LibraryEntry {
+    /// 谱库谱图，必须带有前体离子信息
+    pub spectrum: Spectrum,
+    /// 条目元数据，如肽段序列、化合物名称
+    pub metadata: SmallKeyValueList,
+}
+
+/// 谱库检索命中
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryHit {
+    /// 条目在谱库中的下标（按加入顺序）
+    pub index: usize,
+    /// 谱库谱图的前体离子m/z
+    pub precursor_mz: f64,
+    /// 余弦相似度
+    pub score: f64,
+    /// 匹配的碎片峰数
+    pub matched_peaks: usize,
+    /// 条目元数据
+    pub metadata: SmallKeyValueList,
+}
+
+/// 按前体离子m/z索引的谱库
+#[derive(Debug, Clone, Default)]
+pub struct SpectralLibrary {
+    entries: Vec<LibraryEntry>,
+    /// (前体离子m/z, 条目下标)，按m/z升序
+    by_precursor: Vec<(f64, usize)>,
+}
+
+impl SpectralLibrary {
+    /// 创建空谱库
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 条目数
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 谱库是否为空
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 按下标获取条目
+    pub fn get(&self, index: usize) -> Option<&LibraryEntry> {
+        self.entries.get(index)
+    }
+
+    /// 加入一张谱图，返回其条目下标；谱图没有前体离子信息时返回错误
+    pub fn add_spectrum(&mut self, spectrum: Spectrum, metadata: SmallKeyValueList) -> CoreResult<usize> {
+        let precursor_mz = spectrum
+            .precursor
+            .as_deref()
+            .map(|precursor| precursor.mz)
+            .filter(|mz| mz.is_finite() && *mz > 0.0)
+            .ok_or_else(|| CoreError::InvalidFormat("Library spectrum has no precursor m/z".to_string()))?;
+
+        let index = self.entries.len();
+        let position = self.by_precursor.partition_point(|entry| entry.0 <= precursor_mz);
+        self.by_precursor.insert(position, (precursor_mz, index));
+        self.entries.push(LibraryEntry { spectrum, metadata });
+        Ok(index)
+    }
+
+    /// 检索与`query`最相似的至多`top_k`个条目，按得分降序（得分相同时匹配峰多者在前）
+    ///
+    /// 只对前体离子m/z在`precursor_tolerance`内的条目打分，得分为0的条目不计入结果；
+    /// 查询谱图没有前体离子信息时返回空列表。
+    pub fn search(
+        &self,
+        query: &Spectrum,
+        precursor_tolerance: Tolerance,
+        fragment_tolerance: Tolerance,
+        top_k: usize,
+    ) -> Vec<LibraryHit> {
+        let Some(query_mz) = query.precursor.as_deref().map(|precursor| precursor.mz) else {
+            return Vec::new();
+        };
+        let tolerance = precursor_tolerance.tolerance_at_mz(query_mz);
+        let start = self.by_precursor.partition_point(|entry| entry.0 < query_mz - tolerance);
+        let end = self.by_precursor.partition_point(|entry| entry.0 <= query_mz + tolerance);
+
+        let query_peaks = query.peaks();
+        let mut hits: Vec<LibraryHit> = self.by_precursor[start..end.max(start)]
+            .iter()
+            .filter_map(|&(precursor_mz, index)| {
+                let entry = &self.entries[index];
+                let result = cosine_match(&query_peaks, &entry.spectrum.peaks(), fragment_tolerance, None, IntensityScaling::Raw);
+                (result.score > 0.0).then(|| LibraryHit {
+                    index,
+                    precursor_mz,
+                    score: result.score,
+                    matched_peaks: result.matched_pairs.len(),
+                    metadata: entry.metadata.clone(),
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(b.matched_peaks.cmp(&a.matched_peaks))
+                .then(a.index.cmp(&b.index))
+        });
+        hits.truncate(top_k);
+        hits
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl LibraryHit {
+    /// 条目在谱库中的下标
+    #[getter]
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    /// 谱库谱图的前体离子m/z
+    #[getter]
+    fn precursor_mz(&self) -> f64 {
+        self.precursor_mz
+    }
+
+    /// 余弦相似度
+    #[getter]
+    fn score(&self) -> f64 {
+        self.score
+    }
+
+    /// 匹配的碎片峰数
+    #[getter]
+    fn matched_peaks(&self) -> usize {
+        self.matched_peaks
+    }
+
+    /// 条目元数据
+    #[getter]
+    fn metadata(&self, py: Python) -> PyResult<Py<PyDict>> {
+        Ok(info_to_dict(py, &self.metadata)?.unbind())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "LibraryHit(index={}, precursor_mz={}, score={:.4}, matched_peaks={})",
+            self.index, self.precursor_mz, self.score, self.matched_peaks
+        )
+    }
+}
+
+/// Python可用的谱库
+#[cfg(feature = "python")]
+#[pyclass(name = "SpectralLibrary")]
+pub struct PySpectralLibrary {
+    library: SpectralLibrary,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PySpectralLibrary {
+    /// 由MSObject列表创建谱库，各谱图的额外信息作为条目元数据
+    #[new]
+    #[pyo3(signature = (ms_objects=None))]
+    fn new(ms_objects: Option<Vec<PyRef<'_, MSObject>>>) -> PyResult<Self> {
+        let mut library = SpectralLibrary::new();
+        for ms_object in ms_objects.unwrap_or_default() {
+            let metadata = ms_object.spectrum.additional_info.clone();
+            library
+                .add_spectrum(ms_object.spectrum.clone(), metadata)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        }
+        Ok(Self { library })
+    }
+
+    /// 加入一张谱图，返回条目下标；未给出metadata时使用谱图的额外信息
+    #[pyo3(signature = (ms_object, metadata=None))]
+    fn add_spectrum(&mut self, ms_object: PyRef<'_, MSObject>, metadata: Option<&Bound<'_, PyAny>>) -> PyResult<usize> {
+        let metadata = match metadata {
+            Some(metadata) => parse_info_from_python(metadata)?,
+            None => ms_object.spectrum.additional_info.clone(),
+        };
+        self.library
+            .add_spectrum(ms_object.spectrum.clone(), metadata)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// 检索最相似的条目，碎片容差fragment_tolerance以Da为单位
+    #[pyo3(signature = (query, precursor_ppm=10.0, fragment_tolerance=0.02, top_k=10))]
+    fn search(
+        &self,
+        py: Python,
+        query: PyRef<'_, MSObject>,
+        precursor_ppm: f64,
+        fragment_tolerance: f64,
+        top_k: usize,
+    ) -> Vec<LibraryHit> {
+        let query = query.spectrum.clone();
+        py.allow_threads(|| {
+            self.library.search(&query, Tolerance::PPM(precursor_ppm), Tolerance::Absolute(fragment_tolerance), top_k)
+        })
+    }
+
+    /// 条目数
+    fn __len__(&self) -> usize {
+        self.library.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+
+    fn ms2(precursor_mz: f64, peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum.set_precursor(PrecursorInfo { mz: precursor_mz, charge: 2, ..PrecursorInfo::default() });
+        spectrum
+    }
+
+    fn name(value: &str) -> SmallKeyValueList {
+        vec![KeyValue::new("name", value)]
+    }
+
+    #[test]
+    fn test_true_match_ranks_first_among_decoys() {
+        let target = [(175.119, 40.0), (262.151, 15.0), (389.214, 60.0), (476.246, 25.0), (603.309, 80.0)];
+        let mut library = SpectralLibrary::new();
+        // 前体离子相同的诱饵谱图：峰位整体平移或强度打乱
+        for decoy in 0..10 {
+            let offset = 3.0 + decoy as f64 * 1.7;
+            let peaks: Vec<Peak> = target.iter().rev().map(|&(mz, intensity)| (mz + offset, intensity)).collect();
+            library.add_spectrum(ms2(500.27, &peaks), name(&format!("decoy{}", decoy))).unwrap();
+        }
+        let shuffled: Vec<Peak> = target.iter().zip(target.iter().rev()).map(|(&(mz, _), &(_, i))| (mz, i)).collect();
+        library.add_spectrum(ms2(500.272, &shuffled), name("shuffled")).unwrap();
+        let truth = library.add_spectrum(ms2(500.271, &target), name("PEPTIDE")).unwrap();
+        // 峰列表相同但前体离子超出容差，不应被打分
+        library.add_spectrum(ms2(520.0, &target), name("far")).unwrap();
+        assert!(library.add_spectrum(Spectrum::new(2).unwrap(), Vec::new()).is_err());
+        assert_eq!(library.len(), 13);
+
+        let noisy: Vec<Peak> = target.iter().map(|&(mz, intensity)| (mz + 0.003, intensity * 1.1)).collect();
+        let query = ms2(500.2705, &noisy);
+        let hits = library.search(&query, Tolerance::PPM(10.0), Tolerance::Absolute(0.02), 5);
+
+        assert_eq!(hits.len(), 2, "{:?}", hits);
+        assert_eq!(hits[0].index, truth);
+        assert_eq!(hits[0].metadata, name("PEPTIDE"));
+        assert_eq!(hits[0].matched_peaks, 5);
+        assert!((hits[0].score - 1.0).abs() < 1e-12);
+        assert_eq!(hits[1].metadata, name("shuffled"));
+        assert!(hits[1].score < hits[0].score);
+        assert!(hits.iter().all(|hit| (hit.precursor_mz - 500.2705).abs() < 0.01));
+
+        assert_eq!(library.search(&query, Tolerance::PPM(10.0), Tolerance::Absolute(0.02), 1).len(), 1);
+        assert!(library.search(&Spectrum::new(2).unwrap(), Tolerance::PPM(10.0), Tolerance::Absolute(0.02), 5).is_empty());
+    }
+}
@@ -4,12 +4,19 @@
 //! - 二进制索引实现
 //! - 并行搜索算法
 //! - 范围查询优化
+//! - 谱图相似度打分
+//! - 谱库检索
+//! - 按前体离子分组合并MS2谱图
 
 pub mod binned_index;
+pub mod similarity;
+pub mod library;
 pub mod parallel_search;
+pub mod grouping;
 pub mod range_query;
 
 // 重新导出主要类型
-pub use binned_index::*;
-pub use parallel_search::*;
-pub use range_query::*;
+pub use parallel_search::ParallelRangeSearcher;
+pub use grouping::{group_and_merge_ms2, group_ms2_by_precursor};
+pub use range_query::RangeQueryEngine;
+pub use binned_index::BinnedSpectra;
@@ -1,16 +1,125 @@
 //! 并行搜索算法
-//! 
-//! 提供并行化的质谱数据搜索功能
+//!
+//! 在整个运行上做碎片匹配时需要对同一个索引发起成千上万个m/z窗口查询。
+//! [`ParallelRangeSearcher`]把这些查询分给rayon线程并行执行，结果保持查询顺序。
 
+use crate::core::spectrum::BinnedSpectraIndex;
 use crate::core::types::*;
+use log::warn;
+use rayon::prelude::*;
 
-/// 并行搜索器
-pub struct ParallelSearcher {
-    // 占位实现
+/// 对同一个二进制索引并行执行多个m/z范围查询
+#[derive(Debug, Clone, Copy)]
+pub struct ParallelRangeSearcher<'a> {
+    index: &'a BinnedSpectraIndex,
 }
 
-impl ParallelSearcher {
-    pub fn new() -> Self {
-        Self {}
+impl<'a> ParallelRangeSearcher<'a> {
+    /// 包装一个索引
+    pub fn new(index: &'a BinnedSpectraIndex) -> Self {
+        Self { index }
+    }
+
+    /// 被查询的索引
+    pub fn index(&self) -> &'a BinnedSpectraIndex {
+        self.index
+    }
+
+    /// 逐个执行`ranges`中的查询，第i个结果对应第i个范围，与[`BinnedSpectraIndex::search_range`]相同
+    ///
+    /// `num_threads`为0时使用rayon的全局线程池；无法创建指定大小的线程池时退回全局线程池。
+    pub fn search_many(&self, ranges: &[(f64, f64)], num_threads: usize) -> Vec<Vec<Peak>> {
+        // search_range不会失败，Result只为接口兼容
+        let search = || -> Vec<Vec<Peak>> {
+            ranges
+                .par_iter()
+                .map(|&range| self.index.search_range(range).unwrap_or_default())
+                .collect()
+        };
+        match num_threads {
+            0 => search(),
+            _ => match rayon::ThreadPoolBuilder::new().num_threads(num_threads).build() {
+                Ok(pool) => pool.install(search),
+                Err(e) => {
+                    warn!("Failed to build a {}-thread pool ({}), using the global pool", num_threads, e);
+                    search()
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::Spectrum;
+    use std::time::Instant;
+    use crate::core::test_spectrum::pseudo_random;
+
+    fn random_index(spectra: usize, peaks: usize, seed: &mut u64) -> BinnedSpectraIndex {
+        let spectra = (0..spectra)
+            .map(|_| {
+                let mut spectrum = Spectrum::ms2().unwrap();
+                spectrum
+                    .add_peaks((0..peaks).map(|_| (100.0 + 1900.0 * pseudo_random(seed), 1e4 * pseudo_random(seed))))
+                    .unwrap();
+                spectrum
+            })
+            .collect();
+        BinnedSpectraIndex::new(spectra, 1.0).unwrap()
+    }
+
+    fn random_windows(count: usize, seed: &mut u64) -> Vec<(f64, f64)> {
+        (0..count)
+            .map(|_| {
+                let center = 90.0 + 1920.0 * pseudo_random(seed);
+                let half_width = 0.001 + 0.05 * pseudo_random(seed);
+                (center - half_width, center + half_width)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_many_windows_match_serial_queries() {
+        let mut seed = 2024;
+        let index = random_index(200, 150, &mut seed);
+        let mut ranges = random_windows(10_000, &mut seed);
+        ranges.push((5000.0, 6000.0));
+        ranges.push((10.0, 3000.0));
+
+        let searcher = ParallelRangeSearcher::new(&index);
+        let expected: Vec<Vec<Peak>> = ranges.iter().map(|&range| index.search_range(range).unwrap()).collect();
+        for threads in [0, 1, 4] {
+            assert_eq!(searcher.search_many(&ranges, threads), expected);
+        }
+        assert!(expected.iter().filter(|peaks| !peaks.is_empty()).count() > 1000);
+        assert!(searcher.search_many(&[], 2).is_empty());
+    }
+
+    #[test]
+    #[ignore = "timing benchmark; run with --ignored --nocapture"]
+    fn bench_search_many_thread_scaling() {
+        let mut seed = 99;
+        let index = random_index(2000, 300, &mut seed);
+        let ranges = random_windows(100_000, &mut seed);
+        let searcher = ParallelRangeSearcher::new(&index);
+
+        let start = Instant::now();
+        let single = searcher.search_many(&ranges, 1);
+        let single_time = start.elapsed();
+        let threads = num_cpus::get();
+        let start = Instant::now();
+        let parallel = searcher.search_many(&ranges, threads);
+        let parallel_time = start.elapsed();
+
+        assert_eq!(parallel, single);
+        println!(
+            "{} windows: 1 thread {:?}, {} threads {:?} ({:.1}x)",
+            ranges.len(), single_time, threads, parallel_time,
+            single_time.as_secs_f64() / parallel_time.as_secs_f64(),
+        );
+        if threads > 1 {
+            assert!(parallel_time < single_time, "no speed-up with {} threads", threads);
+        }
     }
 }
@@ -1,16 +1,202 @@
-//! 范围查询优化
-//! 
-//! 提供优化的范围查询功能
+//! RT × m/z 二维范围查询
+//!
+//! 谱图存放在共享的[`SharedSpectra`]中，存储已按保留时间排列谱图、按m/z排序峰，
+//! 两个维度都用二分查找定位：先在保留时间上截取谱图区间，再在区间内每张谱图上截取m/z窗口。
+//! 也支持反向查询：哪些扫描在给定m/z附近有峰。
 
+use crate::core::spectrum::{PeakHit, SharedSpectra, SpectraStore, Spectrum};
 use crate::core::types::*;
+use crate::xic::simd_search::SIMDSearcher;
 
-/// 范围查询器
-pub struct RangeQuery {
-    // 占位实现
+#[cfg(feature = "python")]
+use crate::xic::extractor::py_shared_spectra;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// RT × m/z 范围查询引擎
+#[derive(Debug, Clone)]
+pub struct RangeQueryEngine {
+    /// 共享的谱图数据
+    spectra: SharedSpectra,
+    searcher: SIMDSearcher,
 }
 
-impl RangeQuery {
-    pub fn new() -> Self {
-        Self {}
+impl RangeQueryEngine {
+    /// 由一次运行的谱图创建，保留时间相同的谱图保持输入顺序
+    pub fn new(spectra: Vec<Spectrum>) -> Self {
+        Self::from_shared(SpectraStore::shared(spectra))
+    }
+
+    /// 在已加载的共享谱图数据上创建，不复制谱图
+    pub fn from_shared(spectra: SharedSpectra) -> Self {
+        Self { spectra, searcher: SIMDSearcher::new() }
+    }
+
+    /// 共享的谱图数据
+    pub fn shared_spectra(&self) -> &SharedSpectra {
+        &self.spectra
+    }
+
+    /// 谱图数量
+    pub fn spectrum_count(&self) -> usize {
+        self.spectra.len()
+    }
+
+    /// 保留时间范围 (秒)，没有谱图时为None
+    pub fn rt_range(&self) -> Option<(RetentionTime, RetentionTime)> {
+        self.spectra.rt_range()
+    }
+
+    /// m/z在`[mz_range.0, mz_range.1]`内且保留时间在`[rt_range.0, rt_range.1]`内的所有峰
+    ///
+    /// 结果按保留时间、再按m/z排列；`spectrum_index`为谱图在输入中的下标。
+    pub fn query(&self, mz_range: (f64, f64), rt_range: (RetentionTime, RetentionTime)) -> Vec<PeakHit> {
+        let mut hits = Vec::new();
+        for &index in &self.spectra.rt_order()[self.spectra.rt_span(rt_range)] {
+            let window = self.searcher.window(self.spectra[index].mz_slice(), mz_range.0, mz_range.1);
+            hits.extend(window.map(|peak| self.hit(index, peak)));
+        }
+        hits
+    }
+
+    /// 在`mz`的容差内有峰的扫描
+    ///
+    /// 每张谱图返回容差内强度最高的峰，结果按保留时间排列。
+    pub fn scans_containing(&self, mz: f64, tolerance: impl MzTolerance) -> Vec<PeakHit> {
+        let (low, high) = tolerance.window(mz);
+        self.spectra
+            .rt_order()
+            .iter()
+            .filter_map(|&index| {
+                let spectrum = &self.spectra[index];
+                let intensity = spectrum.intensity_slice();
+                self.searcher
+                    .window(spectrum.mz_slice(), low, high)
+                    .max_by(|&a, &b| intensity[a].total_cmp(&intensity[b]))
+                    .map(|peak| self.hit(index, peak))
+            })
+            .collect()
+    }
+
+    fn hit(&self, index: usize, peak: usize) -> PeakHit {
+        let spectrum = &self.spectra[index];
+        PeakHit {
+            mz: spectrum.mz_slice()[peak],
+            intensity: spectrum.intensity_slice()[peak],
+            spectrum_index: index,
+            scan_number: spectrum.scan.scan_number,
+            retention_time: spectrum.scan.retention_time,
+        }
+    }
+}
+
+/// Python可用的RT × m/z范围查询
+#[cfg(feature = "python")]
+#[pyclass(name = "RangeQuery")]
+pub struct PyRangeQuery {
+    pub engine: RangeQueryEngine,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyRangeQuery {
+    /// 由一次运行的MSObject列表或MZMLObject创建，传入MZMLObject时与它共用同一份谱图数据
+    #[new]
+    fn new(ms_objects: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(Self { engine: RangeQueryEngine::from_shared(py_shared_spectra(ms_objects)?) })
+    }
+
+    /// 谱图数量
+    #[getter]
+    fn spectrum_count(&self) -> usize {
+        self.engine.spectrum_count()
+    }
+
+    /// 保留时间范围 (秒)
+    #[getter]
+    fn rt_range(&self) -> Option<(f64, f64)> {
+        self.engine.rt_range()
+    }
+
+    /// m/z和保留时间都在范围内的所有峰
+    fn query(&self, py: Python<'_>, mz_range: (f64, f64), rt_range: (f64, f64)) -> Vec<PeakHit> {
+        py.allow_threads(|| self.engine.query(mz_range, rt_range))
+    }
+
+    /// 在`mz`的ppm容差内有峰的扫描，每张谱图取最强峰
+    #[pyo3(signature = (mz, ppm=10.0))]
+    fn scans_containing(&self, py: Python<'_>, mz: f64, ppm: f64) -> Vec<PeakHit> {
+        py.allow_threads(|| self.engine.scans_containing(mz, Tolerance::PPM(ppm)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms1(scan: ScanNumber, rt: f64, peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::ms1().unwrap();
+        spectrum.scan.scan_number = scan;
+        spectrum.scan.retention_time = rt;
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum
+    }
+
+    fn engine() -> RangeQueryEngine {
+        // 输入不按保留时间排列，峰也未排序
+        RangeQueryEngine::new(vec![
+            ms1(3, 30.0, &[(500.0, 30.0), (400.0, 3.0)]),
+            ms1(1, 10.0, &[(600.0, 1.0), (500.002, 10.0), (500.0, 5.0)]),
+            ms1(2, 20.0, &[(450.0, 2.0)]),
+            ms1(4, 40.0, &[(500.001, 40.0)]),
+        ])
+    }
+
+    #[test]
+    fn test_query_rt_and_mz_ranges() {
+        let engine = engine();
+        assert_eq!(engine.spectrum_count(), 4);
+        assert_eq!(engine.rt_range(), Some((10.0, 40.0)));
+
+        let hits = engine.query((499.0, 501.0), (10.0, 30.0));
+        let summary: Vec<_> = hits.iter().map(|hit| (hit.scan_number, hit.mz, hit.spectrum_index)).collect();
+        assert_eq!(summary, vec![(1, 500.0, 1), (1, 500.002, 1), (3, 500.0, 0)]);
+        assert_eq!(hits[2].retention_time, 30.0);
+        assert_eq!(hits[2].intensity, 30.0);
+
+        // 边界包含在内
+        assert_eq!(engine.query((450.0, 450.0), (20.0, 20.0)).len(), 1);
+        assert!(engine.query((499.0, 501.0), (31.0, 39.0)).is_empty());
+        assert!(engine.query((501.0, 499.0), (0.0, 100.0)).is_empty());
+        assert!(engine.query((0.0, 1000.0), (40.0, 10.0)).is_empty());
+        assert_eq!(engine.query((0.0, 1000.0), (0.0, 100.0)).len(), 7);
+    }
+
+    #[test]
+    fn test_engine_shares_store() {
+        let store = SpectraStore::shared(vec![ms1(1, 10.0, &[(500.0, 1.0)]), ms1(2, 5.0, &[(500.0, 2.0)])]);
+        let engine = RangeQueryEngine::from_shared(store.clone());
+        assert!(std::sync::Arc::ptr_eq(engine.shared_spectra(), &store));
+        assert_eq!(std::sync::Arc::strong_count(&store), 2);
+
+        let hits = engine.query((499.0, 501.0), (0.0, 20.0));
+        assert_eq!(hits.iter().map(|hit| hit.spectrum_index).collect::<Vec<_>>(), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_scans_containing() {
+        let engine = engine();
+        let hits = engine.scans_containing(500.0, Tolerance::PPM(5.0));
+        let summary: Vec<_> = hits.iter().map(|hit| (hit.scan_number, hit.mz)).collect();
+        // 扫描1在容差内有两个峰，取强度最高的
+        assert_eq!(summary, vec![(1, 500.002), (3, 500.0), (4, 500.001)]);
+
+        assert_eq!(engine.scans_containing(500.0, Tolerance::PPM(1.0)).len(), 2);
+        assert!(engine.scans_containing(700.0, Tolerance::Absolute(0.5)).is_empty());
+
+        let empty = RangeQueryEngine::new(Vec::new());
+        assert_eq!(empty.rt_range(), None);
+        assert!(empty.query((0.0, 1000.0), (0.0, 100.0)).is_empty());
+        assert!(empty.scans_containing(500.0, Tolerance::PPM(10.0)).is_empty());
     }
 }
@@ -0,0 +1,248 @@
+//! 谱图相似度
+//!
+//! 谱库匹配和谱图聚类需要比较两张MS2谱图。这里先在m/z容差内列出所有候选峰对，
+//! 按强度乘积从大到小贪心配对（每个峰至多使用一次），再据此计算余弦相似度
+//! （归一化点积）、允许前体离子质量偏移的修正余弦，以及光谱对比角。
+
+use crate::core::types::*;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 默认的碎片峰匹配容差 (Da)
+pub const DEFAULT_FRAGMENT_TOLERANCE_DA: f64 = 0.02;
+
+/// 打分前的强度缩放
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntensityScaling {
+    /// 使用原始强度
+    #[default]
+    Raw,
+    /// 使用强度的平方根，降低少数强峰的权重
+    Sqrt,
+}
+
+impl IntensityScaling {
+    /// 缩放后的强度，负强度（基线校正数据）视为0
+    fn weight(self, intensity: f64) -> f64 {
+        match self {
+            IntensityScaling::Raw => intensity.max(0.0),
+            IntensityScaling::Sqrt => intensity.max(0.0).sqrt(),
+        }
+    }
+}
+
+/// 相似度打分结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimilarityMatch {
+    /// 余弦相似度，范围[0, 1]
+    pub score: f64,
+    /// 匹配峰对：(第一张谱图中的峰下标, 第二张谱图中的峰下标)，按第一个下标排序
+    pub matched_pairs: Vec<(usize, usize)>,
+}
+
+/// 余弦相似度（归一化点积），任一谱图为空或强度全为0时返回0，相同谱图返回1
+pub fn cosine(a: &[Peak], b: &[Peak], tolerance: impl MzTolerance) -> f64 {
+    cosine_match(a, b, tolerance, None, IntensityScaling::Raw).score
+}
+
+/// 修正余弦：除直接匹配外，第二张谱图中相对第一张偏移两者前体离子m/z之差的峰也可配对
+pub fn modified_cosine(a: &[Peak], b: &[Peak], precursor_mz_a: f64, precursor_mz_b: f64, tolerance: impl MzTolerance) -> f64 {
+    cosine_match(a, b, tolerance, Some(precursor_mz_b - precursor_mz_a), IntensityScaling::Raw).score
+}
+
+/// 光谱对比角：`1 - 2·arccos(cos)/π`，范围[0, 1]，相同谱图为1，无共同峰时为0
+pub fn spectral_angle(a: &[Peak], b: &[Peak], tolerance: impl MzTolerance) -> f64 {
+    angle_from_cosine(cosine(a, b, tolerance))
+}
+
+/// 由余弦相似度换算光谱对比角
+pub fn angle_from_cosine(cosine: f64) -> f64 {
+    1.0 - 2.0 * cosine.clamp(0.0, 1.0).acos() / std::f64::consts::PI
+}
+
+/// 贪心配对并计算余弦相似度，返回分数和匹配峰对
+///
+/// 第一张谱图m/z为`mz_a`的峰可与第二张谱图中m/z在`mz_a`（及给出`mz_shift`时在`mz_a + mz_shift`）
+/// 容差内的峰配对，容差按目标m/z计算。候选峰对按缩放后强度的乘积从大到小选取，
+/// 已配对的峰不再使用。
+pub fn cosine_match(
+    a: &[Peak],
+    b: &[Peak],
+    tolerance: impl MzTolerance,
+    mz_shift: Option<f64>,
+    scaling: IntensityScaling,
+) -> SimilarityMatch {
+    let weights_a: Vec<f64> = a.iter().map(|peak| scaling.weight(peak.1)).collect();
+    let weights_b: Vec<f64> = b.iter().map(|peak| scaling.weight(peak.1)).collect();
+    let norm_a = weights_a.iter().map(|w| w * w).sum::<f64>();
+    let norm_b = weights_b.iter().map(|w| w * w).sum::<f64>();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return SimilarityMatch::default();
+    }
+
+    let mut order_b: Vec<usize> = (0..b.len()).filter(|&j| weights_b[j] > 0.0).collect();
+    order_b.sort_by(|&x, &y| b[x].0.total_cmp(&b[y].0));
+
+    let shifts = [Some(0.0), mz_shift.filter(|shift| *shift != 0.0)];
+    let mut candidates = Vec::new();
+    for (i, peak) in a.iter().enumerate().filter(|&(i, _)| weights_a[i] > 0.0) {
+        for shift in shifts.into_iter().flatten() {
+            let target = peak.0 + shift;
+            let tol = tolerance.tolerance_at_mz(target);
+            let first = order_b.partition_point(|&j| b[j].0 < target - tol);
+            for &j in order_b[first..].iter().take_while(|&&j| b[j].0 <= target + tol) {
+                candidates.push((weights_a[i] * weights_b[j], i, j));
+            }
+        }
+    }
+    candidates.sort_by(|x, y| y.0.total_cmp(&x.0).then((x.1, x.2).cmp(&(y.1, y.2))));
+
+    let mut used_a = vec![false; a.len()];
+    let mut used_b = vec![false; b.len()];
+    let mut matched_pairs = Vec::new();
+    for (_, i, j) in candidates {
+        if !used_a[i] && !used_b[j] {
+            used_a[i] = true;
+            used_b[j] = true;
+            matched_pairs.push((i, j));
+        }
+    }
+    matched_pairs.sort_unstable();
+
+    // 按第一张谱图的峰顺序累加，相同谱图的点积与范数逐位相等，分数恰为1
+    let dot: f64 = matched_pairs.iter().map(|&(i, j)| weights_a[i] * weights_b[j]).sum();
+    SimilarityMatch {
+        score: (dot / (norm_a * norm_b).sqrt()).min(1.0),
+        matched_pairs,
+    }
+}
+
+/// Python可用的谱图相似度打分
+///
+/// `tolerance`为碎片峰匹配容差：数值按Da处理，也可传入ToleranceModel；
+/// `sqrt=True`时先对强度开平方。
+#[cfg(feature = "python")]
+#[pyclass]
+pub struct SpectrumSimilarity;
+
+#[cfg(feature = "python")]
+fn tolerance_from_python(tolerance: Option<&Bound<'_, PyAny>>) -> PyResult<ToleranceModel> {
+    match tolerance {
+        None => Ok(Tolerance::Absolute(DEFAULT_FRAGMENT_TOLERANCE_DA).into()),
+        Some(value) => match value.extract::<PyToleranceModel>() {
+            Ok(model) => Ok(model.model),
+            Err(_) => Ok(Tolerance::Absolute(value.extract::<f64>()?).into()),
+        },
+    }
+}
+
+#[cfg(feature = "python")]
+fn scaling_from_flag(sqrt: bool) -> IntensityScaling {
+    if sqrt { IntensityScaling::Sqrt } else { IntensityScaling::Raw }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl SpectrumSimilarity {
+    /// 余弦相似度
+    #[staticmethod]
+    #[pyo3(signature = (peaks_a, peaks_b, tolerance=None, sqrt=false))]
+    fn cosine(peaks_a: Vec<Peak>, peaks_b: Vec<Peak>, tolerance: Option<&Bound<'_, PyAny>>, sqrt: bool) -> PyResult<f64> {
+        let tolerance = tolerance_from_python(tolerance)?;
+        Ok(cosine_match(&peaks_a, &peaks_b, &tolerance, None, scaling_from_flag(sqrt)).score)
+    }
+
+    /// 修正余弦，允许按前体离子m/z之差偏移配对
+    #[staticmethod]
+    #[pyo3(signature = (peaks_a, peaks_b, precursor_mz_a, precursor_mz_b, tolerance=None, sqrt=false))]
+    fn modified_cosine(
+        peaks_a: Vec<Peak>,
+        peaks_b: Vec<Peak>,
+        precursor_mz_a: f64,
+        precursor_mz_b: f64,
+        tolerance: Option<&Bound<'_, PyAny>>,
+        sqrt: bool,
+    ) -> PyResult<f64> {
+        let tolerance = tolerance_from_python(tolerance)?;
+        let shift = Some(precursor_mz_b - precursor_mz_a);
+        Ok(cosine_match(&peaks_a, &peaks_b, &tolerance, shift, scaling_from_flag(sqrt)).score)
+    }
+
+    /// 光谱对比角
+    #[staticmethod]
+    #[pyo3(signature = (peaks_a, peaks_b, tolerance=None, sqrt=false))]
+    fn spectral_angle(peaks_a: Vec<Peak>, peaks_b: Vec<Peak>, tolerance: Option<&Bound<'_, PyAny>>, sqrt: bool) -> PyResult<f64> {
+        let tolerance = tolerance_from_python(tolerance)?;
+        Ok(angle_from_cosine(cosine_match(&peaks_a, &peaks_b, &tolerance, None, scaling_from_flag(sqrt)).score))
+    }
+
+    /// 余弦相似度及匹配峰对，返回(分数, [(峰下标a, 峰下标b), ...])
+    #[staticmethod]
+    #[pyo3(signature = (peaks_a, peaks_b, tolerance=None, sqrt=false, mz_shift=None))]
+    fn match_peaks(
+        peaks_a: Vec<Peak>,
+        peaks_b: Vec<Peak>,
+        tolerance: Option<&Bound<'_, PyAny>>,
+        sqrt: bool,
+        mz_shift: Option<f64>,
+    ) -> PyResult<(f64, Vec<(usize, usize)>)> {
+        let tolerance = tolerance_from_python(tolerance)?;
+        let result = cosine_match(&peaks_a, &peaks_b, &tolerance, mz_shift, scaling_from_flag(sqrt));
+        Ok((result.score, result.matched_pairs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOL: Tolerance = Tolerance::Absolute(0.02);
+
+    #[test]
+    fn test_identical_and_empty_spectra() {
+        let peaks = [(101.071, 3.7), (175.119, 12.9), (262.151, 0.31), (389.214, 7.3), (503.257, 5.1)];
+        assert_eq!(cosine(&peaks, &peaks, TOL), 1.0);
+        assert_eq!(spectral_angle(&peaks, &peaks, TOL), 1.0);
+        assert_eq!(cosine_match(&peaks, &peaks, TOL, None, IntensityScaling::Sqrt).score, 1.0);
+        assert_eq!(modified_cosine(&peaks, &peaks, 600.0, 600.0, TOL), 1.0);
+
+        assert_eq!(cosine(&peaks, &[], TOL), 0.0);
+        assert_eq!(cosine(&[], &[], TOL), 0.0);
+        assert_eq!(spectral_angle(&[], &peaks, TOL), 0.0);
+        assert_eq!(cosine(&peaks, &[(150.0, 1.0)], TOL), 0.0);
+    }
+
+    #[test]
+    fn test_greedy_matching_and_scaling() {
+        // 第一张谱图的两个峰都落在200.01的容差内，强度乘积大的峰对优先
+        let a = [(200.0, 1.0), (200.02, 10.0)];
+        let b = [(200.01, 5.0)];
+        let result = cosine_match(&a, &b, TOL, None, IntensityScaling::Raw);
+        assert_eq!(result.matched_pairs, vec![(1, 0)]);
+        assert!((result.score - 10.0 / 101f64.sqrt()).abs() < 1e-12);
+
+        let a = [(100.0, 100.0), (200.0, 1.0)];
+        let b = [(100.0, 1.0), (200.0, 100.0)];
+        let raw = cosine(&a, &b, TOL);
+        let sqrt = cosine_match(&a, &b, TOL, None, IntensityScaling::Sqrt).score;
+        assert!((raw - 200.0 / 10001.0).abs() < 1e-12);
+        assert!((sqrt - 20.0 / 101.0).abs() < 1e-12);
+        assert!(spectral_angle(&a, &b, TOL) < raw);
+    }
+
+    #[test]
+    fn test_modified_cosine_follows_precursor_shift() {
+        // 第二张谱图为带+79.966修饰的同一肽段：b离子不变，y离子整体偏移
+        let shift = 79.966;
+        let a = [(147.113, 10.0), (276.155, 20.0), (300.2, 15.0), (415.3, 8.0)];
+        let b = [(147.113, 10.0), (276.155, 20.0), (300.2 + shift, 15.0), (415.3 + shift, 8.0)];
+        let plain = cosine(&a, &b, TOL);
+        let modified = modified_cosine(&a, &b, 500.0, 500.0 + shift, TOL);
+        assert!((plain - 500.0 / 789.0).abs() < 1e-12);
+        assert!((modified - 1.0).abs() < 1e-12);
+
+        let result = cosine_match(&a, &b, TOL, Some(shift), IntensityScaling::Raw);
+        assert_eq!(result.matched_pairs, vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
+    }
+}
@@ -2,8 +2,6 @@
 
 use pyo3::prelude::*;
 use pyo3::types::PyList;
-use crate::core::Spectrum;
-use crate::parsers::{MZMLParser, MZMLUtils};
 
 #[pyclass]
 #[derive(Debug, Clone)]
@@ -31,7 +29,7 @@ impl TestMSObject {
 
     #[getter]
     fn peaks(&self, py: Python) -> PyResult<Py<PyList>> {
-        let list = PyList::empty_bound(py);
+        let list = PyList::empty(py);
         for (mz, intensity) in &self.peaks {
             list.append((mz, intensity))?;
         }
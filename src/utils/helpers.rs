@@ -1,16 +1,16 @@
 //! 基础工具函数
 
 use crate::core::types::*;
+use crate::xic::simd_search::SIMDSearcher;
 
-/// 查找在给定容差范围内匹配的峰
+/// 查找在给定容差范围内匹配的峰，CPU支持时使用AVX2
 pub fn find_peaks_in_tolerance(peaks: &[Peak], target_mz: f64, tolerance: f64) -> Vec<usize> {
-    let mut result = Vec::new();
-    for (i, &(mz, _)) in peaks.iter().enumerate() {
-        if (mz - target_mz).abs() <= tolerance {
-            result.push(i);
-        }
-    }
-    result
+    SIMDSearcher::new().find_in_tolerance(peaks, target_mz, tolerance)
+}
+
+/// 查找m/z数组中在给定容差范围内的峰，CPU支持时使用AVX2
+pub fn find_mz_in_tolerance(mz: &[f64], target_mz: f64, tolerance: f64) -> Vec<usize> {
+    SIMDSearcher::new().find_mz_in_tolerance(mz, target_mz, tolerance)
 }
 
 /// 计算总离子流
@@ -31,4 +31,66 @@ pub fn filter_by_mz_range(peaks: &[Peak], range: (f64, f64)) -> Vec<Peak> {
         .filter(|&&(mz, _)| mz >= range.0 && mz <= range.1)
         .copied()
         .collect()
-}
\ No newline at end of file
+}
+
+/// 最小二乘多项式拟合，返回系数（常数项在前）；点数不足或法方程奇异时返回None
+///
+/// 高次拟合前应先把`x`缩放到[-1, 1]附近，避免法方程病态。
+pub fn polynomial_fit(x: &[f64], y: &[f64], degree: usize) -> Option<Vec<f64>> {
+    let terms = degree + 1;
+    if x.len().min(y.len()) < terms {
+        return None;
+    }
+    // 法方程 (XᵀX) c = Xᵀy 的增广矩阵
+    let mut normal = vec![vec![0.0; terms + 1]; terms];
+    for (&xi, &yi) in x.iter().zip(y) {
+        let powers: Vec<f64> = (0..terms).map(|power| xi.powi(power as i32)).collect();
+        for row in 0..terms {
+            for column in 0..terms {
+                normal[row][column] += powers[row] * powers[column];
+            }
+            normal[row][terms] += powers[row] * yi;
+        }
+    }
+    solve_linear_system(normal)
+}
+
+/// 多项式在`x`处的值，系数常数项在前
+pub fn polynomial_value(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |value, coefficient| value * x + coefficient)
+}
+
+/// 已排序（升序或降序）数组的中位数，空数组为None
+pub(crate) fn median(sorted: &[f64]) -> Option<f64> {
+    let n = sorted.len();
+    match n {
+        0 => None,
+        _ if n % 2 == 1 => Some(sorted[n / 2]),
+        _ => Some((sorted[n / 2 - 1] + sorted[n / 2]) / 2.0),
+    }
+}
+
+/// 部分主元高斯消元求解n×(n+1)增广矩阵，奇异时返回None
+fn solve_linear_system(mut matrix: Vec<Vec<f64>>) -> Option<Vec<f64>> {
+    let n = matrix.len();
+    for column in 0..n {
+        let pivot = (column..n).max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))?;
+        if matrix[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        for row in column + 1..n {
+            let (above, below) = matrix.split_at_mut(row);
+            let factor = below[0][column] / above[column][column];
+            for (value, pivot_value) in below[0][column..].iter_mut().zip(&above[column][column..]) {
+                *value -= factor * pivot_value;
+            }
+        }
+    }
+    let mut solution = vec![0.0; n];
+    for row in (0..n).rev() {
+        let known: f64 = (row + 1..n).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (matrix[row][n] - known) / matrix[row][row];
+    }
+    Some(solution)
+}
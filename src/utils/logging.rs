@@ -0,0 +1,152 @@
+//! 日志配置
+//!
+//! 库内部通过`log`宏输出诊断信息（跳过的谱图、不支持的压缩方式等）。
+//! Python端调用`set_log_level`后，这些日志会转发到Python的`logging`模块，
+//! 记录器名称由Rust模块路径转换而来（`::`替换为`.`）。
+
+use log::LevelFilter;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 按名称解析日志级别（不区分大小写，支持Python的级别名称）
+pub fn parse_level(name: &str) -> Option<LevelFilter> {
+    match name.trim().to_ascii_uppercase().as_str() {
+        "TRACE" => Some(LevelFilter::Trace),
+        "DEBUG" => Some(LevelFilter::Debug),
+        "INFO" => Some(LevelFilter::Info),
+        "WARN" | "WARNING" => Some(LevelFilter::Warn),
+        "ERROR" | "CRITICAL" => Some(LevelFilter::Error),
+        "OFF" | "NONE" => Some(LevelFilter::Off),
+        _ => None,
+    }
+}
+
+/// 将Python `logging`模块的数值级别转换为日志级别
+pub fn level_from_python(level: i64) -> LevelFilter {
+    match level {
+        i64::MIN..=0 => LevelFilter::Trace,
+        1..=10 => LevelFilter::Debug,
+        11..=20 => LevelFilter::Info,
+        21..=30 => LevelFilter::Warn,
+        31..=50 => LevelFilter::Error,
+        _ => LevelFilter::Off,
+    }
+}
+
+#[cfg(feature = "python")]
+static PY_LOGGER: std::sync::OnceLock<pyo3_log::ResetHandle> = std::sync::OnceLock::new();
+
+/// Python接口：设置日志级别并将日志转发到Python的`logging`模块
+///
+/// `level`可以是级别名称（"DEBUG"、"INFO"、"WARNING"、"ERROR"、"OFF"）
+/// 或`logging.DEBUG`之类的整数。
+#[cfg(feature = "python")]
+#[pyfunction(name = "set_log_level")]
+pub fn py_set_log_level(py: Python<'_>, level: &Bound<'_, PyAny>) -> PyResult<()> {
+    let filter = if let Ok(name) = level.extract::<String>() {
+        parse_level(&name).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!("Unknown log level: {}", name))
+        })?
+    } else if let Ok(value) = level.extract::<i64>() {
+        level_from_python(value)
+    } else {
+        return Err(pyo3::exceptions::PyTypeError::new_err(
+            "level must be a level name (str) or a logging level (int)",
+        ));
+    };
+
+    match PY_LOGGER.get() {
+        // Python端的级别配置被缓存，重新设置时需要清空缓存
+        Some(handle) => handle.reset(),
+        None => {
+            let handle = pyo3_log::Logger::new(py, pyo3_log::Caching::LoggersAndLevels)?
+                .filter(LevelFilter::Trace)
+                .install()
+                .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+            let _ = PY_LOGGER.set(handle);
+        }
+    }
+
+    log::set_max_level(filter);
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod test_logger {
+    //! 测试用的日志捕获器
+
+    use log::{Level, Log, Metadata, Record};
+    use std::sync::{Mutex, OnceLock};
+
+    struct CapturingLogger {
+        records: Mutex<Vec<(Level, String)>>,
+    }
+
+    impl Log for CapturingLogger {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            self.records
+                .lock()
+                .unwrap()
+                .push((record.level(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: OnceLock<&'static CapturingLogger> = OnceLock::new();
+
+    fn logger() -> &'static CapturingLogger {
+        LOGGER.get_or_init(|| {
+            let logger: &'static CapturingLogger = Box::leak(Box::new(CapturingLogger {
+                records: Mutex::new(Vec::new()),
+            }));
+            let _ = log::set_logger(logger);
+            log::set_max_level(log::LevelFilter::Trace);
+            logger
+        })
+    }
+
+    /// 安装捕获器（仅首次调用生效）
+    pub(crate) fn install() {
+        logger();
+    }
+
+    /// 返回包含`marker`的日志记录（测试并行运行，用唯一标记区分）
+    pub(crate) fn records_containing(marker: &str) -> Vec<(Level, String)> {
+        logger()
+            .records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, message)| message.contains(marker))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("warning"), Some(LevelFilter::Warn));
+        assert_eq!(parse_level(" DEBUG "), Some(LevelFilter::Debug));
+        assert_eq!(parse_level("off"), Some(LevelFilter::Off));
+        assert_eq!(parse_level("verbose"), None);
+    }
+
+    #[test]
+    fn test_level_from_python() {
+        assert_eq!(level_from_python(10), LevelFilter::Debug);
+        assert_eq!(level_from_python(20), LevelFilter::Info);
+        assert_eq!(level_from_python(30), LevelFilter::Warn);
+        assert_eq!(level_from_python(40), LevelFilter::Error);
+        assert_eq!(level_from_python(100), LevelFilter::Off);
+    }
+}
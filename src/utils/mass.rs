@@ -0,0 +1,252 @@
+//! 质量与m/z换算工具
+//!
+//! 提供中性质量与m/z之间的换算（支持常见加合物和负离子模式），
+//! 以及ppm偏差计算，避免在各处手写 `mz * z - z * 1.00728` 之类的公式。
+
+use crate::core::types::*;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 质子质量 (Da)
+pub const PROTON_MASS: f64 = 1.007_276_466_812;
+
+/// 电子质量 (Da)
+pub const ELECTRON_MASS: f64 = 0.000_548_579_909;
+
+/// 同位素峰间距：13C与12C的质量差 (Da)
+pub const ISOTOPE_SPACING: f64 = 1.003_354_837_8;
+
+/// 水分子质量 (Da)，肽段两端的H和OH
+pub const WATER_MASS: f64 = 18.010_564_684;
+
+/// 加合物定义
+///
+/// 加合离子形式为 `[M + n·carrier]`，其中carrier是带电载体（如H+、Na+、去质子）。
+/// 普通加合物的n等于电荷数；`+2H`这类固定加合物的n固定，只能用于对应电荷。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adduct {
+    /// 加合物名称，如"+H"、"-H"
+    pub name: &'static str,
+    /// 单个带电载体的质量（去质子为负值）
+    pub carrier_mass: f64,
+    /// 单个载体的电荷极性（+1或-1）
+    pub polarity: i8,
+    /// 固定的载体个数（None表示随电荷数变化）
+    pub fixed_count: Option<u8>,
+}
+
+impl Adduct {
+    /// 按名称查找加合物
+    pub fn from_name(name: &str) -> CoreResult<Adduct> {
+        let normalized = name.trim().replace('−', "-");
+        COMMON_ADDUCTS
+            .iter()
+            .find(|adduct| adduct.name.eq_ignore_ascii_case(&normalized))
+            .copied()
+            .ok_or_else(|| CoreError::InvalidFormat(format!("Unknown adduct: {}", name)))
+    }
+
+    /// 是否为负离子模式加合物
+    pub fn is_negative(&self) -> bool {
+        self.polarity < 0
+    }
+
+    /// 给定电荷数下的载体个数
+    fn carrier_count(&self, charge: Charge) -> CoreResult<u8> {
+        let z = charge.unsigned_abs();
+        if z == 0 {
+            return Err(CoreError::InvalidCharge {
+                charge,
+                min: 1,
+                max: Charge::MAX,
+            });
+        }
+        match self.fixed_count {
+            Some(count) if count != z => Err(CoreError::InvalidFormat(format!(
+                "Adduct {} requires charge {}, got {}", self.name, count, z
+            ))),
+            _ => Ok(z),
+        }
+    }
+}
+
+/// 常见加合物
+pub const COMMON_ADDUCTS: &[Adduct] = &[
+    Adduct { name: "+H", carrier_mass: PROTON_MASS, polarity: 1, fixed_count: None },
+    Adduct { name: "+2H", carrier_mass: PROTON_MASS, polarity: 1, fixed_count: Some(2) },
+    Adduct { name: "+3H", carrier_mass: PROTON_MASS, polarity: 1, fixed_count: Some(3) },
+    Adduct { name: "+Na", carrier_mass: 22.989_769_282 - ELECTRON_MASS, polarity: 1, fixed_count: None },
+    Adduct { name: "+K", carrier_mass: 38.963_706_486 - ELECTRON_MASS, polarity: 1, fixed_count: None },
+    Adduct { name: "+NH4", carrier_mass: 18.034_374_132 - ELECTRON_MASS, polarity: 1, fixed_count: None },
+    Adduct { name: "-H", carrier_mass: -PROTON_MASS, polarity: -1, fixed_count: None },
+    Adduct { name: "-2H", carrier_mass: -PROTON_MASS, polarity: -1, fixed_count: Some(2) },
+    Adduct { name: "+Cl", carrier_mass: 34.968_852_682 + ELECTRON_MASS, polarity: -1, fixed_count: None },
+    Adduct { name: "+HCOO", carrier_mass: 44.997_654_268 + ELECTRON_MASS, polarity: -1, fixed_count: None },
+];
+
+/// 由m/z计算中性质量
+///
+/// 电荷的符号被忽略，离子极性由加合物决定（例如"-H"按负离子计算）。
+pub fn neutral_mass(mz: f64, charge: Charge, adduct: &str) -> CoreResult<f64> {
+    let adduct = Adduct::from_name(adduct)?;
+    let count = adduct.carrier_count(charge)? as f64;
+    Ok(mz * count - count * adduct.carrier_mass)
+}
+
+/// 由中性质量计算m/z
+pub fn mz_from_neutral(mass: f64, charge: Charge, adduct: &str) -> CoreResult<f64> {
+    let adduct = Adduct::from_name(adduct)?;
+    let count = adduct.carrier_count(charge)? as f64;
+    Ok((mass + count * adduct.carrier_mass) / count)
+}
+
+/// 氨基酸残基的单同位素质量
+pub fn residue_mass(residue: char) -> Option<f64> {
+    let mass = match residue.to_ascii_uppercase() {
+        'G' => 57.021_463_72,
+        'A' => 71.037_113_79,
+        'S' => 87.032_028_41,
+        'P' => 97.052_763_85,
+        'V' => 99.068_413_91,
+        'T' => 101.047_678_47,
+        'C' => 103.009_184_48,
+        'L' | 'I' => 113.084_064_04,
+        'N' => 114.042_927_44,
+        'D' => 115.026_943_03,
+        'Q' => 128.058_577_51,
+        'K' => 128.094_963_01,
+        'E' => 129.042_593_10,
+        'M' => 131.040_484_92,
+        'H' => 137.058_911_86,
+        'F' => 147.068_413_91,
+        'U' => 150.953_633_41,
+        'R' => 156.101_111_05,
+        'Y' => 163.063_328_53,
+        'W' => 186.079_312_98,
+        _ => return None,
+    };
+    Some(mass)
+}
+
+/// 未修饰肽段的单同位素中性质量
+pub fn peptide_mass(sequence: &str) -> CoreResult<f64> {
+    if sequence.trim().is_empty() {
+        return Err(CoreError::InvalidFormat("Empty peptide sequence".to_string()));
+    }
+    sequence.trim().chars().try_fold(WATER_MASS, |mass, residue| {
+        residue_mass(residue)
+            .map(|residue_mass| mass + residue_mass)
+            .ok_or_else(|| CoreError::InvalidFormat(format!("Unknown amino acid '{}' in sequence {}", residue, sequence)))
+    })
+}
+
+/// 按电荷符号选择默认加合物：正电荷为"+H"，负电荷为"-H"
+pub fn default_adduct(charge: Charge) -> &'static str {
+    if charge < 0 { "-H" } else { "+H" }
+}
+
+/// 计算`observed`相对`reference`的ppm偏差
+pub fn ppm_diff(observed: f64, reference: f64) -> f64 {
+    (observed - reference) / reference * 1e6
+}
+
+/// 检查两个值的ppm偏差是否在容差内
+pub fn within_ppm(observed: f64, reference: f64, ppm: f64) -> bool {
+    ppm_diff(observed, reference).abs() <= ppm
+}
+
+/// Python接口：由m/z计算中性质量
+#[cfg(feature = "python")]
+#[pyfunction(name = "neutral_mass")]
+#[pyo3(signature = (mz, charge, adduct="+H"))]
+pub fn py_neutral_mass(mz: f64, charge: Charge, adduct: &str) -> PyResult<f64> {
+    neutral_mass(mz, charge, adduct)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
+/// Python接口：由中性质量计算m/z
+#[cfg(feature = "python")]
+#[pyfunction(name = "mz_from_neutral")]
+#[pyo3(signature = (mass, charge, adduct="+H"))]
+pub fn py_mz_from_neutral(mass: f64, charge: Charge, adduct: &str) -> PyResult<f64> {
+    mz_from_neutral(mass, charge, adduct)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
+/// Python接口：ppm偏差
+#[cfg(feature = "python")]
+#[pyfunction(name = "ppm_diff")]
+pub fn py_ppm_diff(observed: f64, reference: f64) -> f64 {
+    ppm_diff(observed, reference)
+}
+
+/// Python接口：ppm容差判断
+#[cfg(feature = "python")]
+#[pyfunction(name = "within_ppm")]
+pub fn py_within_ppm(observed: f64, reference: f64, ppm: f64) -> bool {
+    within_ppm(observed, reference, ppm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_doubly_charged_peptide() {
+        // [M+2H]2+，M = 1000.0
+        let mz = (1000.0 + 2.0 * 1.007276466812) / 2.0;
+        assert_close(mz_from_neutral(1000.0, 2, "+H").unwrap(), mz);
+        assert_close(neutral_mass(mz, 2, "+H").unwrap(), 1000.0);
+        assert_close(neutral_mass(mz, 2, "+2H").unwrap(), 1000.0);
+    }
+
+    #[test]
+    fn test_triply_charged_peptide() {
+        // 手算：(2000.0 + 3 * 1.007276466812) / 3 = 667.673943133479
+        assert_close(mz_from_neutral(2000.0, 3, "+H").unwrap(), 667.673_943_133_479);
+        assert_close(neutral_mass(667.673_943_133_479, 3, "+H").unwrap(), 2000.0);
+    }
+
+    #[test]
+    fn test_negative_mode_small_molecule() {
+        // 葡萄糖 C6H12O6 单同位素质量180.063388，[M-H]- = 179.056112
+        assert_close(mz_from_neutral(180.063_388, 1, "-H").unwrap(), 179.056_111_533);
+        assert_close(neutral_mass(179.056_111_533, 1, "-H").unwrap(), 180.063_388);
+        // 电荷符号不影响结果
+        assert_close(neutral_mass(179.056_111_533, -1, "−H").unwrap(), 180.063_388);
+    }
+
+    #[test]
+    fn test_sodium_adduct() {
+        assert_close(mz_from_neutral(500.0, 1, "+Na").unwrap(), 522.989_220_702);
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        assert!(neutral_mass(500.0, 0, "+H").is_err());
+        assert!(neutral_mass(500.0, 3, "+2H").is_err());
+        assert!(neutral_mass(500.0, 1, "+Xe").is_err());
+    }
+
+    #[test]
+    fn test_peptide_mass() {
+        // 手算：PEPTIDE = 97.05276385 + 129.0425931 * 2 + 97.05276385 + 101.04767847
+        //      + 113.08406404 + 115.02694303 + 18.010564684 = 799.359964
+        assert_close(peptide_mass("PEPTIDE").unwrap(), 799.359_964_144);
+        assert!(peptide_mass("PEPTIDEX").is_err());
+        assert!(peptide_mass("").is_err());
+    }
+
+    #[test]
+    fn test_ppm() {
+        assert_close(ppm_diff(1000.01, 1000.0), 10.0);
+        assert!(within_ppm(1000.009, 1000.0, 10.0));
+        assert!(!within_ppm(1000.011, 1000.0, 10.0));
+        assert!(default_adduct(-2) == "-H" && default_adduct(2) == "+H");
+    }
+}
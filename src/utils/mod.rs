@@ -1,3 +1,6 @@
 //! 工具函数模块
 
-pub mod helpers;
\ No newline at end of file
+pub mod helpers;
+pub mod logging;
+pub mod mass;
+pub mod path;
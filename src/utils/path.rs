@@ -0,0 +1,87 @@
+//! 文件路径处理
+//!
+//! Windows上超过MAX_PATH的路径必须带`\\?\`扩展长度前缀才能被文件API打开；
+//! 其他平台上路径原样使用。
+
+use std::borrow::Cow;
+use std::path::Path;
+
+/// 需要加扩展长度前缀的路径长度（UTF-16单元）
+///
+/// 创建目录时的上限为MAX_PATH (260) 减去8.3文件名的长度 (12)。
+pub const LONG_PATH_THRESHOLD: usize = 248;
+
+/// 返回可直接交给文件API的路径
+///
+/// Windows上路径转为绝对路径后长度达到[`LONG_PATH_THRESHOLD`]时加`\\?\`前缀
+/// （UNC路径为`\\?\UNC\`），已带前缀或较短的路径原样返回。
+#[cfg(windows)]
+pub fn extended_length_path(path: &Path) -> Cow<'_, Path> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::{Component, Prefix};
+
+    // absolute会解析`.`和`..`并统一分隔符，带前缀的路径不再做这些处理
+    let Ok(absolute) = std::path::absolute(path) else {
+        return Cow::Borrowed(path);
+    };
+    if absolute.as_os_str().encode_wide().count() < LONG_PATH_THRESHOLD {
+        return Cow::Borrowed(path);
+    }
+
+    let mut components = absolute.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return Cow::Borrowed(path);
+    };
+    let extended = match prefix.kind() {
+        Prefix::Disk(_) => {
+            let mut extended = OsString::from(r"\\?\");
+            extended.push(absolute.as_os_str());
+            extended
+        }
+        Prefix::UNC(server, share) => {
+            let mut extended = OsString::from(r"\\?\UNC\");
+            extended.push(server);
+            extended.push(r"\");
+            extended.push(share);
+            extended.push(components.as_path().as_os_str());
+            extended
+        }
+        // 已是扩展长度路径或设备路径
+        _ => return Cow::Borrowed(path),
+    };
+    Cow::Owned(extended.into())
+}
+
+/// 返回可直接交给文件API的路径
+#[cfg(not(windows))]
+pub fn extended_length_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_path_unchanged() {
+        let path = Path::new("data/run.mzML");
+        assert!(matches!(extended_length_path(path), Cow::Borrowed(p) if p == path));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_long_path_gets_extended_prefix() {
+        let long = format!(r"C:\data\{}\run.mzML", "a".repeat(300));
+        let extended = extended_length_path(Path::new(&long));
+        assert_eq!(extended.as_os_str(), std::ffi::OsStr::new(&format!(r"\\?\{}", long)));
+
+        let unc = format!(r"\\server\share\{}\run.mzML", "b".repeat(300));
+        let extended = extended_length_path(Path::new(&unc));
+        assert_eq!(extended.as_os_str(), std::ffi::OsStr::new(&format!(r"\\?\UNC\server\share\{}\run.mzML", "b".repeat(300))));
+
+        // 已带前缀的路径不重复添加
+        let prefixed = format!(r"\\?\{}", long);
+        assert_eq!(extended_length_path(Path::new(&prefixed)).as_os_str(), std::ffi::OsStr::new(&prefixed));
+    }
+}
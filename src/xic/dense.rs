@@ -0,0 +1,137 @@
+//! 共享保留时间网格上的稠密XIC
+//!
+//! [`XICSExtractor`](crate::xic::XICSExtractor)只输出有峰的扫描点；比较两条色谱曲线时需要
+//! 在同一组保留时间上取值，缺失的点记为0。这里提供稠密XIC提取、重采样到共享网格
+//! 以及Pearson相关系数。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::utils::helpers::find_mz_in_tolerance;
+
+/// 按保留时间升序排列的采样网格
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RtGrid {
+    rts: Vec<RetentionTime>,
+}
+
+impl RtGrid {
+    /// 由任意顺序的保留时间创建网格
+    pub fn new(mut rts: Vec<RetentionTime>) -> Self {
+        rts.sort_by(|a, b| a.total_cmp(b));
+        Self { rts }
+    }
+
+    /// 以谱图的保留时间为网格点
+    pub fn from_spectra<'a>(spectra: impl IntoIterator<Item = &'a Spectrum>) -> Self {
+        Self::new(spectra.into_iter().map(|spectrum| spectrum.scan.retention_time).collect())
+    }
+
+    /// 网格点
+    pub fn rts(&self) -> &[RetentionTime] {
+        &self.rts
+    }
+
+    /// 网格点数
+    pub fn len(&self) -> usize {
+        self.rts.len()
+    }
+
+    /// 网格是否为空
+    pub fn is_empty(&self) -> bool {
+        self.rts.is_empty()
+    }
+
+    /// 网格覆盖的保留时间范围
+    pub fn range(&self) -> Option<(RetentionTime, RetentionTime)> {
+        Some((*self.rts.first()?, *self.rts.last()?))
+    }
+
+    /// 将(`rts`, `values`)描述的曲线线性插值到网格上，超出曲线范围的网格点为0
+    ///
+    /// `rts`需按升序排列。
+    pub fn resample(&self, rts: &[RetentionTime], values: &[f64]) -> Vec<f64> {
+        self.rts
+            .iter()
+            .map(|&rt| {
+                let upper = rts.partition_point(|&x| x < rt);
+                if upper < rts.len() && rts[upper] == rt {
+                    return values[upper];
+                }
+                if upper == 0 || upper == rts.len() {
+                    return 0.0;
+                }
+                let (x0, x1) = (rts[upper - 1], rts[upper]);
+                let (y0, y1) = (values[upper - 1], values[upper]);
+                y0 + (y1 - y0) * (rt - x0) / (x1 - x0)
+            })
+            .collect()
+    }
+}
+
+/// 依次在每张谱图中累加`mz`容差内的峰强度，没有峰的谱图记为0
+pub fn dense_xic<'a>(
+    spectra: impl IntoIterator<Item = &'a Spectrum>,
+    mz: f64,
+    tolerance: impl MzTolerance,
+) -> Vec<f64> {
+    let tolerance = tolerance.tolerance_at_mz(mz);
+    spectra
+        .into_iter()
+        .map(|spectrum| {
+            find_mz_in_tolerance(spectrum.mz_slice(), mz, tolerance)
+                .into_iter()
+                .map(|index| spectrum.intensity_slice()[index])
+                .sum()
+        })
+        .collect()
+}
+
+/// Pearson相关系数；长度不一致、少于3个点或任一曲线为常数时返回0
+pub fn pearson(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() != b.len() || a.len() < 3 {
+        return 0.0;
+    }
+    let n = a.len() as f64;
+    let mean_a = a.iter().sum::<f64>() / n;
+    let mean_b = b.iter().sum::<f64>() / n;
+
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (&x, &y) in a.iter().zip(b) {
+        covariance += (x - mean_a) * (y - mean_b);
+        variance_a += (x - mean_a) * (x - mean_a);
+        variance_b += (y - mean_b) * (y - mean_b);
+    }
+    if variance_a <= 0.0 || variance_b <= 0.0 {
+        return 0.0;
+    }
+    covariance / (variance_a * variance_b).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resample_and_pearson() {
+        let grid = RtGrid::new(vec![3.0, 1.0, 2.0, 5.0]);
+        assert_eq!(grid.rts(), &[1.0, 2.0, 3.0, 5.0]);
+        assert_eq!(grid.resample(&[1.5, 2.0, 4.0], &[10.0, 20.0, 40.0]), vec![0.0, 20.0, 30.0, 0.0]);
+
+        assert!((pearson(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0]) - 1.0).abs() < 1e-12);
+        assert!((pearson(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]) + 1.0).abs() < 1e-12);
+        assert_eq!(pearson(&[1.0, 2.0, 3.0], &[5.0, 5.0, 5.0]), 0.0);
+    }
+
+    #[test]
+    fn test_dense_xic_fills_missing_scans() {
+        let spectra: Vec<Spectrum> = [vec![(400.0, 10.0)], vec![], vec![(400.001, 5.0), (400.002, 1.0)]]
+            .into_iter()
+            .map(|peaks| {
+                let mut spectrum = Spectrum::ms1().unwrap();
+                spectrum.add_peaks(peaks).unwrap();
+                spectrum
+            })
+            .collect();
+        assert_eq!(dense_xic(&spectra, 400.0, Tolerance::PPM(10.0)), vec![10.0, 0.0, 6.0]);
+    }
+}
@@ -2,30 +2,54 @@
 //!
 //! 提供高性能的XIC（提取离子色谱图）提取功能
 
-use crate::core::{Spectrum, BinnedSpectraIndex, CoreResult};
+use crate::core::isotopes::{averagine_pattern, predict_pattern};
+use crate::core::noise::intensity_noise;
+use crate::core::spectrum::{BinnedSpectraIndex, SharedSpectra, SpectraStore, Spectrum};
 use crate::core::types::*;
+use crate::dia::windows::{WindowMap, WindowPooling};
 use crate::utils::helpers::*;
-use crate::xic::result::{XICResult, PolymerInfo, FragmentIon};
+use crate::utils::mass::{default_adduct, neutral_mass, ISOTOPE_SPACING};
+use crate::xic::result::{XICResult, PeakCombination, PolymerInfo, FragmentIon};
+use crate::xic::srm::extract_transitions;
+use log::warn;
+use rayon::prelude::*;
 
+#[cfg(feature = "python")]
+use crate::core::ms_object::MSObject;
+#[cfg(feature = "python")]
+use crate::parsers::mzml::MZMLObject;
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
 #[cfg(feature = "python")]
-use pyo3::types::PyAny;
+use pyo3::types::PyDict;
 
 /// XIC提取器
-#[cfg(feature = "python")]
-#[pyclass]
+///
+/// 谱图存放在共享的[`SharedSpectra`]中，MS1和MS2索引都建立在同一份数据上；
+/// 用[`XICSExtractor::share_spectra`]和[`XICSExtractor::from_shared`]可以让
+/// 多个提取器（如每个线程或每个DIA窗口一个）共用一次加载的运行数据。
+#[cfg_attr(feature = "python", pyclass)]
 pub struct XICSExtractor {
-    /// MS1谱图数据
-    ms1_spectra: Vec<Spectrum>,
-    /// MS2谱图数据
-    ms2_spectra: Vec<Spectrum>,
+    /// 共享的谱图数据
+    spectra: SharedSpectra,
     /// MS1谱图索引用于快速搜索
     ms1_index: BinnedSpectraIndex,
     /// MS2谱图索引用于快速搜索
     ms2_index: BinnedSpectraIndex,
+    /// MS2谱图按DIA分离窗口的分组
+    window_map: WindowMap,
+    /// MS1谱图按保留时间排序的(保留时间, 谱图下标)，相同保留时间保持加载顺序
+    ms1_by_rt: Vec<(f64, usize)>,
+    /// 每张谱图在`ms1_by_rt`中的位置，非MS1谱图为`usize::MAX`
+    ms1_rt_rank: Vec<usize>,
     /// PPM容差
     ppm_tolerance: f64,
+    /// 随m/z变化的容差模型，设置后代替`ppm_tolerance`
+    tolerance_model: Option<ToleranceModel>,
+    /// 注入时间归一化的参考时间 (毫秒)，设置后提取时按注入时间归一化强度
+    injection_time_reference: Option<f64>,
+    /// 同一扫描中多个峰落在容差窗口内时的合并方式
+    peak_combination: PeakCombination,
     /// 是否已加载数据
     loaded: bool,
 }
@@ -34,68 +58,72 @@ impl XICSExtractor {
     /// 创建新的XIC提取器
     pub fn new(ppm_tolerance: f64) -> Self {
         Self {
-            ms1_spectra: Vec::new(),
-            ms2_spectra: Vec::new(),
+            spectra: SharedSpectra::default(),
             ms1_index: BinnedSpectraIndex::empty(),
             ms2_index: BinnedSpectraIndex::empty(),
+            window_map: WindowMap::default(),
+            ms1_by_rt: Vec::new(),
+            ms1_rt_rank: Vec::new(),
             ppm_tolerance,
+            tolerance_model: None,
+            injection_time_reference: None,
+            peak_combination: PeakCombination::default(),
             loaded: false,
         }
     }
 
     /// 从谱图列表创建XIC提取器
     pub fn from_spectra(spectra: Vec<Spectrum>, ppm_tolerance: f64, bin_size: f64) -> CoreResult<Self> {
-        let mut ms1_spectra = Vec::new();
-        let mut ms2_spectra = Vec::new();
-
-        // 分类谱图
-        for spectrum in spectra {
-            match spectrum.level {
-                1 => ms1_spectra.push(spectrum),
-                2 => ms2_spectra.push(spectrum),
-                _ => {} // 忽略其他级别
-            }
-        }
-
-        // 创建索引
-        let ms1_index = BinnedSpectraIndex::new(ms1_spectra.clone(), bin_size)?;
-        let ms2_index = BinnedSpectraIndex::new(ms2_spectra.clone(), bin_size)?;
+        Self::from_shared(SpectraStore::shared(spectra), ppm_tolerance, bin_size)
+    }
 
-        Ok(Self {
-            ms1_spectra,
-            ms2_spectra,
-            ms1_index,
-            ms2_index,
-            ppm_tolerance,
-            loaded: true,
-        })
+    /// 在已加载的共享谱图数据上创建XIC提取器，不复制谱图
+    pub fn from_shared(spectra: SharedSpectra, ppm_tolerance: f64, bin_size: f64) -> CoreResult<Self> {
+        let mut extractor = Self::new(ppm_tolerance);
+        extractor.load_shared(spectra, bin_size)?;
+        Ok(extractor)
     }
 
     /// 加载谱图数据
     pub fn load_spectra(&mut self, spectra: Vec<Spectrum>, bin_size: f64) -> CoreResult<()> {
-        let mut ms1_spectra = Vec::new();
-        let mut ms2_spectra = Vec::new();
-
-        // 分类谱图
-        for spectrum in spectra {
-            match spectrum.level {
-                1 => ms1_spectra.push(spectrum),
-                2 => ms2_spectra.push(spectrum),
-                _ => {}
-            }
-        }
+        self.load_shared(SpectraStore::shared(spectra), bin_size)
+    }
 
-        // 创建索引
-        self.ms1_index = BinnedSpectraIndex::new(ms1_spectra.clone(), bin_size)?;
-        self.ms2_index = BinnedSpectraIndex::new(ms2_spectra.clone(), bin_size)?;
+    /// 加载共享的谱图数据，按MS级别建立索引（其他级别被忽略）
+    pub fn load_shared(&mut self, spectra: SharedSpectra, bin_size: f64) -> CoreResult<()> {
+        self.ms1_index = BinnedSpectraIndex::from_shared(spectra.clone(), spectra.indices_of_level(1), bin_size)?;
+        self.ms2_index = BinnedSpectraIndex::from_shared(spectra.clone(), spectra.indices_of_level(2), bin_size)?;
+        self.window_map = WindowMap::from_spectra(&spectra);
 
-        self.ms1_spectra = ms1_spectra;
-        self.ms2_spectra = ms2_spectra;
+        let metadata = spectra.metadata();
+        self.ms1_by_rt = spectra
+            .rt_order()
+            .iter()
+            .filter(|&&index| metadata[index].level == 1)
+            .map(|&index| (metadata[index].retention_time, index))
+            .collect();
+        self.ms1_rt_rank = vec![usize::MAX; spectra.len()];
+        for (rank, &(_, index)) in self.ms1_by_rt.iter().enumerate() {
+            self.ms1_rt_rank[index] = rank;
+        }
+        self.spectra = spectra;
         self.loaded = true;
 
         Ok(())
     }
 
+    /// 共享的谱图数据，可用于在同一份数据上创建其他提取器或索引
+    pub fn share_spectra(&self) -> SharedSpectra {
+        self.spectra.clone()
+    }
+
+    /// 保留时间在`rt_start`到`rt_end`（含两端）之间的MS1谱图在`ms1_by_rt`中的位置范围
+    fn ms1_rt_span(&self, rt_start: f64, rt_end: f64) -> std::ops::Range<usize> {
+        let start = self.ms1_by_rt.partition_point(|&(rt, _)| rt < rt_start);
+        let end = self.ms1_by_rt.partition_point(|&(rt, _)| rt <= rt_end);
+        start..end.max(start)
+    }
+
     /// 提取前体离子XIC
     pub fn extract_precursor_xics(&self, precursor: &PolymerInfo, num_isotopes: usize) -> CoreResult<Vec<XICResult>> {
         if !self.loaded {
@@ -116,7 +144,9 @@ impl XICSExtractor {
 
         // 提取同位素峰XIC
         for isotope in 1..num_isotopes {
-            let isotope_mz = precursor.mz + (isotope as f64) / precursor.charge as f64;
+            // 负离子模式下电荷为负，同位素峰仍向高m/z方向排列
+            let isotope_mz =
+                precursor.mz + ISOTOPE_SPACING * isotope as f64 / f64::from(precursor.charge.unsigned_abs().max(1));
             let isotope_result = self.extract_single_xic(
                 isotope_mz,
                 precursor.charge,
@@ -130,6 +160,39 @@ impl XICSExtractor {
         Ok(results)
     }
 
+    /// 提取前体离子的同位素XIC，并在每个保留时间点比较观测同位素强度与预测峰型
+    ///
+    /// 给定`formula`时由分子式预测峰型，否则按前体离子m/z和电荷换算的中性质量使用averagine模型；
+    /// 各同位素峰的m/z取预测峰型相对单同位素峰的偏移。
+    pub fn extract_precursor_xics_scored(
+        &self,
+        precursor: &PolymerInfo,
+        num_isotopes: usize,
+        formula: Option<&str>,
+    ) -> CoreResult<IsotopeXICs> {
+        if !self.loaded {
+            return Err(CoreError::EmptyPeakList);
+        }
+        let charge = precursor.charge;
+        let pattern = match formula {
+            Some(formula) => predict_pattern(formula, charge, num_isotopes)?,
+            None => averagine_pattern(neutral_mass(precursor.mz, charge, default_adduct(charge))?, charge, num_isotopes)?,
+        };
+
+        let mut xics = Vec::with_capacity(pattern.len());
+        for (isotope, &(mz, _)) in pattern.iter().enumerate() {
+            let label = match isotope {
+                0 => precursor.sequence.clone(),
+                _ => format!("{}[{}+{}]", precursor.sequence, isotope, charge),
+            };
+            let isotope_mz = precursor.mz + (mz - pattern[0].0);
+            xics.push(self.extract_single_xic(isotope_mz, charge, &label, precursor.rt_start, precursor.rt_stop)?);
+        }
+        let expected: Vec<f64> = pattern.iter().map(|&(_, intensity)| intensity).collect();
+        let (rt_array, scores) = isotope_scores(&xics, &expected);
+        Ok(IsotopeXICs { xics, expected, rt_array, scores })
+    }
+
     /// 提取碎片离子XIC
     pub fn extract_fragment_xics(&self, peptide_info: &PolymerInfo) -> CoreResult<Vec<XICResult>> {
         if !self.loaded {
@@ -153,58 +216,87 @@ impl XICSExtractor {
     }
 
     /// 提取单个XIC
+    ///
+    /// 通过MS1索引一次查询m/z窗口并按谱图分组，保留时间范围用按保留时间排序的视图二分确定。
+    /// 结果按保留时间排序，只包含有信号的扫描。
     pub fn extract_single_xic(&self, mz: f64, charge: i8, ion_type: &str, rt_start: f64, rt_end: f64) -> CoreResult<XICResult> {
         if !self.loaded {
             return Err(CoreError::EmptyPeakList);
         }
 
-        let tolerance = mz * self.ppm_tolerance * 1e-6;
-        let mz_range = (mz - tolerance, mz + tolerance);
-
-        // 提取MS1谱图数据
-        let mut rt_array = Vec::new();
-        let mut intensity_array = Vec::new();
-
-        for spectrum in &self.ms1_spectra {
-            let rt = spectrum.scan.retention_time;
-
-            // 检查保留时间范围
-            if rt < rt_start || rt > rt_end {
-                continue;
-            }
-
-            // 搜索匹配的峰
-            let matching_indices = find_peaks_in_tolerance(&spectrum.peaks, mz, tolerance);
+        let tolerance = self.tolerance_at_mz(mz);
+        let rt_span = self.ms1_rt_span(rt_start, rt_end);
+
+        // 通过MS1索引一次查出m/z窗口内的峰，只保留保留时间在范围内的谱图；
+        // 查询范围略放宽，再用与逐峰比较相同的条件判断，边界上的峰不受舍入影响
+        let slack = (mz.abs() + tolerance) * 4.0 * f64::EPSILON;
+        let mut hits: Vec<(usize, usize)> = self
+            .ms1_index
+            .search_range_locations((mz - tolerance - slack, mz + tolerance + slack))
+            .into_iter()
+            .filter(|&(index, peak)| (self.spectra[index].mz_slice()[peak] - mz).abs() <= tolerance)
+            .map(|(index, peak)| (self.ms1_rt_rank[index], peak))
+            .filter(|(rank, _)| rt_span.contains(rank))
+            .collect();
+        // 按保留时间排序，同一谱图内按峰的顺序合并
+        hits.sort_unstable();
+
+        let mut trace = TraceBuilder::default();
+        let mut matching = Vec::new();
+        for group in hits.chunk_by(|a, b| a.0 == b.0) {
+            let spectrum = &self.spectra[self.ms1_by_rt[group[0].0].1];
+            matching.clear();
+            matching.extend(group.iter().map(|&(_, peak)| peak));
+            self.add_point(&mut trace, spectrum, &matching, mz);
+        }
 
-            if !matching_indices.is_empty() {
-                rt_array.push(rt);
+        Ok(trace.finish(mz, ion_type, charge))
+    }
 
-                // 计算总强度
-                let total_intensity: f64 = matching_indices
-                    .iter()
-                    .map(|&idx| spectrum.peaks[idx].1)
-                    .sum();
+    /// 合并一张谱图中匹配的峰并追加为数据点
+    fn add_point(&self, trace: &mut TraceBuilder, spectrum: &Spectrum, matching: &[usize], target_mz: f64) {
+        if let Some((intensity, observed)) = self.peak_combination.combine(spectrum.mz_slice(), spectrum.intensity_slice(), matching, target_mz) {
+            trace.rt_array.push(spectrum.scan.retention_time);
+            trace.intensity_array.push(intensity / self.injection_time_divisor(spectrum));
+            trace.mz_observed_array.push(observed);
+        }
+    }
 
-                intensity_array.push(total_intensity);
-            }
+    /// 从DIA MS2扫描中提取碎片离子XIC
+    ///
+    /// 使用的MS2扫描由`precursor_mz`所在的分离窗口决定：[`WindowPooling::Assigned`]只用
+    /// 唯一分配的窗口，[`WindowPooling::AllContaining`]合并所有包含前体离子的窗口
+    /// （重叠窗口方案中采样点更密）。结果按保留时间排序，只包含有信号的扫描。
+    pub fn extract_dia_fragment_xic(
+        &self,
+        precursor_mz: f64,
+        fragment_mz: f64,
+        rt_start: f64,
+        rt_end: f64,
+        pooling: WindowPooling,
+    ) -> CoreResult<XICResult> {
+        if !self.loaded {
+            return Err(CoreError::EmptyPeakList);
         }
 
-        // 计算PPM误差
-        let ppm_error = if !rt_array.is_empty() {
-            // 简化计算，实际中可能需要更复杂的计算
-            self.ppm_tolerance
-        } else {
-            0.0
-        };
+        let tolerance = self.tolerance_at_mz(fragment_mz);
+        let mut spectra: Vec<&Spectrum> = self
+            .window_map
+            .windows_for_precursor(precursor_mz, pooling)
+            .into_iter()
+            .flat_map(|window| self.window_map.windows()[window].1.iter())
+            .map(|&index| &self.spectra[index])
+            .filter(|spectrum| (rt_start..=rt_end).contains(&spectrum.scan.retention_time))
+            .collect();
+        spectra.sort_by(|a, b| a.scan.retention_time.total_cmp(&b.scan.retention_time));
+
+        let mut trace = TraceBuilder::default();
+        for spectrum in spectra {
+            let matching = find_mz_in_tolerance(spectrum.mz_slice(), fragment_mz, tolerance);
+            self.add_point(&mut trace, spectrum, &matching, fragment_mz);
+        }
 
-        Ok(XICResult {
-            rt_array,
-            intensity_array,
-            mz,
-            ppm_error,
-            ion_type: ion_type.to_string(),
-            charge,
-        })
+        Ok(trace.finish(fragment_mz, "", 0))
     }
 
     /// 批量提取XIC
@@ -219,8 +311,36 @@ impl XICSExtractor {
         Ok(results)
     }
 
+    /// 用`num_threads`个工作线程并行批量提取XIC，0表示使用rayon的全局线程池
+    ///
+    /// 各线程只读共享的谱图和索引，结果按`targets`的顺序返回，与[`extract_batch_xics`](Self::extract_batch_xics)一致。
+    pub fn extract_batch_xics_parallel(
+        &self,
+        targets: &[(f64, i8, &str)],
+        rt_start: f64,
+        rt_end: f64,
+        num_threads: usize,
+    ) -> CoreResult<Vec<XICResult>> {
+        let extract = || -> CoreResult<Vec<XICResult>> {
+            targets
+                .par_iter()
+                .map(|&(mz, charge, ion_type)| self.extract_single_xic(mz, charge, ion_type, rt_start, rt_end))
+                .collect()
+        };
+        match num_threads {
+            0 => extract(),
+            _ => match rayon::ThreadPoolBuilder::new().num_threads(num_threads).build() {
+                Ok(pool) => pool.install(extract),
+                Err(e) => {
+                    warn!("Failed to build a {}-thread pool ({}), using the global pool", num_threads, e);
+                    extract()
+                }
+            },
+        }
+    }
+
     /// 按保留时间范围过滤谱图
-    pub fn filter_spectra_by_rt(&self, spectra: &[Spectrum], rt_start: f64, rt_end: f64) -> Vec<&Spectrum> {
+    pub fn filter_spectra_by_rt<'a>(&self, spectra: &'a [Spectrum], rt_start: f64, rt_end: f64) -> Vec<&'a Spectrum> {
         spectra
             .iter()
             .filter(|spectrum| {
@@ -231,20 +351,22 @@ impl XICSExtractor {
     }
 
     /// 计算XIC质量评估指标
+    ///
+    /// 噪声水平由轨迹自身按[`intensity_noise`]估计（补零的点不计入）。
     pub fn evaluate_xic_quality(&self, xic: &XICResult) -> XICQualityMetrics {
         if xic.rt_array.is_empty() {
             return XICQualityMetrics::default();
         }
 
         let points = xic.rt_array.len();
-        let max_intensity = xic.intensity_array.iter().fold(0.0, |a, &b| a.max(b));
+        let max_intensity = xic.intensity_array.iter().fold(0.0_f64, |a, &b| a.max(b));
         let total_signal = xic.intensity_array.iter().sum::<f64>();
         let mean_intensity = total_signal / points as f64;
 
-        // 计算信噪比（简化版本）
-        let noise_threshold = mean_intensity * 0.1; // 假设噪声为平均强度的10%
-        let signal_points = xic.intensity_array.iter().filter(|&&intensity| intensity > noise_threshold).count();
-        let signal_to_noise = if noise_threshold > 0.0 { max_intensity / noise_threshold } else { 0.0 };
+        // 由轨迹自身估计噪声水平，高于噪声的点记为信号点
+        let noise = intensity_noise(&xic.intensity_array).unwrap_or(0.0);
+        let signal_points = xic.intensity_array.iter().filter(|&&intensity| intensity > noise).count();
+        let signal_to_noise = if noise > 0.0 { max_intensity / noise } else { 0.0 };
 
         // 计算峰对称性（简化版本）
         let peak_idx = xic.intensity_array.iter()
@@ -272,12 +394,12 @@ impl XICSExtractor {
 
     /// 获取MS1谱图数量
     pub fn ms1_count(&self) -> usize {
-        self.ms1_spectra.len()
+        self.ms1_index.spectrum_count()
     }
 
     /// 获取MS2谱图数量
     pub fn ms2_count(&self) -> usize {
-        self.ms2_spectra.len()
+        self.ms2_index.spectrum_count()
     }
 
     /// 检查是否已加载数据
@@ -290,9 +412,469 @@ impl XICSExtractor {
         self.ppm_tolerance
     }
 
-    /// 设置PPM容差
+    /// 设置PPM容差，同时清除容差模型
     pub fn set_ppm_tolerance(&mut self, ppm_tolerance: f64) {
         self.ppm_tolerance = ppm_tolerance;
+        self.tolerance_model = None;
+    }
+
+    /// 容差模型（未设置时为None，使用`ppm_tolerance`）
+    pub fn tolerance_model(&self) -> Option<&ToleranceModel> {
+        self.tolerance_model.as_ref()
+    }
+
+    /// 设置随m/z变化的容差模型，代替固定的PPM容差
+    pub fn set_tolerance_model(&mut self, model: ToleranceModel) {
+        self.tolerance_model = Some(model);
+    }
+
+    /// 设置容差模型并返回自身
+    pub fn with_tolerance_model(mut self, model: ToleranceModel) -> Self {
+        self.set_tolerance_model(model);
+        self
+    }
+
+    /// 同一扫描中多个峰落在容差窗口内时的合并方式
+    pub fn peak_combination(&self) -> PeakCombination {
+        self.peak_combination
+    }
+
+    /// 设置同一扫描中多个匹配峰的合并方式
+    pub fn set_peak_combination(&mut self, peak_combination: PeakCombination) {
+        self.peak_combination = peak_combination;
+    }
+
+    /// 设置匹配峰的合并方式并返回自身
+    pub fn with_peak_combination(mut self, peak_combination: PeakCombination) -> Self {
+        self.set_peak_combination(peak_combination);
+        self
+    }
+
+    /// 注入时间归一化的参考时间 (毫秒)，None表示不归一化
+    pub fn injection_time_reference(&self) -> Option<f64> {
+        self.injection_time_reference
+    }
+
+    /// 设置提取时是否按注入时间归一化强度（不修改谱图本身）
+    ///
+    /// 缺少注入时间或已经归一化过的谱图使用原始强度。
+    pub fn set_injection_time_normalization(&mut self, reference_ms: Option<f64>) {
+        self.injection_time_reference = reference_ms;
+    }
+
+    /// 提取时对某张谱图强度使用的除数
+    fn injection_time_divisor(&self, spectrum: &Spectrum) -> f64 {
+        match self.injection_time_reference {
+            Some(reference_ms) if spectrum.applied_injection_time_factor().is_none() => {
+                spectrum.injection_time_factor(reference_ms).unwrap_or(1.0)
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// 给定m/z的提取容差 (Da)
+    fn tolerance_at_mz(&self, mz: f64) -> f64 {
+        match &self.tolerance_model {
+            Some(model) => model.tolerance_at_mz(mz),
+            None => mz * self.ppm_tolerance * 1e-6,
+        }
+    }
+}
+
+/// 由谱图汇总得到的色谱图（TIC或BPC）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IonChromatogram {
+    /// 保留时间数组，升序
+    pub rt_array: Vec<f64>,
+    /// 强度数组
+    pub intensity_array: Vec<f64>,
+    /// 因保留时间缺失（为0或非有限值）被跳过的谱图数
+    pub skipped: usize,
+}
+
+/// 由指定MS级别的谱图构建总离子流色谱图（TIC）
+///
+/// `rt_range`为闭区间，None表示不限；保留时间缺失的谱图被跳过并计入`skipped`。
+pub fn build_tic<'a>(
+    spectra: impl IntoIterator<Item = &'a Spectrum>,
+    ms_level: MSLevel,
+    rt_range: Option<(f64, f64)>,
+) -> IonChromatogram {
+    build_chromatogram(spectra, ms_level, rt_range, Spectrum::total_ion_current)
+}
+
+/// 由指定MS级别的谱图构建基峰色谱图（BPC），没有峰的谱图强度为0
+///
+/// 参数同[`build_tic`]。
+pub fn build_bpc<'a>(
+    spectra: impl IntoIterator<Item = &'a Spectrum>,
+    ms_level: MSLevel,
+    rt_range: Option<(f64, f64)>,
+) -> IonChromatogram {
+    build_chromatogram(spectra, ms_level, rt_range, |spectrum| {
+        spectrum.base_peak().map_or(0.0, |(_, intensity)| intensity)
+    })
+}
+
+fn build_chromatogram<'a>(
+    spectra: impl IntoIterator<Item = &'a Spectrum>,
+    ms_level: MSLevel,
+    rt_range: Option<(f64, f64)>,
+    intensity: impl Fn(&Spectrum) -> f64,
+) -> IonChromatogram {
+    let mut points = Vec::new();
+    let mut skipped = 0;
+    for spectrum in spectra.into_iter().filter(|spectrum| spectrum.level == ms_level) {
+        let rt = spectrum.scan.retention_time;
+        if !(rt.is_finite() && rt != 0.0) {
+            skipped += 1;
+            continue;
+        }
+        if rt_range.is_none_or(|(start, end)| rt >= start && rt <= end) {
+            points.push((rt, intensity(spectrum)));
+        }
+    }
+    if skipped > 0 {
+        warn!("Skipped {} MS{} spectra without a retention time", skipped, ms_level);
+    }
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let (rt_array, intensity_array) = points.into_iter().unzip();
+    IonChromatogram { rt_array, intensity_array, skipped }
+}
+
+/// 对Python传入的MSObject列表或MZMLObject中的谱图执行`f`，不复制谱图
+#[cfg(feature = "python")]
+pub(crate) fn with_py_spectra<R>(spectra: &Bound<'_, PyAny>, f: impl FnOnce(&mut dyn Iterator<Item = &Spectrum>) -> R) -> PyResult<R> {
+    if let Ok(object) = spectra.downcast::<MZMLObject>() {
+        let object = object.borrow();
+        return Ok(f(&mut object.spectra.iter()));
+    }
+    let ms_objects: Vec<PyRef<'_, MSObject>> = spectra.extract()?;
+    Ok(f(&mut ms_objects.iter().map(|ms_object| &ms_object.spectrum)))
+}
+
+/// Python传入的MSObject列表或MZMLObject对应的共享谱图数据
+///
+/// MZMLObject只增加引用计数，MSObject列表复制为新的存储。
+#[cfg(feature = "python")]
+pub(crate) fn py_shared_spectra(spectra: &Bound<'_, PyAny>) -> PyResult<SharedSpectra> {
+    if let Ok(object) = spectra.downcast::<MZMLObject>() {
+        return Ok(object.borrow().spectra.clone());
+    }
+    let ms_objects: Vec<PyRef<'_, MSObject>> = spectra.extract()?;
+    Ok(SpectraStore::shared(ms_objects.iter().map(|ms_object| ms_object.spectrum.clone()).collect()))
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl XICSExtractor {
+    /// 创建提取器；`spectra`为MSObject列表或MZMLObject，为None时创建未加载数据的提取器
+    ///
+    /// 传入MZMLObject时与它共用同一份谱图数据。给定`tolerance`（ToleranceModel）时代替`ppm_tolerance`。
+    #[new]
+    #[pyo3(signature = (ppm_tolerance=10.0, tolerance=None, spectra=None, bin_size=1.0))]
+    fn py_new(
+        ppm_tolerance: f64,
+        tolerance: Option<PyToleranceModel>,
+        spectra: Option<&Bound<'_, PyAny>>,
+        bin_size: f64,
+    ) -> PyResult<Self> {
+        let mut extractor = Self::new(ppm_tolerance);
+        if let Some(spectra) = spectra {
+            extractor.py_load_spectra(spectra, bin_size)?;
+        }
+        if let Some(tolerance) = tolerance {
+            extractor.set_tolerance_model(tolerance.model);
+        }
+        Ok(extractor)
+    }
+
+    /// 在MZMLObject的谱图上创建提取器，与它共用同一份谱图数据
+    ///
+    /// 给定`tolerance`（ToleranceModel）时代替`ppm_tolerance`。
+    #[staticmethod]
+    #[pyo3(signature = (mzml_object, ppm_tolerance=10.0, bin_size=1.0, tolerance=None))]
+    fn from_mzml(
+        py: Python<'_>,
+        mzml_object: PyRef<'_, MZMLObject>,
+        ppm_tolerance: f64,
+        bin_size: f64,
+        tolerance: Option<PyToleranceModel>,
+    ) -> PyResult<Self> {
+        let spectra = mzml_object.spectra.clone();
+        let mut extractor = py
+            .allow_threads(|| Self::from_shared(spectra, ppm_tolerance, bin_size))
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        if let Some(tolerance) = tolerance {
+            extractor.set_tolerance_model(tolerance.model);
+        }
+        Ok(extractor)
+    }
+
+    /// 加载谱图并重建索引，`spectra`为MSObject列表或MZMLObject
+    #[pyo3(name = "load_spectra", signature = (spectra, bin_size=1.0))]
+    fn py_load_spectra(&mut self, spectra: &Bound<'_, PyAny>, bin_size: f64) -> PyResult<()> {
+        let result = if let Ok(object) = spectra.downcast::<MZMLObject>() {
+            self.load_shared(object.borrow().spectra.clone(), bin_size)
+        } else {
+            let ms_objects: Vec<MSObject> = spectra.extract()?;
+            self.load_spectra(ms_objects.into_iter().map(|ms_object| ms_object.spectrum).collect(), bin_size)
+        };
+        result.map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// 是否已加载数据
+    #[getter(is_loaded)]
+    fn py_is_loaded(&self) -> bool {
+        self.is_loaded()
+    }
+
+    /// MS1谱图数量
+    #[getter(ms1_count)]
+    fn py_ms1_count(&self) -> usize {
+        self.ms1_count()
+    }
+
+    /// MS2谱图数量
+    #[getter(ms2_count)]
+    fn py_ms2_count(&self) -> usize {
+        self.ms2_count()
+    }
+
+    /// PPM容差
+    #[getter(ppm_tolerance)]
+    fn py_ppm_tolerance(&self) -> f64 {
+        self.ppm_tolerance
+    }
+
+    /// 容差模型，未设置时为None
+    #[getter(tolerance)]
+    fn py_tolerance(&self) -> Option<PyToleranceModel> {
+        self.tolerance_model.clone().map(|model| PyToleranceModel { model })
+    }
+
+    /// 设置容差模型，None恢复为固定PPM容差
+    #[setter(tolerance)]
+    fn py_set_tolerance(&mut self, tolerance: Option<PyToleranceModel>) {
+        self.tolerance_model = tolerance.map(|tolerance| tolerance.model);
+    }
+
+    /// 注入时间归一化的参考时间 (毫秒)，None表示提取时不归一化
+    #[getter(normalize_injection_time)]
+    fn py_injection_time_reference(&self) -> Option<f64> {
+        self.injection_time_reference
+    }
+
+    /// 设置提取时按注入时间归一化强度的参考时间，None关闭
+    #[setter(normalize_injection_time)]
+    fn py_set_injection_time_reference(&mut self, reference_ms: Option<f64>) {
+        self.set_injection_time_normalization(reference_ms);
+    }
+
+    /// 同一扫描中多个峰落在容差窗口内时的合并方式："sum"或"nearest"
+    #[getter(peak_combination)]
+    fn py_peak_combination(&self) -> &'static str {
+        self.peak_combination.name()
+    }
+
+    /// 设置匹配峰的合并方式："sum"（强度相加）或"nearest"（取m/z最接近的峰）
+    #[setter(peak_combination)]
+    fn py_set_peak_combination(&mut self, name: &str) -> PyResult<()> {
+        let peak_combination = PeakCombination::from_name(name).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!("Unknown peak combination '{}', expected 'sum' or 'nearest'", name))
+        })?;
+        self.set_peak_combination(peak_combination);
+        Ok(())
+    }
+
+    /// 由MSObject列表或MZMLObject构建TIC，返回(保留时间列表, 强度列表, 跳过的谱图数)
+    #[staticmethod]
+    #[pyo3(name = "build_tic", signature = (spectra, ms_level=1, rt_range=None))]
+    fn py_build_tic(spectra: &Bound<'_, PyAny>, ms_level: MSLevel, rt_range: Option<(f64, f64)>) -> PyResult<(Vec<f64>, Vec<f64>, usize)> {
+        let chromatogram = with_py_spectra(spectra, |spectra| build_tic(spectra, ms_level, rt_range))?;
+        Ok((chromatogram.rt_array, chromatogram.intensity_array, chromatogram.skipped))
+    }
+
+    /// 由MSObject列表或MZMLObject构建BPC，返回(保留时间列表, 强度列表, 跳过的谱图数)
+    #[staticmethod]
+    #[pyo3(name = "build_bpc", signature = (spectra, ms_level=1, rt_range=None))]
+    fn py_build_bpc(spectra: &Bound<'_, PyAny>, ms_level: MSLevel, rt_range: Option<(f64, f64)>) -> PyResult<(Vec<f64>, Vec<f64>, usize)> {
+        let chromatogram = with_py_spectra(spectra, |spectra| build_bpc(spectra, ms_level, rt_range))?;
+        Ok((chromatogram.rt_array, chromatogram.intensity_array, chromatogram.skipped))
+    }
+
+    /// 提取单个m/z的XIC，返回(保留时间列表, 强度列表)
+    #[pyo3(signature = (mz, rt_start=0.0, rt_end=f64::INFINITY))]
+    fn extract_xic(&self, mz: f64, rt_start: f64, rt_end: f64) -> PyResult<(Vec<f64>, Vec<f64>)> {
+        let result = self
+            .extract_single_xic(mz, 0, "", rt_start, rt_end)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok((result.rt_array, result.intensity_array))
+    }
+
+    /// 提取单个m/z的XIC，`charge`和`ion_type`原样记录在结果中
+    #[pyo3(name = "extract_single_xic", signature = (mz, charge=0, ion_type="", rt_start=0.0, rt_end=f64::INFINITY))]
+    fn py_extract_single_xic(&self, mz: f64, charge: i8, ion_type: &str, rt_start: f64, rt_end: f64) -> PyResult<XICResult> {
+        self.extract_single_xic(mz, charge, ion_type, rt_start, rt_end)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// 批量提取XIC，`targets`为(m/z, 电荷, 离子类型)列表
+    ///
+    /// `parallel=True`时释放GIL并用`num_threads`个线程并行提取（0表示全部CPU），结果顺序不变。
+    #[pyo3(
+        name = "extract_batch_xics",
+        signature = (targets, rt_start=0.0, rt_end=f64::INFINITY, parallel=false, num_threads=0)
+    )]
+    fn py_extract_batch_xics(
+        &self,
+        py: Python<'_>,
+        targets: Vec<(f64, i8, String)>,
+        rt_start: f64,
+        rt_end: f64,
+        parallel: bool,
+        num_threads: usize,
+    ) -> PyResult<Vec<XICResult>> {
+        let targets: Vec<(f64, i8, &str)> = targets
+            .iter()
+            .map(|(mz, charge, ion_type)| (*mz, *charge, ion_type.as_str()))
+            .collect();
+        let results = if parallel {
+            py.allow_threads(|| self.extract_batch_xics_parallel(&targets, rt_start, rt_end, num_threads))
+        } else {
+            self.extract_batch_xics(&targets, rt_start, rt_end)
+        };
+        results.map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// 提取前体离子及其同位素峰的XIC
+    ///
+    /// `precursor`需有sequence、modified_sequence、charge、mz、rt、rt_start、rt_stop属性。
+    #[pyo3(name = "extract_precursor_xics", signature = (precursor, num_isotopes=3))]
+    fn py_extract_precursor_xics(&self, precursor: &Bound<'_, PyAny>, num_isotopes: usize) -> PyResult<Vec<XICResult>> {
+        let precursor = PolymerInfo::from_python(precursor)?;
+        self.extract_precursor_xics(&precursor, num_isotopes)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// 提取前体离子的同位素XIC并逐点与预测峰型比较
+    ///
+    /// 返回字典：xics（XICResult列表）、expected（预测相对强度）、rt_array和scores（各时间点的归一化点积）。
+    /// 给定`formula`时由分子式预测峰型，否则使用averagine模型。
+    #[pyo3(name = "extract_precursor_xics_scored", signature = (precursor, num_isotopes=3, formula=None))]
+    fn py_extract_precursor_xics_scored(
+        &self,
+        py: Python,
+        precursor: &Bound<'_, PyAny>,
+        num_isotopes: usize,
+        formula: Option<&str>,
+    ) -> PyResult<Py<PyDict>> {
+        let precursor = PolymerInfo::from_python(precursor)?;
+        let scored = self
+            .extract_precursor_xics_scored(&precursor, num_isotopes, formula)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let dict = PyDict::new(py);
+        dict.set_item("xics", scored.xics)?;
+        dict.set_item("expected", scored.expected)?;
+        dict.set_item("rt_array", scored.rt_array)?;
+        dict.set_item("scores", scored.scores)?;
+        Ok(dict.unbind())
+    }
+
+    /// 提取碎片离子的XIC，碎片取自`peptide.fragment_ions`（带ion_type、charge、mz属性）
+    #[pyo3(name = "extract_fragment_xics")]
+    fn py_extract_fragment_xics(&self, peptide: &Bound<'_, PyAny>) -> PyResult<Vec<XICResult>> {
+        let peptide = PolymerInfo::from_python(peptide)?;
+        self.extract_fragment_xics(&peptide)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// 从DIA MS2扫描提取碎片离子XIC，返回(保留时间列表, 强度列表)
+    ///
+    /// `pool_windows=True`时合并所有包含前体离子的窗口，否则只用中心最近的窗口。
+    #[pyo3(name = "extract_dia_fragment_xic", signature = (precursor_mz, fragment_mz, rt_start=0.0, rt_end=f64::INFINITY, pool_windows=false))]
+    fn py_extract_dia_fragment_xic(
+        &self,
+        precursor_mz: f64,
+        fragment_mz: f64,
+        rt_start: f64,
+        rt_end: f64,
+        pool_windows: bool,
+    ) -> PyResult<(Vec<f64>, Vec<f64>)> {
+        let pooling = if pool_windows { WindowPooling::AllContaining } else { WindowPooling::Assigned };
+        let result = self
+            .extract_dia_fragment_xic(precursor_mz, fragment_mz, rt_start, rt_end, pooling)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok((result.rt_array, result.intensity_array))
+    }
+
+    /// 从已加载的MS2扫描提取SRM/MRM跃迁轨迹，`transitions`为(前体离子m/z, 产物离子m/z)列表
+    ///
+    /// 前体离子按扫描的分离窗口匹配（没有记录窗口时用`ppm`容差），产物离子用`ppm`容差。
+    #[pyo3(name = "extract_transitions", signature = (transitions, ppm=20.0))]
+    fn py_extract_transitions(&self, py: Python<'_>, transitions: Vec<(f64, f64)>, ppm: f64) -> PyResult<Vec<XICResult>> {
+        if !self.loaded {
+            return Err(pyo3::exceptions::PyValueError::new_err("No spectra loaded"));
+        }
+        let tolerance = Tolerance::PPM(ppm);
+        Ok(py.allow_threads(|| extract_transitions(&self.spectra[..], &transitions, tolerance, tolerance)))
+    }
+}
+
+/// 前体离子各同位素峰的XIC及其与预测峰型的相似度
+#[derive(Debug, Clone, Default)]
+pub struct IsotopeXICs {
+    /// 单同位素峰及各同位素峰的XIC
+    pub xics: Vec<XICResult>,
+    /// 预测的相对强度（最高峰为1），与`xics`一一对应
+    pub expected: Vec<f64>,
+    /// 任一同位素峰有信号的保留时间，升序
+    pub rt_array: Vec<f64>,
+    /// 各保留时间处观测同位素强度与预测相对强度的归一化点积，取值0~1
+    pub scores: Vec<f64>,
+}
+
+/// 按保留时间对齐各同位素XIC，计算每个时间点的观测强度向量与`expected`的归一化点积
+///
+/// 某个同位素在该时间点没有信号时强度按0计。
+fn isotope_scores(xics: &[XICResult], expected: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    let mut points: Vec<(f64, usize, f64)> = xics
+        .iter()
+        .enumerate()
+        .flat_map(|(isotope, xic)| {
+            xic.rt_array.iter().zip(&xic.intensity_array).map(move |(&rt, &intensity)| (rt, isotope, intensity))
+        })
+        .collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    let expected_norm = expected.iter().map(|e| e * e).sum::<f64>().sqrt();
+    let mut rt_array = Vec::new();
+    let mut scores = Vec::new();
+    for group in points.chunk_by(|a, b| a.0 == b.0) {
+        let mut observed = vec![0.0; expected.len()];
+        for &(_, isotope, intensity) in group {
+            observed[isotope] += intensity;
+        }
+        let observed_norm = observed.iter().map(|o| o * o).sum::<f64>().sqrt();
+        let dot: f64 = observed.iter().zip(expected).map(|(o, e)| o * e).sum();
+        rt_array.push(group[0].0);
+        scores.push(if observed_norm > 0.0 && expected_norm > 0.0 { dot / (observed_norm * expected_norm) } else { 0.0 });
+    }
+    (rt_array, scores)
+}
+
+/// 逐点构建XIC的数据数组
+#[derive(Default)]
+struct TraceBuilder {
+    rt_array: Vec<f64>,
+    intensity_array: Vec<f64>,
+    mz_observed_array: Vec<f64>,
+}
+
+impl TraceBuilder {
+    fn finish(self, mz: f64, ion_type: &str, charge: i8) -> XICResult {
+        XICResult::new(self.rt_array, self.intensity_array, self.mz_observed_array, mz, ion_type, charge)
     }
 }
 
@@ -329,9 +911,10 @@ impl Default for XICQualityMetrics {
     }
 }
 
+#[cfg(feature = "python")]
 impl PolymerInfo {
     /// 从Python对象创建PolymerInfo
-    pub fn from_python(obj: &PyAny) -> PyResult<Self> {
+    pub fn from_python(obj: &Bound<'_, PyAny>) -> PyResult<Self> {
         let sequence = obj.getattr("sequence")?.extract::<String>()?;
         let modified_sequence = obj.getattr("modified_sequence")?.extract::<String>()?;
         let charge = obj.getattr("charge")?.extract::<i8>()?;
@@ -343,8 +926,9 @@ impl PolymerInfo {
         // 解析碎片离子
         let mut fragment_ions = Vec::new();
         if let Ok(fragment_list) = obj.getattr("fragment_ions") {
-            if let Ok(fragment_iter) = fragment_list.iter() {
+            if let Ok(fragment_iter) = fragment_list.try_iter() {
                 for fragment in fragment_iter {
+                    let fragment = fragment?;
                     let ion_type = fragment.getattr("ion_type")?.extract::<String>()?;
                     let charge = fragment.getattr("charge")?.extract::<i8>()?;
                     let mz = fragment.getattr("mz")?.extract::<f64>()?;
@@ -369,6 +953,7 @@ impl PolymerInfo {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Arc;
 
     #[test]
     fn test_xic_extractor_creation() {
@@ -402,15 +987,265 @@ mod tests {
         assert_eq!(result.charge, 2);
     }
 
+    /// 保留时间0-10的PEPTIDE前体，没有碎片离子
+    fn test_precursor(mz: f64, charge: Charge) -> PolymerInfo {
+        PolymerInfo {
+            sequence: "PEPTIDE".to_string(),
+            modified_sequence: "PEPTIDE".to_string(),
+            charge,
+            mz,
+            rt: 1.0,
+            rt_start: 0.0,
+            rt_stop: 10.0,
+            fragment_ions: Vec::new(),
+        }
+    }
+
+    /// `n`张保留时间依次为0, 1, 2...的MS1谱图，第i张的峰由`peaks(i)`给出
+    fn ms1_run(n: usize, peaks: impl Fn(usize) -> Vec<Peak>) -> Vec<Spectrum> {
+        (0..n)
+            .map(|i| {
+                let mut spectrum = Spectrum::ms1().unwrap();
+                spectrum.set_retention_time(i as f64).unwrap();
+                spectrum.add_peaks(peaks(i)).unwrap();
+                spectrum
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_negative_charge_isotope_spacing() {
+        let spectra = ms1_run(3, |_| vec![(600.0, 100.0), (600.5, 60.0), (601.0, 20.0)]);
+        let extractor = XICSExtractor::from_spectra(spectra, 10.0, 1.0).unwrap();
+        let precursor = test_precursor(600.0, -2);
+
+        // 负电荷的同位素峰同样按1/|z|向高m/z排列
+        let results = extractor.extract_precursor_xics(&precursor, 3).unwrap();
+        let expected = [600.0, 600.0 + ISOTOPE_SPACING / 2.0, 600.0 + ISOTOPE_SPACING];
+        assert_eq!(results.len(), expected.len());
+        for (result, &mz) in results.iter().zip(&expected) {
+            assert!((result.mz - mz).abs() < 1e-9);
+        }
+        assert!(results.iter().all(|result| result.charge == -2 && result.intensity_array.len() == 3));
+    }
+
+    #[test]
+    fn test_precursor_isotope_target_mz() {
+        let spectra = ms1_run(3, |_| vec![(500.0, 100.0), (500.0 + ISOTOPE_SPACING / 3.0, 60.0)]);
+        let extractor = XICSExtractor::from_spectra(spectra, 10.0, 1.0).unwrap();
+        let precursor = test_precursor(500.0, 3);
+
+        // M+k 目标为 mz + k * 1.00335 / |z|，而非 k / |z|
+        let results = extractor.extract_precursor_xics(&precursor, 3).unwrap();
+        for (isotope, result) in results.iter().enumerate() {
+            let expected = 500.0 + ISOTOPE_SPACING * isotope as f64 / 3.0;
+            assert!((result.mz - expected).abs() < 1e-9);
+        }
+        assert!(results[1].intensity_array.iter().all(|&intensity| intensity == 60.0));
+        // 10 ppm 窗口下 M+2 没有匹配峰
+        assert!(results[2].intensity_array.iter().all(|&intensity| intensity == 0.0));
+    }
+
+    #[test]
+    fn test_scored_isotope_xics() {
+        let pattern = averagine_pattern(1998.0, 2, 3).unwrap();
+        let spectra = ms1_run(6, |i| {
+            pattern
+                .iter()
+                .enumerate()
+                .map(|(isotope, &(mz, relative))| {
+                    // 第4个点的M+1峰受到干扰
+                    let interference = if i == 4 && isotope == 1 { 5.0 } else { 1.0 };
+                    (mz, 1e5 * relative * interference)
+                })
+                .collect()
+        });
+        let extractor = XICSExtractor::from_spectra(spectra, 10.0, 1.0).unwrap();
+        let precursor = test_precursor(pattern[0].0, 2);
+
+        let scored = extractor.extract_precursor_xics_scored(&precursor, 3, None).unwrap();
+        assert_eq!(scored.xics.len(), 3);
+        assert!(scored.xics.iter().all(|xic| xic.rt_array.len() == 6));
+        assert_eq!(scored.xics[1].ion_type, "PEPTIDE[1+2]");
+        assert_eq!(scored.rt_array, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert!(scored.scores.iter().enumerate().all(|(i, &score)| (i == 4) == (score < 0.9)), "{:?}", scored.scores);
+        assert!(scored.scores[0] > 0.9999);
+
+        // 分子式给出不同的峰型时得分下降
+        let sulfur_rich = extractor.extract_precursor_xics_scored(&precursor, 3, Some("C60H100N10O30S8")).unwrap();
+        assert!(sulfur_rich.scores[0] < scored.scores[0]);
+        assert!(extractor.extract_precursor_xics_scored(&precursor, 3, Some("Xx2")).is_err());
+    }
+
+    #[test]
+    fn test_hybrid_tolerance_finds_low_mass_peak() {
+        let spectra: Vec<Spectrum> = (0..3)
+            .map(|i| {
+                let mut spectrum = Spectrum::ms1().unwrap();
+                spectrum.set_retention_time(i as f64).unwrap();
+                // 偏离目标0.0015 Da：m/z 100处为15 ppm
+                spectrum.add_peak(100.0015, 1000.0).unwrap();
+                spectrum.add_peak(1000.008, 500.0).unwrap();
+                spectrum
+            })
+            .collect();
+
+        let ppm_only = XICSExtractor::from_spectra(spectra.clone(), 10.0, 1.0).unwrap();
+        assert!(ppm_only.extract_single_xic(100.0, 1, "low", 0.0, 10.0).unwrap().rt_array.is_empty());
+        assert_eq!(ppm_only.extract_single_xic(1000.0, 1, "high", 0.0, 10.0).unwrap().rt_array.len(), 3);
+
+        let hybrid = XICSExtractor::from_spectra(spectra, 10.0, 1.0)
+            .unwrap()
+            .with_tolerance_model(ToleranceModel::Hybrid { ppm: 10.0, min_da: 0.002 });
+        let low = hybrid.extract_single_xic(100.0, 1, "low", 0.0, 10.0).unwrap();
+        assert_eq!(low.intensity_array, vec![1000.0; 3]);
+        // ppm误差为观测峰的实际偏差，而不是放宽后的容差
+        assert!((low.ppm_error - 15.0).abs() < 1e-6);
+        assert_eq!(hybrid.extract_single_xic(1000.0, 1, "high", 0.0, 10.0).unwrap().rt_array.len(), 3);
+    }
+
+    #[test]
+    fn test_injection_time_normalized_xic() {
+        // 相同离子流下，100 ms注入的谱图强度是50 ms的两倍
+        let spectra: Vec<Spectrum> = [(50.0, 1000.0), (100.0, 2000.0)]
+            .iter()
+            .enumerate()
+            .map(|(i, &(injection_time, intensity))| {
+                let mut spectrum = Spectrum::ms1().unwrap();
+                spectrum.set_retention_time(i as f64).unwrap();
+                spectrum.scan.injection_time = Some(injection_time);
+                spectrum.add_peak(500.0, intensity).unwrap();
+                spectrum
+            })
+            .collect();
+
+        let mut extractor = XICSExtractor::from_spectra(spectra, 10.0, 1.0).unwrap();
+        let raw = extractor.extract_single_xic(500.0, 1, "raw", 0.0, 10.0).unwrap();
+        assert_eq!(raw.intensity_array, vec![1000.0, 2000.0]);
+
+        extractor.set_injection_time_normalization(Some(100.0));
+        let normalized = extractor.extract_single_xic(500.0, 1, "normalized", 0.0, 10.0).unwrap();
+        assert_eq!(normalized.intensity_array, vec![2000.0, 2000.0]);
+    }
+
+    #[test]
+    fn test_extractors_share_spectra() {
+        let mut spectra = Vec::new();
+        for i in 0..4 {
+            let mut spectrum = if i % 2 == 0 { Spectrum::ms1() } else { Spectrum::ms2() }.unwrap();
+            spectrum.set_retention_time(i as f64).unwrap();
+            spectrum.add_peak(500.0, 100.0).unwrap();
+            spectra.push(spectrum);
+        }
+
+        let first = XICSExtractor::from_spectra(spectra, 10.0, 1.0).unwrap();
+        let shared = first.share_spectra();
+        let count = Arc::strong_count(&shared);
+        let second = XICSExtractor::from_shared(shared.clone(), 5.0, 1.0).unwrap();
+
+        // 第二个提取器只增加引用计数（自身和两个索引各一份），谱图数据不复制
+        assert!(Arc::ptr_eq(&first.share_spectra(), &second.share_spectra()));
+        assert_eq!(Arc::strong_count(&shared), count + 3);
+        assert_eq!((second.ms1_count(), second.ms2_count()), (2, 2));
+        assert_eq!(second.extract_single_xic(500.0, 1, "test", 0.0, 10.0).unwrap().rt_array, vec![0.0, 2.0]);
+    }
+
+    #[test]
+    fn test_dia_fragment_xic_window_pooling() {
+        use crate::core::spectrum::PrecursorInfo;
+
+        // 50%重叠的两个窗口交替扫描，520同时落在两个窗口内，中心更近的是512.5-537.5
+        let windows = [(500.0, 525.0), (512.5, 537.5)];
+        let spectra: Vec<Spectrum> = (0..6)
+            .map(|i| {
+                let mut spectrum = Spectrum::ms2().unwrap();
+                spectrum.set_retention_time(i as f64).unwrap();
+                spectrum.add_peak(300.0, 100.0 * (i + 1) as f64).unwrap();
+                spectrum.set_precursor(PrecursorInfo { isolation_window: windows[i % 2], ..PrecursorInfo::default() });
+                spectrum
+            })
+            .collect();
+        let extractor = XICSExtractor::from_spectra(spectra, 10.0, 1.0).unwrap();
+
+        let assigned = extractor.extract_dia_fragment_xic(520.0, 300.0, 0.0, 10.0, WindowPooling::Assigned).unwrap();
+        assert_eq!(assigned.rt_array, vec![1.0, 3.0, 5.0]);
+
+        let pooled = extractor.extract_dia_fragment_xic(520.0, 300.0, 0.0, 10.0, WindowPooling::AllContaining).unwrap();
+        assert_eq!(pooled.rt_array, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(pooled.intensity_array[0], 100.0);
+
+        // 只在第一个窗口内的前体离子两种方式结果相同
+        let low = extractor.extract_dia_fragment_xic(505.0, 300.0, 0.0, 10.0, WindowPooling::AllContaining).unwrap();
+        assert_eq!(low.rt_array, vec![0.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn test_observed_mz_and_peak_combination() {
+        let spectra: Vec<Spectrum> = (0..3)
+            .map(|i| {
+                let mut spectrum = Spectrum::ms1().unwrap();
+                spectrum.set_retention_time(i as f64).unwrap();
+                spectrum.add_peak(499.999, 100.0).unwrap();
+                spectrum.add_peak(500.002, 300.0).unwrap();
+                spectrum
+            })
+            .collect();
+        let mut extractor = XICSExtractor::from_spectra(spectra, 10.0, 1.0).unwrap();
+
+        let summed = extractor.extract_single_xic(500.0, 1, "", 0.0, 10.0).unwrap();
+        assert_eq!(summed.intensity_array, vec![400.0; 3]);
+        assert_eq!(summed.mz_observed_array.len(), summed.rt_array.len());
+        assert!((summed.mz_observed_array[0] - 500.00125).abs() < 1e-9);
+        assert!((summed.ppm_error - 2.5).abs() < 1e-6);
+
+        extractor.set_peak_combination(PeakCombination::Nearest);
+        let nearest = extractor.extract_single_xic(500.0, 1, "", 0.0, 10.0).unwrap();
+        assert_eq!(nearest.intensity_array, vec![100.0; 3]);
+        assert_eq!(nearest.mz_observed_array, vec![499.999; 3]);
+        assert!((nearest.ppm_error + 2.0).abs() < 1e-6);
+        assert!(nearest.ppm_error_array().iter().all(|ppm| (ppm + 2.0).abs() < 1e-6));
+
+        let empty = extractor.extract_single_xic(600.0, 1, "", 0.0, 10.0).unwrap();
+        assert!(empty.mz_observed_array.is_empty());
+        assert_eq!(empty.ppm_error, 0.0);
+    }
+
+    #[test]
+    fn test_tic_and_bpc() {
+        let spectra: Vec<Spectrum> = [(3.0, 1), (1.0, 1), (2.0, 2), (0.0, 1), (2.0, 1)]
+            .iter()
+            .map(|&(rt, level)| {
+                let mut spectrum = Spectrum::new(level).unwrap();
+                spectrum.set_retention_time(rt).unwrap();
+                spectrum.add_peaks([(100.0, rt * 10.0), (200.0, rt * 30.0)]).unwrap();
+                spectrum
+            })
+            .collect();
+
+        let tic = build_tic(&spectra, 1, None);
+        assert_eq!(tic.rt_array, vec![1.0, 2.0, 3.0]);
+        assert_eq!(tic.intensity_array, vec![40.0, 80.0, 120.0]);
+        assert_eq!(tic.skipped, 1);
+
+        let bpc = build_bpc(&spectra, 1, Some((1.5, 3.0)));
+        assert_eq!(bpc.rt_array, vec![2.0, 3.0]);
+        assert_eq!(bpc.intensity_array, vec![60.0, 90.0]);
+
+        let ms2 = build_tic(&spectra, 2, None);
+        assert_eq!((ms2.rt_array, ms2.skipped), (vec![2.0], 0));
+    }
+
     #[test]
     fn test_xic_quality_metrics() {
         let xic = XICResult {
             rt_array: vec![1.0, 2.0, 3.0, 4.0, 5.0],
             intensity_array: vec![100.0, 200.0, 500.0, 200.0, 100.0],
+            mz_observed_array: vec![500.0; 5],
             mz: 500.0,
             ppm_error: 5.0,
             ion_type: "test".to_string(),
             charge: 2,
+            processing_history: Vec::new(),
         };
 
         let extractor = XICSExtractor::new(10.0);
@@ -418,6 +1253,226 @@ mod tests {
 
         assert_eq!(metrics.points, 5);
         assert_eq!(metrics.max_intensity, 500.0);
-        assert!(metrics.signal_to_noise > 0.0);
+        // 最低的四分之一点（两个100）给出噪声100
+        assert_eq!(metrics.signal_to_noise, 5.0);
+        assert_eq!((metrics.signal_points, metrics.noise_points), (3, 2));
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn test_python_extraction_flow() {
+        use pyo3::types::PyDict;
+        use std::ffi::CString;
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let ms_objects: Vec<Py<MSObject>> = (0..4)
+                .map(|i| {
+                    let mut spectrum = Spectrum::ms1().unwrap();
+                    spectrum.set_retention_time(i as f64).unwrap();
+                    spectrum.add_peak(500.0, 100.0 * (i + 1) as f64).unwrap();
+                    spectrum.add_peak(500.5, 50.0).unwrap();
+                    spectrum.add_peak(700.0, 10.0).unwrap();
+                    Py::new(py, MSObject { spectrum }).unwrap()
+                })
+                .collect();
+
+            let locals = PyDict::new(py);
+            locals.set_item("XICSExtractor", py.get_type::<XICSExtractor>()).unwrap();
+            locals.set_item("spectra", ms_objects).unwrap();
+            let code = CString::new(
+                r#"
+from types import SimpleNamespace
+extractor = XICSExtractor(ppm_tolerance=10.0, spectra=spectra, bin_size=0.5)
+single = extractor.extract_single_xic(500.0, 2, "M", 1.0, 2.0)
+batch = extractor.extract_batch_xics([(500.0, 2, "M"), (700.0, 1, "y1")])
+parallel_batch = extractor.extract_batch_xics([(500.0, 2, "M"), (700.0, 1, "y1")], parallel=True, num_threads=2)
+fragment = SimpleNamespace(ion_type="y1", charge=1, mz=700.0)
+peptide = SimpleNamespace(
+    sequence="PEPTIDE", modified_sequence="PEPTIDE", charge=2, mz=500.0,
+    rt=1.5, rt_start=0.0, rt_stop=10.0, fragment_ions=[fragment],
+)
+precursor = extractor.extract_precursor_xics(peptide, 2)
+fragments = extractor.extract_fragment_xics(peptide)
+"#,
+            )
+            .unwrap();
+            py.run(&code, Some(&locals), None).unwrap();
+
+            let (rts, intensities, skipped): (Vec<f64>, Vec<f64>, usize) = py
+                .get_type::<XICSExtractor>()
+                .call_method1("build_bpc", (locals.get_item("spectra").unwrap().unwrap(),))
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(rts, vec![1.0, 2.0, 3.0]);
+            assert_eq!(intensities, vec![200.0, 300.0, 400.0]);
+            assert_eq!(skipped, 1);
+
+            let extractor = locals.get_item("extractor").unwrap().unwrap();
+            assert!(extractor.getattr("is_loaded").unwrap().extract::<bool>().unwrap());
+            assert_eq!(extractor.getattr("ms1_count").unwrap().extract::<usize>().unwrap(), 4);
+
+            let single = locals.get_item("single").unwrap().unwrap();
+            assert_eq!(single.getattr("rt_array").unwrap().extract::<Vec<f64>>().unwrap(), vec![1.0, 2.0]);
+            assert_eq!(single.getattr("intensity_array").unwrap().extract::<Vec<f64>>().unwrap(), vec![200.0, 300.0]);
+            assert_eq!(single.getattr("charge").unwrap().extract::<i8>().unwrap(), 2);
+            assert_eq!(single.getattr("ion_type").unwrap().extract::<String>().unwrap(), "M");
+            assert_eq!(single.len().unwrap(), 2);
+            assert_eq!(single.getattr("mz_observed_array").unwrap().extract::<Vec<f64>>().unwrap(), vec![500.0, 500.0]);
+            assert_eq!(single.getattr("ppm_error").unwrap().extract::<f64>().unwrap(), 0.0);
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("sn_threshold", 3.0).unwrap();
+            let peaks = single.call_method("pick_peaks", (), Some(&kwargs)).unwrap();
+            assert_eq!(peaks.len().unwrap(), 0);
+
+            extractor.setattr("peak_combination", "nearest").unwrap();
+            assert_eq!(extractor.getattr("peak_combination").unwrap().extract::<String>().unwrap(), "nearest");
+            assert!(extractor.setattr("peak_combination", "max").is_err());
+
+            let batch: Vec<XICResult> = locals.get_item("batch").unwrap().unwrap().extract().unwrap();
+            assert_eq!(batch.len(), 2);
+            assert_eq!(batch[1].intensity_array, vec![10.0; 4]);
+            let parallel_batch: Vec<XICResult> = locals.get_item("parallel_batch").unwrap().unwrap().extract().unwrap();
+            assert_eq!(parallel_batch[0].intensity_array, batch[0].intensity_array);
+            assert_eq!(parallel_batch[1].intensity_array, batch[1].intensity_array);
+
+            let precursor: Vec<XICResult> = locals.get_item("precursor").unwrap().unwrap().extract().unwrap();
+            assert_eq!(precursor.len(), 2);
+            assert!((precursor[1].mz - (500.0 + ISOTOPE_SPACING / 2.0)).abs() < 1e-9);
+            assert_eq!(precursor[1].intensity_array, vec![50.0; 4]);
+
+            let fragments: Vec<XICResult> = locals.get_item("fragments").unwrap().unwrap().extract().unwrap();
+            assert_eq!(fragments.len(), 1);
+            assert_eq!(fragments[0].ion_type, "y1");
+        });
+    }
+
+    /// 逐张谱图线性扫描的参考实现，谱图按保留时间稳定排序
+    fn brute_force_xic(extractor: &XICSExtractor, spectra: &[Spectrum], mz: f64, rt_start: f64, rt_end: f64) -> XICResult {
+        let tolerance = extractor.tolerance_at_mz(mz);
+        let mut ms1: Vec<&Spectrum> = spectra.iter().filter(|spectrum| spectrum.level == 1).collect();
+        ms1.sort_by(|a, b| a.scan.retention_time.total_cmp(&b.scan.retention_time));
+
+        let mut rt_array = Vec::new();
+        let mut intensity_array = Vec::new();
+        let mut mz_observed_array = Vec::new();
+        for spectrum in ms1 {
+            let rt = spectrum.scan.retention_time;
+            if rt < rt_start || rt > rt_end {
+                continue;
+            }
+            let matching = find_mz_in_tolerance(spectrum.mz_slice(), mz, tolerance);
+            if let Some((intensity, observed)) =
+                extractor.peak_combination().combine(spectrum.mz_slice(), spectrum.intensity_slice(), &matching, mz)
+            {
+                rt_array.push(rt);
+                intensity_array.push(intensity);
+                mz_observed_array.push(observed);
+            }
+        }
+        XICResult::new(rt_array, intensity_array, mz_observed_array, mz, "", 0)
+    }
+
+    #[test]
+    fn test_indexed_extraction_matches_brute_force() {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+
+        // 保留时间乱序加载，夹杂MS2谱图，峰未按m/z排序
+        let spectra: Vec<Spectrum> = (0..200)
+            .map(|i| {
+                let mut spectrum = if i % 5 == 4 { Spectrum::ms2().unwrap() } else { Spectrum::ms1().unwrap() };
+                spectrum.set_retention_time(((i * 37) % 200) as f64 * 0.5).unwrap();
+                for _ in 0..100 {
+                    spectrum.add_peak(400.0 + next() * 20.0, 1.0 + next() * 1000.0).unwrap();
+                }
+                spectrum
+            })
+            .collect();
+        let extractor = XICSExtractor::from_spectra(spectra.clone(), 20.0, 0.5).unwrap();
+
+        for _ in 0..200 {
+            let mz = 400.0 + next() * 20.0;
+            let rt_start = next() * 60.0;
+            let rt_end = rt_start + next() * 60.0;
+            let indexed = extractor.extract_single_xic(mz, 0, "", rt_start, rt_end).unwrap();
+            let expected = brute_force_xic(&extractor, &spectra, mz, rt_start, rt_end);
+            assert_eq!(indexed.rt_array, expected.rt_array);
+            assert_eq!(indexed.intensity_array, expected.intensity_array);
+            assert_eq!(indexed.mz_observed_array, expected.mz_observed_array);
+            assert_eq!(indexed.ppm_error, expected.ppm_error);
+        }
+
+        // 区间端点恰好落在扫描的保留时间上时两端都包含
+        let full = extractor.extract_single_xic(410.0, 0, "", 10.0, 20.0).unwrap();
+        assert_eq!(full.rt_array, brute_force_xic(&extractor, &spectra, 410.0, 10.0, 20.0).rt_array);
+        assert!(extractor.extract_single_xic(410.0, 0, "", 20.0, 10.0).unwrap().rt_array.is_empty());
+    }
+
+    fn batch_test_extractor() -> XICSExtractor {
+        let spectra: Vec<Spectrum> = (0..300)
+            .map(|i| {
+                let mut spectrum = Spectrum::ms1().unwrap();
+                spectrum.set_retention_time(i as f64 * 0.1).unwrap();
+                for k in 0..2000 {
+                    let mz = 300.0 + k as f64 * 0.5 + (i % 7) as f64 * 1e-4;
+                    spectrum.add_peak(mz, 100.0 + ((i * 31 + k) % 97) as f64).unwrap();
+                }
+                spectrum
+            })
+            .collect();
+        XICSExtractor::from_spectra(spectra, 10.0, 1.0).unwrap()
+    }
+
+    #[test]
+    fn test_parallel_batch_matches_serial() {
+        fn assert_sync<T: Sync + Send>() {}
+        assert_sync::<XICSExtractor>();
+
+        let extractor = batch_test_extractor();
+        let targets: Vec<(f64, i8, &str)> = (0..500).map(|k| (300.0 + k as f64 * 1.7, (k % 3) as i8 + 1, "y")).collect();
+
+        let serial = extractor.extract_batch_xics(&targets, 5.0, 20.0).unwrap();
+        for threads in [0, 1, 4] {
+            let parallel = extractor.extract_batch_xics_parallel(&targets, 5.0, 20.0, threads).unwrap();
+            assert_eq!(parallel.len(), serial.len());
+            for (a, b) in parallel.iter().zip(&serial) {
+                assert_eq!((a.mz, a.charge), (b.mz, b.charge));
+                assert_eq!(a.rt_array, b.rt_array);
+                assert_eq!(a.intensity_array, b.intensity_array);
+            }
+        }
+        assert!(XICSExtractor::new(10.0).extract_batch_xics_parallel(&targets, 0.0, 1.0, 2).is_err());
+    }
+
+    #[test]
+    #[ignore = "timing benchmark; run with --ignored --nocapture"]
+    fn bench_parallel_batch_xics() {
+        let extractor = batch_test_extractor();
+        let targets: Vec<(f64, i8, &str)> = (0..50_000).map(|k| (300.0 + (k % 2000) as f64 * 0.5, 1, "y")).collect();
+
+        let start = std::time::Instant::now();
+        let serial = extractor.extract_batch_xics(&targets, 0.0, f64::INFINITY).unwrap();
+        let serial_time = start.elapsed();
+        let start = std::time::Instant::now();
+        let parallel = extractor.extract_batch_xics_parallel(&targets, 0.0, f64::INFINITY, 0).unwrap();
+        let parallel_time = start.elapsed();
+
+        assert_eq!(parallel.len(), serial.len());
+        println!(
+            "{} targets: serial {:?}, parallel {:?} ({} threads, {:.1}x)",
+            targets.len(),
+            serial_time,
+            parallel_time,
+            rayon::current_num_threads(),
+            serial_time.as_secs_f64() / parallel_time.as_secs_f64()
+        );
+        if rayon::current_num_threads() > 1 {
+            assert!(parallel_time < serial_time);
+        }
     }
 }
@@ -4,12 +4,30 @@
 //! - XIC提取器
 //! - SIMD优化搜索
 //! - XIC结果数据结构
+//! - 按加合物、电荷和同位素展开XIC目标
+//! - 共享保留时间网格上的稠密XIC与相关性
+//! - 边解析边更新的流式XIC
+//! - 色谱峰识别与积分
+//! - XIC平滑与基线扣除
+//! - SRM/MRM跃迁提取
 
 pub mod extractor;
 pub mod simd_search;
 pub mod result;
+pub mod targets;
+pub mod dense;
+pub mod streaming;
+pub mod peak_picking;
+pub mod smoothing;
+pub mod srm;
 
 // 重新导出主要类型
 pub use extractor::*;
 pub use simd_search::*;
 pub use result::*;
+pub use targets::*;
+pub use dense::*;
+pub use streaming::*;
+pub use peak_picking::*;
+pub use smoothing::*;
+pub use srm::*;
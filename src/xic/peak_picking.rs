@@ -0,0 +1,246 @@
+//! 色谱峰识别与积分
+//!
+//! 对XIC先做滑动平均平滑，在平滑曲线上找局部极大值，从峰顶向两侧下降到谷底确定峰边界，
+//! 再在原始曲线上计算峰面积、半高宽和局部信噪比，用于定量。
+
+use crate::core::noise::MAD_SCALE;
+use crate::utils::helpers::median;
+use crate::xic::result::XICResult;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// 默认的最少数据点数
+pub const DEFAULT_MIN_POINTS: usize = 5;
+/// 默认的信噪比阈值
+pub const DEFAULT_SN_THRESHOLD: f64 = 3.0;
+/// 默认的平滑窗口（数据点数）
+pub const DEFAULT_SMOOTHING_WINDOW: usize = 5;
+
+/// 色谱峰
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChromPeak {
+    /// 峰顶保留时间（峰范围内原始强度最大的点）
+    pub apex_rt: f64,
+    /// 峰顶强度
+    pub apex_intensity: f64,
+    /// 峰起点保留时间
+    pub rt_start: f64,
+    /// 峰终点保留时间
+    pub rt_end: f64,
+    /// 峰面积：原始曲线的梯形积分减去两端点连线下的基线面积
+    pub area: f64,
+    /// 半高宽，由半高处两侧的线性插值得到
+    pub fwhm: f64,
+    /// 局部信噪比：扣除基线后的峰高与局部噪声标准差之比，无噪声时为无穷大
+    pub signal_to_noise: f64,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl ChromPeak {
+    fn __repr__(&self) -> String {
+        format!(
+            "ChromPeak(apex_rt={}, rt_start={}, rt_end={}, area={}, sn={:.1})",
+            self.apex_rt, self.rt_start, self.rt_end, self.area, self.signal_to_noise
+        )
+    }
+}
+
+/// 在(保留时间, 强度)曲线上识别色谱峰，结果按保留时间排序
+///
+/// - `smoothing_window`为滑动平均窗口的数据点数，偶数加1，不大于1时不平滑；
+/// - 相邻峰在平滑曲线的谷底处分开；
+/// - 少于`min_points`个数据点或信噪比低于`sn_threshold`的峰被丢弃。
+pub fn pick_peaks(
+    rt_array: &[f64],
+    intensity_array: &[f64],
+    min_points: usize,
+    sn_threshold: f64,
+    smoothing_window: usize,
+) -> Vec<ChromPeak> {
+    let n = rt_array.len().min(intensity_array.len());
+    if n < 3 {
+        return Vec::new();
+    }
+    let raw = &intensity_array[..n];
+    let rts = &rt_array[..n];
+    let smoothed = moving_average(raw, smoothing_window);
+
+    let mut peaks = Vec::new();
+    for apex in 1..n - 1 {
+        // 平顶只在第一个点处计一次
+        if !(smoothed[apex] > smoothed[apex - 1] && smoothed[apex] >= smoothed[apex + 1]) {
+            continue;
+        }
+
+        let mut start = apex;
+        while start > 0 && smoothed[start - 1] <= smoothed[start] {
+            start -= 1;
+        }
+        let mut end = apex;
+        while end + 1 < n && smoothed[end + 1] <= smoothed[end] {
+            end += 1;
+        }
+        if end - start + 1 < min_points.max(2) {
+            continue;
+        }
+
+        let baseline = |i: usize| {
+            let fraction = (rts[i] - rts[start]) / (rts[end] - rts[start]);
+            smoothed[start] + fraction * (smoothed[end] - smoothed[start])
+        };
+        let signal = smoothed[apex] - baseline(apex);
+        let noise = local_noise(raw, &smoothed, start, end);
+        let signal_to_noise = if noise > 0.0 { signal / noise } else if signal > 0.0 { f64::INFINITY } else { 0.0 };
+        if signal_to_noise < sn_threshold {
+            continue;
+        }
+
+        let raw_apex = (start..=end).fold(start, |best, i| if raw[i] > raw[best] { i } else { best });
+        let area: f64 = (start..end)
+            .map(|i| {
+                let width = rts[i + 1] - rts[i];
+                let above = (raw[i] - baseline(i)) + (raw[i + 1] - baseline(i + 1));
+                width * above / 2.0
+            })
+            .sum();
+
+        peaks.push(ChromPeak {
+            apex_rt: rts[raw_apex],
+            apex_intensity: raw[raw_apex],
+            rt_start: rts[start],
+            rt_end: rts[end],
+            area,
+            fwhm: fwhm(rts, raw, start, raw_apex, end),
+            signal_to_noise,
+        });
+    }
+    peaks
+}
+
+impl XICResult {
+    /// 在本XIC上识别色谱峰，参数见[`pick_peaks`]
+    pub fn pick_peaks(&self, min_points: usize, sn_threshold: f64, smoothing_window: usize) -> Vec<ChromPeak> {
+        pick_peaks(&self.rt_array, &self.intensity_array, min_points, sn_threshold, smoothing_window)
+    }
+}
+
+/// 居中的滑动平均，两端只对窗口内存在的点求平均
+pub(crate) fn moving_average(values: &[f64], window: usize) -> Vec<f64> {
+    let half = window / 2;
+    if half == 0 {
+        return values.to_vec();
+    }
+    let mut prefix = Vec::with_capacity(values.len() + 1);
+    prefix.push(0.0);
+    for &value in values {
+        prefix.push(prefix[prefix.len() - 1] + value);
+    }
+    (0..values.len())
+        .map(|i| {
+            let lo = i.saturating_sub(half);
+            let hi = (i + half + 1).min(values.len());
+            (prefix[hi] - prefix[lo]) / (hi - lo) as f64
+        })
+        .collect()
+}
+
+/// 峰两侧各延伸一个峰宽的范围内，原始曲线相对平滑曲线残差的稳健标准差（MAD估计）
+fn local_noise(raw: &[f64], smoothed: &[f64], start: usize, end: usize) -> f64 {
+    let width = end - start + 1;
+    let lo = start.saturating_sub(width);
+    let hi = (end + width + 1).min(raw.len());
+    let mut residuals: Vec<f64> = (lo..hi).map(|i| (raw[i] - smoothed[i]).abs()).collect();
+    residuals.sort_by(|a, b| a.total_cmp(b));
+    median(&residuals).unwrap_or(0.0) * MAD_SCALE
+}
+
+/// 从峰顶向两侧寻找原始强度降到半高的位置并线性插值，到达峰边界时以边界为准
+fn fwhm(rts: &[f64], raw: &[f64], start: usize, apex: usize, end: usize) -> f64 {
+    let half = raw[apex] / 2.0;
+    let crossing = |inner: usize, outer: usize| {
+        let fraction = (raw[inner] - half) / (raw[inner] - raw[outer]);
+        rts[inner] + fraction * (rts[outer] - rts[inner])
+    };
+
+    let left = (start..apex).rev().find(|&i| raw[i] <= half).map_or(rts[start], |i| crossing(i + 1, i));
+    let right = (apex + 1..=end).find(|&i| raw[i] <= half).map_or(rts[end], |i| crossing(i - 1, i));
+    right - left
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FWHM_PER_SIGMA: f64 = 2.354_820_045;
+
+    /// (中心, 峰高, σ)的高斯峰叠加在均匀噪声基线上
+    fn gaussian_trace(peaks: &[(f64, f64, f64)], noise: f64, baseline: f64) -> (Vec<f64>, Vec<f64>) {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move || {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let rts: Vec<f64> = (0..=400).map(|i| i as f64 * 0.05).collect();
+        let intensities = rts
+            .iter()
+            .map(|&rt| {
+                let signal: f64 = peaks
+                    .iter()
+                    .map(|&(center, height, sigma)| height * (-(rt - center).powi(2) / (2.0 * sigma * sigma)).exp())
+                    .sum();
+                signal + baseline + noise * next()
+            })
+            .collect();
+        (rts, intensities)
+    }
+
+    fn gaussian_area(height: f64, sigma: f64) -> f64 {
+        height * sigma * (2.0 * std::f64::consts::PI).sqrt()
+    }
+
+    fn assert_within(actual: f64, expected: f64, relative: f64) {
+        assert!(((actual - expected) / expected).abs() < relative, "{} vs {}", actual, expected);
+    }
+
+    #[test]
+    fn test_noisy_gaussian_areas() {
+        let truth = [(6.0, 1e5, 0.15), (13.0, 4e4, 0.25)];
+        let (rts, intensities) = gaussian_trace(&truth, 1000.0, 500.0);
+        let peaks = pick_peaks(&rts, &intensities, 5, 3.0, 5);
+        assert_eq!(peaks.len(), 2, "{:?}", peaks);
+
+        for (peak, &(center, height, sigma)) in peaks.iter().zip(&truth) {
+            assert!((peak.apex_rt - center).abs() <= 0.1);
+            assert!(peak.rt_start < center - sigma && peak.rt_end > center + sigma);
+            assert_within(peak.area, gaussian_area(height, sigma), 0.03);
+            assert_within(peak.fwhm, FWHM_PER_SIGMA * sigma, 0.05);
+            assert!(peak.signal_to_noise > 10.0);
+        }
+    }
+
+    #[test]
+    fn test_overlapping_peaks_split_at_valley() {
+        let (rts, intensities) = gaussian_trace(&[(8.0, 1e5, 0.2), (9.2, 6e4, 0.2)], 0.0, 0.0);
+        let peaks = pick_peaks(&rts, &intensities, 5, 3.0, 1);
+        assert_eq!(peaks.len(), 2);
+        assert_eq!(peaks[0].rt_end, peaks[1].rt_start);
+        assert!(peaks[0].rt_end > 8.0 && peaks[0].rt_end < 9.2);
+        assert!(peaks[0].signal_to_noise.is_infinite());
+    }
+
+    #[test]
+    fn test_noise_and_short_traces_yield_no_peaks() {
+        let (rts, intensities) = gaussian_trace(&[], 1000.0, 500.0);
+        assert!(pick_peaks(&rts, &intensities, 5, 3.0, 5).is_empty());
+        assert!(pick_peaks(&[1.0, 2.0], &[1.0, 2.0], 1, 0.0, 1).is_empty());
+
+        // 数据点不足的尖峰被丢弃
+        let spike = XICResult::new(vec![0.0, 1.0, 2.0, 3.0], vec![0.0, 10.0, 0.0, 0.0], vec![500.0; 4], 500.0, "", 1);
+        assert!(spike.pick_peaks(5, 0.0, 1).is_empty());
+        assert_eq!(spike.pick_peaks(3, 0.0, 1).len(), 1);
+    }
+}
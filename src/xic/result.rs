@@ -4,7 +4,18 @@
 
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "python")]
+use crate::xic::peak_picking::{ChromPeak, DEFAULT_MIN_POINTS, DEFAULT_SMOOTHING_WINDOW, DEFAULT_SN_THRESHOLD};
+#[cfg(feature = "python")]
+use crate::xic::smoothing::{
+    BaselineMethod, DEFAULT_BASELINE_DEGREE, DEFAULT_BASELINE_ITERATIONS, DEFAULT_BASELINE_WINDOW, DEFAULT_SG_ORDER,
+    DEFAULT_SG_WINDOW,
+};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
 /// XIC提取结果
+#[cfg_attr(feature = "python", pyclass(get_all))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct XICResult {
     /// 保留时间数组
@@ -13,12 +24,192 @@ pub struct XICResult {
     pub intensity_array: Vec<f64>,
     /// 目标质荷比
     pub mz: f64,
-    /// PPM误差
+    /// 各数据点的观测m/z，与`rt_array`一一对应
+    #[serde(default)]
+    pub mz_observed_array: Vec<f64>,
+    /// 观测m/z（按数据点强度加权平均）相对目标m/z的偏差 (ppm)，没有数据点时为0
     pub ppm_error: f64,
     /// 离子类型
     pub ion_type: String,
     /// 电荷状态
     pub charge: i8,
+    /// 依次做过的平滑、基线扣除等处理
+    #[serde(default)]
+    pub processing_history: Vec<String>,
+}
+
+impl XICResult {
+    /// 由各数据点创建结果，`ppm_error`由观测m/z和强度计算
+    pub fn new(
+        rt_array: Vec<f64>,
+        intensity_array: Vec<f64>,
+        mz_observed_array: Vec<f64>,
+        mz: f64,
+        ion_type: &str,
+        charge: i8,
+    ) -> Self {
+        let ppm_error = mean_ppm_error(mz, &mz_observed_array, &intensity_array);
+        Self {
+            rt_array,
+            intensity_array,
+            mz_observed_array,
+            mz,
+            ppm_error,
+            ion_type: ion_type.to_string(),
+            charge,
+            processing_history: Vec::new(),
+        }
+    }
+
+    /// 各数据点观测m/z相对目标m/z的偏差 (ppm)
+    pub fn ppm_error_array(&self) -> Vec<f64> {
+        self.mz_observed_array.iter().map(|&observed| ppm_deviation(self.mz, observed)).collect()
+    }
+}
+
+/// `observed`相对`target`的偏差 (ppm)
+fn ppm_deviation(target: f64, observed: f64) -> f64 {
+    (observed - target) / target * 1e6
+}
+
+/// 按强度加权平均的观测m/z相对`target`的偏差，强度全为0时取算术平均
+fn mean_ppm_error(target: f64, observed: &[f64], intensities: &[f64]) -> f64 {
+    if observed.is_empty() {
+        return 0.0;
+    }
+    let total: f64 = intensities.iter().sum();
+    let mean = if total > 0.0 {
+        observed.iter().zip(intensities).map(|(mz, intensity)| mz * intensity).sum::<f64>() / total
+    } else {
+        observed.iter().sum::<f64>() / observed.len() as f64
+    };
+    ppm_deviation(target, mean)
+}
+
+/// 同一张谱图中有多个峰落在容差窗口内时的合并方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PeakCombination {
+    /// 强度相加，观测m/z取强度加权平均
+    #[default]
+    Sum,
+    /// 只取m/z最接近目标的峰，距离相同时取靠前的峰
+    Nearest,
+}
+
+impl PeakCombination {
+    /// 由名称解析，支持"sum"和"nearest"（不区分大小写）
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sum" => Some(Self::Sum),
+            "nearest" => Some(Self::Nearest),
+            _ => None,
+        }
+    }
+
+    /// 名称，与[`from_name`](Self::from_name)对应
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sum => "sum",
+            Self::Nearest => "nearest",
+        }
+    }
+
+    /// 合并一张谱图中匹配的峰，返回(强度, 观测m/z)；没有匹配峰时为None
+    ///
+    /// `mz`和`intensity`为谱图的平行峰数组，强度按`matching`的顺序累加。
+    pub fn combine(self, mz: &[f64], intensity: &[f64], matching: &[usize], target_mz: f64) -> Option<(f64, f64)> {
+        match self {
+            Self::Sum => {
+                if matching.is_empty() {
+                    return None;
+                }
+                let total: f64 = matching.iter().map(|&index| intensity[index]).sum();
+                let observed = if total > 0.0 {
+                    matching.iter().map(|&index| mz[index] * intensity[index]).sum::<f64>() / total
+                } else {
+                    matching.iter().map(|&index| mz[index]).sum::<f64>() / matching.len() as f64
+                };
+                Some((total, observed))
+            }
+            Self::Nearest => matching
+                .iter()
+                .map(|&index| (mz[index], intensity[index]))
+                .reduce(|best, peak| if (peak.0 - target_mz).abs() < (best.0 - target_mz).abs() { peak } else { best })
+                .map(|(mz, intensity)| (intensity, mz)),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl XICResult {
+    /// 数据点数量
+    fn __len__(&self) -> usize {
+        self.rt_array.len()
+    }
+
+    /// 各数据点的m/z偏差 (ppm)
+    #[pyo3(name = "ppm_error_array")]
+    fn py_ppm_error_array(&self) -> Vec<f64> {
+        self.ppm_error_array()
+    }
+
+    /// 识别色谱峰，返回ChromPeak列表
+    #[pyo3(
+        name = "pick_peaks",
+        signature = (min_points=DEFAULT_MIN_POINTS, sn_threshold=DEFAULT_SN_THRESHOLD, smoothing_window=DEFAULT_SMOOTHING_WINDOW)
+    )]
+    fn py_pick_peaks(&self, min_points: usize, sn_threshold: f64, smoothing_window: usize) -> Vec<ChromPeak> {
+        self.pick_peaks(min_points, sn_threshold, smoothing_window)
+    }
+
+    /// 滑动平均平滑，返回新的XICResult
+    #[pyo3(name = "smooth_moving_average", signature = (window=DEFAULT_SMOOTHING_WINDOW))]
+    fn py_smooth_moving_average(&self, window: usize) -> XICResult {
+        self.smooth_moving_average(window)
+    }
+
+    /// Savitzky-Golay平滑，返回新的XICResult；`window`须为奇数且大于`order`
+    #[pyo3(name = "smooth_savitzky_golay", signature = (window=DEFAULT_SG_WINDOW, order=DEFAULT_SG_ORDER))]
+    fn py_smooth_savitzky_golay(&self, window: usize, order: usize) -> PyResult<XICResult> {
+        self.smooth_savitzky_golay(window, order)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// 扣除基线，返回新的XICResult；`method`为"rolling_minimum"（使用`window`）或"polynomial"
+    /// （使用`degree`和`iterations`）
+    #[pyo3(
+        name = "subtract_baseline",
+        signature = (
+            method="rolling_minimum",
+            window=DEFAULT_BASELINE_WINDOW,
+            degree=DEFAULT_BASELINE_DEGREE,
+            iterations=DEFAULT_BASELINE_ITERATIONS
+        )
+    )]
+    fn py_subtract_baseline(&self, method: &str, window: usize, degree: usize, iterations: usize) -> PyResult<XICResult> {
+        let method = match method.trim().to_ascii_lowercase().as_str() {
+            "rolling_minimum" | "rolling_min" => BaselineMethod::RollingMinimum { window },
+            "polynomial" => BaselineMethod::Polynomial { degree, iterations },
+            _ => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Unknown baseline method: {}",
+                    method
+                )))
+            }
+        };
+        Ok(self.subtract_baseline(method))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "XICResult(mz={}, charge={}, ion_type='{}', points={})",
+            self.mz,
+            self.charge,
+            self.ion_type,
+            self.rt_array.len()
+        )
+    }
 }
 
 /// 碎片离子信息
@@ -1,16 +1,345 @@
 //! SIMD优化搜索
-//! 
-//! 提供SIMD加速的搜索功能
+//!
+//! 提供SIMD加速的峰搜索，结果与标量实现逐位一致：
+//! - 未排序峰列表或m/z数组中容差内峰的线性扫描（XIC提取使用的[`find_mz_in_tolerance`]）；
+//! - 已排序的连续m/z数组上的窗口定位：先二分缩小到一个小块，再在块内向量化计数；
+//! - 一张谱图对多个窗口的批量定位：窗口按边界排序后单遍推进。
+//!
+//! x86_64上运行时检测AVX2，不支持时（或通过[`set_simd_enabled`]关闭时）使用标量实现。
+//!
+//! [`find_mz_in_tolerance`]: crate::utils::helpers::find_mz_in_tolerance
 
-use crate::core::types::*;
+use crate::core::types::Peak;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 二分查找缩小到该长度以内后改为向量化计数
+const SIMD_BLOCK: usize = 32;
+
+/// 全局SIMD开关，默认开启
+static SIMD_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// 打开或关闭SIMD路径（如用于对比或排查问题），对之后创建的[`SIMDSearcher`]生效
+pub fn set_simd_enabled(enabled: bool) {
+    SIMD_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// SIMD路径是否可用：开关开启且CPU支持AVX2
+pub fn simd_enabled() -> bool {
+    SIMD_ENABLED.load(Ordering::Relaxed) && avx2_available()
+}
+
+#[cfg(target_arch = "x86_64")]
+fn avx2_available() -> bool {
+    is_x86_feature_detected!("avx2")
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn avx2_available() -> bool {
+    false
+}
 
 /// SIMD搜索器
+///
+/// 创建时确定使用SIMD还是标量路径；两条路径对同样的输入给出相同结果。
+/// 已排序数组上的方法要求m/z升序且不含NaN。
+#[derive(Debug, Clone, Copy)]
 pub struct SIMDSearcher {
-    // 占位实现
+    simd: bool,
+}
+
+impl Default for SIMDSearcher {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SIMDSearcher {
+    /// 按全局开关和CPU特性选择路径
     pub fn new() -> Self {
-        Self {}
+        Self { simd: simd_enabled() }
+    }
+
+    /// 始终使用标量路径
+    pub fn scalar() -> Self {
+        Self { simd: false }
+    }
+
+    /// 是否使用SIMD路径
+    pub fn is_simd(&self) -> bool {
+        self.simd
+    }
+
+    /// 未排序峰列表中m/z满足`|mz - target_mz| <= tolerance`的峰下标，按下标升序
+    pub fn find_in_tolerance(&self, peaks: &[Peak], target_mz: f64, tolerance: f64) -> Vec<usize> {
+        #[cfg(target_arch = "x86_64")]
+        if self.simd {
+            // SAFETY: 仅在检测到AVX2时进入
+            return unsafe { avx2::find_in_tolerance(peaks, target_mz, tolerance) };
+        }
+        scalar_find_in_tolerance(peaks.iter().map(|peak| peak.0), target_mz, tolerance)
+    }
+
+    /// 未排序m/z数组中满足`|mz - target_mz| <= tolerance`的下标，按下标升序
+    ///
+    /// 与[`find_in_tolerance`](Self::find_in_tolerance)相同，但m/z连续存放，可整块载入。
+    pub fn find_mz_in_tolerance(&self, mz: &[f64], target_mz: f64, tolerance: f64) -> Vec<usize> {
+        #[cfg(target_arch = "x86_64")]
+        if self.simd {
+            // SAFETY: 仅在检测到AVX2时进入
+            return unsafe { avx2::find_mz_in_tolerance(mz, target_mz, tolerance) };
+        }
+        scalar_find_in_tolerance(mz.iter().copied(), target_mz, tolerance)
+    }
+
+    /// 已排序m/z数组中落在`[low, high]`内的下标范围，`low > high`时为空范围
+    pub fn window(&self, mz: &[f64], low: f64, high: f64) -> Range<usize> {
+        let start = self.count_below(mz, low, false);
+        let end = self.count_below(mz, high, true).max(start);
+        start..end
+    }
+
+    /// 对多个窗口调用[`window`](Self::window)的结果，顺序与`windows`一致
+    ///
+    /// 窗口按下限和上限分别排序后各单遍推进，总代价为O(峰数 + 窗口数·log窗口数)，
+    /// 适合对一张谱图查询大量目标m/z。
+    pub fn windows(&self, mz: &[f64], windows: &[(f64, f64)]) -> Vec<Range<usize>> {
+        let mut starts = vec![0; windows.len()];
+        let mut ends = vec![0; windows.len()];
+        for (bounds, inclusive) in [(&mut starts, false), (&mut ends, true)] {
+            let bound = |i: usize| if inclusive { windows[i].1 } else { windows[i].0 };
+            let mut order: Vec<usize> = (0..windows.len()).collect();
+            order.sort_by(|&a, &b| bound(a).total_cmp(&bound(b)));
+            let mut position = 0;
+            for i in order {
+                position = self.advance_below(mz, position, bound(i), inclusive);
+                bounds[i] = position;
+            }
+        }
+        starts.into_iter().zip(ends).map(|(start, end)| start..end.max(start)).collect()
+    }
+
+    /// 已排序数组中小于`bound`（`inclusive`时为不大于）的元素个数
+    fn count_below(&self, mz: &[f64], bound: f64, inclusive: bool) -> usize {
+        let below = |x: f64| if inclusive { x <= bound } else { x < bound };
+        if !self.simd {
+            return mz.partition_point(|&x| below(x));
+        }
+        let (mut lo, mut hi) = (0, mz.len());
+        while hi - lo > SIMD_BLOCK {
+            let mid = lo + (hi - lo) / 2;
+            if below(mz[mid]) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo + self.count_block(&mz[lo..hi], bound, inclusive)
+    }
+
+    /// 块内小于（或不大于）`bound`的元素个数
+    fn count_block(&self, block: &[f64], bound: f64, inclusive: bool) -> usize {
+        #[cfg(target_arch = "x86_64")]
+        if self.simd {
+            // SAFETY: 仅在检测到AVX2时进入
+            return unsafe { avx2::count_below(block, bound, inclusive) };
+        }
+        block.iter().filter(|&&x| if inclusive { x <= bound } else { x < bound }).count()
+    }
+
+    /// 从`position`起向后推进，越过所有小于（或不大于）`bound`的元素，返回新位置
+    fn advance_below(&self, mz: &[f64], position: usize, bound: f64, inclusive: bool) -> usize {
+        #[cfg(target_arch = "x86_64")]
+        if self.simd {
+            // SAFETY: 仅在检测到AVX2时进入
+            return unsafe { avx2::advance_below(mz, position, bound, inclusive) };
+        }
+        position + mz[position..].partition_point(|&x| if inclusive { x <= bound } else { x < bound })
+    }
+}
+
+/// 标量线性扫描
+fn scalar_find_in_tolerance(mz: impl Iterator<Item = f64>, target_mz: f64, tolerance: f64) -> Vec<usize> {
+    mz.enumerate()
+        .filter(|&(_, mz)| (mz - target_mz).abs() <= tolerance)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use crate::core::types::Peak;
+    use std::arch::x86_64::*;
+
+    /// 4个比较结果的位掩码：`inclusive`时为`x <= bound`，否则为`x < bound`
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn below_mask(values: __m256d, bound: __m256d, inclusive: bool) -> i32 {
+        let compared = if inclusive {
+            _mm256_cmp_pd::<_CMP_LE_OQ>(values, bound)
+        } else {
+            _mm256_cmp_pd::<_CMP_LT_OQ>(values, bound)
+        };
+        _mm256_movemask_pd(compared)
+    }
+
+    /// 对`len`个m/z做容差判断，`load(i)`载入第i组4个值，余下的元素由`scalar(i)`取值
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn scan_in_tolerance(
+        len: usize,
+        target_mz: f64,
+        tolerance: f64,
+        load: impl Fn(usize) -> __m256d,
+        scalar: impl Fn(usize) -> f64,
+    ) -> Vec<usize> {
+        let target = _mm256_set1_pd(target_mz);
+        let limit = _mm256_set1_pd(tolerance);
+        // 清除符号位即取绝对值，与f64::abs一致
+        let sign = _mm256_set1_pd(-0.0);
+        let mut result = Vec::new();
+
+        let full = len / 4 * 4;
+        for start in (0..full).step_by(4) {
+            let distance = _mm256_andnot_pd(sign, _mm256_sub_pd(load(start), target));
+            let mut mask = _mm256_movemask_pd(_mm256_cmp_pd::<_CMP_LE_OQ>(distance, limit));
+            while mask != 0 {
+                result.push(start + mask.trailing_zeros() as usize);
+                mask &= mask - 1;
+            }
+        }
+        result.extend((full..len).filter(|&i| (scalar(i) - target_mz).abs() <= tolerance));
+        result
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn find_in_tolerance(peaks: &[Peak], target_mz: f64, tolerance: f64) -> Vec<usize> {
+        // 元组的内存布局没有保证，逐个取出m/z
+        let load = |i: usize| _mm256_set_pd(peaks[i + 3].0, peaks[i + 2].0, peaks[i + 1].0, peaks[i].0);
+        scan_in_tolerance(peaks.len(), target_mz, tolerance, load, |i| peaks[i].0)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn find_mz_in_tolerance(mz: &[f64], target_mz: f64, tolerance: f64) -> Vec<usize> {
+        let load = |i: usize| _mm256_loadu_pd(mz[i..i + 4].as_ptr());
+        scan_in_tolerance(mz.len(), target_mz, tolerance, load, |i| mz[i])
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn count_below(values: &[f64], bound: f64, inclusive: bool) -> usize {
+        let bounds = _mm256_set1_pd(bound);
+        let chunks = values.chunks_exact(4);
+        let remainder = chunks.remainder();
+        let mut count = 0;
+        for chunk in chunks {
+            let loaded = _mm256_loadu_pd(chunk.as_ptr());
+            count += below_mask(loaded, bounds, inclusive).count_ones() as usize;
+        }
+        count + remainder.iter().filter(|&&x| if inclusive { x <= bound } else { x < bound }).count()
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn advance_below(values: &[f64], mut position: usize, bound: f64, inclusive: bool) -> usize {
+        let bounds = _mm256_set1_pd(bound);
+        while position + 4 <= values.len() {
+            let loaded = _mm256_loadu_pd(values[position..position + 4].as_ptr());
+            let mask = below_mask(loaded, bounds, inclusive);
+            if mask != 0b1111 {
+                // 数组有序，满足条件的是前缀
+                return position + mask.trailing_ones() as usize;
+            }
+            position += 4;
+        }
+        while position < values.len() && if inclusive { values[position] <= bound } else { values[position] < bound } {
+            position += 1;
+        }
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_spectrum::pseudo_random;
+
+    fn searchers() -> Vec<SIMDSearcher> {
+        let mut searchers = vec![SIMDSearcher::scalar()];
+        if avx2_available() {
+            searchers.push(SIMDSearcher { simd: true });
+        }
+        searchers
+    }
+
+    #[test]
+    fn test_unsorted_scan_matches_scalar() {
+        let mut seed = 5;
+        for len in [0, 1, 3, 4, 7, 64, 1001] {
+            let peaks: Vec<Peak> = (0..len).map(|_| (100.0 + 10.0 * pseudo_random(&mut seed), 1.0)).collect();
+            for _ in 0..50 {
+                let target = 99.0 + 12.0 * pseudo_random(&mut seed);
+                let tolerance = 0.5 * pseudo_random(&mut seed);
+                let mz: Vec<f64> = peaks.iter().map(|peak| peak.0).collect();
+                let expected = scalar_find_in_tolerance(mz.iter().copied(), target, tolerance);
+                for searcher in searchers() {
+                    assert_eq!(searcher.find_in_tolerance(&peaks, target, tolerance), expected);
+                    assert_eq!(searcher.find_mz_in_tolerance(&mz, target, tolerance), expected);
+                }
+            }
+            // 恰好落在容差边界上的峰
+            if let Some(&(mz, _)) = peaks.last() {
+                for searcher in searchers() {
+                    assert!(searcher.find_in_tolerance(&peaks, mz + 0.25, 0.25).contains(&(len - 1)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sorted_windows_match_partition_point() {
+        let mut seed = 9;
+        for len in [0, 1, 2, 5, 31, 32, 33, 100, 4097] {
+            let mut mz: Vec<f64> = (0..len).map(|_| 200.0 + 1000.0 * pseudo_random(&mut seed)).collect();
+            // 重复值
+            if len > 3 {
+                mz[1] = mz[0];
+                mz[2] = mz[0];
+            }
+            mz.sort_by(|a, b| a.total_cmp(b));
+
+            let mut windows: Vec<(f64, f64)> = (0..200)
+                .map(|_| {
+                    let center = 150.0 + 1100.0 * pseudo_random(&mut seed);
+                    let half_width = 5.0 * pseudo_random(&mut seed);
+                    (center - half_width, center + half_width)
+                })
+                .collect();
+            // 数组两端和元素本身为边界的窗口，以及下限大于上限的窗口
+            if let (Some(&first), Some(&last)) = (mz.first(), mz.last()) {
+                windows.extend([(first, first), (last, last), (first - 1.0, first), (last, last + 1.0), (first, last)]);
+                windows.extend([(mz[len / 2], mz[len / 2]), (last + 1.0, f64::INFINITY), (f64::NEG_INFINITY, first - 1.0)]);
+            }
+            windows.push((700.0, 600.0));
+
+            let expected: Vec<Range<usize>> = windows
+                .iter()
+                .map(|&(low, high)| {
+                    let start = mz.partition_point(|&x| x < low);
+                    start..mz.partition_point(|&x| x <= high).max(start)
+                })
+                .collect();
+            for searcher in searchers() {
+                let single: Vec<Range<usize>> = windows.iter().map(|&(low, high)| searcher.window(&mz, low, high)).collect();
+                assert_eq!(single, expected, "len {}", len);
+                assert_eq!(searcher.windows(&mz, &windows), expected, "len {}", len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_runtime_switch() {
+        set_simd_enabled(false);
+        assert!(!SIMDSearcher::new().is_simd());
+        set_simd_enabled(true);
+        assert_eq!(SIMDSearcher::new().is_simd(), avx2_available());
     }
 }
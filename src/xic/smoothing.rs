@@ -0,0 +1,277 @@
+//! XIC平滑与基线扣除
+//!
+//! 在色谱峰识别之前处理原始XIC，各方法都返回新的[`XICResult`]，并在
+//! [`processing_history`](XICResult::processing_history)末尾记录所做的处理：
+//! - 滑动平均：居中窗口，两端只对窗口内存在的点求平均
+//! - Savitzky-Golay：在每个点两侧的窗口内做最小二乘多项式拟合，取拟合值；两端的点用第一个/最后一个
+//!   完整窗口的拟合多项式求值。按数据点序号计算，假定扫描间隔大致均匀
+//! - 滚动最小值基线：居中窗口内的最小值再做同宽的滑动平均；窗口应宽于色谱峰
+//! - 迭代多项式基线：反复拟合多项式，高于拟合值加一个残差标准差的点视为峰并排除出后续拟合，
+//!   其余点压到该上限以下，直到没有新的点被排除
+//!
+//! 扣除基线后为负的强度取0。
+
+use crate::core::types::*;
+use crate::utils::helpers::{polynomial_fit, polynomial_value};
+use crate::xic::peak_picking::moving_average;
+use crate::xic::result::XICResult;
+
+/// 默认的Savitzky-Golay窗口（数据点数）
+pub const DEFAULT_SG_WINDOW: usize = 7;
+/// 默认的Savitzky-Golay多项式阶数
+pub const DEFAULT_SG_ORDER: usize = 2;
+/// 默认的滚动最小值基线窗口（数据点数）
+pub const DEFAULT_BASELINE_WINDOW: usize = 51;
+/// 默认的多项式基线次数
+pub const DEFAULT_BASELINE_DEGREE: usize = 3;
+/// 多项式基线的默认最大迭代次数
+pub const DEFAULT_BASELINE_ITERATIONS: usize = 100;
+
+/// 基线估计方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaselineMethod {
+    /// 居中窗口内的最小值，再做同宽的滑动平均；`window`为数据点数，偶数加1
+    RollingMinimum { window: usize },
+    /// 迭代多项式拟合，`degree`为多项式次数，最多迭代`iterations`次
+    Polynomial { degree: usize, iterations: usize },
+}
+
+impl BaselineMethod {
+    /// 处理记录中的描述
+    pub fn describe(&self) -> String {
+        match self {
+            Self::RollingMinimum { window } => format!("baseline(rolling_minimum, window={})", window),
+            Self::Polynomial { degree, iterations } => {
+                format!("baseline(polynomial, degree={}, iterations={})", degree, iterations)
+            }
+        }
+    }
+}
+
+/// 以窗口中心为求值点的Savitzky-Golay平滑系数
+///
+/// `window`须为奇数，`order`须小于`window`。
+pub fn savitzky_golay_coefficients(window: usize, order: usize) -> CoreResult<Vec<f64>> {
+    let basis = savitzky_golay_basis(window, order)?;
+    Ok(basis.iter().map(|coefficients| coefficients[0]).collect())
+}
+
+/// Savitzky-Golay平滑，点数少于`window`时原样返回
+pub fn savitzky_golay(values: &[f64], window: usize, order: usize) -> CoreResult<Vec<f64>> {
+    let basis = savitzky_golay_basis(window, order)?;
+    let n = values.len();
+    if n < window {
+        return Ok(values.to_vec());
+    }
+    let half = window / 2;
+    // 窗口内第`position`个点处的拟合值权重
+    let weights = |position: usize| -> Vec<f64> {
+        let z = (position as f64 - half as f64) / half.max(1) as f64;
+        basis.iter().map(|coefficients| polynomial_value(coefficients, z)).collect()
+    };
+    let apply = |start: usize, weights: &[f64]| values[start..start + window].iter().zip(weights).map(|(v, w)| v * w).sum();
+
+    let center = weights(half);
+    let mut smoothed = vec![0.0; n];
+    for (i, value) in smoothed.iter_mut().enumerate().take(n - half).skip(half) {
+        *value = apply(i - half, &center);
+    }
+    for position in 0..half {
+        smoothed[position] = apply(0, &weights(position));
+        smoothed[n - half + position] = apply(n - window, &weights(half + 1 + position));
+    }
+    Ok(smoothed)
+}
+
+/// 每个窗口位置k对应的多项式：对单位向量e_k做最小二乘拟合的系数，自变量缩放到[-1, 1]
+///
+/// 窗口内任意位置的拟合值权重即各多项式在该位置的值。
+fn savitzky_golay_basis(window: usize, order: usize) -> CoreResult<Vec<Vec<f64>>> {
+    if window.is_multiple_of(2) || order >= window {
+        return Err(CoreError::InvalidFormat(format!(
+            "Savitzky-Golay window must be odd and larger than the order, got window={} order={}",
+            window, order
+        )));
+    }
+    let half = window / 2;
+    let z: Vec<f64> = (0..window).map(|i| (i as f64 - half as f64) / half.max(1) as f64).collect();
+    (0..window)
+        .map(|k| {
+            let unit: Vec<f64> = (0..window).map(|i| if i == k { 1.0 } else { 0.0 }).collect();
+            polynomial_fit(&z, &unit, order).ok_or_else(|| {
+                CoreError::InvalidFormat(format!("Savitzky-Golay fit is singular for window={} order={}", window, order))
+            })
+        })
+        .collect()
+}
+
+/// 滚动最小值基线：居中`window`点窗口内的最小值再做同宽的滑动平均
+pub fn rolling_minimum_baseline(values: &[f64], window: usize) -> Vec<f64> {
+    let half = window / 2;
+    let minima: Vec<f64> = (0..values.len())
+        .map(|i| {
+            let hi = (i + half + 1).min(values.len());
+            values[i.saturating_sub(half)..hi].iter().copied().fold(f64::INFINITY, f64::min)
+        })
+        .collect();
+    moving_average(&minima, window)
+}
+
+/// 迭代多项式基线：`x`为横坐标（如保留时间），点数不足时降低多项式次数
+pub fn polynomial_baseline(x: &[f64], values: &[f64], degree: usize, iterations: usize) -> Vec<f64> {
+    let n = x.len().min(values.len());
+    if n == 0 {
+        return Vec::new();
+    }
+    let degree = degree.min(n - 1);
+    let (low, high) = x[..n].iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &xi| (low.min(xi), high.max(xi)));
+    let half_width = (high - low) / 2.0;
+    let scaled: Vec<f64> = x[..n]
+        .iter()
+        .map(|&xi| if half_width > 0.0 { (xi - (low + high) / 2.0) / half_width } else { 0.0 })
+        .collect();
+    // 高于拟合值加一个残差标准差的点视为峰，不再参与后续拟合；其余点压到该上限以下
+    let mut working = values[..n].to_vec();
+    let mut in_fit = vec![true; n];
+    let mut baseline = working.clone();
+    for _ in 0..iterations.max(1) {
+        let (fit_x, fit_y): (Vec<f64>, Vec<f64>) =
+            (0..n).filter(|&i| in_fit[i]).map(|i| (scaled[i], working[i])).unzip();
+        let Some(coefficients) = polynomial_fit(&fit_x, &fit_y, degree) else {
+            break;
+        };
+        baseline = scaled.iter().map(|&xi| polynomial_value(&coefficients, xi)).collect();
+        let residuals: Vec<f64> = (0..n).filter(|&i| in_fit[i]).map(|i| working[i] - baseline[i]).collect();
+        let deviation = (residuals.iter().map(|r| r * r).sum::<f64>() / residuals.len() as f64).sqrt();
+
+        let mut excluded = 0;
+        for i in 0..n {
+            let ceiling = baseline[i] + deviation;
+            if in_fit[i] && working[i] > ceiling {
+                in_fit[i] = false;
+                excluded += 1;
+            }
+            working[i] = working[i].min(ceiling);
+        }
+        if excluded == 0 || in_fit.iter().filter(|&&kept| kept).count() <= degree {
+            break;
+        }
+    }
+    baseline
+}
+
+impl XICResult {
+    /// 复制本结果，强度替换为`intensity_array`并追加一条处理记录
+    fn processed(&self, intensity_array: Vec<f64>, step: String) -> XICResult {
+        let mut result = self.clone();
+        result.intensity_array = intensity_array;
+        result.processing_history.push(step);
+        result
+    }
+
+    /// 滑动平均平滑，`window`为数据点数，偶数加1
+    pub fn smooth_moving_average(&self, window: usize) -> XICResult {
+        let window = window | 1;
+        self.processed(moving_average(&self.intensity_array, window), format!("moving_average(window={})", window))
+    }
+
+    /// Savitzky-Golay平滑，参数要求见[`savitzky_golay_coefficients`]
+    pub fn smooth_savitzky_golay(&self, window: usize, order: usize) -> CoreResult<XICResult> {
+        let smoothed = savitzky_golay(&self.intensity_array, window, order)?;
+        Ok(self.processed(smoothed, format!("savitzky_golay(window={}, order={})", window, order)))
+    }
+
+    /// 估计并扣除基线，扣除后为负的强度取0
+    pub fn subtract_baseline(&self, method: BaselineMethod) -> XICResult {
+        let method = match method {
+            BaselineMethod::RollingMinimum { window } => BaselineMethod::RollingMinimum { window: window | 1 },
+            polynomial => polynomial,
+        };
+        let baseline = match method {
+            BaselineMethod::RollingMinimum { window } => rolling_minimum_baseline(&self.intensity_array, window),
+            BaselineMethod::Polynomial { degree, iterations } => {
+                polynomial_baseline(&self.rt_array, &self.intensity_array, degree, iterations)
+            }
+        };
+        let corrected =
+            self.intensity_array.iter().zip(&baseline).map(|(intensity, base)| (intensity - base).max(0.0)).collect();
+        self.processed(corrected, method.describe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_spectrum::gaussian_noise;
+
+    fn trace(intensity_array: Vec<f64>) -> XICResult {
+        let rt_array = (0..intensity_array.len()).map(|i| i as f64 * 0.5).collect();
+        XICResult::new(rt_array, intensity_array, Vec::new(), 500.0, "test", 2)
+    }
+
+    #[test]
+    fn test_savitzky_golay_coefficients_and_polynomials() {
+        let coefficients = savitzky_golay_coefficients(5, 2).unwrap();
+        let expected = [-3.0, 12.0, 17.0, 12.0, -3.0].map(|c| c / 35.0);
+        assert!(coefficients.iter().zip(expected).all(|(c, e)| (c - e).abs() < 1e-12), "{:?}", coefficients);
+        assert!(savitzky_golay_coefficients(6, 2).is_err());
+        assert!(savitzky_golay_coefficients(5, 5).is_err());
+
+        // 不高于阶数的多项式被完整保留，包括两端的点
+        let cubic: Vec<f64> = (0..20).map(|i| i as f64).map(|x| 2.0 - 0.5 * x + 0.1 * x * x - 0.01 * x * x * x).collect();
+        let smoothed = savitzky_golay(&cubic, 7, 3).unwrap();
+        assert!(smoothed.iter().zip(&cubic).all(|(s, c)| (s - c).abs() < 1e-9));
+        assert_eq!(savitzky_golay(&[1.0, 5.0], 5, 2).unwrap(), vec![1.0, 5.0]);
+    }
+
+    #[test]
+    fn test_savitzky_golay_smooths_noisy_gaussian() {
+        let mut seed = 21;
+        let sigma = 1000.0;
+        let truth: Vec<f64> = (0..400).map(|i| 1e5 * (-0.5 * ((i as f64 - 200.0) / 25.0).powi(2)).exp()).collect();
+        let noisy: Vec<f64> = truth.iter().map(|t| t + sigma * gaussian_noise(&mut seed)).collect();
+        let xic = trace(noisy.clone());
+        let smoothed = xic.smooth_savitzky_golay(5, 2).unwrap();
+        assert_eq!(smoothed.processing_history, vec!["savitzky_golay(window=5, order=2)".to_string()]);
+        assert_eq!(xic.processing_history, Vec::<String>::new());
+
+        // 峰宽远大于窗口时偏差可以忽略，残差为噪声乘以系数平方和的平方根：sqrt(17/35)
+        let expected_rms = sigma * (17.0f64 / 35.0).sqrt();
+        let residual_rms =
+            (smoothed.intensity_array.iter().zip(&truth).map(|(s, t)| (s - t).powi(2)).sum::<f64>() / 400.0).sqrt();
+        assert!((residual_rms / expected_rms - 1.0).abs() < 0.1, "residual rms {} vs {}", residual_rms, expected_rms);
+        assert!((smoothed.intensity_array[200] - 1e5).abs() < 3.0 * expected_rms);
+
+        let averaged = xic.smooth_moving_average(4);
+        assert_eq!(averaged.processing_history, vec!["moving_average(window=5)".to_string()]);
+        assert_eq!(averaged.intensity_array[10], noisy[8..13].iter().sum::<f64>() / 5.0);
+    }
+
+    #[test]
+    fn test_baselines_remove_constant_offset() {
+        let flat = trace(vec![250.0; 60]);
+        for method in [
+            BaselineMethod::RollingMinimum { window: DEFAULT_BASELINE_WINDOW },
+            BaselineMethod::Polynomial { degree: DEFAULT_BASELINE_DEGREE, iterations: DEFAULT_BASELINE_ITERATIONS },
+        ] {
+            let corrected = flat.subtract_baseline(method);
+            assert!(corrected.intensity_array.iter().all(|&intensity| intensity.abs() < 1e-6), "{:?}", method);
+            assert_eq!(corrected.processing_history, vec![method.describe()]);
+        }
+
+        // 常数偏移上的色谱峰：扣除后峰外为0，峰高保持
+        let peak: Vec<f64> = (0..200).map(|i| 250.0 + 1e4 * (-0.5 * ((i as f64 - 100.0) / 4.0).powi(2)).exp()).collect();
+        let xic = trace(peak);
+        let rolling = xic.subtract_baseline(BaselineMethod::RollingMinimum { window: 50 });
+        assert_eq!(rolling.processing_history, vec!["baseline(rolling_minimum, window=51)".to_string()]);
+        assert!(rolling.intensity_array[..60].iter().all(|&intensity| intensity < 1e-6));
+        assert!((rolling.intensity_array[100] - 1e4).abs() < 1.0);
+        let polynomial = xic.subtract_baseline(BaselineMethod::Polynomial { degree: 2, iterations: 200 });
+        assert!(polynomial.intensity_array[..60].iter().all(|&intensity| intensity < 1e-6));
+        assert!((polynomial.intensity_array[100] - 1e4).abs() < 1.0);
+
+        let chained = rolling.smooth_moving_average(3);
+        assert_eq!(chained.processing_history.len(), 2);
+        assert!(trace(Vec::new()).subtract_baseline(BaselineMethod::Polynomial { degree: 3, iterations: 10 }).intensity_array.is_empty());
+    }
+}
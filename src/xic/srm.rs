@@ -0,0 +1,218 @@
+//! SRM/MRM跃迁提取
+//!
+//! 靶向实验的跃迁数据有两种存放方式，都实现了[`TransitionSource`]：
+//! - 窄窗口MS2谱图：选取分离窗口包含前体离子m/z的MS2扫描，取产物离子m/z容差内的强度；
+//!   没有记录分离窗口的扫描按前体离子m/z与容差比较。结果按保留时间排序，只包含有信号的扫描
+//! - SRM色谱图：按前体离子和产物离子的分离窗口目标m/z匹配，时间换算为秒
+//!
+//! 结果的`mz`为产物离子m/z，`ion_type`为"前体离子m/z>产物离子m/z"。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::parsers::mzml::chromatogram::Chromatogram;
+use crate::utils::helpers::find_mz_in_tolerance;
+use crate::xic::result::{PeakCombination, XICResult};
+
+/// 可以提取跃迁轨迹的数据
+pub trait TransitionSource {
+    /// 提取一个(前体离子m/z, 产物离子m/z)跃迁的轨迹
+    fn transition_trace(
+        &self,
+        transition: (f64, f64),
+        precursor_tol: &impl MzTolerance,
+        product_tol: &impl MzTolerance,
+    ) -> XICResult;
+}
+
+/// 按`transitions`的顺序提取各跃迁的轨迹
+pub fn extract_transitions<S: TransitionSource + ?Sized>(
+    source: &S,
+    transitions: &[(f64, f64)],
+    precursor_tol: impl MzTolerance,
+    product_tol: impl MzTolerance,
+) -> Vec<XICResult> {
+    transitions
+        .iter()
+        .map(|&transition| source.transition_trace(transition, &precursor_tol, &product_tol))
+        .collect()
+}
+
+/// 跃迁的离子类型标签
+fn transition_label((precursor_mz, product_mz): (f64, f64)) -> String {
+    format!("{}>{}", precursor_mz, product_mz)
+}
+
+/// MS2扫描是否选择了`precursor_mz`
+fn selects_precursor(spectrum: &Spectrum, precursor_mz: f64, precursor_tol: &impl MzTolerance) -> bool {
+    let Some(precursor) = spectrum.precursor.as_deref().filter(|_| spectrum.level > 1) else {
+        return false;
+    };
+    let (lower, upper) = precursor.isolation_window;
+    if upper > lower {
+        (lower..=upper).contains(&precursor_mz)
+    } else {
+        precursor_tol.is_within_tolerance(precursor_mz, precursor.mz)
+    }
+}
+
+impl TransitionSource for [Spectrum] {
+    fn transition_trace(
+        &self,
+        transition: (f64, f64),
+        precursor_tol: &impl MzTolerance,
+        product_tol: &impl MzTolerance,
+    ) -> XICResult {
+        let (precursor_mz, product_mz) = transition;
+        let mut spectra: Vec<&Spectrum> =
+            self.iter().filter(|spectrum| selects_precursor(spectrum, precursor_mz, precursor_tol)).collect();
+        spectra.sort_by(|a, b| a.scan.retention_time.total_cmp(&b.scan.retention_time));
+
+        let tolerance = product_tol.tolerance_at_mz(product_mz);
+        let mut rt_array = Vec::new();
+        let mut intensity_array = Vec::new();
+        let mut mz_observed_array = Vec::new();
+        for spectrum in spectra {
+            let matching = find_mz_in_tolerance(spectrum.mz_slice(), product_mz, tolerance);
+            let combined =
+                PeakCombination::Sum.combine(spectrum.mz_slice(), spectrum.intensity_slice(), &matching, product_mz);
+            if let Some((intensity, observed)) = combined {
+                rt_array.push(spectrum.scan.retention_time);
+                intensity_array.push(intensity);
+                mz_observed_array.push(observed);
+            }
+        }
+        XICResult::new(rt_array, intensity_array, mz_observed_array, product_mz, &transition_label(transition), 0)
+    }
+}
+
+impl TransitionSource for [Chromatogram] {
+    /// 前体离子和产物离子都在容差内的色谱图中取两者偏差之和最小的一个，没有时返回空轨迹
+    fn transition_trace(
+        &self,
+        transition: (f64, f64),
+        precursor_tol: &impl MzTolerance,
+        product_tol: &impl MzTolerance,
+    ) -> XICResult {
+        let (precursor_mz, product_mz) = transition;
+        let best = self
+            .iter()
+            .filter_map(|chromatogram| {
+                let (precursor, product) = (chromatogram.precursor_mz()?, chromatogram.product_mz()?);
+                let matches = precursor_tol.is_within_tolerance(precursor_mz, precursor)
+                    && product_tol.is_within_tolerance(product_mz, product);
+                matches.then(|| ((precursor - precursor_mz).abs() + (product - product_mz).abs(), chromatogram, product))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+
+        let label = transition_label(transition);
+        match best {
+            Some((_, chromatogram, product)) => XICResult::new(
+                chromatogram.times_in_seconds(),
+                chromatogram.intensity_array.clone(),
+                vec![product; chromatogram.len()],
+                product_mz,
+                &label,
+                0,
+            ),
+            None => XICResult::new(Vec::new(), Vec::new(), Vec::new(), product_mz, &label, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+    use crate::parsers::common::CVParam;
+    use crate::parsers::mzml::spectrum::{MZMLIsolationWindow, MZMLPrecursor};
+
+    fn srm_scan(rt: f64, window: (f64, f64), peaks: &[Peak]) -> Spectrum {
+        let mut spectrum = Spectrum::new(2).unwrap();
+        spectrum.set_retention_time(rt).unwrap();
+        spectrum.add_peaks(peaks.iter().copied()).unwrap();
+        spectrum.set_precursor(PrecursorInfo {
+            mz: (window.0 + window.1) / 2.0,
+            isolation_window: window,
+            ..Default::default()
+        });
+        spectrum
+    }
+
+    #[test]
+    fn test_spectrum_transitions_stay_in_their_window() {
+        // 两个分离窗口交替采集，都含有m/z 600.3的产物离子，但强度不同
+        let mut spectra = Vec::new();
+        for cycle in 0..4 {
+            let rt = cycle as f64 * 2.0;
+            spectra.push(srm_scan(rt, (499.8, 500.6), &[(600.3, 100.0 + cycle as f64), (700.4, 5.0)]));
+            spectra.push(srm_scan(rt + 1.0, (549.8, 550.6), &[(600.3, 1000.0 + cycle as f64)]));
+        }
+        spectra.reverse();
+        let mut ms1 = Spectrum::ms1().unwrap();
+        ms1.add_peak(600.3, 1e6).unwrap();
+        spectra.push(ms1);
+
+        let traces = extract_transitions(
+            spectra.as_slice(),
+            &[(500.2, 600.3), (550.2, 600.3), (500.2, 700.4), (600.0, 600.3)],
+            Tolerance::PPM(20.0),
+            Tolerance::PPM(20.0),
+        );
+        assert_eq!(traces[0].rt_array, vec![0.0, 2.0, 4.0, 6.0]);
+        assert_eq!(traces[0].intensity_array, vec![100.0, 101.0, 102.0, 103.0]);
+        assert_eq!(traces[0].ion_type, "500.2>600.3");
+        assert_eq!(traces[1].rt_array, vec![1.0, 3.0, 5.0, 7.0]);
+        assert_eq!(traces[1].intensity_array, vec![1000.0, 1001.0, 1002.0, 1003.0]);
+        assert_eq!(traces[2].intensity_array, vec![5.0; 4]);
+        assert!(traces[3].rt_array.is_empty());
+        assert_eq!(traces[0].mz, 600.3);
+    }
+
+    #[test]
+    fn test_scans_without_window_match_precursor_mz() {
+        let mut scan = srm_scan(1.0, (0.0, 0.0), &[(300.0, 7.0)]);
+        scan.precursor.as_deref_mut().unwrap().mz = 400.001;
+        let spectra = [scan];
+        let traces = extract_transitions(&spectra[..], &[(400.0, 300.0), (400.1, 300.0)], Tolerance::PPM(10.0), Tolerance::Absolute(0.01));
+        assert_eq!(traces[0].intensity_array, vec![7.0]);
+        assert!(traces[1].intensity_array.is_empty());
+    }
+
+    fn srm_chromatogram(id: &str, precursor_mz: f64, product_mz: f64, intensity: f64) -> Chromatogram {
+        let window = |mz: f64| MZMLIsolationWindow {
+            cv_params: vec![CVParam::new("MS:1000827", "isolation window target m/z", mz.to_string())],
+            ..Default::default()
+        };
+        let mut chromatogram = Chromatogram::new(id.to_string(), 2);
+        chromatogram.precursor = Some(MZMLPrecursor { isolation_windows: vec![window(precursor_mz)], ..Default::default() });
+        chromatogram.product = Some(window(product_mz));
+        chromatogram.time_array = vec![0.5, 1.0];
+        chromatogram.intensity_array = vec![intensity, intensity * 2.0];
+        chromatogram.time_unit = Some("minute".to_string());
+        chromatogram
+    }
+
+    #[test]
+    fn test_chromatogram_transitions() {
+        let mut tic = Chromatogram::new("TIC".to_string(), 1);
+        tic.time_array = vec![0.0];
+        tic.intensity_array = vec![1.0];
+        let chromatograms = vec![
+            tic,
+            srm_chromatogram("a", 500.2, 600.3, 10.0),
+            srm_chromatogram("b", 550.2, 600.3, 20.0),
+        ];
+
+        let traces = extract_transitions(
+            chromatograms.as_slice(),
+            &[(550.2, 600.3), (500.2, 600.3), (500.2, 650.0)],
+            Tolerance::PPM(20.0),
+            Tolerance::PPM(20.0),
+        );
+        assert_eq!(traces[0].intensity_array, vec![20.0, 40.0]);
+        assert_eq!(traces[0].rt_array, vec![30.0, 60.0]);
+        assert_eq!(traces[1].intensity_array, vec![10.0, 20.0]);
+        assert_eq!(traces[1].ppm_error, 0.0);
+        assert!(traces[2].rt_array.is_empty());
+    }
+}
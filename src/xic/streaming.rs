@@ -0,0 +1,226 @@
+//! 流式XIC提取
+//!
+//! 边解析边更新XIC，用于采集中文件的准实时处理。谱图应按保留时间顺序到达；
+//! 最近的若干个MS1数据点先放在重排缓冲区中按保留时间排序，超出缓冲区的点才并入曲线，
+//! 因此缓冲区大小以内的乱序不影响结果。比已并入曲线的点还早到达的点会被插入到曲线中的
+//! 对应位置。
+
+use crate::core::spectrum::Spectrum;
+use crate::core::types::*;
+use crate::utils::helpers::find_mz_in_tolerance;
+use crate::xic::result::{PeakCombination, XICResult};
+use log::debug;
+
+/// 默认的重排缓冲区大小（MS1谱图数）
+pub const DEFAULT_REORDER_WINDOW: usize = 8;
+
+/// 一张MS1谱图中各目标的(强度, 观测m/z)，没有匹配峰的目标为None
+type PendingPoint = (RetentionTime, Vec<Option<(f64, f64)>>);
+
+/// 一个目标已并入的曲线：保留时间、强度和观测m/z
+type Trace = (Vec<f64>, Vec<f64>, Vec<f64>);
+
+/// 流式XIC提取器
+///
+/// 与[`XICSExtractor`](crate::xic::XICSExtractor)对按保留时间排序的同一组谱图提取的结果一致：
+/// 只使用MS1谱图，同一张谱图中容差内的峰强度相加（[`PeakCombination::Sum`]），
+/// 没有匹配峰的扫描不产生数据点。
+#[derive(Debug, Clone)]
+pub struct StreamingXICExtractor {
+    /// 目标m/z
+    targets: Vec<f64>,
+    /// 各目标的容差 (Da)
+    tolerances: Vec<f64>,
+    /// 重排缓冲区大小
+    reorder_window: usize,
+    /// 尚未并入曲线的数据点，按保留时间升序
+    pending: Vec<PendingPoint>,
+    /// 各目标已并入的曲线
+    traces: Vec<Trace>,
+    /// 已接收的谱图数（含非MS1谱图）
+    ingested: usize,
+}
+
+impl StreamingXICExtractor {
+    /// 创建流式提取器，`ppm`为m/z容差
+    pub fn new(targets: Vec<f64>, ppm: f64) -> Self {
+        let tolerances = targets.iter().map(|&mz| Tolerance::PPM(ppm).tolerance_at_mz(mz)).collect();
+        let traces = vec![Trace::default(); targets.len()];
+        Self {
+            targets,
+            tolerances,
+            reorder_window: DEFAULT_REORDER_WINDOW,
+            pending: Vec::new(),
+            traces,
+            ingested: 0,
+        }
+    }
+
+    /// 设置重排缓冲区大小，0表示每个数据点立即并入曲线
+    pub fn with_reorder_window(mut self, reorder_window: usize) -> Self {
+        self.reorder_window = reorder_window;
+        self
+    }
+
+    /// 目标m/z
+    pub fn targets(&self) -> &[f64] {
+        &self.targets
+    }
+
+    /// 已接收的谱图数
+    pub fn ingested(&self) -> usize {
+        self.ingested
+    }
+
+    /// 接收一张谱图，非MS1谱图只计数
+    pub fn ingest(&mut self, spectrum: &Spectrum) {
+        self.ingested += 1;
+        if !spectrum.is_ms1() {
+            return;
+        }
+
+        let intensities = self
+            .targets
+            .iter()
+            .zip(&self.tolerances)
+            .map(|(&mz, &tolerance)| {
+                let matching = find_mz_in_tolerance(spectrum.mz_slice(), mz, tolerance);
+                PeakCombination::Sum.combine(spectrum.mz_slice(), spectrum.intensity_slice(), &matching, mz)
+            })
+            .collect();
+
+        let rt = spectrum.scan.retention_time;
+        let position = self.pending.partition_point(|(pending_rt, _)| *pending_rt <= rt);
+        self.pending.insert(position, (rt, intensities));
+        while self.pending.len() > self.reorder_window {
+            let point = self.pending.remove(0);
+            self.commit(point);
+        }
+    }
+
+    /// 把缓冲区中的所有数据点并入曲线
+    pub fn flush(&mut self) {
+        for point in std::mem::take(&mut self.pending) {
+            self.commit(point);
+        }
+    }
+
+    /// 当前的XIC（含缓冲区中尚未并入的数据点）
+    pub fn snapshot(&self) -> Vec<XICResult> {
+        let mut current = self.clone();
+        current.flush();
+        current.into_results()
+    }
+
+    /// 结束接收，返回最终的XIC
+    pub fn finish(mut self) -> Vec<XICResult> {
+        self.flush();
+        self.into_results()
+    }
+
+    /// 把一个数据点并入各目标的曲线，早于曲线末端的点插入到对应位置
+    fn commit(&mut self, (rt, intensities): PendingPoint) {
+        for ((rts, values, observed), point) in self.traces.iter_mut().zip(intensities) {
+            let Some((intensity, observed_mz)) = point else {
+                continue;
+            };
+            if rts.last().is_some_and(|&last| rt < last) {
+                debug!("Spectrum at RT {} arrived after the reorder window", rt);
+                let position = rts.partition_point(|&existing| existing <= rt);
+                rts.insert(position, rt);
+                values.insert(position, intensity);
+                observed.insert(position, observed_mz);
+            } else {
+                rts.push(rt);
+                values.push(intensity);
+                observed.push(observed_mz);
+            }
+        }
+    }
+
+    fn into_results(self) -> Vec<XICResult> {
+        self.targets
+            .iter()
+            .zip(self.traces)
+            .map(|(&mz, (rt_array, intensity_array, observed))| XICResult::new(rt_array, intensity_array, observed, mz, "", 0))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::spectrum::PrecursorInfo;
+    use crate::xic::XICSExtractor;
+
+    fn run() -> Vec<Spectrum> {
+        let mut spectra = Vec::new();
+        for cycle in 0..12 {
+            let rt = 60.0 + cycle as f64 * 2.0;
+            let mut ms1 = Spectrum::ms1().unwrap();
+            ms1.add_peak(500.0, 1000.0 + cycle as f64 * 10.0).unwrap();
+            if cycle % 3 != 0 {
+                ms1.add_peaks([(650.3, 50.0 * cycle as f64), (650.301, 1.0)]).unwrap();
+            }
+            ms1.set_retention_time(rt).unwrap();
+            spectra.push(ms1);
+
+            let mut ms2 = Spectrum::ms2().unwrap();
+            ms2.add_peak(500.0, 1e6).unwrap();
+            ms2.set_retention_time(rt + 1.0).unwrap();
+            ms2.set_precursor(PrecursorInfo { mz: 500.0, ..Default::default() });
+            spectra.push(ms2);
+        }
+        spectra
+    }
+
+    fn assert_same_traces(streamed: &[XICResult], batch: &[XICResult]) {
+        assert_eq!(streamed.len(), batch.len());
+        for (streamed, batch) in streamed.iter().zip(batch) {
+            assert_eq!(streamed.mz, batch.mz);
+            assert_eq!(streamed.rt_array, batch.rt_array);
+            assert_eq!(streamed.intensity_array, batch.intensity_array);
+            assert_eq!(streamed.mz_observed_array, batch.mz_observed_array);
+            assert_eq!(streamed.ppm_error, batch.ppm_error);
+        }
+    }
+
+    #[test]
+    fn test_streamed_traces_match_batch() {
+        let spectra = run();
+        let targets = [500.0, 650.3, 800.0];
+        let extractor = XICSExtractor::from_spectra(spectra.clone(), 10.0, 1.0).unwrap();
+        let batch = extractor
+            .extract_batch_xics(&targets.map(|mz| (mz, 0, "")), 0.0, f64::INFINITY)
+            .unwrap();
+
+        // 交换两张相邻的MS1谱图，乱序在重排缓冲区之内
+        let mut arrivals = spectra;
+        arrivals.swap(4, 6);
+        let mut streaming = StreamingXICExtractor::new(targets.to_vec(), 10.0).with_reorder_window(2);
+        for (count, spectrum) in arrivals.iter().enumerate() {
+            streaming.ingest(spectrum);
+            if count == 9 {
+                let snapshot = streaming.snapshot();
+                assert_eq!(snapshot[0].rt_array, vec![60.0, 62.0, 64.0, 66.0, 68.0]);
+            }
+        }
+        assert_eq!(streaming.ingested(), 24);
+        assert_same_traces(&streaming.snapshot(), &batch);
+        assert_same_traces(&streaming.finish(), &batch);
+        assert!(batch[2].rt_array.is_empty());
+    }
+
+    #[test]
+    fn test_late_point_inserted_in_order() {
+        let spectra: Vec<Spectrum> = run().into_iter().filter(|spectrum| spectrum.is_ms1()).collect();
+        let mut streaming = StreamingXICExtractor::new(vec![500.0], 10.0).with_reorder_window(0);
+        for spectrum in spectra.iter().skip(1) {
+            streaming.ingest(spectrum);
+        }
+        streaming.ingest(&spectra[0]);
+        let traces = streaming.finish();
+        assert_eq!(traces[0].rt_array.first(), Some(&60.0));
+        assert!(traces[0].rt_array.is_sorted());
+    }
+}
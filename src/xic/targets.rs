@@ -0,0 +1,240 @@
+//! XIC目标展开
+//!
+//! 给定中性质量或肽段序列，按指定的电荷态、加合物和同位素峰展开为
+//! 完整的(m/z, 电荷, 标签)目标列表，可直接传给[`XICSExtractor::extract_batch_xics`]。
+//!
+//! [`XICSExtractor::extract_batch_xics`]: crate::xic::XICSExtractor::extract_batch_xics
+
+use crate::core::types::*;
+use crate::utils::mass::{mz_from_neutral, peptide_mass, Adduct, ISOTOPE_SPACING};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::{PyDict, PyList};
+
+/// 单个XIC提取目标
+#[derive(Debug, Clone, PartialEq)]
+pub struct XICTarget {
+    /// 目标m/z
+    pub mz: f64,
+    /// 带符号的电荷数（负离子模式加合物为负）
+    pub charge: Charge,
+    /// 加合物名称
+    pub adduct: String,
+    /// 同位素峰序号，0为单同位素峰
+    pub isotope: usize,
+    /// 标签，如"M+Na [+1] iso1"
+    pub label: String,
+}
+
+impl XICTarget {
+    /// 转换为`extract_batch_xics`使用的元组
+    pub fn as_batch_target(&self) -> (f64, Charge, &str) {
+        (self.mz, self.charge, &self.label)
+    }
+}
+
+/// XIC目标构建器
+#[derive(Debug, Clone)]
+pub struct XICTargetBuilder {
+    neutral_mass: f64,
+    name: String,
+    charges: Vec<Charge>,
+    adducts: Vec<String>,
+    num_isotopes: usize,
+}
+
+impl XICTargetBuilder {
+    /// 以中性质量创建构建器，默认电荷1-3、加合物"+H"和"+Na"、2个同位素峰
+    pub fn from_mass(neutral_mass: f64) -> Self {
+        Self {
+            neutral_mass,
+            name: "M".to_string(),
+            charges: vec![1, 2, 3],
+            adducts: vec!["+H".to_string(), "+Na".to_string()],
+            num_isotopes: 2,
+        }
+    }
+
+    /// 以未修饰肽段序列创建构建器，标签以序列代替"M"
+    pub fn from_sequence(sequence: &str) -> CoreResult<Self> {
+        let mut builder = Self::from_mass(peptide_mass(sequence)?);
+        builder.name = sequence.trim().to_string();
+        Ok(builder)
+    }
+
+    /// 设置电荷态（取绝对值，极性由加合物决定）
+    pub fn with_charges(mut self, charges: impl IntoIterator<Item = Charge>) -> Self {
+        self.charges = charges.into_iter().collect();
+        self
+    }
+
+    /// 设置加合物名称
+    pub fn with_adducts<S: Into<String>>(mut self, adducts: impl IntoIterator<Item = S>) -> Self {
+        self.adducts = adducts.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 设置同位素峰数（包含单同位素峰）
+    pub fn with_isotopes(mut self, num_isotopes: usize) -> Self {
+        self.num_isotopes = num_isotopes;
+        self
+    }
+
+    /// 中性质量
+    pub fn neutral_mass(&self) -> f64 {
+        self.neutral_mass
+    }
+
+    /// 生成目标列表，按加合物、电荷、同位素的顺序排列
+    ///
+    /// `+2H`这类固定载体数的加合物只生成对应电荷的目标；未知加合物或0电荷返回错误。
+    pub fn build(&self) -> CoreResult<Vec<XICTarget>> {
+        let mut targets = Vec::new();
+        for name in &self.adducts {
+            let adduct = Adduct::from_name(name)?;
+            for &charge in &self.charges {
+                let z = charge.unsigned_abs();
+                if z == 0 {
+                    return Err(CoreError::InvalidCharge { charge, min: 1, max: Charge::MAX });
+                }
+                if adduct.fixed_count.is_some_and(|count| count != z) {
+                    continue;
+                }
+                let monoisotopic = mz_from_neutral(self.neutral_mass, charge, adduct.name)?;
+                let signed = if adduct.is_negative() { -(z as Charge) } else { z as Charge };
+                for isotope in 0..self.num_isotopes {
+                    targets.push(XICTarget {
+                        mz: monoisotopic + isotope as f64 * ISOTOPE_SPACING / z as f64,
+                        charge: signed,
+                        adduct: adduct.name.to_string(),
+                        isotope,
+                        label: format!("{}{} [{:+}] iso{}", self.name, adduct.name, signed, isotope),
+                    });
+                }
+            }
+        }
+        Ok(targets)
+    }
+}
+
+/// Python可用的XIC目标构建器
+#[cfg(feature = "python")]
+#[pyclass(name = "XICTargetBuilder")]
+pub struct PyXICTargetBuilder {
+    builder: XICTargetBuilder,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyXICTargetBuilder {
+    /// 创建构建器，`neutral_mass`与`sequence`二选一
+    #[new]
+    #[pyo3(signature = (neutral_mass=None, sequence=None, charges=None, adducts=None, isotopes=2))]
+    fn new(
+        neutral_mass: Option<f64>,
+        sequence: Option<&str>,
+        charges: Option<Vec<Charge>>,
+        adducts: Option<Vec<String>>,
+        isotopes: usize,
+    ) -> PyResult<Self> {
+        let builder = match (neutral_mass, sequence) {
+            (Some(mass), None) => XICTargetBuilder::from_mass(mass),
+            (None, Some(sequence)) => XICTargetBuilder::from_sequence(sequence)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?,
+            _ => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "exactly one of neutral_mass or sequence must be given",
+                ))
+            }
+        };
+        let mut builder = builder.with_isotopes(isotopes);
+        if let Some(charges) = charges {
+            builder = builder.with_charges(charges);
+        }
+        if let Some(adducts) = adducts {
+            builder = builder.with_adducts(adducts);
+        }
+        Ok(Self { builder })
+    }
+
+    /// 中性质量
+    #[getter]
+    fn neutral_mass(&self) -> f64 {
+        self.builder.neutral_mass()
+    }
+
+    /// 生成目标列表，每个目标为包含mz、charge、adduct、isotope和label的字典
+    fn targets<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let targets = self
+            .builder
+            .build()
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let list = PyList::empty(py);
+        for target in targets {
+            let dict = PyDict::new(py);
+            dict.set_item("mz", target.mz)?;
+            dict.set_item("charge", target.charge)?;
+            dict.set_item("adduct", target.adduct)?;
+            dict.set_item("isotope", target.isotope)?;
+            dict.set_item("label", target.label)?;
+            list.append(dict)?;
+        }
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_expand_500_da_compound() {
+        let targets = XICTargetBuilder::from_mass(500.0)
+            .with_adducts(["+H", "+Na"])
+            .with_charges([1, 2])
+            .with_isotopes(2)
+            .build()
+            .unwrap();
+        assert_eq!(targets.len(), 8);
+
+        // 手算：H+ = 1.007276467，Na+ = 22.989769282 - 0.000548580 = 22.989220702
+        let expected = [
+            ("M+H [+1] iso0", 501.007_276_467),
+            ("M+H [+1] iso1", 502.010_631_305),
+            ("M+H [+2] iso0", 251.007_276_467),
+            ("M+H [+2] iso1", 251.508_953_886),
+            ("M+Na [+1] iso0", 522.989_220_702),
+            ("M+Na [+1] iso1", 523.992_575_540),
+            ("M+Na [+2] iso0", 272.989_220_702),
+            ("M+Na [+2] iso1", 273.490_898_121),
+        ];
+        for (target, (label, mz)) in targets.iter().zip(expected) {
+            assert_eq!(target.label, label);
+            assert_close(target.mz, mz);
+        }
+        assert_eq!(targets[3].as_batch_target(), (targets[3].mz, 2, "M+H [+2] iso1"));
+    }
+
+    #[test]
+    fn test_fixed_and_negative_adducts() {
+        let targets = XICTargetBuilder::from_sequence("PEPTIDE")
+            .unwrap()
+            .with_adducts(["+2H", "-H"])
+            .with_charges([1, 2])
+            .with_isotopes(1)
+            .build()
+            .unwrap();
+        let labels: Vec<&str> = targets.iter().map(|t| t.label.as_str()).collect();
+        assert_eq!(labels, vec!["PEPTIDE+2H [+2] iso0", "PEPTIDE-H [-1] iso0", "PEPTIDE-H [-2] iso0"]);
+        assert_eq!(targets[2].charge, -2);
+
+        assert!(XICTargetBuilder::from_mass(500.0).with_adducts(["+Xe"]).build().is_err());
+        assert!(XICTargetBuilder::from_mass(500.0).with_charges([0]).build().is_err());
+    }
+}
@@ -0,0 +1,879 @@
+# Type stubs for the Rust extension module `_openms_utils_rust`.
+#
+# Keep in sync with the #[pymethods] in src/. `cargo test stub` checks that every
+# exported class, function, method and attribute is declared here and that the
+# parameter names match the runtime signatures.
+#
+# Everything is also re-exported from the submodules `core`, `mzml`, `mzxml`, `mgf`,
+# `conversion`, `search`, `analysis`, `xic`, `dia`, `ion_mobility` and `utils`
+# (see SUBMODULES in src/lib.rs); `cargo test submodules` checks the grouping.
+
+import os
+from typing import Any, Callable, Dict, Iterator, List, Mapping, Optional, Sequence, Tuple, Union
+
+import numpy as np
+import numpy.typing as npt
+
+__version__: str
+
+Peak = Tuple[float, float]
+# Paths accept str or any os.PathLike such as pathlib.Path
+StrPath = Union[str, os.PathLike[str]]
+
+class TestMSObject:
+    level: int
+    scan_number: int
+    retention_time: float
+    def __init__(self, level: int) -> None: ...
+    @property
+    def peaks(self) -> List[Peak]: ...
+    def add_peak(self, mz: float, intensity: float) -> None: ...
+    def sort_peaks(self) -> None: ...
+    def peak_count(self) -> int: ...
+    def total_ion_current(self) -> float: ...
+
+class Spectrum:
+    level: int
+    scan_number: int
+    retention_time: float
+    def __init__(self, level: int) -> None: ...
+    @staticmethod
+    def with_peaks(level: int, mz_array: npt.ArrayLike, intensity_array: npt.ArrayLike) -> Spectrum: ...
+    @property
+    def peaks(self) -> List[Peak]: ...
+    def peaks_numpy(self) -> npt.NDArray[np.float64]: ...
+    @property
+    def peak_count(self) -> int: ...
+    @property
+    def total_ion_current(self) -> float: ...
+    @property
+    def base_peak_intensity(self) -> float: ...
+    @property
+    def base_peak_mz(self) -> float: ...
+    @property
+    def mz_array(self) -> npt.NDArray[np.float64]: ...
+    @property
+    def intensity_array(self) -> npt.NDArray[np.float64]: ...
+    @property
+    def intensity_transforms(self) -> List[str]: ...
+    def add_peak(self, mz: float, intensity: float) -> None: ...
+    def add_peaks(self, mz_array: npt.ArrayLike, intensity_array: npt.ArrayLike) -> None: ...
+    def sort_peaks(self) -> None: ...
+    def is_sorted(self) -> bool: ...
+    def clear_peaks(self) -> None: ...
+    def filter_by_intensity(self, threshold: float) -> int: ...
+    def filter_by_mz_range(self, min_mz: float, max_mz: float) -> int: ...
+    def keep_top_n(self, n: int) -> int: ...
+    def filter_by_relative_intensity(self, fraction: float) -> int: ...
+    def get_mz_range(self, min_mz: float, max_mz: float) -> Spectrum: ...
+    def find_peaks_in_tolerance(self, target_mz: float, tolerance: float) -> List[Peak]: ...
+    def find_peaks_in_ppm(self, target_mz: float, ppm: float) -> List[Peak]: ...
+    def normalize(self) -> float: ...
+    def fingerprint(self, mz_precision_da: float = 0.01, top_n: int = 30) -> str: ...
+    def transform_intensities(self, method: str, force: bool = False) -> None: ...
+    def inverse_intensity_transform(self) -> str: ...
+
+class MZMLParser:
+    def __init__(self, file_path: StrPath) -> None: ...
+    @property
+    def file_path(self) -> str: ...
+    @property
+    def version(self) -> Optional[str]: ...
+    @property
+    def cancelled(self) -> bool: ...
+    def parse_all_spectra(self) -> List[MSObject]: ...
+    def parse_spectra_with_callback(
+        self, callback: Optional[Callable[[int, int, int], Any]] = None, every: int = 100
+    ) -> List[MSObject]: ...
+    def validate_file(self) -> bool: ...
+
+class MZMLUtils:
+    @staticmethod
+    def is_valid_mzml(file_path: StrPath) -> bool: ...
+    @staticmethod
+    def get_file_info(file_path: StrPath) -> Dict[str, Any]: ...
+    @staticmethod
+    def extract_subset(
+        input_path: StrPath,
+        output_path: StrPath,
+        rt_range: Optional[Tuple[float, float]] = None,
+        scan_range: Optional[Tuple[int, int]] = None,
+        ms_levels: Optional[Sequence[int]] = None,
+    ) -> Dict[str, int]: ...
+    @staticmethod
+    def diff_files(
+        path_a: StrPath,
+        path_b: StrPath,
+        mz_tolerance_ppm: float = 5.0,
+        intensity_rel_tol: float = 0.01,
+    ) -> Dict[str, Any]: ...
+
+class KeyValue:
+    key: str
+    value: str
+    def __init__(self, key: str, value: str) -> None: ...
+
+class Precursor:
+    mz: float
+    charge: int
+    ref_scan_number: int
+    activation_method: str
+    activation_energy: float
+    collision_energy_ev: Optional[float]
+    normalized_collision_energy: Optional[float]
+    isolation_window: Tuple[float, float]
+    isolation_target_mz: Optional[float]
+    def __init__(
+        self,
+        mz: float = 0.0,
+        charge: int = 0,
+        ref_scan_number: int = 0,
+        isolation_window: Optional[Tuple[float, float]] = None,
+        activation_method: str = "unknown",
+        activation_energy: float = 0.0,
+    ) -> None: ...
+    @property
+    def neutral_mass(self) -> Optional[float]: ...
+
+class Scan:
+    scan_number: int
+    retention_time: float
+    drift_time: float
+    inverse_k0: Optional[float]
+    injection_time: Optional[float]
+    polarity: str
+    scan_window: Tuple[float, float]
+    def __init__(
+        self,
+        scan_number: int = 0,
+        retention_time: float = 0.0,
+        drift_time: float = 0.0,
+        scan_window: Optional[Tuple[float, float]] = None,
+        additional_info: Optional[Mapping[str, str]] = None,
+        injection_time: Optional[float] = None,
+    ) -> None: ...
+    @property
+    def additional_info(self) -> Dict[str, str]: ...
+
+# Precursor/scan arguments accept the extension classes, plain dicts, or any object
+# exposing the same attribute names (e.g. the pure-Python MSObject classes).
+PrecursorLike = Union[Precursor, Mapping[str, Any], Any]
+ScanLike = Union[Scan, Mapping[str, Any], Any]
+
+class MSObject:
+    level: int
+    peaks: List[Peak]
+    scan_number: int
+    native_id: str
+    retention_time: float
+    polarity: str
+    additional_info: Dict[str, str]
+    def __init__(
+        self,
+        level: int = 1,
+        peaks: Optional[List[Peak]] = None,
+        precursor: Optional[PrecursorLike] = None,
+        scan: Optional[ScanLike] = None,
+        additional_info: Optional[Dict[str, str]] = None,
+    ) -> None: ...
+    @property
+    def precursor(self) -> Optional[Precursor]: ...
+    @precursor.setter
+    def precursor(self, value: Optional[PrecursorLike]) -> None: ...
+    @property
+    def has_precursor(self) -> bool: ...
+    @property
+    def precursor_neutral_mass(self) -> Optional[float]: ...
+    @property
+    def index(self) -> Optional[int]: ...
+    @property
+    def scan(self) -> Scan: ...
+    @property
+    def intensity_transforms(self) -> List[str]: ...
+    def add_peak(self, mz: float, intensity: float) -> None: ...
+    def clear_peaks(self) -> None: ...
+    def sort_peaks(self) -> None: ...
+    def set_precursor(
+        self,
+        ref_scan_number: Optional[int] = None,
+        mz: Optional[float] = None,
+        charge: Optional[int] = None,
+        activation_method: Optional[str] = None,
+        activation_energy: Optional[float] = None,
+        isolation_window: Optional[Tuple[float, float]] = None,
+    ) -> None: ...
+    def set_scan(
+        self,
+        scan_number: Optional[int] = None,
+        retention_time: Optional[float] = None,
+        drift_time: Optional[float] = None,
+        scan_window: Optional[Tuple[float, float]] = None,
+    ) -> None: ...
+    def add_additional_info_item(self, key: str, value: str) -> None: ...
+    def clear_additional_info(self) -> None: ...
+    def peak_count(self) -> int: ...
+    def total_ion_current(self) -> float: ...
+    def base_peak(self) -> Optional[Peak]: ...
+    def mz_range(self) -> Optional[Tuple[float, float]]: ...
+    def get_peaks_in_range(self, min_mz: float, max_mz: float) -> Tuple[npt.NDArray[np.float64], npt.NDArray[np.float64]]: ...
+    def get_peaks_in_tolerance(self, mz: float, ppm: float) -> Tuple[npt.NDArray[np.float64], npt.NDArray[np.float64]]: ...
+    def extract_subspectrum(self, min_mz: float, max_mz: float) -> MSObject: ...
+    def peak_widths(self, min_intensity: float = 0.0) -> List[Tuple[float, float, float]]: ...
+    def median_resolution(self) -> Optional[float]: ...
+    def fingerprint(self, mz_precision_da: float = 0.01, top_n: int = 30) -> str: ...
+    def transform_intensities(self, method: str, force: bool = False) -> None: ...
+    def inverse_intensity_transform(self) -> str: ...
+    def to_vector(
+        self,
+        min_mz: float = 100.0,
+        max_mz: float = 1700.0,
+        bin: float = 1.0,
+        transform: Optional[str] = "sqrt",
+        aggregation: str = "sum",
+        normalize: bool = True,
+    ) -> npt.NDArray[np.float64]: ...
+    def normalize_by_injection_time(self, reference_ms: float = 100.0) -> float: ...
+    def deisotope(self, ppm: float = 10.0, max_charge: int = 4, keep_unassigned: bool = True, annotate_charges: bool = False) -> None: ...
+    def ms2_quality_score(self) -> Dict[str, Any]: ...
+    def estimate_noise(self) -> Dict[str, Any]: ...
+    def denoise(self, snr: float = 3.0) -> int: ...
+    def validate(self) -> None: ...
+    def is_ms1(self) -> bool: ...
+    def is_ms2(self) -> bool: ...
+
+class MZMLFileInfo:
+    @property
+    def file_path(self) -> str: ...
+    @property
+    def spectrum_count(self) -> int: ...
+    @property
+    def ms1_count(self) -> int: ...
+    @property
+    def ms2_count(self) -> int: ...
+    @property
+    def file_format(self) -> str: ...
+    @property
+    def version(self) -> Optional[str]: ...
+    @property
+    def skipped_spectra(self) -> List[Dict[str, Any]]: ...
+    @property
+    def cancelled(self) -> bool: ...
+
+class MZMLObject:
+    @property
+    def spectrum_count(self) -> int: ...
+    @property
+    def ms1_spectra(self) -> List[MSObject]: ...
+    @property
+    def ms2_spectra(self) -> List[MSObject]: ...
+    @property
+    def spectra(self) -> List[MSObject]: ...
+    @property
+    def file_info(self) -> MZMLFileInfo: ...
+    def get_spectrum(self, index: int) -> MSObject: ...
+    def get_spectrum_by_scan_number(self, scan_number: int) -> MSObject: ...
+    def get_spectrum_by_native_id(self, native_id: str) -> MSObject: ...
+    def get_spectra_by_rt_range(self, rt_min: float, rt_max: float) -> List[MSObject]: ...
+    def get_spectra_by_mz_range(self, mz_min: float, mz_max: float) -> List[MSObject]: ...
+    def scan_table(self) -> Dict[str, List[Any]]: ...
+    def dia_scheme(self) -> Dict[str, Any]: ...
+    def correct_precursors(self, ppm: float = 10.0, max_shift: int = 3) -> int: ...
+    def split_segments(
+        self, by: str = "scan_window", hysteresis: int = 3
+    ) -> Tuple[List[MZMLObject], List[float]]: ...
+    def append(self, ms_object: MSObject) -> None: ...
+    def extend(self, ms_objects: Sequence[MSObject]) -> None: ...
+    def remove(self, index: int) -> MSObject: ...
+    def sort_by_rt(self) -> None: ...
+    def renumber_scans(self, start: int = 1) -> None: ...
+    @staticmethod
+    def concat(objects: Sequence[MZMLObject], offset_scan_numbers: bool = False) -> MZMLObject: ...
+    def xic_extractor(
+        self, ppm_tolerance: float = 10.0, bin_size: float = 1.0, tolerance: Optional[ToleranceModel] = None
+    ) -> XICSExtractor: ...
+    def tic(
+        self, ms_level: int = 1, rt_range: Optional[Tuple[float, float]] = None
+    ) -> Tuple[List[float], List[float], int]: ...
+    def bpc(
+        self, ms_level: int = 1, rt_range: Optional[Tuple[float, float]] = None
+    ) -> Tuple[List[float], List[float], int]: ...
+    def spectra_index(self, bin_size: float = 1.0, ms_level: Optional[int] = None) -> SpectraIndex: ...
+    def find_fragment(self, mz: float, ppm: float = 20.0, min_rel_intensity: float = 0.05) -> List[Dict[str, Any]]: ...
+    def find_neutral_loss(self, mass: float, ppm: float = 20.0) -> List[Dict[str, Any]]: ...
+    def rank_ms2(self, top_n: int = 100) -> List[Dict[str, Any]]: ...
+    def __iter__(self) -> Iterator[MSObject]: ...
+    def __len__(self) -> int: ...
+
+class MZMLReader:
+    def __init__(self) -> None: ...
+    def read(
+        self,
+        filename: StrPath,
+        parse_spectra: bool = True,
+        parallel: bool = False,
+        num_processes: Optional[int] = None,
+        centroid: bool = False,
+        infer_charges: bool = False,
+        use_cache: bool = False,
+        lenient: bool = False,
+        progress_callback: Optional[Callable[[int, int, int], Any]] = None,
+        progress_every: int = 100,
+    ) -> MZMLObject: ...
+    def read_to_msobjects(
+        self, filename: StrPath, parallel: bool = False, num_processes: Optional[int] = None
+    ) -> List[MSObject]: ...
+    def read_spectrum(self, filename: StrPath, spectrum_index: int) -> MSObject: ...
+    def read_spectrum_by_id(self, filename: StrPath, native_id: str) -> MSObject: ...
+    def read_chromatograms(self, filename: StrPath) -> List[MZMLChromatogram]: ...
+    def get_file_info(self, filename: StrPath) -> MZMLFileInfo: ...
+    def read_headers(self, filename: StrPath) -> List[SpectrumHeader]: ...
+    def scan_table(self, filename: StrPath) -> Dict[str, List[Any]]: ...
+    def iter(self, filename: StrPath, skip_errors: bool = False) -> MZMLSpectrumIterator: ...
+    def stream_xics(
+        self,
+        filename: StrPath,
+        targets: List[float],
+        ppm: float = 10.0,
+        callback: Optional[Callable[[int, List[Dict[str, Any]]], Any]] = None,
+        every: int = 100,
+        reorder_window: int = 8,
+    ) -> List[Dict[str, Any]]: ...
+    def validate_file(self, filename: StrPath) -> bool: ...
+    def get_spectrum_count(self, filename: StrPath) -> int: ...
+    def get_ms1_count(self, filename: StrPath) -> int: ...
+    def get_ms2_count(self, filename: StrPath) -> int: ...
+
+class MZXMLReader:
+    def __init__(self) -> None: ...
+    def read(
+        self,
+        filename: StrPath,
+        parse_spectra: bool = True,
+        parallel: bool = False,
+        num_processes: Optional[int] = None,
+    ) -> MZMLObject: ...
+    def read_to_msobjects(
+        self, filename: StrPath, parallel: bool = False, num_processes: Optional[int] = None
+    ) -> List[MSObject]: ...
+    def get_spectrum_count(self, filename: StrPath) -> int: ...
+
+class MGFReader:
+    def __init__(self) -> None: ...
+    def read(self, filename: StrPath) -> List[MSObject]: ...
+
+class MGFWriter:
+    def __init__(self) -> None: ...
+    def write(self, spectra: Sequence[MSObject], filename: StrPath) -> int: ...
+
+class MZMLSpectrumIterator:
+    def __iter__(self) -> Iterator[MSObject]: ...
+    def __next__(self) -> MSObject: ...
+
+class SpectrumHeader:
+    @property
+    def id(self) -> str: ...
+    @property
+    def index(self) -> Optional[int]: ...
+    @property
+    def scan_number(self) -> int: ...
+    @property
+    def ms_level(self) -> int: ...
+    @property
+    def retention_time(self) -> float: ...
+    @property
+    def precursor_mz(self) -> Optional[float]: ...
+    @property
+    def charge(self) -> Optional[int]: ...
+    @property
+    def peak_count(self) -> int: ...
+
+class MZMLChromatogram:
+    @property
+    def id(self) -> str: ...
+    @property
+    def index(self) -> Optional[int]: ...
+    @property
+    def chromatogram_type(self) -> Optional[str]: ...
+    @property
+    def time_array(self) -> List[float]: ...
+    @property
+    def intensity_array(self) -> List[float]: ...
+    @property
+    def time_unit(self) -> Optional[str]: ...
+    @property
+    def precursor_mz(self) -> Optional[float]: ...
+    @property
+    def product_mz(self) -> Optional[float]: ...
+    def __len__(self) -> int: ...
+
+class SpectraConverter:
+    @staticmethod
+    def to_msobject(spectrum: Union[MSObject, Dict[str, Any], List[Peak]]) -> MSObject: ...
+    @staticmethod
+    def to_spectra(
+        ms_object: MSObject, spectra_type: str
+    ) -> Union[Dict[str, Any], List[Peak], List[Dict[str, Union[int, float]]]]: ...
+    @staticmethod
+    def batch_to_records(ms_objects: Union[Sequence[MSObject], MZMLObject]) -> Dict[str, npt.NDArray[Any]]: ...
+    @staticmethod
+    def to_matrix(
+        ms_objects: Union[Sequence[MSObject], MZMLObject],
+        min_mz: float = 100.0,
+        max_mz: float = 1700.0,
+        bin: float = 1.0,
+        transform: Optional[str] = "sqrt",
+        aggregation: str = "sum",
+        normalize: bool = True,
+    ) -> npt.NDArray[np.float64]: ...
+    @staticmethod
+    def batch_convert(spectra: Sequence[Any], target_format: str) -> List[Any]: ...
+    @staticmethod
+    def find_duplicates(
+        ms_objects: Sequence[MSObject],
+        mz_precision_da: float = 0.01,
+        top_n: int = 30,
+        min_cosine: float = 0.99,
+    ) -> List[List[int]]: ...
+    @staticmethod
+    def blank_subtract(
+        sample_msobjects: Sequence[MSObject],
+        blank_msobjects: Sequence[MSObject],
+        ppm: float = 10.0,
+        rt_tol: float = 30.0,
+        ratio: float = 0.5,
+    ) -> Tuple[List[MSObject], List[int]]: ...
+    @staticmethod
+    def normalize_injection_time(
+        ms_objects: Sequence[MSObject], reference_ms: float = 100.0
+    ) -> Tuple[List[MSObject], int]: ...
+    @staticmethod
+    def validate_spectrum(
+        spectrum: Any,
+        allow_empty_peaks: bool = False,
+        require_sorted: bool = False,
+        max_mz: Optional[float] = None,
+        require_precursor_for_ms2: bool = False,
+    ) -> Dict[str, Any]: ...
+    @staticmethod
+    def validate_run(
+        ms_objects: Union[Sequence[MSObject], MZMLObject],
+        allow_empty_peaks: bool = False,
+        require_sorted: bool = False,
+        max_mz: Optional[float] = None,
+        require_precursor_for_ms2: bool = False,
+        check_monotonic_rt: bool = False,
+    ) -> Dict[str, Any]: ...
+
+class SpectraClusterer:
+    def __init__(
+        self,
+        precursor_ppm: float = 10.0,
+        rt_tol: float = 30.0,
+        min_cosine: float = 0.7,
+        fragment_tolerance: Union[float, ToleranceModel, None] = None,
+    ) -> None: ...
+    def cluster(self, ms_objects: Sequence[MSObject]) -> Tuple[List[int], List[MSObject]]: ...
+
+class RtAlignment:
+    @staticmethod
+    def fit(pairs: Sequence[Tuple[float, float]], method: str = "linear", span: float = 0.3) -> RtAlignment: ...
+    @staticmethod
+    def from_runs(
+        run_a: Union[MZMLObject, Sequence[MSObject]],
+        run_b: Union[MZMLObject, Sequence[MSObject]],
+        ppm: float = 10.0,
+        method: str = "linear",
+        span: float = 0.3,
+    ) -> RtAlignment: ...
+    @staticmethod
+    def base_peak_anchors(
+        run_a: Union[MZMLObject, Sequence[MSObject]],
+        run_b: Union[MZMLObject, Sequence[MSObject]],
+        ppm: float = 10.0,
+    ) -> List[Tuple[float, float]]: ...
+    def map(self, rt: float) -> float: ...
+    def inverse(self, rt: float) -> float: ...
+    def map_spectra(self, ms_objects: Sequence[MSObject]) -> List[MSObject]: ...
+    @property
+    def method(self) -> str: ...
+    @property
+    def knots(self) -> List[Tuple[float, float]]: ...
+    @property
+    def residuals(self) -> Dict[str, Union[int, float]]: ...
+
+class MassCalibration:
+    @staticmethod
+    def fit(pairs: Sequence[Tuple[float, float]], model: str = "linear") -> MassCalibration: ...
+    def apply(self, ms_objects: Sequence[MSObject]) -> List[MSObject]: ...
+    def correct_mz(self, mz: float) -> float: ...
+    def ppm_error_at(self, mz: float) -> float: ...
+    @property
+    def model(self) -> str: ...
+    @property
+    def coefficients(self) -> List[float]: ...
+    @property
+    def calibrant_count(self) -> int: ...
+    @property
+    def rms_ppm_before(self) -> float: ...
+    @property
+    def rms_ppm_after(self) -> float: ...
+
+class SpectrumSimilarity:
+    @staticmethod
+    def cosine(
+        peaks_a: Sequence[Peak],
+        peaks_b: Sequence[Peak],
+        tolerance: Union[float, ToleranceModel, None] = None,
+        sqrt: bool = False,
+    ) -> float: ...
+    @staticmethod
+    def modified_cosine(
+        peaks_a: Sequence[Peak],
+        peaks_b: Sequence[Peak],
+        precursor_mz_a: float,
+        precursor_mz_b: float,
+        tolerance: Union[float, ToleranceModel, None] = None,
+        sqrt: bool = False,
+    ) -> float: ...
+    @staticmethod
+    def spectral_angle(
+        peaks_a: Sequence[Peak],
+        peaks_b: Sequence[Peak],
+        tolerance: Union[float, ToleranceModel, None] = None,
+        sqrt: bool = False,
+    ) -> float: ...
+    @staticmethod
+    def match_peaks(
+        peaks_a: Sequence[Peak],
+        peaks_b: Sequence[Peak],
+        tolerance: Union[float, ToleranceModel, None] = None,
+        sqrt: bool = False,
+        mz_shift: Optional[float] = None,
+    ) -> Tuple[float, List[Tuple[int, int]]]: ...
+
+class LibraryHit:
+    index: int
+    precursor_mz: float
+    score: float
+    matched_peaks: int
+    metadata: Dict[str, str]
+
+class SpectralLibrary:
+    def __init__(self, ms_objects: Optional[Sequence[MSObject]] = None) -> None: ...
+    def add_spectrum(self, ms_object: MSObject, metadata: Optional[Dict[str, str]] = None) -> int: ...
+    def search(
+        self,
+        query: MSObject,
+        precursor_ppm: float = 10.0,
+        fragment_tolerance: float = 0.02,
+        top_k: int = 10,
+    ) -> List[LibraryHit]: ...
+    def __len__(self) -> int: ...
+
+class ToleranceModel:
+    @staticmethod
+    def ppm(ppm: float) -> ToleranceModel: ...
+    @staticmethod
+    def absolute(da: float) -> ToleranceModel: ...
+    @staticmethod
+    def hybrid(ppm: float, min_da: float) -> ToleranceModel: ...
+    @staticmethod
+    def piecewise(segments: Sequence[Tuple[float, float, str]]) -> ToleranceModel: ...
+    def tolerance_at_mz(self, mz: float) -> float: ...
+
+class PeakHit:
+    mz: float
+    intensity: float
+    spectrum_index: int
+    scan_number: int
+    retention_time: float
+
+class SpectraIndex:
+    def __init__(self, ms_objects: Sequence[MSObject], bin_size: float = 1.0) -> None: ...
+    @property
+    def bin_count(self) -> int: ...
+    @property
+    def spectrum_count(self) -> int: ...
+    @property
+    def total_peak_count(self) -> int: ...
+    def save(self, path: StrPath) -> None: ...
+    @staticmethod
+    def load(path: StrPath) -> SpectraIndex: ...
+    def add_spectra(self, ms_objects: Sequence[MSObject]) -> None: ...
+    def search_range(self, mz_range: Tuple[float, float]) -> List[Peak]: ...
+    def search_range_detailed(self, mz_range: Tuple[float, float]) -> List[PeakHit]: ...
+    def search_peaks_batch(
+        self, ranges: Sequence[Tuple[float, float]], num_threads: Optional[int] = None
+    ) -> List[List[Peak]]: ...
+    def search_mz(self, mz: float, tolerance: ToleranceModel) -> List[Peak]: ...
+    def search_around(
+        self, mz: float, tolerance: Union[float, Tuple[str, float], ToleranceModel]
+    ) -> List[Peak]: ...
+    def search_range_min_intensity(self, mz_range: Tuple[float, float], min_intensity: float) -> List[Peak]: ...
+    def bins_above(self, threshold: float) -> List[Tuple[Tuple[float, float], float]]: ...
+
+class BinnedSpectra:
+    def __init__(self, spectra_list: Sequence[Union[MSObject, Sequence[Peak]]], bin_size: float) -> None: ...
+    def add_spectra(self, spectra_list: Sequence[Union[MSObject, Sequence[Peak]]]) -> None: ...
+    def search_peaks(self, mz_range: Tuple[float, float]) -> List[Peak]: ...
+
+class RangeQuery:
+    def __init__(self, ms_objects: Union[Sequence[MSObject], MZMLObject]) -> None: ...
+    @property
+    def spectrum_count(self) -> int: ...
+    @property
+    def rt_range(self) -> Optional[Tuple[float, float]]: ...
+    def query(self, mz_range: Tuple[float, float], rt_range: Tuple[float, float]) -> List[PeakHit]: ...
+    def scans_containing(self, mz: float, ppm: float = 10.0) -> List[PeakHit]: ...
+
+class TargetedExtractor:
+    @staticmethod
+    def extract(
+        source: Union[StrPath, MZMLObject, Sequence[MSObject]],
+        targets: Sequence[Tuple[float, float]],
+        ppm: float = 10.0,
+        rt_window: float = 30.0,
+        mgf_path: Optional[StrPath] = None,
+        per_target: bool = False,
+        title_format: Optional[str] = None,
+    ) -> Dict[Tuple[float, float], List[MSObject]]: ...
+
+class DIAPseudoSpectrumGenerator:
+    def __init__(self, ppm: float = 10.0, min_corr: float = 0.8) -> None: ...
+    def run(self, ms_objects: Sequence[MSObject]) -> List[MSObject]: ...
+
+class DIAWindowMap:
+    def __init__(self, ms_objects: Sequence[MSObject]) -> None: ...
+    @staticmethod
+    def from_windows(windows: Sequence[Tuple[float, float]]) -> DIAWindowMap: ...
+    @property
+    def windows(self) -> List[Tuple[float, float]]: ...
+    @property
+    def spectrum_indices(self) -> List[List[int]]: ...
+    def windows_containing(self, mz: float) -> List[Tuple[float, float]]: ...
+    def assign_unique(self, precursor_mz: float) -> Optional[Tuple[float, float]]: ...
+    def overlap_regions(self) -> List[Tuple[float, float]]: ...
+    def gaps(self) -> List[Tuple[float, float]]: ...
+    def validate(self) -> None: ...
+    def __len__(self) -> int: ...
+
+class IonMobilityUtils:
+    @staticmethod
+    def parse_ion_mobility(
+        ms_object_list: Union[Sequence[MSObject], MZMLObject],
+        rt_range: Optional[Tuple[float, float]] = None,
+        mz_tolerance: Union[float, ToleranceModel, None] = None,
+        rt_tolerance: Optional[float] = None,
+        bin_width: float = 0.0001,
+    ) -> Dict[float, List[Tuple[float, float]]]: ...
+    @staticmethod
+    def extract_heatmap(
+        ms_object_list: Union[Sequence[MSObject], MZMLObject],
+        mz_range: Tuple[float, float],
+        mz_bin_size: float,
+        dt_range: Tuple[float, float],
+        dt_bin_size: float,
+        bin_width: float = 0.0001,
+    ) -> Tuple[npt.NDArray[np.float64], npt.NDArray[np.float64], npt.NDArray[np.float64]]: ...
+    @staticmethod
+    def merge_peaks_by_mz(
+        peaks: Sequence[Tuple[float, float]], mz_tolerance: Union[float, ToleranceModel]
+    ) -> List[Tuple[float, float]]: ...
+    @staticmethod
+    def calculate_calibration_curve(calibration_points: Sequence[Tuple[float, float]]) -> Tuple[float, float]: ...
+    @staticmethod
+    def apply_calibration(drift_times: Sequence[float], slope: float, intercept: float) -> List[float]: ...
+    @staticmethod
+    def mason_schamp_ccs(
+        drift_time: float,
+        mz: float,
+        charge: int,
+        drift_length_cm: float,
+        drift_voltage: float,
+        temperature: float = 298.15,
+        pressure_torr: float = 3.95,
+        gas_mass: float = 28.006148,
+    ) -> float: ...
+    @staticmethod
+    def fit_ccs_calibration(
+        calibrants: Sequence[Tuple[float, int, float, float]], gas_mass: float = 28.006148
+    ) -> Tuple[float, float]: ...
+    @staticmethod
+    def apply_ccs_calibration(
+        calibration: Tuple[float, float], mz: float, charge: int, mobility: float, gas_mass: float = 28.006148
+    ) -> float: ...
+    @staticmethod
+    def agilent_tune_mix() -> List[Tuple[float, int, float]]: ...
+    @staticmethod
+    def analyze_mobility_distribution(mobility_data: Mapping[float, List[Tuple[float, float]]]) -> Dict[str, Any]: ...
+
+class XICResult:
+    @property
+    def rt_array(self) -> List[float]: ...
+    @property
+    def intensity_array(self) -> List[float]: ...
+    @property
+    def mz_observed_array(self) -> List[float]: ...
+    @property
+    def mz(self) -> float: ...
+    @property
+    def ppm_error(self) -> float: ...
+    @property
+    def ion_type(self) -> str: ...
+    @property
+    def charge(self) -> int: ...
+    @property
+    def processing_history(self) -> List[str]: ...
+    def ppm_error_array(self) -> List[float]: ...
+    def pick_peaks(
+        self, min_points: int = 5, sn_threshold: float = 3.0, smoothing_window: int = 5
+    ) -> List[ChromPeak]: ...
+    def smooth_moving_average(self, window: int = 5) -> XICResult: ...
+    def smooth_savitzky_golay(self, window: int = 7, order: int = 2) -> XICResult: ...
+    def subtract_baseline(
+        self, method: str = "rolling_minimum", window: int = 51, degree: int = 3, iterations: int = 100
+    ) -> XICResult: ...
+    def __len__(self) -> int: ...
+
+class ChromPeak:
+    @property
+    def apex_rt(self) -> float: ...
+    @property
+    def apex_intensity(self) -> float: ...
+    @property
+    def rt_start(self) -> float: ...
+    @property
+    def rt_end(self) -> float: ...
+    @property
+    def area(self) -> float: ...
+    @property
+    def fwhm(self) -> float: ...
+    @property
+    def signal_to_noise(self) -> float: ...
+
+class XICSExtractor:
+    def __init__(
+        self,
+        ppm_tolerance: float = 10.0,
+        tolerance: Optional[ToleranceModel] = None,
+        spectra: Optional[Union[List[MSObject], MZMLObject]] = None,
+        bin_size: float = 1.0,
+    ) -> None: ...
+    @staticmethod
+    def from_mzml(
+        mzml_object: MZMLObject,
+        ppm_tolerance: float = 10.0,
+        bin_size: float = 1.0,
+        tolerance: Optional[ToleranceModel] = None,
+    ) -> XICSExtractor: ...
+    def load_spectra(self, spectra: Union[List[MSObject], MZMLObject], bin_size: float = 1.0) -> None: ...
+    @property
+    def is_loaded(self) -> bool: ...
+    @property
+    def ms1_count(self) -> int: ...
+    @property
+    def ms2_count(self) -> int: ...
+    @property
+    def ppm_tolerance(self) -> float: ...
+    @property
+    def tolerance(self) -> Optional[ToleranceModel]: ...
+    @tolerance.setter
+    def tolerance(self, value: Optional[ToleranceModel]) -> None: ...
+    @property
+    def normalize_injection_time(self) -> Optional[float]: ...
+    @normalize_injection_time.setter
+    def normalize_injection_time(self, value: Optional[float]) -> None: ...
+    @property
+    def peak_combination(self) -> str: ...
+    @peak_combination.setter
+    def peak_combination(self, value: str) -> None: ...
+    @staticmethod
+    def build_tic(
+        spectra: Union[List[MSObject], MZMLObject],
+        ms_level: int = 1,
+        rt_range: Optional[Tuple[float, float]] = None,
+    ) -> Tuple[List[float], List[float], int]: ...
+    @staticmethod
+    def build_bpc(
+        spectra: Union[List[MSObject], MZMLObject],
+        ms_level: int = 1,
+        rt_range: Optional[Tuple[float, float]] = None,
+    ) -> Tuple[List[float], List[float], int]: ...
+    def extract_xic(
+        self, mz: float, rt_start: float = 0.0, rt_end: float = ...
+    ) -> Tuple[List[float], List[float]]: ...
+    def extract_single_xic(
+        self,
+        mz: float,
+        charge: int = 0,
+        ion_type: str = "",
+        rt_start: float = 0.0,
+        rt_end: float = ...,
+    ) -> XICResult: ...
+    def extract_batch_xics(
+        self,
+        targets: List[Tuple[float, int, str]],
+        rt_start: float = 0.0,
+        rt_end: float = ...,
+        parallel: bool = False,
+        num_threads: int = 0,
+    ) -> List[XICResult]: ...
+    def extract_precursor_xics(self, precursor: Any, num_isotopes: int = 3) -> List[XICResult]: ...
+    def extract_precursor_xics_scored(
+        self, precursor: Any, num_isotopes: int = 3, formula: Optional[str] = None
+    ) -> Dict[str, Any]: ...
+    def extract_fragment_xics(self, peptide: Any) -> List[XICResult]: ...
+    def extract_dia_fragment_xic(
+        self,
+        precursor_mz: float,
+        fragment_mz: float,
+        rt_start: float = 0.0,
+        rt_end: float = ...,
+        pool_windows: bool = False,
+    ) -> Tuple[List[float], List[float]]: ...
+    def extract_transitions(
+        self, transitions: Sequence[Tuple[float, float]], ppm: float = 20.0
+    ) -> List[XICResult]: ...
+
+class XICTargetBuilder:
+    def __init__(
+        self,
+        neutral_mass: Optional[float] = None,
+        sequence: Optional[str] = None,
+        charges: Optional[Sequence[int]] = None,
+        adducts: Optional[Sequence[str]] = None,
+        isotopes: int = 2,
+    ) -> None: ...
+    @property
+    def neutral_mass(self) -> float: ...
+    def targets(self) -> List[Dict[str, Any]]: ...
+
+def neutral_mass(mz: float, charge: int, adduct: str = "+H") -> float: ...
+def mz_from_neutral(mass: float, charge: int, adduct: str = "+H") -> float: ...
+def ppm_diff(observed: float, reference: float) -> float: ...
+def within_ppm(observed: float, reference: float, ppm: float) -> bool: ...
+def predict_pattern(formula: str, charge: int = 1, n_isotopes: int = 5) -> List[Tuple[float, float]]: ...
+def averagine_pattern(monoisotopic_mass: float, charge: int = 1, n_isotopes: int = 5) -> List[Tuple[float, float]]: ...
+def format_spectrum_title(
+    ms_object: MSObject,
+    template: str = "{file}.{scan}.{scan}.{charge}",
+    file: str = "",
+    native_id: str = "",
+    omit_unknown_charge: bool = False,
+) -> str: ...
+def parse_spectrum_title(title: str) -> Tuple[Optional[int], Optional[int]]: ...
+def read_mgf(path: StrPath) -> List[MSObject]: ...
+def read(
+    path: StrPath,
+    parse_spectra: bool = True,
+    parallel: bool = False,
+    num_processes: Optional[int] = None,
+    infer_charges: bool = False,
+) -> MZMLObject: ...
+def group_and_merge_ms2(
+    ms_objects: Sequence[MSObject],
+    precursor_ppm: float = 10.0,
+    rt_window: float = 30.0,
+    strategy: str = "max",
+) -> List[MSObject]: ...
+def set_log_level(level: Union[str, int]) -> None: ...
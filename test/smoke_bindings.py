@@ -0,0 +1,246 @@
+#!/usr/bin/env python3
+"""
+Smoke test for the Rust extension module.
+
+Constructs every exported class and calls its main methods once, so that a
+binding whose signature or argument conversion broke fails loudly. The same
+script is run in-process by `cargo test smoke` with the module built from the
+current tree; it can also be run against an installed wheel:
+
+    python test/smoke_bindings.py path/to/file.mzML
+
+Methods returning numpy arrays are skipped when numpy is not installed.
+"""
+
+import os
+import sys
+import tempfile
+from types import SimpleNamespace
+
+
+def run(module, mzml_path, work_dir):
+    """Exercise the module; returns the names of the classes and functions used."""
+    try:
+        import numpy
+        has_numpy = True
+    except ImportError:
+        has_numpy = False
+
+    used = set()
+
+    def use(name):
+        used.add(name)
+        return getattr(module, name)
+
+    # Basic spectrum classes
+    test_object = use("TestMSObject")(2)
+    test_object.add_peak(100.0, 10.0)
+    test_object.sort_peaks()
+    assert test_object.peak_count() == 1 and test_object.total_ion_current() == 10.0
+
+    spectrum = use("Spectrum")(1)
+    spectrum.add_peak(200.0, 5.0)
+    spectrum.add_peak(100.0, 10.0)
+    spectrum.sort_peaks()
+    assert spectrum.peaks == [(100.0, 10.0), (200.0, 5.0)]
+    assert spectrum.keep_top_n(1) == 1
+    if has_numpy:
+        assert spectrum.peaks_numpy().shape == (1, 2)
+
+    # MSObject and its parts
+    KeyValue, Precursor, Scan, MSObject = use("KeyValue"), use("Precursor"), use("Scan"), use("MSObject")
+    assert KeyValue("a", "b").value == "b"
+    precursor = Precursor(500.0, 2, 1, None, "HCD", 30.0)
+    assert precursor.neutral_mass is not None
+    scan = Scan(2, 12.0, 0.0, None, {"filter": "x"}, 20.0)
+    scan.polarity = "negative"
+    assert scan.polarity == "negative"
+    ms2 = MSObject(2, [(150.0, 50.0), (250.0, 20.0)], precursor, scan, {"k": "v"})
+    assert ms2.has_precursor and ms2.precursor.charge == 2
+    ms2.set_precursor(mz=501.0)
+    assert ms2.precursor.mz == 501.0
+    ms1 = MSObject(1, [(500.0, 100.0), (501.0, 50.0)], None, {"scan_number": 1, "retention_time": 10.0})
+    assert ms1.precursor is None and not ms1.has_precursor
+    assert ms1.total_ion_current() == 150.0
+    ms1.validate()
+    assert set(ms1.estimate_noise()) == {"global", "window", "local"}
+    noisy = MSObject(1, [(100.0, 1.0), (200.0, 1.0), (300.0, 1.0), (400.0, 50.0)])
+    assert noisy.denoise() == 3 and noisy.peaks == [(400.0, 50.0)]
+
+    # mzML
+    assert use("MZMLUtils").is_valid_mzml(mzml_path)
+    file_info = use("MZMLUtils").get_file_info(mzml_path)
+    assert file_info["spectrum_count"] == sum(file_info["ms_level_counts"].values())
+    parser = use("MZMLParser")(mzml_path)
+    parsed = parser.parse_all_spectra()
+    assert parsed and parser.validate_file()
+    assert len(parser.parse_spectra_with_callback(lambda *p: None, every=1)) == len(parsed) and not parser.cancelled
+    reader = use("MZMLReader")()
+    mzml = reader.read(mzml_path)
+    assert len(mzml) == len(parsed) == reader.get_spectrum_count(mzml_path)
+    assert len(reader.read(mzml_path, infer_charges=True)) == len(parsed)
+    cached_path = os.path.join(work_dir, "cached.mzML")
+    with open(mzml_path, "rb") as source, open(cached_path, "wb") as copy:
+        copy.write(source.read())
+    assert len(reader.read(cached_path, use_cache=True)) == len(parsed)
+    assert os.path.exists(cached_path + ".omsu")
+    assert len(reader.read(cached_path, use_cache=True)) == len(parsed)
+    assert mzml.file_info.spectrum_count == len(parsed)
+    assert reader.read(mzml_path, lenient=True).file_info.skipped_spectra == []
+    progress = []
+    assert len(reader.read(mzml_path, progress_callback=lambda *p: progress.append(p), progress_every=1)) == len(parsed)
+    assert progress[-1][0] == len(parsed) and progress[-1][1] == progress[-1][2]
+    cancelled = reader.read(mzml_path, progress_callback=lambda *p: False, progress_every=1)
+    assert cancelled.file_info.cancelled and len(cancelled) == 1
+    assert len(reader.read_headers(mzml_path)) == len(parsed)
+    assert reader.read_spectrum(mzml_path, 0).scan_number == parsed[0].scan_number
+    assert sum(1 for _ in reader.iter(mzml_path)) == len(parsed)
+    assert isinstance(reader.read_chromatograms(mzml_path), list)
+    assert set(mzml.scan_table()) >= {"scan_number", "retention_time"}
+    assert set(mzml.dia_scheme()) == {"is_dda", "overlapping", "cycle_time", "windows"}
+    spectra = mzml.spectra
+    ms1_spectra, ms2_spectra = mzml.ms1_spectra, mzml.ms2_spectra
+    assert ms1_spectra and ms2_spectra
+    assert all(spectrum.precursor is not None for spectrum in ms2_spectra)
+    for name in ("MZMLObject", "MZMLFileInfo", "MZMLSpectrumIterator", "SpectrumHeader", "MZMLChromatogram"):
+        use(name)
+
+    # MGF round trip
+    mgf_path = os.path.join(work_dir, "smoke.mgf")
+    assert use("MGFWriter")().write(ms2_spectra, mgf_path) == len(ms2_spectra)
+    assert len(use("MGFReader")().read(mgf_path)) == len(ms2_spectra)
+    assert len(use("read_mgf")(mgf_path)) == len(ms2_spectra)
+    opened = use("read")(mgf_path)
+    assert opened.file_info.file_format == "MGF" and len(opened) == len(ms2_spectra)
+    assert use("read")(mzml_path, parse_spectra=False).file_info.spectrum_count == len(parsed)
+    use("MZXMLReader")()
+
+    # Conversion
+    converter = use("SpectraConverter")
+    as_dict = converter.to_spectra(ms2, "dict")
+    assert converter.to_msobject(as_dict).precursor.mz == 501.0
+    records = converter.to_spectra(ms2, "records")
+    assert len(records) == 2 and set(records[0]) == {"scan_number", "rt", "mz", "intensity", "level"}
+    if has_numpy:
+        assert converter.to_spectra(ms2, "numpy")["mz_array"].dtype == numpy.float64
+        table = converter.batch_to_records(mzml)
+        assert len(table["mz"]) == sum(len(spectrum.peaks) for spectrum in spectra)
+        assert table["scan_number"].dtype == numpy.uint32 and table["level"].dtype == numpy.uint8
+        vector = ms2.to_vector(min_mz=100.0, max_mz=300.0, bin=1.0)
+        assert vector.shape == (200,) and abs(numpy.linalg.norm(vector) - 1.0) < 1e-9
+        assert converter.to_matrix(mzml, bin=10.0, transform=None).shape == (len(spectra), 160)
+        assert (converter.to_matrix([ms2], 100.0, 300.0)[0] == vector).all()
+    report = converter.validate_spectrum(ms1)
+    assert report["valid"] and report["has_precursor"] is False
+    empty = MSObject(1, [])
+    assert not converter.validate_spectrum(empty)["valid"]
+    assert converter.validate_spectrum(empty, allow_empty_peaks=True)["issues"] == []
+    run_report = converter.validate_run([ms1, empty], check_monotonic_rt=True)
+    assert run_report["issue_counts"] == {"empty_peak_list": 1, "non_monotonic_rt": 1}
+    assert converter.validate_run(mzml)["spectrum_count"] == len(spectra)
+
+    # Tolerances, indexes and range queries
+    tolerance = use("ToleranceModel").ppm(10.0)
+    assert tolerance.tolerance_at_mz(1e6) == 10.0
+    index = use("SpectraIndex")(spectra, 1.0)
+    assert index.search_range((499.0, 502.0))
+    assert all(isinstance(hit, use("PeakHit")) for hit in index.search_range_detailed((499.0, 502.0)))
+    binned = use("BinnedSpectra")([ms1, [(300.0, 1.0)]], 1.0)
+    binned.add_spectra([ms2])
+    assert binned.search_peaks((299.0, 301.0)) == [(300.0, 1.0)]
+    range_query = use("RangeQuery")(spectra)
+    assert range_query.spectrum_count == len(spectra) == use("RangeQuery")(mzml).spectrum_count
+    assert isinstance(range_query.query((0.0, 2000.0), (0.0, 1e6)), list)
+    assert isinstance(range_query.scans_containing(500.0, 10.0), list)
+
+    # Similarity, library and clustering
+    similarity = use("SpectrumSimilarity")
+    assert abs(similarity.cosine(ms2.peaks, ms2.peaks) - 1.0) < 1e-9
+    library = use("SpectralLibrary")()
+    library.add_spectrum(ms2, {"name": "smoke"})
+    hits = library.search(ms2)
+    assert len(library) == 1 and hits and isinstance(hits[0], use("LibraryHit"))
+    labels, consensus = use("SpectraClusterer")().cluster([ms2, ms2])
+    assert len(labels) == 2 and consensus
+    assert len(use("group_and_merge_ms2")([ms2, ms2])) == 1
+    alignment = use("RtAlignment").fit([(10.0, 25.0), (20.0, 45.0), (30.0, 65.0)])
+    assert alignment.map(40.0) == 85.0 and alignment.inverse(85.0) == 40.0
+    assert alignment.map_spectra([ms1])[0].retention_time == 25.0 and ms1.retention_time == 10.0
+    assert alignment.residuals["anchor_count"] == 3 and alignment.method == "linear"
+    assert isinstance(use("RtAlignment").base_peak_anchors(mzml, spectra), list)
+    calibration = use("MassCalibration").fit([(500.004, 500.0), (1000.008, 1000.0)], "constant")
+    assert abs(calibration.rms_ppm_before - 8.0) < 1e-6 and calibration.rms_ppm_after < 1e-6
+    calibrated = calibration.apply([ms2])[0]
+    assert abs(calibrated.precursor.mz - calibration.correct_mz(501.0)) < 1e-9 and ms2.precursor.mz == 501.0
+
+    # Targeted extraction, DIA and ion mobility
+    extracted = use("TargetedExtractor").extract(spectra, [(501.0, 12.0)])
+    assert isinstance(extracted, dict)
+    window_map = use("DIAWindowMap").from_windows([(400.0, 425.0), (425.0, 450.0)])
+    assert len(window_map) == 2 and window_map.windows_containing(410.0)
+    assert isinstance(use("DIAPseudoSpectrumGenerator")().run(spectra), list)
+    mobility = use("IonMobilityUtils")
+    frame = MSObject(1, [(500.0, 10.0)], None, Scan(1, 10.0, 0.025))
+    assert list(mobility.parse_ion_mobility([frame], bin_width=0.0001)) == [0.025]
+    assert len(mobility.merge_peaks_by_mz([(100.0, 1.0), (100.001, 2.0)], 0.01)) == 1
+    assert len(mobility.merge_peaks_by_mz([(100.0, 1.0), (100.005, 2.0)], tolerance)) == 2
+    calibrants = [
+        (mz, charge, ccs, 0.002 * ccs) for mz, charge, ccs in mobility.agilent_tune_mix()
+    ]
+    calibration = mobility.fit_ccs_calibration(calibrants)
+    assert mobility.apply_ccs_calibration(calibration, *calibrants[0][:2], calibrants[0][3]) > 0
+    if has_numpy:
+        mz_axis, dt_axis, matrix = mobility.extract_heatmap([frame], (400.0, 600.0), 10.0, (0.0, 0.05), 0.01)
+        assert matrix.shape == (len(dt_axis), len(mz_axis))
+
+    # XIC
+    extractor = use("XICSExtractor")(10.0, None, spectra)
+    assert extractor.is_loaded
+    assert use("XICSExtractor").from_mzml(mzml).ms1_count == len(ms1_spectra)
+    xic = extractor.extract_single_xic(500.0)
+    transition = ms2_spectra[0].precursor.mz, ms2_spectra[0].peaks[0][0]
+    assert extractor.extract_transitions([transition])[0].intensity_array
+    assert isinstance(xic, use("XICResult"))
+    ChromPeak = use("ChromPeak")
+    assert all(isinstance(peak, ChromPeak) for peak in xic.pick_peaks(min_points=1))
+    processed = xic.smooth_savitzky_golay(5, 2).subtract_baseline("polynomial").smooth_moving_average(3)
+    assert len(processed) == len(xic) and len(processed.processing_history) == 3 and xic.processing_history == []
+    assert use("XICTargetBuilder")(neutral_mass=998.0, charges=[2]).targets()
+    peptide = SimpleNamespace(
+        sequence="PEPTIDE", modified_sequence="PEPTIDE", charge=1, mz=500.0, rt=10.0, rt_start=0.0, rt_stop=100.0
+    )
+    scored = extractor.extract_precursor_xics_scored(peptide, 2)
+    assert len(scored["xics"]) == 2 and len(scored["rt_array"]) == len(scored["scores"])
+
+    # Module-level helpers
+    assert abs(use("mz_from_neutral")(use("neutral_mass")(500.0, 2), 2) - 500.0) < 1e-9
+    assert use("within_ppm")(500.001, 500.0, 5.0) and use("ppm_diff")(500.0, 500.0) == 0.0
+    glucose = use("predict_pattern")("C6H12O6", 1)
+    assert len(glucose) == 5 and glucose[0][1] == 1.0 and abs(glucose[1][1] - 0.0686) < 0.01
+    assert max(intensity for _, intensity in use("averagine_pattern")(2000.0, 2, 3)) == 1.0
+    title = use("format_spectrum_title")(ms2, file="run")
+    assert use("parse_spectrum_title")(title)[0] == 2
+    set_log_level = use("set_log_level")
+    try:
+        set_log_level("warning")
+    except RuntimeError:
+        # The embedding process (e.g. `cargo test`) already installed its own logger
+        pass
+
+    return sorted(used)
+
+
+def main():
+    if len(sys.argv) != 2:
+        print(__doc__)
+        return 2
+    import _openms_utils_rust as module
+
+    with tempfile.TemporaryDirectory() as work_dir:
+        used = run(module, sys.argv[1], work_dir)
+    print(f"[OK] exercised {len(used)} bindings")
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())